@@ -21,51 +21,46 @@
 // SOFTWARE.
 
 use codspeed_criterion_compat::{criterion_group, criterion_main, Criterion};
-use ordered_float::OrderedFloat;
-use rand::rngs::ThreadRng;
-use rand::{thread_rng, Rng};
-use rustplanning::planning::rrt::rrt;
-use rustplanning::tree::Distance;
+use rustplanning::bounds::Bounds;
+use rustplanning::float::{FloatArray, FloatState};
+use rustplanning::planning::bidirectional::{rrt_bidirectional, rrt_bidirectional_threaded};
+use rustplanning::planning::rrt::{rrt, RrtOptions};
+use rustplanning::planning::stats::PlannerStats;
+use rustplanning::planning::termination::MaxIterations;
+use rustplanning::tree::{Distance, HashTree, SpatialIndex};
 
 /// Basic 2D point class for representing hashable points in the plane
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
-struct Point2D(OrderedFloat<f64>, OrderedFloat<f64>);
+#[derive(Debug, Clone, Copy, FloatState)]
+struct Point2D {
+    x: f64,
+    y: f64,
+}
 
 impl Point2D {
     pub fn new(x: f64, y: f64) -> Self {
-        Point2D(OrderedFloat(x), OrderedFloat(y))
-    }
-
-    pub fn x(&self) -> f64 {
-        self.0.into_inner()
-    }
-
-    pub fn y(&self) -> f64 {
-        self.1.into_inner()
+        Point2D { x, y }
     }
 }
 
-// Norm distance function for 2D points
-impl Distance for Point2D {
-    fn distance(&self, other: &Point2D) -> f64 {
-        let (dx, dy) = (self.x() - other.x(), self.y() - other.y());
-        (dx * dx + dy * dy).sqrt()
+/// Returns a seeded sampler of the 2-D plane
+fn sampler_2d(seed: u64, max_x: f64, max_y: f64) -> impl FnMut() -> Point2D {
+    let mut sample = Bounds::new([0.0, 0.0], [max_x, max_y])
+        .unwrap()
+        .seeded_sampler(seed);
+    move || {
+        let FloatArray([x, y]) = sample();
+        Point2D::new(x, y)
     }
 }
 
-/// Function for randomly sampling the 2-D plane
-fn sample_2d(rng: &mut ThreadRng, max_x: f64, max_y: f64) -> Point2D {
-    Point2D::new(rng.gen_range(0.0..=max_x), rng.gen_range(0.0..=max_y))
-}
-
 // Returns a point that is step_size along the line between the specified start and end pose
 fn extend_2d(start: &Point2D, end: &Point2D, step_size: f64) -> Point2D {
-    let direction = (end.0 - start.0, end.1 - start.1);
+    let direction = (end.x - start.x, end.y - start.y);
     let length = (direction.0.powi(2) + direction.1.powi(2)).sqrt();
     let norm_direction = (direction.0 / length, direction.1 / length);
-    Point2D(
-        start.0 + norm_direction.0 * step_size,
-        start.1 + norm_direction.1 * step_size,
+    Point2D::new(
+        start.x + norm_direction.0 * step_size,
+        start.y + norm_direction.1 * step_size,
     )
 }
 
@@ -76,13 +71,13 @@ fn run_rrt(
     goal: &Point2D,
     grid_size: f64,
 ) {
-    let mut rng = thread_rng();
+    // Use the same seed for every run so each iteration solves an identical problem
     let step_size = 1.0;
     let rewire_radius = 3.0;
 
     // Define closures
     let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
-    let mut sample_fn = || sample_2d(&mut rng, grid_size, grid_size);
+    let mut sample_fn = sampler_2d(1, grid_size, grid_size);
     let connectable_fn = |start: &Point2D, end: &Point2D| start.distance(end) < rewire_radius;
 
     let result = rrt(
@@ -94,12 +89,53 @@ fn run_rrt(
         use_rrtstar,
         rewire_radius,
         use_rrtconnect,
-        100000,
+        100_000,
         10.0,
         true,
     );
 
-    assert!(result.is_ok(), "Expected Ok result, got Err");
+    assert!(result.path.is_ok(), "Expected Ok result, got Err");
+}
+
+fn run_bidirectional(threaded: bool, start: &Point2D, goal: &Point2D, grid_size: f64) {
+    let step_size = 1.0;
+    let make_sampler = |index: usize| sampler_2d(1 + index as u64, grid_size, grid_size);
+    let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
+    let connectable_fn = |_: &Point2D, _: &Point2D| true;
+    let options = RrtOptions {
+        max_extension: step_size,
+        use_rrtconnect: true,
+        ..RrtOptions::default()
+    };
+    let make_termination = || MaxIterations(1_000_000);
+
+    let found = if threaded {
+        rrt_bidirectional_threaded(
+            start,
+            goal,
+            make_sampler,
+            extend_fn,
+            connectable_fn,
+            &options,
+            make_termination,
+            50,
+        )
+        .is_ok()
+    } else {
+        rrt_bidirectional(
+            start,
+            goal,
+            make_sampler(0),
+            extend_fn,
+            connectable_fn,
+            &options,
+            make_termination(),
+            &mut PlannerStats::new(),
+        )
+        .is_ok()
+    };
+
+    assert!(found, "Expected Ok result, got Err");
 }
 
 fn bench_rrt(c: &mut Criterion) {
@@ -107,7 +143,7 @@ fn bench_rrt(c: &mut Criterion) {
     let end = Point2D::new(50.0, 50.0);
     let grid_size: f64 = 50.0;
     c.bench_function("rrt", |b| {
-        b.iter(|| run_rrt(false, false, &start, &end, grid_size))
+        b.iter(|| run_rrt(false, false, &start, &end, grid_size));
     });
 }
 
@@ -116,7 +152,7 @@ fn bench_rrtstar(c: &mut Criterion) {
     let end = Point2D::new(50.0, 50.0);
     let grid_size: f64 = 50.0;
     c.bench_function("rrtstar", |b| {
-        b.iter(|| run_rrt(true, false, &start, &end, grid_size))
+        b.iter(|| run_rrt(true, false, &start, &end, grid_size));
     });
 }
 
@@ -125,9 +161,60 @@ fn bench_rrtconnect(c: &mut Criterion) {
     let end = Point2D::new(50.0, 50.0);
     let grid_size: f64 = 50.0;
     c.bench_function("rrtconnect", |b| {
-        b.iter(|| run_rrt(false, true, &start, &end, grid_size))
+        b.iter(|| run_rrt(false, true, &start, &end, grid_size));
+    });
+}
+
+// Compare growing both trees on one thread with a thread per tree on a longer query
+fn bench_bidirectional(c: &mut Criterion) {
+    let start = Point2D::new(1.0, 1.0);
+    let end = Point2D::new(200.0, 200.0);
+    let grid_size: f64 = 200.0;
+    c.bench_function("bidirectional", |b| {
+        b.iter(|| run_bidirectional(false, &start, &end, grid_size));
+    });
+    c.bench_function("bidirectional_threaded", |b| {
+        b.iter(|| run_bidirectional(true, &start, &end, grid_size));
+    });
+}
+
+// Compare scanning every node with the k-d tree index for nearest neighbor queries
+fn bench_nearest_neighbor(c: &mut Criterion) {
+    let grid_size = 100.0;
+    let mut sample_fn = sampler_2d(1, grid_size, grid_size);
+    let root = Point2D::new(0.0, 0.0);
+    let mut tree = HashTree::new(root);
+    for _ in 0..10_000 {
+        let point = sample_fn();
+        tree.add_child(&root, point).unwrap();
+    }
+    let queries: Vec<Point2D> = (0..100).map(|_| sample_fn()).collect();
+
+    c.bench_function("nearest_neighbor_scan", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|query| tree.nearest_neighbor(query).x)
+                .sum::<f64>()
+        });
+    });
+    tree.set_spatial_index(Some(SpatialIndex::new(|p: &Point2D| vec![p.x, p.y])));
+    c.bench_function("nearest_neighbor_kdtree", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|query| tree.nearest_neighbor(query).x)
+                .sum::<f64>()
+        });
     });
 }
 
-criterion_group!(benches, bench_rrt, bench_rrtstar, bench_rrtconnect);
+criterion_group!(
+    benches,
+    bench_rrt,
+    bench_rrtstar,
+    bench_rrtconnect,
+    bench_bidirectional,
+    bench_nearest_neighbor
+);
 criterion_main!(benches);
@@ -0,0 +1,112 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Derive macros for `rustplanning`, enabled with its `derive` feature and re-exported
+//! from `rustplanning::float`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Index, Member};
+
+/// Implements `PartialEq`, `Eq`, `Hash` and `rustplanning::tree::Distance` for a struct of
+/// float fields, see `rustplanning::float::FloatState`.
+#[proc_macro_derive(FloatState, attributes(float_state))]
+pub fn derive_float_state(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "FloatState can only be derived for structs",
+        ));
+    };
+
+    // Every field with its weight, one unless annotated
+    let mut members = Vec::new();
+    let mut weights = Vec::new();
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields.named.iter().collect(),
+        Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+        Fields::Unit => Vec::new(),
+    };
+    for (index, field) in fields.into_iter().enumerate() {
+        members.push(
+            field
+                .ident
+                .clone()
+                .map_or_else(|| Member::Unnamed(Index::from(index)), Member::Named),
+        );
+        weights.push(weight(&field.attrs)?);
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let fields = quote!(::rustplanning::float::FloatFields);
+    Ok(quote! {
+        impl #impl_generics ::std::cmp::PartialEq for #name #type_generics #where_clause {
+            fn eq(&self, other: &Self) -> bool {
+                true #(&& #fields::float_eq(&self.#members, &other.#members))*
+            }
+        }
+
+        impl #impl_generics ::std::cmp::Eq for #name #type_generics #where_clause {}
+
+        impl #impl_generics ::std::hash::Hash for #name #type_generics #where_clause {
+            fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                #(#fields::float_hash(&self.#members, state);)*
+            }
+        }
+
+        impl #impl_generics ::rustplanning::tree::Distance for #name #type_generics #where_clause {
+            fn distance(&self, other: &Self) -> f64 {
+                let squared: f64 = 0.0
+                    #(+ (#weights) * #fields::squared_distance(&self.#members, &other.#members))*;
+                squared.sqrt()
+            }
+        }
+    })
+}
+
+// Reads the weight from a `#[float_state(weight = w)]` attribute
+fn weight(attrs: &[syn::Attribute]) -> syn::Result<Expr> {
+    let mut weight = syn::parse_quote!(1.0);
+    for attr in attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("float_state"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("weight") {
+                weight = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("Expected `weight = <f64>`"))
+            }
+        })?;
+    }
+    Ok(weight)
+}
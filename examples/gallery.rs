@@ -0,0 +1,339 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Runs every scenario with every planner and writes an SVG rendering and a JSON summary of
+// each run to a directory, as a quick visual check after changing planner internals.
+//
+//     cargo run --example gallery -- [output directory]
+
+use rustplanning::bounds::Bounds;
+use rustplanning::error::PlanningError;
+use rustplanning::float::FloatArray;
+use rustplanning::planning::bidirectional::rrt_connect;
+use rustplanning::planning::fallback::GridFallback;
+use rustplanning::planning::prm::{PrmOptions, Roadmap};
+use rustplanning::planning::rrt::{plan, RrtOptions};
+use rustplanning::report::RunReport;
+use rustplanning::tree::{Distance, HashTree};
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+type State = FloatArray<2>;
+
+const SIZE: f64 = 10.0;
+const STEP: f64 = 0.5;
+const SEED: u64 = 42;
+
+/// An axis aligned rectangular obstacle, `[min x, min y, max x, max y]`.
+type Rectangle = [f64; 4];
+
+struct Scenario {
+    name: &'static str,
+    start: State,
+    goal: State,
+    obstacles: Vec<Rectangle>,
+}
+
+impl Scenario {
+    fn is_free(&self, state: &State) -> bool {
+        let [x, y] = state.0;
+        (0.0..=SIZE).contains(&x)
+            && (0.0..=SIZE).contains(&y)
+            && self
+                .obstacles
+                .iter()
+                .all(|r| !(r[0]..=r[2]).contains(&x) || !(r[1]..=r[3]).contains(&y))
+    }
+
+    fn is_motion_free(&self, from: &State, to: &State) -> bool {
+        self.is_free(from)
+            && self.is_free(to)
+            && self
+                .obstacles
+                .iter()
+                .all(|rectangle| !crosses(from, to, rectangle))
+    }
+}
+
+/// Liang-Barsky clipping of the segment against the rectangle.
+fn crosses(from: &State, to: &State, rectangle: &Rectangle) -> bool {
+    let delta = [to.0[0] - from.0[0], to.0[1] - from.0[1]];
+    let (mut low, mut high) = (0.0_f64, 1.0_f64);
+    for axis in 0..2 {
+        for (p, q) in [
+            (-delta[axis], from.0[axis] - rectangle[axis]),
+            (delta[axis], rectangle[axis + 2] - from.0[axis]),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return false;
+                }
+            } else if p < 0.0 {
+                low = low.max(q / p);
+            } else {
+                high = high.min(q / p);
+            }
+        }
+    }
+    low <= high
+}
+
+fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario {
+            name: "open",
+            start: FloatArray([1.0, 1.0]),
+            goal: FloatArray([9.0, 9.0]),
+            obstacles: Vec::new(),
+        },
+        Scenario {
+            name: "wall",
+            start: FloatArray([1.0, 1.0]),
+            goal: FloatArray([9.0, 1.0]),
+            obstacles: vec![[4.5, 0.0, 5.5, 8.0]],
+        },
+        Scenario {
+            name: "narrow_passage",
+            start: FloatArray([1.0, 5.0]),
+            goal: FloatArray([9.0, 5.0]),
+            obstacles: vec![[4.0, 0.0, 6.0, 4.7], [4.0, 5.3, 6.0, 10.0]],
+        },
+        Scenario {
+            name: "maze",
+            start: FloatArray([1.0, 1.0]),
+            goal: FloatArray([9.0, 9.0]),
+            obstacles: vec![
+                [2.5, 0.0, 3.0, 7.5],
+                [5.0, 2.5, 5.5, 10.0],
+                [7.5, 0.0, 8.0, 7.5],
+            ],
+        },
+        Scenario {
+            name: "unreachable",
+            start: FloatArray([1.0, 1.0]),
+            goal: FloatArray([8.0, 8.0]),
+            obstacles: vec![
+                [6.5, 6.5, 9.5, 7.0],
+                [6.5, 9.0, 9.5, 9.5],
+                [6.5, 6.5, 7.0, 9.5],
+                [9.0, 6.5, 9.5, 9.5],
+            ],
+        },
+    ]
+}
+
+type RunFn = fn(&Scenario) -> Run;
+
+/// The result of running a planner on a scenario.
+struct Run {
+    path: Result<Vec<State>, PlanningError>,
+    edges: Vec<(State, State)>,
+}
+
+fn tree_edges(tree: &HashTree<State>) -> Vec<(State, State)> {
+    tree.iter_insertion_order()
+        .filter_map(|node| Some((*tree.get_parent(node)?, *node)))
+        .collect()
+}
+
+fn sampler(seed: u64) -> impl FnMut() -> State {
+    Bounds::new([0.0, 0.0], [SIZE, SIZE])
+        .expect("The gallery's bounds are valid")
+        .seeded_sampler(seed)
+}
+
+fn extend(from: &State, to: &State) -> State {
+    let scale = (STEP / from.distance(to)).min(1.0);
+    FloatArray([
+        from.0[0] + (to.0[0] - from.0[0]) * scale,
+        from.0[1] + (to.0[1] - from.0[1]) * scale,
+    ])
+}
+
+fn run_rrt(scenario: &Scenario, options: &RrtOptions) -> Run {
+    match plan(
+        &scenario.start,
+        &scenario.goal,
+        sampler(SEED),
+        extend,
+        |from: &State, to: &State| scenario.is_motion_free(from, to),
+        options,
+    ) {
+        Ok((path, tree)) => Run {
+            path: Ok(path),
+            edges: tree_edges(&tree),
+        },
+        Err(error) => Run {
+            path: Err(error),
+            edges: Vec::new(),
+        },
+    }
+}
+
+fn rrt_options() -> RrtOptions {
+    RrtOptions::default()
+        .with_max_extension(STEP)
+        .with_max_iterations(5000)
+}
+
+/// Every planner in the gallery, by name.
+fn planners() -> Vec<(&'static str, RunFn)> {
+    vec![
+        ("rrt", |scenario| run_rrt(scenario, &rrt_options())),
+        ("rrt_star", |scenario| {
+            let options = rrt_options().with_rrtstar(1.5).with_fast_return(false);
+            run_rrt(scenario, &options)
+        }),
+        ("rrt_connect", |scenario| {
+            match rrt_connect(
+                &scenario.start,
+                &scenario.goal,
+                sampler(SEED),
+                extend,
+                |from: &State, to: &State| scenario.is_motion_free(from, to),
+                &rrt_options(),
+            ) {
+                Ok((path, tree)) => Run {
+                    path: Ok(path),
+                    edges: tree_edges(&tree),
+                },
+                Err(error) => Run {
+                    path: Err(error),
+                    edges: Vec::new(),
+                },
+            }
+        }),
+        ("lazy_prm", |scenario| {
+            let valid = |from: &State, to: &State| scenario.is_motion_free(from, to);
+            let options = PrmOptions {
+                samples: 500,
+                connection_radius: 2.0,
+                lazy: true,
+                ..PrmOptions::default()
+            };
+            let mut roadmap = Roadmap::build(sampler(SEED), valid, options);
+            let path = roadmap.query(&scenario.start, &scenario.goal, valid);
+            let edges = roadmap
+                .edges()
+                .into_iter()
+                .map(|(from, to, _)| (*from, *to))
+                .collect();
+            Run { path, edges }
+        }),
+        ("grid_search", |scenario| {
+            let bounds = Bounds::new([0.0, 0.0], [SIZE, SIZE]).expect("Valid bounds");
+            let grid = GridFallback::new(bounds, 0.25, |state: &State| state.0, FloatArray);
+            let path = grid
+                .search(
+                    &scenario.start,
+                    &scenario.goal,
+                    |from: &State, to: &State| scenario.is_motion_free(from, to),
+                )
+                .ok_or(PlanningError::GoalUnreachable);
+            Run {
+                path,
+                edges: Vec::new(),
+            }
+        }),
+    ]
+}
+
+fn position(state: &State) -> (f64, f64) {
+    (state.0[0], state.0[1])
+}
+
+fn to_json(scenario: &Scenario, planner: &str, run: &Run, seconds: f64) -> String {
+    let mut json = String::new();
+    let _ = write!(
+        json,
+        "{{\"scenario\":\"{}\",\"planner\":\"{planner}\",\"seed\":{SEED},\"time_seconds\":{seconds}",
+        scenario.name
+    );
+    let _ = write!(json, ",\"edges\":{}", run.edges.len());
+    match &run.path {
+        Ok(path) => {
+            let cost: f64 = path.windows(2).map(|pair| pair[0].distance(&pair[1])).sum();
+            let points: Vec<String> = path
+                .iter()
+                .map(|state| format!("[{},{}]", state.0[0], state.0[1]))
+                .collect();
+            let _ = write!(
+                json,
+                ",\"success\":true,\"cost\":{cost},\"path\":[{}]",
+                points.join(",")
+            );
+        }
+        Err(error) => {
+            let _ = write!(json, ",\"success\":false,\"error\":{:?}", error.to_string());
+        }
+    }
+    json.push_str("}\n");
+    json
+}
+
+fn write_artifacts(directory: &Path, scenario: &Scenario, planner: &str, run: &Run, seconds: f64) {
+    let mut report = RunReport::new(format!("{} {planner}", scenario.name))
+        .with_edges(run.edges.iter().map(|(from, to)| (from, to)), position);
+    for r in &scenario.obstacles {
+        report = report.with_obstacle(vec![(r[0], r[1]), (r[2], r[1]), (r[2], r[3]), (r[0], r[3])]);
+    }
+    if let Ok(path) = &run.path {
+        report = report.with_path(path, position);
+    }
+
+    let name = format!("{}-{planner}", scenario.name);
+    fs::write(directory.join(format!("{name}.svg")), report.to_svg()).expect("Failed to write SVG");
+    fs::write(
+        directory.join(format!("{name}.json")),
+        to_json(scenario, planner, run, seconds),
+    )
+    .expect("Failed to write JSON");
+}
+
+pub fn main() {
+    let directory = env::args()
+        .nth(1)
+        .map_or_else(|| PathBuf::from("target/gallery"), PathBuf::from);
+    fs::create_dir_all(&directory).expect("Failed to create the output directory");
+
+    for scenario in scenarios() {
+        for (planner, run_fn) in planners() {
+            let start_time = Instant::now();
+            let run = run_fn(&scenario);
+            let seconds = start_time.elapsed().as_secs_f64();
+            write_artifacts(&directory, &scenario, planner, &run, seconds);
+
+            let outcome = match &run.path {
+                Ok(path) => format!("{} states", path.len()),
+                Err(error) => error.to_string(),
+            };
+            println!(
+                "{:<16} {planner:<12} {seconds:>8.4}s  {outcome}",
+                scenario.name
+            );
+        }
+    }
+    println!("Wrote artifacts to {}", directory.display());
+}
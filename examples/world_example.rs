@@ -21,74 +21,60 @@
 // SOFTWARE.
 
 use geo::{coord, polygon, Coord, EuclideanDistance, Line, Point, Polygon};
-use ordered_float::OrderedFloat;
 use plotly::common::{Fill, Line as PlotlyLine, Mode};
 use plotly::{Layout, Plot, Scatter};
-use rand::Rng;
-use rustplanning::planning::rrt::rrt;
+use rustplanning::benchmark::{seed_sweep, ConfigurationReport, RunOutcome};
+use rustplanning::bounds::Bounds;
+use rustplanning::float::{FloatArray, FloatState};
+use rustplanning::planning::batch::ProblemDefinition;
+use rustplanning::planning::rrt::{rrt_with_termination, RrtOptions};
+use rustplanning::planning::stats::PlannerStats;
+use rustplanning::planning::termination::{
+    MaxDuration, MaxIterations, PlannerProgress, TerminationCondition,
+};
 use rustplanning::tree::{Distance, HashTree};
 use std::env;
+use std::time::Duration;
 
-// Define a new wrapper type around `geo::Point<f64>` for robot poses, and
-// to satisfy additional required traits.
-#[derive(Debug, Hash, Clone, Copy)]
-struct RobotPose(Point<OrderedFloat<f64>>);
+// Robot poses in the plane, deriving the traits required of tree states.
+#[derive(Debug, Clone, Copy, FloatState)]
+struct RobotPose {
+    x: f64,
+    y: f64,
+}
 
-// Implement methods to easily create and interact with `MyPoint`
+// Implement methods to easily create and interact with `RobotPose`
 impl RobotPose {
     fn new(x: f64, y: f64) -> Self {
-        RobotPose(Point::new(OrderedFloat(x), OrderedFloat(y)))
-    }
-
-    fn inner(&self) -> &Point<OrderedFloat<f64>> {
-        &self.0
+        RobotPose { x, y }
     }
 
-    fn to_point(&self) -> Point<f64> {
-        Point::new(self.inner().x().into_inner(), self.inner().y().into_inner())
+    fn to_point(self) -> Point<f64> {
+        Point::new(self.x, self.y)
     }
 
-    fn to_coord(&self) -> Coord<f64> {
-        coord! {x: self.inner().x().into_inner(), y: self.inner().y().into_inner()}
+    fn to_coord(self) -> Coord<f64> {
+        coord! {x: self.x, y: self.y}
     }
 
     fn extend(&self, end: &Self, step_size: f64) -> Self {
-        let direction = (
-            (end.0.x() - self.0.x()).into_inner(),
-            (end.0.y() - self.0.y()).into_inner(),
-        );
+        let direction = (end.x - self.x, end.y - self.y);
         let length = self.distance(end);
         let norm_direction = (direction.0 / length, direction.1 / length);
         RobotPose::new(
-            self.0.x().into_inner() + norm_direction.0 * step_size,
-            self.0.y().into_inner() + norm_direction.1 * step_size,
+            self.x + norm_direction.0 * step_size,
+            self.y + norm_direction.1 * step_size,
         )
     }
 }
 
-impl PartialEq for RobotPose {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.x() == other.0.x() && self.0.y() == other.0.y()
-    }
-}
-
-impl Eq for RobotPose {}
-
-// Required inherited trait
-impl Distance for RobotPose {
-    fn distance(&self, other: &Self) -> f64 {
-        let (dx, dy) = (self.0.x() - other.0.x(), self.0.y() - other.0.y());
-        (dx * dx + dy * dy).sqrt()
-    }
-}
-
 /// Simple representation of a 2-D rectangular world.
 ///
-/// Limits are from 0 to x_max and y_max.
+/// Limits are from 0 to `x_max` and `y_max`.
 /// Obstacles are represented by Polygons.
 struct World {
-    /// x_max and y_max for the world, must be >0.0
-    pub bounds: (f64, f64),
+    /// Limits of the world, from the origin to `x_max` and `y_max`
+    pub bounds: Bounds<2>,
 
     // Closed polygons with inaccessible interiors
     pub obstacles: Vec<Polygon>,
@@ -98,43 +84,35 @@ impl World {
     /// Constructs a new world object with the specified shapes
     pub fn new(x_max: f64, y_max: f64, obstacles: Vec<Polygon>) -> Self {
         World {
-            bounds: (x_max, y_max),
-            obstacles: obstacles,
+            bounds: Bounds::new([0.0, 0.0], [x_max, y_max]).expect("Limits must be positive"),
+            obstacles,
         }
     }
 
-    pub fn sample(&self) -> RobotPose {
-        let mut generator = rand::thread_rng();
-        let x = generator.gen_range(0.0..=self.bounds.0);
-        let y = generator.gen_range(0.0..=self.bounds.1);
-        RobotPose::new(x, y)
+    /// Returns a sampler drawing poses uniformly within the world's bounds.
+    ///
+    /// The same seed always yields the same samples, so planning runs are repeatable.
+    pub fn sampler(&self, seed: u64) -> impl FnMut() -> RobotPose {
+        let mut sample = self.bounds.seeded_sampler(seed);
+        move || {
+            let FloatArray([x, y]) = sample();
+            RobotPose::new(x, y)
+        }
     }
 
-    /// Returns whether or not a line between the two provided poses intersects with
-    /// any obstacles and if the distance is within the maximum connectable step size.
-    pub fn connectable(
-        &self,
-        from: &RobotPose,
-        to: &RobotPose,
-        buffer: f64,
-        step_size: f64,
-    ) -> bool {
+    /// Returns whether or not a line between the two provided poses stays at least
+    /// `buffer` away from all obstacles.
+    pub fn connectable(&self, from: &RobotPose, to: &RobotPose, buffer: f64) -> bool {
         let line = Line::new(from.to_coord(), to.to_coord());
-        let intersects = self
+        !self
             .obstacles
             .iter()
-            .any(|obstacle| line.euclidean_distance(obstacle) < buffer);
-        let reachable = from.distance(to) < step_size;
-        !intersects && reachable
+            .any(|obstacle| line.euclidean_distance(obstacle) < buffer)
     }
 }
 
 /// Visualize a successful path
-fn visualize_rrt(
-    world: &World,
-    path: &Vec<RobotPose>,
-    tree: &HashTree<RobotPose>,
-) {
+fn visualize_rrt(world: &World, path: &[RobotPose], tree: &HashTree<RobotPose>) {
     let mut plot = Plot::new();
 
     // Plot obstacles
@@ -161,14 +139,8 @@ fn visualize_rrt(
     }
 
     // Plot path
-    let path_x: Vec<_> = path
-        .iter()
-        .map(|pose| pose.inner().x().into_inner())
-        .collect();
-    let path_y: Vec<_> = path
-        .iter()
-        .map(|pose| pose.inner().y().into_inner())
-        .collect();
+    let path_x: Vec<_> = path.iter().map(|pose| pose.x).collect();
+    let path_y: Vec<_> = path.iter().map(|pose| pose.y).collect();
     let path_trace = Scatter::new(path_x, path_y)
         .mode(Mode::Lines)
         .line(PlotlyLine::new().color("red").width(4.0));
@@ -177,23 +149,17 @@ fn visualize_rrt(
     // Plot start and end
     let start = path.first().unwrap();
     let end = path.last().unwrap();
-    let start_trace = Scatter::new(
-        vec![start.inner().x().into_inner()],
-        vec![start.inner().y().into_inner()],
-    )
-    .mode(Mode::Markers)
-    .marker(plotly::common::Marker::new().color("green").size(16));
-    let end_trace = Scatter::new(
-        vec![end.inner().x().into_inner()],
-        vec![end.inner().y().into_inner()],
-    )
-    .mode(Mode::Markers)
-    .marker(plotly::common::Marker::new().color("yellow").size(16));
+    let start_trace = Scatter::new(vec![start.x], vec![start.y])
+        .mode(Mode::Markers)
+        .marker(plotly::common::Marker::new().color("green").size(16));
+    let end_trace = Scatter::new(vec![end.x], vec![end.y])
+        .mode(Mode::Markers)
+        .marker(plotly::common::Marker::new().color("yellow").size(16));
     plot.add_trace(start_trace);
     plot.add_trace(end_trace);
 
     let layout = Layout::new()
-        .title(format!("RRT Path Finding Result").as_str().into())
+        .title("RRT Path Finding Result".into())
         .show_legend(false)
         .width(750)
         .height(750)
@@ -204,10 +170,86 @@ fn visualize_rrt(
     plot.show();
 }
 
+/// Builds the world with a few rectangular obstacles.
+fn build_world() -> World {
+    let obstacles = vec![
+        polygon![(x: 10.0, y: 10.0), (x: 30.0, y: 10.0), (x: 30.0, y: 30.0), (x: 10.0, y: 30.0), (x: 10.0, y: 10.0)],
+        polygon![(x: 50.0, y: 50.0), (x: 80.0, y: 50.0), (x: 80.0, y: 80.0), (x: 50.0, y: 80.0), (x: 50.0, y: 50.0)],
+        polygon![(x: 70.0, y: 20.0), (x: 90.0, y: 20.0), (x: 90.0, y: 40.0), (x: 70.0, y: 40.0), (x: 70.0, y: 20.0)],
+        polygon![(x: 35.0, y: 30.0), (x: 45.0, y: 30.0), (x: 45.0, y: 90.0), (x: 35.0, y: 90.0), (x: 35.0, y: 30.0)],
+    ];
+    World::new(100.0, 100.0, obstacles)
+}
+
+/// Runs the planner once per seed from 0 to `seeds`, printing each result and a summary.
+fn run_sweep(args: &[String]) {
+    if args.len() != 8 {
+        eprintln!("Usage: program sweep start_x start_y end_x end_y use_rrtstar use_rrtconnect seeds timeout");
+        return;
+    }
+    let parse = |i: usize, name: &str| -> f64 { args[i].parse().expect(name) };
+    let use_rrtstar: bool = args[4]
+        .parse()
+        .expect("Invalid use_rrtstar argument; should be true or false");
+    let use_rrtconnect: bool = args[5]
+        .parse()
+        .expect("Invalid use_rrtconnect argument; should be true or false");
+    let seeds: u64 = args[6].parse().expect("Invalid seeds");
+    let timeout = parse(7, "Invalid timeout");
+
+    let world = build_world();
+    let problem = ProblemDefinition::new(
+        RobotPose::new(parse(0, "Invalid start_x"), parse(1, "Invalid start_y")),
+        RobotPose::new(parse(2, "Invalid end_x"), parse(3, "Invalid end_y")),
+    );
+    let options = RrtOptions {
+        max_extension: 1.0,
+        use_rrtstar,
+        rewire_radius: 5.0,
+        use_rrtconnect,
+        ..RrtOptions::default()
+    };
+
+    // Every run returns as soon as it finds a path, or gives up after the timeout
+    let world = &world;
+    let results = seed_sweep(
+        &problem,
+        &(0..seeds).collect::<Vec<_>>(),
+        |seed| world.sampler(seed),
+        |from: &RobotPose, to: &RobotPose| from.extend(to, 1.0),
+        |from: &RobotPose, to: &RobotPose| world.connectable(from, to, 1.0),
+        &options,
+        || {
+            MaxIterations(1_000_000)
+                .or(MaxDuration(Duration::from_secs_f64(timeout)))
+                .or(|progress: &PlannerProgress| progress.best_cost.is_some())
+        },
+    );
+
+    for result in &results {
+        println!("{result}");
+    }
+    let outcomes: Vec<RunOutcome> = results.iter().map(|result| result.outcome).collect();
+    print!(
+        "{}",
+        ConfigurationReport::from_runs("world_example", &outcomes)
+    );
+}
+
+/// Seed for sampling the world, so that every run with the same arguments produces the
+/// same tree.
+const SEED: u64 = 1;
+
 pub fn main() {
     let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("sweep") {
+        run_sweep(&args[2..]);
+        return;
+    }
     if args.len() != 7 && args.len() != 8 {
-        eprintln!("Usage: program start_x start_y end_x end_y use_rrtstar use_rrtconnect [timeout]");
+        eprintln!(
+            "Usage: program start_x start_y end_x end_y use_rrtstar use_rrtconnect [timeout]"
+        );
         return;
     }
 
@@ -232,22 +274,15 @@ pub fn main() {
     let goal = RobotPose::new(end_x, end_y);
 
     println!("Starting pathfinding with parameters:");
-    println!("  start pose: ({}, {})", start_x, start_y);
-    println!("  end pose: ({}, {})", end_x, end_y);
-    println!("  use_rrtstar: {}", use_rrtstar);
-    println!("  use_rrtconnect: {}", use_rrtconnect);
-    println!("  fast_return: {}", fast_return);
-    println!("  timeout: {}", timeout);
-
-    // Add a few rectangular obstacles to the world
-    let obstacles = vec![
-        polygon![(x: 10.0, y: 10.0), (x: 30.0, y: 10.0), (x: 30.0, y: 30.0), (x: 10.0, y: 30.0), (x: 10.0, y: 10.0)],
-        polygon![(x: 50.0, y: 50.0), (x: 80.0, y: 50.0), (x: 80.0, y: 80.0), (x: 50.0, y: 80.0), (x: 50.0, y: 50.0)],
-        polygon![(x: 70.0, y: 20.0), (x: 90.0, y: 20.0), (x: 90.0, y: 40.0), (x: 70.0, y: 40.0), (x: 70.0, y: 20.0)],
-        polygon![(x: 35.0, y: 30.0), (x: 45.0, y: 30.0), (x: 45.0, y: 90.0), (x: 35.0, y: 90.0), (x: 35.0, y: 30.0)],
-    ];
+    println!("  start pose: ({start_x}, {start_y})");
+    println!("  end pose: ({end_x}, {end_y})");
+    println!("  use_rrtstar: {use_rrtstar}");
+    println!("  use_rrtconnect: {use_rrtconnect}");
+    println!("  fast_return: {fast_return}");
+    println!("  timeout: {timeout}");
+    println!("  seed: {SEED}");
 
-    let world = World::new(100.0, 100.0, obstacles);
+    let world = build_world();
 
     // Constants for this particular run
     let buffer = 1.0; // All samples must be > 1.0 away from obstacles.
@@ -255,31 +290,40 @@ pub fn main() {
     let rewire_radius = 5.0; // Radius for rewiring tree if using RRT*.
 
     // Define closures
-    let sample_fn = || world.sample();
+    let sample_fn = world.sampler(SEED);
     let extend_fn = |from: &RobotPose, to: &RobotPose| from.extend(to, step_size);
-    let connectable_fn =
-        |from: &RobotPose, to: &RobotPose| world.connectable(from, to, buffer, rewire_radius);
+    let connectable_fn = |from: &RobotPose, to: &RobotPose| world.connectable(from, to, buffer);
 
-    let result = rrt(
+    // The planner limits extensions to the step size, while RRT* may rewire any valid
+    // edges within the rewire radius.
+    let options = RrtOptions {
+        max_extension: step_size,
+        use_rrtstar,
+        rewire_radius,
+        use_rrtconnect,
+        ..RrtOptions::default()
+    };
+    let termination = MaxIterations(1_000_000)
+        .or(MaxDuration(Duration::from_secs_f64(timeout)))
+        .or(|progress: &PlannerProgress| fast_return && progress.best_cost.is_some());
+
+    let result = rrt_with_termination(
         &start,
         &goal,
         sample_fn,
         extend_fn,
         connectable_fn,
-        use_rrtstar,
-        rewire_radius,
-        use_rrtconnect,
-        1000000,
-        timeout,
-        fast_return,
+        &options,
+        termination,
+        &mut PlannerStats::new(),
     );
     match result {
         Ok((path, tree)) => {
-            println!("Path found!");
+            println!("Path found with {} states, tree: {tree}", path.len());
             visualize_rrt(&world, &path, &tree);
         }
         Err(e) => {
-            println!("RRT failed: {}", e);
+            println!("RRT failed: {e}");
         }
     }
 }
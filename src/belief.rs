@@ -0,0 +1,295 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::float::canonical_bits;
+use crate::tree::Distance;
+use std::hash::{Hash, Hasher};
+
+/// A state known only up to a Gaussian distribution, with mean and covariance.
+///
+/// Compared and hashed by the bits of every component like
+/// [`crate::float::FloatArray`]. The distance between beliefs is the Euclidean distance
+/// between their means plus the Frobenius norm of the difference of their covariances,
+/// so beliefs at the same position with different certainty are distinct tree nodes.
+#[derive(Debug, Clone, Copy)]
+pub struct Belief<const N: usize> {
+    /// Expected state.
+    pub mean: [f64; N],
+
+    /// Covariance of the state, symmetric and positive semi-definite.
+    pub covariance: [[f64; N]; N],
+}
+
+impl<const N: usize> Belief<N> {
+    /// Construct a belief from its mean and covariance.
+    #[must_use]
+    pub fn new(mean: [f64; N], covariance: [[f64; N]; N]) -> Self {
+        Belief { mean, covariance }
+    }
+
+    /// Construct a belief with no uncertainty, e.g. for sampling positions.
+    #[must_use]
+    pub fn certain(mean: [f64; N]) -> Self {
+        Belief {
+            mean,
+            covariance: [[0.0; N]; N],
+        }
+    }
+
+    /// Sum of the variances along each axis.
+    #[must_use]
+    pub fn trace(&self) -> f64 {
+        (0..N).map(|i| self.covariance[i][i]).sum()
+    }
+
+    /// An upper bound on the standard deviation in any direction, the square root of the
+    /// trace. Cheap and conservative, it is exact when uncertainty is along a single axis.
+    #[must_use]
+    pub fn max_std_dev(&self) -> f64 {
+        self.trace().max(0.0).sqrt()
+    }
+
+    fn components(&self) -> impl Iterator<Item = f64> + '_ {
+        self.mean
+            .iter()
+            .chain(self.covariance.iter().flatten())
+            .copied()
+    }
+}
+
+impl<const N: usize> PartialEq for Belief<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.components()
+            .zip(other.components())
+            .all(|(a, b)| canonical_bits(a) == canonical_bits(b))
+    }
+}
+
+impl<const N: usize> Eq for Belief<N> {}
+
+impl<const N: usize> Hash for Belief<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in self.components() {
+            canonical_bits(value).hash(state);
+        }
+    }
+}
+
+impl<const N: usize> Distance for Belief<N> {
+    fn distance(&self, other: &Self) -> f64 {
+        let mean = self
+            .mean
+            .iter()
+            .zip(&other.mean)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        mean + covariance_distance(&self.covariance, &other.covariance)
+    }
+}
+
+// Frobenius norm of the difference between two covariances
+fn covariance_distance<const N: usize>(a: &[[f64; N]; N], b: &[[f64; N]; N]) -> f64 {
+    a.iter()
+        .flatten()
+        .zip(b.iter().flatten())
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+// Propagates a belief in a straight line to `target` in steps of at most `resolution`,
+// calling `visit` with every intermediate and final belief until it returns false.
+fn propagate_along<const N: usize, FP, FV>(
+    from: &Belief<N>,
+    target: &[f64; N],
+    resolution: f64,
+    propagate_fn: &mut FP,
+    mut visit: FV,
+) -> Option<Belief<N>>
+where
+    FP: FnMut(&Belief<N>, &[f64; N]) -> Belief<N>,
+    FV: FnMut(&Belief<N>) -> bool,
+{
+    let length = Belief::certain(from.mean).distance(&Belief::certain(*target));
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let steps = (length / resolution).ceil().max(1.0) as usize;
+    let mut belief = *from;
+    for step in 1..=steps {
+        #[allow(clippy::cast_precision_loss)]
+        let t = step as f64 / steps as f64;
+        let mut mean = from.mean;
+        for (value, (a, b)) in mean.iter_mut().zip(from.mean.iter().zip(target)) {
+            *value = a + (b - a) * t;
+        }
+        belief = propagate_fn(&belief, &mean);
+        if !visit(&belief) {
+            return None;
+        }
+    }
+    Some(belief)
+}
+
+/// Builds a belief space extension function from a steering function on means.
+///
+/// `steer_fn` returns the mean reached when steering from one mean towards another, as
+/// for an ordinary planner. `propagate_fn` returns the belief after moving the mean from
+/// the given belief to a nearby mean, e.g. growing the covariance with process noise and
+/// shrinking it near landmarks. The motion is propagated in steps of at most
+/// `resolution`, exactly as [`belief_validator`] checks it.
+pub fn belief_steer<const N: usize, FS, FP>(
+    mut steer_fn: FS,
+    mut propagate_fn: FP,
+    resolution: f64,
+) -> impl FnMut(&Belief<N>, &Belief<N>) -> Belief<N>
+where
+    FS: FnMut(&[f64; N], &[f64; N]) -> [f64; N],
+    FP: FnMut(&Belief<N>, &[f64; N]) -> Belief<N>,
+{
+    move |from: &Belief<N>, to: &Belief<N>| {
+        let target = steer_fn(&from.mean, &to.mean);
+        propagate_along(from, &target, resolution, &mut propagate_fn, |_| true).unwrap_or(*from)
+    }
+}
+
+/// Builds a belief space motion validator with a chance constraint on collisions.
+///
+/// `clearance_fn` returns the distance from a mean to the nearest obstacle. The motion is
+/// propagated from `from` towards `to` in steps of at most `resolution` with
+/// `propagate_fn`, and every step must keep `sigmas` upper bound standard deviations of
+/// clearance, see [`Belief::max_std_dev`]. The motion is also rejected unless the
+/// propagated belief matches `to`, so states the planner did not reach through
+/// propagation, like raw samples, are never added to the tree.
+pub fn belief_validator<const N: usize, FC, FP>(
+    mut clearance_fn: FC,
+    mut propagate_fn: FP,
+    sigmas: f64,
+    resolution: f64,
+) -> impl FnMut(&Belief<N>, &Belief<N>) -> bool
+where
+    FC: FnMut(&[f64; N]) -> f64,
+    FP: FnMut(&Belief<N>, &[f64; N]) -> Belief<N>,
+{
+    move |from: &Belief<N>, to: &Belief<N>| {
+        let mut safe =
+            |belief: &Belief<N>| clearance_fn(&belief.mean) >= sigmas * belief.max_std_dev();
+        if !safe(from) {
+            return false;
+        }
+        propagate_along(from, &to.mean, resolution, &mut propagate_fn, &mut safe).is_some_and(
+            |reached| {
+                let scale = 1.0 + reached.trace().abs();
+                covariance_distance(&reached.covariance, &to.covariance) <= 1e-9 * scale
+            },
+        )
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::rrt::rrt_simple;
+    use crate::planning::termination::MaxIterations;
+
+    // Variance grows by 0.005 per unit travelled along x, and resets to zero at the beacon
+    // at x = 5
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn propagate(belief: &Belief<1>, mean: &[f64; 1]) -> Belief<1> {
+        let travelled = (mean[0] - belief.mean[0]).abs();
+        let variance = if (mean[0] - 5.0).abs() < 1e-9 {
+            0.0
+        } else {
+            belief.covariance[0][0] + 0.005 * travelled
+        };
+        Belief::new(*mean, [[variance]])
+    }
+
+    // A wall at x = 10
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn clearance(mean: &[f64; 1]) -> f64 {
+        10.0 - mean[0]
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn steer(from: &[f64; 1], to: &[f64; 1]) -> [f64; 1] {
+        [from[0] + (to[0] - from[0]).clamp(-1.0, 1.0)]
+    }
+
+    #[test]
+    fn test_belief() {
+        let a = Belief::new([0.0, 0.0], [[1.0, 0.0], [0.0, 3.0]]);
+        let b = Belief::new([3.0, 4.0], [[1.0, 0.0], [0.0, 3.0]]);
+        assert!((a.trace() - 4.0).abs() < 1e-9);
+        assert!((a.max_std_dev() - 2.0).abs() < 1e-9);
+        assert!((a.distance(&b) - 5.0).abs() < 1e-9);
+        assert!((a.distance(&Belief::certain([0.0, 0.0])) - 10.0_f64.sqrt()).abs() < 1e-9);
+        assert_ne!(a, Belief::certain([0.0, 0.0]));
+        assert_eq!(Belief::certain([0.0]), Belief::certain([-0.0]));
+    }
+
+    #[test]
+    fn test_belief_steer_and_validate() {
+        let mut extend = belief_steer(steer, propagate, 0.5);
+        let mut valid = belief_validator(clearance, propagate, 3.0, 0.5);
+
+        let start = Belief::certain([0.0]);
+        let next = extend(&start, &Belief::certain([4.0]));
+        assert!((next.mean[0] - 1.0).abs() < 1e-9);
+        assert!((next.covariance[0][0] - 0.005).abs() < 1e-9);
+        assert!(valid(&start, &next));
+
+        // A raw sample does not match the propagated covariance
+        assert!(!valid(&start, &Belief::certain([1.0])));
+
+        // Close to the wall the uncertainty is too large
+        let near = Belief::new([9.0], [[0.2]]);
+        let closer = extend(&near, &Belief::certain([10.0]));
+        assert!(!valid(&near, &closer));
+        let certain = Belief::certain([9.0]);
+        assert!(valid(&certain, &extend(&certain, &Belief::certain([9.5]))));
+    }
+
+    #[test]
+    fn test_belief_rrt() {
+        // Reaching 9.5 needs the uncertainty reset from passing the beacon
+        let mut samples = [9.5, 2.0, 5.0, 7.0].into_iter().cycle();
+        let (path, _) = rrt_simple(
+            &Belief::new([0.0], [[0.05]]),
+            |belief: &Belief<1>| (belief.mean[0] - 9.5).abs() < 1e-9,
+            || Belief::certain([samples.next().unwrap()]),
+            belief_steer(steer, propagate, 0.5),
+            belief_validator(clearance, propagate, 3.0, 0.5),
+            MaxIterations(100),
+        )
+        .unwrap();
+        assert!(path.iter().any(|belief| belief.trace() == 0.0));
+        assert!(path.last().unwrap().trace() < 0.03);
+    }
+}
@@ -0,0 +1,773 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::clock::Instant;
+use crate::planning::batch::ProblemDefinition;
+use crate::planning::rrt::{rrt_with_termination, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::Distance;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Memory allocated during a single run, as measured by a [`CountingAllocator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+    /// Number of allocations, including reallocations.
+    pub allocations: u64,
+
+    /// Total bytes requested over all allocations.
+    pub bytes_allocated: u64,
+
+    /// Most memory in use at once, in bytes above what was in use when the run started.
+    pub peak_bytes: u64,
+}
+
+/// A global allocator that counts allocations and tracks peak memory use, so benchmarks can
+/// report the memory used by each run alongside its time.
+///
+/// Allocation is forwarded to the [`System`] allocator. Counts are process wide, so runs
+/// should be measured one at a time and without other threads allocating. It only counts
+/// anything once installed as the global allocator of the benchmark binary:
+///
+/// ```
+/// # use rustplanning::benchmark::CountingAllocator;
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+///
+/// let (_, usage) = ALLOCATOR.measure(|| vec![0u8; 1000]);
+/// assert_eq!(usage.allocations, 1);
+/// assert!(usage.peak_bytes >= 1000);
+/// ```
+#[derive(Debug, Default)]
+pub struct CountingAllocator {
+    allocations: AtomicU64,
+    bytes_allocated: AtomicU64,
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl CountingAllocator {
+    /// Construct an allocator with all counts at zero.
+    #[must_use]
+    pub const fn new() -> Self {
+        CountingAllocator {
+            allocations: AtomicU64::new(0),
+            bytes_allocated: AtomicU64::new(0),
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Runs `f`, returning its result along with the memory it allocated.
+    pub fn measure<R>(&self, f: impl FnOnce() -> R) -> (R, MemoryUsage) {
+        let allocations = self.allocations.load(Ordering::Relaxed);
+        let bytes_allocated = self.bytes_allocated.load(Ordering::Relaxed);
+        let baseline = self.current.load(Ordering::Relaxed);
+        self.peak.store(baseline, Ordering::Relaxed);
+
+        let result = f();
+        let usage = MemoryUsage {
+            allocations: self.allocations.load(Ordering::Relaxed) - allocations,
+            bytes_allocated: self.bytes_allocated.load(Ordering::Relaxed) - bytes_allocated,
+            peak_bytes: (self.peak.load(Ordering::Relaxed).saturating_sub(baseline)) as u64,
+        };
+        (result, usage)
+    }
+
+    fn record_allocation(&self, size: usize) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.bytes_allocated
+            .fetch_add(size as u64, Ordering::Relaxed);
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            self.record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            self.record_allocation(new_size);
+            self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+/// The outcome of a single planner run in an experiment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunOutcome {
+    /// Whether a path was found.
+    pub success: bool,
+
+    /// Wall clock time taken by the run.
+    pub elapsed: Duration,
+
+    /// Cost of the path, if one was found.
+    pub cost: Option<f64>,
+
+    /// Memory allocated by the run, if measured.
+    pub memory: Option<MemoryUsage>,
+}
+
+/// Order statistics of a set of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+
+    /// First quartile, the 25th percentile.
+    pub q1: f64,
+
+    /// Third quartile, the 75th percentile.
+    pub q3: f64,
+}
+
+impl Summary {
+    /// Summarizes the samples, or returns `None` if there are none. NaN samples are ignored.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        let mut sorted: Vec<f64> = samples.iter().copied().filter(|v| !v.is_nan()).collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(f64::total_cmp);
+        Some(Summary {
+            count: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            median: quantile(&sorted, 0.5),
+            q1: quantile(&sorted, 0.25),
+            q3: quantile(&sorted, 0.75),
+        })
+    }
+
+    /// The interquartile range, a measure of spread that is robust to outliers.
+    #[must_use]
+    pub fn iqr(&self) -> f64 {
+        self.q3 - self.q1
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "median {:.4} (IQR {:.4}, range {:.4}..{:.4}, n={})",
+            self.median,
+            self.iqr(),
+            self.min,
+            self.max,
+            self.count
+        )
+    }
+}
+
+// Linearly interpolated quantile of sorted, non-empty samples
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// A success rate with a confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SuccessRate {
+    pub successes: usize,
+    pub trials: usize,
+}
+
+impl SuccessRate {
+    /// The observed fraction of successful trials, or 0 if there were none.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn rate(&self) -> f64 {
+        if self.trials == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.trials as f64
+        }
+    }
+
+    /// Wilson score interval for the true success rate at the given z score, e.g. 1.96 for
+    /// 95% confidence.
+    ///
+    /// Unlike the normal approximation, the interval behaves well for small numbers of trials
+    /// and rates near 0 or 1. Returns `(0, 1)` if there were no trials.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn wilson_interval(&self, z: f64) -> (f64, f64) {
+        if self.trials == 0 {
+            return (0.0, 1.0);
+        }
+        let n = self.trials as f64;
+        let p = self.rate();
+        let z2 = z * z;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+        ((center - margin).max(0.0), (center + margin).min(1.0))
+    }
+}
+
+impl fmt::Display for SuccessRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (low, high) = self.wilson_interval(1.96);
+        write!(
+            f,
+            "{}/{} ({:.1}%, 95% CI {:.1}%..{:.1}%)",
+            self.successes,
+            self.trials,
+            self.rate() * 100.0,
+            low * 100.0,
+            high * 100.0
+        )
+    }
+}
+
+// Error function, Abramowitz and Stegun 7.1.26, accurate to 1.5e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let y = 1.0 - polynomial * (-x * x).exp();
+    y.copysign(x)
+}
+
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / std::f64::consts::SQRT_2))
+}
+
+/// Result of a Mann-Whitney U test between two sets of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignificanceTest {
+    /// The U statistic of the first set of samples.
+    pub u: f64,
+
+    /// Normal approximation of the U statistic, negative if the first set tends to be smaller.
+    pub z: f64,
+
+    /// Two sided p-value, the probability of a difference at least this large if both sets
+    /// came from the same distribution.
+    pub p_value: f64,
+}
+
+/// Tests whether two sets of samples come from different distributions, e.g. the solution
+/// costs of two planner configurations.
+///
+/// Uses the Mann-Whitney U test, which makes no assumptions about the distributions and is
+/// robust to the heavy tails typical of sampling based planners. Ties are given their mean
+/// rank and the variance is corrected for them, since ties are common in e.g. iteration
+/// counts capped at a limit. The p-value uses the normal approximation which is reasonable
+/// for around 10 or more samples each. Returns `None` if either set is empty.
+#[must_use]
+#[allow(clippy::cast_precision_loss, clippy::many_single_char_names)]
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> Option<SignificanceTest> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Rank the pooled samples, averaging the ranks of ties and summing t^3 - t over groups
+    // of t tied samples for the variance
+    let mut pooled: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&v| (v, true))
+        .chain(b.iter().map(|&v| (v, false)))
+        .collect();
+    pooled.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < pooled.len() {
+        let mut j = i;
+        while j + 1 < pooled.len() && pooled[j + 1].0.total_cmp(&pooled[i].0).is_eq() {
+            j += 1;
+        }
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        rank_sum_a += rank * pooled[i..=j].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        let group = (j - i + 1) as f64;
+        ties += group.powi(3) - group;
+        i = j + 1;
+    }
+
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let mean = n1 * n2 / 2.0;
+    let tie_correction = if n > 1.0 { ties / (n * (n - 1.0)) } else { 0.0 };
+    let deviation = (n1 * n2 / 12.0 * (n + 1.0 - tie_correction)).sqrt();
+
+    // If every sample is tied there is no spread, and no evidence of a difference
+    let z = if deviation > 0.0 {
+        (u - mean) / deviation
+    } else {
+        0.0
+    };
+    Some(SignificanceTest {
+        u,
+        z,
+        p_value: (2.0 * (1.0 - normal_cdf(z.abs()))).clamp(0.0, 1.0),
+    })
+}
+
+/// Aggregate report over many runs of one planner configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigurationReport {
+    pub name: String,
+    pub success: SuccessRate,
+
+    /// Planning time in seconds over all runs.
+    pub time: Option<Summary>,
+
+    /// Path cost over the successful runs.
+    pub cost: Option<Summary>,
+
+    /// Number of allocations over the runs that measured memory.
+    pub allocations: Option<Summary>,
+
+    /// Peak memory use in bytes over the runs that measured memory.
+    pub peak_memory: Option<Summary>,
+}
+
+impl ConfigurationReport {
+    /// Summarize the runs of a configuration.
+    pub fn from_runs(name: impl Into<String>, runs: &[RunOutcome]) -> Self {
+        let times: Vec<f64> = runs.iter().map(|run| run.elapsed.as_secs_f64()).collect();
+        #[allow(clippy::cast_precision_loss)]
+        let memory = |field: fn(&MemoryUsage) -> u64| -> Vec<f64> {
+            runs.iter()
+                .filter_map(|run| run.memory.as_ref().map(|usage| field(usage) as f64))
+                .collect()
+        };
+        ConfigurationReport {
+            name: name.into(),
+            success: SuccessRate {
+                successes: runs.iter().filter(|run| run.success).count(),
+                trials: runs.len(),
+            },
+            time: Summary::from_samples(&times),
+            cost: Summary::from_samples(&costs(runs)),
+            allocations: Summary::from_samples(&memory(|usage| usage.allocations)),
+            peak_memory: Summary::from_samples(&memory(|usage| usage.peak_bytes)),
+        }
+    }
+}
+
+fn costs(runs: &[RunOutcome]) -> Vec<f64> {
+    runs.iter().filter_map(|run| run.cost).collect()
+}
+
+impl fmt::Display for ConfigurationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        writeln!(f, "  success: {}", self.success)?;
+        if let Some(time) = &self.time {
+            writeln!(f, "  time (s): {time}")?;
+        }
+        if let Some(cost) = &self.cost {
+            writeln!(f, "  cost: {cost}")?;
+        }
+        if let Some(allocations) = &self.allocations {
+            writeln!(f, "  allocations: {allocations}")?;
+        }
+        if let Some(peak_memory) = &self.peak_memory {
+            writeln!(f, "  peak memory (bytes): {peak_memory}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the runs of two planner configurations, reporting both and whether their
+/// planning times and path costs differ significantly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub a: ConfigurationReport,
+    pub b: ConfigurationReport,
+    pub time_test: Option<SignificanceTest>,
+    pub cost_test: Option<SignificanceTest>,
+}
+
+impl Comparison {
+    /// Compare two named sets of runs.
+    pub fn new(
+        name_a: impl Into<String>,
+        runs_a: &[RunOutcome],
+        name_b: impl Into<String>,
+        runs_b: &[RunOutcome],
+    ) -> Self {
+        let times = |runs: &[RunOutcome]| -> Vec<f64> {
+            runs.iter().map(|run| run.elapsed.as_secs_f64()).collect()
+        };
+        Comparison {
+            a: ConfigurationReport::from_runs(name_a, runs_a),
+            b: ConfigurationReport::from_runs(name_b, runs_b),
+            time_test: mann_whitney_u(&times(runs_a), &times(runs_b)),
+            cost_test: mann_whitney_u(&costs(runs_a), &costs(runs_b)),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.a, self.b)?;
+        for (label, test) in [("time", &self.time_test), ("cost", &self.cost_test)] {
+            if let Some(test) = test {
+                writeln!(
+                    f,
+                    "{label}: Mann-Whitney U={:.1}, z={:.3}, p={:.4}",
+                    test.u, test.z, test.p_value
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of planning with a single seed in a [`seed_sweep`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeedResult {
+    pub seed: u64,
+    pub outcome: RunOutcome,
+}
+
+impl fmt::Display for SeedResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed {}: {} in {:.4}s",
+            self.seed,
+            if self.outcome.success {
+                "success"
+            } else {
+                "failure"
+            },
+            self.outcome.elapsed.as_secs_f64()
+        )?;
+        if let Some(cost) = self.outcome.cost {
+            write!(f, ", cost {cost:.4}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs RRT on a single problem once for every seed.
+///
+/// `make_sampler` is called with each seed to construct the sampling function for that run,
+/// e.g. from a seeded RNG, and `make_termination` constructs a fresh termination condition
+/// for every run. Runs are executed in order on the calling thread so that their timings
+/// are comparable.
+///
+/// Use [`ConfigurationReport::from_runs`] on the outcomes to aggregate the results.
+pub fn seed_sweep<T, MS, FS, FE, FC, MT, TC>(
+    problem: &ProblemDefinition<T>,
+    seeds: &[u64],
+    make_sampler: MS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    make_termination: MT,
+) -> Vec<SeedResult>
+where
+    T: Eq + Clone + Hash + Distance,
+    MS: FnMut(u64) -> FS,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    MT: FnMut() -> TC,
+    TC: TerminationCondition,
+{
+    sweep(
+        problem,
+        seeds,
+        None,
+        make_sampler,
+        extend_fn,
+        connectable_fn,
+        options,
+        make_termination,
+    )
+}
+
+/// Runs a [`seed_sweep`], also measuring the memory allocated by each run with `allocator`,
+/// which must be the global allocator to count anything.
+#[allow(clippy::too_many_arguments)]
+pub fn seed_sweep_with_memory<T, MS, FS, FE, FC, MT, TC>(
+    problem: &ProblemDefinition<T>,
+    seeds: &[u64],
+    allocator: &CountingAllocator,
+    make_sampler: MS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    make_termination: MT,
+) -> Vec<SeedResult>
+where
+    T: Eq + Clone + Hash + Distance,
+    MS: FnMut(u64) -> FS,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    MT: FnMut() -> TC,
+    TC: TerminationCondition,
+{
+    sweep(
+        problem,
+        seeds,
+        Some(allocator),
+        make_sampler,
+        extend_fn,
+        connectable_fn,
+        options,
+        make_termination,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sweep<T, MS, FS, FE, FC, MT, TC>(
+    problem: &ProblemDefinition<T>,
+    seeds: &[u64],
+    allocator: Option<&CountingAllocator>,
+    mut make_sampler: MS,
+    mut extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    mut make_termination: MT,
+) -> Vec<SeedResult>
+where
+    T: Eq + Clone + Hash + Distance,
+    MS: FnMut(u64) -> FS,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    MT: FnMut() -> TC,
+    TC: TerminationCondition,
+{
+    seeds
+        .iter()
+        .map(|&seed| {
+            let start_time = Instant::now();
+            let mut run = || {
+                rrt_with_termination(
+                    &problem.start,
+                    &problem.goal,
+                    make_sampler(seed),
+                    &mut extend_fn,
+                    &mut connectable_fn,
+                    options,
+                    make_termination(),
+                    &mut PlannerStats::new(),
+                )
+            };
+            let (result, memory) = match allocator {
+                Some(allocator) => {
+                    let (result, memory) = allocator.measure(run);
+                    (result, Some(memory))
+                }
+                None => (run(), None),
+            };
+            SeedResult {
+                seed,
+                outcome: RunOutcome {
+                    success: result.is_ok(),
+                    elapsed: start_time.elapsed(),
+                    cost: result
+                        .ok()
+                        .and_then(|(_, tree)| tree.cost(&problem.goal).ok()),
+                    memory,
+                },
+            }
+        })
+        .collect()
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::from_samples(&[7.0, 1.0, 3.0, f64::NAN, 5.0, 9.0]).unwrap();
+        assert_eq!(summary.count, 5);
+        assert!(approx_eq!(f64, summary.median, 5.0));
+        assert!(approx_eq!(f64, summary.q1, 3.0));
+        assert!(approx_eq!(f64, summary.q3, 7.0));
+        assert!(approx_eq!(f64, summary.iqr(), 4.0));
+        assert!(approx_eq!(f64, summary.mean, 5.0));
+
+        let summary = Summary::from_samples(&[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert!(approx_eq!(f64, summary.median, 2.5));
+        assert!(approx_eq!(f64, summary.q1, 1.75));
+
+        assert!(Summary::from_samples(&[]).is_none());
+    }
+
+    #[test]
+    fn test_success_rate() {
+        let rate = SuccessRate {
+            successes: 8,
+            trials: 10,
+        };
+        let (low, high) = rate.wilson_interval(1.96);
+        assert!(approx_eq!(f64, low, 0.490_157, epsilon = 1e-6));
+        assert!(approx_eq!(f64, high, 0.943_319, epsilon = 1e-6));
+        assert_eq!(rate.to_string(), "8/10 (80.0%, 95% CI 49.0%..94.3%)");
+
+        // Perfect success still leaves some uncertainty
+        let rate = SuccessRate {
+            successes: 5,
+            trials: 5,
+        };
+        let (low, high) = rate.wilson_interval(1.96);
+        assert!(low > 0.5 && low < 1.0);
+        assert!(approx_eq!(f64, high, 1.0));
+    }
+
+    #[test]
+    fn test_mann_whitney_u() {
+        let a: Vec<f64> = (0..20).map(f64::from).collect();
+        let b: Vec<f64> = (10..30).map(f64::from).collect();
+        let test = mann_whitney_u(&a, &b).unwrap();
+        assert!(approx_eq!(f64, test.u, 50.0));
+        assert!(test.z < 0.0);
+        assert!(test.p_value < 0.001);
+
+        // Identical samples are all ties and indistinguishable
+        let test = mann_whitney_u(&[1.0; 10], &[1.0; 10]).unwrap();
+        assert!(approx_eq!(f64, test.p_value, 1.0, epsilon = 1e-6));
+
+        // Heavily tied iteration counts capped at 10, checked against the tie corrected
+        // normal approximation without continuity correction, as in SciPy's
+        // `mannwhitneyu(a, b, use_continuity=False, method="asymptotic")`
+        let a: Vec<f64> = [3.0, 5.0, 5.0, 8.0].into_iter().chain([10.0; 8]).collect();
+        let b: Vec<f64> = [7.0, 9.0, 9.0].into_iter().chain([10.0; 9]).collect();
+        let test = mann_whitney_u(&a, &b).unwrap();
+        assert!(approx_eq!(f64, test.u, 61.0));
+        assert!(approx_eq!(f64, test.z, -0.791_173_758, epsilon = 1e-8));
+        assert!(approx_eq!(f64, test.p_value, 0.428_842_603, epsilon = 1e-6));
+        assert!(mann_whitney_u(&[], &[1.0]).is_none());
+    }
+
+    #[test]
+    fn test_seed_sweep() {
+        use crate::planning::termination::{MaxIterations, SolutionFound};
+
+        // Odd seeds only sample in the wrong direction and never reach the goal
+        let make_sampler = |seed: u64| move || if seed.is_multiple_of(2) { 10 } else { -10 };
+        let extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let connectable_fn = |from: &i32, to: &i32| (to - from).abs() <= 1;
+        let results = seed_sweep(
+            &ProblemDefinition::new(0, 3),
+            &[0, 1, 2],
+            make_sampler,
+            extend_fn,
+            connectable_fn,
+            &RrtOptions::default(),
+            || MaxIterations(5).or(SolutionFound),
+        );
+
+        let seeds: Vec<u64> = results.iter().map(|result| result.seed).collect();
+        assert_eq!(seeds, vec![0, 1, 2]);
+        assert!(results[0].outcome.success);
+        assert!(!results[1].outcome.success);
+        assert!(approx_eq!(f64, results[2].outcome.cost.unwrap(), 3.0));
+        assert!(results[1].to_string().starts_with("seed 1: failure in "));
+
+        assert!(results.iter().all(|result| result.outcome.memory.is_none()));
+
+        let outcomes: Vec<RunOutcome> = results.iter().map(|result| result.outcome).collect();
+        let report = ConfigurationReport::from_runs("RRT", &outcomes);
+        assert_eq!(report.success.successes, 2);
+        assert!(report.allocations.is_none() && report.peak_memory.is_none());
+    }
+
+    #[test]
+    fn test_comparison_report() {
+        let run = |success: bool, millis: u64| RunOutcome {
+            success,
+            elapsed: Duration::from_millis(millis),
+            cost: success.then(|| f64::from(u32::try_from(millis).unwrap())),
+            memory: Some(MemoryUsage {
+                allocations: millis,
+                bytes_allocated: millis * 16,
+                peak_bytes: millis * 8,
+            }),
+        };
+        let a: Vec<RunOutcome> = (1..=10).map(|i| run(i % 5 != 0, i * 10)).collect();
+        let b: Vec<RunOutcome> = (1..=10).map(|i| run(true, i * 10 + 100)).collect();
+
+        let comparison = Comparison::new("RRT", &a, "RRT*", &b);
+        assert_eq!(comparison.a.success.successes, 8);
+        assert!(approx_eq!(f64, comparison.a.time.unwrap().median, 0.055));
+        assert!(approx_eq!(f64, comparison.a.cost.unwrap().max, 90.0));
+        assert!(comparison.time_test.unwrap().p_value < 0.001);
+
+        let report = comparison.to_string();
+        assert!(report.starts_with("RRT\n  success: 8/10"), "{report}");
+        assert!(report.contains("RRT*\n  success: 10/10"), "{report}");
+        assert!(report.contains("time: Mann-Whitney U=0.0"), "{report}");
+        assert!(approx_eq!(
+            f64,
+            comparison.b.peak_memory.unwrap().max,
+            1600.0
+        ));
+        assert!(report.contains("  allocations: median 55.0000"), "{report}");
+    }
+}
@@ -0,0 +1,144 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+// The system clock panics on `wasm32-unknown-unknown`, so planners time themselves with
+// this `Instant` instead. In the browser it reads `Date.now()` when the `wasm` feature is
+// enabled, and otherwise never advances, so only iteration based termination works there.
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) use std::time::Instant;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Instant(f64);
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+impl Instant {
+    pub(crate) fn now() -> Self {
+        #[cfg(feature = "wasm")]
+        let millis = js_sys::Date::now();
+        #[cfg(not(feature = "wasm"))]
+        let millis = 0.0;
+        Instant(millis)
+    }
+
+    pub(crate) fn elapsed(&self) -> std::time::Duration {
+        Duration::from_secs_f64((Self::now().0 - self.0).max(0.0) / 1000.0)
+    }
+}
+
+/// Measures the time since planning started, for termination conditions and provenance.
+///
+/// Planners read the system clock by default. Pass a [`ManualClock`] instead to make time
+/// based termination deterministic, e.g. in unit tests. Phase timings are always measured
+/// with the system clock, as they only describe where time was spent.
+pub trait Clock {
+    /// Returns the time elapsed since the clock started.
+    fn elapsed(&self) -> Duration;
+}
+
+/// Reads the system clock, starting from when it was constructed.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Starts a clock at the current time.
+    #[must_use]
+    pub fn start() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only advances when told to, or by a fixed step every time it is read.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    elapsed: Cell<Duration>,
+    step: Duration,
+}
+
+impl ManualClock {
+    /// Construct a clock stopped at zero.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a clock starting at zero that advances by `step` after every read, so a
+    /// planner sees `step` pass per iteration.
+    #[must_use]
+    pub fn with_step(step: Duration) -> Self {
+        ManualClock {
+            elapsed: Cell::new(Duration::ZERO),
+            step,
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.set(self.elapsed.get() + duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn elapsed(&self) -> Duration {
+        let elapsed = self.elapsed.get();
+        self.elapsed.set(elapsed + self.step);
+        elapsed
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(clock.elapsed(), Duration::from_secs(2));
+        assert_eq!(clock.elapsed(), Duration::from_secs(2));
+
+        let clock = ManualClock::with_step(Duration::from_millis(10));
+        let reads: Vec<u128> = (0..3).map(|_| clock.elapsed().as_millis()).collect();
+        assert_eq!(reads, vec![0, 10, 20]);
+
+        assert!(SystemClock::start().elapsed() < Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,613 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::planning::stats::StopReason;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// Errors from modifying or querying a [`crate::tree::HashTree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeError {
+    /// The node is not in the tree.
+    NodeNotFound,
+
+    /// The parent is not in the tree.
+    ParentNotFound,
+
+    /// The node is already in the tree.
+    DuplicateNode,
+
+    /// The root of the tree cannot be reparented.
+    ReparentRoot,
+
+    /// The new parent is the node itself or one of its descendants, which would create a
+    /// cycle.
+    Cycle,
+
+    /// The subtree has nodes in common with the tree.
+    Overlap,
+
+    /// No node on the path is within tolerance of the pose.
+    OutOfTolerance,
+
+    /// The query needs the coordinates of nodes, but the tree has no spatial index.
+    NoSpatialIndex,
+
+    /// A structural invariant of the tree does not hold, which indicates a bug.
+    Corrupt(String),
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::NodeNotFound => write!(f, "The node is not present in the tree"),
+            TreeError::ParentNotFound => write!(f, "The parent was not found in the tree"),
+            TreeError::DuplicateNode => write!(f, "The child is already in the tree"),
+            TreeError::ReparentRoot => write!(f, "Cannot reparent the root of the tree"),
+            TreeError::Cycle => {
+                write!(f, "Cannot reparent a node under itself or its descendants")
+            }
+            TreeError::Overlap => write!(f, "The subtree overlaps with the tree"),
+            TreeError::OutOfTolerance => {
+                write!(f, "No node on the path is within tolerance of the pose")
+            }
+            TreeError::NoSpatialIndex => write!(f, "The tree has no spatial index"),
+            TreeError::Corrupt(message) => write!(f, "Corrupt tree: {message}"),
+        }
+    }
+}
+
+impl Error for TreeError {}
+
+/// Errors from planning a path.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanningError {
+    /// No path to the goal was found before the time limit was reached.
+    Timeout,
+
+    /// No path to the goal was found before the iteration limit was reached.
+    MaxIterationsReached,
+
+    /// No path to the goal was found before planning stopped for any other reason, e.g. a
+    /// custom termination condition.
+    GoalUnreachable,
+
+    /// Every path to the goal that was found cost more than the maximum allowed.
+    NoSolutionWithinCost {
+        max_cost: f64,
+
+        /// Cost of the cheapest path found, if the goal was reached at all.
+        best_cost: Option<f64>,
+    },
+
+    /// Planning was cancelled with a
+    /// [`crate::planning::termination::CancellationToken`] before it finished.
+    Cancelled,
+
+    /// A tree operation failed.
+    Tree(TreeError),
+}
+
+impl PlanningError {
+    /// Returns the error for a run that stopped for `reason` without reaching the goal.
+    pub(crate) fn not_found(reason: Option<StopReason>) -> Self {
+        match reason {
+            Some(StopReason::Timeout) => PlanningError::Timeout,
+            Some(StopReason::MaxIterations) => PlanningError::MaxIterationsReached,
+            Some(StopReason::Cancelled) => PlanningError::Cancelled,
+            _ => PlanningError::GoalUnreachable,
+        }
+    }
+}
+
+impl fmt::Display for PlanningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanningError::Timeout => write!(f, "Timed out before finding a path"),
+            PlanningError::MaxIterationsReached => {
+                write!(f, "Reached the iteration limit before finding a path")
+            }
+            PlanningError::GoalUnreachable => write!(f, "Failed to find path between poses"),
+            PlanningError::NoSolutionWithinCost {
+                max_cost,
+                best_cost,
+            } => {
+                write!(f, "No solution within the maximum cost of {max_cost}")?;
+                if let Some(cost) = best_cost {
+                    write!(f, ", the best found costs {cost}")?;
+                }
+                Ok(())
+            }
+            PlanningError::Cancelled => write!(f, "Planning was cancelled"),
+            PlanningError::Tree(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for PlanningError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PlanningError::Tree(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<TreeError> for PlanningError {
+    fn from(error: TreeError) -> Self {
+        PlanningError::Tree(error)
+    }
+}
+
+/// Errors from [`crate::planning::hierarchical::plan_hierarchical`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum HierarchicalError {
+    /// The start is not in any region.
+    StartOutsideRegions,
+
+    /// The goal is not in any region.
+    GoalOutsideRegions,
+
+    /// No sequence of regions connects the start and goal.
+    NoRegionPath,
+
+    /// The region is not in the graph.
+    UnknownRegion(usize),
+
+    /// Planning within the corridor of regions failed.
+    Planning(PlanningError),
+}
+
+impl fmt::Display for HierarchicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HierarchicalError::StartOutsideRegions => write!(f, "The start is not in any region"),
+            HierarchicalError::GoalOutsideRegions => write!(f, "The goal is not in any region"),
+            HierarchicalError::NoRegionPath => {
+                write!(f, "No sequence of regions connects the start and goal")
+            }
+            HierarchicalError::UnknownRegion(region) => {
+                write!(f, "Region {region} is not in the graph")
+            }
+            HierarchicalError::Planning(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for HierarchicalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HierarchicalError::Planning(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<PlanningError> for HierarchicalError {
+    fn from(error: PlanningError) -> Self {
+        HierarchicalError::Planning(error)
+    }
+}
+
+/// Errors from [`crate::planning::waypoints::plan_waypoints`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaypointError {
+    /// Fewer than two waypoints were provided.
+    TooFewWaypoints,
+
+    /// A segment could not be planned, where segment `i` connects waypoint `i` to waypoint
+    /// `i + 1`.
+    Segment {
+        segment: usize,
+        error: PlanningError,
+    },
+}
+
+impl fmt::Display for WaypointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaypointError::TooFewWaypoints => write!(f, "At least two waypoints are required"),
+            WaypointError::Segment { segment, error } => write!(f, "Segment {segment}: {error}"),
+        }
+    }
+}
+
+impl Error for WaypointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WaypointError::Segment { error, .. } => Some(error),
+            WaypointError::TooFewWaypoints => None,
+        }
+    }
+}
+
+/// Error from [`crate::planning::coverage::plan_coverage`] when two points of the coverage
+/// path could not be connected, with the error from the planner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageError<E> {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+    pub error: E,
+}
+
+impl<E: fmt::Display> fmt::Display for CoverageError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to connect {:?} to {:?}: {}",
+            self.from, self.to, self.error
+        )
+    }
+}
+
+impl<E: Error + 'static> Error for CoverageError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Errors from [`crate::planning::exploration::plan_to_frontier`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExplorationError<E> {
+    /// There are no frontiers to explore.
+    NoFrontiers,
+
+    /// No frontier could be reached, with the error from the planner for each frontier in
+    /// order.
+    Unreachable(Vec<E>),
+}
+
+impl<E> fmt::Display for ExplorationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplorationError::NoFrontiers => write!(f, "No frontiers to explore"),
+            ExplorationError::Unreachable(_) => {
+                write!(f, "Failed to find a path to any frontier")
+            }
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ExplorationError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExplorationError::Unreachable(errors) => {
+                errors.first().map(|error| error as &(dyn Error + 'static))
+            }
+            ExplorationError::NoFrontiers => None,
+        }
+    }
+}
+
+/// Errors from building a [`crate::planning::heuristic::CostField`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostFieldError {
+    /// The goal is outside the grid.
+    GoalOutsideGrid,
+
+    /// The goal is in a cell that cannot be traversed.
+    GoalNotTraversable,
+}
+
+impl fmt::Display for CostFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CostFieldError::GoalOutsideGrid => write!(f, "The goal is outside the grid"),
+            CostFieldError::GoalNotTraversable => {
+                write!(f, "The goal is not in a traversable cell")
+            }
+        }
+    }
+}
+
+impl Error for CostFieldError {}
+
+/// Errors from constructing [`crate::bounds::Bounds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundsError {
+    /// A limit of the axis is not finite.
+    NotFinite { axis: usize },
+
+    /// The lower limit of the axis is above its upper limit.
+    LowerAboveUpper { axis: usize },
+}
+
+impl fmt::Display for BoundsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoundsError::NotFinite { axis } => write!(f, "Limits of axis {axis} must be finite"),
+            BoundsError::LowerAboveUpper { axis } => {
+                write!(f, "Lower limit is above upper limit on axis {axis}")
+            }
+        }
+    }
+}
+
+impl Error for BoundsError {}
+
+/// Errors from time parameterizing a path with [`crate::trajectory`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrajectoryError {
+    /// The path has no states.
+    EmptyPath,
+
+    /// The profile length is negative or NaN.
+    InvalidLength(f64),
+
+    /// A motion limit is not positive.
+    InvalidLimits,
+
+    /// The time step is not positive.
+    InvalidTimeStep(f64),
+
+    /// The interpolation resolution is not positive.
+    InvalidResolution(f64),
+}
+
+impl fmt::Display for TrajectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrajectoryError::EmptyPath => write!(f, "Path must not be empty"),
+            TrajectoryError::InvalidLength(length) => write!(f, "Invalid profile length {length}"),
+            TrajectoryError::InvalidLimits => write!(f, "Motion limits must be positive"),
+            TrajectoryError::InvalidTimeStep(dt) => write!(f, "Invalid time step {dt}"),
+            TrajectoryError::InvalidResolution(resolution) => {
+                write!(f, "Invalid resolution {resolution}")
+            }
+        }
+    }
+}
+
+impl Error for TrajectoryError {}
+
+/// Errors from modifying the cells of a grid in [`crate::grid`] or [`crate::voxel`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridError {
+    /// The cell is outside the grid.
+    OutOfBounds,
+
+    /// The value is not a probability.
+    InvalidProbability(f64),
+
+    /// The terrain cost is not positive.
+    InvalidCost(f64),
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::OutOfBounds => write!(f, "The cell is outside the grid"),
+            GridError::InvalidProbability(value) => write!(f, "{value} is not a probability"),
+            GridError::InvalidCost(cost) => write!(f, "Terrain cost {cost} is not positive"),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+/// Errors from attaching objects to and detaching them from a
+/// [`crate::scene::PlanningScene`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneError {
+    /// An object is already attached with the name.
+    AlreadyAttached(String),
+
+    /// No object is attached with the name.
+    NotAttached(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::AlreadyAttached(name) => {
+                write!(f, "An object named '{name}' is already attached")
+            }
+            SceneError::NotAttached(name) => write!(f, "No object named '{name}' is attached"),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+/// Errors from reading the files written by [`crate::persist`] and
+/// [`crate::planning::replay::write_events`].
+///
+/// I/O errors compare equal when their [`io::ErrorKind`] matches.
+#[derive(Debug, Clone)]
+pub enum PersistError {
+    /// Reading from the underlying reader failed.
+    Io(Arc<io::Error>),
+
+    /// The file does not start with the header for the expected kind of file.
+    MissingHeader { kind: &'static str },
+
+    /// The file was written by a newer version of the format than this release can read.
+    UnsupportedVersion {
+        kind: &'static str,
+        version: u32,
+
+        /// The latest version this release can read.
+        supported: u32,
+    },
+
+    /// A line is malformed, counting from 1 for the header.
+    Parse { line: usize, message: String },
+
+    /// A node could not be added to the tree.
+    Tree { line: usize, error: TreeError },
+
+    /// The file ended before the data was complete, e.g. a tree without a root.
+    Truncated,
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Io(error) => write!(f, "Failed to read: {error}"),
+            PersistError::MissingHeader { kind } => write!(f, "Missing {kind} header"),
+            PersistError::UnsupportedVersion {
+                kind,
+                version,
+                supported,
+            } => write!(
+                f,
+                "Unsupported {kind} version {version}, expected at most {supported}"
+            ),
+            PersistError::Parse { line, message } => write!(f, "Line {line}: {message}"),
+            PersistError::Tree { line, error } => write!(f, "Line {line}: {error}"),
+            PersistError::Truncated => write!(f, "The file ended unexpectedly"),
+        }
+    }
+}
+
+impl Error for PersistError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PersistError::Io(error) => Some(error.as_ref()),
+            PersistError::Tree { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for PersistError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PersistError::Io(a), PersistError::Io(b)) => a.kind() == b.kind(),
+            (PersistError::MissingHeader { kind: a }, PersistError::MissingHeader { kind: b }) => {
+                a == b
+            }
+            (
+                PersistError::UnsupportedVersion {
+                    kind: a,
+                    version: a_version,
+                    supported: a_supported,
+                },
+                PersistError::UnsupportedVersion {
+                    kind: b,
+                    version: b_version,
+                    supported: b_supported,
+                },
+            ) => a == b && a_version == b_version && a_supported == b_supported,
+            (
+                PersistError::Parse {
+                    line: a,
+                    message: a_message,
+                },
+                PersistError::Parse {
+                    line: b,
+                    message: b_message,
+                },
+            ) => a == b && a_message == b_message,
+            (
+                PersistError::Tree {
+                    line: a,
+                    error: a_error,
+                },
+                PersistError::Tree {
+                    line: b,
+                    error: b_error,
+                },
+            ) => a == b && a_error == b_error,
+            (PersistError::Truncated, PersistError::Truncated) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PersistError {}
+
+impl From<io::Error> for PersistError {
+    fn from(error: io::Error) -> Self {
+        PersistError::Io(Arc::new(error))
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors() {
+        let error = PlanningError::from(TreeError::Cycle);
+        assert_eq!(error, PlanningError::Tree(TreeError::Cycle));
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            TreeError::Cycle.to_string()
+        );
+        assert!(PlanningError::GoalUnreachable.source().is_none());
+        assert_eq!(
+            PlanningError::Cancelled.to_string(),
+            "Planning was cancelled"
+        );
+
+        let error = PlanningError::NoSolutionWithinCost {
+            max_cost: 2.0,
+            best_cost: Some(2.5),
+        };
+        assert_eq!(
+            error.to_string(),
+            "No solution within the maximum cost of 2, the best found costs 2.5"
+        );
+    }
+
+    #[test]
+    fn test_not_found() {
+        assert_eq!(
+            PlanningError::not_found(Some(StopReason::Timeout)),
+            PlanningError::Timeout
+        );
+        assert_eq!(
+            PlanningError::not_found(Some(StopReason::MaxIterations)),
+            PlanningError::MaxIterationsReached
+        );
+        assert_eq!(
+            PlanningError::not_found(Some(StopReason::Cancelled)),
+            PlanningError::Cancelled
+        );
+        assert_eq!(
+            PlanningError::not_found(Some(StopReason::Converged)),
+            PlanningError::GoalUnreachable
+        );
+        assert_eq!(
+            PlanningError::not_found(None),
+            PlanningError::GoalUnreachable
+        );
+    }
+    #[test]
+    fn test_persist_io_error() {
+        let error = PersistError::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(error.to_string(), "Failed to read: no such file");
+        assert_eq!(error.source().unwrap().to_string(), "no such file");
+        assert_eq!(
+            error,
+            PersistError::from(io::Error::from(io::ErrorKind::NotFound))
+        );
+        assert_ne!(error, PersistError::Truncated);
+    }
+}
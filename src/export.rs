@@ -0,0 +1,343 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::tree::{Distance, HashTree};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, Write};
+
+/// Converts a path to rows of `[index, fields...]`, where `fields_fn` flattens each state
+/// into numbers, e.g. `|p| vec![p.x, p.y]`.
+pub fn path_rows<T, F>(path: &[T], mut fields_fn: F) -> Vec<Vec<f64>>
+where
+    F: FnMut(&T) -> Vec<f64>,
+{
+    #[allow(clippy::cast_precision_loss)]
+    path.iter()
+        .enumerate()
+        .map(|(index, state)| {
+            let mut row = vec![index as f64];
+            row.extend(fields_fn(state));
+            row
+        })
+        .collect()
+}
+
+/// Converts a tree to rows of `[id, parent, cost, fields...]` in the order nodes were
+/// added, where ids are row numbers and the root's parent is -1. Every row other than the
+/// root is an edge from its parent.
+pub fn tree_rows<T, F>(tree: &HashTree<T>, mut fields_fn: F) -> Vec<Vec<f64>>
+where
+    T: Eq + Clone + Hash + Distance,
+    F: FnMut(&T) -> Vec<f64>,
+{
+    let ids: HashMap<&T, usize> = tree
+        .iter_insertion_order()
+        .enumerate()
+        .map(|(id, node)| (node, id))
+        .collect();
+
+    #[allow(clippy::cast_precision_loss)]
+    tree.iter_insertion_order()
+        .enumerate()
+        .map(|(id, node)| {
+            let parent = tree
+                .get_parent(node)
+                .map_or(-1.0, |parent| ids[parent] as f64);
+            let mut row = vec![id as f64, parent, tree.cost(node).unwrap_or(f64::NAN)];
+            row.extend(fields_fn(node));
+            row
+        })
+        .collect()
+}
+
+/// Writes rows as CSV with the given column names as the header.
+///
+/// # Errors
+///
+/// If writing fails, or a row does not have one value per column.
+pub fn write_csv<W: Write>(writer: &mut W, columns: &[&str], rows: &[Vec<f64>]) -> io::Result<()> {
+    writeln!(writer, "{}", columns.join(","))?;
+    for row in rows {
+        check_width(row, columns.len())?;
+        let values: Vec<String> = row.iter().map(ToString::to_string).collect();
+        writeln!(writer, "{}", values.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes a path as CSV with an `index` column followed by `columns`, one row per state.
+///
+/// # Errors
+///
+/// If writing fails, or `fields_fn` does not return one value per column.
+pub fn write_path_csv<W, T, F>(
+    writer: &mut W,
+    path: &[T],
+    columns: &[&str],
+    fields_fn: F,
+) -> io::Result<()>
+where
+    W: Write,
+    F: FnMut(&T) -> Vec<f64>,
+{
+    let header: Vec<&str> = ["index"].iter().chain(columns).copied().collect();
+    write_csv(writer, &header, &path_rows(path, fields_fn))
+}
+
+/// Writes a tree as CSV with `id`, `parent`, and `cost` columns followed by `columns`, one
+/// row per node as described in [`tree_rows`].
+///
+/// # Errors
+///
+/// If writing fails, or `fields_fn` does not return one value per column.
+pub fn write_tree_csv<W, T, F>(
+    writer: &mut W,
+    tree: &HashTree<T>,
+    columns: &[&str],
+    fields_fn: F,
+) -> io::Result<()>
+where
+    W: Write,
+    T: Eq + Clone + Hash + Distance,
+    F: FnMut(&T) -> Vec<f64>,
+{
+    let header: Vec<&str> = ["id", "parent", "cost"]
+        .iter()
+        .chain(columns)
+        .copied()
+        .collect();
+    write_csv(writer, &header, &tree_rows(tree, fields_fn))
+}
+
+fn check_width(row: &[f64], width: usize) -> io::Result<()> {
+    if row.len() == width {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Expected {width} values in a row, got {}", row.len()),
+        ))
+    }
+}
+
+/// Writes rows as a 2D little endian `float64` array in the `.npy` format, readable with
+/// `numpy.load`.
+///
+/// # Errors
+///
+/// If writing fails, or the rows have different lengths.
+#[cfg(feature = "npy")]
+pub fn write_npy<W: Write>(writer: &mut W, rows: &[Vec<f64>]) -> io::Result<()> {
+    let width = rows.first().map_or(0, Vec::len);
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {width}), }}",
+        rows.len()
+    );
+
+    // The magic string, version, header length, and header are padded to a multiple of 64
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+    let header_len = u16::try_from(header.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Array shape is too large"))?;
+
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&header_len.to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for row in rows {
+        check_width(row, width)?;
+        for value in row {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes several named arrays to an uncompressed `.npz` archive, readable with
+/// `numpy.load`, e.g. `[("path", &path_rows(...)), ("tree", &tree_rows(...))]`.
+///
+/// # Errors
+///
+/// If writing fails, a set of rows have different lengths, or the archive is too large.
+#[cfg(feature = "npy")]
+pub fn write_npz<W: Write>(writer: &mut W, arrays: &[(&str, &[Vec<f64>])]) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "Archive is too large");
+
+    // A zip archive storing each array without compression
+    let mut central = Vec::new();
+    let mut offset = 0_u32;
+    for (name, rows) in arrays {
+        let mut data = Vec::new();
+        write_npy(&mut data, rows)?;
+        let name = format!("{name}.npy");
+        let name_len = u16::try_from(name.len()).map_err(|_| too_large())?;
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let crc = crc32(&data);
+
+        // Version 2.0, no flags, stored, and dated 1980-01-01
+        let common = [
+            &20_u16.to_le_bytes()[..],
+            &0_u16.to_le_bytes(),
+            &0_u16.to_le_bytes(),
+            &0_u16.to_le_bytes(),
+            &0x21_u16.to_le_bytes(),
+            &crc.to_le_bytes(),
+            &size.to_le_bytes(),
+            &size.to_le_bytes(),
+            &name_len.to_le_bytes(),
+            &0_u16.to_le_bytes(),
+        ]
+        .concat();
+
+        writer.write_all(&0x0403_4b50_u32.to_le_bytes())?;
+        writer.write_all(&common)?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(&data)?;
+
+        central.extend(0x0201_4b50_u32.to_le_bytes());
+        central.extend(20_u16.to_le_bytes());
+        central.extend(&common);
+        // No comment, on the first disk, and no file attributes
+        central.extend([0; 6]);
+        central.extend(0_u32.to_le_bytes());
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+
+        let entry = 30 + u32::from(name_len) + size;
+        offset = offset.checked_add(entry).ok_or_else(too_large)?;
+    }
+
+    let count = u16::try_from(arrays.len()).map_err(|_| too_large())?;
+    let central_len = u32::try_from(central.len()).map_err(|_| too_large())?;
+    writer.write_all(&central)?;
+    writer.write_all(&0x0605_4b50_u32.to_le_bytes())?;
+    writer.write_all(&[0; 4])?;
+    writer.write_all(&count.to_le_bytes())?;
+    writer.write_all(&count.to_le_bytes())?;
+    writer.write_all(&central_len.to_le_bytes())?;
+    writer.write_all(&offset.to_le_bytes())?;
+    writer.write_all(&0_u16.to_le_bytes())?;
+    Ok(())
+}
+
+// CRC-32 as used by zip archives
+#[cfg(feature = "npy")]
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tree is: 0 -> 2 -> 3
+    //            -> -1
+    fn test_tree() -> HashTree<i32> {
+        let mut tree = HashTree::new(0);
+        assert!(tree.add_child(&0, 2).is_ok());
+        assert!(tree.add_child(&2, 3).is_ok());
+        assert!(tree.add_child(&0, -1).is_ok());
+        tree
+    }
+
+    #[test]
+    fn test_write_path_csv() {
+        let mut out = Vec::new();
+        let path = [(0.0, 0.5), (1.0, 1.5)];
+        write_path_csv(&mut out, &path, &["x", "y"], |p: &(f64, f64)| {
+            vec![p.0, p.1]
+        })
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "index,x,y\n0,0,0.5\n1,1,1.5\n"
+        );
+
+        let result = write_path_csv(&mut Vec::new(), &path, &["x"], |p: &(f64, f64)| {
+            vec![p.0, p.1]
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_write_tree_csv() {
+        let mut out = Vec::new();
+        write_tree_csv(&mut out, &test_tree(), &["x"], |node| {
+            vec![f64::from(*node)]
+        })
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,parent,cost,x\n0,-1,0,0\n1,0,2,2\n2,1,3,3\n3,0,1,-1\n"
+        );
+    }
+
+    #[cfg(feature = "npy")]
+    #[test]
+    fn test_write_npy() {
+        let mut out = Vec::new();
+        write_npy(&mut out, &[vec![1.0, 2.0], vec![3.0, 4.0]]).unwrap();
+        assert_eq!(&out[..8], b"\x93NUMPY\x01\x00");
+        let header_len = usize::from(u16::from_le_bytes([out[8], out[9]]));
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&out[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 2)"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(out.len(), 10 + header_len + 4 * 8);
+        assert_eq!(&out[out.len() - 8..], &4.0_f64.to_le_bytes());
+
+        assert!(write_npy(&mut Vec::new(), &[vec![1.0], vec![2.0, 3.0]]).is_err());
+    }
+
+    #[cfg(feature = "npy")]
+    #[test]
+    fn test_write_npz() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let rows = tree_rows(&test_tree(), |node| vec![f64::from(*node)]);
+        let mut out = Vec::new();
+        write_npz(&mut out, &[("tree", &rows), ("empty", &[])]).unwrap();
+        assert_eq!(&out[..4], &0x0403_4b50_u32.to_le_bytes());
+        assert_eq!(&out[30..38], b"tree.npy");
+
+        // The end of central directory record lists both arrays
+        let end = &out[out.len() - 22..];
+        assert_eq!(&end[..4], &0x0605_4b50_u32.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+        let offset = u32::from_le_bytes([end[16], end[17], end[18], end[19]]) as usize;
+        assert_eq!(&out[offset..offset + 4], &0x0201_4b50_u32.to_le_bytes());
+        assert_eq!(&out[offset + 46..offset + 54], b"tree.npy");
+    }
+}
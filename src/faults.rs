@@ -0,0 +1,330 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::Duration;
+
+/// When a wrapped function misbehaves, in terms of how many times it has been called.
+/// Calls are counted from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaultSchedule {
+    /// Never inject a fault.
+    Never,
+
+    /// Inject a fault on every call.
+    Always,
+
+    /// Inject a fault on the listed calls.
+    Calls(Vec<u64>),
+
+    /// Inject a fault on every nth call, starting with call n - 1.
+    Every(u64),
+
+    /// Inject a fault on every call from the given call onwards.
+    After(u64),
+}
+
+impl FaultSchedule {
+    /// Returns whether or not a fault is due on the given call.
+    #[must_use]
+    pub fn fires(&self, call: u64) -> bool {
+        match self {
+            FaultSchedule::Never => false,
+            FaultSchedule::Always => true,
+            FaultSchedule::Calls(calls) => calls.contains(&call),
+            FaultSchedule::Every(n) => *n > 0 && (call + 1).is_multiple_of(*n),
+            FaultSchedule::After(first) => call >= *first,
+        }
+    }
+}
+
+/// How a wrapped function misbehaves when a fault is due.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault<R> {
+    /// Return this value instead of calling the function, e.g. a state containing NaN or
+    /// `false` from a validator.
+    Return(R),
+
+    /// Panic instead of calling the function.
+    Panic,
+
+    /// Sleep for the duration, then call the function as normal.
+    Delay(Duration),
+}
+
+/// Wraps a function of any arity so that it misbehaves on schedule.
+fn inject<R: Clone>(
+    schedule: &FaultSchedule,
+    fault: &Fault<R>,
+    calls: &mut u64,
+    call: impl FnOnce() -> R,
+) -> R {
+    let due = schedule.fires(*calls);
+    *calls += 1;
+    if !due {
+        return call();
+    }
+    match fault {
+        Fault::Return(value) => value.clone(),
+        Fault::Panic => panic!("Injected fault on call {}", *calls - 1),
+        Fault::Delay(duration) => {
+            thread::sleep(*duration);
+            call()
+        }
+    }
+}
+
+/// Wraps a sampling function so that it misbehaves on schedule.
+pub fn faulty_sampler<T, FS>(
+    mut sample_fn: FS,
+    schedule: FaultSchedule,
+    fault: Fault<T>,
+) -> impl FnMut() -> T
+where
+    T: Clone,
+    FS: FnMut() -> T,
+{
+    let mut calls = 0;
+    move || inject(&schedule, &fault, &mut calls, &mut sample_fn)
+}
+
+/// Wraps an extension function so that it misbehaves on schedule.
+pub fn faulty_extender<T, FE>(
+    mut extend_fn: FE,
+    schedule: FaultSchedule,
+    fault: Fault<T>,
+) -> impl FnMut(&T, &T) -> T
+where
+    T: Clone,
+    FE: FnMut(&T, &T) -> T,
+{
+    let mut calls = 0;
+    move |from: &T, to: &T| inject(&schedule, &fault, &mut calls, || extend_fn(from, to))
+}
+
+/// Wraps a motion validator so that it misbehaves on schedule.
+pub fn faulty_validator<T, FC>(
+    mut connectable_fn: FC,
+    schedule: FaultSchedule,
+    fault: Fault<bool>,
+) -> impl FnMut(&T, &T) -> bool
+where
+    FC: FnMut(&T, &T) -> bool,
+{
+    let mut calls = 0;
+    move |from: &T, to: &T| inject(&schedule, &fault, &mut calls, || connectable_fn(from, to))
+}
+
+/// Runs a planner, converting any panic from it or the functions it calls into an error.
+///
+/// The planner's state is discarded when it panics, so this is intended for surviving
+/// faulty user functions rather than recovering a partial result.
+///
+/// # Errors
+///
+/// If the planner fails or panics.
+pub fn catch_panic<R, F>(planner: F) -> Result<R, String>
+where
+    F: FnOnce() -> Result<R, String>,
+{
+    panic::catch_unwind(AssertUnwindSafe(planner))
+        .unwrap_or_else(|payload| Err(format!("Planner panicked: {}", panic_message(&*payload))))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown cause"
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::FloatArray;
+    use crate::planning::rrt::{rrt_with_termination, RrtOptions};
+    use crate::planning::stats::PlannerStats;
+    use crate::planning::termination::{MaxDuration, MaxIterations, TerminationCondition};
+
+    fn plan<FS, FE, FC, TC>(
+        sample_fn: FS,
+        extend_fn: FE,
+        connectable_fn: FC,
+        termination: TC,
+        stats: &mut PlannerStats<FloatArray<2>>,
+    ) -> Result<Vec<FloatArray<2>>, String>
+    where
+        FS: FnMut() -> FloatArray<2>,
+        FE: FnMut(&FloatArray<2>, &FloatArray<2>) -> FloatArray<2>,
+        FC: FnMut(&FloatArray<2>, &FloatArray<2>) -> bool,
+        TC: TerminationCondition,
+    {
+        let options = RrtOptions {
+            max_extension: 1.0,
+            ..RrtOptions::default()
+        };
+        rrt_with_termination(
+            &FloatArray([0.0, 0.0]),
+            &FloatArray([3.0, 0.0]),
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            &options,
+            termination,
+            stats,
+        )
+        .map(|(path, _)| path)
+        .map_err(|error| error.to_string())
+    }
+
+    fn sampler() -> impl FnMut() -> FloatArray<2> {
+        let mut x = 0.0;
+        move || {
+            x += 1.0;
+            FloatArray([x, 0.0])
+        }
+    }
+
+    fn extend(from: &FloatArray<2>, to: &FloatArray<2>) -> FloatArray<2> {
+        let step = (to.0[0] - from.0[0]).clamp(-1.0, 1.0);
+        FloatArray([from.0[0] + step, from.0[1]])
+    }
+
+    #[test]
+    fn test_schedule() {
+        let fired = |schedule: FaultSchedule| -> Vec<u64> {
+            (0..6).filter(|&call| schedule.fires(call)).collect()
+        };
+        assert!(fired(FaultSchedule::Never).is_empty());
+        assert_eq!(fired(FaultSchedule::Always), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(fired(FaultSchedule::Calls(vec![1, 4])), vec![1, 4]);
+        assert_eq!(fired(FaultSchedule::Every(2)), vec![1, 3, 5]);
+        assert!(fired(FaultSchedule::Every(0)).is_empty());
+        assert_eq!(fired(FaultSchedule::After(4)), vec![4, 5]);
+    }
+
+    #[test]
+    fn test_faulty_functions() {
+        let mut sample = faulty_sampler(|| 1, FaultSchedule::Calls(vec![1]), Fault::Return(-1));
+        assert_eq!((sample(), sample(), sample()), (1, -1, 1));
+
+        let mut extend = faulty_extender(
+            |a: &i32, _: &i32| *a,
+            FaultSchedule::Always,
+            Fault::Return(9),
+        );
+        assert_eq!(extend(&1, &2), 9);
+
+        let mut valid = faulty_validator(
+            |_: &i32, _: &i32| true,
+            FaultSchedule::Never,
+            Fault::Return(false),
+        );
+        assert!(valid(&1, &2));
+
+        let mut slow = faulty_sampler(
+            || 1,
+            FaultSchedule::Always,
+            Fault::Delay(Duration::from_millis(5)),
+        );
+        let start = std::time::Instant::now();
+        assert_eq!(slow(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_planner_survives_nan() {
+        let nan = FloatArray([f64::NAN, 0.0]);
+
+        // NaN samples are skipped and counted
+        let mut stats = PlannerStats::new();
+        let sample_fn = faulty_sampler(sampler(), FaultSchedule::Every(2), Fault::Return(nan));
+        let path = plan(
+            sample_fn,
+            extend,
+            |_, _| true,
+            MaxIterations(50),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(stats.invalid_states > 0);
+
+        // NaN extensions are never added to the tree
+        let mut stats = PlannerStats::new();
+        let extend_fn = faulty_extender(extend, FaultSchedule::Every(2), Fault::Return(nan));
+        let sample_fn = || FloatArray([10.0, 0.0]);
+        let path = plan(
+            sample_fn,
+            extend_fn,
+            |_, _| true,
+            MaxIterations(50),
+            &mut stats,
+        )
+        .unwrap();
+        assert!(path.iter().all(|state| !state.0[0].is_nan()));
+    }
+
+    #[test]
+    fn test_planner_survives_panics() {
+        let valid = faulty_validator(|_, _| true, FaultSchedule::Calls(vec![3]), Fault::Panic);
+        let mut stats = PlannerStats::new();
+        let result = catch_panic(|| plan(sampler(), extend, valid, MaxIterations(50), &mut stats));
+        assert_eq!(
+            result.unwrap_err(),
+            "Planner panicked: Injected fault on call 3"
+        );
+
+        let result = catch_panic(|| -> Result<(), String> { panic!("{}", String::from("owned")) });
+        assert_eq!(result.unwrap_err(), "Planner panicked: owned");
+    }
+
+    #[test]
+    fn test_planner_survives_slow_functions() {
+        // Time limits are still respected, albeit only checked between iterations
+        let valid = faulty_validator(
+            |_, _| false,
+            FaultSchedule::Always,
+            Fault::Delay(Duration::from_millis(2)),
+        );
+        let mut stats = PlannerStats::new();
+        let start = std::time::Instant::now();
+        let result = plan(
+            sampler(),
+            extend,
+            valid,
+            MaxDuration(Duration::from_millis(20)),
+            &mut stats,
+        );
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
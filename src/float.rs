@@ -0,0 +1,410 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::tree::Distance;
+use std::hash::{Hash, Hasher};
+
+/// Returns the bits of a float with `-0.0` mapped to `0.0` and every NaN mapped to a single
+/// NaN, so that values which should compare equal also hash equally.
+#[must_use]
+pub fn canonical_bits(value: f64) -> u64 {
+    if value.is_nan() {
+        f64::NAN.to_bits()
+    } else if value == 0.0 {
+        0.0f64.to_bits()
+    } else {
+        value.to_bits()
+    }
+}
+
+/// An `f64` that can be used as a hash map key or tree state.
+///
+/// Plain floats are not `Eq` or `Hash` because NaN is not equal to itself. Hashing the raw
+/// bits with [`f64::to_bits`] avoids that, but then `0.0` and `-0.0` are different keys
+/// even though they compare equal, and NaNs with different payloads are different keys.
+/// `FloatKey` compares and hashes by [`canonical_bits`] instead, so `0.0` and `-0.0` are the
+/// same key and every NaN is the same key. Note that states computed in slightly different
+/// ways, e.g. the same pose reached along two routes, will still generally differ in their
+/// last bits. Use [`Quantized`] when those should be treated as duplicates.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FloatKey(pub f64);
+
+impl PartialEq for FloatKey {
+    fn eq(&self, other: &Self) -> bool {
+        canonical_bits(self.0) == canonical_bits(other.0)
+    }
+}
+
+impl Eq for FloatKey {}
+
+impl Hash for FloatKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        canonical_bits(self.0).hash(state);
+    }
+}
+
+impl From<f64> for FloatKey {
+    fn from(value: f64) -> Self {
+        FloatKey(value)
+    }
+}
+
+impl Distance for FloatKey {
+    fn distance(&self, other: &Self) -> f64 {
+        (self.0 - other.0).abs()
+    }
+}
+
+/// A fixed size vector of `f64`s that can be used as a tree state, compared and hashed like
+/// [`FloatKey`] with Euclidean distance.
+#[derive(Debug, Clone, Copy)]
+pub struct FloatArray<const N: usize>(pub [f64; N]);
+
+impl<const N: usize> PartialEq for FloatArray<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .all(|(a, b)| canonical_bits(*a) == canonical_bits(*b))
+    }
+}
+
+impl<const N: usize> Eq for FloatArray<N> {}
+
+impl<const N: usize> Hash for FloatArray<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in &self.0 {
+            canonical_bits(*value).hash(state);
+        }
+    }
+}
+
+impl<const N: usize> From<[f64; N]> for FloatArray<N> {
+    fn from(values: [f64; N]) -> Self {
+        FloatArray(values)
+    }
+}
+
+impl<const N: usize> Distance for FloatArray<N> {
+    fn distance(&self, other: &Self) -> f64 {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+// Rounds coordinates to the nearest multiple of the resolution, as grid cell indices
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn quantize(coordinates: &[f64], resolution: f64) -> Vec<i64> {
+    coordinates
+        .iter()
+        .map(|c| (c / resolution).round() as i64)
+        .collect()
+}
+
+// Panics unless a grid or discretization resolution is finite and positive
+#[track_caller]
+pub(crate) fn check_resolution(resolution: f64) {
+    assert!(
+        resolution.is_finite() && resolution > 0.0,
+        "Resolution must be finite and positive, got {resolution}"
+    );
+}
+
+/// Wraps a state so that it is compared and hashed by its coordinates rounded to a grid.
+///
+/// States within the same grid cell of size `resolution` are equal, which lets the tree
+/// treat nearly identical states as duplicates. Distances are still computed between the
+/// exact wrapped values. Coordinates are rounded to the nearest multiple of the resolution,
+/// so values close to a cell boundary may still land in different cells, and NaN
+/// coordinates land in the cell at 0.
+#[derive(Debug, Clone)]
+pub struct Quantized<T> {
+    value: T,
+    key: Vec<i64>,
+}
+
+impl<T> Quantized<T> {
+    /// Wraps `value`, keyed by its `coordinates` quantized to `resolution`.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    #[must_use]
+    pub fn new(value: T, coordinates: &[f64], resolution: f64) -> Self {
+        check_resolution(resolution);
+        Quantized {
+            value,
+            key: quantize(coordinates, resolution),
+        }
+    }
+
+    /// The wrapped value.
+    #[must_use]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// The grid cell the value is in.
+    #[must_use]
+    pub fn key(&self) -> &[i64] {
+        &self.key
+    }
+
+    /// Returns the wrapped value.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> PartialEq for Quantized<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for Quantized<T> {}
+
+impl<T> Hash for Quantized<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl<T: Distance> Distance for Quantized<T> {
+    fn distance(&self, other: &Self) -> f64 {
+        self.value.distance(&other.value)
+    }
+}
+
+/// Derives `PartialEq`, `Eq`, `Hash` and [`Distance`] for a struct of floats, so that it
+/// can be used directly as a tree state.
+///
+/// Fields are compared and hashed by their [`canonical_bits`], and the distance is the
+/// Euclidean distance over all fields. A field may be given a
+/// `#[float_state(weight = w)]` to scale its squared difference, e.g. to trade off
+/// position against heading. Fields may be any [`FloatFields`], and other traits can be
+/// derived as usual.
+///
+/// ```
+/// use rustplanning::float::FloatState;
+/// use rustplanning::tree::Distance;
+///
+/// #[derive(Debug, Clone, Copy, FloatState)]
+/// pub struct Pose {
+///     pub x: f64,
+///     pub y: f64,
+///     #[float_state(weight = 0.25)]
+///     pub heading: f64,
+/// }
+///
+/// let a = Pose { x: 0.0, y: 0.0, heading: 0.0 };
+/// let b = Pose { x: 3.0, y: 4.0, heading: 0.0 };
+/// assert_eq!(a.distance(&b), 5.0);
+/// assert_eq!(a, Pose { x: -0.0, y: 0.0, heading: 0.0 });
+/// ```
+#[cfg(feature = "derive")]
+pub use rustplanning_derive::FloatState;
+
+/// Fields of a struct deriving [`FloatState`].
+///
+/// Implemented for `f64` and for arrays and vectors of them, which are compared and hashed
+/// value by value like [`FloatArray`].
+pub trait FloatFields {
+    /// Whether the values have the same [`canonical_bits`].
+    fn float_eq(&self, other: &Self) -> bool;
+
+    /// Hashes the [`canonical_bits`] of the values.
+    fn float_hash<H: Hasher>(&self, state: &mut H);
+
+    /// Sum of the squared differences between the values.
+    fn squared_distance(&self, other: &Self) -> f64;
+}
+
+impl FloatFields for f64 {
+    fn float_eq(&self, other: &Self) -> bool {
+        canonical_bits(*self) == canonical_bits(*other)
+    }
+
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        canonical_bits(*self).hash(state);
+    }
+
+    fn squared_distance(&self, other: &Self) -> f64 {
+        (self - other).powi(2)
+    }
+}
+
+impl FloatFields for [f64] {
+    fn float_eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.float_eq(b))
+    }
+
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for value in self {
+            value.float_hash(state);
+        }
+    }
+
+    fn squared_distance(&self, other: &Self) -> f64 {
+        self.iter()
+            .zip(other)
+            .map(|(a, b)| a.squared_distance(b))
+            .sum()
+    }
+}
+
+impl<const N: usize> FloatFields for [f64; N] {
+    fn float_eq(&self, other: &Self) -> bool {
+        self.as_slice().float_eq(other)
+    }
+
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().float_hash(state);
+    }
+
+    fn squared_distance(&self, other: &Self) -> f64 {
+        self.as_slice().squared_distance(other)
+    }
+}
+
+impl FloatFields for Vec<f64> {
+    fn float_eq(&self, other: &Self) -> bool {
+        self.as_slice().float_eq(other)
+    }
+
+    fn float_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().float_hash(state);
+    }
+
+    fn squared_distance(&self, other: &Self) -> f64 {
+        self.as_slice().squared_distance(other)
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::{Distance, HashTree};
+    use float_cmp::approx_eq;
+    use std::collections::HashSet;
+
+    #[cfg(feature = "derive")]
+    #[derive(Debug, Clone, Copy, FloatState)]
+    struct State {
+        x: f64,
+        #[float_state(weight = 4.0)]
+        y: f64,
+    }
+
+    #[cfg(feature = "derive")]
+    #[derive(Debug, Clone, FloatState)]
+    struct Joints(Vec<f64>, [f64; 2]);
+
+    #[test]
+    fn test_canonical_bits() {
+        assert_eq!(canonical_bits(-0.0), canonical_bits(0.0));
+        assert_eq!(canonical_bits(f64::NAN), canonical_bits(-f64::NAN));
+        assert_ne!(canonical_bits(1.0), canonical_bits(-1.0));
+    }
+
+    #[test]
+    fn test_float_key() {
+        let mut keys = HashSet::new();
+        keys.insert(FloatKey(0.0));
+        keys.insert(FloatKey(f64::NAN));
+        assert!(keys.contains(&FloatKey(-0.0)));
+        assert!(keys.contains(&FloatKey::from(-f64::NAN)));
+        assert!(!keys.contains(&FloatKey(1.0)));
+        assert!(approx_eq!(
+            f64,
+            FloatKey(1.0).distance(&FloatKey(-2.0)),
+            3.0
+        ));
+
+        let a = FloatArray([0.0, -0.0]);
+        assert_eq!(a, FloatArray::from([-0.0, 0.0]));
+        assert_ne!(a, FloatArray([0.0, 1.0]));
+        assert!(approx_eq!(f64, a.distance(&FloatArray([3.0, 4.0])), 5.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Resolution must be finite and positive")]
+    fn test_quantized_infinite_resolution() {
+        let _ = Quantized::new(1.0, &[1.0], f64::INFINITY);
+    }
+
+    #[test]
+    fn test_quantized() {
+        let quantize = |x: f64, y: f64| Quantized::new(FloatArray([x, y]), &[x, y], 0.1);
+        let a = quantize(1.0, 2.0);
+        let b = quantize(1.0 + 1e-9, 2.0 - 1e-9);
+        assert_eq!(a, b);
+        assert_eq!(a.key(), &[10, 20]);
+        assert_ne!(a, quantize(1.1, 2.0));
+
+        // The tree rejects states in the same cell as duplicates
+        let mut tree = HashTree::new(quantize(0.0, 0.0));
+        tree.add_child(&quantize(0.0, 0.0), a).unwrap();
+        assert!(tree.add_child(&quantize(0.0, 0.0), b.clone()).is_err());
+        assert_eq!(b.into_inner(), FloatArray([1.0 + 1e-9, 2.0 - 1e-9]));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_float_state() {
+        let a = State { x: 0.0, y: 0.0 };
+        let b = State { x: 3.0, y: 2.0 };
+        assert!(approx_eq!(f64, a.distance(&b), 5.0));
+        assert_eq!(a, State { x: -0.0, y: 0.0 });
+        assert_ne!(a, b);
+
+        // Usable as a tree state, including lookups of equal but not identical values
+        let mut tree = HashTree::new(a);
+        tree.add_child(&a, b).unwrap();
+        assert_eq!(tree.get_parent(&b), Some(&State { x: -0.0, y: 0.0 }));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_float_state_fields() {
+        let a = Joints(vec![0.0, 0.0], [0.0, 0.0]);
+        let b = Joints(vec![1.0, 2.0], [2.0, 4.0]);
+        assert!(approx_eq!(f64, a.distance(&b), 5.0));
+        assert_eq!(a, Joints(vec![-0.0, 0.0], [0.0, -0.0]));
+        assert_ne!(a, Joints(vec![0.0], [0.0, 0.0]));
+
+        let mut states = HashSet::new();
+        states.insert(a);
+        assert!(states.contains(&Joints(vec![0.0, -0.0], [-0.0, 0.0])));
+        assert!(!states.contains(&b));
+    }
+}
@@ -0,0 +1,473 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+/// A region of the workspace, in the coordinates returned by a geofence's position
+/// function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    /// Axis aligned box in any number of dimensions.
+    Box { min: Vec<f64>, max: Vec<f64> },
+
+    /// Ball in any number of dimensions.
+    Sphere { center: Vec<f64>, radius: f64 },
+
+    /// Simple polygon in the plane of the first two coordinates, extending infinitely in
+    /// any others. Vertices may be in either winding order.
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Region {
+    /// Returns true if the point lies inside or on the boundary of the region.
+    #[must_use]
+    pub fn contains(&self, point: &[f64]) -> bool {
+        match self {
+            Region::Box { min, max } => point
+                .iter()
+                .zip(min.iter().zip(max))
+                .all(|(p, (low, high))| low <= p && p <= high),
+            Region::Sphere { center, radius } => squared_distance(point, center) <= radius * radius,
+            Region::Polygon(vertices) => {
+                let p = planar(point);
+                edges(vertices).any(|(a, b)| on_segment(p, a, b)) || winds_around(vertices, p)
+            }
+        }
+    }
+
+    /// Returns the lower and upper corners of the smallest axis aligned box containing the
+    /// region. Polygons are only bounded in the first two coordinates.
+    #[must_use]
+    pub fn bounding_box(&self) -> (Vec<f64>, Vec<f64>) {
+        match self {
+            Region::Box { min, max } => (min.clone(), max.clone()),
+            Region::Sphere { center, radius } => (
+                center.iter().map(|c| c - radius).collect(),
+                center.iter().map(|c| c + radius).collect(),
+            ),
+            Region::Polygon(vertices) => vertices.iter().fold(
+                (vec![f64::INFINITY; 2], vec![f64::NEG_INFINITY; 2]),
+                |(lower, upper), &(x, y)| {
+                    (
+                        vec![lower[0].min(x), lower[1].min(y)],
+                        vec![upper[0].max(x), upper[1].max(y)],
+                    )
+                },
+            ),
+        }
+    }
+
+    /// Returns true if any part of the straight segment between two points lies in the
+    /// region.
+    #[must_use]
+    pub fn intersects_segment(&self, from: &[f64], to: &[f64]) -> bool {
+        match self {
+            Region::Box { min, max } => {
+                // Clip the segment against each slab in turn
+                let (mut enter, mut exit) = (0.0_f64, 1.0_f64);
+                for (i, (low, high)) in min.iter().zip(max).enumerate() {
+                    let (start, delta) = (from[i], to[i] - from[i]);
+                    if delta == 0.0 {
+                        if start < *low || start > *high {
+                            return false;
+                        }
+                        continue;
+                    }
+                    let (t0, t1) = ((low - start) / delta, (high - start) / delta);
+                    enter = enter.max(t0.min(t1));
+                    exit = exit.min(t0.max(t1));
+                    if enter > exit {
+                        return false;
+                    }
+                }
+                true
+            }
+            Region::Sphere { center, radius } => {
+                let delta: Vec<f64> = to.iter().zip(from).map(|(b, a)| b - a).collect();
+                let length = delta.iter().map(|d| d * d).sum::<f64>();
+                let t = if length > 0.0 {
+                    let along: f64 = center
+                        .iter()
+                        .zip(from)
+                        .zip(&delta)
+                        .map(|((c, a), d)| (c - a) * d)
+                        .sum();
+                    (along / length).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let closest: Vec<f64> = from.iter().zip(&delta).map(|(a, d)| a + d * t).collect();
+                squared_distance(&closest, center) <= radius * radius
+            }
+            Region::Polygon(vertices) => {
+                let (a, b) = (planar(from), planar(to));
+                self.contains(from)
+                    || self.contains(to)
+                    || edges(vertices).any(|(c, d)| segments_intersect(a, b, c, d))
+            }
+        }
+    }
+
+    /// Returns true if the whole straight segment between two points lies in the region.
+    #[must_use]
+    pub fn contains_segment(&self, from: &[f64], to: &[f64]) -> bool {
+        if !(self.contains(from) && self.contains(to)) {
+            return false;
+        }
+        match self {
+            // Convex, so containing both ends is enough
+            Region::Box { .. } | Region::Sphere { .. } => true,
+            Region::Polygon(vertices) => {
+                // The segment may only leave through a crossing of the boundary. Checking
+                // the midpoint of every piece between crossings covers segments that touch
+                // the boundary at a reflex vertex.
+                let (a, b) = (planar(from), planar(to));
+                let mut cuts = vec![0.0, 1.0];
+                for (c, d) in edges(vertices) {
+                    if let Some(t) = crossing(a, b, c, d) {
+                        cuts.push(t);
+                    }
+                    for vertex in [c, d] {
+                        if on_segment(vertex, a, b) {
+                            cuts.push(project(vertex, a, b));
+                        }
+                    }
+                }
+                cuts.sort_by(f64::total_cmp);
+                cuts.windows(2).all(|pair| {
+                    let t = f64::midpoint(pair[0], pair[1]);
+                    let middle = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+                    self.contains(&[middle.0, middle.1])
+                })
+            }
+        }
+    }
+}
+
+/// Keep in and keep out constraints on where the robot may go, independent of the
+/// collision backend.
+///
+/// A position is allowed if it is inside at least one keep in region, if there are any,
+/// and outside every keep out region. A motion is allowed if it stays entirely inside a
+/// single keep in region and never touches a keep out region.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Geofence {
+    /// Regions the robot must stay inside.
+    pub keep_in: Vec<Region>,
+
+    /// Regions the robot must never enter.
+    pub keep_out: Vec<Region>,
+}
+
+impl Geofence {
+    /// Construct a geofence that allows everything.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a region the robot must stay inside.
+    #[must_use]
+    pub fn with_keep_in(mut self, region: Region) -> Self {
+        self.keep_in.push(region);
+        self
+    }
+
+    /// Add a region the robot must never enter.
+    #[must_use]
+    pub fn with_keep_out(mut self, region: Region) -> Self {
+        self.keep_out.push(region);
+        self
+    }
+
+    /// Returns true if the position satisfies every constraint.
+    #[must_use]
+    pub fn allows(&self, point: &[f64]) -> bool {
+        (self.keep_in.is_empty() || self.keep_in.iter().any(|region| region.contains(point)))
+            && !self.keep_out.iter().any(|region| region.contains(point))
+    }
+
+    /// Returns true if the straight motion between two positions satisfies every
+    /// constraint.
+    #[must_use]
+    pub fn allows_segment(&self, from: &[f64], to: &[f64]) -> bool {
+        (self.keep_in.is_empty()
+            || self
+                .keep_in
+                .iter()
+                .any(|region| region.contains_segment(from, to)))
+            && !self
+                .keep_out
+                .iter()
+                .any(|region| region.intersects_segment(from, to))
+    }
+}
+
+/// Restricts a sampler to the geofence by rejection sampling.
+///
+/// `position_fn` returns the workspace position of a state. Gives up after `max_attempts`
+/// and returns the last sample, which the planner will then fail to connect, so that a
+/// geofence which excludes nearly everything cannot hang the planner.
+pub fn constrain_sampler<T, FS, FP>(
+    mut sample_fn: FS,
+    mut position_fn: FP,
+    geofence: Geofence,
+    max_attempts: usize,
+) -> impl FnMut() -> T
+where
+    FS: FnMut() -> T,
+    FP: FnMut(&T) -> Vec<f64>,
+{
+    move || {
+        let mut sample = sample_fn();
+        for _ in 1..max_attempts {
+            if geofence.allows(&position_fn(&sample)) {
+                break;
+            }
+            sample = sample_fn();
+        }
+        sample
+    }
+}
+
+/// Restricts a motion validator to the geofence, assuming the workspace position moves in
+/// a straight line between states. The geofence is checked before the validator.
+pub fn constrain_motion<T, FC, FP>(
+    mut connectable_fn: FC,
+    mut position_fn: FP,
+    geofence: Geofence,
+) -> impl FnMut(&T, &T) -> bool
+where
+    FC: FnMut(&T, &T) -> bool,
+    FP: FnMut(&T) -> Vec<f64>,
+{
+    move |from: &T, to: &T| {
+        geofence.allows_segment(&position_fn(from), &position_fn(to)) && connectable_fn(from, to)
+    }
+}
+
+fn squared_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+fn planar(point: &[f64]) -> (f64, f64) {
+    (point[0], point[1])
+}
+
+fn edges(vertices: &[(f64, f64)]) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(a, b)| (*a, *b))
+}
+
+fn cross(o: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+// Even-odd test, points on the boundary are handled separately
+fn winds_around(vertices: &[(f64, f64)], p: (f64, f64)) -> bool {
+    edges(vertices)
+        .filter(|(a, b)| {
+            (a.1 > p.1) != (b.1 > p.1) && p.0 < a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+fn on_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> bool {
+    cross(a, b, p) == 0.0
+        && p.0 >= a.0.min(b.0)
+        && p.0 <= a.0.max(b.0)
+        && p.1 >= a.1.min(b.1)
+        && p.1 <= a.1.max(b.1)
+}
+
+// Fraction along a-b of the projection of p
+fn project(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    if length > 0.0 {
+        ((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length
+    } else {
+        0.0
+    }
+}
+
+// Fraction along a-b where it properly crosses c-d, if it does
+fn crossing(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> Option<f64> {
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    ((d1 > 0.0) != (d2 > 0.0)
+        && d1 != 0.0
+        && d2 != 0.0
+        && (d3 > 0.0) != (d4 > 0.0)
+        && d3 != 0.0
+        && d4 != 0.0)
+        .then(|| d1 / (d1 - d2))
+}
+
+fn segments_intersect(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    crossing(a, b, c, d).is_some()
+        || on_segment(a, c, d)
+        || on_segment(b, c, d)
+        || on_segment(c, a, b)
+        || on_segment(d, a, b)
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An L shaped polygon missing its top right quadrant
+    fn l_shape() -> Region {
+        Region::Polygon(vec![
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+        ])
+    }
+
+    #[test]
+    fn test_region_contains() {
+        let square = Region::Box {
+            min: vec![0.0, 0.0],
+            max: vec![1.0, 1.0],
+        };
+        assert!(square.contains(&[0.5, 0.5]));
+        assert!(square.contains(&[1.0, 0.0]));
+        assert!(!square.contains(&[1.5, 0.5]));
+
+        let ball = Region::Sphere {
+            center: vec![0.0, 0.0, 0.0],
+            radius: 1.0,
+        };
+        assert!(ball.contains(&[0.0, 0.0, 1.0]));
+        assert!(!ball.contains(&[0.0, 1.0, 1.0]));
+
+        let l = l_shape();
+        assert!(l.contains(&[0.5, 1.5]));
+        assert!(l.contains(&[1.0, 1.5]));
+        assert!(!l.contains(&[1.5, 1.5]));
+        assert!(!l.contains(&[3.0, 0.5]));
+    }
+
+    #[test]
+    fn test_region_bounding_box() {
+        let ball = Region::Sphere {
+            center: vec![1.0, 2.0],
+            radius: 0.5,
+        };
+        assert_eq!(ball.bounding_box(), (vec![0.5, 1.5], vec![1.5, 2.5]));
+        assert_eq!(l_shape().bounding_box(), (vec![0.0, 0.0], vec![2.0, 2.0]));
+    }
+
+    #[test]
+    fn test_region_segments() {
+        let square = Region::Box {
+            min: vec![0.0, 0.0],
+            max: vec![1.0, 1.0],
+        };
+        assert!(square.intersects_segment(&[-1.0, 0.5], &[2.0, 0.5]));
+        assert!(!square.intersects_segment(&[-1.0, 1.5], &[2.0, 1.5]));
+        assert!(!square.intersects_segment(&[-1.0, 0.5], &[-0.5, 0.5]));
+        assert!(square.contains_segment(&[0.0, 0.0], &[1.0, 1.0]));
+
+        let ball = Region::Sphere {
+            center: vec![0.0, 0.0],
+            radius: 1.0,
+        };
+        assert!(ball.intersects_segment(&[-2.0, 0.5], &[2.0, 0.5]));
+        assert!(!ball.intersects_segment(&[-2.0, 1.5], &[2.0, 1.5]));
+        assert!(!ball.intersects_segment(&[2.0, 0.0], &[3.0, 0.0]));
+
+        let l = l_shape();
+        assert!(l.intersects_segment(&[-1.0, 0.5], &[3.0, 0.5]));
+        assert!(!l.intersects_segment(&[1.5, 1.5], &[3.0, 3.0]));
+        assert!(l.contains_segment(&[0.5, 0.5], &[1.5, 0.5]));
+        assert!(l.contains_segment(&[0.5, 1.5], &[0.5, 0.5]));
+
+        // Both ends are inside, but the segment cuts across the missing corner
+        assert!(!l.contains_segment(&[1.5, 0.5], &[0.5, 1.9]));
+
+        // Running along the boundary through the reflex vertex stays inside
+        assert!(l.contains_segment(&[2.0, 1.0], &[0.0, 1.0]));
+    }
+
+    #[test]
+    fn test_geofence() {
+        let geofence = Geofence::new()
+            .with_keep_in(Region::Box {
+                min: vec![0.0, 0.0],
+                max: vec![10.0, 10.0],
+            })
+            .with_keep_out(Region::Sphere {
+                center: vec![5.0, 5.0],
+                radius: 1.0,
+            });
+        assert!(Geofence::new().allows(&[100.0, 100.0]));
+        assert!(geofence.allows(&[1.0, 1.0]));
+        assert!(!geofence.allows(&[11.0, 1.0]));
+        assert!(!geofence.allows(&[5.0, 5.5]));
+
+        assert!(geofence.allows_segment(&[1.0, 1.0], &[9.0, 1.0]));
+        assert!(!geofence.allows_segment(&[1.0, 5.0], &[9.0, 5.0]));
+        assert!(!geofence.allows_segment(&[1.0, 1.0], &[11.0, 1.0]));
+
+        let position = |p: &[i32; 2]| p.iter().map(|&v| f64::from(v)).collect();
+
+        // Samples are redrawn until they land inside the fence
+        let mut samples = vec![[5, 5], [20, 1], [2, 2]].into_iter();
+        let mut sample_fn = constrain_sampler(
+            || samples.next().unwrap_or([0, 0]),
+            |p: &[i32; 2]| position(p),
+            geofence.clone(),
+            10,
+        );
+        assert_eq!(sample_fn(), [2, 2]);
+
+        // Giving up returns the last sample
+        let mut sample_fn =
+            constrain_sampler(|| [5, 5], |p: &[i32; 2]| position(p), geofence.clone(), 3);
+        assert_eq!(sample_fn(), [5, 5]);
+
+        let mut backend_calls = 0;
+        let mut connectable_fn = constrain_motion(
+            |_: &[i32; 2], _: &[i32; 2]| {
+                backend_calls += 1;
+                true
+            },
+            |p: &[i32; 2]| position(p),
+            geofence,
+        );
+        assert!(connectable_fn(&[1, 1], &[9, 1]));
+        assert!(!connectable_fn(&[1, 5], &[9, 5]));
+        drop(connectable_fn);
+        assert_eq!(backend_calls, 1);
+    }
+}
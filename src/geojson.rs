@@ -0,0 +1,402 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::geofence::Region;
+use crate::grid::{CellState, OccupancyGrid};
+use std::f64::consts::TAU;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// Equatorial radius of the WGS 84 ellipsoid in meters.
+const EARTH_RADIUS: f64 = 6_378_137.0;
+
+/// Number of vertices used to approximate a [`Region::Sphere`] as a polygon.
+const CIRCLE_VERTICES: usize = 32;
+
+/// Places the planning frame on the globe, with x pointing east and y north in meters
+/// from the origin.
+///
+/// Positions are converted with an equirectangular projection around the origin, which
+/// is accurate to well under a meter over the few kilometers an outdoor robot plans in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoOrigin {
+    /// Latitude of the origin in degrees.
+    pub latitude: f64,
+
+    /// Longitude of the origin in degrees.
+    pub longitude: f64,
+}
+
+impl GeoOrigin {
+    /// Returns the longitude and latitude in degrees of a position in the planning frame.
+    #[must_use]
+    pub fn to_lon_lat(&self, x: f64, y: f64) -> (f64, f64) {
+        let latitude = self.latitude + (y / EARTH_RADIUS).to_degrees();
+        let longitude =
+            self.longitude + (x / (EARTH_RADIUS * self.latitude.to_radians().cos())).to_degrees();
+        (longitude, latitude)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Geometry {
+    Point((f64, f64)),
+    LineString(Vec<(f64, f64)>),
+    Polygon(Vec<(f64, f64)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Feature {
+    name: String,
+    kind: &'static str,
+    geometry: Geometry,
+}
+
+/// A `GeoJSON` feature collection of 2D planning results, for viewing in GIS tools like QGIS
+/// or on web maps.
+///
+/// Paths are written as `LineString`s and obstacles as `Polygon`s, each with `name` and
+/// `kind` properties, where the kind is one of `path`, `point`, or `obstacle`. Positions
+/// are written as is unless a [`GeoOrigin`] is set, in which case they are converted to
+/// longitude and latitude as `GeoJSON` expects.
+///
+/// ```
+/// use rustplanning::geojson::{FeatureCollection, GeoOrigin};
+///
+/// let collection = FeatureCollection::new()
+///     .with_origin(GeoOrigin { latitude: 47.6, longitude: -122.3 })
+///     .with_path("route", &[(0.0, 0.0), (10.0, 5.0)])
+///     .with_polygon("rock", &[(4.0, 0.0), (6.0, 0.0), (5.0, 2.0)]);
+/// let mut json = Vec::new();
+/// collection.write(&mut json).unwrap();
+/// assert!(String::from_utf8(json).unwrap().starts_with("{\"type\":\"FeatureCollection\""));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeatureCollection {
+    origin: Option<GeoOrigin>,
+    features: Vec<Feature>,
+}
+
+impl FeatureCollection {
+    /// Construct an empty collection in the planning frame.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts every position to longitude and latitude around the origin when written.
+    #[must_use]
+    pub fn with_origin(mut self, origin: GeoOrigin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Adds a path as a `LineString`.
+    #[must_use]
+    pub fn with_path(self, name: &str, path: &[(f64, f64)]) -> Self {
+        self.with_feature(name, "path", Geometry::LineString(path.to_vec()))
+    }
+
+    /// Adds a single position, e.g. the start or goal, as a `Point`.
+    #[must_use]
+    pub fn with_point(self, name: &str, point: (f64, f64)) -> Self {
+        self.with_feature(name, "point", Geometry::Point(point))
+    }
+
+    /// Adds an obstacle outline as a `Polygon`. The ring is closed and wound
+    /// counterclockwise as `GeoJSON` requires, so vertices may be in either order.
+    #[must_use]
+    pub fn with_polygon(self, name: &str, vertices: &[(f64, f64)]) -> Self {
+        let mut ring = vertices.to_vec();
+        if signed_area(&ring) < 0.0 {
+            ring.reverse();
+        }
+        if let Some(&first) = ring.first() {
+            if ring.last() != Some(&first) {
+                ring.push(first);
+            }
+        }
+        self.with_feature(name, "obstacle", Geometry::Polygon(ring))
+    }
+
+    /// Adds a geofence region as a `Polygon` in the plane of its first two coordinates, with
+    /// spheres approximated by a circle of 32 vertices. Regions with fewer than two
+    /// coordinates are skipped.
+    #[must_use]
+    pub fn with_region(self, name: &str, region: &Region) -> Self {
+        let vertices: Vec<(f64, f64)> = match region {
+            Region::Box { min, max } if min.len() >= 2 && max.len() >= 2 => vec![
+                (min[0], min[1]),
+                (max[0], min[1]),
+                (max[0], max[1]),
+                (min[0], max[1]),
+            ],
+            Region::Sphere { center, radius } if center.len() >= 2 => (0..CIRCLE_VERTICES)
+                .map(|i| {
+                    #[allow(clippy::cast_precision_loss)]
+                    let angle = TAU * i as f64 / CIRCLE_VERTICES as f64;
+                    (
+                        center[0] + radius * angle.cos(),
+                        center[1] + radius * angle.sin(),
+                    )
+                })
+                .collect(),
+            Region::Polygon(vertices) => vertices.clone(),
+            _ => return self,
+        };
+        self.with_polygon(name, &vertices)
+    }
+
+    /// Adds the occupied cells of a grid as `Polygon`s, merging adjacent occupied cells in
+    /// each row into a single rectangle.
+    #[must_use]
+    pub fn with_grid(mut self, name: &str, grid: &OccupancyGrid) -> Self {
+        let resolution = grid.resolution();
+        for row in 0..grid.height() {
+            let mut column = 0;
+            while column < grid.width() {
+                if grid.state(column, row) != Some(CellState::Occupied) {
+                    column += 1;
+                    continue;
+                }
+                let start = column;
+                while grid.state(column, row) == Some(CellState::Occupied) {
+                    column += 1;
+                }
+                #[allow(clippy::cast_precision_loss)]
+                let (x0, x1, y0, y1) = (
+                    start as f64 * resolution,
+                    column as f64 * resolution,
+                    row as f64 * resolution,
+                    (row + 1) as f64 * resolution,
+                );
+                self = self.with_polygon(name, &[(x0, y0), (x1, y0), (x1, y1), (x0, y1)]);
+            }
+        }
+        self
+    }
+
+    /// Returns the number of features.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    /// Returns whether there are no features.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Writes the collection as `GeoJSON` on a single line.
+    ///
+    /// # Errors
+    ///
+    /// If writing fails, or a position is not finite.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut json = String::from("{\"type\":\"FeatureCollection\",\"features\":[");
+        for (i, feature) in self.features.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let (kind, coordinates) = match &feature.geometry {
+                Geometry::Point(point) => ("Point", self.position(*point)?),
+                Geometry::LineString(points) => ("LineString", self.positions(points)?),
+                Geometry::Polygon(ring) => ("Polygon", format!("[{}]", self.positions(ring)?)),
+            };
+            let _ = write!(
+                json,
+                "{{\"type\":\"Feature\",\"properties\":{{\"name\":{},\"kind\":\"{}\"}},\
+                 \"geometry\":{{\"type\":\"{kind}\",\"coordinates\":{coordinates}}}}}",
+                json_string(&feature.name),
+                feature.kind
+            );
+        }
+        json.push_str("]}");
+        writer.write_all(json.as_bytes())
+    }
+
+    fn with_feature(mut self, name: &str, kind: &'static str, geometry: Geometry) -> Self {
+        self.features.push(Feature {
+            name: name.to_string(),
+            kind,
+            geometry,
+        });
+        self
+    }
+
+    // Formats a position as a JSON array, converted to longitude and latitude if georeferenced
+    fn position(&self, (x, y): (f64, f64)) -> io::Result<String> {
+        let (x, y) = self.origin.map_or((x, y), |origin| origin.to_lon_lat(x, y));
+        if !x.is_finite() || !y.is_finite() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Position ({x}, {y}) is not finite"),
+            ));
+        }
+        Ok(format!("[{x},{y}]"))
+    }
+
+    fn positions(&self, points: &[(f64, f64)]) -> io::Result<String> {
+        let positions: io::Result<Vec<String>> =
+            points.iter().map(|&point| self.position(point)).collect();
+        Ok(format!("[{}]", positions?.join(",")))
+    }
+}
+
+// Twice the signed area of a polygon, positive if wound counterclockwise
+fn signed_area(vertices: &[(f64, f64)]) -> f64 {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum()
+}
+
+// Quotes and escapes a string for JSON
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_string(collection: &FeatureCollection) -> String {
+        let mut json = Vec::new();
+        collection.write(&mut json).unwrap();
+        String::from_utf8(json).unwrap()
+    }
+
+    #[test]
+    fn test_feature_collection() {
+        assert_eq!(
+            to_string(&FeatureCollection::new()),
+            "{\"type\":\"FeatureCollection\",\"features\":[]}"
+        );
+
+        // Clockwise polygons are reversed and closed
+        let collection = FeatureCollection::new()
+            .with_path("route \"a\"", &[(0.0, 0.0), (1.5, -2.0)])
+            .with_point("goal", (1.5, -2.0))
+            .with_polygon("rock", &[(0.0, 0.0), (0.0, 1.0), (1.0, 0.0)]);
+        assert_eq!(collection.len(), 3);
+        assert_eq!(
+            to_string(&collection),
+            "{\"type\":\"FeatureCollection\",\"features\":[\
+             {\"type\":\"Feature\",\"properties\":{\"name\":\"route \\\"a\\\"\",\"kind\":\"path\"},\
+             \"geometry\":{\"type\":\"LineString\",\"coordinates\":[[0,0],[1.5,-2]]}},\
+             {\"type\":\"Feature\",\"properties\":{\"name\":\"goal\",\"kind\":\"point\"},\
+             \"geometry\":{\"type\":\"Point\",\"coordinates\":[1.5,-2]}},\
+             {\"type\":\"Feature\",\"properties\":{\"name\":\"rock\",\"kind\":\"obstacle\"},\
+             \"geometry\":{\"type\":\"Polygon\",\"coordinates\":[[[1,0],[0,1],[0,0],[1,0]]]}}]}"
+        );
+
+        let invalid = FeatureCollection::new().with_path("bad", &[(0.0, f64::NAN)]);
+        let error = invalid.write(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(json_string("a\u{1}\n"), "\"a\\u0001\\n\"");
+    }
+
+    #[test]
+    fn test_regions_and_grids() {
+        let collection = FeatureCollection::new()
+            .with_region(
+                "box",
+                &Region::Box {
+                    min: vec![0.0, 0.0, 0.0],
+                    max: vec![2.0, 1.0, 5.0],
+                },
+            )
+            .with_region(
+                "ball",
+                &Region::Sphere {
+                    center: vec![1.0, 1.0],
+                    radius: 1.0,
+                },
+            )
+            .with_region(
+                "line",
+                &Region::Box {
+                    min: vec![0.0],
+                    max: vec![1.0],
+                },
+            );
+        assert_eq!(collection.len(), 2);
+        let json = to_string(&collection);
+        assert!(json.contains("[[[0,0],[2,0],[2,1],[0,1],[0,0]]]"));
+
+        // Two runs in the bottom row and one in the top
+        let mut grid = OccupancyGrid::new(4, 2, 0.5);
+        for (column, row) in [(0, 0), (1, 0), (3, 0), (2, 1)] {
+            grid.set_occupied(column, row, true).unwrap();
+        }
+        grid.set_state(3, 1, CellState::Unknown).unwrap();
+        let collection = FeatureCollection::new().with_grid("wall", &grid);
+        assert_eq!(collection.len(), 3);
+        let json = to_string(&collection);
+        assert!(json.contains("[[[0,0],[1,0],[1,0.5],[0,0.5],[0,0]]]"));
+        assert!(json.contains("[[[1.5,0],[2,0],[2,0.5],[1.5,0.5],[1.5,0]]]"));
+        assert!(json.contains("[[[1,0.5],[1.5,0.5],[1.5,1],[1,1],[1,0.5]]]"));
+    }
+
+    #[test]
+    fn test_georeferencing() {
+        let origin = GeoOrigin {
+            latitude: 60.0,
+            longitude: 10.0,
+        };
+        assert_eq!(origin.to_lon_lat(0.0, 0.0), (10.0, 60.0));
+
+        // A degree of latitude is about 111km, and half that in longitude at 60 degrees
+        let (longitude, latitude) = origin.to_lon_lat(55_660.0, 111_320.0);
+        assert!((latitude - 61.0).abs() < 1e-3);
+        assert!((longitude - 11.0).abs() < 1e-3);
+
+        let json = to_string(
+            &FeatureCollection::new()
+                .with_origin(origin)
+                .with_point("start", (0.0, 0.0)),
+        );
+        assert!(json.contains("\"coordinates\":[10,60]"));
+    }
+}
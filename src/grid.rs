@@ -0,0 +1,946 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::bounds::Bounds;
+use crate::error::GridError;
+use crate::float::check_resolution;
+/// The known state of a cell in an [`OccupancyGrid`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellState {
+    Free,
+    Occupied,
+
+    /// The cell has not been observed, e.g. it has not been seen by a SLAM system yet.
+    Unknown,
+}
+
+/// Whether planning queries on an [`OccupancyGrid`] may pass through unknown cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum UnknownSpacePolicy {
+    /// Unknown cells are treated as obstacles.
+    #[default]
+    Blocked,
+
+    /// Unknown cells are treated as free, e.g. for optimistic exploration.
+    Traversable,
+}
+
+/// A 2D laser scan, e.g. from a planar lidar.
+///
+/// Beam `i` was measured at `angles[i]` radians relative to the sensor's heading and
+/// returned `ranges[i]`. Ranges that are not finite or are at least `max_range` are treated
+/// as beams that did not hit anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaserScan {
+    pub angles: Vec<f64>,
+    pub ranges: Vec<f64>,
+    pub max_range: f64,
+}
+
+impl LaserScan {
+    /// Returns the world coordinate each beam ends at for a sensor at `pose`, given as
+    /// `(x, y, heading)`, along with whether the beam hit an obstacle. Beams without a hit
+    /// end at the maximum range.
+    pub fn endpoints(
+        &self,
+        pose: (f64, f64, f64),
+    ) -> impl Iterator<Item = ((f64, f64), bool)> + '_ {
+        let (x, y, heading) = pose;
+        self.angles
+            .iter()
+            .zip(&self.ranges)
+            .map(move |(angle, &range)| {
+                let is_hit = range.is_finite() && range < self.max_range;
+                let range = if is_hit { range } else { self.max_range };
+                let angle = heading + angle;
+                ((x + range * angle.cos(), y + range * angle.sin()), is_hit)
+            })
+    }
+}
+
+/// A 2D occupancy grid over the plane.
+///
+/// The grid covers `width` by `height` square cells of side length `resolution`, with cell
+/// `(0, 0)` occupying `[0, resolution) x [0, resolution)` in world coordinates. Cells are
+/// addressed by `(column, row)`, where columns increase along x and rows along y.
+///
+/// The world coordinate queries used for planning, [`OccupancyGrid::is_free`] and
+/// [`OccupancyGrid::is_segment_free`], respect the grid's [`UnknownSpacePolicy`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OccupancyGrid {
+    width: usize,
+    height: usize,
+    resolution: f64,
+    cells: Vec<CellState>,
+    unknown_policy: UnknownSpacePolicy,
+}
+
+impl OccupancyGrid {
+    /// Constructs an entirely free grid.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    #[must_use]
+    pub fn new(width: usize, height: usize, resolution: f64) -> Self {
+        Self::filled(width, height, resolution, CellState::Free)
+    }
+
+    /// Constructs a grid with every cell in the specified state.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    #[must_use]
+    pub fn filled(width: usize, height: usize, resolution: f64, state: CellState) -> Self {
+        check_resolution(resolution);
+        OccupancyGrid {
+            width,
+            height,
+            resolution,
+            cells: vec![state; width * height],
+            unknown_policy: UnknownSpacePolicy::default(),
+        }
+    }
+
+    /// Sets whether planning queries may traverse unknown cells.
+    #[must_use]
+    pub fn with_unknown_policy(mut self, policy: UnknownSpacePolicy) -> Self {
+        self.unknown_policy = policy;
+        self
+    }
+
+    /// Returns the policy for traversing unknown cells.
+    #[must_use]
+    pub fn unknown_policy(&self) -> UnknownSpacePolicy {
+        self.unknown_policy
+    }
+
+    /// Number of columns in the grid.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows in the grid.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Side length of a cell in world units.
+    #[must_use]
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    /// Returns the extent of the grid in world coordinates.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds<2> {
+        #[allow(clippy::cast_precision_loss)]
+        let upper = [
+            self.width as f64 * self.resolution,
+            self.height as f64 * self.resolution,
+        ];
+        Bounds {
+            lower: [0.0, 0.0],
+            upper,
+        }
+    }
+
+    fn index(&self, column: usize, row: usize) -> Option<usize> {
+        (column < self.width && row < self.height).then(|| row * self.width + column)
+    }
+
+    /// Returns the state of the cell, if it is within the grid.
+    #[must_use]
+    pub fn state(&self, column: usize, row: usize) -> Option<CellState> {
+        self.index(column, row).map(|i| self.cells[i])
+    }
+
+    /// Sets the state of a cell.
+    ///
+    /// # Errors
+    ///
+    /// If the cell is outside the grid.
+    pub fn set_state(
+        &mut self,
+        column: usize,
+        row: usize,
+        state: CellState,
+    ) -> Result<(), GridError> {
+        let i = self.index(column, row).ok_or(GridError::OutOfBounds)?;
+        self.cells[i] = state;
+        Ok(())
+    }
+
+    /// Returns true if the cell is occupied, cells outside the grid are considered occupied.
+    #[must_use]
+    pub fn is_occupied(&self, column: usize, row: usize) -> bool {
+        self.state(column, row)
+            .is_none_or(|state| state == CellState::Occupied)
+    }
+
+    /// Returns true if the cell is within the grid and known to be free.
+    #[must_use]
+    pub fn is_cell_free(&self, column: usize, row: usize) -> bool {
+        self.state(column, row) == Some(CellState::Free)
+    }
+
+    /// Returns true if the planner may pass through the cell, which depends on the
+    /// [`UnknownSpacePolicy`] for unknown cells. Cells outside the grid are never traversable.
+    #[must_use]
+    pub fn is_traversable(&self, column: usize, row: usize) -> bool {
+        match self.state(column, row) {
+            Some(CellState::Free) => true,
+            Some(CellState::Unknown) => self.unknown_policy == UnknownSpacePolicy::Traversable,
+            Some(CellState::Occupied) | None => false,
+        }
+    }
+
+    /// Marks a cell as occupied or free.
+    ///
+    /// # Errors
+    ///
+    /// If the cell is outside the grid.
+    pub fn set_occupied(
+        &mut self,
+        column: usize,
+        row: usize,
+        occupied: bool,
+    ) -> Result<(), GridError> {
+        let state = if occupied {
+            CellState::Occupied
+        } else {
+            CellState::Free
+        };
+        self.set_state(column, row, state)
+    }
+
+    /// Returns the cell containing the world coordinate, if it is within the grid.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let (column, row) = (
+            (x / self.resolution).floor() as usize,
+            (y / self.resolution).floor() as usize,
+        );
+        self.index(column, row).map(|_| (column, row))
+    }
+
+    /// Returns the world coordinate of the center of a cell.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cell_center(&self, column: usize, row: usize) -> (f64, f64) {
+        (
+            (column as f64 + 0.5) * self.resolution,
+            (row as f64 + 0.5) * self.resolution,
+        )
+    }
+
+    /// Returns true if the world coordinate lies in a traversable cell within the grid.
+    #[must_use]
+    pub fn is_free(&self, x: f64, y: f64) -> bool {
+        self.cell_at(x, y)
+            .is_some_and(|(column, row)| self.is_traversable(column, row))
+    }
+
+    // World coordinates along the segment at intervals of at most half the resolution
+    fn segment_points(&self, from: (f64, f64), to: (f64, f64)) -> impl Iterator<Item = (f64, f64)> {
+        segment_points(from, to, self.resolution)
+    }
+
+    /// Returns true if the straight line between two world coordinates only passes
+    /// through traversable cells.
+    ///
+    /// The line is checked at intervals of half the resolution, which is suitable for use in
+    /// a planner's connectable function.
+    #[must_use]
+    pub fn is_segment_free(&self, from: (f64, f64), to: (f64, f64)) -> bool {
+        self.segment_points(from, to)
+            .all(|(x, y)| self.is_free(x, y))
+    }
+
+    /// Inserts a laser scan taken from `pose` into the grid.
+    ///
+    /// Cells along every beam are marked free. Beams that returned within the scan's maximum
+    /// range mark the cell they hit as occupied, along with every cell whose center is within
+    /// `inflation` of the hit, e.g. the robot radius. Free space is cleared for the whole scan
+    /// before any hits are marked, so beams cannot erase obstacles seen in the same scan.
+    /// Portions of the scan outside the grid are ignored.
+    pub fn insert_scan(&mut self, scan: &LaserScan, pose: (f64, f64, f64), inflation: f64) {
+        let origin = (pose.0, pose.1);
+        for (endpoint, _) in scan.endpoints(pose) {
+            let hit = self.cell_at(endpoint.0, endpoint.1);
+            let free: Vec<(usize, usize)> = self
+                .segment_points(origin, endpoint)
+                .filter_map(|(x, y)| self.cell_at(x, y))
+                .filter(|&cell| Some(cell) != hit)
+                .collect();
+            for (column, row) in free {
+                let _ = self.set_state(column, row, CellState::Free);
+            }
+        }
+
+        for ((x, y), _) in scan.endpoints(pose).filter(|(_, is_hit)| *is_hit) {
+            self.occupy_disc(x, y, inflation);
+        }
+    }
+
+    // Marks the cell containing the point, and every cell whose center is within the radius
+    // of it, as occupied
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn occupy_disc(&mut self, x: f64, y: f64, radius: f64) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        // Clamp the search window to the grid
+        let clamp = |v: f64, n: usize| ((v / self.resolution).floor().max(0.0) as usize).min(n - 1);
+        let (first_column, last_column) =
+            (clamp(x - radius, self.width), clamp(x + radius, self.width));
+        let (first_row, last_row) = (
+            clamp(y - radius, self.height),
+            clamp(y + radius, self.height),
+        );
+
+        let hit = self.cell_at(x, y);
+        for row in first_row..=last_row {
+            for column in first_column..=last_column {
+                let (cx, cy) = self.cell_center(column, row);
+                let within = (cx - x).powi(2) + (cy - y).powi(2) <= radius * radius;
+                if within || hit == Some((column, row)) {
+                    let _ = self.set_state(column, row, CellState::Occupied);
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of the grid with every occupied cell grown by `radius`.
+    ///
+    /// Cells whose centers are within `radius` of an occupied cell's center become occupied,
+    /// so planning for a point in the inflated grid keeps a disc shaped robot of that radius
+    /// clear of obstacles. Inflating once up front is much cheaper than checking clearance
+    /// for every query.
+    #[must_use]
+    pub fn inflated(&self, radius: f64) -> Self {
+        let mut inflated = self.clone();
+        for row in 0..self.height {
+            for column in 0..self.width {
+                if self.state(column, row) == Some(CellState::Occupied) {
+                    let (x, y) = self.cell_center(column, row);
+                    inflated.occupy_disc(x, y, radius);
+                }
+            }
+        }
+        inflated
+    }
+
+    /// Returns the known free cell whose center is closest to the world coordinate, which
+    /// may be outside of the grid. Ties go to the first cell in row major order.
+    ///
+    /// Useful for snapping a start or goal that lies in unknown or occupied space.
+    #[must_use]
+    pub fn nearest_free_cell(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        let squared_distance = |(column, row): (usize, usize)| {
+            let (cx, cy) = self.cell_center(column, row);
+            (cx - x).powi(2) + (cy - y).powi(2)
+        };
+        (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |column| (column, row)))
+            .filter(|&(column, row)| self.is_cell_free(column, row))
+            .min_by(|&a, &b| squared_distance(a).total_cmp(&squared_distance(b)))
+    }
+}
+
+/// World coordinates along the segment at intervals of at most half the resolution.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn segment_points(
+    from: (f64, f64),
+    to: (f64, f64),
+    resolution: f64,
+) -> impl Iterator<Item = (f64, f64)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let steps = (length / (resolution / 2.0)).ceil() as usize;
+    (0..=steps).map(move |i| {
+        let t = if steps == 0 {
+            0.0
+        } else {
+            i as f64 / steps as f64
+        };
+        (from.0 + t * dx, from.1 + t * dy)
+    })
+}
+
+/// A 2D grid of occupancy probabilities, e.g. from a mapping system fusing noisy sensors.
+///
+/// Uses the same layout as [`OccupancyGrid`]. Rather than thresholding the map, planners
+/// can bound the probability of collision along each motion, assuming cells are occupied
+/// independently. Everything outside the grid is treated as certainly occupied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbabilityGrid {
+    width: usize,
+    height: usize,
+    resolution: f64,
+    probabilities: Vec<f64>,
+}
+
+impl ProbabilityGrid {
+    /// Constructs a grid with every cell occupied with probability `prior`.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive, or the prior is not a probability.
+    #[must_use]
+    pub fn new(width: usize, height: usize, resolution: f64, prior: f64) -> Self {
+        check_resolution(resolution);
+        assert!((0.0..=1.0).contains(&prior), "Prior must be a probability");
+        ProbabilityGrid {
+            width,
+            height,
+            resolution,
+            probabilities: vec![prior; width * height],
+        }
+    }
+
+    /// Number of columns in the grid.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows in the grid.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Side length of each cell in world units.
+    #[must_use]
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    fn index(&self, column: usize, row: usize) -> Option<usize> {
+        (column < self.width && row < self.height).then(|| row * self.width + column)
+    }
+
+    /// Returns the probability that the cell is occupied, if it is within the grid.
+    #[must_use]
+    pub fn probability(&self, column: usize, row: usize) -> Option<f64> {
+        self.index(column, row).map(|i| self.probabilities[i])
+    }
+
+    /// Sets the probability that a cell is occupied.
+    ///
+    /// # Errors
+    ///
+    /// If the cell is outside the grid or the value is not a probability.
+    pub fn set_probability(
+        &mut self,
+        column: usize,
+        row: usize,
+        probability: f64,
+    ) -> Result<(), GridError> {
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(GridError::InvalidProbability(probability));
+        }
+        let i = self.index(column, row).ok_or(GridError::OutOfBounds)?;
+        self.probabilities[i] = probability;
+        Ok(())
+    }
+
+    /// Returns the cell containing the world coordinate, if it is within the grid.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let (column, row) = (
+            (x / self.resolution).floor() as usize,
+            (y / self.resolution).floor() as usize,
+        );
+        self.index(column, row).map(|_| (column, row))
+    }
+
+    /// Returns the probability that the world coordinate is occupied.
+    #[must_use]
+    pub fn probability_at(&self, x: f64, y: f64) -> f64 {
+        self.cell_at(x, y)
+            .and_then(|(column, row)| self.probability(column, row))
+            .unwrap_or(1.0)
+    }
+
+    /// Returns the probability of a collision anywhere along a polyline of world
+    /// coordinates, counting each cell it passes through once.
+    #[must_use]
+    pub fn path_risk(&self, path: &[(f64, f64)]) -> f64 {
+        let mut visited = Vec::new();
+        let points = path
+            .windows(2)
+            .flat_map(|pair| segment_points(pair[0], pair[1], self.resolution))
+            .chain(path.first().copied());
+        let mut survival = 1.0;
+        for (x, y) in points {
+            let Some(cell) = self.cell_at(x, y) else {
+                return 1.0;
+            };
+            if !visited.contains(&cell) {
+                visited.push(cell);
+                survival *= 1.0 - self.probability(cell.0, cell.1).unwrap_or(1.0);
+            }
+        }
+        1.0 - survival
+    }
+
+    /// Returns the probability of a collision along the straight line between two world
+    /// coordinates.
+    #[must_use]
+    pub fn segment_risk(&self, from: (f64, f64), to: (f64, f64)) -> f64 {
+        self.path_risk(&[from, to])
+    }
+
+    /// Returns true if the straight line between two world coordinates is collision free
+    /// with at least the given confidence, e.g. `0.99`. Suitable for use in a planner's
+    /// connectable function as a chance constraint on each motion.
+    #[must_use]
+    pub fn is_segment_safe(&self, from: (f64, f64), to: (f64, f64), confidence: f64) -> bool {
+        self.segment_risk(from, to) <= 1.0 - confidence
+    }
+
+    /// Converts to an occupancy grid, with cells at least `threshold` likely to be
+    /// occupied marked as occupied and the rest as free.
+    #[must_use]
+    pub fn thresholded(&self, threshold: f64) -> OccupancyGrid {
+        let mut grid = OccupancyGrid::new(self.width, self.height, self.resolution);
+        for (i, &probability) in self.probabilities.iter().enumerate() {
+            if probability >= threshold {
+                grid.cells[i] = CellState::Occupied;
+            }
+        }
+        grid
+    }
+}
+
+/// A 2D grid of terrain traversal costs, e.g. for outdoor robots that prefer pavement to
+/// grass and grass to mud.
+///
+/// Uses the same layout as [`OccupancyGrid`]. Each cell holds a positive cost per unit
+/// distance travelled through it, with infinite costs marking impassable cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainGrid {
+    width: usize,
+    height: usize,
+    resolution: f64,
+    costs: Vec<f64>,
+}
+
+impl TerrainGrid {
+    /// Constructs a grid with every cell having the same cost.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive, or the cost is not positive.
+    #[must_use]
+    pub fn new(width: usize, height: usize, resolution: f64, cost: f64) -> Self {
+        check_resolution(resolution);
+        assert!(cost > 0.0, "Terrain costs must be positive");
+        TerrainGrid {
+            width,
+            height,
+            resolution,
+            costs: vec![cost; width * height],
+        }
+    }
+
+    /// Constructs a grid from an occupancy grid, with traversable cells having the given
+    /// cost and all others impassable.
+    ///
+    /// # Panics
+    ///
+    /// If the cost is not positive.
+    #[must_use]
+    pub fn from_occupancy(grid: &OccupancyGrid, cost: f64) -> Self {
+        let mut terrain = Self::new(grid.width, grid.height, grid.resolution, cost);
+        for row in 0..grid.height {
+            for column in 0..grid.width {
+                if !grid.is_traversable(column, row) {
+                    terrain.costs[row * grid.width + column] = f64::INFINITY;
+                }
+            }
+        }
+        terrain
+    }
+
+    /// Number of columns in the grid.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows in the grid.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Side length of each cell in world units.
+    #[must_use]
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    fn index(&self, column: usize, row: usize) -> Option<usize> {
+        (column < self.width && row < self.height).then(|| row * self.width + column)
+    }
+
+    /// Returns the cost of the cell, if it is within the grid.
+    #[must_use]
+    pub fn cost(&self, column: usize, row: usize) -> Option<f64> {
+        self.index(column, row).map(|i| self.costs[i])
+    }
+
+    /// Sets the cost of a cell, use infinity to make it impassable.
+    ///
+    /// # Errors
+    ///
+    /// If the cell is outside the grid or the cost is not positive.
+    pub fn set_cost(&mut self, column: usize, row: usize, cost: f64) -> Result<(), GridError> {
+        if cost.is_nan() || cost <= 0.0 {
+            return Err(GridError::InvalidCost(cost));
+        }
+        let i = self.index(column, row).ok_or(GridError::OutOfBounds)?;
+        self.costs[i] = cost;
+        Ok(())
+    }
+
+    /// Returns true if the cell is within the grid and passable.
+    #[must_use]
+    pub fn is_traversable(&self, column: usize, row: usize) -> bool {
+        self.cost(column, row).is_some_and(f64::is_finite)
+    }
+
+    /// Returns the cell containing the world coordinate, if it is within the grid.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let (column, row) = (
+            (x / self.resolution).floor() as usize,
+            (y / self.resolution).floor() as usize,
+        );
+        self.index(column, row).map(|_| (column, row))
+    }
+
+    /// Returns the cost at the world coordinate, which is infinite outside the grid.
+    #[must_use]
+    pub fn cost_at(&self, x: f64, y: f64) -> f64 {
+        self.cell_at(x, y)
+            .and_then(|(column, row)| self.cost(column, row))
+            .unwrap_or(f64::INFINITY)
+    }
+
+    /// Returns the terrain cost of the straight line between two world coordinates, its
+    /// length weighted by the cost of the cells it passes through. The line is sampled at
+    /// intervals of half the resolution, so this is suitable for use as an edge cost.
+    #[must_use]
+    pub fn segment_cost(&self, from: (f64, f64), to: (f64, f64)) -> f64 {
+        let costs: Vec<f64> = segment_points(from, to, self.resolution)
+            .map(|(x, y)| self.cost_at(x, y))
+            .collect();
+        if costs.len() < 2 {
+            return 0.0;
+        }
+
+        // Trapezoidal integration over the evenly spaced samples
+        let length = (to.0 - from.0).hypot(to.1 - from.1);
+        #[allow(clippy::cast_precision_loss)]
+        let step = length / (costs.len() - 1) as f64;
+        costs
+            .windows(2)
+            .map(|pair| step * f64::midpoint(pair[0], pair[1]))
+            .sum()
+    }
+
+    /// Returns the total terrain cost along a polyline of world coordinates.
+    #[must_use]
+    pub fn path_cost(&self, path: &[(f64, f64)]) -> f64 {
+        path.windows(2)
+            .map(|pair| self.segment_cost(pair[0], pair[1]))
+            .sum()
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn test_occupancy() {
+        let mut grid = OccupancyGrid::new(4, 3, 0.5);
+        assert!(!grid.is_occupied(3, 2));
+        assert!(grid.is_occupied(4, 0));
+        assert!(grid.is_occupied(0, 3));
+
+        grid.set_occupied(1, 2, true).unwrap();
+        assert!(grid.is_occupied(1, 2));
+        assert_eq!(grid.set_occupied(4, 2, true), Err(GridError::OutOfBounds));
+    }
+
+    #[test]
+    fn test_unknown_cells() {
+        let mut grid = OccupancyGrid::filled(2, 2, 1.0, CellState::Unknown);
+        assert_eq!(grid.state(1, 1), Some(CellState::Unknown));
+        assert_eq!(grid.state(2, 1), None);
+        assert!(!grid.is_occupied(1, 1));
+        assert!(!grid.is_cell_free(1, 1));
+        assert!(!grid.is_free(1.5, 1.5));
+
+        grid.set_state(1, 1, CellState::Free).unwrap();
+        assert!(grid.is_cell_free(1, 1));
+        assert_eq!(
+            grid.set_state(1, 2, CellState::Free),
+            Err(GridError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_unknown_space_policy() {
+        let mut grid = OccupancyGrid::filled(4, 1, 1.0, CellState::Unknown);
+        grid.set_state(0, 0, CellState::Free).unwrap();
+        grid.set_state(3, 0, CellState::Occupied).unwrap();
+        assert_eq!(grid.unknown_policy(), UnknownSpacePolicy::Blocked);
+        assert!(grid.is_traversable(0, 0));
+        assert!(!grid.is_traversable(1, 0));
+        assert!(!grid.is_segment_free((0.5, 0.5), (2.5, 0.5)));
+
+        let grid = grid.with_unknown_policy(UnknownSpacePolicy::Traversable);
+        assert!(grid.is_traversable(1, 0));
+        assert!(!grid.is_traversable(3, 0));
+        assert!(!grid.is_traversable(4, 0));
+        assert!(grid.is_segment_free((0.5, 0.5), (2.5, 0.5)));
+        assert!(!grid.is_segment_free((0.5, 0.5), (3.5, 0.5)));
+
+        // Traversable unknown space is still not known to be free
+        assert!(!grid.is_cell_free(1, 0));
+    }
+
+    #[test]
+    fn test_scan_endpoints() {
+        let scan = LaserScan {
+            angles: vec![0.0, std::f64::consts::FRAC_PI_2],
+            ranges: vec![2.0, f64::INFINITY],
+            max_range: 3.0,
+        };
+        let endpoints: Vec<((f64, f64), bool)> = scan.endpoints((1.0, 1.0, 0.0)).collect();
+        assert!(approx_eq!(f64, endpoints[0].0 .0, 3.0));
+        assert!(approx_eq!(f64, endpoints[0].0 .1, 1.0));
+        assert!(endpoints[0].1);
+        assert!(approx_eq!(f64, endpoints[1].0 .0, 1.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, endpoints[1].0 .1, 4.0));
+        assert!(!endpoints[1].1);
+    }
+
+    #[test]
+    fn test_insert_scan() {
+        // A sensor in the bottom left facing along x, the first beam hits at x = 3.5 and the
+        // second beam along y does not hit anything.
+        let scan = LaserScan {
+            angles: vec![0.0, std::f64::consts::FRAC_PI_2],
+            ranges: vec![3.0, 10.0],
+            max_range: 5.0,
+        };
+        let mut grid = OccupancyGrid::filled(5, 5, 1.0, CellState::Unknown);
+        grid.insert_scan(&scan, (0.5, 0.5, 0.0), 0.0);
+        for column in 0..3 {
+            assert_eq!(grid.state(column, 0), Some(CellState::Free));
+        }
+        assert_eq!(grid.state(3, 0), Some(CellState::Occupied));
+        assert_eq!(grid.state(4, 0), Some(CellState::Unknown));
+        for row in 1..5 {
+            assert_eq!(grid.state(0, row), Some(CellState::Free));
+        }
+        assert_eq!(grid.state(1, 1), Some(CellState::Unknown));
+
+        // Inflation marks the neighboring cells, overriding the cleared beam
+        let mut grid = OccupancyGrid::filled(5, 5, 1.0, CellState::Unknown);
+        grid.insert_scan(&scan, (0.5, 0.5, 0.0), 1.0);
+        assert_eq!(grid.state(2, 0), Some(CellState::Occupied));
+        assert_eq!(grid.state(4, 0), Some(CellState::Occupied));
+        assert_eq!(grid.state(3, 1), Some(CellState::Occupied));
+        assert_eq!(grid.state(2, 1), Some(CellState::Unknown));
+        assert_eq!(grid.state(1, 0), Some(CellState::Free));
+    }
+
+    #[test]
+    fn test_inflated() {
+        let mut grid = OccupancyGrid::filled(5, 5, 1.0, CellState::Unknown);
+        grid.set_state(2, 2, CellState::Occupied).unwrap();
+        grid.set_state(0, 0, CellState::Occupied).unwrap();
+
+        let inflated = grid.inflated(1.0);
+        let occupied: Vec<(usize, usize)> = (0..5)
+            .flat_map(|row| (0..5).map(move |column| (column, row)))
+            .filter(|&(column, row)| inflated.state(column, row) == Some(CellState::Occupied))
+            .collect();
+        assert_eq!(
+            occupied,
+            vec![
+                (0, 0),
+                (1, 0),
+                (0, 1),
+                (2, 1),
+                (1, 2),
+                (2, 2),
+                (3, 2),
+                (2, 3)
+            ]
+        );
+        assert_eq!(inflated.state(1, 1), Some(CellState::Unknown));
+        assert_eq!(grid.inflated(0.0), grid);
+    }
+
+    #[test]
+    fn test_nearest_free_cell() {
+        let mut grid = OccupancyGrid::filled(4, 4, 1.0, CellState::Unknown);
+        assert_eq!(grid.nearest_free_cell(1.5, 1.5), None);
+
+        grid.set_state(3, 0, CellState::Free).unwrap();
+        grid.set_state(0, 3, CellState::Free).unwrap();
+        grid.set_state(2, 2, CellState::Occupied).unwrap();
+        assert_eq!(grid.nearest_free_cell(2.5, 2.5), Some((3, 0)));
+        assert_eq!(grid.nearest_free_cell(0.0, 5.0), Some((0, 3)));
+
+        grid.set_state(2, 1, CellState::Free).unwrap();
+        assert_eq!(grid.nearest_free_cell(2.5, 2.5), Some((2, 1)));
+    }
+
+    #[test]
+    fn test_coordinates() {
+        let mut grid = OccupancyGrid::new(4, 3, 0.5);
+        assert_eq!(grid.cell_at(0.0, 0.0), Some((0, 0)));
+        assert_eq!(grid.cell_at(1.9, 1.4), Some((3, 2)));
+        assert_eq!(grid.cell_at(2.0, 1.0), None);
+        assert_eq!(grid.cell_at(-0.1, 1.0), None);
+        assert_eq!(grid.cell_center(1, 2), (0.75, 1.25));
+        assert_eq!(grid.bounds().upper().to_vec(), vec![2.0, 1.5]);
+        assert!(grid.bounds().contains(&[1.9, 1.4]));
+
+        grid.set_occupied(1, 1, true).unwrap();
+        assert!(grid.is_free(0.75, 0.25));
+        assert!(!grid.is_free(0.6, 0.6));
+        assert!(!grid.is_free(5.0, 0.0));
+    }
+
+    #[test]
+    fn test_segment_free() {
+        let mut grid = OccupancyGrid::new(4, 4, 1.0);
+        grid.set_occupied(2, 1, true).unwrap();
+        assert!(grid.is_segment_free((0.5, 0.5), (3.5, 0.5)));
+        assert!(!grid.is_segment_free((0.5, 1.5), (3.5, 1.5)));
+        assert!(grid.is_segment_free((0.5, 0.5), (0.5, 0.5)));
+        assert!(!grid.is_segment_free((0.5, 0.5), (4.5, 0.5)));
+    }
+
+    #[test]
+    fn test_probability_grid() {
+        let mut grid = ProbabilityGrid::new(4, 4, 1.0, 0.0);
+        assert!(grid.set_probability(1, 0, 0.5).is_ok());
+        assert!(grid.set_probability(2, 0, 0.5).is_ok());
+        assert!(grid.set_probability(0, 3, 0.01).is_ok());
+        assert_eq!(grid.set_probability(4, 0, 0.5), Err(GridError::OutOfBounds));
+        assert_eq!(
+            grid.set_probability(0, 0, 1.5),
+            Err(GridError::InvalidProbability(1.5))
+        );
+        assert!((grid.probability_at(1.5, 0.5) - 0.5).abs() < 1e-12);
+        assert!((grid.probability_at(-1.0, 0.5) - 1.0).abs() < 1e-12);
+
+        // Two independent cells that are each occupied half the time
+        let risk = grid.segment_risk((0.5, 0.5), (3.5, 0.5));
+        assert!((risk - 0.75).abs() < 1e-12);
+        assert!(grid.segment_risk((0.5, 1.5), (3.5, 1.5)).abs() < 1e-12);
+        assert!((grid.segment_risk((0.5, 0.5), (5.5, 0.5)) - 1.0).abs() < 1e-12);
+
+        // Cells are only counted once, even where segments meet
+        let risk = grid.path_risk(&[(0.5, 1.5), (1.5, 0.5), (1.5, 1.5)]);
+        assert!((risk - 0.5).abs() < 1e-12);
+        assert!((grid.path_risk(&[(1.5, 0.5)]) - 0.5).abs() < 1e-12);
+        assert!(grid.path_risk(&[]).abs() < 1e-12);
+
+        assert!(grid.is_segment_safe((0.5, 2.5), (0.5, 3.5), 0.95));
+        assert!(!grid.is_segment_safe((0.5, 2.5), (0.5, 3.5), 0.999));
+
+        let occupancy = grid.thresholded(0.5);
+        assert!(occupancy.is_occupied(1, 0));
+        assert!(occupancy.is_occupied(2, 0));
+        assert!(!occupancy.is_occupied(0, 3));
+    }
+
+    #[test]
+    fn test_terrain_grid() {
+        let mut terrain = TerrainGrid::new(4, 2, 1.0, 1.0);
+        assert!(terrain.set_cost(2, 0, 3.0).is_ok());
+        assert!(terrain.set_cost(3, 1, f64::INFINITY).is_ok());
+        assert_eq!(
+            terrain.set_cost(0, 0, 0.0),
+            Err(GridError::InvalidCost(0.0))
+        );
+        assert_eq!(terrain.set_cost(4, 0, 1.0), Err(GridError::OutOfBounds));
+        assert!(terrain.is_traversable(2, 0));
+        assert!(!terrain.is_traversable(3, 1));
+        assert!(!terrain.is_traversable(4, 0));
+        assert!((terrain.cost_at(2.5, 0.5) - 3.0).abs() < 1e-12);
+        assert!(terrain.cost_at(-0.5, 0.5).is_infinite());
+
+        // Uniform terrain costs the length of the segment
+        assert!((terrain.segment_cost((0.5, 1.5), (2.5, 1.5)) - 2.0).abs() < 1e-9);
+        assert!(terrain.segment_cost((0.5, 0.5), (0.5, 0.5)).abs() < 1e-12);
+
+        // Mud in the way costs more, and impassable cells cost infinitely much
+        let cost = terrain.segment_cost((0.5, 0.5), (3.5, 0.5));
+        assert!(cost > 3.0 && cost < 9.0, "Unexpected cost {cost}");
+        assert!(terrain
+            .path_cost(&[(0.5, 0.5), (0.5, 1.5), (3.5, 1.5)])
+            .is_infinite());
+        assert!((terrain.path_cost(&[(0.5, 0.5), (0.5, 1.5), (2.5, 1.5)]) - 3.0).abs() < 1e-9);
+
+        let mut grid = OccupancyGrid::new(2, 1, 1.0);
+        grid.set_state(1, 0, CellState::Occupied).unwrap();
+        let terrain = TerrainGrid::from_occupancy(&grid, 2.0);
+        assert_eq!(terrain.cost(0, 0), Some(2.0));
+        assert!(!terrain.is_traversable(1, 0));
+    }
+}
@@ -0,0 +1,236 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::tree::Distance;
+use std::hash::Hash;
+
+/// A discrete mode of a hybrid system, such as a contact state or gear.
+pub trait Mode: Eq + Hash + Clone {
+    /// Returns the cost of switching from this mode to `to`, or `None` if the switch is
+    /// never allowed. Only called for different modes. By default no switches are allowed.
+    fn switch_cost(&self, to: &Self) -> Option<f64> {
+        let _ = to;
+        None
+    }
+}
+
+/// A continuous state labeled with the discrete mode the system is in.
+///
+/// States in the same mode are as far apart as their continuous parts. States in modes
+/// that can be switched between directly are further by the switch cost, and otherwise
+/// they are infinitely far apart, so planners never connect them directly and nearest
+/// neighbor queries prefer states in compatible modes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HybridState<M, S> {
+    /// The discrete mode.
+    pub mode: M,
+
+    /// The continuous state.
+    pub state: S,
+}
+
+impl<M, S> HybridState<M, S> {
+    /// Construct a state in `mode`.
+    pub fn new(mode: M, state: S) -> Self {
+        HybridState { mode, state }
+    }
+}
+
+impl<M: Mode, S: Distance> Distance for HybridState<M, S> {
+    fn distance(&self, other: &Self) -> f64 {
+        let switch = if self.mode == other.mode {
+            0.0
+        } else {
+            match self.mode.switch_cost(&other.mode) {
+                Some(cost) => cost,
+                None => return f64::INFINITY,
+            }
+        };
+        switch + self.state.distance(&other.state)
+    }
+}
+
+/// Builds a hybrid extension function from a continuous one.
+///
+/// When extending towards a state in another mode, `guard_fn(from_mode, to_mode, state)`
+/// decides whether the switch may happen at the starting state, e.g. only while a foot is
+/// on the ground. If it may, the new state is in the target mode, otherwise the extension
+/// stays in the current mode. Switches happen at the start of an edge, as they do in
+/// [`hybrid_connectable`].
+pub fn hybrid_extender<M, S, FE, FG>(
+    mut extend_fn: FE,
+    mut guard_fn: FG,
+) -> impl FnMut(&HybridState<M, S>, &HybridState<M, S>) -> HybridState<M, S>
+where
+    M: Mode,
+    FE: FnMut(&S, &S) -> S,
+    FG: FnMut(&M, &M, &S) -> bool,
+{
+    move |from: &HybridState<M, S>, to: &HybridState<M, S>| {
+        let mode = if from.mode == to.mode
+            || (from.mode.switch_cost(&to.mode).is_some()
+                && guard_fn(&from.mode, &to.mode, &from.state))
+        {
+            to.mode.clone()
+        } else {
+            from.mode.clone()
+        };
+        HybridState::new(mode, extend_fn(&from.state, &to.state))
+    }
+}
+
+/// Builds a hybrid motion validator from a per mode continuous one.
+///
+/// `connectable_fn(mode, from, to)` validates a continuous motion within a mode. Motions
+/// between modes are valid only if the switch is allowed, `guard_fn` accepts it at the
+/// starting state, and the continuous motion is valid in the new mode.
+pub fn hybrid_connectable<M, S, FC, FG>(
+    mut connectable_fn: FC,
+    mut guard_fn: FG,
+) -> impl FnMut(&HybridState<M, S>, &HybridState<M, S>) -> bool
+where
+    M: Mode,
+    FC: FnMut(&M, &S, &S) -> bool,
+    FG: FnMut(&M, &M, &S) -> bool,
+{
+    move |from: &HybridState<M, S>, to: &HybridState<M, S>| {
+        (from.mode == to.mode
+            || (from.mode.switch_cost(&to.mode).is_some()
+                && guard_fn(&from.mode, &to.mode, &from.state)))
+            && connectable_fn(&to.mode, &from.state, &to.state)
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::rrt::rrt_simple;
+    use crate::planning::termination::MaxIterations;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Gear {
+        Park,
+        Low,
+        High,
+    }
+
+    // Gears shift one at a time, and parking is free
+    impl Mode for Gear {
+        fn switch_cost(&self, to: &Self) -> Option<f64> {
+            match (self, to) {
+                (Gear::Park, Gear::Low) | (Gear::Low, Gear::Park) => Some(0.0),
+                (Gear::Low, Gear::High) | (Gear::High, Gear::Low) => Some(1.0),
+                _ => None,
+            }
+        }
+    }
+
+    // Shifting is only allowed at even positions
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn guard(_: &Gear, _: &Gear, state: &i32) -> bool {
+        state % 2 == 0
+    }
+
+    // Steps of up to 1 in low gear and 3 in high gear, and no motion while parked
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn extend(from: &i32, to: &i32) -> i32 {
+        from + (to - from).clamp(-3, 3)
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn connectable(gear: &Gear, from: &i32, to: &i32) -> bool {
+        let limit = match gear {
+            Gear::Park => 0,
+            Gear::Low => 1,
+            Gear::High => 3,
+        };
+        (to - from).abs() <= limit
+    }
+
+    #[test]
+    fn test_hybrid_distance() {
+        let state = |mode, x| HybridState::new(mode, x);
+        assert!((state(Gear::Low, 0).distance(&state(Gear::Low, 2)) - 2.0).abs() < 1e-9);
+        assert!((state(Gear::Low, 0).distance(&state(Gear::High, 2)) - 3.0).abs() < 1e-9);
+        assert!((state(Gear::Park, 0).distance(&state(Gear::Low, 0))).abs() < 1e-9);
+        assert!(state(Gear::Park, 0)
+            .distance(&state(Gear::High, 0))
+            .is_infinite());
+    }
+
+    #[test]
+    fn test_hybrid_extend_and_connect() {
+        let mut extend = hybrid_extender(extend, guard);
+        let mut valid = hybrid_connectable(connectable, guard);
+        let state = |mode, x| HybridState::new(mode, x);
+
+        assert_eq!(
+            extend(&state(Gear::Low, 0), &state(Gear::High, 9)),
+            state(Gear::High, 3)
+        );
+        assert!(valid(&state(Gear::Low, 0), &state(Gear::High, 3)));
+
+        // The guard blocks shifting at odd positions, and parking to high is never allowed
+        assert_eq!(
+            extend(&state(Gear::Low, 1), &state(Gear::High, 9)),
+            state(Gear::Low, 4)
+        );
+        assert!(!valid(&state(Gear::Low, 1), &state(Gear::High, 4)));
+        assert!(!valid(&state(Gear::Park, 0), &state(Gear::High, 0)));
+        assert!(!valid(&state(Gear::Low, 0), &state(Gear::Low, 2)));
+        assert!(valid(&state(Gear::Low, 0), &state(Gear::Park, 0)));
+    }
+
+    #[test]
+    fn test_hybrid_rrt() {
+        // Leaving park and driving to 12 requires going through low gear
+        let mut samples = [
+            (Gear::High, 12),
+            (Gear::Low, 2),
+            (Gear::High, 6),
+            (Gear::Low, 1),
+        ]
+        .into_iter()
+        .cycle();
+        let (path, _) = rrt_simple(
+            &HybridState::new(Gear::Park, 0),
+            |state: &HybridState<Gear, i32>| state.state == 12,
+            || {
+                let (mode, x) = samples.next().unwrap();
+                HybridState::new(mode, x)
+            },
+            hybrid_extender(extend, guard),
+            hybrid_connectable(connectable, guard),
+            MaxIterations(200),
+        )
+        .unwrap();
+
+        assert_eq!(path[0], HybridState::new(Gear::Park, 0));
+        assert!(path.iter().any(|state| state.mode == Gear::Low));
+        let mut valid = hybrid_connectable(connectable, guard);
+        assert!(path.windows(2).all(|pair| valid(&pair[0], &pair[1])));
+    }
+}
@@ -0,0 +1,260 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::float::FloatArray;
+use crate::path::Pose2D;
+use crate::planning::goal::angle_difference;
+
+/// Computes the states a robot passes through when moving between two states.
+///
+/// Motion validators and trajectory generators given the same interpolation agree on the
+/// motion between waypoints, so a validated path is the one that is executed. Any
+/// `Fn(&T, &T, f64) -> T` closure is also an interpolation.
+pub trait Interpolate<T> {
+    /// Returns the state a fraction `t` of the way from `from` to `to`, with `t` in
+    /// `[0, 1]`.
+    fn interpolate(&self, from: &T, to: &T, t: f64) -> T;
+}
+
+impl<T, F> Interpolate<T> for F
+where
+    F: Fn(&T, &T, f64) -> T,
+{
+    fn interpolate(&self, from: &T, to: &T, t: f64) -> T {
+        self(from, to, t)
+    }
+}
+
+/// Straight line interpolation, turning through the smaller angle for poses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Linear;
+
+impl<const N: usize> Interpolate<FloatArray<N>> for Linear {
+    fn interpolate(&self, from: &FloatArray<N>, to: &FloatArray<N>, t: f64) -> FloatArray<N> {
+        FloatArray(std::array::from_fn(|i| {
+            from.0[i] + (to.0[i] - from.0[i]) * t
+        }))
+    }
+}
+
+impl Interpolate<(f64, f64)> for Linear {
+    fn interpolate(&self, from: &(f64, f64), to: &(f64, f64), t: f64) -> (f64, f64) {
+        (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t)
+    }
+}
+
+impl Interpolate<Pose2D> for Linear {
+    fn interpolate(&self, from: &Pose2D, to: &Pose2D, t: f64) -> Pose2D {
+        let (x, y) = Linear.interpolate(&(from.0, from.1), &(to.0, to.1), t);
+        (x, y, from.2 + angle_difference(to.2, from.2) * t)
+    }
+}
+
+/// How to move between two [`Pose2D`]s.
+///
+/// The Hermite curves leave and arrive along the headings of the poses, with tangents as
+/// long as the distance between them, so a path of poses is followed without turning on
+/// the spot. The cubic curve's heading is continuous across waypoints, while the quintic
+/// curve also has zero curvature at every waypoint, so its curvature is continuous along
+/// the whole path. The heading along either curve is the direction of travel.
+///
+/// ```
+/// use rustplanning::interpolation::{Interpolate, PoseInterpolation};
+///
+/// // Sidestepping while facing forwards makes an S bend
+/// let (x, y, heading) =
+///     PoseInterpolation::QuinticHermite.interpolate(&(0.0, 0.0, 0.0), &(2.0, 1.0, 0.0), 0.5);
+/// assert!((x - 1.0).abs() < 1e-12 && (y - 0.5).abs() < 1e-12);
+/// assert!(heading > 0.5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoseInterpolation {
+    /// Straight line between the positions, turning through the smaller angle.
+    #[default]
+    Linear,
+
+    /// Cubic Hermite curve between the positions.
+    CubicHermite,
+
+    /// Quintic Hermite curve between the positions, with zero curvature at either end.
+    QuinticHermite,
+}
+
+impl Interpolate<Pose2D> for PoseInterpolation {
+    fn interpolate(&self, from: &Pose2D, to: &Pose2D, t: f64) -> Pose2D {
+        let length = (to.0 - from.0).hypot(to.1 - from.1);
+        if *self == PoseInterpolation::Linear || length == 0.0 {
+            return Linear.interpolate(from, to, t);
+        }
+        if t <= 0.0 {
+            return *from;
+        }
+        if t >= 1.0 {
+            return *to;
+        }
+
+        // Weights of the start, start tangent, end, and end tangent, and their derivatives
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let (weights, derivatives) = if *self == PoseInterpolation::CubicHermite {
+            (
+                [
+                    2.0 * t3 - 3.0 * t2 + 1.0,
+                    t3 - 2.0 * t2 + t,
+                    3.0 * t2 - 2.0 * t3,
+                    t3 - t2,
+                ],
+                [
+                    6.0 * t2 - 6.0 * t,
+                    3.0 * t2 - 4.0 * t + 1.0,
+                    6.0 * t - 6.0 * t2,
+                    3.0 * t2 - 2.0 * t,
+                ],
+            )
+        } else {
+            let (t4, t5) = (t3 * t, t3 * t2);
+            (
+                [
+                    1.0 - 10.0 * t3 + 15.0 * t4 - 6.0 * t5,
+                    t - 6.0 * t3 + 8.0 * t4 - 3.0 * t5,
+                    10.0 * t3 - 15.0 * t4 + 6.0 * t5,
+                    -4.0 * t3 + 7.0 * t4 - 3.0 * t5,
+                ],
+                [
+                    -30.0 * t2 + 60.0 * t3 - 30.0 * t4,
+                    1.0 - 18.0 * t2 + 32.0 * t3 - 15.0 * t4,
+                    30.0 * t2 - 60.0 * t3 + 30.0 * t4,
+                    -12.0 * t2 + 28.0 * t3 - 15.0 * t4,
+                ],
+            )
+        };
+        let tangents = [
+            (length * from.2.cos(), length * from.2.sin()),
+            (length * to.2.cos(), length * to.2.sin()),
+        ];
+        let combine = |w: [f64; 4]| {
+            (
+                w[0] * from.0 + w[1] * tangents[0].0 + w[2] * to.0 + w[3] * tangents[1].0,
+                w[0] * from.1 + w[1] * tangents[0].1 + w[2] * to.1 + w[3] * tangents[1].1,
+            )
+        };
+        let (x, y) = combine(weights);
+        let (dx, dy) = combine(derivatives);
+
+        // Opposing headings can bring the curve to a momentary stop
+        let heading = if dx.hypot(dy) > 1e-12 * length {
+            dy.atan2(dx)
+        } else {
+            Linear.interpolate(from, to, t).2
+        };
+        (x, y, heading)
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::approx_eq;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn test_linear() {
+        let point = Linear.interpolate(&FloatArray([0.0, 2.0]), &FloatArray([4.0, -2.0]), 0.25);
+        assert_eq!(point, FloatArray([1.0, 1.0]));
+        assert_eq!(
+            Linear.interpolate(&(0.0, 0.0), &(2.0, 4.0), 0.5),
+            (1.0, 2.0)
+        );
+
+        // Turns through the smaller angle
+        let (x, y, heading) = Linear.interpolate(&(0.0, 0.0, 3.0), &(1.0, 0.0, -3.0), 0.5);
+        assert!(approx_eq!(f64, x, 0.5));
+        assert!(approx_eq!(f64, y, 0.0));
+        assert!(approx_eq!(f64, heading.abs(), PI, epsilon = 1e-12));
+
+        let halve = |a: &f64, b: &f64, t: f64| a + (b - a) * t / 2.0;
+        assert!(approx_eq!(f64, halve.interpolate(&0.0, &4.0, 1.0), 2.0));
+    }
+
+    #[test]
+    fn test_hermite() {
+        let from = (0.0, 0.0, 0.0);
+        let to = (2.0, 2.0, FRAC_PI_2);
+        for interpolation in [
+            PoseInterpolation::CubicHermite,
+            PoseInterpolation::QuinticHermite,
+        ] {
+            assert_eq!(interpolation.interpolate(&from, &to, 0.0), from);
+            assert_eq!(interpolation.interpolate(&from, &to, 1.0), to);
+
+            // Leaves and arrives along the headings
+            let start = interpolation.interpolate(&from, &to, 1e-6);
+            let end = interpolation.interpolate(&from, &to, 1.0 - 1e-6);
+            assert!(approx_eq!(f64, start.2, 0.0, epsilon = 1e-4));
+            assert!(approx_eq!(f64, end.2, FRAC_PI_2, epsilon = 1e-4));
+            assert!(start.1.abs() < 1e-9);
+            assert!((end.0 - 2.0).abs() < 1e-9);
+
+            // Heads along the curve, turning one way
+            let mut previous = start;
+            for i in 1..100 {
+                let pose = interpolation.interpolate(&from, &to, f64::from(i) / 100.0);
+                let direction = (pose.1 - previous.1).atan2(pose.0 - previous.0);
+                assert!(approx_eq!(f64, pose.2, direction, epsilon = 0.05));
+                assert!(pose.2 >= previous.2 - 1e-9);
+                previous = pose;
+            }
+        }
+    }
+
+    #[test]
+    fn test_hermite_degenerate() {
+        // Turning on the spot
+        let pose =
+            PoseInterpolation::CubicHermite.interpolate(&(1.0, 1.0, 0.0), &(1.0, 1.0, 1.0), 0.5);
+        assert_eq!(pose, (1.0, 1.0, 0.5));
+
+        // Arriving facing back the way it came overshoots the goal and turns around
+        let pose =
+            PoseInterpolation::CubicHermite.interpolate(&(0.0, 0.0, 0.0), &(1.0, 0.0, PI), 0.5);
+        assert!(approx_eq!(f64, pose.0, 0.75));
+        assert!(approx_eq!(f64, pose.1, 0.0));
+        assert!(pose.2.is_finite());
+    }
+
+    #[test]
+    fn test_quintic_curvature() {
+        // Curvature from finite differences vanishes at the ends of the quintic curve only
+        let curvature = |interpolation: PoseInterpolation, t: f64| {
+            let h = 1e-4;
+            let a = interpolation.interpolate(&(0.0, 0.0, 0.0), &(2.0, 1.0, 0.0), t);
+            let b = interpolation.interpolate(&(0.0, 0.0, 0.0), &(2.0, 1.0, 0.0), t + h);
+            angle_difference(b.2, a.2) / (b.0 - a.0).hypot(b.1 - a.1)
+        };
+        assert!(curvature(PoseInterpolation::QuinticHermite, 1e-4).abs() < 0.01);
+        assert!(curvature(PoseInterpolation::CubicHermite, 1e-4).abs() > 0.5);
+    }
+}
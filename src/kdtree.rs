@@ -0,0 +1,447 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::cmp::Ordering;
+
+// A point in the tree, which splits its subtree along one axis.
+#[derive(Debug, Clone)]
+struct KdNode {
+    id: usize,
+    point: Vec<f64>,
+    axis: usize,
+
+    // Points below the split along the axis, or equal to it.
+    left: Option<usize>,
+
+    // Points above the split along the axis, or equal to it.
+    right: Option<usize>,
+}
+
+/// A k-d tree of points, each identified by an id, for nearest neighbor and radius queries
+/// in logarithmic time rather than scanning every point.
+///
+/// Queries take the distance to each id as a function so that callers can use their own
+/// metric, e.g. [`crate::tree::HashTree`] uses [`crate::tree::Distance`] between states.
+/// Results are exact as long as that distance is never less than the difference in any
+/// one coordinate of the points, which holds for Euclidean distance on the coordinates,
+/// or for coordinates that only include part of the state.
+///
+/// Points are inserted as leaves, so the tree stays reasonably balanced for randomly
+/// sampled points. Use [`KdTree::build`] to construct a balanced tree from known points.
+#[derive(Debug, Clone, Default)]
+pub struct KdTree {
+    // The root is the first node, if any.
+    nodes: Vec<KdNode>,
+}
+
+impl KdTree {
+    /// Construct an empty tree.
+    #[must_use]
+    pub fn new() -> Self {
+        KdTree::default()
+    }
+
+    /// Construct a balanced tree by splitting at the median point along each axis.
+    ///
+    /// # Panics
+    ///
+    /// If the points do not all have the same, non zero, number of coordinates.
+    pub fn build<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, Vec<f64>)>,
+    {
+        let mut entries: Vec<(usize, Vec<f64>)> = points.into_iter().collect();
+        let dimension = entries.first().map_or(1, |(_, point)| point.len());
+        assert!(
+            dimension > 0 && entries.iter().all(|(_, point)| point.len() == dimension),
+            "Points must have the same, non zero, number of coordinates"
+        );
+
+        let mut tree = KdTree::new();
+        // Ranges of entries still to add, with their depth and the node to attach them to
+        let mut pending = vec![(0, entries.len(), 0, None)];
+        while let Some((start, end, depth, parent)) = pending.pop() {
+            if start == end {
+                continue;
+            }
+            let axis = depth % dimension;
+            let mid = start + (end - start) / 2;
+            entries[start..end]
+                .select_nth_unstable_by(mid - start, |a, b| a.1[axis].total_cmp(&b.1[axis]));
+
+            let index = tree.nodes.len();
+            tree.nodes.push(KdNode {
+                id: entries[mid].0,
+                point: std::mem::take(&mut entries[mid].1),
+                axis,
+                left: None,
+                right: None,
+            });
+            tree.attach(parent, index);
+            pending.push((start, mid, depth + 1, Some((index, true))));
+            pending.push((mid + 1, end, depth + 1, Some((index, false))));
+        }
+        tree
+    }
+
+    /// Returns the number of points in the tree.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the tree has no points.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterates over the ids and points in the tree, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &[f64])> {
+        self.nodes
+            .iter()
+            .map(|node| (node.id, node.point.as_slice()))
+    }
+
+    /// Adds a point as a leaf of the tree.
+    ///
+    /// # Panics
+    ///
+    /// If the point has no coordinates, or a different number than the points already in
+    /// the tree.
+    pub fn insert(&mut self, id: usize, point: Vec<f64>) {
+        let dimension = self
+            .nodes
+            .first()
+            .map_or(point.len(), |root| root.point.len());
+        assert!(
+            dimension > 0 && point.len() == dimension,
+            "Points must have the same, non zero, number of coordinates"
+        );
+
+        let mut parent = None;
+        let mut current = self.root();
+        while let Some(index) = current {
+            let node = &self.nodes[index];
+            let left = point[node.axis] < node.point[node.axis];
+            parent = Some((index, left));
+            current = if left { node.left } else { node.right };
+        }
+
+        let axis = parent.map_or(0, |(index, _)| (self.nodes[index].axis + 1) % dimension);
+        let index = self.nodes.len();
+        self.nodes.push(KdNode {
+            id,
+            point,
+            axis,
+            left: None,
+            right: None,
+        });
+        self.attach(parent, index);
+    }
+
+    /// Returns the id closest to `point` and its distance, with ties ordered by `compare`.
+    ///
+    /// `compare` orders pairs of ids and their distances, and must order by distance first.
+    pub fn nearest<D, C>(
+        &self,
+        point: &[f64],
+        mut distance: D,
+        mut compare: C,
+    ) -> Option<(usize, f64)>
+    where
+        D: FnMut(usize) -> f64,
+        C: FnMut((usize, f64), (usize, f64)) -> Ordering,
+    {
+        let mut best: Option<(usize, f64)> = None;
+        self.search(point, |candidate_id| {
+            let candidate = (candidate_id, distance(candidate_id));
+            if best.is_none_or(|best| compare(candidate, best).is_lt()) {
+                best = Some(candidate);
+            }
+            best.map_or(f64::INFINITY, |(_, distance)| distance)
+        });
+        best
+    }
+
+    /// Returns up to `k` of the ids closest to `point` and their distances, sorted by
+    /// `compare`, which must order by distance first.
+    pub fn k_nearest<D, C>(
+        &self,
+        point: &[f64],
+        k: usize,
+        mut distance: D,
+        mut compare: C,
+    ) -> Vec<(usize, f64)>
+    where
+        D: FnMut(usize) -> f64,
+        C: FnMut((usize, f64), (usize, f64)) -> Ordering,
+    {
+        let mut best: Vec<(usize, f64)> = Vec::new();
+        if k == 0 {
+            return best;
+        }
+        self.search(point, |candidate_id| {
+            let candidate = (candidate_id, distance(candidate_id));
+            let position = best.partition_point(|&other| compare(other, candidate).is_lt());
+            if position < k {
+                best.insert(position, candidate);
+                best.truncate(k);
+            }
+            if best.len() < k {
+                f64::INFINITY
+            } else {
+                best[k - 1].1
+            }
+        });
+        best
+    }
+
+    /// Returns every id within `radius` of `point` and its distance, in no particular
+    /// order.
+    pub fn within<D>(&self, point: &[f64], radius: f64, mut distance: D) -> Vec<(usize, f64)>
+    where
+        D: FnMut(usize) -> f64,
+    {
+        let mut found = Vec::new();
+        self.search(point, |id| {
+            let candidate = distance(id);
+            if candidate <= radius {
+                found.push((id, candidate));
+            }
+            radius
+        });
+        found
+    }
+
+    /// Returns every id and point between `lower` and `upper` inclusive, in no particular
+    /// order. Dimensions past the end of either limit are unbounded.
+    #[must_use]
+    pub fn in_box(&self, lower: &[f64], upper: &[f64]) -> Vec<(usize, &[f64])> {
+        let bounded = lower.len().min(upper.len());
+        let mut found = Vec::new();
+        let mut pending: Vec<_> = self.root().into_iter().collect();
+        while let Some(index) = pending.pop() {
+            let node = &self.nodes[index];
+            let inside = (0..bounded.min(node.point.len()))
+                .all(|i| lower[i] <= node.point[i] && node.point[i] <= upper[i]);
+            if inside {
+                found.push((node.id, node.point.as_slice()));
+            }
+
+            let split = node.point[node.axis];
+            let (below, above) = if node.axis < bounded {
+                (lower[node.axis] <= split, upper[node.axis] >= split)
+            } else {
+                (true, true)
+            };
+            pending.extend(node.left.filter(|_| below));
+            pending.extend(node.right.filter(|_| above));
+        }
+        found
+    }
+
+    /// Returns true if every point is on the correct side of its ancestors' splits, and
+    /// every node is reachable from the root exactly once.
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let dimension = self.nodes.first().map_or(0, |root| root.point.len());
+        let mut pending: Vec<_> = self
+            .root()
+            .map(|root| {
+                (
+                    root,
+                    vec![f64::NEG_INFINITY; dimension],
+                    vec![f64::INFINITY; dimension],
+                )
+            })
+            .into_iter()
+            .collect();
+        while let Some((index, lower, upper)) = pending.pop() {
+            let node = &self.nodes[index];
+            if visited[index] || node.point.len() != dimension || node.axis >= dimension {
+                return false;
+            }
+            visited[index] = true;
+            let inside =
+                (0..dimension).all(|i| lower[i] <= node.point[i] && node.point[i] <= upper[i]);
+            if !inside {
+                return false;
+            }
+
+            let split = node.point[node.axis];
+            if let Some(left) = node.left {
+                let mut upper = upper.clone();
+                upper[node.axis] = split;
+                pending.push((left, lower.clone(), upper));
+            }
+            if let Some(right) = node.right {
+                let mut lower = lower;
+                lower[node.axis] = split;
+                pending.push((right, lower, upper));
+            }
+        }
+        visited.into_iter().all(|visited| visited)
+    }
+
+    fn root(&self) -> Option<usize> {
+        (!self.nodes.is_empty()).then_some(0)
+    }
+
+    fn attach(&mut self, parent: Option<(usize, bool)>, index: usize) {
+        match parent {
+            Some((parent, true)) => self.nodes[parent].left = Some(index),
+            Some((parent, false)) => self.nodes[parent].right = Some(index),
+            None => {}
+        }
+    }
+
+    // Visits points near `point` first, skipping subtrees that are further away than the
+    // distance `visit` returns after each point.
+    fn search<V>(&self, point: &[f64], mut visit: V)
+    where
+        V: FnMut(usize) -> f64,
+    {
+        let mut limit = f64::INFINITY;
+        // Subtrees to visit, along with a lower bound on their distance
+        let mut pending: Vec<_> = self.root().map(|root| (root, 0.0)).into_iter().collect();
+        while let Some((index, bound)) = pending.pop() {
+            if bound > limit {
+                continue;
+            }
+            let node = &self.nodes[index];
+            limit = visit(node.id);
+
+            let offset = point[node.axis] - node.point[node.axis];
+            let (near, far) = if offset < 0.0 {
+                (node.left, node.right)
+            } else {
+                (node.right, node.left)
+            };
+            pending.extend(far.map(|far| (far, bound.max(offset.abs()))));
+            pending.extend(near.map(|near| (near, bound)));
+        }
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::tuning::SplitMix64;
+
+    fn brute_force(points: &[Vec<f64>], query: &[f64]) -> Vec<(usize, f64)> {
+        let mut distances: Vec<(usize, f64)> = points
+            .iter()
+            .enumerate()
+            .map(|(id, point)| (id, distance(point, query)))
+            .collect();
+        distances.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        distances
+    }
+
+    fn distance(a: &[f64], b: &[f64]) -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    fn by_distance(a: (usize, f64), b: (usize, f64)) -> Ordering {
+        a.1.total_cmp(&b.1).then(a.0.cmp(&b.0))
+    }
+
+    #[test]
+    fn test_kdtree_queries() {
+        let mut rng = SplitMix64(3);
+        // Coarse coordinates so that there are plenty of exact ties
+        let points: Vec<Vec<f64>> = (0..300)
+            .map(|_| {
+                vec![
+                    (rng.next_f64() * 10.0).round(),
+                    (rng.next_f64() * 10.0).round(),
+                ]
+            })
+            .collect();
+
+        let mut inserted = KdTree::new();
+        for (id, point) in points.iter().enumerate() {
+            inserted.insert(id, point.clone());
+        }
+        let built = KdTree::build(points.iter().cloned().enumerate());
+        assert!(inserted.is_consistent());
+        assert!(built.is_consistent());
+        assert_eq!(built.len(), points.len());
+
+        for _ in 0..50 {
+            let query = [rng.next_f64() * 12.0 - 1.0, rng.next_f64() * 12.0 - 1.0];
+            let expected = brute_force(&points, &query);
+            let dist = |id: usize| distance(&points[id], &query);
+            for tree in [&inserted, &built] {
+                assert_eq!(tree.nearest(&query, dist, by_distance), Some(expected[0]));
+                assert_eq!(tree.k_nearest(&query, 7, dist, by_distance), expected[..7]);
+
+                let mut within = tree.within(&query, 2.5, dist);
+                within.sort_by(|&a, &b| by_distance(a, b));
+                let in_radius: Vec<_> = expected.iter().copied().filter(|p| p.1 <= 2.5).collect();
+                assert_eq!(within, in_radius);
+            }
+        }
+    }
+
+    #[test]
+    fn test_kdtree_in_box() {
+        let mut rng = SplitMix64(8);
+        let points: Vec<Vec<f64>> = (0..200)
+            .map(|_| vec![rng.next_f64(), rng.next_f64(), rng.next_f64()])
+            .collect();
+        let tree = KdTree::build(points.iter().cloned().enumerate());
+
+        let (lower, upper) = ([0.2, 0.5, 0.0], [0.6, 0.9, 0.5]);
+        let mut found: Vec<usize> = tree.in_box(&lower, &upper).iter().map(|p| p.0).collect();
+        found.sort_unstable();
+        let expected: Vec<usize> = (0..points.len())
+            .filter(|&id| (0..3).all(|i| lower[i] <= points[id][i] && points[id][i] <= upper[i]))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+
+        // Missing dimensions are unbounded
+        let found = tree.in_box(&[0.0], &[0.5]);
+        assert_eq!(found.len(), points.iter().filter(|p| p[0] <= 0.5).count());
+    }
+
+    #[test]
+    fn test_kdtree_empty() {
+        let tree = KdTree::new();
+        assert!(tree.is_empty());
+        assert!(tree.is_consistent());
+        assert_eq!(tree.nearest(&[0.0], |_| 0.0, by_distance), None);
+        assert!(tree.k_nearest(&[0.0], 3, |_| 0.0, by_distance).is_empty());
+        assert!(KdTree::build(Vec::new()).is_empty());
+    }
+}
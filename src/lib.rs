@@ -20,10 +20,65 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-pub mod tree;
+// Lets derived impls name the crate as `::rustplanning` from inside it too
+extern crate self as rustplanning;
+
+pub mod belief;
+pub mod benchmark;
+pub mod bounds;
+pub mod clock;
+pub mod error;
+pub mod export;
+pub mod faults;
+pub mod float;
+pub mod geofence;
+pub mod geojson;
+pub mod grid;
+pub mod hybrid;
+pub mod interpolation;
+pub mod kdtree;
+pub mod metric;
+pub mod path;
+pub mod persist;
 pub mod planning;
+pub mod prediction;
+pub mod report;
+pub mod scene;
+pub mod snapshot;
+pub mod trajectory;
+pub mod tree;
+pub mod validity;
+pub mod voxel;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub mod prelude {
-    pub use crate::tree::*;
+    pub use crate::belief::*;
+    pub use crate::benchmark::*;
+    pub use crate::bounds::*;
+    pub use crate::clock::*;
+    pub use crate::error::*;
+    pub use crate::export::*;
+    pub use crate::faults::*;
+    pub use crate::float::*;
+    pub use crate::geofence::*;
+    pub use crate::geojson::*;
+    pub use crate::grid::*;
+    pub use crate::hybrid::*;
+    pub use crate::interpolation::*;
+    pub use crate::kdtree::*;
+    pub use crate::metric::*;
+    pub use crate::path::*;
+    pub use crate::persist::*;
     pub use crate::planning::*;
+    pub use crate::prediction::*;
+    pub use crate::report::*;
+    pub use crate::scene::*;
+    pub use crate::snapshot::*;
+    pub use crate::trajectory::*;
+    pub use crate::tree::*;
+    pub use crate::validity::*;
+    pub use crate::voxel::*;
+    #[cfg(feature = "wasm")]
+    pub use crate::wasm::*;
 }
@@ -0,0 +1,330 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::planning::radius::AdaptiveRadius;
+use crate::tree::Distance;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// A Euclidean metric with a weight on each component of a state, e.g. to count a radian of
+/// yaw as half a meter. The distance is `sqrt(sum w_i (a_i - b_i)^2)`.
+///
+/// Wrap states with [`WeightedMetric::wrap`] to use the metric as their [`Distance`], so
+/// that nearest neighbor queries, the RRT* rewire radius, and the maximum extension are all
+/// measured the same way. Extend functions should then steer with
+/// [`WeightedMetric::steer`], and adaptive radii should come from
+/// [`WeightedMetric::adaptive_radius`], so that changing a weight does not leave step sizes
+/// or radii in the old units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedMetric {
+    weights: Arc<[f64]>,
+}
+
+impl WeightedMetric {
+    /// Creates a metric with one weight per component.
+    ///
+    /// # Panics
+    ///
+    /// If any weight is not positive and finite.
+    #[must_use]
+    pub fn new(weights: &[f64]) -> Self {
+        assert!(
+            weights.iter().all(|w| w.is_finite() && *w > 0.0),
+            "Weights must be positive and finite"
+        );
+        WeightedMetric {
+            weights: weights.into(),
+        }
+    }
+
+    /// The weight of each component.
+    #[must_use]
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// The number of components.
+    #[must_use]
+    pub fn dimension(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Returns the weighted distance between two sets of coordinates.
+    #[must_use]
+    pub fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        self.weights
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|(w, (a, b))| w * (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Scales coordinates by the square root of their weights, so that the Euclidean
+    /// distance between scaled coordinates is the weighted distance, e.g. to index wrapped
+    /// states with a [`crate::tree::SpatialIndex`].
+    #[must_use]
+    pub fn scale(&self, coordinates: &[f64]) -> Vec<f64> {
+        self.weights
+            .iter()
+            .zip(coordinates)
+            .map(|(w, c)| w.sqrt() * c)
+            .collect()
+    }
+
+    /// Inverts [`WeightedMetric::scale`].
+    #[must_use]
+    pub fn unscale(&self, scaled: &[f64]) -> Vec<f64> {
+        self.weights
+            .iter()
+            .zip(scaled)
+            .map(|(w, c)| c / w.sqrt())
+            .collect()
+    }
+
+    /// Moves from `from` towards `to` by at most `step` as measured by this metric,
+    /// returning `to` if it is within the step.
+    #[must_use]
+    pub fn steer(&self, from: &[f64], to: &[f64], step: f64) -> Vec<f64> {
+        let distance = self.distance(from, to);
+        if distance <= step {
+            return to.to_vec();
+        }
+        let fraction = step / distance;
+        from.iter()
+            .zip(to)
+            .map(|(a, b)| a + (b - a) * fraction)
+            .collect()
+    }
+
+    /// Converts a measure of the unweighted space, e.g. [`crate::bounds::Bounds::volume`],
+    /// into the weighted space, where each component is stretched by the square root of its
+    /// weight.
+    #[must_use]
+    pub fn measure(&self, unweighted: f64) -> f64 {
+        unweighted * self.weights.iter().map(|w| w.sqrt()).product::<f64>()
+    }
+
+    /// Returns the RRT* radius schedule for the weighted space, given the measure of the
+    /// free space without weights.
+    #[must_use]
+    pub fn adaptive_radius(&self, free_measure: f64) -> AdaptiveRadius {
+        AdaptiveRadius::new(self.dimension(), self.measure(free_measure))
+    }
+
+    /// Wraps a state whose components are `coordinates`, so that distances between wrapped
+    /// states use this metric.
+    #[must_use]
+    pub fn wrap<T>(&self, value: T, coordinates: &[f64]) -> Weighted<T> {
+        Weighted {
+            value,
+            scaled: self.scale(coordinates),
+        }
+    }
+}
+
+/// A state measured with a [`WeightedMetric`], created by [`WeightedMetric::wrap`].
+///
+/// Equality and hashing are those of the wrapped state, while the [`Distance`] between
+/// wrapped states is the weighted distance between their coordinates. States should be
+/// wrapped with the same metric.
+#[derive(Debug, Clone)]
+pub struct Weighted<T> {
+    value: T,
+    scaled: Vec<f64>,
+}
+
+impl<T> Weighted<T> {
+    /// The wrapped value.
+    #[must_use]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// The coordinates scaled by [`WeightedMetric::scale`].
+    #[must_use]
+    pub fn scaled_coordinates(&self) -> &[f64] {
+        &self.scaled
+    }
+
+    /// Returns the wrapped value.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for Weighted<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Weighted<T> {}
+
+impl<T: Hash> Hash for Weighted<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
+impl<T> Distance for Weighted<T> {
+    fn distance(&self, other: &Self) -> f64 {
+        self.scaled
+            .iter()
+            .zip(&other.scaled)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::FloatArray;
+    use crate::planning::rrt::{rrt_with_tree, RrtOptions};
+    use crate::planning::stats::PlannerStats;
+    use crate::planning::termination::MaxIterations;
+    use crate::planning::tuning::SplitMix64;
+    use crate::tree::{HashTree, SpatialIndex};
+    use float_cmp::approx_eq;
+
+    type Pose = Weighted<FloatArray<3>>;
+
+    fn pose(metric: &WeightedMetric, coordinates: [f64; 3]) -> Pose {
+        metric.wrap(FloatArray(coordinates), &coordinates)
+    }
+
+    #[test]
+    fn test_weighted_metric() {
+        // Yaw counts half as much as position, so a radian is 1/sqrt(2) meters
+        let metric = WeightedMetric::new(&[1.0, 1.0, 0.5]);
+        assert_eq!(metric.dimension(), 3);
+        assert!(approx_eq!(
+            f64,
+            metric.distance(&[0.0, 0.0, 0.0], &[3.0, 4.0, 0.0]),
+            5.0
+        ));
+        assert!(approx_eq!(
+            f64,
+            metric.distance(&[0.0, 0.0, 0.0], &[0.0, 0.0, 2.0]),
+            2.0f64.sqrt()
+        ));
+        let a = pose(&metric, [1.0, 2.0, 0.0]);
+        let b = pose(&metric, [1.0, 2.0, 2.0]);
+        assert!(approx_eq!(f64, a.distance(&b), 2.0f64.sqrt()));
+        assert_ne!(a, b);
+        assert_eq!(a, pose(&metric, [1.0, 2.0, 0.0]));
+
+        let scaled = metric.scale(&[1.0, 2.0, 2.0]);
+        assert_eq!(scaled, b.scaled_coordinates());
+        assert_eq!(metric.unscale(&scaled), vec![1.0, 2.0, 2.0]);
+
+        // Steps are measured in the weighted units
+        let stepped = metric.steer(&[0.0, 0.0, 0.0], &[0.0, 0.0, 4.0], 1.0);
+        assert!(approx_eq!(
+            f64,
+            metric.distance(&[0.0, 0.0, 0.0], &stepped),
+            1.0
+        ));
+        assert_eq!(
+            metric.steer(&[0.0; 3], &[0.0, 0.0, 1.0], 1.0),
+            [0.0, 0.0, 1.0]
+        );
+
+        // Halving the weight of yaw shrinks the space along it
+        assert!(approx_eq!(f64, metric.measure(8.0), 8.0 * 0.5f64.sqrt()));
+        let radius = metric.adaptive_radius(8.0);
+        assert_eq!(radius.dimension, 3);
+        assert!(radius.gamma() < WeightedMetric::new(&[1.0; 3]).adaptive_radius(8.0).gamma());
+    }
+
+    #[test]
+    #[should_panic(expected = "Weights must be positive and finite")]
+    fn test_weighted_metric_rejects_zero() {
+        let _ = WeightedMetric::new(&[1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_weighted_planning() {
+        // Yaw is cheap, so the nearest node to a sample is the one with the closest position
+        let metric = WeightedMetric::new(&[1.0, 1.0, 0.01]);
+        let mut tree = HashTree::new(pose(&metric, [0.0, 0.0, 0.0]));
+        let near = pose(&metric, [0.5, 0.0, 3.0]);
+        assert!(tree
+            .add_child(&pose(&metric, [0.0, 0.0, 0.0]), near.clone())
+            .is_ok());
+        assert!(tree
+            .add_child(&near, pose(&metric, [2.0, 0.0, 0.0]))
+            .is_ok());
+        assert_eq!(
+            tree.nearest_neighbor(&pose(&metric, [0.6, 0.0, 0.0])),
+            &near
+        );
+
+        // The planner's extension limit and rewire radius apply in the weighted units, and
+        // indexing the scaled coordinates finds the same path
+        let run = |index: bool| {
+            let mut rng = SplitMix64(3);
+            let mut sample = || {
+                let c = [
+                    rng.next_f64() * 10.0,
+                    rng.next_f64() * 10.0,
+                    rng.next_f64() * 6.0,
+                ];
+                pose(&metric, c)
+            };
+            let extend = |from: &Pose, to: &Pose| {
+                let coordinates = metric.steer(&from.value().0, &to.value().0, 1.0);
+                pose(&metric, coordinates.try_into().unwrap())
+            };
+            let options = RrtOptions::default()
+                .with_max_extension(1.0)
+                .with_rrtstar(2.0);
+            let mut tree = HashTree::new(pose(&metric, [0.0; 3]));
+            if index {
+                tree.set_spatial_index(Some(SpatialIndex::new(|p: &Pose| {
+                    p.scaled_coordinates().to_vec()
+                })));
+            }
+            rrt_with_tree(
+                &mut tree,
+                &pose(&metric, [8.0, 8.0, 5.0]),
+                &mut sample,
+                extend,
+                |_: &Pose, _: &Pose| true,
+                &options,
+                MaxIterations(300),
+                &mut PlannerStats::new(),
+            )
+            .unwrap()
+        };
+        let path = run(false);
+        assert!(path.windows(2).all(|w| w[0].distance(&w[1]) <= 2.0 + 1e-9));
+        assert_eq!(path, run(true));
+    }
+}
@@ -0,0 +1,614 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::float::check_resolution;
+use crate::tree::Distance;
+
+/// A 2D pose as `(x, y, heading)`, with the heading in radians.
+pub type Pose2D = (f64, f64, f64);
+
+/// Configuration for [`assign_headings`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HeadingOptions {
+    /// Number of neighboring segments on each side to average the tangent over, 0 uses the
+    /// direction of the outgoing segment at each point.
+    pub smoothing: usize,
+
+    /// Heading the path must end with, e.g. to dock. If unset the final point uses the
+    /// direction of the last segment.
+    pub final_heading: Option<f64>,
+}
+
+/// Assigns a heading to every point of a positional path, producing poses for a tracking
+/// controller.
+///
+/// Each point faces along its outgoing segment, and the last point faces along the incoming
+/// one. Zero length segments, e.g. from duplicated points, are ignored. With smoothing, the
+/// unit direction vectors of nearby segments are averaged, which avoids sharp heading
+/// changes at corners. A path with a single point or without any motion gets the final
+/// heading, or zero.
+#[must_use]
+pub fn assign_headings(path: &[(f64, f64)], options: &HeadingOptions) -> Vec<Pose2D> {
+    // Unit direction of every segment, None for segments without motion
+    let directions: Vec<Option<(f64, f64)>> = path
+        .windows(2)
+        .map(|pair| {
+            let (dx, dy) = (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+            let length = (dx * dx + dy * dy).sqrt();
+            (length > 0.0).then(|| (dx / length, dy / length))
+        })
+        .collect();
+
+    let default_heading = options.final_heading.unwrap_or(0.0);
+    let mut heading = directions
+        .iter()
+        .flatten()
+        .next()
+        .map_or(default_heading, |d| d.1.atan2(d.0));
+
+    let mut poses = Vec::with_capacity(path.len());
+    for (i, point) in path.iter().enumerate() {
+        // The segment leaving the point, or entering it for the last point
+        let segment = i.min(directions.len().saturating_sub(1));
+        let first = segment.saturating_sub(options.smoothing);
+        let last = (segment + options.smoothing).min(directions.len().saturating_sub(1));
+        let (sx, sy) = directions
+            .get(first..=last)
+            .unwrap_or_default()
+            .iter()
+            .flatten()
+            .fold((0.0, 0.0), |sum, d| (sum.0 + d.0, sum.1 + d.1));
+
+        // Keep the previous heading if there is no motion nearby or it cancels out
+        if sx != 0.0 || sy != 0.0 {
+            heading = sy.atan2(sx);
+        }
+        poses.push((point.0, point.1, heading));
+    }
+
+    if let (Some(last), Some(final_heading)) = (poses.last_mut(), options.final_heading) {
+        last.2 = final_heading;
+    }
+    poses
+}
+
+/// Returns the total length of a polyline path.
+#[must_use]
+pub fn path_length(path: &[(f64, f64)]) -> f64 {
+    path.windows(2)
+        .map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1))
+        .sum()
+}
+
+/// Returns the point `distance` along a polyline path, clamped to the ends of the path.
+///
+/// # Panics
+///
+/// If the path is empty.
+#[must_use]
+pub fn point_at_distance(path: &[(f64, f64)], distance: f64) -> (f64, f64) {
+    let mut remaining = distance.max(0.0);
+    for pair in path.windows(2) {
+        let length = (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1);
+        if remaining <= length && length > 0.0 {
+            let t = remaining / length;
+            return (
+                pair[0].0 + t * (pair[1].0 - pair[0].0),
+                pair[0].1 + t * (pair[1].1 - pair[0].1),
+            );
+        }
+        remaining -= length;
+    }
+    *path.last().expect("Path must not be empty")
+}
+
+/// Shortens a path by greedily skipping over waypoints.
+///
+/// Starting from the first point, connects directly to the furthest later point for which
+/// `valid_fn` accepts the straight motion, and repeats from there. The first and last
+/// points are always kept.
+pub fn shortcut<FV>(path: &[(f64, f64)], mut valid_fn: FV) -> Vec<(f64, f64)>
+where
+    FV: FnMut(&(f64, f64), &(f64, f64)) -> bool,
+{
+    let Some(&first) = path.first() else {
+        return Vec::new();
+    };
+
+    let mut result = vec![first];
+    let mut i = 0;
+    while i + 1 < path.len() {
+        // Consecutive points are assumed to be connectable
+        let next = (i + 2..path.len())
+            .rev()
+            .find(|&j| valid_fn(&path[i], &path[j]))
+            .unwrap_or(i + 1);
+        result.push(path[next]);
+        i = next;
+    }
+    result
+}
+
+// Replaces every corner of the path with a circular arc of the given radius, sampled at
+// the given spacing, or returns the index of the first corner where the arc does not fit.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn fillet_corners(
+    path: &[(f64, f64)],
+    radius: f64,
+    resolution: f64,
+) -> Result<Vec<(f64, f64)>, usize> {
+    let unit = |a: (f64, f64), b: (f64, f64)| {
+        let length = (b.0 - a.0).hypot(b.1 - a.1);
+        ((b.0 - a.0) / length, (b.1 - a.1) / length, length)
+    };
+
+    let mut result = path[..1].to_vec();
+    for i in 1..path.len().saturating_sub(1) {
+        let (a, b, c) = (path[i - 1], path[i], path[i + 1]);
+        let (u1x, u1y, incoming) = unit(a, b);
+        let (u2x, u2y, outgoing) = unit(b, c);
+        let turn = (u1x * u2y - u1y * u2x).atan2(u1x * u2x + u1y * u2y);
+        if turn.abs() < 1e-9 {
+            result.push(b);
+            continue;
+        }
+
+        // Distance from the corner to where the arc meets each segment. Interior segments
+        // are shared between two arcs, so each may only use half of it.
+        let tangent = radius * (turn.abs() / 2.0).tan();
+        let available_in = if i == 1 { incoming } else { incoming / 2.0 };
+        let available_out = if i + 2 == path.len() {
+            outgoing
+        } else {
+            outgoing / 2.0
+        };
+        if !tangent.is_finite() || tangent > available_in || tangent > available_out {
+            return Err(i);
+        }
+
+        // The center is on the inside of the turn, perpendicular to the incoming segment
+        let start = (b.0 - u1x * tangent, b.1 - u1y * tangent);
+        let side = turn.signum();
+        let center = (start.0 - side * u1y * radius, start.1 + side * u1x * radius);
+        let start_angle = (start.1 - center.1).atan2(start.0 - center.0);
+        let steps = ((radius * turn.abs() / resolution).ceil() as usize).max(1);
+        for k in 0..=steps {
+            let angle = start_angle + turn * k as f64 / steps as f64;
+            result.push((
+                center.0 + radius * angle.cos(),
+                center.1 + radius * angle.sin(),
+            ));
+        }
+    }
+    if path.len() > 1 {
+        result.push(path[path.len() - 1]);
+    }
+    Ok(result)
+}
+
+/// Smooths a path so that it never turns tighter than `min_radius`, e.g. the minimum
+/// turning radius of a car-like vehicle.
+///
+/// The path is first shortened with [`shortcut`], then every corner is replaced with a
+/// circular arc of `min_radius` sampled every `resolution` along the arc. Every motion of
+/// the result is validated with `valid_fn`. If the shortened path cannot be smoothed, the
+/// corners of the original path are filleted instead.
+///
+/// Consecutive duplicate points must be removed beforehand.
+///
+/// # Errors
+///
+/// If an arc does not fit at a corner, i.e. the corner is too sharp for its adjacent
+/// segments, or the smoothed path is invalid.
+pub fn smooth_with_min_radius<FV>(
+    path: &[(f64, f64)],
+    min_radius: f64,
+    resolution: f64,
+    mut valid_fn: FV,
+) -> Result<Vec<(f64, f64)>, String>
+where
+    FV: FnMut(&(f64, f64), &(f64, f64)) -> bool,
+{
+    if path.is_empty() {
+        return Err("Path must not be empty".into());
+    }
+    if min_radius.is_nan() || min_radius < 0.0 || resolution.is_nan() || resolution <= 0.0 {
+        return Err("Radius must not be negative and resolution must be positive".into());
+    }
+
+    let shortened = shortcut(path, &mut valid_fn);
+    let mut error = String::new();
+    for candidate in [shortened.as_slice(), path] {
+        match fillet_corners(candidate, min_radius, resolution) {
+            Ok(smoothed) if smoothed.windows(2).all(|pair| valid_fn(&pair[0], &pair[1])) => {
+                return Ok(smoothed);
+            }
+            Ok(_) => error = "Smoothed path is not valid".into(),
+            Err(i) => error = format!("Corner at {:?} is too sharp for the radius", candidate[i]),
+        }
+    }
+    Err(error)
+}
+
+/// Returns the cost of a path of any state type, the sum of the distances between
+/// consecutive states.
+#[must_use]
+pub fn path_cost<T: Distance>(path: &[T]) -> f64 {
+    path.windows(2).map(|pair| pair[0].distance(&pair[1])).sum()
+}
+
+/// Returns the discrete Fréchet distance between two paths.
+///
+/// Informally, the shortest leash that lets two walkers traverse the paths from start to
+/// end without backtracking, only stopping at states. Unlike comparing states pairwise,
+/// paths with different numbers of states can be compared. Returns infinity if either path
+/// is empty.
+#[must_use]
+pub fn frechet_distance<T: Distance>(a: &[T], b: &[T]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return f64::INFINITY;
+    }
+
+    // Rolling row of the dynamic programming table over b
+    let mut previous: Vec<f64> = Vec::with_capacity(b.len());
+    for (i, state_a) in a.iter().enumerate() {
+        let mut current: Vec<f64> = Vec::with_capacity(b.len());
+        for (j, state_b) in b.iter().enumerate() {
+            let d = state_a.distance(state_b);
+            let reachable = match (i, j) {
+                (0, 0) => d,
+                (0, _) => current[j - 1],
+                (_, 0) => previous[0],
+                _ => previous[j].min(previous[j - 1]).min(current[j - 1]),
+            };
+            current.push(reachable.max(d));
+        }
+        previous = current;
+    }
+    previous[b.len() - 1]
+}
+
+/// Returns the ratio of the cost of `path` to the cost of `reference`, so values below 1
+/// mean `path` is cheaper.
+#[must_use]
+pub fn cost_ratio<T: Distance>(path: &[T], reference: &[T]) -> f64 {
+    path_cost(path) / path_cost(reference)
+}
+
+/// Checks a planned path against a stored golden path, for regression testing planners.
+///
+/// Passes if the path costs at most `cost_tolerance` more than the golden path, as a
+/// fraction, e.g. `0.05` allows 5% more, and its Fréchet distance from the golden path is
+/// at most `max_deviation`.
+///
+/// # Errors
+///
+/// With a description of the regression if either check fails.
+pub fn compare_to_golden<T: Distance>(
+    path: &[T],
+    golden: &[T],
+    cost_tolerance: f64,
+    max_deviation: f64,
+) -> Result<(), String> {
+    let ratio = cost_ratio(path, golden);
+    if ratio.is_nan() || ratio > 1.0 + cost_tolerance {
+        return Err(format!(
+            "Path cost {} is {:.1}% of the golden cost {}, which exceeds the {:.1}% tolerance",
+            path_cost(path),
+            ratio * 100.0,
+            path_cost(golden),
+            cost_tolerance * 100.0
+        ));
+    }
+
+    let deviation = frechet_distance(path, golden);
+    if deviation > max_deviation {
+        return Err(format!(
+            "Path deviates from the golden path by {deviation}, which exceeds {max_deviation}"
+        ));
+    }
+    Ok(())
+}
+
+/// Clearance from obstacles along a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clearance<T> {
+    /// The smallest clearance anywhere along the path.
+    pub minimum: f64,
+
+    /// The checked state with the smallest clearance, which may lie between path states.
+    pub location: T,
+
+    /// The smallest clearance along each segment, in path order.
+    pub segments: Vec<f64>,
+}
+
+/// Computes the clearance along a path with a distance query, e.g. the same signed distance
+/// field used with [`crate::validity::with_clearance`] while planning.
+///
+/// Every segment is checked at its end points and at intermediate states from
+/// `interpolate_fn(from, to, fraction)` no more than `resolution` apart, so clearance
+/// between checked states is not guaranteed. Returns `None` for an empty path.
+///
+/// # Panics
+///
+/// If the resolution is not finite and positive.
+pub fn path_clearance<T, FD, FI>(
+    path: &[T],
+    mut distance_fn: FD,
+    mut interpolate_fn: FI,
+    resolution: f64,
+) -> Option<Clearance<T>>
+where
+    T: Distance + Clone,
+    FD: FnMut(&T) -> f64,
+    FI: FnMut(&T, &T, f64) -> T,
+{
+    check_resolution(resolution);
+    let first = path.first()?;
+    let mut clearance = Clearance {
+        minimum: distance_fn(first),
+        location: first.clone(),
+        segments: Vec::with_capacity(path.len().saturating_sub(1)),
+    };
+
+    for pair in path.windows(2) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let steps = (pair[0].distance(&pair[1]) / resolution).ceil().max(1.0) as usize;
+        let mut segment = f64::INFINITY;
+        for step in 0..=steps {
+            #[allow(clippy::cast_precision_loss)]
+            let state = match step {
+                0 => pair[0].clone(),
+                _ if step == steps => pair[1].clone(),
+                _ => interpolate_fn(&pair[0], &pair[1], step as f64 / steps as f64),
+            };
+            let distance = distance_fn(&state);
+            segment = segment.min(distance);
+            if distance < clearance.minimum {
+                clearance.minimum = distance;
+                clearance.location = state;
+            }
+        }
+        clearance.segments.push(segment);
+    }
+    Some(clearance)
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::FloatArray;
+    use float_cmp::approx_eq;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    fn headings(poses: &[Pose2D]) -> Vec<f64> {
+        poses.iter().map(|pose| pose.2).collect()
+    }
+
+    fn assert_headings(poses: &[Pose2D], expected: &[f64]) {
+        assert_eq!(poses.len(), expected.len());
+        for (actual, expected) in headings(poses).iter().zip(expected) {
+            assert!(
+                approx_eq!(f64, *actual, *expected, epsilon = 1e-9),
+                "{:?} != {expected:?}",
+                headings(poses)
+            );
+        }
+    }
+
+    #[test]
+    fn test_path_length() {
+        let path = [(0.0, 0.0), (3.0, 4.0), (3.0, 4.0), (3.0, 6.0)];
+        assert!(approx_eq!(f64, path_length(&path), 7.0));
+        assert!(approx_eq!(f64, path_length(&path[..1]), 0.0));
+
+        assert_eq!(point_at_distance(&path, -1.0), (0.0, 0.0));
+        assert_eq!(point_at_distance(&path, 2.5), (1.5, 2.0));
+        assert_eq!(point_at_distance(&path, 6.0), (3.0, 5.0));
+        assert_eq!(point_at_distance(&path, 10.0), (3.0, 6.0));
+        assert_eq!(point_at_distance(&path[..1], 1.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_path_clearance() {
+        // A single round obstacle of radius 1 at the origin
+        let distance_fn = |p: &FloatArray<2>| p.0[0].hypot(p.0[1]) - 1.0;
+        let interpolate_fn = |a: &FloatArray<2>, b: &FloatArray<2>, t: f64| {
+            FloatArray([
+                a.0[0] + (b.0[0] - a.0[0]) * t,
+                a.0[1] + (b.0[1] - a.0[1]) * t,
+            ])
+        };
+
+        // The closest approach is half way along the first segment
+        let path = [
+            FloatArray([-2.0, 2.0]),
+            FloatArray([2.0, 2.0]),
+            FloatArray([2.0, 4.0]),
+        ];
+        let clearance = path_clearance(&path, distance_fn, interpolate_fn, 0.5).unwrap();
+        assert!(approx_eq!(f64, clearance.minimum, 1.0, epsilon = 1e-9));
+        assert!(approx_eq!(
+            f64,
+            clearance.location.0[0],
+            0.0,
+            epsilon = 1e-9
+        ));
+        assert_eq!(clearance.segments.len(), 2);
+        assert!(approx_eq!(f64, clearance.segments[0], 1.0, epsilon = 1e-9));
+        let corner = 8.0_f64.sqrt() - 1.0;
+        assert!(approx_eq!(
+            f64,
+            clearance.segments[1],
+            corner,
+            epsilon = 1e-9
+        ));
+
+        // A coarse resolution only checks the end points
+        let clearance = path_clearance(&path, distance_fn, interpolate_fn, 10.0).unwrap();
+        assert!(approx_eq!(f64, clearance.minimum, corner, epsilon = 1e-9));
+
+        let clearance = path_clearance(&path[..1], distance_fn, interpolate_fn, 0.5).unwrap();
+        assert!(clearance.segments.is_empty());
+        assert_eq!(clearance.location, path[0]);
+        assert!(path_clearance(&[], distance_fn, interpolate_fn, 0.5).is_none());
+    }
+
+    #[test]
+    fn test_frechet_distance() {
+        assert!(approx_eq!(
+            f64,
+            frechet_distance(&[0, 1, 2], &[0, 1, 2]),
+            0.0
+        ));
+        assert!(approx_eq!(f64, frechet_distance(&[0, 2], &[0, 1, 2]), 1.0));
+        assert!(approx_eq!(f64, frechet_distance(&[0, 5], &[1, 3, 4]), 2.0));
+
+        // Backtracking is penalized even though the same states are visited
+        assert!(approx_eq!(
+            f64,
+            frechet_distance(&[0, 4, 0, 4], &[0, 4]),
+            4.0
+        ));
+        assert!(frechet_distance::<i32>(&[], &[1]).is_infinite());
+    }
+
+    #[test]
+    fn test_compare_to_golden() {
+        assert!(approx_eq!(f64, path_cost(&[0, 3, 1]), 5.0));
+        assert!(approx_eq!(f64, cost_ratio(&[0, 3, 1], &[0, 10]), 0.5));
+
+        let golden = [0, 5, 10];
+        assert!(compare_to_golden(&[0, 10], &golden, 0.0, 5.0).is_ok());
+        assert!(compare_to_golden(&[0, 6, 5, 10], &golden, 0.25, 1.0).is_ok());
+
+        let err = compare_to_golden(&[0, 6, 5, 10], &golden, 0.1, 1.0).unwrap_err();
+        assert!(err.contains("120.0% of the golden cost"), "{err}");
+        let err = compare_to_golden(&[0, 10], &golden, 0.0, 1.0).unwrap_err();
+        assert!(err.contains("deviates"), "{err}");
+    }
+
+    #[test]
+    fn test_shortcut() {
+        // A wall along x = 1 from y = -1 to 1 blocks the direct route
+        let valid = |a: &(f64, f64), b: &(f64, f64)| {
+            if (a.0 - 1.0) * (b.0 - 1.0) >= 0.0 {
+                return true;
+            }
+            let t = (1.0 - a.0) / (b.0 - a.0);
+            (a.1 + t * (b.1 - a.1)).abs() > 1.0
+        };
+        let path = [(0.0, 0.0), (0.0, 2.0), (1.0, 2.0), (2.0, 2.0), (2.0, 0.0)];
+        assert_eq!(
+            shortcut(&path, valid),
+            vec![(0.0, 0.0), (1.0, 2.0), (2.0, 0.0)]
+        );
+        assert_eq!(shortcut(&path, |_, _| true), vec![(0.0, 0.0), (2.0, 0.0)]);
+        assert!(shortcut(&[], valid).is_empty());
+    }
+
+    #[test]
+    fn test_smooth_with_min_radius() {
+        // Shortcutting is disallowed so the corner must be replaced with an arc
+        let path = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let valid =
+            |a: &(f64, f64), b: &(f64, f64)| (a.0 - b.0).abs() < 5.0 || (a.1 - b.1).abs() < 5.0;
+        let smoothed = smooth_with_min_radius(&path, 2.0, 0.1, valid).unwrap();
+        assert_eq!(smoothed[0], (0.0, 0.0));
+        assert_eq!(smoothed[smoothed.len() - 1], (10.0, 10.0));
+
+        // Every point on the corner is on the circle of radius 2 centered at (8, 2)
+        let corner: Vec<&(f64, f64)> = smoothed[1..smoothed.len() - 1].iter().collect();
+        assert!(corner.len() > 2);
+        for p in corner {
+            assert!(approx_eq!(
+                f64,
+                (p.0 - 8.0).hypot(p.1 - 2.0),
+                2.0,
+                epsilon = 1e-9
+            ));
+        }
+        assert!(path_length(&smoothed) < path_length(&path));
+    }
+
+    #[test]
+    fn test_smooth_with_min_radius_failure() {
+        // The segments are too short for a radius of 2
+        let path = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+        let err = smooth_with_min_radius(&path, 2.0, 0.1, |a, b| {
+            (a.0 - b.0).abs() < 1e-9 || (a.1 - b.1).abs() < 1e-9
+        });
+        assert!(err.unwrap_err().contains("too sharp"));
+
+        // Straight paths are unchanged
+        let path = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)];
+        let smoothed = smooth_with_min_radius(&path, 2.0, 0.1, |_, _| true).unwrap();
+        assert_eq!(smoothed, vec![(0.0, 0.0), (2.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_assign_headings() {
+        let path = [(0.0, 0.0), (1.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let poses = assign_headings(&path, &HeadingOptions::default());
+        assert_eq!((poses[3].0, poses[3].1), (1.0, 1.0));
+        assert_headings(&poses, &[0.0, 0.0, FRAC_PI_2, PI, PI]);
+
+        assert_headings(
+            &assign_headings(&[(1.0, 1.0)], &HeadingOptions::default()),
+            &[0.0],
+        );
+        assert!(assign_headings(&[], &HeadingOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_assign_headings_smoothing() {
+        let path = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (1.0, 2.0)];
+        let options = HeadingOptions {
+            smoothing: 1,
+            ..HeadingOptions::default()
+        };
+        let poses = assign_headings(&path, &options);
+        assert_headings(
+            &poses,
+            &[FRAC_PI_4, 1.107_148_717_794_090_4, FRAC_PI_2, FRAC_PI_2],
+        );
+    }
+
+    #[test]
+    fn test_assign_headings_final_heading() {
+        let options = HeadingOptions {
+            final_heading: Some(-FRAC_PI_2),
+            ..HeadingOptions::default()
+        };
+        let poses = assign_headings(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)], &options);
+        assert_headings(&poses, &[0.0, 0.0, -FRAC_PI_2]);
+
+        let poses = assign_headings(&[(0.0, 0.0), (0.0, 0.0)], &options);
+        assert_headings(&poses, &[-FRAC_PI_2, -FRAC_PI_2]);
+    }
+}
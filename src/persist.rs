@@ -0,0 +1,790 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::PersistError;
+use crate::planning::prm::{EdgeState, PrmOptions, Roadmap};
+use crate::planning::radius::AdaptiveRadius;
+use crate::planning::rrt::{
+    ConnectLimit, ConnectionDirection, DuplicatePolicy, GoalCandidates, NodeSelection, RrtOptions,
+};
+use crate::tree::{Distance, HashTree, TieBreak};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Version of the tree format written by [`write_tree`].
+pub const TREE_VERSION: u32 = 1;
+
+/// Version of the options format written by [`write_options`]. Version 2 added `seed`.
+pub const OPTIONS_VERSION: u32 = 2;
+
+/// Version of the roadmap format written by [`write_roadmap`].
+pub const ROADMAP_VERSION: u32 = 1;
+
+const TREE_KIND: &str = "rustplanning-tree";
+const OPTIONS_KIND: &str = "rustplanning-rrt-options";
+const ROADMAP_KIND: &str = "rustplanning-roadmap";
+
+/// Reads a `<kind> <version>` header line, returning the version.
+///
+/// Files from any earlier version are accepted and converted by the readers, files from a
+/// later version than `current` were written by a newer release and are rejected.
+pub(crate) fn read_header<R: BufRead>(
+    lines: &mut io::Lines<R>,
+    kind: &'static str,
+    current: u32,
+) -> Result<u32, PersistError> {
+    let line = lines.next().ok_or(PersistError::MissingHeader { kind })??;
+    let version = line
+        .strip_prefix(kind)
+        .and_then(|rest| rest.strip_prefix(' '))
+        .and_then(|version| version.parse::<u32>().ok())
+        .ok_or(PersistError::MissingHeader { kind })?;
+    if version == 0 || version > current {
+        return Err(PersistError::UnsupportedVersion {
+            kind,
+            version,
+            supported: current,
+        });
+    }
+    Ok(version)
+}
+
+pub(crate) fn parse<V: FromStr>(token: Option<&str>) -> Result<V, String> {
+    let token = token.ok_or("Missing value")?;
+    token
+        .parse()
+        .map_err(|_| format!("Invalid value '{token}'"))
+}
+
+/// Writes a tree as a line based file, where `fields_fn` flattens each state into numbers.
+///
+/// After the header every node is written as `N <parent id> <fields...>`, where ids are
+/// line numbers after the header and the root has parent -1. Nodes are written in the
+/// order they were added to the tree, except that nodes rewired below a later node are
+/// moved after it. Writing a tree read with [`read_tree`] gives an identical file, so
+/// files can be compared or hashed to detect changes.
+///
+/// Only the states and edges are kept, edge costs are recomputed when the tree is read.
+///
+/// # Errors
+///
+/// If writing fails.
+pub fn write_tree<W, T, F>(writer: &mut W, tree: &HashTree<T>, mut fields_fn: F) -> io::Result<()>
+where
+    W: Write,
+    T: Eq + Clone + Hash + Distance,
+    F: FnMut(&T) -> Vec<f64>,
+{
+    // Parents must come before their children
+    let mut order: Vec<&T> = Vec::with_capacity(tree.size());
+    let mut written: HashSet<&T> = HashSet::with_capacity(tree.size());
+    for node in tree.iter_insertion_order() {
+        let mut chain = Vec::new();
+        let mut current = Some(node);
+        while let Some(state) = current.filter(|state| !written.contains(state)) {
+            chain.push(state);
+            current = tree.get_parent(state);
+        }
+        for state in chain.into_iter().rev() {
+            written.insert(state);
+            order.push(state);
+        }
+    }
+
+    let ids: HashMap<&T, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(id, &state)| (state, id))
+        .collect();
+    writeln!(writer, "{TREE_KIND} {TREE_VERSION}")?;
+    for state in order {
+        let fields: Vec<String> = fields_fn(state).iter().map(ToString::to_string).collect();
+        match tree.get_parent(state) {
+            Some(parent) => writeln!(writer, "N {} {}", ids[parent], fields.join(" "))?,
+            None => writeln!(writer, "N -1 {}", fields.join(" "))?,
+        }
+    }
+    Ok(())
+}
+
+/// Reads a tree written by [`write_tree`] by any earlier version of the crate, where
+/// `state_fn` rebuilds each state from its fields.
+///
+/// # Errors
+///
+/// If reading fails, the file is malformed, or it was written by a newer version.
+pub fn read_tree<R, T, F>(reader: R, mut state_fn: F) -> Result<HashTree<T>, PersistError>
+where
+    R: BufRead,
+    T: Eq + Clone + Hash + Distance,
+    F: FnMut(&[f64]) -> Result<T, String>,
+{
+    let mut lines = reader.lines();
+    read_header(&mut lines, TREE_KIND, TREE_VERSION)?;
+
+    let mut nodes: Vec<T> = Vec::new();
+    let mut tree: Option<HashTree<T>> = None;
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        let number = number + 2;
+        let context = |message: String| PersistError::Parse {
+            line: number,
+            message,
+        };
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("N") {
+            return Err(context(format!("Malformed node '{line}'")));
+        }
+        let parent: i64 = parse(tokens.next()).map_err(context)?;
+        let fields: Vec<f64> = tokens
+            .map(|token| parse(Some(token)))
+            .collect::<Result<_, _>>()
+            .map_err(context)?;
+        let state = state_fn(&fields).map_err(context)?;
+
+        match (&mut tree, usize::try_from(parent)) {
+            (None, Err(_)) => tree = Some(HashTree::new(state.clone())),
+            (Some(tree), Ok(parent)) => {
+                let parent = nodes
+                    .get(parent)
+                    .ok_or_else(|| context(format!("Unknown node id {parent}")))?;
+                tree.add_child(parent, state.clone())
+                    .map_err(|error| PersistError::Tree {
+                        line: number,
+                        error,
+                    })?;
+            }
+            (None, Ok(_)) => return Err(context("The first node must be the root".into())),
+            (Some(_), Err(_)) => return Err(context("Multiple roots".into())),
+        }
+        nodes.push(state);
+    }
+    tree.ok_or(PersistError::Truncated)
+}
+
+/// Writes planner options as a line based file with one `<name> <values...>` line per
+/// option, in a fixed order so that the same options always give an identical file.
+///
+/// # Errors
+///
+/// If writing fails.
+pub fn write_options<W: Write>(writer: &mut W, options: &RrtOptions) -> io::Result<()> {
+    // Destructured so that new options cannot be left out
+    let RrtOptions {
+        max_extension,
+        use_rrtstar,
+        rewire_radius,
+        adaptive_radius,
+        use_rrtconnect,
+        connect_limit,
+        goal_connection,
+        record_goal_rejections,
+        tie_break,
+        duplicate_policy,
+        node_selection,
+        record_provenance,
+        record_timings,
+        start_tolerance,
+        record_events,
+        max_cost,
+        max_iterations,
+        timeout,
+        fast_return,
+        // Cancellation only applies to the run in progress
+        cancellation: _,
+        informed_sampling,
+        seed,
+    } = options;
+    let adaptive_radius = match adaptive_radius {
+        Some(radius) => format!(
+            "{} {} {}",
+            radius.dimension, radius.free_measure, radius.gamma_scale
+        ),
+        None => "none".into(),
+    };
+    let direction = match goal_connection.direction {
+        ConnectionDirection::GoalToTree => "goal-to-tree",
+        ConnectionDirection::TreeToGoal => "tree-to-goal",
+    };
+    let candidates = match goal_connection.candidates {
+        GoalCandidates::Latest => "latest".into(),
+        GoalCandidates::Nearest(k) => format!("nearest {k}"),
+    };
+    let tie_break = match tie_break {
+        TieBreak::InsertionOrder => "insertion-order",
+        TieBreak::LowestCost => "lowest-cost",
+    };
+    let duplicate_policy = match duplicate_policy {
+        DuplicatePolicy::Ignore => "ignore".into(),
+        DuplicatePolicy::Reject => "reject".into(),
+        DuplicatePolicy::Within(distance) => format!("within {distance}"),
+    };
+    let node_selection = match node_selection {
+        NodeSelection::Nearest => "nearest".into(),
+        NodeSelection::LeastVisited { candidates } => format!("least-visited {candidates}"),
+        NodeSelection::LowestDensity { candidates, radius } => {
+            format!("lowest-density {candidates} {radius}")
+        }
+    };
+    let timeout = timeout.map_or("none".into(), |timeout| timeout.as_secs_f64().to_string());
+
+    writeln!(writer, "{OPTIONS_KIND} {OPTIONS_VERSION}")?;
+    let lines: [(&str, String); 25] = [
+        ("max_extension", max_extension.to_string()),
+        ("use_rrtstar", use_rrtstar.to_string()),
+        ("rewire_radius", rewire_radius.to_string()),
+        ("adaptive_radius", adaptive_radius),
+        ("use_rrtconnect", use_rrtconnect.to_string()),
+        (
+            "connect_limit",
+            format!("{} {}", connect_limit.max_nodes, connect_limit.max_length),
+        ),
+        ("goal_direction", direction.into()),
+        ("goal_frequency", goal_connection.frequency.to_string()),
+        ("goal_candidates", candidates),
+        (
+            "goal_max_distance",
+            goal_connection.max_distance.to_string(),
+        ),
+        ("goal_use_connect", goal_connection.use_connect.to_string()),
+        ("record_goal_rejections", record_goal_rejections.to_string()),
+        ("tie_break", tie_break.into()),
+        ("duplicate_policy", duplicate_policy),
+        ("node_selection", node_selection),
+        ("record_provenance", record_provenance.to_string()),
+        ("record_timings", record_timings.to_string()),
+        ("start_tolerance", start_tolerance.to_string()),
+        ("record_events", record_events.to_string()),
+        ("max_cost", max_cost.to_string()),
+        ("max_iterations", max_iterations.to_string()),
+        ("timeout", timeout),
+        ("fast_return", fast_return.to_string()),
+        ("informed_sampling", informed_sampling.to_string()),
+        ("seed", seed.to_string()),
+    ];
+    for (name, value) in lines {
+        writeln!(writer, "{name} {value}")?;
+    }
+    Ok(())
+}
+
+/// Reads planner options written by [`write_options`] by any earlier version of the crate.
+///
+/// Options that are missing from the file, e.g. because they were added after it was
+/// written, keep their default values.
+///
+/// # Errors
+///
+/// If reading fails, the file is malformed or has unknown options, or it was written by a
+/// newer version.
+pub fn read_options<R: BufRead>(reader: R) -> Result<RrtOptions, PersistError> {
+    let mut lines = reader.lines();
+    read_header(&mut lines, OPTIONS_KIND, OPTIONS_VERSION)?;
+
+    let mut options = RrtOptions::default();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let Some(name) = tokens.next() else {
+            continue;
+        };
+        read_option(&mut options, name, tokens).map_err(|e| PersistError::Parse {
+            line: number + 2,
+            message: format!("{name}: {e}"),
+        })?;
+    }
+    Ok(options)
+}
+
+/// Sets a single option from the tokens following its name.
+fn read_option<'a>(
+    options: &mut RrtOptions,
+    name: &str,
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> Result<(), String> {
+    let goal = &mut options.goal_connection;
+    match name {
+        "max_extension" => options.max_extension = parse(tokens.next())?,
+        "use_rrtstar" => options.use_rrtstar = parse(tokens.next())?,
+        "rewire_radius" => options.rewire_radius = parse(tokens.next())?,
+        "adaptive_radius" => {
+            options.adaptive_radius = match tokens.next() {
+                Some("none") => None,
+                dimension => Some(AdaptiveRadius {
+                    dimension: parse(dimension)?,
+                    free_measure: parse(tokens.next())?,
+                    gamma_scale: parse(tokens.next())?,
+                }),
+            };
+        }
+        "use_rrtconnect" => options.use_rrtconnect = parse(tokens.next())?,
+        "connect_limit" => {
+            options.connect_limit = ConnectLimit {
+                max_nodes: parse(tokens.next())?,
+                max_length: parse(tokens.next())?,
+            };
+        }
+        "goal_direction" => {
+            goal.direction = match tokens.next() {
+                Some("goal-to-tree") => ConnectionDirection::GoalToTree,
+                Some("tree-to-goal") => ConnectionDirection::TreeToGoal,
+                _ => return Err("Invalid direction".into()),
+            };
+        }
+        "goal_frequency" => goal.frequency = parse(tokens.next())?,
+        "goal_candidates" => {
+            goal.candidates = match tokens.next() {
+                Some("latest") => GoalCandidates::Latest,
+                Some("nearest") => GoalCandidates::Nearest(parse(tokens.next())?),
+                _ => return Err("Invalid candidates".into()),
+            };
+        }
+        "goal_max_distance" => goal.max_distance = parse(tokens.next())?,
+        "goal_use_connect" => goal.use_connect = parse(tokens.next())?,
+        "record_goal_rejections" => options.record_goal_rejections = parse(tokens.next())?,
+        "tie_break" => {
+            options.tie_break = match tokens.next() {
+                Some("insertion-order") => TieBreak::InsertionOrder,
+                Some("lowest-cost") => TieBreak::LowestCost,
+                _ => return Err("Invalid tie break".into()),
+            };
+        }
+        "duplicate_policy" => {
+            options.duplicate_policy = match tokens.next() {
+                Some("ignore") => DuplicatePolicy::Ignore,
+                Some("reject") => DuplicatePolicy::Reject,
+                Some("within") => DuplicatePolicy::Within(parse(tokens.next())?),
+                _ => return Err("Invalid duplicate policy".into()),
+            };
+        }
+        "node_selection" => {
+            options.node_selection = match tokens.next() {
+                Some("nearest") => NodeSelection::Nearest,
+                Some("least-visited") => NodeSelection::LeastVisited {
+                    candidates: parse(tokens.next())?,
+                },
+                Some("lowest-density") => NodeSelection::LowestDensity {
+                    candidates: parse(tokens.next())?,
+                    radius: parse(tokens.next())?,
+                },
+                _ => return Err("Invalid node selection".into()),
+            };
+        }
+        "record_provenance" => options.record_provenance = parse(tokens.next())?,
+        "record_timings" => options.record_timings = parse(tokens.next())?,
+        "start_tolerance" => options.start_tolerance = parse(tokens.next())?,
+        "record_events" => options.record_events = parse(tokens.next())?,
+        "max_cost" => options.max_cost = parse(tokens.next())?,
+        "max_iterations" => options.max_iterations = parse(tokens.next())?,
+        "timeout" => {
+            options.timeout = match tokens.next() {
+                Some("none") => None,
+                seconds => {
+                    Some(Duration::try_from_secs_f64(parse(seconds)?).map_err(|e| e.to_string())?)
+                }
+            };
+        }
+        "fast_return" => options.fast_return = parse(tokens.next())?,
+        "informed_sampling" => options.informed_sampling = parse(tokens.next())?,
+        "seed" => options.seed = parse(tokens.next())?,
+        _ => return Err("Unknown option".into()),
+    }
+    match tokens.next() {
+        Some(token) => Err(format!("Unexpected value '{token}'")),
+        None => Ok(()),
+    }
+}
+
+/// Writes a roadmap as a line based file, where `fields_fn` flattens each state into
+/// numbers.
+///
+/// After the header the options are written as `O <name> <value>` lines, then every state
+/// as `N <fields...>` in the order they were added, then every edge once as
+/// `E <id> <id> <valid|unchecked>`, where ids are the states' positions in the file. Edges
+/// a lazy roadmap found to be invalid were already removed, so the work of earlier
+/// queries is kept, but the counts from [`Roadmap::stats`] are not.
+///
+/// # Errors
+///
+/// If writing fails.
+pub fn write_roadmap<W, T, F>(
+    writer: &mut W,
+    roadmap: &Roadmap<T>,
+    mut fields_fn: F,
+) -> io::Result<()>
+where
+    W: Write,
+    T: Eq + Clone + Hash + Distance,
+    F: FnMut(&T) -> Vec<f64>,
+{
+    let PrmOptions {
+        samples,
+        max_neighbors,
+        connection_radius,
+        lazy,
+    } = roadmap.options();
+
+    writeln!(writer, "{ROADMAP_KIND} {ROADMAP_VERSION}")?;
+    writeln!(writer, "O samples {samples}")?;
+    writeln!(writer, "O max_neighbors {max_neighbors}")?;
+    writeln!(writer, "O connection_radius {connection_radius}")?;
+    writeln!(writer, "O lazy {lazy}")?;
+    for state in roadmap.nodes() {
+        let fields: Vec<String> = fields_fn(state).iter().map(ToString::to_string).collect();
+        writeln!(writer, "N {}", fields.join(" "))?;
+    }
+
+    let ids: HashMap<&T, usize> = roadmap
+        .nodes()
+        .iter()
+        .enumerate()
+        .map(|(id, state)| (state, id))
+        .collect();
+    for (from, to, state) in roadmap.edges() {
+        let state = match state {
+            EdgeState::Unchecked => "unchecked",
+            EdgeState::Valid => "valid",
+        };
+        writeln!(writer, "E {} {} {state}", ids[from], ids[to])?;
+    }
+    Ok(())
+}
+
+/// Reads a roadmap written by [`write_roadmap`] by any earlier version of the crate, where
+/// `state_fn` rebuilds each state from its fields.
+///
+/// Options that are missing from the file, e.g. because they were added after it was
+/// written, keep their default values.
+///
+/// # Errors
+///
+/// If reading fails, the file is malformed, or it was written by a newer version.
+pub fn read_roadmap<R, T, F>(reader: R, mut state_fn: F) -> Result<Roadmap<T>, PersistError>
+where
+    R: BufRead,
+    T: Eq + Clone + Hash + Distance,
+    F: FnMut(&[f64]) -> Result<T, String>,
+{
+    let mut lines = reader.lines();
+    read_header(&mut lines, ROADMAP_KIND, ROADMAP_VERSION)?;
+
+    // Options may follow states in hand written files, so the roadmap is built at the end
+    let mut options = PrmOptions::default();
+    let mut nodes: Vec<(usize, T)> = Vec::new();
+    let mut edges: Vec<(usize, usize, usize, EdgeState)> = Vec::new();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        let number = number + 2;
+        let context = |message: String| PersistError::Parse {
+            line: number,
+            message,
+        };
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            None => {}
+            Some("O") => {
+                let name = tokens.next().unwrap_or_default();
+                read_roadmap_option(&mut options, name, tokens)
+                    .map_err(|e| context(format!("{name}: {e}")))?;
+            }
+            Some("N") => {
+                let fields: Vec<f64> = tokens
+                    .map(|token| parse(Some(token)))
+                    .collect::<Result<_, _>>()
+                    .map_err(context)?;
+                nodes.push((number, state_fn(&fields).map_err(context)?));
+            }
+            Some("E") => {
+                let from = parse(tokens.next()).map_err(context)?;
+                let to = parse(tokens.next()).map_err(context)?;
+                let state = match tokens.next() {
+                    Some("unchecked") => EdgeState::Unchecked,
+                    Some("valid") => EdgeState::Valid,
+                    _ => return Err(context("Invalid edge state".into())),
+                };
+                if let Some(token) = tokens.next() {
+                    return Err(context(format!("Unexpected value '{token}'")));
+                }
+                edges.push((number, from, to, state));
+            }
+            Some(_) => return Err(context(format!("Malformed line '{line}'"))),
+        }
+    }
+
+    let mut roadmap = Roadmap::new(options);
+    for (line, state) in nodes {
+        if !roadmap.insert_node(state) {
+            return Err(PersistError::Parse {
+                line,
+                message: "Duplicate state".into(),
+            });
+        }
+    }
+    for (line, from, to, state) in edges {
+        if from == to || from.max(to) >= roadmap.len() {
+            return Err(PersistError::Parse {
+                line,
+                message: format!("Invalid edge between {from} and {to}"),
+            });
+        }
+        roadmap.insert_edge(from, to, state);
+    }
+    Ok(roadmap)
+}
+
+/// Sets a single roadmap option from the tokens following its name.
+fn read_roadmap_option<'a>(
+    options: &mut PrmOptions,
+    name: &str,
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> Result<(), String> {
+    match name {
+        "samples" => options.samples = parse(tokens.next())?,
+        "max_neighbors" => options.max_neighbors = parse(tokens.next())?,
+        "connection_radius" => options.connection_radius = parse(tokens.next())?,
+        "lazy" => options.lazy = parse(tokens.next())?,
+        _ => return Err("Unknown option".into()),
+    }
+    match tokens.next() {
+        Some(token) => Err(format!("Unexpected value '{token}'")),
+        None => Ok(()),
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TreeError;
+    use crate::planning::rrt::GoalConnection;
+    use float_cmp::approx_eq;
+
+    fn to_string(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut buffer = Vec::new();
+        write(&mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn state_fn(fields: &[f64]) -> Result<i32, String> {
+        match fields {
+            [value] => Ok(*value as i32),
+            _ => Err("Expected one field".into()),
+        }
+    }
+
+    #[test]
+    fn test_tree_round_trip() {
+        let mut tree = HashTree::new(0);
+        tree.add_child(&0, 4).unwrap();
+        tree.add_child(&4, 5).unwrap();
+        tree.add_child(&0, 2).unwrap();
+        tree.set_parent(&4, &2).unwrap();
+
+        let fields = |state: &i32| vec![f64::from(*state)];
+        let written = to_string(|w| write_tree(w, &tree, fields));
+        assert_eq!(
+            written,
+            "rustplanning-tree 1\nN -1 0\nN 0 2\nN 1 4\nN 2 5\n"
+        );
+
+        let read = read_tree(written.as_bytes(), state_fn).unwrap();
+        assert_eq!(read.size(), 4);
+        assert_eq!(read.path(&5).unwrap(), vec![0, 2, 4, 5]);
+        assert!(approx_eq!(f64, read.cost(&5).unwrap(), 5.0));
+        assert_eq!(to_string(|w| write_tree(w, &read, fields)), written);
+    }
+
+    #[test]
+    fn test_read_tree_errors() {
+        let read = |text: &str| read_tree(text.as_bytes(), state_fn).map(|tree| tree.size());
+        assert_eq!(read("rustplanning-tree 1\nN -1 0\nN 0 1\n"), Ok(2));
+        assert!(read("").is_err());
+        assert!(read("rustplanning-events 1\nN -1 0\n").is_err());
+        assert_eq!(
+            read("rustplanning-tree 2\nN -1 0\n"),
+            Err(PersistError::UnsupportedVersion {
+                kind: TREE_KIND,
+                version: 2,
+                supported: 1,
+            })
+        );
+        assert_eq!(read("rustplanning-tree 1\n"), Err(PersistError::Truncated));
+        assert!(read("rustplanning-tree 1\nN 0 0\n").is_err());
+        assert!(read("rustplanning-tree 1\nN -1 0\nN -1 1\n").is_err());
+        assert!(read("rustplanning-tree 1\nN -1 0\nN 3 1\n").is_err());
+        assert_eq!(
+            read("rustplanning-tree 1\nN -1 0\nN 0 0\n"),
+            Err(PersistError::Tree {
+                line: 3,
+                error: TreeError::DuplicateNode,
+            })
+        );
+        assert!(read("rustplanning-tree 1\nN -1 0 1\n").is_err());
+    }
+
+    #[test]
+    fn test_options_round_trip() {
+        let defaults = RrtOptions::default();
+        let written = to_string(|w| write_options(w, &defaults));
+        assert!(written.starts_with("rustplanning-rrt-options 2\nmax_extension inf\n"));
+        assert_eq!(read_options(written.as_bytes()), Ok(defaults.clone()));
+
+        let options = RrtOptions {
+            adaptive_radius: Some(AdaptiveRadius::new(2, 100.0)),
+            connect_limit: ConnectLimit {
+                max_nodes: 5,
+                max_length: 2.5,
+            },
+            goal_connection: GoalConnection {
+                direction: ConnectionDirection::TreeToGoal,
+                frequency: 3,
+                candidates: GoalCandidates::Nearest(4),
+                max_distance: 0.1,
+                use_connect: true,
+            },
+            tie_break: TieBreak::LowestCost,
+            duplicate_policy: DuplicatePolicy::Within(0.01),
+            node_selection: NodeSelection::LowestDensity {
+                candidates: 3,
+                radius: 0.7,
+            },
+            timeout: Some(Duration::from_millis(1500)),
+            seed: u64::MAX,
+            ..defaults
+                .with_max_extension(0.3)
+                .with_rrtstar(1.2)
+                .with_max_cost(40.0)
+        };
+        let written = to_string(|w| write_options(w, &options));
+        let read = read_options(written.as_bytes()).unwrap();
+        assert_eq!(read, options);
+        assert_eq!(to_string(|w| write_options(w, &read)), written);
+    }
+
+    #[test]
+    fn test_read_options_migration() {
+        // Options missing from older files keep their defaults
+        let read = read_options("rustplanning-rrt-options 1\nmax_extension 2\n".as_bytes());
+        assert_eq!(read, Ok(RrtOptions::default().with_max_extension(2.0)));
+
+        let read = |text: &str| read_options(text.as_bytes());
+        assert!(matches!(
+            read("rustplanning-rrt-options 3\n"),
+            Err(PersistError::UnsupportedVersion { version: 3, .. })
+        ));
+        assert!(matches!(
+            read("rustplanning-rrt-options 1\nmax_extenson 2\n"),
+            Err(PersistError::Parse { line: 2, .. })
+        ));
+        assert!(read("rustplanning-rrt-options 1\nmax_extension two\n").is_err());
+        assert!(read("rustplanning-rrt-options 1\nmax_extension 2 3\n").is_err());
+        assert!(read("rustplanning-rrt-options 1\ntie_break random\n").is_err());
+    }
+
+    fn roadmap() -> Roadmap<i32> {
+        let options = PrmOptions {
+            samples: 0,
+            max_neighbors: 2,
+            connection_radius: 2.0,
+            lazy: true,
+        };
+        let mut roadmap = Roadmap::new(options);
+        for state in [0, 2, 4, 1, 3] {
+            roadmap.add_state(state, |_: &i32, _: &i32| true);
+        }
+
+        // Finding 2 to 4 invalid removes it and validates the detour
+        let connectable = |from: &i32, to: &i32| from.min(to) != &2 || from.max(to) != &4;
+        assert_eq!(roadmap.query(&0, &4, connectable).unwrap().len(), 4);
+        roadmap
+    }
+
+    #[test]
+    fn test_roadmap_round_trip() {
+        let roadmap = roadmap();
+        let fields = |state: &i32| vec![f64::from(*state)];
+        let written = to_string(|w| write_roadmap(w, &roadmap, fields));
+        assert!(written.starts_with("rustplanning-roadmap 1\nO samples 0\n"));
+        assert!(written.contains("\nE 0 1 valid\n"));
+        assert!(written.contains("unchecked"));
+
+        let read = read_roadmap(written.as_bytes(), state_fn).unwrap();
+        assert_eq!(read.options(), roadmap.options());
+        assert_eq!(read.nodes(), roadmap.nodes());
+        assert_eq!(read.edges(), roadmap.edges());
+        assert_eq!(to_string(|w| write_roadmap(w, &read, fields)), written);
+    }
+
+    #[test]
+    fn test_read_roadmap_migration() {
+        // Options missing from older files keep their defaults
+        let read = read_roadmap(
+            "rustplanning-roadmap 1\nN 0\nN 1\nE 0 1 valid\n".as_bytes(),
+            state_fn,
+        )
+        .unwrap();
+        assert_eq!(read.options(), &PrmOptions::default());
+        assert_eq!(read.edges(), vec![(&0, &1, EdgeState::Valid)]);
+
+        let read =
+            |text: &str| read_roadmap(text.as_bytes(), state_fn).map(|roadmap| roadmap.len());
+        assert_eq!(read("rustplanning-roadmap 1\nO lazy true\nN 0\n"), Ok(1));
+        assert_eq!(
+            read("rustplanning-roadmap 2\n"),
+            Err(PersistError::UnsupportedVersion {
+                kind: ROADMAP_KIND,
+                version: 2,
+                supported: 1,
+            })
+        );
+        assert_eq!(
+            read("rustplanning-tree 1\n"),
+            Err(PersistError::MissingHeader { kind: ROADMAP_KIND })
+        );
+        let parse_line = |text: &str| match read(text) {
+            Err(PersistError::Parse { line, .. }) => Some(line),
+            _ => None,
+        };
+        assert_eq!(
+            parse_line("rustplanning-roadmap 1\nO lazy maybe\n"),
+            Some(2)
+        );
+        assert_eq!(parse_line("rustplanning-roadmap 1\nO lasy true\n"), Some(2));
+        assert_eq!(parse_line("rustplanning-roadmap 1\nN 0\nN 0\n"), Some(3));
+        assert_eq!(
+            parse_line("rustplanning-roadmap 1\nN 0\nE 0 1 valid\n"),
+            Some(3)
+        );
+        assert_eq!(
+            parse_line("rustplanning-roadmap 1\nN 0\nN 1\nE 0 1 fine\n"),
+            Some(4)
+        );
+        assert_eq!(parse_line("rustplanning-roadmap 1\nX\n"), Some(2));
+    }
+}
@@ -0,0 +1,418 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::clock::Instant;
+use crate::error::PlanningError;
+use crate::planning::rrt::{grow_tree, solution_path, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::{Distance, HashTree};
+use std::fmt;
+use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// A single start and goal pair to plan between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProblemDefinition<T> {
+    pub start: T,
+    pub goal: T,
+}
+
+impl<T> ProblemDefinition<T> {
+    #[must_use]
+    pub fn new(start: T, goal: T) -> Self {
+        ProblemDefinition { start, goal }
+    }
+}
+
+/// The outcome of planning a single [`ProblemDefinition`].
+#[derive(Debug, Clone)]
+pub struct PlanningResult<T> {
+    /// The path from the start to the goal, or why planning failed.
+    pub path: Result<Vec<T>, PlanningError>,
+
+    /// Cost of the path, if one was found.
+    pub cost: Option<f64>,
+
+    /// Wall clock time spent planning this problem.
+    pub elapsed: Duration,
+
+    /// Diagnostic information collected while planning.
+    pub stats: PlannerStats<T>,
+}
+
+impl<T> PlanningResult<T> {
+    /// Returns true if a path was found.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.path.is_ok()
+    }
+}
+
+impl<T> fmt::Display for PlanningResult<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Ok(path) => write!(f, "success: {} states", path.len())?,
+            Err(error) => write!(f, "failure: {error}")?,
+        }
+        if let Some(cost) = self.cost {
+            write!(f, ", cost {cost:.4}")?;
+        }
+        write!(
+            f,
+            ", {} iterations in {:.4}s",
+            self.stats.iterations,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Controls how a batch of problems is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Number of worker threads to plan with, 1 plans everything on the calling thread.
+    pub threads: usize,
+
+    /// Problems with identical start states share a single tree, which is grown further
+    /// for each goal rather than planning from scratch.
+    pub share_tree: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            threads: 1,
+            share_tree: false,
+        }
+    }
+}
+
+/// Aggregate statistics over a batch of [`PlanningResult`]s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchSummary {
+    /// Number of problems that found a path.
+    pub succeeded: usize,
+
+    /// Number of problems that did not find a path.
+    pub failed: usize,
+
+    /// Sum of the planning time of every problem.
+    pub total_elapsed: Duration,
+
+    /// Mean path cost over the successful problems, if there were any.
+    pub mean_cost: Option<f64>,
+}
+
+impl BatchSummary {
+    /// Summarize the provided results.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn from_results<T>(results: &[PlanningResult<T>]) -> Self {
+        let costs: Vec<f64> = results.iter().filter_map(|result| result.cost).collect();
+        let succeeded = results.iter().filter(|result| result.is_success()).count();
+        BatchSummary {
+            succeeded,
+            failed: results.len() - succeeded,
+            total_elapsed: results.iter().map(|result| result.elapsed).sum(),
+            mean_cost: (!costs.is_empty()).then(|| costs.iter().sum::<f64>() / costs.len() as f64),
+        }
+    }
+
+    /// Fraction of problems that found a path, or 0 for an empty batch.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn success_rate(&self) -> f64 {
+        let total = self.succeeded + self.failed;
+        if total == 0 {
+            0.0
+        } else {
+            self.succeeded as f64 / total as f64
+        }
+    }
+}
+
+/// Groups problem indices that will be planned with the same tree, in the order the
+/// groups first appear.
+fn group_problems<T: PartialEq>(
+    problems: &[ProblemDefinition<T>],
+    share_tree: bool,
+) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, problem) in problems.iter().enumerate() {
+        let existing = share_tree
+            .then(|| {
+                groups
+                    .iter_mut()
+                    .find(|group| problems[group[0]].start == problem.start)
+            })
+            .flatten();
+        match existing {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+    groups
+}
+
+/// Plans between many start and goal pairs in the same environment using RRT.
+///
+/// Every problem shares the same extension and connectable functions and [`RrtOptions`].
+/// Since each problem needs its own source of samples, `make_sampler` is called with the
+/// index of the problem to construct a sampling function for it, e.g. an RNG seeded with
+/// the index. Likewise `make_termination` constructs a fresh termination condition for
+/// every problem.
+///
+/// If [`BatchOptions::share_tree`] is set, problems with the same start are planned in
+/// order with a single tree and sampler, created with the index of the first such problem.
+/// Groups of problems are distributed across [`BatchOptions::threads`] threads.
+///
+/// Results are returned in the same order as `problems`, use [`BatchSummary`] for
+/// aggregate statistics.
+///
+/// # Panics
+///
+/// If a user provided function panics, no further problems are started and the original
+/// panic is resumed on the calling thread with its payload unchanged.
+pub fn plan_batch<T, MS, FS, FE, FC, MT, TC>(
+    problems: &[ProblemDefinition<T>],
+    make_sampler: MS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    make_termination: MT,
+    batch: &BatchOptions,
+) -> Vec<PlanningResult<T>>
+where
+    T: Eq + Clone + Hash + Distance + Send + Sync,
+    MS: Fn(usize) -> FS + Sync,
+    FS: FnMut() -> T,
+    FE: Fn(&T, &T) -> T + Sync,
+    FC: Fn(&T, &T) -> bool + Sync,
+    MT: Fn() -> TC + Sync,
+    TC: TerminationCondition,
+{
+    let groups = group_problems(problems, batch.share_tree);
+    let results: Mutex<Vec<Option<PlanningResult<T>>>> =
+        Mutex::new((0..problems.len()).map(|_| None).collect());
+
+    // Plans every problem in a group with a shared tree
+    let plan_group = |group: &[usize]| {
+        let first = &problems[group[0]];
+        let mut tree = HashTree::new(first.start.clone());
+        let mut sample_fn = make_sampler(group[0]);
+        for &i in group {
+            let problem = &problems[i];
+            if !batch.share_tree {
+                tree = HashTree::new(problem.start.clone());
+            }
+
+            let start_time = Instant::now();
+            let mut stats = PlannerStats::new();
+            grow_tree(
+                &mut tree,
+                &problem.goal,
+                &mut sample_fn,
+                &extend_fn,
+                &connectable_fn,
+                options,
+                make_termination(),
+                &mut stats,
+            );
+
+            let result = PlanningResult {
+                path: solution_path(&tree, &problem.goal, options, &stats),
+                cost: tree
+                    .cost(&problem.goal)
+                    .ok()
+                    .filter(|&cost| cost <= options.max_cost),
+                elapsed: start_time.elapsed(),
+                stats,
+            };
+            results.lock().unwrap_or_else(PoisonError::into_inner)[i] = Some(result);
+        }
+    };
+
+    // Worker threads pull groups off of a shared counter until they are exhausted, a panic
+    // exhausts it for every other worker too
+    let next_group = AtomicUsize::new(0);
+    let worker = || loop {
+        let i = next_group.fetch_add(1, Ordering::Relaxed);
+        let Some(group) = groups.get(i) else {
+            break;
+        };
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| plan_group(group))) {
+            next_group.store(groups.len(), Ordering::Relaxed);
+            panic::resume_unwind(payload);
+        }
+    };
+
+    let threads = batch.threads.clamp(1, groups.len().max(1));
+    if threads == 1 {
+        worker();
+    } else {
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads).map(|_| scope.spawn(worker)).collect();
+            for handle in handles {
+                if let Err(payload) = handle.join() {
+                    panic::resume_unwind(payload);
+                }
+            }
+        });
+    }
+
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .map(|result| result.expect("every problem is planned"))
+        .collect()
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::termination::{MaxIterations, SolutionFound};
+
+    #[test]
+    fn test_group_problems() {
+        let problems = vec![
+            ProblemDefinition::new(0, 5),
+            ProblemDefinition::new(1, 5),
+            ProblemDefinition::new(0, -5),
+        ];
+        assert_eq!(
+            group_problems(&problems, false),
+            vec![vec![0], vec![1], vec![2]]
+        );
+        assert_eq!(group_problems(&problems, true), vec![vec![0, 2], vec![1]]);
+    }
+
+    fn run_batch(batch: &BatchOptions) -> Vec<PlanningResult<i32>> {
+        let problems = vec![
+            ProblemDefinition::new(0, 5),
+            ProblemDefinition::new(0, -3),
+            ProblemDefinition::new(10, 12),
+            ProblemDefinition::new(0, 100),
+        ];
+
+        // Sample back and forth between -10 and 20, offset by the problem index
+        let make_sampler = |i: usize| {
+            let mut samples = [-10, 20].into_iter().cycle().skip(i);
+            move || samples.next().unwrap()
+        };
+        let extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let connectable_fn = |from: &i32, to: &i32| (to - from).abs() <= 1;
+        let options = RrtOptions::default();
+        let make_termination = || MaxIterations(100).or(SolutionFound);
+
+        plan_batch(
+            &problems,
+            make_sampler,
+            extend_fn,
+            connectable_fn,
+            &options,
+            make_termination,
+            batch,
+        )
+    }
+
+    #[test]
+    fn test_plan_batch() {
+        for threads in [1, 3] {
+            let results = run_batch(&BatchOptions {
+                threads,
+                share_tree: false,
+            });
+            assert_eq!(results.len(), 4);
+            assert_eq!(results[0].path.as_ref().unwrap(), &vec![0, 1, 2, 3, 4, 5]);
+            assert_eq!(results[1].path.as_ref().unwrap(), &vec![0, -1, -2, -3]);
+            assert_eq!(results[2].path.as_ref().unwrap(), &vec![10, 11, 12]);
+            assert!(results[3].path.is_err());
+            assert!(results[0]
+                .to_string()
+                .starts_with("success: 6 states, cost 5.0000, "));
+            let failure = results[3].to_string();
+            assert!(failure.starts_with("failure: "));
+            assert!(failure.contains(", 100 iterations in "));
+
+            let summary = BatchSummary::from_results(&results);
+            assert_eq!(summary.succeeded, 3);
+            assert_eq!(summary.failed, 1);
+            assert!((summary.success_rate() - 0.75).abs() < 1e-9);
+            assert!((summary.mean_cost.unwrap() - 10.0 / 3.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_plan_batch_shared_tree() {
+        let results = run_batch(&BatchOptions {
+            threads: 2,
+            share_tree: true,
+        });
+
+        // The second problem reuses the tree built for the first, which already reaches
+        // down to -3 while sampling back and forth.
+        assert_eq!(results[1].path.as_ref().unwrap(), &vec![0, -1, -2, -3]);
+        assert_eq!(results[1].stats.goal_rejections.len(), 0);
+        assert!(results[0].is_success());
+        assert!(results[2].is_success());
+        assert!(results[3].path.is_err());
+    }
+
+    #[test]
+    fn test_plan_batch_panic() {
+        // A panic planning one problem reaches the caller unchanged
+        for threads in [1, 3] {
+            let problems: Vec<_> = (0..6).map(|i| ProblemDefinition::new(i, 10)).collect();
+            let make_sampler = |i: usize| {
+                move || {
+                    assert!(i != 4, "sampler failed");
+                    10
+                }
+            };
+            let result = panic::catch_unwind(|| {
+                plan_batch(
+                    &problems,
+                    make_sampler,
+                    |from: &i32, to: &i32| from + (to - from).signum(),
+                    |from: &i32, to: &i32| (to - from).abs() <= 1,
+                    &RrtOptions::default(),
+                    || MaxIterations(100).or(SolutionFound),
+                    &BatchOptions {
+                        threads,
+                        share_tree: false,
+                    },
+                )
+            });
+            let payload = result.unwrap_err();
+            assert_eq!(payload.downcast_ref::<&str>(), Some(&"sampler failed"));
+        }
+    }
+}
@@ -0,0 +1,1005 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::clock::Instant;
+use crate::error::{PlanningError, TreeError};
+use crate::planning::rrt::{
+    add_path, extend_towards, extend_tree, rewire_added, within_extension, ConnectLimit, RrtOptions,
+};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::{PlannerProgress, TerminationCondition};
+use crate::tree::{Distance, HashTree};
+use std::hash::Hash;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// Index of the tree rooted at the start, the tree rooted at the goal is the other one.
+const START_TREE: usize = 0;
+
+/// Which of the two trees of a bidirectional planner a node belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeOrigin {
+    /// The tree rooted at the start.
+    Start,
+
+    /// The tree rooted at the goal.
+    Goal,
+}
+
+/// Both trees grown by a bidirectional planner.
+#[derive(Debug)]
+pub struct BidirectionalTrees<T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    /// The tree rooted at the start, containing the start half of the path.
+    pub start: HashTree<T>,
+
+    /// The tree rooted at the goal, containing the goal half of the path. Its edges point
+    /// away from the goal, i.e. against the direction of travel.
+    pub goal: HashTree<T>,
+
+    /// The node of the start tree and the node of the goal tree that were joined. These
+    /// are the same state if the trees met exactly.
+    pub connection: (T, T),
+}
+
+impl<T> BidirectionalTrees<T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    /// Returns which tree the state was added to, preferring the start tree for the
+    /// state shared by both trees when they met exactly.
+    #[must_use]
+    pub fn origin(&self, state: &T) -> Option<TreeOrigin> {
+        if self.start.contains(state) {
+            Some(TreeOrigin::Start)
+        } else if self.goal.contains(state) {
+            Some(TreeOrigin::Goal)
+        } else {
+            None
+        }
+    }
+
+    /// Merges the goal tree into the start tree, so that both trees are a single tree
+    /// rooted at the start.
+    ///
+    /// The goal tree is re-rooted at its half of the connection, which becomes a child of
+    /// the start tree's half, so the path to the goal in the merged tree is the solution
+    /// path. Any other state that is in both trees stays where it is in the start tree,
+    /// along with its descendants from the goal tree.
+    ///
+    /// # Errors
+    ///
+    /// If a state on the goal half of the path is also in the start tree.
+    pub fn into_tree(self) -> Result<HashTree<T>, TreeError> {
+        let BidirectionalTrees {
+            mut start,
+            goal,
+            connection: (from, to),
+        } = self;
+
+        // Edges between the connection and the goal are reversed
+        let mut parent = from;
+        for node in goal.path(&to)?.into_iter().rev() {
+            if node != parent {
+                start.add_child(&parent, node.clone())?;
+            }
+            parent = node;
+        }
+
+        // Everything else keeps its parent from the goal tree
+        for node in goal.iter_insertion_order() {
+            let mut chain = Vec::new();
+            let mut current = node;
+            while !start.contains(current) {
+                chain.push(current.clone());
+                current = goal.get_parent(current).ok_or(TreeError::ParentNotFound)?;
+            }
+            let mut parent = current.clone();
+            for child in chain.into_iter().rev() {
+                start.add_child(&parent, child.clone())?;
+                parent = child;
+            }
+        }
+        Ok(start)
+    }
+}
+
+/// Orients a motion validator for a tree.
+///
+/// Edges in the goal tree are traversed from child to parent, so the validator is queried
+/// with its arguments swapped to always check motions in the direction of travel.
+fn directed<T, FC>(connectable: &mut FC, tree: usize) -> impl FnMut(&T, &T) -> bool + '_
+where
+    FC: FnMut(&T, &T) -> bool,
+{
+    move |from: &T, to: &T| {
+        if tree == START_TREE {
+            connectable(from, to)
+        } else {
+            connectable(to, from)
+        }
+    }
+}
+
+/// Joins the start tree path to `from` with the goal tree path from `to`.
+fn join_path<T>(trees: &[HashTree<T>; 2], from: &T, to: &T) -> Result<Vec<T>, TreeError>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    let mut path = trees[START_TREE].path(from)?;
+    let mut rest = trees[1 - START_TREE].path(to)?;
+    rest.reverse();
+    if from == to {
+        rest.remove(0);
+    }
+    path.extend(rest);
+    Ok(path)
+}
+
+/// Builds the path and result trees once the trees are joined between `from` and `to`.
+fn into_solution<T>(
+    trees: [HashTree<T>; 2],
+    from: T,
+    to: T,
+) -> Result<(Vec<T>, BidirectionalTrees<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    let path = join_path(&trees, &from, &to)?;
+    let [start, goal] = trees;
+    Ok((
+        path,
+        BidirectionalTrees {
+            start,
+            goal,
+            connection: (from, to),
+        },
+    ))
+}
+
+/// Extends the `active` tree towards the sample, then greedily extends the other tree
+/// towards the new node.
+///
+/// Returns the pair of nodes joining the trees, ordered start tree first, if they connected.
+fn extend_and_connect<T, FE, FC>(
+    trees: &mut [HashTree<T>; 2],
+    active: usize,
+    sample: T,
+    extend: &mut FE,
+    connectable: &mut FC,
+    options: &RrtOptions,
+    stats: &mut PlannerStats<T>,
+) -> Option<(T, T)>
+where
+    T: Eq + Clone + Hash + Distance,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let (new_points, nearest) = {
+        let mut valid = directed(connectable, active);
+        let mut reachable = within_extension(&mut valid, options.max_extension);
+        extend_tree(
+            &trees[active],
+            sample,
+            extend,
+            &mut reachable,
+            options.use_rrtconnect.then_some(&options.connect_limit),
+        )
+    };
+    let added = add_path(
+        &mut trees[active],
+        &nearest,
+        new_points,
+        options.duplicate_policy,
+        stats,
+    );
+    let target = added.last()?.clone();
+
+    let other = 1 - active;
+    let mut valid = directed(connectable, other);
+    let mut reachable = within_extension(&mut valid, options.max_extension);
+    let nearest = trees[other].nearest_neighbor(&target).clone();
+    let path = extend_towards(
+        &nearest,
+        &target,
+        extend,
+        &mut reachable,
+        &ConnectLimit::default(),
+    );
+    let added = add_path(
+        &mut trees[other],
+        &nearest,
+        path,
+        options.duplicate_policy,
+        stats,
+    );
+    let last = added.last().unwrap_or(&nearest).clone();
+    let joined = if other == START_TREE {
+        (last.clone(), target.clone())
+    } else {
+        (target.clone(), last.clone())
+    };
+    reachable(&last, &target).then_some(joined)
+}
+
+/// Bidirectional RRT-Connect, growing one tree from the start and one from the goal.
+///
+/// Every iteration one tree is extended towards a sample as configured by `options`, then
+/// the other tree greedily extends towards the newly added node. The trees alternate roles
+/// each iteration and planning stops as soon as they connect, so unlike
+/// [`crate::planning::rrt::rrt_with_termination`] the path is not optimized further and
+/// `use_rrtstar` is ignored, see [`rrt_bidirectional_star`] for an optimizing variant.
+/// Motions in the goal tree are validated in the direction of travel towards the goal.
+///
+/// Returns the path along with both trees.
+///
+/// # Errors
+///
+/// If the trees did not connect before terminating.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_bidirectional<T, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    mut sample_fn: FS,
+    mut extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, BidirectionalTrees<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut termination = options.cancellable(termination);
+    let mut trees = [HashTree::new(start.clone()), HashTree::new(goal.clone())];
+    for tree in &mut trees {
+        tree.set_tie_break(options.tie_break);
+    }
+
+    let start_time = Instant::now();
+    let mut active = START_TREE;
+    for iteration in 0.. {
+        let progress = PlannerProgress {
+            iteration,
+            elapsed: start_time.elapsed(),
+            best_cost: None,
+        };
+        if termination.should_terminate(&progress) {
+            stats.stop_reason = termination.stop_reason(&progress);
+            break;
+        }
+        stats.iterations += 1;
+
+        let joined = extend_and_connect(
+            &mut trees,
+            active,
+            sample_fn(),
+            &mut extend_fn,
+            &mut connectable_fn,
+            options,
+            stats,
+        );
+        if let Some((from, to)) = joined {
+            return into_solution(trees, from, to);
+        }
+        active = 1 - active;
+    }
+
+    Err(PlanningError::not_found(stats.stop_reason))
+}
+
+/// Bidirectional RRT-Connect, returning a single tree that contains both trees.
+///
+/// Plans as [`rrt_bidirectional`] until the termination condition from
+/// [`RrtOptions::termination`] is met, then merges the trees with
+/// [`BidirectionalTrees::into_tree`], so the result can be used wherever the result of
+/// [`crate::planning::rrt::plan`] is. Unlike [`RrtOptions::use_rrtconnect`], which only
+/// greedily extends a single tree, this grows trees from both ends and tries to join them
+/// every iteration.
+///
+/// # Errors
+///
+/// If the trees did not connect before terminating.
+pub fn rrt_connect<T, FS, FE, FC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let (path, trees) = rrt_bidirectional(
+        start,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        options.termination(),
+        &mut PlannerStats::new(),
+    )?;
+    Ok((path, trees.into_tree()?))
+}
+
+/// Adds a chain of states below `parent` in the tree with the given index, rewiring around
+/// each new node if using RRT*. Returns the nodes of the chain that are in the tree.
+fn add_rewired<T, FC>(
+    tree: &mut HashTree<T>,
+    index: usize,
+    parent: &T,
+    chain: Vec<T>,
+    connectable: &mut FC,
+    options: &RrtOptions,
+    stats: &mut PlannerStats<T>,
+) -> Vec<T>
+where
+    T: Eq + Clone + Hash + Distance,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let size = tree.size();
+    let added = add_path(tree, parent, chain, options.duplicate_policy, stats);
+    if options.use_rrtstar {
+        let radius = options.rewire_radius_at(tree.size());
+        let mut valid = directed(connectable, index);
+        rewire_added(tree, &mut valid, size, &added, radius, stats);
+    }
+    added
+}
+
+/// Returns the cost of the path through the start tree to `from`, across to `to`, and
+/// through the goal tree to the goal.
+fn connection_cost<T>(trees: &[HashTree<T>; 2], from: &T, to: &T) -> f64
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    let start_cost = trees[START_TREE].cost(from).unwrap_or(f64::INFINITY);
+    let goal_cost = trees[1 - START_TREE].cost(to).unwrap_or(f64::INFINITY);
+    start_cost + from.distance(to) + goal_cost
+}
+
+/// Returns the cheapest valid connection between `target` in the `active` tree and its
+/// neighbors in the other tree, plus `last`, if it is cheaper than `best_cost`. Pairs are
+/// ordered start tree first.
+#[allow(clippy::too_many_arguments)]
+fn cheaper_connection<T, FC>(
+    trees: &[HashTree<T>; 2],
+    active: usize,
+    target: &T,
+    last: T,
+    connectable: &mut FC,
+    options: &RrtOptions,
+    best_cost: f64,
+) -> Option<(T, T)>
+where
+    T: Eq + Clone + Hash + Distance,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let other = &trees[1 - active];
+    let radius = options.rewire_radius_at(other.size());
+    let mut candidates: Vec<(T, T, f64)> = other
+        .neighbors_within(target, radius)
+        .into_iter()
+        .map(|(neighbor, _)| neighbor.clone())
+        .chain(std::iter::once(last))
+        .map(|neighbor| {
+            let (from, to) = if active == START_TREE {
+                (target.clone(), neighbor)
+            } else {
+                (neighbor, target.clone())
+            };
+            let cost = connection_cost(trees, &from, &to);
+            (from, to, cost)
+        })
+        .filter(|(_, _, cost)| *cost < best_cost)
+        .collect();
+    candidates.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let mut reachable = within_extension(connectable, options.max_extension);
+    candidates
+        .into_iter()
+        .find(|(from, to, _)| from == to || reachable(from, to))
+        .map(|(from, to, _)| (from, to))
+}
+
+/// Bidirectional RRT*, growing one tree from the start and one from the goal and keeping
+/// the cheapest connection between them.
+///
+/// Every iteration one tree is extended towards a sample, then the other tree greedily
+/// extends towards the newly added node as in [`rrt_bidirectional`]. Both trees are
+/// rewired around their new nodes if `use_rrtstar` is set, and the new node is connected
+/// to the other tree through whichever of its neighbors within the rewire radius gives
+/// the cheapest path. Unlike [`rrt_bidirectional`] planning continues after the trees
+/// first connect, so the path keeps improving until the termination condition is met,
+/// which sees the cost of the best connection so far.
+///
+/// Returns the cheapest path found along with both trees.
+///
+/// # Errors
+///
+/// If the trees did not connect before terminating.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_bidirectional_star<T, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    mut sample_fn: FS,
+    mut extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, BidirectionalTrees<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut termination = options.cancellable(termination);
+    let mut trees = [HashTree::new(start.clone()), HashTree::new(goal.clone())];
+    for tree in &mut trees {
+        tree.set_tie_break(options.tie_break);
+    }
+    let (extend, connectable) = (&mut extend_fn, &mut connectable_fn);
+
+    let start_time = Instant::now();
+    let mut best: Option<(T, T)> = None;
+    let mut active = START_TREE;
+    for iteration in 0.. {
+        // Rewiring may have made the best connection cheaper since it was found
+        let best_cost = best
+            .as_ref()
+            .map(|(from, to)| connection_cost(&trees, from, to));
+        let progress = PlannerProgress {
+            iteration,
+            elapsed: start_time.elapsed(),
+            best_cost,
+        };
+        if termination.should_terminate(&progress) {
+            stats.stop_reason = termination.stop_reason(&progress);
+            break;
+        }
+        stats.iterations += 1;
+
+        let (new_points, nearest) = {
+            let mut valid = directed(connectable, active);
+            let mut reachable = within_extension(&mut valid, options.max_extension);
+            extend_tree(
+                &trees[active],
+                sample_fn(),
+                extend,
+                &mut reachable,
+                options.use_rrtconnect.then_some(&options.connect_limit),
+            )
+        };
+        let tree = &mut trees[active];
+        let added = add_rewired(
+            tree,
+            active,
+            &nearest,
+            new_points,
+            connectable,
+            options,
+            stats,
+        );
+        if let Some(target) = added.last() {
+            let other = 1 - active;
+            let nearest = trees[other].nearest_neighbor(target).clone();
+            let path = {
+                let mut valid = directed(connectable, other);
+                let mut reachable = within_extension(&mut valid, options.max_extension);
+                let limit = ConnectLimit::default();
+                extend_towards(&nearest, target, extend, &mut reachable, &limit)
+            };
+            let tree = &mut trees[other];
+            let added = add_rewired(tree, other, &nearest, path, connectable, options, stats);
+            let last = added.last().unwrap_or(&nearest).clone();
+
+            let best_cost = best_cost.unwrap_or(f64::INFINITY);
+            let cheaper = cheaper_connection(
+                &trees,
+                active,
+                target,
+                last,
+                connectable,
+                options,
+                best_cost,
+            );
+            best = cheaper.or(best);
+        }
+        active = 1 - active;
+    }
+
+    match best {
+        Some((from, to)) => into_solution(trees, from, to),
+        None => Err(PlanningError::not_found(stats.stop_reason)),
+    }
+}
+
+/// Bidirectional RRT-Connect with each tree grown on its own thread.
+///
+/// Each thread extends its tree towards its own samples as configured by `options`, then
+/// greedily extends from the new node towards the nearest node of the other tree. Threads
+/// only see each other's trees through snapshots that are published every
+/// `sync_interval` iterations, which costs a copy of the tree's states but lets the trees
+/// grow without contention. Planning stops as soon as either thread connects the trees.
+///
+/// `make_sampler` is called with 0 for the start tree and 1 for the goal tree, and
+/// `make_termination` is called once for each thread. Otherwise behaves like
+/// [`rrt_bidirectional`], which is the better choice for short queries.
+///
+/// # Errors
+///
+/// If the trees did not connect before both threads terminated.
+///
+/// # Panics
+///
+/// If a user provided function panics on either thread, the other thread is stopped and
+/// the original panic is resumed on the calling thread with its payload unchanged.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_bidirectional_threaded<T, MS, FS, FE, FC, MT, TC>(
+    start: &T,
+    goal: &T,
+    make_sampler: MS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    make_termination: MT,
+    sync_interval: u64,
+) -> Result<(Vec<T>, BidirectionalTrees<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance + Send + Sync,
+    MS: Fn(usize) -> FS + Sync,
+    FS: FnMut() -> T,
+    FE: Fn(&T, &T) -> T + Sync,
+    FC: Fn(&T, &T) -> bool + Sync,
+    MT: Fn() -> TC + Sync,
+    TC: TerminationCondition,
+{
+    let snapshots: [Mutex<Vec<T>>; 2] = [
+        Mutex::new(vec![start.clone()]),
+        Mutex::new(vec![goal.clone()]),
+    ];
+    let solution: Mutex<Option<(T, T)>> = Mutex::new(None);
+    let done = AtomicBool::new(false);
+
+    let worker = |index: usize, root: &T| {
+        let _stop = StopOnPanic(&done);
+        let mut tree = HashTree::new(root.clone());
+        tree.set_tie_break(options.tie_break);
+        let mut sample_fn = make_sampler(index);
+        let mut termination = options.cancellable(make_termination());
+        let mut extend = &extend_fn;
+        let mut connectable = &connectable_fn;
+        let mut valid = directed(&mut connectable, index);
+        let mut reachable = within_extension(&mut valid, options.max_extension);
+        let mut stats = PlannerStats::new();
+        let start_time = Instant::now();
+
+        for iteration in 0.. {
+            let progress = PlannerProgress {
+                iteration,
+                elapsed: start_time.elapsed(),
+                best_cost: None,
+            };
+            if done.load(Ordering::Relaxed) {
+                break;
+            }
+            if termination.should_terminate(&progress) {
+                stats.stop_reason = termination.stop_reason(&progress);
+                break;
+            }
+            if iteration % sync_interval.max(1) == 0 {
+                *lock(&snapshots[index]) = tree.iter_depth_first().cloned().collect();
+            }
+
+            let (new_points, nearest) = extend_tree(
+                &tree,
+                sample_fn(),
+                &mut extend,
+                &mut reachable,
+                options.use_rrtconnect.then_some(&options.connect_limit),
+            );
+            let added = add_path(
+                &mut tree,
+                &nearest,
+                new_points,
+                options.duplicate_policy,
+                &mut stats,
+            );
+            let Some(new_node) = added.last().cloned() else {
+                continue;
+            };
+
+            // Head for the nearest node in the other tree's latest snapshot
+            let Some(target) = lock(&snapshots[1 - index])
+                .iter()
+                .min_by(|a, b| new_node.distance(a).total_cmp(&new_node.distance(b)))
+                .cloned()
+            else {
+                continue;
+            };
+            let path = extend_towards(
+                &new_node,
+                &target,
+                &mut extend,
+                &mut reachable,
+                &ConnectLimit::default(),
+            );
+            let added = add_path(
+                &mut tree,
+                &new_node,
+                path,
+                options.duplicate_policy,
+                &mut stats,
+            );
+            let last = added.last().unwrap_or(&new_node).clone();
+            if reachable(&last, &target) {
+                let mut solution = lock(&solution);
+                if solution.is_none() {
+                    *solution = Some(if index == START_TREE {
+                        (last, target)
+                    } else {
+                        (target, last)
+                    });
+                }
+                done.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+        (tree, stats.stop_reason)
+    };
+
+    let ((start_tree, start_reason), (goal_tree, goal_reason)) = thread::scope(|scope| {
+        let start_tree = scope.spawn(|| worker(START_TREE, start));
+        let goal_tree = scope.spawn(|| worker(1 - START_TREE, goal));
+        (join(start_tree), join(goal_tree))
+    });
+
+    match solution
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+    {
+        Some((from, to)) => into_solution([start_tree, goal_tree], from, to),
+        None => Err(PlanningError::not_found(start_reason.or(goal_reason))),
+    }
+}
+
+// Locks a mutex shared with the other worker, which stays usable if that worker panicked
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Joins a worker, resuming its panic on the calling thread
+fn join<T>(handle: thread::ScopedJoinHandle<'_, T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+// Stops the other worker of a threaded planner if this one panics
+struct StopOnPanic<'a>(&'a AtomicBool);
+
+impl Drop for StopOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::FloatArray;
+    use crate::planning::termination::MaxIterations;
+    use crate::planning::tuning::SplitMix64;
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn extend_fn(from: &i32, to: &i32) -> i32 {
+        from + (to - from).signum()
+    }
+
+    // Only allow single steps, and never step downwards out of 5 to check that motions
+    // in the goal tree are validated in the direction of travel.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn connectable_fn(from: &i32, to: &i32) -> bool {
+        (to - from).abs() <= 1 && !(*from == 5 && *to == 4)
+    }
+
+    fn assert_valid_path(path: &[i32], start: i32, goal: i32) {
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path
+            .windows(2)
+            .all(|pair| connectable_fn(&pair[0], &pair[1])));
+    }
+
+    #[test]
+    fn test_directed() {
+        let mut connectable = connectable_fn;
+        assert!(directed(&mut connectable, START_TREE)(&4, &5));
+        assert!(!directed(&mut connectable, 1 - START_TREE)(&4, &5));
+    }
+
+    #[test]
+    fn test_into_tree() {
+        let mut start = HashTree::new(0);
+        start.add_child(&0, 1).unwrap();
+        start.add_child(&0, -1).unwrap();
+        let mut goal = HashTree::new(4);
+        goal.add_child(&4, 3).unwrap();
+        goal.add_child(&3, 2).unwrap();
+        goal.add_child(&4, 5).unwrap();
+        goal.add_child(&3, -1).unwrap();
+        goal.add_child(&-1, -2).unwrap();
+        let trees = BidirectionalTrees {
+            start,
+            goal,
+            connection: (1, 2),
+        };
+
+        // The goal half of the path is reversed and branches keep their parents, except
+        // for the state in both trees which stays in the start tree
+        let tree = trees.into_tree().unwrap();
+        assert_eq!(tree.size(), 8);
+        assert_eq!(tree.path(&5).unwrap(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(tree.get_parent(&-1), Some(&0));
+        assert_eq!(tree.get_parent(&-2), Some(&-1));
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_rrt_connect() {
+        let mut samples = [7, -10].into_iter().cycle();
+        let options = RrtOptions::default().with_max_extension(1.0);
+        let (path, tree) = rrt_connect(
+            &0,
+            &10,
+            || samples.next().unwrap(),
+            extend_fn,
+            connectable_fn,
+            &options,
+        )
+        .unwrap();
+        assert_valid_path(&path, 0, 10);
+        assert_eq!(tree.root(), &0);
+        assert_eq!(tree.path(&10).unwrap(), path);
+        assert!(tree.check_invariants().is_ok());
+
+        let blocked = |from: &i32, to: &i32| connectable_fn(from, to) && *to != 5;
+        let options = options.with_max_iterations(50);
+        assert_eq!(
+            rrt_connect(&0, &10, || 7, extend_fn, blocked, &options).map(|(path, _)| path),
+            Err(PlanningError::MaxIterationsReached)
+        );
+    }
+
+    #[test]
+    fn test_rrt_bidirectional() {
+        // The start tree connects to the first sample and the goal tree connects to that
+        let mut samples = [7, -10].into_iter().cycle();
+        let options = RrtOptions {
+            max_extension: 1.0,
+            use_rrtconnect: true,
+            ..RrtOptions::default()
+        };
+        let (path, trees) = rrt_bidirectional(
+            &0,
+            &10,
+            || samples.next().unwrap(),
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(10),
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+        assert_eq!(path, (0..=10).collect::<Vec<_>>());
+        assert_eq!(trees.start.size(), 8);
+        assert_eq!(trees.goal.size(), 3);
+        assert_eq!(trees.connection, (7, 8));
+        assert_eq!(trees.origin(&7), Some(TreeOrigin::Start));
+        assert_eq!(trees.origin(&8), Some(TreeOrigin::Goal));
+        assert_eq!(trees.origin(&-1), None);
+
+        // Going down through 5 is never valid
+        let mut samples = [7, -10].into_iter().cycle();
+        let result = rrt_bidirectional(
+            &10,
+            &0,
+            || samples.next().unwrap(),
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(10),
+            &mut PlannerStats::new(),
+        );
+        assert_eq!(
+            result.map(|(path, _)| path),
+            Err(PlanningError::MaxIterationsReached)
+        );
+    }
+
+    #[test]
+    fn test_rrt_bidirectional_star() {
+        // A disc between the corners, which the first connection rarely passes closely
+        let mut rng = SplitMix64(11);
+        let sample_fn = || FloatArray([rng.next_f64() * 10.0, rng.next_f64() * 10.0]);
+        let extend_fn = |from: &FloatArray<2>, to: &FloatArray<2>| {
+            let scale = (1.0 / from.distance(to)).min(1.0);
+            FloatArray(std::array::from_fn(|i| {
+                from.0[i] + (to.0[i] - from.0[i]) * scale
+            }))
+        };
+        let options = RrtOptions {
+            max_extension: 1.0,
+            use_rrtstar: true,
+            rewire_radius: 2.0,
+            ..RrtOptions::default()
+        };
+
+        // The best cost only ever improves once the trees connect
+        let mut costs = Vec::new();
+        let termination = |progress: &PlannerProgress| {
+            costs.extend(progress.best_cost);
+            progress.iteration >= 1000
+        };
+        let (start, goal) = (FloatArray([0.0, 0.0]), FloatArray([10.0, 10.0]));
+        let (path, trees) = rrt_bidirectional_star(
+            &start,
+            &goal,
+            sample_fn,
+            extend_fn,
+            |from: &FloatArray<2>, to: &FloatArray<2>| {
+                let mid = FloatArray(std::array::from_fn(|i| f64::midpoint(from.0[i], to.0[i])));
+                let obstacle = FloatArray([5.0, 5.0]);
+                from.distance(to) <= 1.0 + 1e-9
+                    && to.distance(&obstacle) > 2.0
+                    && mid.distance(&obstacle) > 2.0
+            },
+            &options,
+            termination,
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(costs.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(costs[costs.len() - 1] < costs[0]);
+
+        // The shortest path around the disc is about 14.7 long
+        let cost: f64 = path.windows(2).map(|pair| pair[0].distance(&pair[1])).sum();
+        assert!(cost < costs[0] && cost < 1.15 * 14.7, "cost {cost}");
+        let (from, to) = &trees.connection;
+        assert!(trees.start.contains(from) && trees.goal.contains(to));
+
+        // Going down through 5 is never valid
+        let options = RrtOptions {
+            max_extension: 1.0,
+            use_rrtstar: true,
+            ..RrtOptions::default()
+        };
+        let result = rrt_bidirectional_star(
+            &10,
+            &0,
+            || 7,
+            self::extend_fn,
+            self::connectable_fn,
+            &options,
+            MaxIterations(10),
+            &mut PlannerStats::new(),
+        );
+        assert_eq!(
+            result.map(|(path, _)| path),
+            Err(PlanningError::MaxIterationsReached)
+        );
+    }
+
+    #[test]
+    fn test_rrt_bidirectional_threaded() {
+        // Each tree samples away from the other, so they only meet by extending towards
+        // each other's snapshots
+        let make_sampler = |index: usize| {
+            let sample = if index == START_TREE { -10 } else { 20 };
+            move || sample
+        };
+        let options = RrtOptions {
+            max_extension: 1.0,
+            ..RrtOptions::default()
+        };
+        let (path, trees) = rrt_bidirectional_threaded(
+            &0,
+            &10,
+            make_sampler,
+            extend_fn,
+            connectable_fn,
+            &options,
+            || MaxIterations(100),
+            5,
+        )
+        .unwrap();
+        assert_valid_path(&path, 0, 10);
+        assert_eq!(trees.start.get_parent(&-1), Some(&0));
+        let (from, to) = &trees.connection;
+        assert!(trees.start.contains(from));
+        assert!(trees.goal.contains(to));
+
+        let result = rrt_bidirectional_threaded(
+            &10,
+            &0,
+            make_sampler,
+            extend_fn,
+            connectable_fn,
+            &options,
+            || MaxIterations(100),
+            5,
+        );
+        assert_eq!(
+            result.map(|(path, _)| path),
+            Err(PlanningError::MaxIterationsReached)
+        );
+    }
+
+    #[test]
+    fn test_rrt_bidirectional_threaded_panic() {
+        // The start tree can never connect, so it only stops once the goal tree panics
+        let make_sampler = |index: usize| {
+            move || {
+                assert!(index == START_TREE, "goal sampler failed");
+                -10
+            }
+        };
+        let result = panic::catch_unwind(|| {
+            rrt_bidirectional_threaded(
+                &0,
+                &10,
+                make_sampler,
+                extend_fn,
+                |_: &i32, to: &i32| *to <= 0,
+                &RrtOptions::default(),
+                || MaxIterations(u64::MAX),
+                5,
+            )
+        });
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"goal sampler failed"));
+    }
+}
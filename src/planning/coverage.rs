@@ -0,0 +1,284 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::CoverageError;
+use crate::grid::OccupancyGrid;
+
+/// A cell of the boustrophedon decomposition of free space.
+///
+/// Each cell spans a contiguous range of grid columns with a single run of free rows in
+/// every column, so it can be covered by sweeping back and forth without obstacles in
+/// between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageCell {
+    /// First grid column of the cell.
+    pub first_column: usize,
+
+    /// Inclusive range of free rows in each column of the cell, starting at `first_column`.
+    pub rows: Vec<(usize, usize)>,
+}
+
+impl CoverageCell {
+    /// Last grid column of the cell.
+    #[must_use]
+    pub fn last_column(&self) -> usize {
+        self.first_column + self.rows.len() - 1
+    }
+}
+
+// Inclusive ranges of consecutive free rows in a grid column
+fn free_runs(grid: &OccupancyGrid, column: usize) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for row in 0..=grid.height() {
+        match (start, grid.is_cell_free(column, row)) {
+            (None, true) => start = Some(row),
+            (Some(first), false) => {
+                runs.push((first, row - 1));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+fn overlaps(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+/// Decomposes the free space of the grid into boustrophedon cells.
+///
+/// Columns are scanned from left to right. A cell continues into the next column as long as
+/// its run of free rows connects to exactly one run in the next column and vice versa, any
+/// split or merge caused by an obstacle closes the affected cells and opens new ones. Cells
+/// are returned in the order they are opened.
+#[must_use]
+pub fn decompose(grid: &OccupancyGrid) -> Vec<CoverageCell> {
+    let mut cells: Vec<CoverageCell> = Vec::new();
+
+    // Runs in the previous column and the cell each belongs to
+    let mut open: Vec<((usize, usize), usize)> = Vec::new();
+    for column in 0..grid.width() {
+        let runs = free_runs(grid, column);
+        let mut next_open = Vec::with_capacity(runs.len());
+        for &run in &runs {
+            let previous: Vec<&((usize, usize), usize)> = open
+                .iter()
+                .filter(|(prev, _)| overlaps(*prev, run))
+                .collect();
+            let continues = match previous.as_slice() {
+                [(prev, cell)] => {
+                    (runs.iter().filter(|&&r| overlaps(*prev, r)).count() == 1).then_some(*cell)
+                }
+                _ => None,
+            };
+
+            let cell = if let Some(cell) = continues {
+                cells[cell].rows.push(run);
+                cell
+            } else {
+                cells.push(CoverageCell {
+                    first_column: column,
+                    rows: vec![run],
+                });
+                cells.len() - 1
+            };
+            next_open.push((run, cell));
+        }
+        open = next_open;
+    }
+    cells
+}
+
+/// Returns the lawnmower sweep over a single cell, as world coordinates.
+///
+/// Sweep lines run along the columns of the cell, `stride` columns apart, alternating
+/// between bottom to top and top to bottom.
+fn sweep_cell(grid: &OccupancyGrid, cell: &CoverageCell, stride: usize) -> Vec<(f64, f64)> {
+    let columns = cell.rows.len();
+    let mut points = Vec::new();
+    for (k, offset) in (0..columns).step_by(stride).enumerate() {
+        // Sweep through the middle of the band of columns covered by this pass
+        let offset = (offset + (stride - 1) / 2).min(columns - 1);
+        let (low, high) = cell.rows[offset];
+        let column = cell.first_column + offset;
+        let (first, last) = if k % 2 == 0 { (low, high) } else { (high, low) };
+        points.push(grid.cell_center(column, first));
+        points.push(grid.cell_center(column, last));
+    }
+    points
+}
+
+/// Plans a boustrophedon (lawnmower) path covering all known free space in the grid.
+///
+/// Free space is split into cells with [`decompose`], and each cell is covered with sweep
+/// lines spaced `tool_width` apart, rounded down to a whole number of grid cells. The
+/// sweeps are joined with straight lines wherever the grid allows. Otherwise, including
+/// moving between cells, `connect_fn` is called to plan a path between two points, e.g. with
+/// [`crate::planning::rrt::rrt_with_termination`]. It must return the full path including
+/// both endpoints.
+///
+/// # Errors
+///
+/// If `connect_fn` fails to connect two points, with its error and the points.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn plan_coverage<FP, E>(
+    grid: &OccupancyGrid,
+    tool_width: f64,
+    mut connect_fn: FP,
+) -> Result<Vec<(f64, f64)>, CoverageError<E>>
+where
+    FP: FnMut(&(f64, f64), &(f64, f64)) -> Result<Vec<(f64, f64)>, E>,
+{
+    // Allow for tool widths that are an exact multiple of the resolution
+    let stride = ((tool_width / grid.resolution() + 1e-9).floor() as usize).max(1);
+
+    let mut path: Vec<(f64, f64)> = Vec::new();
+    for cell in decompose(grid) {
+        for point in sweep_cell(grid, &cell, stride) {
+            match path.last() {
+                None => path.push(point),
+                Some(last) if *last == point => {}
+                Some(last) if grid.is_segment_free(*last, point) => path.push(point),
+                Some(last) => {
+                    let connection = connect_fn(last, &point).map_err(|error| CoverageError {
+                        from: *last,
+                        to: point,
+                        error,
+                    })?;
+                    path.extend(connection.into_iter().skip(1));
+                }
+            }
+        }
+    }
+    Ok(path)
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PlanningError;
+    use float_cmp::approx_eq;
+
+    // A 6x4 grid with a 2x2 block in the middle
+    fn block_grid() -> OccupancyGrid {
+        let mut grid = OccupancyGrid::new(6, 4, 1.0);
+        for column in 2..4 {
+            for row in 1..3 {
+                grid.set_occupied(column, row, true).unwrap();
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_decompose() {
+        let cells = decompose(&block_grid());
+        assert_eq!(
+            cells,
+            vec![
+                CoverageCell {
+                    first_column: 0,
+                    rows: vec![(0, 3), (0, 3)]
+                },
+                CoverageCell {
+                    first_column: 2,
+                    rows: vec![(0, 0), (0, 0)]
+                },
+                CoverageCell {
+                    first_column: 2,
+                    rows: vec![(3, 3), (3, 3)]
+                },
+                CoverageCell {
+                    first_column: 4,
+                    rows: vec![(0, 3), (0, 3)]
+                },
+            ]
+        );
+        assert_eq!(cells[3].last_column(), 5);
+
+        assert!(decompose(&OccupancyGrid::new(0, 0, 1.0)).is_empty());
+    }
+
+    #[test]
+    fn test_plan_coverage() {
+        let grid = block_grid();
+
+        // Go around the block through the left hand cell, which is required to reach the top
+        // cell and then the right hand cell
+        let mut connections = 0;
+        let connect_fn = |from: &(f64, f64), to: &(f64, f64)| {
+            connections += 1;
+            Ok::<_, PlanningError>(vec![*from, (1.5, from.1), (1.5, to.1), *to])
+        };
+        let path = plan_coverage(&grid, 1.0, connect_fn).unwrap();
+        assert_eq!(connections, 2);
+
+        assert_eq!(path[..4], [(0.5, 0.5), (0.5, 3.5), (1.5, 3.5), (1.5, 0.5)]);
+        assert_eq!(path.last(), Some(&(5.5, 0.5)));
+        assert!(path
+            .windows(2)
+            .all(|pair| grid.is_segment_free(pair[0], pair[1])));
+
+        // Every free cell is swept
+        for column in 0..grid.width() {
+            for row in 0..grid.height() {
+                let center = grid.cell_center(column, row);
+                let swept = path.windows(2).any(|pair| {
+                    let (a, b) = (pair[0], pair[1]);
+                    let cross = (b.0 - a.0) * (center.1 - a.1) - (b.1 - a.1) * (center.0 - a.0);
+                    approx_eq!(f64, cross, 0.0)
+                        && a.0.min(b.0) <= center.0
+                        && center.0 <= a.0.max(b.0)
+                        && a.1.min(b.1) <= center.1
+                        && center.1 <= a.1.max(b.1)
+                });
+                assert_eq!(swept, grid.is_cell_free(column, row), "{center:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_coverage_tool_width() {
+        let grid = OccupancyGrid::new(6, 2, 0.5);
+        let path = plan_coverage(&grid, 1.5, |_, _| Err(PlanningError::GoalUnreachable)).unwrap();
+        assert_eq!(
+            path,
+            vec![(0.75, 0.25), (0.75, 0.75), (2.25, 0.75), (2.25, 0.25)]
+        );
+
+        let err = plan_coverage(&block_grid(), 1.0, |_, _| Err(PlanningError::Timeout));
+        assert_eq!(
+            err,
+            Err(CoverageError {
+                from: (3.5, 0.5),
+                to: (2.5, 3.5),
+                error: PlanningError::Timeout,
+            })
+        );
+    }
+}
@@ -0,0 +1,545 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::planning::rrt::RrtOptions;
+use crate::planning::stats::PlannerStats;
+use crate::tree::{Distance, HashTree};
+use std::cell::Cell;
+use std::fmt;
+use std::hash::Hash;
+
+/// When the [`Watchdog`] considers a run unhealthy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchdogThresholds {
+    /// Fraction of samples the state validity function may reject before the sampler is
+    /// reported as mismatched with the valid space.
+    pub sample_rejection: f64,
+
+    /// Fraction of motions the connectable function may reject before extensions are
+    /// reported as failing.
+    pub motion_rejection: f64,
+
+    /// Fraction of extensions that may step further than the maximum extension, which the
+    /// planner discards without checking them.
+    pub overlong_steps: f64,
+
+    /// Fraction of the most recently added nodes that may bring the tree no closer to the
+    /// goal before growth is reported as stalled.
+    pub stall: f64,
+
+    /// Fewest samples, motions, or nodes needed before a rate is judged.
+    pub min_count: usize,
+}
+
+impl Default for WatchdogThresholds {
+    fn default() -> Self {
+        WatchdogThresholds {
+            sample_rejection: 0.9,
+            motion_rejection: 0.9,
+            overlong_steps: 0.5,
+            stall: 0.5,
+            min_count: 20,
+        }
+    }
+}
+
+/// A likely cause of a failed or slow run, found by [`Watchdog::diagnose`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue<T> {
+    /// Most samples are invalid, so the sampler is drawing from a different space than the
+    /// validity function accepts, e.g. bounds in the wrong units or frame.
+    SamplesRejected { rate: f64 },
+
+    /// Most extensions step further than [`RrtOptions::max_extension`], so the planner
+    /// discards them before they are checked.
+    StepsTooLong {
+        rate: f64,
+        longest: f64,
+        max_extension: f64,
+    },
+
+    /// The connectable function rejects most motions. If it accepted only short motions
+    /// the step size is likely larger than its collision checking resolution allows.
+    MotionsRejected {
+        rate: f64,
+        longest_accepted: Option<f64>,
+        max_extension: f64,
+    },
+
+    /// The tree kept growing without getting any closer to the goal, e.g. in front of a
+    /// narrow passage. `nearest` is the node closest to the goal.
+    Stalled {
+        nearest: T,
+        distance: f64,
+        nodes_since_progress: usize,
+    },
+}
+
+impl<T> fmt::Display for Issue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::SamplesRejected { rate } => write!(
+                f,
+                "{:.0}% of samples are invalid, check that the sampler draws from the same \
+                 space as the validity function",
+                rate * 100.0
+            ),
+            Issue::StepsTooLong {
+                rate,
+                longest,
+                max_extension,
+            } => write!(
+                f,
+                "{:.0}% of extensions step further than the maximum extension of \
+                 {max_extension} (up to {longest:.4}), make the extend function respect it",
+                rate * 100.0
+            ),
+            Issue::MotionsRejected {
+                rate,
+                longest_accepted,
+                max_extension,
+            } => {
+                write!(
+                    f,
+                    "{:.0}% of motions are rejected by the connectable function",
+                    rate * 100.0
+                )?;
+                match longest_accepted {
+                    Some(longest) if longest < max_extension => write!(
+                        f,
+                        ", none longer than {longest:.4} were accepted, try a maximum \
+                         extension below that instead of {max_extension}"
+                    ),
+                    Some(_) => Ok(()),
+                    None => write!(f, ", none were accepted"),
+                }
+            }
+            Issue::Stalled {
+                distance,
+                nodes_since_progress,
+                ..
+            } => write!(
+                f,
+                "the last {nodes_since_progress} nodes got no closer than {distance:.4} to \
+                 the goal, the tree may be stuck in front of a narrow passage"
+            ),
+        }
+    }
+}
+
+/// The findings of [`Watchdog::diagnose`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics<T> {
+    /// Likely causes of problems, empty if the run looks healthy.
+    pub issues: Vec<Issue<T>>,
+
+    /// Fraction of samples that were invalid, if any were checked.
+    pub sample_rejection_rate: Option<f64>,
+
+    /// Fraction of motions the connectable function rejected, if any were checked.
+    pub motion_rejection_rate: Option<f64>,
+
+    /// Fraction of iterations that added at least one node to the tree.
+    pub growth_rate: Option<f64>,
+}
+
+impl<T> Diagnostics<T> {
+    /// Returns whether no issues were found.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl<T> fmt::Display for Diagnostics<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.issues.is_empty() {
+            return write!(f, "no issues found");
+        }
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "- {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Watches a run for common misconfigurations and reports their likely causes.
+///
+/// Wrap the functions given to the planner with [`Watchdog::sample_fn`],
+/// [`Watchdog::extend_fn`] and [`Watchdog::connectable_fn`], then call
+/// [`Watchdog::diagnose`] with the resulting tree, e.g. after planning fails. Planners that
+/// keep the tree on failure, like [`crate::planning::rrt::rrt_with_tree`], allow stalled
+/// growth to be detected. Each wrapper
+/// only counts calls, so planning is unchanged. The state validity function given to the
+/// sampler is only used to count invalid samples, which are still returned.
+///
+/// ```
+/// use rustplanning::float::FloatArray;
+/// use rustplanning::planning::diagnostics::Watchdog;
+/// use rustplanning::planning::rrt::{rrt_with_tree, RrtOptions};
+/// use rustplanning::planning::stats::PlannerStats;
+/// use rustplanning::planning::termination::MaxIterations;
+/// use rustplanning::tree::HashTree;
+///
+/// let options = RrtOptions::default().with_max_extension(1.0);
+/// let watchdog = Watchdog::new(&options);
+/// let mut x = 0.0;
+/// let sample_fn = || {
+///     x = (x + 3.7) % 10.0;
+///     FloatArray([x])
+/// };
+/// let extend_fn = |a: &FloatArray<1>, b: &FloatArray<1>| {
+///     FloatArray([a.0[0] + (b.0[0] - a.0[0]).clamp(-1.0, 1.0)])
+/// };
+/// // The validity checker only accepts motions a tenth as long as the step size
+/// let connectable_fn = |a: &FloatArray<1>, b: &FloatArray<1>| (a.0[0] - b.0[0]).abs() <= 0.1;
+///
+/// let (mut tree, mut stats) = (HashTree::new(FloatArray([0.0])), PlannerStats::new());
+/// let result = rrt_with_tree(
+///     &mut tree,
+///     &FloatArray([5.0]),
+///     watchdog.sample_fn(sample_fn, |_| true),
+///     watchdog.extend_fn(extend_fn),
+///     watchdog.connectable_fn(connectable_fn),
+///     &options,
+///     MaxIterations(100),
+///     &mut stats,
+/// );
+/// assert!(result.is_err());
+/// let diagnostics = watchdog.diagnose(&stats, Some(&tree), &FloatArray([5.0]));
+/// assert!(!diagnostics.is_healthy());
+/// println!("{diagnostics}");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    thresholds: WatchdogThresholds,
+    max_extension: f64,
+    samples: Cell<usize>,
+    invalid_samples: Cell<usize>,
+    steps: Cell<usize>,
+    overlong_steps: Cell<usize>,
+    longest_step: Cell<f64>,
+    motions: Cell<usize>,
+    rejected_motions: Cell<usize>,
+    longest_accepted: Cell<Option<f64>>,
+}
+
+impl Watchdog {
+    /// Creates a watchdog for a run with the given options, with default thresholds.
+    #[must_use]
+    pub fn new(options: &RrtOptions) -> Self {
+        Watchdog {
+            thresholds: WatchdogThresholds::default(),
+            max_extension: options.max_extension,
+            samples: Cell::new(0),
+            invalid_samples: Cell::new(0),
+            steps: Cell::new(0),
+            overlong_steps: Cell::new(0),
+            longest_step: Cell::new(0.0),
+            motions: Cell::new(0),
+            rejected_motions: Cell::new(0),
+            longest_accepted: Cell::new(None),
+        }
+    }
+
+    /// Sets when the run is considered unhealthy.
+    #[must_use]
+    pub fn with_thresholds(mut self, thresholds: WatchdogThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Wraps a sample function, counting the samples `valid_fn` rejects.
+    pub fn sample_fn<'a, T, FS, FV>(
+        &'a self,
+        mut sample_fn: FS,
+        mut valid_fn: FV,
+    ) -> impl FnMut() -> T + 'a
+    where
+        FS: FnMut() -> T + 'a,
+        FV: FnMut(&T) -> bool + 'a,
+    {
+        move || {
+            let sample = sample_fn();
+            increment(&self.samples);
+            if !valid_fn(&sample) {
+                increment(&self.invalid_samples);
+            }
+            sample
+        }
+    }
+
+    /// Wraps an extend function, counting the steps longer than the maximum extension.
+    pub fn extend_fn<'a, T, FE>(&'a self, mut extend_fn: FE) -> impl FnMut(&T, &T) -> T + 'a
+    where
+        T: Distance,
+        FE: FnMut(&T, &T) -> T + 'a,
+    {
+        move |from: &T, to: &T| {
+            let state = extend_fn(from, to);
+            let length = from.distance(&state);
+            increment(&self.steps);
+            if length > self.max_extension {
+                increment(&self.overlong_steps);
+                self.longest_step.set(self.longest_step.get().max(length));
+            }
+            state
+        }
+    }
+
+    /// Wraps a connectable function, counting the motions it rejects and the longest one it
+    /// accepts.
+    pub fn connectable_fn<'a, T, FC>(
+        &'a self,
+        mut connectable_fn: FC,
+    ) -> impl FnMut(&T, &T) -> bool + 'a
+    where
+        T: Distance,
+        FC: FnMut(&T, &T) -> bool + 'a,
+    {
+        move |from: &T, to: &T| {
+            let valid = connectable_fn(from, to);
+            increment(&self.motions);
+            if valid {
+                let length = from.distance(to);
+                let longest = self
+                    .longest_accepted
+                    .get()
+                    .map_or(length, |l| l.max(length));
+                self.longest_accepted.set(Some(longest));
+            } else {
+                increment(&self.rejected_motions);
+            }
+            valid
+        }
+    }
+
+    /// Reports likely causes of problems in the run so far, using the counts collected by
+    /// the wrapped functions, the stats of the run, and its tree if available. Growth is
+    /// judged by the order nodes were added to the tree, so is only checked if a tree is
+    /// given.
+    #[must_use]
+    pub fn diagnose<T>(
+        &self,
+        stats: &PlannerStats<T>,
+        tree: Option<&HashTree<T>>,
+        goal: &T,
+    ) -> Diagnostics<T>
+    where
+        T: Eq + Clone + Hash + Distance,
+    {
+        let thresholds = &self.thresholds;
+        let mut issues = Vec::new();
+
+        let sample_rejection_rate = self.rate(&self.invalid_samples, &self.samples);
+        if let Some(rate) = sample_rejection_rate.filter(|&r| r > thresholds.sample_rejection) {
+            issues.push(Issue::SamplesRejected { rate });
+        }
+
+        let step_rate = self.rate(&self.overlong_steps, &self.steps);
+        if let Some(rate) = step_rate.filter(|&r| r > thresholds.overlong_steps) {
+            issues.push(Issue::StepsTooLong {
+                rate,
+                longest: self.longest_step.get(),
+                max_extension: self.max_extension,
+            });
+        }
+
+        let motion_rejection_rate = self.rate(&self.rejected_motions, &self.motions);
+        if let Some(rate) = motion_rejection_rate.filter(|&r| r > thresholds.motion_rejection) {
+            issues.push(Issue::MotionsRejected {
+                rate,
+                longest_accepted: self.longest_accepted.get(),
+                max_extension: self.max_extension,
+            });
+        }
+
+        let growth_rate = tree.and_then(|tree| {
+            #[allow(clippy::cast_precision_loss)]
+            (stats.iterations > 0)
+                .then(|| ((tree.size() - 1) as f64 / stats.iterations as f64).min(1.0))
+        });
+        if let Some(issue) = tree.and_then(|tree| self.stall(tree, goal)) {
+            issues.push(issue);
+        }
+
+        Diagnostics {
+            issues,
+            sample_rejection_rate,
+            motion_rejection_rate,
+            growth_rate,
+        }
+    }
+
+    // Returns the fraction of `count` out of `total`, if there were enough to judge
+    fn rate(&self, count: &Cell<usize>, total: &Cell<usize>) -> Option<f64> {
+        let total = total.get();
+        #[allow(clippy::cast_precision_loss)]
+        (total >= self.thresholds.min_count.max(1)).then(|| count.get() as f64 / total as f64)
+    }
+
+    // Checks whether the last nodes added to the tree got any closer to the goal
+    fn stall<T>(&self, tree: &HashTree<T>, goal: &T) -> Option<Issue<T>>
+    where
+        T: Eq + Clone + Hash + Distance,
+    {
+        if tree.size() < self.thresholds.min_count || tree.contains(goal) {
+            return None;
+        }
+        let mut nearest: Option<(usize, &T, f64)> = None;
+        for (i, node) in tree.iter_insertion_order().enumerate() {
+            let distance = node.distance(goal);
+            if nearest.is_none_or(|(_, _, best)| distance < best) {
+                nearest = Some((i, node, distance));
+            }
+        }
+        let (index, node, distance) = nearest?;
+        let nodes_since_progress = tree.size() - 1 - index;
+        #[allow(clippy::cast_precision_loss)]
+        let stalled = nodes_since_progress as f64 > self.thresholds.stall * tree.size() as f64;
+        stalled.then(|| Issue::Stalled {
+            nearest: node.clone(),
+            distance,
+            nodes_since_progress,
+        })
+    }
+}
+
+fn increment(count: &Cell<usize>) {
+    count.set(count.get() + 1);
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::rrt::rrt_with_tree;
+    use crate::planning::termination::MaxIterations;
+    use crate::planning::tuning::SplitMix64;
+
+    // Plans from 0 to 20 by steps of up to `step`, where only motions up to `resolution`
+    // long are valid, and returns the diagnostics
+    #[allow(clippy::cast_possible_truncation)]
+    fn diagnose(step: i32, resolution: i32, sample_range: f64) -> Diagnostics<i32> {
+        let options = RrtOptions::default().with_max_extension(4.0);
+        let watchdog = Watchdog::new(&options);
+        let mut rng = SplitMix64(5);
+        let sample_fn = || (rng.next_f64() * sample_range) as i32 - 5;
+        let extend_fn = |from: &i32, to: &i32| from + (to - from).clamp(-step, step);
+        let connectable_fn = |from: &i32, to: &i32| (to - from).abs() <= resolution;
+        let mut tree = HashTree::new(0);
+        let mut stats = PlannerStats::new();
+        let _ = rrt_with_tree(
+            &mut tree,
+            &20,
+            watchdog.sample_fn(sample_fn, |s: &i32| (-5..=25).contains(s)),
+            watchdog.extend_fn(extend_fn),
+            watchdog.connectable_fn(connectable_fn),
+            &options,
+            MaxIterations(200),
+            &mut stats,
+        );
+        watchdog.diagnose(&stats, Some(&tree), &20)
+    }
+
+    #[test]
+    fn test_healthy() {
+        let diagnostics = diagnose(4, 4, 30.0);
+        assert!(diagnostics.is_healthy(), "{diagnostics}");
+        assert_eq!(diagnostics.sample_rejection_rate, Some(0.0));
+        assert!(diagnostics.growth_rate.is_some());
+        assert_eq!(diagnostics.to_string(), "no issues found");
+    }
+
+    #[test]
+    fn test_samples_rejected() {
+        // Sampling far outside the valid space
+        let diagnostics = diagnose(4, 4, 3000.0);
+        assert!(matches!(
+            diagnostics.issues[..],
+            [Issue::SamplesRejected { rate }] if rate > 0.9
+        ));
+    }
+
+    #[test]
+    fn test_extensions_failing() {
+        // Steps are longer than the maximum extension
+        let diagnostics = diagnose(8, 8, 30.0);
+        assert!(matches!(
+            diagnostics.issues[..],
+            [Issue::StepsTooLong { longest, .. }] if longest > 4.0
+        ));
+
+        // Or longer than the validity checker accepts
+        let diagnostics = diagnose(4, 0, 30.0);
+        assert!(matches!(
+            diagnostics.issues[..],
+            [Issue::MotionsRejected { rate, longest_accepted: None, .. }] if rate > 0.99
+        ));
+        assert!(diagnostics.to_string().ends_with("none were accepted"));
+        let issue: Issue<i32> = Issue::MotionsRejected {
+            rate: 0.95,
+            longest_accepted: Some(0.5),
+            max_extension: 4.0,
+        };
+        assert_eq!(
+            issue.to_string(),
+            "95% of motions are rejected by the connectable function, none longer than \
+             0.5000 were accepted, try a maximum extension below that instead of 4"
+        );
+    }
+
+    #[test]
+    fn test_stalled() {
+        // The tree grows in front of the wall but never passes it
+        let options = RrtOptions::default().with_max_extension(4.0);
+        let mut tree = HashTree::new(0);
+        for i in 1..=9 {
+            assert!(tree.add_child(&(i - 1), i).is_ok());
+        }
+        for i in 1..30 {
+            assert!(tree.add_child(&0, -i).is_ok());
+        }
+        let diagnostics = Watchdog::new(&options).diagnose(&PlannerStats::new(), Some(&tree), &20);
+        assert_eq!(
+            diagnostics.issues,
+            vec![Issue::Stalled {
+                nearest: 9,
+                distance: 11.0,
+                nodes_since_progress: 29
+            }]
+        );
+        assert!(diagnostics.to_string().contains("narrow passage"));
+        assert_eq!(diagnostics.growth_rate, None);
+
+        // Not once the goal is reached
+        assert!(tree.add_child(&9, 20).is_ok());
+        let diagnostics = Watchdog::new(&options).diagnose(&PlannerStats::new(), Some(&tree), &20);
+        assert!(diagnostics.is_healthy());
+    }
+}
@@ -0,0 +1,508 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::PlanningError;
+use crate::planning::interactive::{IterationSummary, StepControl};
+use crate::planning::replay::PlannerEvent;
+use crate::planning::rrt::{grow_tree_with_selection, select_node, solution_path, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::{CancellationToken, Or, PlannerProgress, TerminationCondition};
+use crate::tree::{Distance, HashTree};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// A change to the planner's state, produced by [`RrtEvents`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent<T> {
+    /// A node was added to the tree.
+    NodeAdded { iteration: u64, parent: T, node: T },
+
+    /// RRT* rewiring moved a node to a new parent.
+    Rewired { iteration: u64, node: T, parent: T },
+
+    /// The cost to reach the goal improved, or the goal was reached for the first time.
+    SolutionImproved { iteration: u64, cost: f64 },
+
+    /// The termination condition was met after `iterations` iterations. Always the last
+    /// event, with the path to the goal or why there is none.
+    Finished {
+        iterations: u64,
+        result: Result<Vec<T>, PlanningError>,
+    },
+}
+
+/// RRT as an iterator over the changes it makes, which runs the planner one iteration at
+/// a time as events are requested.
+///
+/// Iterations that change nothing produce no events, so iterating until `Finished` runs
+/// the whole search. Dropping the iterator early stops planning, e.g. after
+/// `take_while` sees a good enough solution, and the tree built so far remains available
+/// through [`RrtEvents::tree`]. The tree and path are the same as from
+/// [`crate::planning::rrt::rrt_with_termination`] given the same samples.
+pub struct RrtEvents<T, FS, FE, FC, TC>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    tree: HashTree<T>,
+    goal: T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: RrtOptions,
+    termination: Or<Option<CancellationToken>, TC>,
+    stats: PlannerStats<T>,
+    clock: SystemClock,
+    visits: HashMap<T, usize>,
+    iteration: u64,
+    pending: VecDeque<ProgressEvent<T>>,
+    finished: bool,
+}
+
+/// Plans a path from `start` to `goal` with RRT, returning an iterator over the changes
+/// made to the tree as described in [`RrtEvents`].
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_events<T, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+) -> RrtEvents<T, FS, FE, FC, TC>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut stats = PlannerStats::new();
+    stats.events = Some(Vec::new());
+    RrtEvents {
+        tree: HashTree::new(start.clone()),
+        goal: goal.clone(),
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options: options.clone(),
+        termination: options.cancellable(termination),
+        stats,
+        clock: SystemClock::start(),
+        visits: HashMap::new(),
+        iteration: 0,
+        pending: VecDeque::new(),
+        finished: false,
+    }
+}
+
+impl<T, FS, FE, FC, TC> RrtEvents<T, FS, FE, FC, TC>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    /// Returns the tree as of the latest event.
+    #[must_use]
+    pub fn tree(&self) -> &HashTree<T> {
+        &self.tree
+    }
+
+    /// Returns diagnostics collected so far. Events are handed out by the iterator rather
+    /// than recorded in [`PlannerStats::events`].
+    #[must_use]
+    pub fn stats(&self) -> &PlannerStats<T> {
+        &self.stats
+    }
+
+    /// Runs a single iteration, or finishes if the termination condition is met.
+    fn step(&mut self) {
+        let progress = PlannerProgress {
+            iteration: self.iteration,
+            elapsed: self.clock.elapsed(),
+            best_cost: self
+                .tree
+                .cost(&self.goal)
+                .ok()
+                .filter(|&cost| cost <= self.options.max_cost),
+        };
+        if self.termination.should_terminate(&progress) {
+            self.stats.stop_reason = self.termination.stop_reason(&progress);
+            self.finished = true;
+            self.pending.push_back(ProgressEvent::Finished {
+                iterations: self.iteration,
+                result: solution_path(&self.tree, &self.goal, &self.options, &self.stats),
+            });
+            return;
+        }
+
+        // Events are always recorded to hand them out. Each step continues the iteration
+        // count, so goal checks, provenance and the first solution count iterations as
+        // a single run would
+        let iteration = self.iteration;
+        let options = RrtOptions {
+            record_events: true,
+            ..self.options.clone()
+        };
+        let visits = &mut self.visits;
+        grow_tree_with_selection(
+            &mut self.tree,
+            &self.goal,
+            &mut self.sample_fn,
+            |tree: &HashTree<T>, sample: &T| {
+                select_node(tree, sample, options.node_selection, visits)
+            },
+            &mut self.extend_fn,
+            &mut self.connectable_fn,
+            &options,
+            |progress: &PlannerProgress| progress.iteration > iteration,
+            &self.clock,
+            iteration,
+            &mut self.stats,
+            None,
+        );
+        self.iteration += 1;
+
+        let events = self.stats.events.as_mut().map(std::mem::take);
+        self.pending.extend(
+            events
+                .into_iter()
+                .flatten()
+                .filter_map(|event| match event {
+                    PlannerEvent::Sample { .. } => None,
+                    PlannerEvent::NodeAdded { parent, node } => Some(ProgressEvent::NodeAdded {
+                        iteration,
+                        parent,
+                        node,
+                    }),
+                    PlannerEvent::Rewired { node, parent } => Some(ProgressEvent::Rewired {
+                        iteration,
+                        node,
+                        parent,
+                    }),
+                    PlannerEvent::Solution { cost, .. } => {
+                        Some(ProgressEvent::SolutionImproved { iteration, cost })
+                    }
+                }),
+        );
+    }
+}
+
+impl<T, FS, FE, FC, TC> Iterator for RrtEvents<T, FS, FE, FC, TC>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    type Item = ProgressEvent<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.finished {
+            self.step();
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// Plans a path from `start` to `goal` with RRT, calling `callback` with every event as it
+/// happens, e.g. to stream the tree to a live visualizer or log the solution cost over
+/// time.
+///
+/// Each iteration starts with a [`PlannerEvent::Sample`], followed by the nodes it added,
+/// the edges it rewired, and any improvement to the solution. Events are only kept in
+/// [`PlannerStats::events`] if `RrtOptions::record_events` is set.
+///
+/// # Errors
+///
+/// If no path to the goal was found before the termination condition was met.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_with_callback<T, FS, FE, FC, TC, FU>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    mut callback: FU,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+    FU: FnMut(&PlannerEvent<T>),
+{
+    let mut observer = |summary: &IterationSummary<T>, _: &HashTree<T>| {
+        summary.events.iter().for_each(&mut callback);
+        StepControl::Continue
+    };
+
+    let mut tree = HashTree::new(start.clone());
+    let mut visits = HashMap::new();
+    grow_tree_with_selection(
+        &mut tree,
+        goal,
+        sample_fn,
+        |tree: &HashTree<T>, sample: &T| {
+            select_node(tree, sample, options.node_selection, &mut visits)
+        },
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        &SystemClock::start(),
+        0,
+        stats,
+        Some(&mut observer),
+    );
+
+    let path = solution_path(&tree, goal, options, stats)?;
+    Ok((path, tree))
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::rrt::{rrt_with_termination, GoalCandidates, GoalConnection};
+    use crate::planning::termination::MaxIterations;
+    use crate::planning::tuning::SplitMix64;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn sampler() -> impl FnMut() -> i32 {
+        let mut rng = SplitMix64(11);
+        move || (rng.next_f64() * 20.0) as i32 - 10
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn extend_fn(from: &i32, to: &i32) -> i32 {
+        from + (to - from).signum()
+    }
+
+    #[test]
+    fn test_rrt_events() {
+        let options = RrtOptions::default()
+            .with_max_extension(1.0)
+            .with_rrtstar(2.0);
+        let connectable_fn = |_: &i32, to: &i32| *to != 3;
+        let events: Vec<_> = rrt_events(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(200),
+        )
+        .collect();
+
+        let (path, tree) = rrt_with_termination(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(200),
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+
+        let added = events
+            .iter()
+            .filter(|event| matches!(event, ProgressEvent::NodeAdded { .. }))
+            .count();
+        assert_eq!(added + 1, tree.size());
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, ProgressEvent::NodeAdded { node: -6, .. })));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, ProgressEvent::SolutionImproved { .. })));
+        assert_eq!(
+            events.last(),
+            Some(&ProgressEvent::Finished {
+                iterations: 200,
+                result: Ok(path),
+            })
+        );
+    }
+
+    #[test]
+    fn test_rrt_events_stop_early() {
+        let options = RrtOptions::default().with_max_extension(1.0);
+        let mut events = rrt_events(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            |_: &i32, _: &i32| true,
+            &options,
+            MaxIterations(1000),
+        );
+        let first: Vec<_> = events
+            .by_ref()
+            .take_while(|event| !matches!(event, ProgressEvent::SolutionImproved { .. }))
+            .collect();
+        assert!(!first.is_empty());
+        assert!(events.tree().contains(&-6));
+        assert!(events.stats().iterations < 1000);
+    }
+
+    #[test]
+    fn test_rrt_events_counts_iterations_across_steps() {
+        let options = RrtOptions {
+            goal_connection: GoalConnection {
+                frequency: 4,
+                candidates: GoalCandidates::Nearest(1),
+                ..GoalConnection::default()
+            },
+            ..RrtOptions::default().with_max_extension(1.0)
+        };
+        let connectable_fn = |_: &i32, to: &i32| *to != 3;
+        let mut events = rrt_events(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(40),
+        );
+        events.by_ref().for_each(drop);
+
+        let mut stats = PlannerStats::new();
+        rrt_with_termination(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(40),
+            &mut stats,
+        )
+        .unwrap();
+
+        let first_solution = |stats: &PlannerStats<i32>| {
+            stats
+                .first_solution
+                .as_ref()
+                .map(|solution| (solution.iteration, solution.path.clone()))
+        };
+        assert_eq!(events.stats().goal_attempts, stats.goal_attempts);
+        assert_eq!(first_solution(events.stats()), first_solution(&stats));
+        assert!(stats
+            .first_solution
+            .is_some_and(|solution| solution.iteration > 0));
+    }
+
+    #[test]
+    fn test_rrt_with_callback() {
+        let options = RrtOptions::default()
+            .with_max_extension(1.0)
+            .with_rrtstar(2.0);
+        let connectable_fn = |_: &i32, to: &i32| *to != 3;
+        let mut events = Vec::new();
+        let mut stats = PlannerStats::new();
+        let (path, tree) = rrt_with_callback(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(200),
+            |event: &PlannerEvent<i32>| events.push(event.clone()),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(stats.events, None);
+
+        // Same events as recording them, and in the same order
+        let mut recorded = PlannerStats::new();
+        let (expected_path, _) = rrt_with_termination(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            connectable_fn,
+            &RrtOptions {
+                record_events: true,
+                ..options
+            },
+            MaxIterations(200),
+            &mut recorded,
+        )
+        .unwrap();
+        assert_eq!(path, expected_path);
+        assert_eq!(Some(&events), recorded.events.as_ref());
+
+        let samples = events
+            .iter()
+            .filter(|event| matches!(event, PlannerEvent::Sample { .. }))
+            .count();
+        let added = events
+            .iter()
+            .filter(|event| matches!(event, PlannerEvent::NodeAdded { .. }))
+            .count();
+        assert_eq!(samples, 200);
+        assert_eq!(added + 1, tree.size());
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, PlannerEvent::Solution { .. })));
+    }
+
+    #[test]
+    fn test_rrt_with_callback_keeps_recorded_events() {
+        let options = RrtOptions {
+            record_events: true,
+            ..RrtOptions::default().with_max_extension(1.0)
+        };
+        let mut count = 0;
+        let mut stats = PlannerStats::new();
+        let result = rrt_with_callback(
+            &0,
+            &-6,
+            sampler(),
+            extend_fn,
+            |_: &i32, _: &i32| true,
+            &options,
+            MaxIterations(50),
+            |_: &PlannerEvent<i32>| count += 1,
+            &mut stats,
+        );
+        assert!(result.is_ok());
+        assert_eq!(stats.events.map(|events| events.len()), Some(count));
+    }
+}
@@ -0,0 +1,280 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::ExplorationError;
+use crate::grid::{CellState, OccupancyGrid};
+use std::collections::VecDeque;
+
+/// A connected set of frontier cells, free cells that border unknown space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frontier {
+    /// The `(column, row)` of every cell in the frontier.
+    pub cells: Vec<(usize, usize)>,
+
+    /// Mean world coordinate of the frontier's cells.
+    pub centroid: (f64, f64),
+
+    /// World coordinate of the frontier cell closest to the centroid. Unlike the centroid,
+    /// this is always in free space so it is suitable as a planning goal.
+    pub target: (f64, f64),
+}
+
+impl Frontier {
+    /// Number of cells in the frontier, a rough measure of how much unknown space
+    /// could be observed by visiting it.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+// The in-bounds 8-connected neighbors of a cell
+fn neighbors(grid: &OccupancyGrid, column: usize, row: usize) -> Vec<(usize, usize)> {
+    let mut result = Vec::with_capacity(8);
+    for dr in [-1, 0, 1] {
+        for dc in [-1, 0, 1] {
+            if dr == 0 && dc == 0 {
+                continue;
+            }
+            let (Some(c), Some(r)) = (column.checked_add_signed(dc), row.checked_add_signed(dr))
+            else {
+                continue;
+            };
+            if grid.state(c, r).is_some() {
+                result.push((c, r));
+            }
+        }
+    }
+    result
+}
+
+/// Returns true if the cell is free and has an unknown cell directly above, below, or
+/// beside it.
+#[must_use]
+pub fn is_frontier_cell(grid: &OccupancyGrid, column: usize, row: usize) -> bool {
+    let is_unknown = |c: Option<usize>, r: Option<usize>| {
+        c.zip(r)
+            .is_some_and(|(c, r)| grid.state(c, r) == Some(CellState::Unknown))
+    };
+    grid.is_cell_free(column, row)
+        && (is_unknown(column.checked_sub(1), Some(row))
+            || is_unknown(column.checked_add(1), Some(row))
+            || is_unknown(Some(column), row.checked_sub(1))
+            || is_unknown(Some(column), row.checked_add(1)))
+}
+
+/// Extracts all frontiers with at least `min_size` cells from the grid.
+///
+/// Frontier cells are grouped with their 8-connected neighbors. Frontiers are returned
+/// in the order they are found scanning the grid row by row.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn find_frontiers(grid: &OccupancyGrid, min_size: usize) -> Vec<Frontier> {
+    let mut visited = vec![false; grid.width() * grid.height()];
+    let mut frontiers = Vec::new();
+    for row in 0..grid.height() {
+        for column in 0..grid.width() {
+            if visited[row * grid.width() + column] || !is_frontier_cell(grid, column, row) {
+                continue;
+            }
+
+            // Flood fill the connected frontier cells
+            let mut cells = Vec::new();
+            let mut queue = VecDeque::from([(column, row)]);
+            visited[row * grid.width() + column] = true;
+            while let Some((c, r)) = queue.pop_front() {
+                cells.push((c, r));
+                for (nc, nr) in neighbors(grid, c, r) {
+                    let i = nr * grid.width() + nc;
+                    if !visited[i] && is_frontier_cell(grid, nc, nr) {
+                        visited[i] = true;
+                        queue.push_back((nc, nr));
+                    }
+                }
+            }
+            if cells.len() < min_size {
+                continue;
+            }
+
+            let centers: Vec<(f64, f64)> =
+                cells.iter().map(|&(c, r)| grid.cell_center(c, r)).collect();
+            let n = centers.len() as f64;
+            let centroid = (
+                centers.iter().map(|p| p.0).sum::<f64>() / n,
+                centers.iter().map(|p| p.1).sum::<f64>() / n,
+            );
+            let Some(&target) = centers
+                .iter()
+                .min_by(|a, b| distance(**a, centroid).total_cmp(&distance(**b, centroid)))
+            else {
+                continue;
+            };
+            frontiers.push(Frontier {
+                cells,
+                centroid,
+                target,
+            });
+        }
+    }
+    frontiers
+}
+
+/// Sorts frontiers from most to least promising for a robot at `position`.
+///
+/// Each frontier is scored by the straight line distance to its target minus
+/// `size_weight` times its size, and lower scores are better. A `size_weight` of zero
+/// simply visits the nearest frontier first. Ties keep their original order.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn rank_frontiers(
+    mut frontiers: Vec<Frontier>,
+    position: (f64, f64),
+    size_weight: f64,
+) -> Vec<Frontier> {
+    let score = |frontier: &Frontier| {
+        distance(position, frontier.target) - size_weight * frontier.size() as f64
+    };
+    frontiers.sort_by(|a, b| score(a).total_cmp(&score(b)));
+    frontiers
+}
+
+/// Plans to the first reachable frontier in `frontiers`.
+///
+/// `plan_fn` is called with the target of each frontier in order, e.g. using one of the
+/// RRT planners, until one succeeds. Returns the selected frontier along with the result of
+/// `plan_fn`.
+///
+/// # Errors
+///
+/// If there are no frontiers, or none of them could be reached along with the error from
+/// `plan_fn` for each.
+pub fn plan_to_frontier<P, E, FP>(
+    frontiers: &[Frontier],
+    mut plan_fn: FP,
+) -> Result<(&Frontier, P), ExplorationError<E>>
+where
+    FP: FnMut(&(f64, f64)) -> Result<P, E>,
+{
+    if frontiers.is_empty() {
+        return Err(ExplorationError::NoFrontiers);
+    }
+    let mut errors = Vec::with_capacity(frontiers.len());
+    for frontier in frontiers {
+        match plan_fn(&frontier.target) {
+            Ok(path) => return Ok((frontier, path)),
+            Err(error) => errors.push(error),
+        }
+    }
+    Err(ExplorationError::Unreachable(errors))
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PlanningError;
+
+    // A 6x4 map where the left half is known free and the right half is unknown, aside from
+    // a single free cell in the top right corner.
+    fn partial_map() -> OccupancyGrid {
+        let mut grid = OccupancyGrid::filled(6, 4, 1.0, CellState::Unknown);
+        for column in 0..3 {
+            for row in 0..4 {
+                grid.set_state(column, row, CellState::Free).unwrap();
+            }
+        }
+        grid.set_state(0, 2, CellState::Occupied).unwrap();
+        grid.set_state(5, 3, CellState::Free).unwrap();
+        grid
+    }
+
+    #[test]
+    fn test_is_frontier_cell() {
+        let grid = partial_map();
+        assert!(is_frontier_cell(&grid, 2, 0));
+        assert!(!is_frontier_cell(&grid, 1, 0));
+        assert!(!is_frontier_cell(&grid, 0, 2));
+        assert!(!is_frontier_cell(&grid, 3, 0));
+        assert!(is_frontier_cell(&grid, 5, 3));
+    }
+
+    #[test]
+    fn test_find_frontiers() {
+        let grid = partial_map();
+        let frontiers = find_frontiers(&grid, 1);
+        assert_eq!(frontiers.len(), 2);
+        assert_eq!(frontiers[0].cells, vec![(2, 0), (2, 1), (2, 2), (2, 3)]);
+        assert_eq!(frontiers[0].centroid, (2.5, 2.0));
+        assert_eq!(frontiers[0].target, (2.5, 1.5));
+        assert_eq!(frontiers[1].cells, vec![(5, 3)]);
+
+        assert_eq!(find_frontiers(&grid, 2).len(), 1);
+        assert!(find_frontiers(&OccupancyGrid::new(3, 3, 1.0), 1).is_empty());
+    }
+
+    #[test]
+    fn test_rank_frontiers() {
+        let frontiers = find_frontiers(&partial_map(), 1);
+
+        // The small frontier is closer, but the larger frontier wins if size is valued
+        let ranked = rank_frontiers(frontiers.clone(), (5.5, 0.5), 0.0);
+        assert_eq!(ranked[0].size(), 1);
+        let ranked = rank_frontiers(frontiers, (5.5, 0.5), 1.0);
+        assert_eq!(ranked[0].size(), 4);
+    }
+
+    #[test]
+    fn test_plan_to_frontier() {
+        let frontiers = find_frontiers(&partial_map(), 1);
+
+        // Only the isolated frontier is reachable
+        let (frontier, path) = plan_to_frontier(&frontiers, |target| {
+            if target.0 > 5.0 {
+                Ok(vec![(0.5, 0.5), *target])
+            } else {
+                Err(PlanningError::GoalUnreachable)
+            }
+        })
+        .unwrap();
+        assert_eq!(frontier.size(), 1);
+        assert_eq!(path, vec![(0.5, 0.5), (5.5, 3.5)]);
+
+        assert_eq!(
+            plan_to_frontier(&frontiers, |_| Err::<(), _>(PlanningError::Timeout)),
+            Err(ExplorationError::Unreachable(vec![
+                PlanningError::Timeout;
+                2
+            ]))
+        );
+        assert_eq!(
+            plan_to_frontier::<(), PlanningError, _>(&[], |_| Ok(())),
+            Err(ExplorationError::NoFrontiers)
+        );
+    }
+}
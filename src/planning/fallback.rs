@@ -0,0 +1,379 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::bounds::Bounds;
+use crate::error::PlanningError;
+use crate::float::check_resolution;
+use crate::planning::heuristic::Visit;
+use crate::planning::rrt::{rrt_with_termination, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::Distance;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+type PositionFn<T> = dyn Fn(&T) -> [f64; 2] + Send + Sync;
+type StateFn<T> = dyn Fn([f64; 2]) -> T + Send + Sync;
+
+/// Which stage of [`rrt_with_fallback`] produced the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanningStage {
+    /// The sampling based planner found the path.
+    Sampling,
+
+    /// The sampling based planner failed and the grid search found the path.
+    GridSearch,
+}
+
+/// A resolution complete search over an 8-connected lattice of points covering a bounded
+/// 2D problem, for when sampling based planners fail.
+///
+/// States are mapped to and from the plane with a pair of functions, so the search can be
+/// used with any state type whose configuration is a 2D position. If a path exists through
+/// lattice points spaced `resolution` apart the search finds the shortest one, and it is
+/// fully deterministic. The number of points grows with the square of the extent over the
+/// resolution, so this is only suited to small problems.
+#[derive(Clone)]
+pub struct GridFallback<T> {
+    bounds: Bounds<2>,
+    resolution: f64,
+    position: Arc<PositionFn<T>>,
+    state: Arc<StateFn<T>>,
+}
+
+impl<T> GridFallback<T> {
+    /// Covers `bounds` with points `resolution` apart, where `position_fn` returns the
+    /// position of a state and `state_fn` the state at a position.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    pub fn new<FP, FT>(bounds: Bounds<2>, resolution: f64, position_fn: FP, state_fn: FT) -> Self
+    where
+        FP: Fn(&T) -> [f64; 2] + Send + Sync + 'static,
+        FT: Fn([f64; 2]) -> T + Send + Sync + 'static,
+    {
+        check_resolution(resolution);
+        GridFallback {
+            bounds,
+            resolution,
+            position: Arc::new(position_fn),
+            state: Arc::new(state_fn),
+        }
+    }
+
+    /// Returns the bounds covered by the lattice.
+    #[must_use]
+    pub fn bounds(&self) -> &Bounds<2> {
+        &self.bounds
+    }
+
+    /// Returns the spacing of the lattice.
+    #[must_use]
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    /// Returns the number of columns and rows of lattice points.
+    #[must_use]
+    pub fn dimensions(&self) -> (usize, usize) {
+        let extent = self.bounds.extent();
+        (self.steps(extent[0]) + 1, self.steps(extent[1]) + 1)
+    }
+
+    /// Returns the shortest path from `start` to `goal` through the lattice, or `None` if
+    /// there is none. The start and goal connect to lattice points within one diagonal
+    /// step of them, and every motion is validated with `connectable_fn`.
+    pub fn search<FC>(&self, start: &T, goal: &T, mut connectable_fn: FC) -> Option<Vec<T>>
+    where
+        T: Clone,
+        FC: FnMut(&T, &T) -> bool,
+    {
+        let (columns, rows) = self.dimensions();
+        let (start_node, goal_node) = (columns * rows, columns * rows + 1);
+        let goal_position = (self.position)(goal);
+        let position = |node: usize| match node {
+            node if node == start_node => (self.position)(start),
+            node if node == goal_node => goal_position,
+            node => self.point(node % columns, node / columns),
+        };
+        let state = |node: usize| match node {
+            node if node == start_node => start.clone(),
+            node if node == goal_node => goal.clone(),
+            node => (self.state)(position(node)),
+        };
+
+        let mut costs = vec![f64::INFINITY; goal_node + 1];
+        let mut previous: Vec<Option<usize>> = vec![None; goal_node + 1];
+        let mut closed = vec![false; goal_node + 1];
+        let mut queue = BinaryHeap::from([Reverse(Visit {
+            cost: 0.0,
+            node: start_node,
+        })]);
+        costs[start_node] = 0.0;
+        while let Some(Reverse(Visit { node, .. })) = queue.pop() {
+            if node == goal_node {
+                let mut path = vec![goal.clone()];
+                let mut current = previous[goal_node];
+                while let Some(node) = current {
+                    path.push(state(node));
+                    current = previous[node];
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if std::mem::replace(&mut closed[node], true) {
+                continue;
+            }
+
+            let from_position = position(node);
+            let from = state(node);
+            let mut successors = if node == start_node {
+                self.points_near(from_position)
+            } else {
+                self.neighbors(node % columns, node / columns)
+            };
+            if distance(from_position, goal_position) <= self.resolution * SQRT_2 {
+                successors.push(goal_node);
+            }
+            for next in successors {
+                let next_position = position(next);
+                let cost = costs[node] + distance(from_position, next_position);
+                if closed[next] || cost >= costs[next] || !connectable_fn(&from, &state(next)) {
+                    continue;
+                }
+                costs[next] = cost;
+                previous[next] = Some(node);
+                queue.push(Reverse(Visit {
+                    cost: cost + distance(next_position, goal_position),
+                    node: next,
+                }));
+            }
+        }
+        None
+    }
+
+    // Number of whole lattice steps in a distance, rounding down.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn steps(&self, distance: f64) -> usize {
+        (distance / self.resolution).floor().max(0.0) as usize
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn point(&self, column: usize, row: usize) -> [f64; 2] {
+        [
+            self.bounds.lower[0] + column as f64 * self.resolution,
+            self.bounds.lower[1] + row as f64 * self.resolution,
+        ]
+    }
+
+    // Indices of the lattice points adjacent to a lattice point, including diagonally.
+    fn neighbors(&self, column: usize, row: usize) -> Vec<usize> {
+        let (columns, rows) = self.dimensions();
+        let mut neighbors = Vec::with_capacity(8);
+        for next_row in row.saturating_sub(1)..=(row + 1).min(rows - 1) {
+            for next_column in column.saturating_sub(1)..=(column + 1).min(columns - 1) {
+                if (next_column, next_row) != (column, row) {
+                    neighbors.push(next_row * columns + next_column);
+                }
+            }
+        }
+        neighbors
+    }
+
+    // Indices of the lattice points within one diagonal step of a position.
+    fn points_near(&self, position: [f64; 2]) -> Vec<usize> {
+        let (columns, rows) = self.dimensions();
+        let lower = self.bounds.lower;
+        let column = self.steps(position[0] - lower[0]).min(columns - 1);
+        let row = self.steps(position[1] - lower[1]).min(rows - 1);
+        let mut near = Vec::new();
+        for next_row in row.saturating_sub(1)..=(row + 2).min(rows - 1) {
+            for next_column in column.saturating_sub(1)..=(column + 2).min(columns - 1) {
+                let point = self.point(next_column, next_row);
+                if distance(position, point) <= self.resolution * SQRT_2 {
+                    near.push(next_row * columns + next_column);
+                }
+            }
+        }
+        near
+    }
+}
+
+impl<T> fmt::Debug for GridFallback<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GridFallback")
+            .field("bounds", &self.bounds)
+            .field("resolution", &self.resolution)
+            .finish_non_exhaustive()
+    }
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+/// RRT with a grid search as a last resort.
+///
+/// Plans as [`rrt_with_termination`], and if that fails searches `fallback`'s lattice for
+/// a path instead, so that small 2D problems are always solved if the lattice is fine
+/// enough to pass through the free space. The grid path is discarded if it costs more than
+/// [`RrtOptions::max_cost`], in which case the planner's error is returned.
+///
+/// Returns the path along with the stage that produced it.
+///
+/// # Errors
+///
+/// If neither stage found a path to the goal.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_with_fallback<T, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    fallback: &GridFallback<T>,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, PlanningStage), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let error = match rrt_with_termination(
+        start,
+        goal,
+        sample_fn,
+        extend_fn,
+        &mut connectable_fn,
+        options,
+        termination,
+        stats,
+    ) {
+        Ok((path, _)) => return Ok((path, PlanningStage::Sampling)),
+        Err(error) => error,
+    };
+
+    fallback
+        .search(start, goal, &mut connectable_fn)
+        .filter(|path| {
+            let cost: f64 = path.windows(2).map(|pair| pair[0].distance(&pair[1])).sum();
+            cost <= options.max_cost
+        })
+        .map(|path| (path, PlanningStage::GridSearch))
+        .ok_or(error)
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::FloatArray;
+    use crate::planning::termination::MaxIterations;
+    use crate::planning::tuning::SplitMix64;
+
+    // A wall at x = 5 with a narrow gap around y = 7.2
+    fn connectable_fn(from: &FloatArray<2>, to: &FloatArray<2>) -> bool {
+        let crosses = (from.0[0] - 5.0) * (to.0[0] - 5.0) <= 0.0;
+        let in_gap = |p: &FloatArray<2>| (7.0..=7.4).contains(&p.0[1]);
+        !crosses || (in_gap(from) && in_gap(to))
+    }
+
+    fn fallback() -> GridFallback<FloatArray<2>> {
+        let bounds = Bounds::new([0.0, 0.0], [10.0, 10.0]).unwrap();
+        GridFallback::new(bounds, 0.25, |p: &FloatArray<2>| p.0, FloatArray)
+    }
+
+    fn plan(
+        start: FloatArray<2>,
+        iterations: u64,
+    ) -> Result<(Vec<FloatArray<2>>, PlanningStage), PlanningError> {
+        let mut rng = SplitMix64(3);
+        let sample_fn = || FloatArray([rng.next_f64() * 10.0, rng.next_f64() * 10.0]);
+        let extend_fn = |from: &FloatArray<2>, to: &FloatArray<2>| {
+            let scale = (1.0 / from.distance(to)).min(1.0);
+            FloatArray(std::array::from_fn(|i| {
+                from.0[i] + (to.0[i] - from.0[i]) * scale
+            }))
+        };
+        let options = RrtOptions::default().with_max_extension(1.0);
+        rrt_with_fallback(
+            &start,
+            &FloatArray([9.0, 1.0]),
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(iterations),
+            &fallback(),
+            &mut PlannerStats::new(),
+        )
+    }
+
+    #[test]
+    fn test_grid_fallback_search() {
+        let fallback = fallback();
+        assert_eq!(fallback.dimensions(), (41, 41));
+
+        let (start, goal) = (FloatArray([1.1, 1.05]), FloatArray([9.0, 1.0]));
+        let path = fallback.search(&start, &goal, connectable_fn).unwrap();
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path
+            .windows(2)
+            .all(|pair| connectable_fn(&pair[0], &pair[1])));
+        assert!(path.iter().any(|p| (7.0..=7.4).contains(&p.0[1])));
+
+        // Through the gap and back is about 20 long
+        let cost: f64 = path.windows(2).map(|pair| pair[0].distance(&pair[1])).sum();
+        assert!(cost < 21.0, "cost {cost}");
+
+        // Nothing fits through a closed wall
+        let closed =
+            |from: &FloatArray<2>, to: &FloatArray<2>| (from.0[0] - 5.0) * (to.0[0] - 5.0) > 0.0;
+        assert!(fallback.search(&start, &goal, closed).is_none());
+    }
+
+    #[test]
+    fn test_rrt_with_fallback() {
+        // Easy problems are solved by sampling
+        let (path, stage) = plan(FloatArray([6.0, 2.0]), 1000).unwrap();
+        assert_eq!(stage, PlanningStage::Sampling);
+        assert_eq!(path.first(), Some(&FloatArray([6.0, 2.0])));
+
+        // The narrow gap is rarely found with few samples, but the grid search finds it
+        let (path, stage) = plan(FloatArray([1.0, 1.0]), 50).unwrap();
+        assert_eq!(stage, PlanningStage::GridSearch);
+        assert_eq!(path.last(), Some(&FloatArray([9.0, 1.0])));
+    }
+}
@@ -0,0 +1,536 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::clock::Instant;
+use crate::error::PlanningError;
+use crate::path::Pose2D;
+use crate::planning::rrt::{
+    add_path, connect_goal, extend_from, rewire_added, within_extension, RrtOptions,
+};
+use crate::planning::stats::{FirstSolution, PlannerStats};
+use crate::planning::termination::{PlannerProgress, TerminationCondition};
+use crate::tree::{Distance, HashTree};
+use std::f64::consts::{PI, TAU};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+type PoseErrorFn<T> = dyn Fn(&T, &T) -> (f64, f64) + Send + Sync;
+
+/// The set of states a planner may finish in.
+pub trait Goal<T> {
+    /// Returns whether the state is in the goal.
+    fn is_satisfied(&self, state: &T) -> bool;
+
+    /// Returns a state in the goal for the planner to try to connect to, or `None` if the
+    /// goal cannot be sampled. Planners then rely on the tree growing into the goal.
+    fn sample_goal(&mut self) -> Option<T> {
+        None
+    }
+}
+
+/// A single goal state, which must be reached exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExactGoal<T>(pub T);
+
+impl<T> Goal<T> for ExactGoal<T>
+where
+    T: PartialEq + Clone,
+{
+    fn is_satisfied(&self, state: &T) -> bool {
+        *state == self.0
+    }
+
+    fn sample_goal(&mut self) -> Option<T> {
+        Some(self.0.clone())
+    }
+}
+
+/// Every state within `tolerance` of `center`. Sampling the goal always returns the center.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalBall<T> {
+    /// The state at the center of the goal.
+    pub center: T,
+
+    /// Maximum distance from the center.
+    pub tolerance: f64,
+}
+
+impl<T> Goal<T> for GoalBall<T>
+where
+    T: Clone + Distance,
+{
+    fn is_satisfied(&self, state: &T) -> bool {
+        self.center.distance(state) <= self.tolerance
+    }
+
+    fn sample_goal(&mut self) -> Option<T> {
+        Some(self.center.clone())
+    }
+}
+
+/// Any one of several goal states, e.g. alternative grasp poses. Sampling the goal cycles
+/// through the states in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalSet<T>(pub Vec<T>);
+
+impl<T> Goal<T> for GoalSet<T>
+where
+    T: PartialEq + Clone,
+{
+    fn is_satisfied(&self, state: &T) -> bool {
+        self.0.contains(state)
+    }
+
+    fn sample_goal(&mut self) -> Option<T> {
+        let next = self.0.first()?.clone();
+        self.0.rotate_left(1);
+        Some(next)
+    }
+}
+
+/// Returns the signed difference `a - b` between two angles in radians, wrapped to
+/// `[-PI, PI]`.
+#[must_use]
+pub fn angle_difference(a: f64, b: f64) -> f64 {
+    let wrapped = (a - b + PI).rem_euclid(TAU) - PI;
+    // rem_euclid can round up to TAU for tiny negative inputs
+    if wrapped > PI {
+        wrapped - TAU
+    } else {
+        wrapped
+    }
+}
+
+/// Returns the angle in radians of the rotation between two unit quaternions, in `[0, PI]`.
+/// Both `q` and `-q` describe the same rotation, so either sign may be used.
+#[must_use]
+pub fn quaternion_angle(a: &[f64; 4], b: &[f64; 4]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    2.0 * dot.abs().min(1.0).acos()
+}
+
+/// A goal pose with separate position and orientation tolerances, e.g. within 0.1m and 15
+/// degrees of a docking pose. This avoids a single distance threshold mixing meters and
+/// radians, as with a [`GoalBall`] over poses. Sampling the goal always returns the goal pose.
+#[derive(Clone)]
+pub struct PoseGoal<T> {
+    /// The goal pose.
+    pub pose: T,
+
+    /// Maximum distance between positions.
+    pub position_tolerance: f64,
+
+    /// Maximum angle in radians between orientations.
+    pub orientation_tolerance: f64,
+
+    error: Arc<PoseErrorFn<T>>,
+}
+
+impl<T> PoseGoal<T> {
+    /// Creates a pose goal, where `error_fn` returns the position and orientation errors
+    /// between the goal pose and a state.
+    pub fn new<FE>(
+        pose: T,
+        position_tolerance: f64,
+        orientation_tolerance: f64,
+        error_fn: FE,
+    ) -> Self
+    where
+        FE: Fn(&T, &T) -> (f64, f64) + Send + Sync + 'static,
+    {
+        PoseGoal {
+            pose,
+            position_tolerance,
+            orientation_tolerance,
+            error: Arc::new(error_fn),
+        }
+    }
+
+    /// Creates a goal for planar poses, where `pose_fn` returns the `(x, y, heading)` of a
+    /// state. Headings are compared modulo a full turn.
+    pub fn se2<FP>(
+        pose: T,
+        position_tolerance: f64,
+        orientation_tolerance: f64,
+        pose_fn: FP,
+    ) -> Self
+    where
+        FP: Fn(&T) -> Pose2D + Send + Sync + 'static,
+    {
+        Self::new(
+            pose,
+            position_tolerance,
+            orientation_tolerance,
+            move |a, b| {
+                let (ax, ay, ah) = pose_fn(a);
+                let (bx, by, bh) = pose_fn(b);
+                ((ax - bx).hypot(ay - by), angle_difference(ah, bh).abs())
+            },
+        )
+    }
+
+    /// Creates a goal for spatial poses, where `pose_fn` returns the position of a state and
+    /// its orientation as a unit quaternion.
+    pub fn se3<FP>(
+        pose: T,
+        position_tolerance: f64,
+        orientation_tolerance: f64,
+        pose_fn: FP,
+    ) -> Self
+    where
+        FP: Fn(&T) -> ([f64; 3], [f64; 4]) + Send + Sync + 'static,
+    {
+        Self::new(
+            pose,
+            position_tolerance,
+            orientation_tolerance,
+            move |a, b| {
+                let (ap, aq) = pose_fn(a);
+                let (bp, bq) = pose_fn(b);
+                let position = ap
+                    .iter()
+                    .zip(&bp)
+                    .map(|(a, b)| (a - b).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                (position, quaternion_angle(&aq, &bq))
+            },
+        )
+    }
+
+    /// Returns the position and orientation errors of the state relative to the goal pose.
+    #[must_use]
+    pub fn error(&self, state: &T) -> (f64, f64) {
+        (self.error)(&self.pose, state)
+    }
+}
+
+impl<T> Goal<T> for PoseGoal<T>
+where
+    T: Clone,
+{
+    fn is_satisfied(&self, state: &T) -> bool {
+        let (position, orientation) = self.error(state);
+        position <= self.position_tolerance && orientation <= self.orientation_tolerance
+    }
+
+    fn sample_goal(&mut self) -> Option<T> {
+        Some(self.pose.clone())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoseGoal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoseGoal")
+            .field("pose", &self.pose)
+            .field("position_tolerance", &self.position_tolerance)
+            .field("orientation_tolerance", &self.orientation_tolerance)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the cheapest of the nodes, along with its cost.
+fn cheapest<'a, T>(tree: &HashTree<T>, nodes: &'a [T]) -> Option<(&'a T, f64)>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    nodes
+        .iter()
+        .filter_map(|node| Some((node, tree.cost(node).ok()?)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// RRT to any state satisfying a [`Goal`], rather than a single goal state.
+///
+/// Grows the tree as [`crate::planning::rrt::rrt_with_termination`] does, and every
+/// [`crate::planning::rrt::GoalConnection::frequency`] iterations tries to connect the
+/// latest node to a state drawn with [`Goal::sample_goal`] using the configured goal
+/// connection strategy. Every node added to the tree is checked against the goal, so goals
+/// that cannot be sampled are still reached if the tree grows into them.
+///
+/// Returns the path to the cheapest node in the goal when terminating, along with the tree.
+/// The termination condition sees the cost of that path as soon as there is one.
+///
+/// # Errors
+///
+/// If no node in the goal was reached within [`RrtOptions::max_cost`] before terminating.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_to_goal<T, G, FS, FE, FC, TC>(
+    start: &T,
+    goal: &mut G,
+    mut sample_fn: FS,
+    mut extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    G: Goal<T>,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut termination = options.cancellable(termination);
+    let mut tree = HashTree::new(start.clone());
+    tree.set_tie_break(options.tie_break);
+    let mut satisfied: Vec<T> = Vec::new();
+    if goal.is_satisfied(start) {
+        satisfied.push(start.clone());
+    }
+
+    let start_time = Instant::now();
+    for iteration in 0.. {
+        let progress = PlannerProgress {
+            iteration,
+            elapsed: start_time.elapsed(),
+            best_cost: cheapest(&tree, &satisfied)
+                .map(|(_, cost)| cost)
+                .filter(|&cost| cost <= options.max_cost),
+        };
+        if termination.should_terminate(&progress) {
+            stats.stop_reason = termination.stop_reason(&progress);
+            break;
+        }
+        stats.iterations += 1;
+        if stats.first_solution.is_none() {
+            if let Some(((node, cost), _)) = cheapest(&tree, &satisfied).zip(progress.best_cost) {
+                stats.first_solution = Some(FirstSolution {
+                    path: tree.path(node)?,
+                    cost,
+                    iteration,
+                    elapsed: progress.elapsed,
+                });
+            }
+        }
+
+        let size = tree.size();
+        let sample = sample_fn();
+        if sample.distance(start).is_nan() {
+            stats.invalid_states += 1;
+            continue;
+        }
+        let nearest = tree.nearest_neighbor(&sample).clone();
+        let new_points = extend_from(
+            &nearest,
+            sample,
+            &mut extend_fn,
+            &mut within_extension(&mut connectable_fn, options.max_extension),
+            options.use_rrtconnect.then_some(&options.connect_limit),
+        );
+        let added = add_path(
+            &mut tree,
+            &nearest,
+            new_points,
+            options.duplicate_policy,
+            stats,
+        );
+
+        if iteration % options.goal_connection.frequency.max(1) == 0 {
+            if let Some(target) = goal.sample_goal().filter(|target| !tree.contains(target)) {
+                let (extend, valid) = (&mut extend_fn, &mut connectable_fn);
+                connect_goal(
+                    &mut tree,
+                    &target,
+                    added.last(),
+                    extend,
+                    valid,
+                    options,
+                    stats,
+                );
+            }
+        }
+
+        let new_nodes: Vec<T> = tree.iter_insertion_order().skip(size).cloned().collect();
+        if options.use_rrtstar {
+            let radius = options.rewire_radius_at(tree.size());
+            rewire_added(
+                &mut tree,
+                &mut connectable_fn,
+                size,
+                &new_nodes,
+                radius,
+                stats,
+            );
+        }
+        satisfied.extend(new_nodes.into_iter().filter(|node| goal.is_satisfied(node)));
+    }
+
+    match cheapest(&tree, &satisfied) {
+        Some((node, cost)) if cost <= options.max_cost => Ok((tree.path(node)?, tree)),
+        Some((_, cost)) => Err(PlanningError::NoSolutionWithinCost {
+            max_cost: options.max_cost,
+            best_cost: Some(cost),
+        }),
+        None if stats.cost_rejections > 0 => Err(PlanningError::NoSolutionWithinCost {
+            max_cost: options.max_cost,
+            best_cost: None,
+        }),
+        None => Err(PlanningError::not_found(stats.stop_reason)),
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::termination::MaxIterations;
+    use crate::planning::tuning::SplitMix64;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn plan<G: Goal<i32>>(goal: &mut G, iterations: u64) -> Result<Vec<i32>, PlanningError> {
+        let mut rng = SplitMix64(7);
+        let sample_fn = || (rng.next_f64() * 40.0) as i32 - 20;
+        let extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        // Nothing can move onto 3
+        let connectable_fn = |_: &i32, to: &i32| *to != 3;
+        let options = RrtOptions::default().with_max_extension(1.0);
+        let (path, tree) = rrt_to_goal(
+            &0,
+            goal,
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxIterations(iterations),
+            &mut PlannerStats::new(),
+        )?;
+        assert_eq!(path, tree.path(path.last().unwrap()).unwrap());
+        Ok(path)
+    }
+
+    // Even states beyond 5 in either direction, which cannot be sampled
+    struct FarEven;
+
+    impl Goal<i32> for FarEven {
+        fn is_satisfied(&self, state: &i32) -> bool {
+            state.abs() > 5 && state % 2 == 0
+        }
+    }
+
+    #[test]
+    fn test_goals() {
+        assert!(ExactGoal(2).is_satisfied(&2));
+        assert!(!ExactGoal(2).is_satisfied(&1));
+
+        let ball = GoalBall {
+            center: 10,
+            tolerance: 2.0,
+        };
+        assert!(ball.is_satisfied(&8) && ball.is_satisfied(&12));
+        assert!(!ball.is_satisfied(&7));
+
+        let mut set = GoalSet(vec![1, 2, 3]);
+        assert!(set.is_satisfied(&2) && !set.is_satisfied(&4));
+        let samples: Vec<_> = (0..4).filter_map(|_| set.sample_goal()).collect();
+        assert_eq!(samples, vec![1, 2, 3, 1]);
+        assert_eq!(GoalSet::<i32>(Vec::new()).sample_goal(), None);
+        assert_eq!(FarEven.sample_goal(), None);
+    }
+
+    #[test]
+    fn test_angle_difference() {
+        use std::f64::consts::FRAC_PI_2;
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(close(angle_difference(0.1, -0.1), 0.2));
+        assert!(close(angle_difference(PI - 0.1, -PI + 0.1), -0.2));
+        assert!(close(angle_difference(-PI + 0.1, PI - 0.1), 0.2));
+        assert!(close(
+            angle_difference(5.0 * TAU + FRAC_PI_2, 0.0),
+            FRAC_PI_2
+        ));
+        assert!(angle_difference(PI, -PI).abs() < 1e-9);
+        for i in -100..100 {
+            let d = angle_difference(f64::from(i) * 0.37, 1.0);
+            assert!((-PI..=PI).contains(&d));
+        }
+
+        let identity = [1.0, 0.0, 0.0, 0.0];
+        let half = FRAC_PI_2 / 2.0;
+        let quarter_turn = [half.cos(), 0.0, 0.0, half.sin()];
+        assert!(close(quaternion_angle(&identity, &quarter_turn), FRAC_PI_2));
+        assert!(close(
+            quaternion_angle(&identity, &[-1.0, 0.0, 0.0, 0.0]),
+            0.0
+        ));
+    }
+
+    #[test]
+    fn test_pose_goal() {
+        let degrees = |d: f64| d.to_radians();
+        let goal = PoseGoal::se2((1.0, 2.0, PI), 0.1, degrees(15.0), |p: &Pose2D| *p);
+        assert!(goal.is_satisfied(&(1.05, 2.05, PI)));
+        // Headings either side of the wrap are close to the goal
+        assert!(goal.is_satisfied(&(1.0, 2.0, -PI + degrees(10.0))));
+        assert!(goal.is_satisfied(&(1.0, 2.0, PI - degrees(10.0) + 4.0 * TAU)));
+        assert!(!goal.is_satisfied(&(1.0, 2.0, -PI + degrees(20.0))));
+        assert!(!goal.is_satisfied(&(1.2, 2.0, PI)));
+        let (position, orientation) = goal.error(&(1.0, 2.1, -PI));
+        assert!((position - 0.1).abs() < 1e-9 && orientation < 1e-9);
+
+        // A state that would be within a combined scalar tolerance is rejected on heading
+        assert!(!goal.is_satisfied(&(1.0, 2.0, 0.0)));
+
+        let pose = |yaw: f64| {
+            (
+                [0.0, 0.0, 1.0],
+                [(yaw / 2.0).cos(), 0.0, 0.0, (yaw / 2.0).sin()],
+            )
+        };
+        let mut goal = PoseGoal::se3(0.0, 0.1, degrees(15.0), move |yaw: &f64| pose(*yaw));
+        assert!(goal.is_satisfied(&degrees(10.0)) && goal.is_satisfied(&(TAU - degrees(10.0))));
+        assert!(!goal.is_satisfied(&degrees(20.0)));
+        assert_eq!(goal.sample_goal(), Some(0.0));
+    }
+
+    #[test]
+    fn test_rrt_to_goal() {
+        assert_eq!(plan(&mut ExactGoal(-3), 200), Ok(vec![0, -1, -2, -3]));
+
+        // The edge of the ball is good enough
+        let mut ball = GoalBall {
+            center: -10,
+            tolerance: 2.0,
+        };
+        assert_eq!(plan(&mut ball, 200).unwrap().last(), Some(&-8));
+
+        // The closest reachable goal in the set is found
+        assert_eq!(
+            plan(&mut GoalSet(vec![6, -6]), 200).unwrap().last(),
+            Some(&-6)
+        );
+
+        // Goals that cannot be sampled are found by growing into them
+        assert_eq!(plan(&mut FarEven, 200).unwrap().last(), Some(&-6));
+
+        assert_eq!(
+            plan(&mut ExactGoal(5), 200),
+            Err(PlanningError::MaxIterationsReached)
+        );
+    }
+}
@@ -0,0 +1,575 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::{CostFieldError, PlanningError};
+use crate::grid::{OccupancyGrid, TerrainGrid};
+use crate::planning::rrt::{rrt_with_selection, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::{Distance, HashTree};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+use std::hash::Hash;
+
+// Entry in a Dijkstra queue, ordered by cost and then by node
+#[derive(Debug, PartialEq)]
+pub(crate) struct Visit<N> {
+    pub(crate) cost: f64,
+    pub(crate) node: N,
+}
+
+impl<N: Ord> Eq for Visit<N> {}
+
+impl<N: Ord> PartialOrd for Visit<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: Ord> Ord for Visit<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost
+            .total_cmp(&other.cost)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+// Returns the goal cell, if there is one and it can be traversed
+fn goal_cell<F>(
+    cell: Option<(usize, usize)>,
+    is_traversable: F,
+) -> Result<(usize, usize), CostFieldError>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let (column, row) = cell.ok_or(CostFieldError::GoalOutsideGrid)?;
+    if !is_traversable(column, row) {
+        return Err(CostFieldError::GoalNotTraversable);
+    }
+    Ok((column, row))
+}
+
+/// Cost-to-go from every cell of a grid to a goal.
+///
+/// Built by flooding outwards from the goal cell over 8-connected traversable cells,
+/// diagonal moves are only allowed when both adjacent cells are traversable so paths
+/// never cut corners. Costs are in world units, weighted by terrain costs if built from a
+/// [`TerrainGrid`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostField {
+    width: usize,
+    height: usize,
+    resolution: f64,
+    costs: Vec<f64>,
+
+    // Index of the next cell towards the goal from each cell.
+    next: Vec<Option<usize>>,
+}
+
+impl CostField {
+    /// Computes the cost-to-go field for the goal position in the grid.
+    ///
+    /// # Errors
+    ///
+    /// If the goal is outside the grid or not in a traversable cell.
+    pub fn from_grid(grid: &OccupancyGrid, goal: (f64, f64)) -> Result<Self, CostFieldError> {
+        let goal = goal_cell(grid.cell_at(goal.0, goal.1), |column, row| {
+            grid.is_traversable(column, row)
+        })?;
+        Ok(Self::flood(
+            grid.width(),
+            grid.height(),
+            grid.resolution(),
+            goal,
+            |column, row| grid.is_traversable(column, row),
+            |_, _, length| length,
+        ))
+    }
+
+    /// Computes the cost-to-go field for the goal position over terrain, where moving
+    /// between two cells costs the distance times the average of their terrain costs.
+    ///
+    /// # Errors
+    ///
+    /// If the goal is outside the grid or not in a traversable cell.
+    pub fn from_terrain(terrain: &TerrainGrid, goal: (f64, f64)) -> Result<Self, CostFieldError> {
+        let goal = goal_cell(terrain.cell_at(goal.0, goal.1), |column, row| {
+            terrain.is_traversable(column, row)
+        })?;
+        Ok(Self::flood(
+            terrain.width(),
+            terrain.height(),
+            terrain.resolution(),
+            goal,
+            |column, row| terrain.is_traversable(column, row),
+            |from, to, length| {
+                let cost = |(column, row)| terrain.cost(column, row).unwrap_or(f64::INFINITY);
+                length * f64::midpoint(cost(from), cost(to))
+            },
+        ))
+    }
+
+    /// Computes the cost-to-go field for the goal position in the grid, where moving
+    /// between the centers of two neighboring cells costs `edge_cost_fn(from, to)`, e.g.
+    /// energy use including elevation gain. Costs may be asymmetric, and moves with an
+    /// infinite cost are not allowed.
+    ///
+    /// # Errors
+    ///
+    /// If the goal is outside the grid or not in a traversable cell.
+    pub fn from_edge_costs<F>(
+        grid: &OccupancyGrid,
+        goal: (f64, f64),
+        edge_cost_fn: F,
+    ) -> Result<Self, CostFieldError>
+    where
+        F: Fn((f64, f64), (f64, f64)) -> f64,
+    {
+        let goal = goal_cell(grid.cell_at(goal.0, goal.1), |column, row| {
+            grid.is_traversable(column, row)
+        })?;
+        Ok(Self::flood(
+            grid.width(),
+            grid.height(),
+            grid.resolution(),
+            goal,
+            |column, row| grid.is_traversable(column, row),
+            |from: (usize, usize), to: (usize, usize), _| {
+                edge_cost_fn(
+                    grid.cell_center(from.0, from.1),
+                    grid.cell_center(to.0, to.1),
+                )
+            },
+        ))
+    }
+
+    // Dijkstra from the goal cell. `step_cost(from, to, length)` is the cost of moving
+    // from one cell to a neighbor towards the goal, where `length` is the distance between
+    // their centers.
+    fn flood<FT, FC>(
+        width: usize,
+        height: usize,
+        resolution: f64,
+        goal: (usize, usize),
+        is_traversable: FT,
+        step_cost: FC,
+    ) -> Self
+    where
+        FT: Fn(usize, usize) -> bool,
+        FC: Fn((usize, usize), (usize, usize), f64) -> f64,
+    {
+        let mut costs = vec![f64::INFINITY; width * height];
+        let mut next = vec![None; width * height];
+        costs[goal.1 * width + goal.0] = 0.0;
+        let mut queue = BinaryHeap::from([Reverse(Visit {
+            cost: 0.0,
+            node: goal,
+        })]);
+        let traversable = |column: usize, row: usize| {
+            column < width && row < height && is_traversable(column, row)
+        };
+        while let Some(Reverse(Visit {
+            cost,
+            node: (column, row),
+        })) = queue.pop()
+        {
+            let current = row * width + column;
+            if cost > costs[current] {
+                continue;
+            }
+            for (dx, dy) in [
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ] {
+                let (Some(next_column), Some(next_row)) =
+                    (column.checked_add_signed(dx), row.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                if !traversable(next_column, next_row) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                if diagonal && !(traversable(next_column, row) && traversable(column, next_row)) {
+                    continue;
+                }
+                let step = if diagonal { SQRT_2 } else { 1.0 };
+                let next_cost =
+                    cost + step_cost((next_column, next_row), (column, row), step * resolution);
+                let index = next_row * width + next_column;
+                if next_cost < costs[index] {
+                    costs[index] = next_cost;
+                    next[index] = Some(current);
+                    queue.push(Reverse(Visit {
+                        cost: next_cost,
+                        node: (next_column, next_row),
+                    }));
+                }
+            }
+        }
+
+        CostField {
+            width,
+            height,
+            resolution,
+            costs,
+            next,
+        }
+    }
+
+    // Index of the cell containing the world coordinate, if it is within the grid
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn index_at(&self, x: f64, y: f64) -> Option<usize> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let (column, row) = (
+            (x / self.resolution).floor() as usize,
+            (y / self.resolution).floor() as usize,
+        );
+        (column < self.width && row < self.height).then(|| row * self.width + column)
+    }
+
+    /// Returns the cost-to-go from the world coordinate, which is infinite outside the
+    /// grid or in cells that cannot reach the goal.
+    #[must_use]
+    pub fn cost_at(&self, x: f64, y: f64) -> f64 {
+        self.index_at(x, y)
+            .map_or(f64::INFINITY, |index| self.costs[index])
+    }
+
+    /// Returns the lowest cost grid path from the world coordinate to the goal, as the
+    /// centers of the cells along it, or `None` if the goal cannot be reached.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn path_from(&self, x: f64, y: f64) -> Option<Vec<(f64, f64)>> {
+        let mut index = self
+            .index_at(x, y)
+            .filter(|&index| self.costs[index].is_finite())?;
+        let center = |index: usize| {
+            (
+                ((index % self.width) as f64 + 0.5) * self.resolution,
+                ((index / self.width) as f64 + 0.5) * self.resolution,
+            )
+        };
+        let mut path = vec![center(index)];
+        while let Some(next) = self.next[index] {
+            path.push(center(next));
+            index = next;
+        }
+        Some(path)
+    }
+}
+
+/// Options for biasing RRT with a heuristic cost-to-go.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuidanceOptions {
+    /// Number of samples drawn per iteration, the one with the lowest heuristic is used.
+    /// One disables sample biasing.
+    pub sample_candidates: usize,
+
+    /// Number of nearest nodes considered when choosing which node to extend. One
+    /// extends from the nearest node as in regular RRT.
+    pub node_candidates: usize,
+
+    /// Weight of the heuristic when choosing a node to extend, candidates are scored by
+    /// their distance to the sample plus the weighted heuristic.
+    pub heuristic_weight: f64,
+}
+
+impl Default for GuidanceOptions {
+    fn default() -> Self {
+        GuidanceOptions {
+            sample_candidates: 3,
+            node_candidates: 10,
+            heuristic_weight: 1.0,
+        }
+    }
+}
+
+// Chooses the node to extend towards the sample, scoring the nearest candidates by
+// their distance to the sample plus the weighted heuristic
+fn select_node<T, FH>(
+    tree: &HashTree<T>,
+    sample: &T,
+    heuristic_fn: &FH,
+    guidance: &GuidanceOptions,
+) -> T
+where
+    T: Eq + Clone + Hash + Distance,
+    FH: Fn(&T) -> f64,
+{
+    tree.k_nearest_neighbors(sample, guidance.node_candidates.max(1))
+        .into_iter()
+        .map(|(node, distance)| {
+            (
+                node,
+                distance + guidance.heuristic_weight * heuristic_fn(node),
+            )
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or_else(|| tree.nearest_neighbor(sample), |(node, _)| node)
+        .clone()
+}
+
+/// RRT guided by a heuristic estimate of the cost-to-go, such as a [`CostField`].
+///
+/// Each iteration draws several samples and keeps the one with the lowest heuristic,
+/// then extends from whichever of the nearest nodes minimizes its distance to the
+/// sample plus the weighted heuristic. This focuses the search towards the goal before
+/// any solution exists, the heuristic need not be admissible. Samples and nodes with an
+/// infinite heuristic are never preferred over finite ones.
+///
+/// # Errors
+///
+/// If no path to the goal is found before the termination condition is met.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_guided<T, FS, FE, FC, FH, TC>(
+    start: &T,
+    goal: &T,
+    mut sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    heuristic_fn: FH,
+    options: &RrtOptions,
+    guidance: &GuidanceOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    FH: Fn(&T) -> f64,
+    TC: TerminationCondition,
+{
+    let guided_sample_fn = || {
+        let mut best = sample_fn();
+        let mut best_cost = heuristic_fn(&best);
+        for _ in 1..guidance.sample_candidates {
+            let sample = sample_fn();
+            let cost = heuristic_fn(&sample);
+            if cost < best_cost {
+                best = sample;
+                best_cost = cost;
+            }
+        }
+        best
+    };
+    let select_fn =
+        |tree: &HashTree<T>, sample: &T| select_node(tree, sample, &heuristic_fn, guidance);
+
+    rrt_with_selection(
+        start,
+        goal,
+        guided_sample_fn,
+        select_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        stats,
+    )
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::CellState;
+    use crate::planning::termination::MaxIterations;
+
+    #[test]
+    fn test_cost_field() {
+        // A wall along column 2 with a gap at the top row
+        let mut grid = OccupancyGrid::new(5, 5, 1.0);
+        for row in 0..4 {
+            grid.set_state(2, row, CellState::Occupied).unwrap();
+        }
+        let field = CostField::from_grid(&grid, (0.5, 0.5)).unwrap();
+
+        assert!(field.cost_at(0.5, 0.5).abs() < 1e-9);
+        assert!((field.cost_at(1.5, 0.5) - 1.0).abs() < 1e-9);
+        assert!((field.cost_at(1.5, 1.5) - SQRT_2).abs() < 1e-9);
+        assert!(field.cost_at(2.5, 0.5).is_infinite());
+        assert!(field.cost_at(-1.0, 0.5).is_infinite());
+        assert!(field.cost_at(5.5, 0.5).is_infinite());
+
+        // Across the wall the flood goes around through the gap
+        let around = field.cost_at(3.5, 0.5);
+        assert!(around > 6.0, "Expected a detour, got {around}");
+
+        // A fully walled off cell is unreachable
+        grid.set_state(4, 4, CellState::Free).unwrap();
+        grid.set_state(3, 4, CellState::Occupied).unwrap();
+        grid.set_state(3, 3, CellState::Occupied).unwrap();
+        grid.set_state(4, 3, CellState::Occupied).unwrap();
+        let field = CostField::from_grid(&grid, (0.5, 0.5)).unwrap();
+        assert!(field.cost_at(4.5, 4.5).is_infinite());
+
+        assert_eq!(
+            CostField::from_grid(&grid, (2.5, 0.5)),
+            Err(CostFieldError::GoalNotTraversable)
+        );
+        assert_eq!(
+            CostField::from_grid(&grid, (9.5, 0.5)),
+            Err(CostFieldError::GoalOutsideGrid)
+        );
+    }
+
+    #[test]
+    fn test_cost_field_terrain() {
+        // Crossing the mud in the middle row is worse than going around it
+        let mut terrain = TerrainGrid::new(3, 3, 1.0, 1.0);
+        assert!(terrain.set_cost(1, 1, 10.0).is_ok());
+        assert!(terrain.set_cost(0, 1, 10.0).is_ok());
+        let field = CostField::from_terrain(&terrain, (0.5, 0.5)).unwrap();
+
+        // Going around takes two straight and two diagonal steps over open ground
+        let path = field.path_from(0.5, 2.5).unwrap();
+        assert_eq!(path.first(), Some(&(0.5, 2.5)));
+        assert_eq!(path.last(), Some(&(0.5, 0.5)));
+        assert!(!path.contains(&(1.5, 1.5)));
+        assert!(!path.contains(&(0.5, 1.5)));
+        assert!((field.cost_at(0.5, 2.5) - (2.0 + 2.0 * SQRT_2)).abs() < 1e-9);
+
+        // Impassable terrain is a wall
+        assert!(terrain.set_cost(2, 1, f64::INFINITY).is_ok());
+        let field = CostField::from_terrain(&terrain, (0.5, 0.5)).unwrap();
+        let path = field.path_from(0.5, 2.5).unwrap();
+        assert_eq!(path, vec![(0.5, 2.5), (0.5, 1.5), (0.5, 0.5)]);
+        assert!((field.cost_at(0.5, 2.5) - 11.0).abs() < 1e-9);
+
+        assert_eq!(
+            CostField::from_terrain(&terrain, (2.5, 1.5)),
+            Err(CostFieldError::GoalNotTraversable)
+        );
+        assert!(field.path_from(5.0, 5.0).is_none());
+    }
+
+    #[test]
+    fn test_cost_field_edge_costs() {
+        // Climbing towards larger x costs three times as much as descending
+        let grid = OccupancyGrid::filled(3, 1, 1.0, CellState::Free);
+        let climb = |from: (f64, f64), to: (f64, f64)| {
+            let rise = to.0 - from.0;
+            if rise > 0.0 {
+                3.0 * rise
+            } else {
+                -rise
+            }
+        };
+        let downhill = CostField::from_edge_costs(&grid, (0.5, 0.5), climb).unwrap();
+        assert!((downhill.cost_at(2.5, 0.5) - 2.0).abs() < 1e-9);
+        let uphill = CostField::from_edge_costs(&grid, (2.5, 0.5), climb).unwrap();
+        assert!((uphill.cost_at(0.5, 0.5) - 6.0).abs() < 1e-9);
+
+        // Moves with an infinite cost are cut
+        let cliff = |from: (f64, f64), to: (f64, f64)| {
+            if from.0 > 2.0 || to.0 > 2.0 {
+                f64::INFINITY
+            } else {
+                climb(from, to)
+            }
+        };
+        let field = CostField::from_edge_costs(&grid, (0.5, 0.5), cliff).unwrap();
+        assert!((field.cost_at(1.5, 0.5) - 1.0).abs() < 1e-9);
+        assert!(field.path_from(2.5, 0.5).is_none());
+        assert!(CostField::from_edge_costs(&grid, (5.0, 0.5), climb).is_err());
+    }
+
+    #[test]
+    fn test_rrt_guided() {
+        // Samples cycle through the line, the heuristic prefers those closest to the goal
+        let samples = [-10, -5, 10, 0, 3];
+        let mut index = 0;
+        let sample_fn = || {
+            let sample = samples[index % samples.len()];
+            index += 1;
+            sample
+        };
+        let extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let connectable_fn = |_: &i32, _: &i32| true;
+        let heuristic_fn = |state: &i32| f64::from((10 - state).abs());
+        let options = RrtOptions {
+            max_extension: 1.0,
+            ..RrtOptions::default()
+        };
+        let guidance = GuidanceOptions {
+            sample_candidates: samples.len(),
+            ..GuidanceOptions::default()
+        };
+        let mut stats = PlannerStats::new();
+
+        let (path, tree) = rrt_guided(
+            &0,
+            &10,
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            heuristic_fn,
+            &options,
+            &guidance,
+            MaxIterations(100),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(path, (0..=10).collect::<Vec<i32>>());
+
+        // Only the sample towards the goal was ever used
+        assert_eq!(tree.size(), 11);
+    }
+
+    #[test]
+    fn test_select_node() {
+        let mut tree = HashTree::new(0);
+        tree.add_child(&0, -1).unwrap();
+        tree.add_child(&0, 1).unwrap();
+        let heuristic_fn = |state: &i32| f64::from((10 - state).abs());
+
+        // Without weight on the heuristic this is the nearest node
+        let mut guidance = GuidanceOptions {
+            heuristic_weight: 0.0,
+            ..GuidanceOptions::default()
+        };
+        assert_eq!(select_node(&tree, &0, &heuristic_fn, &guidance), 0);
+
+        // Otherwise a slightly farther node closer to the goal wins
+        guidance.heuristic_weight = 2.0;
+        assert_eq!(select_node(&tree, &0, &heuristic_fn, &guidance), 1);
+
+        // Unless it is not among the candidates
+        guidance.node_candidates = 1;
+        assert_eq!(select_node(&tree, &0, &heuristic_fn, &guidance), 0);
+    }
+}
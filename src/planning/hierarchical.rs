@@ -0,0 +1,502 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::HierarchicalError;
+use crate::grid::OccupancyGrid;
+use crate::planning::heuristic::Visit;
+use crate::planning::rrt::{rrt_with_termination, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::{Distance, HashTree};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::Hash;
+
+/// A graph of regions of the world, with edges between regions that can be travelled
+/// between directly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionGraph {
+    edges: Vec<Vec<(usize, f64)>>,
+}
+
+impl RegionGraph {
+    /// Construct a graph of `regions` regions with no edges.
+    #[must_use]
+    pub fn new(regions: usize) -> Self {
+        RegionGraph {
+            edges: vec![Vec::new(); regions],
+        }
+    }
+
+    /// Number of regions in the graph.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Returns true if the graph has no regions.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// Connects two regions in both directions with the given traversal cost.
+    ///
+    /// # Errors
+    ///
+    /// If either region is not in the graph.
+    pub fn add_edge(&mut self, a: usize, b: usize, cost: f64) -> Result<(), HierarchicalError> {
+        if a >= self.len() || b >= self.len() {
+            return Err(HierarchicalError::UnknownRegion(a.max(b)));
+        }
+        self.connect(a, b, cost);
+        Ok(())
+    }
+
+    // Adds an edge between two regions that are known to be in the graph
+    fn connect(&mut self, a: usize, b: usize, cost: f64) {
+        self.edges[a].push((b, cost));
+        self.edges[b].push((a, cost));
+    }
+
+    /// Regions adjacent to `region`, with the cost of travelling to them.
+    #[must_use]
+    pub fn neighbors(&self, region: usize) -> &[(usize, f64)] {
+        self.edges.get(region).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the cheapest sequence of regions from `from` to `to`, inclusive, or `None`
+    /// if `to` can't be reached.
+    #[must_use]
+    pub fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        if from >= self.len() || to >= self.len() {
+            return None;
+        }
+
+        let mut costs = vec![f64::INFINITY; self.len()];
+        let mut previous: Vec<Option<usize>> = vec![None; self.len()];
+        let mut queue = BinaryHeap::from([Reverse(Visit {
+            cost: 0.0,
+            node: from,
+        })]);
+        costs[from] = 0.0;
+        while let Some(Reverse(Visit { cost, node: region })) = queue.pop() {
+            if region == to {
+                break;
+            }
+            if cost > costs[region] {
+                continue;
+            }
+            for &(next, edge_cost) in &self.edges[region] {
+                let next_cost = cost + edge_cost;
+                if next_cost < costs[next] {
+                    costs[next] = next_cost;
+                    previous[next] = Some(region);
+                    queue.push(Reverse(Visit {
+                        cost: next_cost,
+                        node: next,
+                    }));
+                }
+            }
+        }
+
+        if costs[to].is_infinite() {
+            return None;
+        }
+        let mut path = vec![to];
+        while let Some(region) = previous[*path.last()?] {
+            path.push(region);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Returns the regions along `path` plus every region within `width` edges of it.
+    #[must_use]
+    pub fn corridor(&self, path: &[usize], width: usize) -> HashSet<usize> {
+        let mut corridor: HashSet<usize> = path.iter().copied().collect();
+        let mut frontier: Vec<usize> = path.to_vec();
+        for _ in 0..width {
+            let mut next = Vec::new();
+            for region in frontier {
+                for &(neighbor, _) in self.neighbors(region) {
+                    if corridor.insert(neighbor) {
+                        next.push(neighbor);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        corridor
+    }
+}
+
+/// Partitions an [`OccupancyGrid`] into square blocks of cells, used as regions for
+/// hierarchical planning.
+///
+/// Regions are numbered in row-major order of blocks. Two horizontally or vertically
+/// adjacent blocks are connected if some pair of traversable cells across their shared
+/// border are adjacent, with the distance between block centers as the cost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridRegions {
+    /// Number of grid cells along each side of a block.
+    block_size: usize,
+
+    /// Number of blocks along each axis.
+    columns: usize,
+    rows: usize,
+
+    resolution: f64,
+    graph: RegionGraph,
+}
+
+impl GridRegions {
+    /// Partitions the grid into blocks of `block_size` by `block_size` cells.
+    ///
+    /// # Panics
+    ///
+    /// If `block_size` is 0.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(grid: &OccupancyGrid, block_size: usize) -> Self {
+        assert!(block_size > 0, "Block size must be positive");
+        let columns = grid.width().div_ceil(block_size);
+        let rows = grid.height().div_ceil(block_size);
+        let mut graph = RegionGraph::new(columns * rows);
+        let cost = block_size as f64 * grid.resolution();
+        let traversable = |a: (usize, usize), b: (usize, usize)| {
+            grid.is_traversable(a.0, a.1) && grid.is_traversable(b.0, b.1)
+        };
+
+        for block_row in 0..rows {
+            for block_column in 0..columns {
+                let region = block_row * columns + block_column;
+
+                // Border with the block to the right
+                let column = (block_column + 1) * block_size;
+                if block_column + 1 < columns
+                    && (block_row * block_size..((block_row + 1) * block_size).min(grid.height()))
+                        .any(|row| traversable((column - 1, row), (column, row)))
+                {
+                    graph.connect(region, region + 1, cost);
+                }
+
+                // Border with the block above
+                let row = (block_row + 1) * block_size;
+                if block_row + 1 < rows
+                    && (block_column * block_size
+                        ..((block_column + 1) * block_size).min(grid.width()))
+                        .any(|column| traversable((column, row - 1), (column, row)))
+                {
+                    graph.connect(region, region + columns, cost);
+                }
+            }
+        }
+
+        GridRegions {
+            block_size,
+            columns,
+            rows,
+            resolution: grid.resolution(),
+            graph,
+        }
+    }
+
+    /// The region adjacency graph.
+    #[must_use]
+    pub fn graph(&self) -> &RegionGraph {
+        &self.graph
+    }
+
+    /// Returns the region containing the point, if it is inside the grid.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn region_at(&self, x: f64, y: f64) -> Option<usize> {
+        let size = self.block_size as f64 * self.resolution;
+        if x.is_nan() || y.is_nan() || x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let (column, row) = ((x / size) as usize, (y / size) as usize);
+        (column < self.columns && row < self.rows).then_some(row * self.columns + column)
+    }
+
+    /// Returns the lower and upper corners of a region in world coordinates. Blocks at the
+    /// edges of the grid may extend past it.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn region_bounds(&self, region: usize) -> ((f64, f64), (f64, f64)) {
+        let size = self.block_size as f64 * self.resolution;
+        let (column, row) = (region % self.columns, region / self.columns);
+        let lower = (column as f64 * size, row as f64 * size);
+        (lower, (lower.0 + size, lower.1 + size))
+    }
+}
+
+/// Controls how [`plan_hierarchical`] uses the region corridor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HierarchicalOptions {
+    /// Regions within this many edges of the region path are included in the corridor.
+    pub corridor_width: usize,
+
+    /// Maximum number of samples drawn to find one inside the corridor, after which the
+    /// last sample is used anyway. Lower values bias rather than restrict exploration.
+    pub max_sample_attempts: usize,
+
+    /// Reject motions that leave the corridor.
+    pub restrict_motions: bool,
+}
+
+impl Default for HierarchicalOptions {
+    fn default() -> Self {
+        HierarchicalOptions {
+            corridor_width: 1,
+            max_sample_attempts: 100,
+            restrict_motions: false,
+        }
+    }
+}
+
+/// Two level planning: finds a corridor of regions between the start and goal, then runs
+/// RRT within it.
+///
+/// `region_fn` maps a state to the region of `graph` containing it, e.g. with
+/// [`GridRegions::region_at`]. The cheapest sequence of regions from the start to the goal
+/// is widened into a corridor as configured by `hierarchy`, samples outside of it are
+/// redrawn, and optionally motions leaving it are rejected. Only the endpoints of motions
+/// are checked, so `connectable_fn` is still responsible for validating the motion itself.
+/// Otherwise planning is the same as
+/// [`crate::planning::rrt::rrt_with_termination`].
+///
+/// # Errors
+///
+/// If the start or goal is not in a region, if no sequence of regions connects them, or
+/// if RRT fails to find a path.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_hierarchical<T, FR, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    graph: &RegionGraph,
+    region_fn: FR,
+    mut sample_fn: FS,
+    extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    hierarchy: &HierarchicalOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), HierarchicalError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FR: Fn(&T) -> Option<usize>,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let from = region_fn(start).ok_or(HierarchicalError::StartOutsideRegions)?;
+    let to = region_fn(goal).ok_or(HierarchicalError::GoalOutsideRegions)?;
+    let regions = graph
+        .shortest_path(from, to)
+        .ok_or(HierarchicalError::NoRegionPath)?;
+    let corridor = graph.corridor(&regions, hierarchy.corridor_width);
+    let in_corridor = |state: &T| region_fn(state).is_some_and(|region| corridor.contains(&region));
+
+    // Redraw samples until one lands in the corridor
+    let corridor_sample_fn = || {
+        let mut sample = sample_fn();
+        for _ in 1..hierarchy.max_sample_attempts {
+            if in_corridor(&sample) {
+                break;
+            }
+            sample = sample_fn();
+        }
+        sample
+    };
+    let corridor_connectable_fn = |from: &T, to: &T| {
+        (!hierarchy.restrict_motions || (in_corridor(from) && in_corridor(to)))
+            && connectable_fn(from, to)
+    };
+
+    rrt_with_termination(
+        start,
+        goal,
+        corridor_sample_fn,
+        extend_fn,
+        corridor_connectable_fn,
+        options,
+        termination,
+        stats,
+    )
+    .map_err(HierarchicalError::Planning)
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PlanningError;
+    use crate::planning::termination::{MaxIterations, SolutionFound};
+
+    #[test]
+    fn test_region_graph() {
+        // 0 - 1 - 2
+        //  \     /
+        //   - 3 -      4
+        let mut graph = RegionGraph::new(5);
+        graph.add_edge(0, 1, 1.0).unwrap();
+        graph.add_edge(1, 2, 1.0).unwrap();
+        graph.add_edge(0, 3, 0.5).unwrap();
+        graph.add_edge(3, 2, 1.0).unwrap();
+        assert_eq!(
+            graph.add_edge(0, 5, 1.0),
+            Err(HierarchicalError::UnknownRegion(5))
+        );
+
+        assert_eq!(graph.shortest_path(0, 2), Some(vec![0, 3, 2]));
+        assert_eq!(graph.shortest_path(2, 2), Some(vec![2]));
+        assert_eq!(graph.shortest_path(0, 4), None);
+        assert_eq!(graph.shortest_path(0, 9), None);
+
+        let corridor = graph.corridor(&[0, 3], 1);
+        assert_eq!(corridor, HashSet::from([0, 1, 2, 3]));
+        assert_eq!(graph.corridor(&[4], 2), HashSet::from([4]));
+    }
+
+    // A 6x6 grid with a wall across the middle, open only on the right
+    fn wall_grid() -> OccupancyGrid {
+        let mut grid = OccupancyGrid::new(6, 6, 1.0);
+        for column in 0..5 {
+            grid.set_occupied(column, 3, true).unwrap();
+        }
+        grid
+    }
+
+    #[test]
+    fn test_grid_regions() {
+        let regions = GridRegions::new(&wall_grid(), 3);
+        assert_eq!(regions.graph().len(), 4);
+        assert_eq!(regions.region_at(1.0, 1.0), Some(0));
+        assert_eq!(regions.region_at(4.0, 5.0), Some(3));
+        assert_eq!(regions.region_at(6.5, 1.0), None);
+        assert_eq!(regions.region_at(-1.0, 1.0), None);
+        assert_eq!(regions.region_bounds(3), ((3.0, 3.0), (6.0, 6.0)));
+
+        // The wall blocks the left blocks from each other
+        assert_eq!(regions.graph().shortest_path(0, 2), Some(vec![0, 1, 3, 2]));
+    }
+
+    impl Distance for (i32, i32) {
+        fn distance(&self, other: &Self) -> f64 {
+            f64::from(self.0 - other.0).hypot(f64::from(self.1 - other.1))
+        }
+    }
+
+    #[test]
+    fn test_plan_hierarchical() {
+        // A 3x3 grid of regions, the region path along the bottom row is the corridor
+        let grid = OccupancyGrid::new(9, 9, 1.0);
+        let regions = GridRegions::new(&grid, 3);
+
+        // Points on a half cell lattice, sampled from the top down
+        let mut samples = (0..18)
+            .rev()
+            .flat_map(|row| (0..18).map(move |column| (column, row)))
+            .cycle();
+        let to_world = |(column, row): (i32, i32)| (f64::from(column) / 2.0, f64::from(row) / 2.0);
+        let region_fn = |state: &(i32, i32)| {
+            let (x, y) = to_world(*state);
+            regions.region_at(x, y)
+        };
+        let hierarchy = HierarchicalOptions {
+            corridor_width: 0,
+            max_sample_attempts: 1000,
+            restrict_motions: true,
+        };
+
+        let (path, tree) = plan_hierarchical(
+            &(1, 1),
+            &(16, 1),
+            regions.graph(),
+            region_fn,
+            || samples.next().unwrap(),
+            |from: &(i32, i32), to: &(i32, i32)| {
+                (
+                    from.0 + (to.0 - from.0).signum(),
+                    from.1 + (to.1 - from.1).signum(),
+                )
+            },
+            |from: &(i32, i32), to: &(i32, i32)| from.distance(to) <= 2.0,
+            &RrtOptions::default(),
+            &hierarchy,
+            MaxIterations(1000).or(SolutionFound),
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+        assert_eq!(path.first(), Some(&(1, 1)));
+        assert_eq!(path.last(), Some(&(16, 1)));
+
+        // Every node stays in the bottom row of regions
+        for node in tree.iter_depth_first() {
+            assert!(region_fn(node).is_some_and(|region| region < 3), "{node:?}");
+        }
+
+        // Without a region path there's nothing to plan in
+        let graph = RegionGraph::new(regions.graph().len());
+        let result = plan_hierarchical(
+            &(1, 1),
+            &(16, 1),
+            &graph,
+            region_fn,
+            || (0, 0),
+            |from: &(i32, i32), _: &(i32, i32)| *from,
+            |_: &(i32, i32), _: &(i32, i32)| true,
+            &RrtOptions::default(),
+            &hierarchy,
+            MaxIterations(10),
+            &mut PlannerStats::new(),
+        );
+        assert_eq!(result.unwrap_err(), HierarchicalError::NoRegionPath);
+
+        // Failures within the corridor say why planning stopped
+        let result = plan_hierarchical(
+            &(1, 1),
+            &(16, 1),
+            regions.graph(),
+            region_fn,
+            || (0, 0),
+            |from: &(i32, i32), _: &(i32, i32)| *from,
+            |_: &(i32, i32), _: &(i32, i32)| false,
+            &RrtOptions::default(),
+            &hierarchy,
+            MaxIterations(10),
+            &mut PlannerStats::new(),
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            HierarchicalError::Planning(PlanningError::MaxIterationsReached)
+        );
+    }
+}
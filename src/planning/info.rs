@@ -0,0 +1,125 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt;
+
+/// Self-describing record of which planner and configuration produced a result.
+///
+/// Intended for logs and experiment records, so parameter values are stored as their
+/// display strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannerInfo {
+    /// Name of the planner variant, e.g. `RRT*`.
+    pub name: String,
+
+    /// Seed used for any randomness, if known.
+    pub seed: Option<u64>,
+
+    /// Key parameters as name, value pairs in a stable order.
+    pub parameters: Vec<(String, String)>,
+}
+
+impl PlannerInfo {
+    /// Construct a new record for the named planner with no parameters.
+    pub fn new(name: impl Into<String>) -> Self {
+        PlannerInfo {
+            name: name.into(),
+            seed: None,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Record the seed used for the run.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Append a parameter to the record.
+    #[must_use]
+    pub fn with_parameter(mut self, name: impl Into<String>, value: impl fmt::Display) -> Self {
+        self.parameters.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Returns the recorded value of the named parameter, if present.
+    #[must_use]
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// Renders as `RRT* (seed=1, rewire_radius=2)`
+impl fmt::Display for PlannerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        let seed = self.seed.map(|seed| ("seed".to_string(), seed.to_string()));
+        let mut parameters = seed.iter().chain(&self.parameters).peekable();
+        if parameters.peek().is_none() {
+            return Ok(());
+        }
+
+        write!(f, " (")?;
+        for (i, (name, value)) in parameters.enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{name}={value}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planner_info() {
+        let info = PlannerInfo::new("RRT*")
+            .with_parameter("rewire_radius", 2.5)
+            .with_parameter("use_rrtconnect", false);
+        assert_eq!(info.parameter("rewire_radius"), Some("2.5"));
+        assert_eq!(info.parameter("max_extension"), None);
+        assert_eq!(
+            info.to_string(),
+            "RRT* (rewire_radius=2.5, use_rrtconnect=false)"
+        );
+
+        let info = info.with_seed(7);
+        assert_eq!(
+            info.to_string(),
+            "RRT* (seed=7, rewire_radius=2.5, use_rrtconnect=false)"
+        );
+
+        assert_eq!(PlannerInfo::new("RRT").to_string(), "RRT");
+    }
+}
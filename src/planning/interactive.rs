@@ -0,0 +1,342 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::clock::SystemClock;
+use crate::error::PlanningError;
+use crate::planning::replay::PlannerEvent;
+use crate::planning::rrt::{grow_tree_with_selection, select_node, solution_path, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::TerminationCondition;
+use crate::planning::tuning::SplitMix64;
+use crate::tree::{Distance, HashTree};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// What happened during a single iteration of the planner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IterationSummary<T> {
+    /// Index of the iteration.
+    pub iteration: u64,
+
+    /// The sample the tree was extended towards.
+    pub sample: T,
+
+    /// Nodes added to the tree, in the order they were added, including any added while
+    /// connecting to the goal.
+    pub added: Vec<T>,
+
+    /// Number of nodes moved to a new parent by RRT* rewiring.
+    pub rewires: usize,
+
+    /// Cost of the path to the goal after the iteration, if one has been found.
+    pub best_cost: Option<f64>,
+
+    /// Distance from the goal to the node closest to it after the iteration.
+    pub goal_distance: f64,
+
+    /// Every sample, tree change, and solution improvement during the iteration, in the
+    /// order they occurred.
+    pub events: Vec<PlannerEvent<T>>,
+}
+
+impl<T: Eq + Clone + Hash + Distance> IterationSummary<T> {
+    // Summarizes an iteration that started with `size` nodes in the tree.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        iteration: u64,
+        sample: T,
+        tree: &HashTree<T>,
+        size: usize,
+        rewires: usize,
+        goal: &T,
+        stats: &PlannerStats<T>,
+        events: Vec<PlannerEvent<T>>,
+    ) -> Self {
+        IterationSummary {
+            iteration,
+            sample,
+            added: tree.iter_insertion_order().skip(size).cloned().collect(),
+            rewires,
+            best_cost: tree.cost(goal).ok(),
+            goal_distance: stats
+                .nearest_to_goal
+                .as_ref()
+                .map_or(f64::INFINITY, |(_, distance)| *distance),
+            events,
+        }
+    }
+}
+
+/// How the planner should proceed after an iteration of [`rrt_interactive`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepControl<T> {
+    /// Run the next iteration as normal.
+    Continue,
+
+    /// Stop planning and return the result so far.
+    Stop,
+
+    /// Extend towards this state in the next iteration instead of drawing a sample.
+    Sample(T),
+
+    /// From the next iteration on, sample the goal itself with this probability.
+    GoalBias(f64),
+
+    /// Move the goal to this state, e.g. to follow a moving target, keeping the tree. The
+    /// new goal is connected to the tree straight away if possible, and goal biased
+    /// samples are drawn from it from then on.
+    MoveGoal(T),
+}
+
+/// RRT that hands control back to the caller after every iteration, e.g. to visualize the
+/// search one step at a time or let a user steer it.
+///
+/// `step_fn` is given a summary of each iteration along with the tree, and decides how
+/// planning proceeds. Planning also stops once the termination condition is met. Goal
+/// biasing draws from [`RrtOptions::seed`], so a sequence of controls always produces the
+/// same run given the same options and samples.
+///
+/// # Errors
+///
+/// If no path to the goal has been found when planning stops.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_interactive<T, FS, FE, FC, TC, FU>(
+    start: &T,
+    goal: &T,
+    mut sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    mut step_fn: FU,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+    FU: FnMut(&IterationSummary<T>, &HashTree<T>) -> StepControl<T>,
+{
+    let next_sample: Cell<Option<T>> = Cell::new(None);
+    let goal_bias = Cell::new(0.0);
+    let current_goal = RefCell::new(goal.clone());
+    let mut rng = SplitMix64(options.seed);
+    let steered_sample_fn = || {
+        if let Some(sample) = next_sample.take() {
+            sample
+        } else if goal_bias.get() > 0.0 && rng.next_f64() < goal_bias.get() {
+            current_goal.borrow().clone()
+        } else {
+            sample_fn()
+        }
+    };
+    let mut observer = |summary: &IterationSummary<T>, tree: &HashTree<T>| {
+        match step_fn(summary, tree) {
+            StepControl::Sample(sample) => next_sample.set(Some(sample)),
+            StepControl::GoalBias(bias) => goal_bias.set(bias),
+            StepControl::MoveGoal(goal) => {
+                current_goal.replace(goal.clone());
+                return StepControl::MoveGoal(goal);
+            }
+            control => return control,
+        }
+        StepControl::Continue
+    };
+
+    let mut tree = HashTree::new(start.clone());
+    let mut visits = HashMap::new();
+    grow_tree_with_selection(
+        &mut tree,
+        goal,
+        steered_sample_fn,
+        |tree: &HashTree<T>, sample: &T| {
+            select_node(tree, sample, options.node_selection, &mut visits)
+        },
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        &SystemClock::start(),
+        0,
+        stats,
+        Some(&mut observer),
+    );
+
+    let path = solution_path(&tree, &current_goal.into_inner(), options, stats)?;
+    Ok((path, tree))
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::termination::MaxIterations;
+
+    fn options() -> RrtOptions {
+        RrtOptions {
+            max_extension: 1.0,
+            use_rrtstar: true,
+            rewire_radius: 1.0,
+            ..RrtOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_rrt_interactive() {
+        // Samples always pull away from the goal, so the user has to steer
+        let mut summaries = Vec::new();
+        let (path, tree) = rrt_interactive(
+            &0,
+            &3,
+            || -5,
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &options(),
+            MaxIterations(100),
+            |summary: &IterationSummary<i32>, tree: &HashTree<i32>| {
+                summaries.push(summary.clone());
+                assert!(summary.added.iter().all(|node| tree.contains(node)));
+                match summary.iteration {
+                    0 => StepControl::Sample(1),
+                    1 => StepControl::Sample(2),
+                    _ if summary.best_cost.is_some() => StepControl::Stop,
+                    _ => StepControl::Continue,
+                }
+            },
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+
+        // Extending to 2 also connects the goal in the same iteration
+        assert_eq!(path, vec![0, 1, 2, 3]);
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].sample, -5);
+        assert_eq!(summaries[0].added, vec![-1]);
+        assert_eq!(summaries[1].sample, 1);
+        assert_eq!(summaries[2].added, vec![2, 3]);
+        assert_eq!(summaries[2].best_cost, Some(3.0));
+        let goal_distances: Vec<f64> = summaries.iter().map(|s| s.goal_distance).collect();
+        assert_eq!(goal_distances, vec![3.0, 2.0, 0.0]);
+        assert_eq!(tree.size(), 5);
+    }
+
+    #[test]
+    fn test_rrt_interactive_goal_bias() {
+        let mut iterations = 0;
+        let result = rrt_interactive(
+            &0,
+            &3,
+            || -5,
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &options(),
+            MaxIterations(100),
+            |summary: &IterationSummary<i32>, _: &HashTree<i32>| {
+                iterations += 1;
+                if summary.best_cost.is_some() {
+                    StepControl::Stop
+                } else {
+                    StepControl::GoalBias(1.0)
+                }
+            },
+            &mut PlannerStats::new(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(iterations, 3);
+
+        // Stopping straight away leaves only the first extension
+        let result = rrt_interactive(
+            &0,
+            &3,
+            || -5,
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &options(),
+            MaxIterations(100),
+            |_: &IterationSummary<i32>, _: &HashTree<i32>| StepControl::Stop,
+            &mut PlannerStats::new(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_rrt_interactive_seed() {
+        // Which samples are replaced by the goal depends only on the seed
+        let goal_samples = |seed: u64| {
+            let mut samples = Vec::new();
+            let _ = rrt_interactive(
+                &0,
+                &30,
+                || -5,
+                |from: &i32, to: &i32| from + (to - from).signum(),
+                |from: &i32, to: &i32| (to - from).abs() <= 1,
+                &options().with_seed(seed),
+                MaxIterations(20),
+                |summary: &IterationSummary<i32>, _: &HashTree<i32>| {
+                    samples.push(summary.sample);
+                    StepControl::GoalBias(0.5)
+                },
+                &mut PlannerStats::new(),
+            );
+            samples
+        };
+        assert_eq!(goal_samples(1), goal_samples(1));
+        assert_ne!(goal_samples(1), goal_samples(2));
+        assert!(goal_samples(1).contains(&30));
+    }
+
+    #[test]
+    fn test_rrt_interactive_move_goal() {
+        // The target moves behind the start after the first extension
+        let mut goals = Vec::new();
+        let (path, tree) = rrt_interactive(
+            &0,
+            &3,
+            || -5,
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &options(),
+            MaxIterations(100),
+            |summary: &IterationSummary<i32>, _: &HashTree<i32>| {
+                goals.push(summary.goal_distance);
+                match summary.iteration {
+                    0 => StepControl::MoveGoal(-2),
+                    _ if summary.best_cost.is_some() => StepControl::Stop,
+                    _ => StepControl::GoalBias(1.0),
+                }
+            },
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+
+        // The node added before the move is kept and reused
+        assert_eq!(path, vec![0, -1, -2]);
+        assert_eq!(goals, vec![3.0, 0.0]);
+        assert_eq!(tree.size(), 3);
+    }
+}
@@ -20,4 +20,27 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+pub mod batch;
+pub mod bidirectional;
+pub mod coverage;
+pub mod diagnostics;
+pub mod events;
+pub mod exploration;
+pub mod fallback;
+pub mod goal;
+pub mod heuristic;
+pub mod hierarchical;
+pub mod info;
+pub mod interactive;
+pub mod objective;
+pub mod planner;
+pub mod prm;
+pub mod radius;
+pub mod repair;
+pub mod replay;
 pub mod rrt;
+pub mod stats;
+pub mod termination;
+pub mod tour;
+pub mod tuning;
+pub mod waypoints;
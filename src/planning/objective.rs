@@ -0,0 +1,129 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::tree::{Distance, EdgeCost};
+
+/// Path length plus a penalty on turning, so that RRT* converges towards straighter paths.
+///
+/// Each edge costs its [`Distance`] plus `turn_weight` times the angle in radians between
+/// it and the edge before it, where `position_fn` returns the position of a state in any
+/// number of dimensions. Edges from the root of the tree have no turning penalty. Use with
+/// [`crate::planning::rrt::rrt_with_edge_cost`].
+pub fn smoothness_cost<T, FP>(turn_weight: f64, position_fn: FP) -> EdgeCost<T>
+where
+    T: Distance,
+    FP: Fn(&T) -> Vec<f64> + Send + Sync + 'static,
+{
+    EdgeCost::with_predecessor(move |previous: Option<&T>, from: &T, to: &T| {
+        let turn = previous.map_or(0.0, |previous| {
+            turning_angle(&position_fn(previous), &position_fn(from), &position_fn(to))
+        });
+        from.distance(to) + turn_weight * turn
+    })
+}
+
+/// Returns the angle in radians between the direction from `a` to `b` and the direction
+/// from `b` to `c`, or 0 if either segment has no length.
+#[must_use]
+pub fn turning_angle(a: &[f64], b: &[f64], c: &[f64]) -> f64 {
+    let incoming: Vec<f64> = b.iter().zip(a).map(|(b, a)| b - a).collect();
+    let outgoing: Vec<f64> = c.iter().zip(b).map(|(c, b)| c - b).collect();
+    let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let lengths = norm(&incoming) * norm(&outgoing);
+    if lengths == 0.0 {
+        return 0.0;
+    }
+    let dot: f64 = incoming.iter().zip(&outgoing).map(|(i, o)| i * o).sum();
+    (dot / lengths).clamp(-1.0, 1.0).acos()
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::FloatArray;
+    use crate::planning::rrt::{rrt_with_edge_cost, RrtOptions};
+    use crate::planning::stats::PlannerStats;
+    use crate::planning::termination::MaxIterations;
+    use crate::tree::HashTree;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    fn position(p: &FloatArray<2>) -> Vec<f64> {
+        p.0.to_vec()
+    }
+
+    #[test]
+    fn test_turning_angle() {
+        assert!(turning_angle(&[0.0, 0.0], &[1.0, 0.0], &[2.0, 0.0]).abs() < 1e-9);
+        assert!((turning_angle(&[0.0, 0.0], &[1.0, 0.0], &[1.0, 1.0]) - FRAC_PI_2).abs() < 1e-9);
+        assert!((turning_angle(&[0.0, 0.0], &[1.0, 0.0], &[0.0, 0.0]) - PI).abs() < 1e-9);
+        assert!(turning_angle(&[0.0, 0.0], &[0.0, 0.0], &[1.0, 0.0]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_smoothness_cost_in_tree() {
+        let (a, b, c, d) = (
+            FloatArray([0.0, 0.0]),
+            FloatArray([1.0, 0.0]),
+            FloatArray([2.0, 0.0]),
+            FloatArray([1.0, 1.0]),
+        );
+        let cost = smoothness_cost(2.0, position);
+        let mut tree = HashTree::with_edge_cost(a, cost.clone());
+        tree.add_child(&a, b).unwrap();
+        tree.add_child(&b, c).unwrap();
+        tree.add_child(&b, d).unwrap();
+
+        assert!((tree.cost(&c).unwrap() - 2.0).abs() < 1e-9);
+        assert!((tree.cost(&d).unwrap() - (2.0 + PI)).abs() < 1e-9);
+        assert!((cost.path_cost(&tree.path(&d).unwrap()) - tree.cost(&d).unwrap()).abs() < 1e-9);
+
+        // Going straight on from the root costs nothing extra
+        assert!((tree.edge_cost(&a, &d) - 2.0_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rrt_with_smoothness() {
+        // A corner on the way to the goal is paid for in the path cost
+        let goal = FloatArray([1.0, 1.0]);
+        let cost = smoothness_cost(1.0, position);
+        let mut samples = [FloatArray([1.0, 0.0]), goal].into_iter();
+        let (path, tree) = rrt_with_edge_cost(
+            &FloatArray([0.0, 0.0]),
+            &goal,
+            || samples.next().unwrap(),
+            |_: &FloatArray<2>, to: &FloatArray<2>| *to,
+            |from: &FloatArray<2>, to: &FloatArray<2>| from.distance(to) <= 1.0,
+            cost.clone(),
+            &RrtOptions::default(),
+            MaxIterations(2),
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+        assert_eq!(path.len(), 3);
+        assert!((tree.cost(&goal).unwrap() - (2.0 + FRAC_PI_2)).abs() < 1e-9);
+        assert!((cost.path_cost(&path) - tree.cost(&goal).unwrap()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,611 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::clock::Instant;
+use crate::error::PlanningError;
+use crate::planning::bidirectional::rrt_bidirectional;
+use crate::planning::events::{rrt_events, RrtEvents};
+use crate::planning::info::PlannerInfo;
+use crate::planning::rrt::{rrt_with_termination, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::{CancellationToken, TerminationCondition};
+use crate::tree::Distance;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// A configured planner that finds paths between pairs of states.
+///
+/// Planners own their sampling, steering, and validity functions, so downstream code can
+/// choose between them at runtime, e.g. as a `Box<dyn Planner<T>>`.
+pub trait Planner<T> {
+    /// Plans a path from `start` to `goal`, including both.
+    ///
+    /// # Errors
+    ///
+    /// If no path to the goal was found.
+    fn plan(&mut self, start: &T, goal: &T) -> Result<Vec<T>, PlanningError>;
+
+    /// Plans as [`Planner::plan`], but also stops as soon as `cancellation` is cancelled,
+    /// e.g. from another thread.
+    ///
+    /// # Errors
+    ///
+    /// [`PlanningError::Cancelled`] if planning was cancelled before a path was found,
+    /// otherwise as [`Planner::plan`].
+    fn plan_cancellable(
+        &mut self,
+        start: &T,
+        goal: &T,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<T>, PlanningError>;
+
+    /// Describes the planner and its parameters.
+    fn info(&self) -> PlannerInfo;
+
+    /// Does any work that only depends on the environment ahead of queries, e.g. building
+    /// a distance field or drawing samples, so that it is not counted against their time.
+    /// Planners with nothing to precompute do nothing.
+    fn precompute(&mut self) {}
+}
+
+type PrecomputeFn = Box<dyn FnMut() + Send>;
+
+/// RRT as a [`Planner`], stopping according to its options' iteration limit, timeout, and
+/// fast return setting, see [`crate::planning::rrt::plan`].
+pub struct Rrt<T, FS, FE, FC> {
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: RrtOptions,
+    stats: PlannerStats<T>,
+    precompute_fn: Option<PrecomputeFn>,
+    presample: usize,
+    samples: VecDeque<T>,
+    precompute_time: Option<Duration>,
+}
+
+impl<T, FS, FE, FC> Rrt<T, FS, FE, FC> {
+    /// Construct a planner that adds edges no longer than `max_extension`.
+    pub fn new(sample_fn: FS, extend_fn: FE, connectable_fn: FC, max_extension: f64) -> Self {
+        let options = RrtOptions::default().with_max_extension(max_extension);
+        Self::with_options(sample_fn, extend_fn, connectable_fn, options)
+    }
+
+    /// Construct a planner with any RRT variant and parameters.
+    pub fn with_options(
+        sample_fn: FS,
+        extend_fn: FE,
+        connectable_fn: FC,
+        options: RrtOptions,
+    ) -> Self {
+        Rrt {
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            options,
+            stats: PlannerStats::new(),
+            precompute_fn: None,
+            presample: 0,
+            samples: VecDeque::new(),
+            precompute_time: None,
+        }
+    }
+
+    /// Runs `precompute_fn` in [`Planner::precompute`], for environment dependent work the
+    /// other functions rely on, e.g. inflating a costmap.
+    #[must_use]
+    pub fn with_precompute<F>(mut self, precompute_fn: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.precompute_fn = Some(Box::new(precompute_fn));
+        self
+    }
+
+    /// Draws `count` samples in [`Planner::precompute`], which the following queries use
+    /// before drawing any more. Samples are drawn in the same order either way, so this
+    /// does not change the planner's results.
+    #[must_use]
+    pub fn with_presampling(mut self, count: usize) -> Self {
+        self.presample = count;
+        self
+    }
+
+    /// Returns the options used for planning.
+    #[must_use]
+    pub fn options(&self) -> &RrtOptions {
+        &self.options
+    }
+
+    /// Returns the options used for planning, for changing them between queries.
+    pub fn options_mut(&mut self) -> &mut RrtOptions {
+        &mut self.options
+    }
+
+    /// Returns diagnostics from the most recent query.
+    #[must_use]
+    pub fn stats(&self) -> &PlannerStats<T> {
+        &self.stats
+    }
+}
+
+impl<T, FS, FE, FC> Rrt<T, FS, FE, FC>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    /// Plans a path from `start` to `goal` one iteration at a time as the returned
+    /// iterator is advanced, see [`RrtEvents`]. Diagnostics for the query are available
+    /// from the iterator rather than [`Rrt::stats`].
+    pub fn events(
+        &mut self,
+        start: &T,
+        goal: &T,
+    ) -> RrtEvents<T, impl FnMut() -> T + '_, &mut FE, &mut FC, impl TerminationCondition> {
+        let (samples, sample_fn) = (&mut self.samples, &mut self.sample_fn);
+        rrt_events(
+            start,
+            goal,
+            move || samples.pop_front().unwrap_or_else(&mut *sample_fn),
+            &mut self.extend_fn,
+            &mut self.connectable_fn,
+            &self.options,
+            self.options.termination(),
+        )
+    }
+
+    /// Runs a query with `planner`, drawing presampled states first and recording its
+    /// diagnostics, with the options also stopping on `cancellation`. Shared by every
+    /// planner built on [`Rrt`].
+    fn query<P, R>(
+        &mut self,
+        cancellation: Option<&CancellationToken>,
+        planner: P,
+    ) -> Result<Vec<T>, PlanningError>
+    where
+        P: FnOnce(
+            &mut dyn FnMut() -> T,
+            &mut FE,
+            &mut FC,
+            &RrtOptions,
+            &mut PlannerStats<T>,
+        ) -> Result<(Vec<T>, R), PlanningError>,
+    {
+        self.stats = PlannerStats {
+            precompute_time: self.precompute_time.take(),
+            ..PlannerStats::new()
+        };
+        let mut options = self.options.clone();
+        if let Some(cancellation) = cancellation {
+            options.cancellation = Some(cancellation.clone());
+        }
+        let (samples, sample_fn) = (&mut self.samples, &mut self.sample_fn);
+        let (path, _) = planner(
+            &mut || samples.pop_front().unwrap_or_else(&mut *sample_fn),
+            &mut self.extend_fn,
+            &mut self.connectable_fn,
+            &options,
+            &mut self.stats,
+        )?;
+        Ok(path)
+    }
+
+    /// Plans a path from `start` to `goal` with RRT.
+    fn plan_rrt(
+        &mut self,
+        start: &T,
+        goal: &T,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<T>, PlanningError> {
+        self.query(
+            cancellation,
+            |sample_fn, extend_fn, connectable_fn, options, stats| {
+                rrt_with_termination(
+                    start,
+                    goal,
+                    sample_fn,
+                    extend_fn,
+                    connectable_fn,
+                    options,
+                    options.termination(),
+                    stats,
+                )
+            },
+        )
+    }
+}
+
+impl<T, FS, FE, FC> Planner<T> for Rrt<T, FS, FE, FC>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    fn plan(&mut self, start: &T, goal: &T) -> Result<Vec<T>, PlanningError> {
+        self.plan_rrt(start, goal, None)
+    }
+
+    fn plan_cancellable(
+        &mut self,
+        start: &T,
+        goal: &T,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<T>, PlanningError> {
+        self.plan_rrt(start, goal, Some(cancellation))
+    }
+
+    fn info(&self) -> PlannerInfo {
+        self.options.info()
+    }
+
+    fn precompute(&mut self) {
+        let start_time = Instant::now();
+        if let Some(precompute_fn) = &mut self.precompute_fn {
+            precompute_fn();
+        }
+        while self.samples.len() < self.presample {
+            self.samples.push_back((self.sample_fn)());
+        }
+        self.precompute_time = Some(start_time.elapsed());
+    }
+}
+
+// Builders and accessors of a planner wrapping an [`Rrt`], delegating to it
+macro_rules! delegate_to_rrt {
+    ($planner:ident) => {
+        impl<T, FS, FE, FC> $planner<T, FS, FE, FC> {
+            /// Runs `precompute_fn` in [`Planner::precompute`], see [`Rrt::with_precompute`].
+            #[must_use]
+            pub fn with_precompute<F>(self, precompute_fn: F) -> Self
+            where
+                F: FnMut() + Send + 'static,
+            {
+                Self(self.0.with_precompute(precompute_fn))
+            }
+
+            /// Draws `count` samples in [`Planner::precompute`], see
+            /// [`Rrt::with_presampling`].
+            #[must_use]
+            pub fn with_presampling(self, count: usize) -> Self {
+                Self(self.0.with_presampling(count))
+            }
+
+            /// Returns the options used for planning.
+            #[must_use]
+            pub fn options(&self) -> &RrtOptions {
+                self.0.options()
+            }
+
+            /// Returns the options used for planning, for changing them between queries.
+            pub fn options_mut(&mut self) -> &mut RrtOptions {
+                self.0.options_mut()
+            }
+
+            /// Returns diagnostics from the most recent query.
+            #[must_use]
+            pub fn stats(&self) -> &PlannerStats<T> {
+                self.0.stats()
+            }
+        }
+    };
+}
+
+/// RRT* as a [`Planner`]. Unlike [`Rrt`] it keeps improving the path until the iteration
+/// limit or timeout by default, rather than returning the first path found.
+pub struct RrtStar<T, FS, FE, FC>(Rrt<T, FS, FE, FC>);
+
+impl<T, FS, FE, FC> RrtStar<T, FS, FE, FC> {
+    /// Construct a planner that adds edges no longer than `max_extension` and rewires
+    /// nodes within `rewire_radius` of each new node.
+    pub fn new(
+        sample_fn: FS,
+        extend_fn: FE,
+        connectable_fn: FC,
+        max_extension: f64,
+        rewire_radius: f64,
+    ) -> Self {
+        let options = RrtOptions::default()
+            .with_max_extension(max_extension)
+            .with_rrtstar(rewire_radius)
+            .with_fast_return(false);
+        RrtStar(Rrt::with_options(
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            options,
+        ))
+    }
+}
+
+delegate_to_rrt!(RrtStar);
+
+impl<T, FS, FE, FC> Planner<T> for RrtStar<T, FS, FE, FC>
+where
+    Rrt<T, FS, FE, FC>: Planner<T>,
+{
+    fn plan(&mut self, start: &T, goal: &T) -> Result<Vec<T>, PlanningError> {
+        self.0.plan(start, goal)
+    }
+
+    fn plan_cancellable(
+        &mut self,
+        start: &T,
+        goal: &T,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<T>, PlanningError> {
+        self.0.plan_cancellable(start, goal, cancellation)
+    }
+
+    fn info(&self) -> PlannerInfo {
+        self.0.info()
+    }
+
+    fn precompute(&mut self) {
+        self.0.precompute();
+    }
+}
+
+/// RRT-Connect as a [`Planner`], which grows trees from both the start and the goal and
+/// extends repeatedly towards each sample, see [`rrt_bidirectional`].
+pub struct RrtConnect<T, FS, FE, FC>(Rrt<T, FS, FE, FC>);
+
+impl<T, FS, FE, FC> RrtConnect<T, FS, FE, FC> {
+    /// Construct a planner that adds edges no longer than `max_extension`.
+    pub fn new(sample_fn: FS, extend_fn: FE, connectable_fn: FC, max_extension: f64) -> Self {
+        let options = RrtOptions::default()
+            .with_max_extension(max_extension)
+            .with_rrtconnect();
+        RrtConnect(Rrt::with_options(
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            options,
+        ))
+    }
+}
+
+delegate_to_rrt!(RrtConnect);
+
+impl<T, FS, FE, FC> RrtConnect<T, FS, FE, FC>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    /// Plans a path from `start` to `goal` with bidirectional RRT-Connect.
+    fn plan_connect(
+        &mut self,
+        start: &T,
+        goal: &T,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<T>, PlanningError> {
+        self.0.query(
+            cancellation,
+            |sample_fn, extend_fn, connectable_fn, options, stats| {
+                rrt_bidirectional(
+                    start,
+                    goal,
+                    sample_fn,
+                    extend_fn,
+                    connectable_fn,
+                    options,
+                    options.termination(),
+                    stats,
+                )
+            },
+        )
+    }
+}
+
+impl<T, FS, FE, FC> Planner<T> for RrtConnect<T, FS, FE, FC>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    fn plan(&mut self, start: &T, goal: &T) -> Result<Vec<T>, PlanningError> {
+        self.plan_connect(start, goal, None)
+    }
+
+    fn plan_cancellable(
+        &mut self,
+        start: &T,
+        goal: &T,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<T>, PlanningError> {
+        self.plan_connect(start, goal, Some(cancellation))
+    }
+
+    fn info(&self) -> PlannerInfo {
+        self.0.info()
+    }
+
+    fn precompute(&mut self) {
+        self.0.precompute();
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::{Planner, Rrt, RrtConnect, RrtStar};
+    use crate::error::PlanningError;
+    use crate::planning::termination::CancellationToken;
+    use crate::planning::tuning::SplitMix64;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn sampler(seed: u64) -> impl FnMut() -> i32 {
+        let mut rng = SplitMix64(seed);
+        move || (rng.next_f64() * 20.0) as i32 - 10
+    }
+
+    #[test]
+    fn test_planners() {
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let valid = |_: &i32, to: &i32| *to != 3;
+        let mut planners: Vec<Box<dyn Planner<i32>>> = vec![
+            Box::new(Rrt::new(sampler(1), extend, valid, 1.0)),
+            Box::new(RrtStar::new(sampler(2), extend, valid, 1.0, 2.0)),
+            Box::new(RrtConnect::new(sampler(3), extend, valid, 1.0)),
+        ];
+        let names: Vec<String> = planners.iter().map(|planner| planner.info().name).collect();
+        assert_eq!(names, vec!["RRT", "RRT*", "RRT-Connect"]);
+
+        // RRT-Connect does not shorten the path once the trees meet
+        for planner in &mut planners {
+            let path = planner.plan(&0, &-4).unwrap();
+            assert_eq!((path.first(), path.last()), (Some(&0), Some(&-4)));
+            assert!(path.windows(2).all(|pair| (pair[1] - pair[0]).abs() == 1));
+            assert_eq!(
+                planner.plan(&0, &5),
+                Err(PlanningError::MaxIterationsReached)
+            );
+        }
+    }
+
+    #[test]
+    fn test_planners_cancellable() {
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let valid = |_: &i32, to: &i32| *to != 3;
+        let mut planners: Vec<Box<dyn Planner<i32>>> = vec![
+            Box::new(Rrt::new(sampler(1), extend, valid, 1.0)),
+            Box::new(RrtStar::new(sampler(2), extend, valid, 1.0, 2.0)),
+            Box::new(RrtConnect::new(sampler(3), extend, valid, 1.0)),
+        ];
+
+        let cancellation = CancellationToken::new();
+        for planner in &mut planners {
+            let path = planner.plan_cancellable(&0, &-4, &cancellation).unwrap();
+            assert_eq!(path.last(), Some(&-4));
+        }
+
+        // Cancelling stops the search for a path that does not exist
+        cancellation.cancel();
+        for planner in &mut planners {
+            assert_eq!(
+                planner.plan_cancellable(&0, &5, &cancellation),
+                Err(PlanningError::Cancelled)
+            );
+        }
+    }
+
+    #[test]
+    fn test_planners_cancelled_after_solution() {
+        // Cancelling on the motion that reaches the goal still returns the path
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let valid = |stop: CancellationToken| {
+            move |from: &i32, to: &i32| {
+                if *from == -4 || *to == -4 {
+                    stop.cancel();
+                }
+                *to != 3
+            }
+        };
+        let tokens = [CancellationToken::new(), CancellationToken::new()];
+        let mut planners: Vec<Box<dyn Planner<i32>>> = vec![
+            Box::new(Rrt::new(sampler(1), extend, valid(tokens[0].clone()), 1.0)),
+            Box::new(RrtStar::new(
+                sampler(2),
+                extend,
+                valid(tokens[1].clone()),
+                1.0,
+                2.0,
+            )),
+        ];
+        for (planner, token) in planners.iter_mut().zip(&tokens) {
+            let path = planner.plan_cancellable(&0, &-4, token).unwrap();
+            assert_eq!(path.last(), Some(&-4));
+            assert!(token.is_cancelled());
+        }
+    }
+
+    #[test]
+    fn test_planner_precompute() {
+        use std::cell::Cell;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let valid = |_: &i32, to: &i32| *to != 3;
+        let expected = Rrt::new(sampler(5), extend, valid, 1.0).plan(&0, &-6);
+
+        let drawn = Cell::new(0);
+        let mut sample = sampler(5);
+        let counted = || {
+            drawn.set(drawn.get() + 1);
+            sample()
+        };
+        let runs = Arc::new(AtomicUsize::new(0));
+        let hook_runs = Arc::clone(&runs);
+        let mut planner = RrtStar::new(counted, extend, valid, 1.0, 2.0)
+            .with_precompute(move || {
+                hook_runs.fetch_add(1, Ordering::Relaxed);
+            })
+            .with_presampling(20);
+        planner.options_mut().fast_return = true;
+        planner.options_mut().use_rrtstar = false;
+        planner.precompute();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(drawn.get(), 20);
+
+        // Presampled queries find the same paths and report the precompute time separately
+        assert_eq!(planner.plan(&0, &-6), expected);
+        assert!(planner.stats().precompute_time.is_some());
+        assert!(planner.plan(&0, &-6).is_ok());
+        assert!(planner.stats().precompute_time.is_none());
+    }
+
+    #[test]
+    fn test_planner_events() {
+        use crate::planning::events::ProgressEvent;
+
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let valid = |_: &i32, to: &i32| *to != 3;
+        let mut planner = Rrt::new(sampler(6), extend, valid, 1.0);
+        let last = planner.events(&0, &-4).last();
+        let Some(ProgressEvent::Finished { result, .. }) = last else {
+            panic!("Expected the last event to be Finished, got {last:?}");
+        };
+        assert_eq!(result, Ok(vec![0, -1, -2, -3, -4]));
+    }
+
+    #[test]
+    fn test_planner_options() {
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let valid = |_: &i32, _: &i32| true;
+        let mut planner = RrtStar::new(sampler(4), extend, valid, 1.0, 2.0);
+        assert!(!planner.options().fast_return);
+        planner.options_mut().max_iterations = 100;
+        assert!(planner.plan(&0, &-4).is_ok());
+        assert!(planner.stats().first_solution.is_some());
+        assert_eq!(planner.stats().iterations, 100);
+    }
+}
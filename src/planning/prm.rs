@@ -0,0 +1,419 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::PlanningError;
+use crate::planning::heuristic::Visit;
+use crate::tree::Distance;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::hash::Hash;
+
+/// Algorithm parameters for building and querying a [`Roadmap`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrmOptions {
+    /// Number of states sampled when building the roadmap.
+    pub samples: usize,
+
+    /// Maximum number of neighbors each new state is connected to.
+    pub max_neighbors: usize,
+
+    /// Maximum length of an edge.
+    pub connection_radius: f64,
+
+    /// Defer validating edges until they are on a candidate shortest path, as in Lazy
+    /// PRM, rather than validating every edge as it is added.
+    pub lazy: bool,
+}
+
+impl Default for PrmOptions {
+    fn default() -> Self {
+        PrmOptions {
+            samples: 1000,
+            max_neighbors: 10,
+            connection_radius: f64::INFINITY,
+            lazy: false,
+        }
+    }
+}
+
+/// Whether an edge of a [`Roadmap`] has been validated. Edges found to be invalid are
+/// removed from the roadmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeState {
+    /// The edge has not been checked yet, which is only the case for lazy roadmaps.
+    Unchecked,
+
+    /// The edge was checked and is valid.
+    Valid,
+}
+
+/// Counts of the work done by a [`Roadmap`] across all queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RoadmapStats {
+    /// Number of calls to `connectable_fn`.
+    pub edges_checked: usize,
+
+    /// Number of edges removed for being invalid.
+    pub edges_invalidated: usize,
+
+    /// Number of graph searches run, more than one per query when a lazy roadmap finds
+    /// invalid edges on the shortest path.
+    pub searches: usize,
+}
+
+/// A probabilistic roadmap, a graph of sampled states connected to their nearest
+/// neighbors, which can answer many queries in the same environment.
+///
+/// Motions are assumed to be symmetric, an edge that is valid in one direction is used in
+/// both. Queries add the start and goal to the roadmap, so later queries reuse them.
+#[derive(Debug, Clone)]
+pub struct Roadmap<T> {
+    nodes: Vec<T>,
+    index: HashMap<T, usize>,
+    edges: Vec<BTreeMap<usize, EdgeState>>,
+    options: PrmOptions,
+    stats: RoadmapStats,
+}
+
+impl<T> Roadmap<T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    /// Construct an empty roadmap.
+    #[must_use]
+    pub fn new(options: PrmOptions) -> Self {
+        Roadmap {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            edges: Vec::new(),
+            options,
+            stats: RoadmapStats::default(),
+        }
+    }
+
+    /// Builds a roadmap of `options.samples` states drawn from `sample_fn`. Unless the
+    /// roadmap is lazy every edge is validated with `connectable_fn` as it is added.
+    pub fn build<FS, FC>(mut sample_fn: FS, mut connectable_fn: FC, options: PrmOptions) -> Self
+    where
+        FS: FnMut() -> T,
+        FC: FnMut(&T, &T) -> bool,
+    {
+        let mut roadmap = Roadmap::new(options);
+        for _ in 0..options.samples {
+            roadmap.add_state(sample_fn(), &mut connectable_fn);
+        }
+        roadmap
+    }
+
+    /// Returns the options the roadmap was built with.
+    #[must_use]
+    pub fn options(&self) -> &PrmOptions {
+        &self.options
+    }
+
+    /// Returns the work done by the roadmap so far.
+    #[must_use]
+    pub fn stats(&self) -> &RoadmapStats {
+        &self.stats
+    }
+
+    /// Returns the number of states in the roadmap.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the roadmap has no states.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the states in the order they were added.
+    #[must_use]
+    pub fn nodes(&self) -> &[T] {
+        &self.nodes
+    }
+
+    /// Returns every edge once along with its state, ordered by the states' insertion order.
+    #[must_use]
+    pub fn edges(&self) -> Vec<(&T, &T, EdgeState)> {
+        self.edges
+            .iter()
+            .enumerate()
+            .flat_map(|(from, neighbors)| {
+                neighbors
+                    .range(from + 1..)
+                    .map(move |(&to, &state)| (&self.nodes[from], &self.nodes[to], state))
+            })
+            .collect()
+    }
+
+    /// Adds a state, connecting it to its nearest neighbors within the connection radius.
+    /// Does nothing if the state is already in the roadmap.
+    ///
+    /// Returns the state's index.
+    pub fn add_state<FC>(&mut self, state: T, mut connectable_fn: FC) -> usize
+    where
+        FC: FnMut(&T, &T) -> bool,
+    {
+        if let Some(&index) = self.index.get(&state) {
+            return index;
+        }
+
+        let mut neighbors: Vec<(usize, f64)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (index, node.distance(&state)))
+            .filter(|&(_, distance)| distance <= self.options.connection_radius)
+            .collect();
+        neighbors.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        neighbors.truncate(self.options.max_neighbors);
+
+        let index = self.nodes.len();
+        self.edges.push(BTreeMap::new());
+        for (neighbor, _) in neighbors {
+            let edge = if self.options.lazy {
+                EdgeState::Unchecked
+            } else {
+                self.stats.edges_checked += 1;
+                if !connectable_fn(&self.nodes[neighbor], &state) {
+                    continue;
+                }
+                EdgeState::Valid
+            };
+            self.edges[index].insert(neighbor, edge);
+            self.edges[neighbor].insert(index, edge);
+        }
+        self.index.insert(state.clone(), index);
+        self.nodes.push(state);
+        index
+    }
+
+    /// Adds a state without connecting it, for rebuilding a saved roadmap. Returns false if
+    /// the state is already in the roadmap.
+    pub(crate) fn insert_node(&mut self, state: T) -> bool {
+        if self.index.contains_key(&state) {
+            return false;
+        }
+        self.index.insert(state.clone(), self.nodes.len());
+        self.nodes.push(state);
+        self.edges.push(BTreeMap::new());
+        true
+    }
+
+    /// Adds an edge between the states at two indices, which must be in the roadmap.
+    pub(crate) fn insert_edge(&mut self, from: usize, to: usize, state: EdgeState) {
+        self.edges[from].insert(to, state);
+        self.edges[to].insert(from, state);
+    }
+
+    /// Finds the shortest path from `start` to `goal` through the roadmap.
+    ///
+    /// Lazy roadmaps validate the edges of the shortest path in order from the start,
+    /// removing the first invalid edge and searching again until every edge on the path
+    /// is valid. Edges are only ever checked once, so later queries reuse the results.
+    ///
+    /// # Errors
+    ///
+    /// If the start and goal are not connected by valid edges.
+    pub fn query<FC>(
+        &mut self,
+        start: &T,
+        goal: &T,
+        mut connectable_fn: FC,
+    ) -> Result<Vec<T>, PlanningError>
+    where
+        FC: FnMut(&T, &T) -> bool,
+    {
+        let start = self.add_state(start.clone(), &mut connectable_fn);
+        let goal = self.add_state(goal.clone(), &mut connectable_fn);
+
+        loop {
+            self.stats.searches += 1;
+            let path = self
+                .shortest_path(start, goal)
+                .ok_or(PlanningError::GoalUnreachable)?;
+            let invalid = path.windows(2).find(|pair| {
+                let (from, to) = (pair[0], pair[1]);
+                if self.edges[from][&to] == EdgeState::Valid {
+                    return false;
+                }
+                self.stats.edges_checked += 1;
+                let valid = connectable_fn(&self.nodes[from], &self.nodes[to]);
+                if valid {
+                    self.edges[from].insert(to, EdgeState::Valid);
+                    self.edges[to].insert(from, EdgeState::Valid);
+                }
+                !valid
+            });
+            match invalid {
+                Some(pair) => {
+                    self.stats.edges_invalidated += 1;
+                    self.edges[pair[0]].remove(&pair[1]);
+                    self.edges[pair[1]].remove(&pair[0]);
+                }
+                None => return Ok(path.into_iter().map(|i| self.nodes[i].clone()).collect()),
+            }
+        }
+    }
+
+    /// A* over every edge that has not been found invalid, returning the node indices of
+    /// the shortest path.
+    fn shortest_path(&self, start: usize, goal: usize) -> Option<Vec<usize>> {
+        let mut costs = vec![f64::INFINITY; self.nodes.len()];
+        let mut previous: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        let mut queue = BinaryHeap::from([Reverse(Visit {
+            cost: 0.0,
+            node: start,
+        })]);
+        costs[start] = 0.0;
+        let heuristic = |node: usize| self.nodes[node].distance(&self.nodes[goal]);
+
+        while let Some(Reverse(Visit { cost, node })) = queue.pop() {
+            if node == goal {
+                let mut path = vec![goal];
+                while let Some(node) = previous[*path.last()?] {
+                    path.push(node);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost > costs[node] + heuristic(node) {
+                continue;
+            }
+            for &next in self.edges[node].keys() {
+                let next_cost = costs[node] + self.nodes[node].distance(&self.nodes[next]);
+                if next_cost < costs[next] {
+                    costs[next] = next_cost;
+                    previous[next] = Some(node);
+                    queue.push(Reverse(Visit {
+                        cost: next_cost + heuristic(next),
+                        node: next,
+                    }));
+                }
+            }
+        }
+        None
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::FloatArray;
+    use crate::planning::tuning::SplitMix64;
+    use std::cell::Cell;
+
+    // A wall at x = 5 with a gap above y = 8
+    fn connectable_fn(from: &FloatArray<2>, to: &FloatArray<2>) -> bool {
+        let crosses = (from.0[0] - 5.0) * (to.0[0] - 5.0) <= 0.0;
+        !crosses || (from.0[1] > 8.0 && to.0[1] > 8.0)
+    }
+
+    fn counted(checks: &Cell<usize>) -> impl FnMut(&FloatArray<2>, &FloatArray<2>) -> bool + '_ {
+        |from: &FloatArray<2>, to: &FloatArray<2>| {
+            checks.set(checks.get() + 1);
+            connectable_fn(from, to)
+        }
+    }
+
+    fn build(lazy: bool, checks: &Cell<usize>) -> Roadmap<FloatArray<2>> {
+        let mut rng = SplitMix64(5);
+        let options = PrmOptions {
+            samples: 200,
+            connection_radius: 3.0,
+            lazy,
+            ..PrmOptions::default()
+        };
+        Roadmap::build(
+            || FloatArray([rng.next_f64() * 10.0, rng.next_f64() * 10.0]),
+            counted(checks),
+            options,
+        )
+    }
+
+    #[test]
+    fn test_roadmap() {
+        let checks = Cell::new(0);
+        let mut roadmap = build(false, &checks);
+        assert_eq!(roadmap.len(), 200);
+        assert_eq!(roadmap.stats().edges_checked, checks.get());
+        assert!(roadmap
+            .edges()
+            .iter()
+            .all(|(from, to, state)| *state == EdgeState::Valid && connectable_fn(from, to)));
+
+        let (start, goal) = (FloatArray([1.0, 1.0]), FloatArray([9.0, 1.0]));
+        let path = roadmap.query(&start, &goal, connectable_fn).unwrap();
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path
+            .windows(2)
+            .all(|pair| connectable_fn(&pair[0], &pair[1])));
+        assert!(path.iter().any(|state| state.0[1] > 8.0));
+        assert_eq!(roadmap.stats().searches, 1);
+        assert_eq!(roadmap.len(), 202);
+
+        // Nothing crosses a closed wall
+        let closed =
+            |from: &FloatArray<2>, to: &FloatArray<2>| (from.0[0] - 5.0) * (to.0[0] - 5.0) > 0.0;
+        let mut roadmap = Roadmap::build(|| FloatArray([1.0, 1.0]), closed, PrmOptions::default());
+        assert_eq!(
+            roadmap.query(&start, &goal, closed),
+            Err(PlanningError::GoalUnreachable)
+        );
+    }
+
+    #[test]
+    fn test_lazy_roadmap() {
+        let eager_checks = Cell::new(0);
+        let mut eager = build(false, &eager_checks);
+        let lazy_checks = Cell::new(0);
+        let mut lazy = build(true, &lazy_checks);
+        assert_eq!(lazy_checks.get(), 0);
+        assert!(lazy
+            .edges()
+            .iter()
+            .all(|(_, _, state)| *state == EdgeState::Unchecked));
+
+        let (start, goal) = (FloatArray([1.0, 1.0]), FloatArray([9.0, 1.0]));
+        let expected = eager.query(&start, &goal, counted(&eager_checks)).unwrap();
+        let path = lazy.query(&start, &goal, counted(&lazy_checks)).unwrap();
+
+        // The same shortest path is found for a fraction of the checks
+        assert_eq!(path, expected);
+        assert!(lazy.stats().searches > 1);
+        assert!(lazy.stats().edges_invalidated > 0);
+        assert!(lazy_checks.get() * 5 < eager_checks.get());
+
+        // Checked edges are reused
+        let checked = lazy_checks.get();
+        assert_eq!(lazy.query(&start, &goal, counted(&lazy_checks)), Ok(path));
+        assert_eq!(lazy_checks.get(), checked);
+    }
+}
@@ -0,0 +1,171 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::f64::consts::PI;
+
+/// Shrinks the RRT* rewire radius as the tree grows, following Karaman and Frazzoli.
+///
+/// With `n` nodes in a `d` dimensional space the radius is `gamma (ln n / n)^(1/d)`, and
+/// RRT* is asymptotically optimal for any `gamma` above
+/// `2 (1 + 1/d)^(1/d) (free_measure / unit_ball)^(1/d)`, where `unit_ball` is the volume of
+/// the unit ball in `d` dimensions. That bound is computed here from the dimension and the
+/// measure of the free space, and scaled by `gamma_scale`. Use [`estimate_free_measure`] if
+/// the free space is not known.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveRadius {
+    /// Dimension of the state space.
+    pub dimension: usize,
+
+    /// Measure of the free space, its area in two dimensions or volume in three.
+    pub free_measure: f64,
+
+    /// Multiplies the smallest gamma for which RRT* is asymptotically optimal, should be
+    /// above 1.
+    pub gamma_scale: f64,
+}
+
+impl AdaptiveRadius {
+    /// Construct a radius schedule with a `gamma_scale` of 1.1.
+    #[must_use]
+    pub fn new(dimension: usize, free_measure: f64) -> Self {
+        AdaptiveRadius {
+            dimension,
+            free_measure,
+            gamma_scale: 1.1,
+        }
+    }
+
+    /// Returns gamma, the radius is `gamma (ln n / n)^(1/d)`.
+    #[must_use]
+    pub fn gamma(&self) -> f64 {
+        let d = self.dimension.max(1);
+        #[allow(clippy::cast_precision_loss)]
+        let inverse = 1.0 / d as f64;
+        let bound = 2.0
+            * (1.0 + inverse).powf(inverse)
+            * (self.free_measure / unit_ball_volume(d)).powf(inverse);
+        self.gamma_scale * bound
+    }
+
+    /// Returns the rewire radius for a tree of `nodes` nodes, which is unbounded for a
+    /// tree of fewer than 2 nodes.
+    #[must_use]
+    pub fn radius(&self, nodes: usize) -> f64 {
+        if nodes < 2 {
+            return f64::INFINITY;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let (n, d) = (nodes as f64, self.dimension.max(1) as f64);
+        self.gamma() * (n.ln() / n).powf(1.0 / d)
+    }
+}
+
+/// Volume of the unit ball in `dimension` dimensions.
+#[must_use]
+pub fn unit_ball_volume(dimension: usize) -> f64 {
+    // V(d) = 2 pi / d V(d - 2), from V(0) = 1 and V(1) = 2
+    let mut volume = if dimension.is_multiple_of(2) {
+        1.0
+    } else {
+        2.0
+    };
+    for d in (2 + dimension % 2..=dimension).step_by(2) {
+        #[allow(clippy::cast_precision_loss)]
+        let d = d as f64;
+        volume *= 2.0 * PI / d;
+    }
+    volume
+}
+
+/// Estimates the measure of the free space from the fraction of `samples` states drawn by
+/// `sample_fn` that `valid_fn` accepts, scaled by `total_measure`, the measure of the
+/// whole sampled space, e.g. [`crate::bounds::Bounds::volume`].
+#[must_use]
+pub fn estimate_free_measure<T, FS, FV>(
+    total_measure: f64,
+    samples: usize,
+    mut sample_fn: FS,
+    mut valid_fn: FV,
+) -> f64
+where
+    FS: FnMut() -> T,
+    FV: FnMut(&T) -> bool,
+{
+    if samples == 0 {
+        return total_measure;
+    }
+    let valid = (0..samples).filter(|_| valid_fn(&sample_fn())).count();
+    #[allow(clippy::cast_precision_loss)]
+    let fraction = valid as f64 / samples as f64;
+    total_measure * fraction
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounds::Bounds;
+    use crate::planning::tuning::SplitMix64;
+
+    #[test]
+    fn test_unit_ball_volume() {
+        let expected = [1.0, 2.0, PI, 4.0 / 3.0 * PI, PI * PI / 2.0];
+        for (d, volume) in expected.iter().enumerate() {
+            assert!((unit_ball_volume(d) - volume).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_radius() {
+        // A 10 x 10 square
+        let radius = AdaptiveRadius {
+            gamma_scale: 1.0,
+            ..AdaptiveRadius::new(2, 100.0)
+        };
+        let expected = 2.0 * 1.5f64.sqrt() * (100.0 / PI).sqrt();
+        assert!((radius.gamma() - expected).abs() < 1e-9);
+        assert!(radius.radius(1).is_infinite());
+        assert!((radius.radius(100) - expected * (100f64.ln() / 100.0).sqrt()).abs() < 1e-9);
+        assert!(radius.radius(1000) < radius.radius(100));
+
+        // Less free space means a smaller radius
+        assert!(AdaptiveRadius::new(2, 50.0).gamma() < AdaptiveRadius::new(2, 100.0).gamma());
+    }
+
+    #[test]
+    fn test_estimate_free_measure() {
+        // The left half of a 4 x 2 box is free
+        let bounds = Bounds::new([0.0, 0.0], [4.0, 2.0]).unwrap();
+        let mut rng = SplitMix64(3);
+        let measure = estimate_free_measure(
+            bounds.volume(),
+            2000,
+            bounds.sampler(|| rng.next_f64()),
+            |p| p.0[0] < 2.0,
+        );
+        assert!((measure - 4.0).abs() < 0.3);
+        assert!((estimate_free_measure(8.0, 0, || 0, |_| false) - 8.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,111 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::tree::{Distance, HashTree};
+use std::hash::Hash;
+
+/// Attempts to re-attach subtrees detached by [`HashTree::invalidate_edges`] to the tree.
+///
+/// Each subtree's root is connected to the node within `radius` that gives it the lowest
+/// cost and for which `connectable_fn(node, root)` holds. Subtrees are retried until no
+/// more can be attached, since attaching one can bring others within reach. Costs of all
+/// attached nodes are updated.
+///
+/// Returns the subtrees that could not be salvaged, in their original order.
+pub fn reconnect_subtrees<T, FC>(
+    tree: &mut HashTree<T>,
+    subtrees: Vec<HashTree<T>>,
+    mut connectable_fn: FC,
+    radius: f64,
+) -> Vec<HashTree<T>>
+where
+    T: Eq + Clone + Hash + Distance,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let mut pending = subtrees;
+    loop {
+        let mut remaining = Vec::new();
+        let count = pending.len();
+        for subtree in pending {
+            let root = subtree.root();
+            let mut candidates: Vec<(T, f64)> = tree
+                .neighbors_within(root, radius)
+                .into_iter()
+                .filter_map(|(node, distance)| {
+                    tree.cost(node)
+                        .ok()
+                        .map(|cost| (node.clone(), cost + distance))
+                })
+                .collect();
+            candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+            let parent = candidates
+                .into_iter()
+                .map(|(node, _)| node)
+                .find(|node| connectable_fn(node, root));
+            let attached = parent.is_some_and(|parent| tree.graft(&parent, &subtree).is_ok());
+            if !attached {
+                remaining.push(subtree);
+            }
+        }
+
+        if remaining.is_empty() || remaining.len() == count {
+            return remaining;
+        }
+        pending = remaining;
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_subtrees() {
+        // Tree is: 0 -> 1 -> 2 -> 3 -> 4
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        for (parent, child) in [(0, 1), (1, 2), (2, 3), (3, 4)] {
+            assert!(tree.add_child(&parent, child).is_ok());
+        }
+        let blocked = |from: &i32, to: &i32| (*from, *to) == (2, 3);
+        let mut subtrees = tree.invalidate_edges(|from, to| !blocked(from, to));
+        assert_eq!(subtrees.len(), 1);
+
+        // 6 -> 7 is only in reach once 3 -> 4 has been reattached, and 20 is never in reach
+        let mut detached = HashTree::new(6);
+        assert!(detached.add_child(&6, 7).is_ok());
+        subtrees.insert(0, HashTree::new(20));
+        subtrees.insert(0, detached);
+
+        let connectable_fn = |from: &i32, to: &i32| (to - from).abs() <= 2 && !blocked(from, to);
+        let remaining = reconnect_subtrees(&mut tree, subtrees, connectable_fn, 2.0);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].root(), &20);
+
+        assert_eq!(tree.path(&7).unwrap(), vec![0, 1, 3, 4, 6, 7]);
+        assert!((tree.cost(&7).unwrap() - 7.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,481 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::{PersistError, TreeError};
+use crate::persist::{parse, read_header};
+use crate::tree::{Distance, HashTree};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, BufRead, Write};
+
+/// Version of the event log format written by [`write_events`].
+pub const EVENTS_VERSION: u32 = 1;
+
+const EVENTS_KIND: &str = "rustplanning-events";
+
+/// A single step of a planning run, recorded in [`PlannerStats::events`] when
+/// `RrtOptions::record_events` is set.
+///
+/// [`PlannerStats::events`]: crate::planning::stats::PlannerStats::events
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlannerEvent<T> {
+    /// A sample was drawn at the start of an iteration.
+    Sample { iteration: u64, state: T },
+
+    /// A node was added to the tree.
+    NodeAdded { parent: T, node: T },
+
+    /// RRT* rewiring moved a node to a new parent.
+    Rewired { node: T, parent: T },
+
+    /// The cost to reach the goal improved, or the goal was reached for the first time.
+    Solution { iteration: u64, cost: f64 },
+}
+
+/// Writes a recorded run as a compact line based log, where `fields_fn` flattens each
+/// state into numbers.
+///
+/// States are only written out when they are added to the tree, afterwards they are
+/// referred to by id in insertion order with the root as 0. Lines are one of
+/// - `N <parent id> <fields...>`: a node was added, the root has parent -1
+/// - `S <iteration> <fields...>`: a sample was drawn
+/// - `R <node id> <parent id>`: a node was rewired
+/// - `G <iteration> <cost>`: the solution improved
+///
+/// # Errors
+///
+/// If writing fails, or an event refers to a state that is not in the tree.
+pub fn write_events<W, T, F>(
+    writer: &mut W,
+    root: &T,
+    events: &[PlannerEvent<T>],
+    mut fields_fn: F,
+) -> io::Result<()>
+where
+    W: Write,
+    T: Eq + Clone + Hash,
+    F: FnMut(&T) -> Vec<f64>,
+{
+    let mut ids: HashMap<&T, usize> = HashMap::from([(root, 0)]);
+    let id = |ids: &HashMap<&T, usize>, state: &T| {
+        ids.get(state).copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Event refers to a state that is not in the tree",
+            )
+        })
+    };
+    let fields = |values: Vec<f64>| {
+        values
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    writeln!(writer, "{EVENTS_KIND} {EVENTS_VERSION}")?;
+    writeln!(writer, "N -1 {}", fields(fields_fn(root)))?;
+    for event in events {
+        match event {
+            PlannerEvent::Sample { iteration, state } => {
+                writeln!(writer, "S {iteration} {}", fields(fields_fn(state)))?;
+            }
+            PlannerEvent::NodeAdded { parent, node } => {
+                writeln!(
+                    writer,
+                    "N {} {}",
+                    id(&ids, parent)?,
+                    fields(fields_fn(node))
+                )?;
+                let next = ids.len();
+                ids.insert(node, next);
+            }
+            PlannerEvent::Rewired { node, parent } => {
+                writeln!(writer, "R {} {}", id(&ids, node)?, id(&ids, parent)?)?;
+            }
+            PlannerEvent::Solution { iteration, cost } => {
+                writeln!(writer, "G {iteration} {cost}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a log written by [`write_events`] by any earlier version of the crate, where
+/// `state_fn` rebuilds each state from its fields.
+///
+/// Returns the root of the tree and the recorded events.
+///
+/// # Errors
+///
+/// If reading fails, the log is malformed, or it was written by a newer version.
+pub fn read_events<R, T, F>(
+    reader: R,
+    mut state_fn: F,
+) -> Result<(T, Vec<PlannerEvent<T>>), PersistError>
+where
+    R: BufRead,
+    T: Clone,
+    F: FnMut(&[f64]) -> Result<T, String>,
+{
+    let mut lines = reader.lines();
+    read_header(&mut lines, EVENTS_KIND, EVENTS_VERSION)?;
+
+    let mut nodes: Vec<T> = Vec::new();
+    let mut events = Vec::new();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        let number = number + 2;
+        let context = |message: String| PersistError::Parse {
+            line: number,
+            message,
+        };
+        let mut tokens = line.split_whitespace();
+        let kind = tokens.next();
+        let head = tokens.next();
+        let rest: Vec<&str> = tokens.collect();
+        let fields = || -> Result<Vec<f64>, String> {
+            rest.iter().map(|token| parse(Some(token))).collect()
+        };
+
+        let event = match (kind, rest.len()) {
+            (Some("N"), _) => {
+                let parent: i64 = parse(head).map_err(context)?;
+                let node = state_fn(&fields().map_err(context)?).map_err(context)?;
+                let parent = match (nodes.is_empty(), usize::try_from(parent)) {
+                    (true, Err(_)) => {
+                        nodes.push(node);
+                        continue;
+                    }
+                    (false, Ok(parent)) => lookup(&nodes, parent).map_err(context)?,
+                    (true, Ok(_)) => return Err(context("The first node must be the root".into())),
+                    (false, Err(_)) => return Err(context("Multiple roots".into())),
+                };
+                nodes.push(node.clone());
+                PlannerEvent::NodeAdded { parent, node }
+            }
+            (Some("S"), _) => PlannerEvent::Sample {
+                iteration: parse(head).map_err(context)?,
+                state: state_fn(&fields().map_err(context)?).map_err(context)?,
+            },
+            (Some("R"), 1) => {
+                let node = parse(head).and_then(|id| lookup(&nodes, id));
+                let parent = parse(Some(rest[0])).and_then(|id| lookup(&nodes, id));
+                PlannerEvent::Rewired {
+                    node: node.map_err(context)?,
+                    parent: parent.map_err(context)?,
+                }
+            }
+            (Some("G"), 1) => PlannerEvent::Solution {
+                iteration: parse(head).map_err(context)?,
+                cost: parse(Some(rest[0])).map_err(context)?,
+            },
+            _ => return Err(context(format!("Malformed event '{line}'"))),
+        };
+        events.push(event);
+    }
+
+    let root = nodes.first().cloned().ok_or(PersistError::Truncated)?;
+    Ok((root, events))
+}
+
+fn lookup<T: Clone>(nodes: &[T], id: usize) -> Result<T, String> {
+    nodes
+        .get(id)
+        .cloned()
+        .ok_or_else(|| format!("Unknown node id {id}"))
+}
+
+/// Steps through a recorded run, rebuilding the tree as it was after each event.
+///
+/// The tree can be passed to any of the visualization or export functions between steps.
+pub struct Replay<'a, T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    events: &'a [PlannerEvent<T>],
+    position: usize,
+    tree: HashTree<T>,
+    sample: Option<T>,
+    iteration: u64,
+    best_cost: Option<f64>,
+}
+
+impl<'a, T> Replay<'a, T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    /// Start a replay of `events` from a tree containing only `root`.
+    pub fn new(root: T, events: &'a [PlannerEvent<T>]) -> Self {
+        Replay {
+            events,
+            position: 0,
+            tree: HashTree::new(root),
+            sample: None,
+            iteration: 0,
+            best_cost: None,
+        }
+    }
+
+    /// Applies the next event, returning it, or None once the replay is complete.
+    ///
+    /// # Errors
+    ///
+    /// If the event cannot be applied to the tree, which means the log does not match
+    /// the recorded run.
+    pub fn step(&mut self) -> Result<Option<&'a PlannerEvent<T>>, TreeError> {
+        let Some(event) = self.events.get(self.position) else {
+            return Ok(None);
+        };
+        match event {
+            PlannerEvent::Sample { iteration, state } => {
+                self.iteration = *iteration;
+                self.sample = Some(state.clone());
+            }
+            PlannerEvent::NodeAdded { parent, node } => {
+                self.tree.add_child(parent, node.clone())?;
+            }
+            PlannerEvent::Rewired { node, parent } => self.tree.set_parent(node, parent)?,
+            PlannerEvent::Solution { iteration, cost } => {
+                self.iteration = *iteration;
+                self.best_cost = Some(*cost);
+            }
+        }
+        self.position += 1;
+        Ok(Some(event))
+    }
+
+    /// Applies events until the end of the given iteration, or the end of the log.
+    ///
+    /// # Errors
+    ///
+    /// If an event cannot be applied to the tree.
+    pub fn run_through(&mut self, iteration: u64) -> Result<(), TreeError> {
+        while let Some(event) = self.events.get(self.position) {
+            if matches!(event, PlannerEvent::Sample { iteration: next, .. } if *next > iteration) {
+                break;
+            }
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// The tree as of the last applied event.
+    #[must_use]
+    pub fn tree(&self) -> &HashTree<T> {
+        &self.tree
+    }
+
+    /// The most recent sample, if any.
+    #[must_use]
+    pub fn sample(&self) -> Option<&T> {
+        self.sample.as_ref()
+    }
+
+    /// The iteration of the last applied event.
+    #[must_use]
+    pub fn iteration(&self) -> u64 {
+        self.iteration
+    }
+
+    /// The best solution cost as of the last applied event, if any.
+    #[must_use]
+    pub fn best_cost(&self) -> Option<f64> {
+        self.best_cost
+    }
+
+    /// Returns true once every event has been applied.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.events.len()
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::rrt::{rrt_with_termination, RrtOptions};
+    use crate::planning::stats::PlannerStats;
+    use crate::planning::termination::MaxIterations;
+
+    fn events() -> Vec<PlannerEvent<i32>> {
+        vec![
+            PlannerEvent::Sample {
+                iteration: 0,
+                state: 3,
+            },
+            PlannerEvent::NodeAdded { parent: 0, node: 2 },
+            PlannerEvent::NodeAdded { parent: 2, node: 3 },
+            PlannerEvent::Sample {
+                iteration: 1,
+                state: 1,
+            },
+            PlannerEvent::NodeAdded { parent: 0, node: 1 },
+            PlannerEvent::Rewired { node: 2, parent: 1 },
+            PlannerEvent::Solution {
+                iteration: 1,
+                cost: 3.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_read_events() {
+        let mut bytes = Vec::new();
+        write_events(&mut bytes, &0, &events(), |&x| vec![f64::from(x)]).unwrap();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert_eq!(
+            text,
+            "rustplanning-events 1\nN -1 0\nS 0 3\nN 0 2\nN 1 3\nS 1 1\nN 0 1\nR 1 3\nG 1 3\n"
+        );
+
+        #[allow(clippy::cast_possible_truncation)]
+        let (root, read) = read_events(bytes.as_slice(), |fields| {
+            fields
+                .first()
+                .map(|&x| x as i32)
+                .ok_or_else(|| "Missing field".to_string())
+        })
+        .unwrap();
+        assert_eq!(root, 0);
+        assert_eq!(read, events());
+
+        // Unknown states and malformed logs are rejected
+        let unknown = [PlannerEvent::Rewired { node: 5, parent: 0 }];
+        assert!(write_events(&mut Vec::new(), &0, &unknown, |&x| vec![f64::from(x)]).is_err());
+        let parse = |text: &str| read_events(text.as_bytes(), |_| Ok(0)).map(|_| ());
+        assert_eq!(
+            parse("N -1 0\n"),
+            Err(PersistError::MissingHeader {
+                kind: "rustplanning-events"
+            })
+        );
+        assert_eq!(
+            parse("rustplanning-events 2\nN -1 0\n"),
+            Err(PersistError::UnsupportedVersion {
+                kind: "rustplanning-events",
+                version: 2,
+                supported: EVENTS_VERSION,
+            })
+        );
+        assert_eq!(
+            parse("rustplanning-events 1\nN -1 0\nR 4 0\n"),
+            Err(PersistError::Parse {
+                line: 3,
+                message: "Unknown node id 4".into()
+            })
+        );
+        assert_eq!(
+            parse("rustplanning-events 1\nN 0 0\n"),
+            Err(PersistError::Parse {
+                line: 2,
+                message: "The first node must be the root".into()
+            })
+        );
+        assert!(matches!(
+            parse("rustplanning-events 1\nN -1 0\nN -1 1\n"),
+            Err(PersistError::Parse { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse("rustplanning-events 1\nX 1\n"),
+            Err(PersistError::Parse { line: 2, .. })
+        ));
+        assert_eq!(
+            parse("rustplanning-events 1\n"),
+            Err(PersistError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let events = events();
+        let mut replay = Replay::new(0, &events);
+        assert_eq!(replay.tree().size(), 1);
+
+        replay.run_through(0).unwrap();
+        assert_eq!(replay.sample(), Some(&3));
+        assert_eq!(replay.tree().size(), 3);
+        assert_eq!(replay.tree().get_parent(&2), Some(&0));
+        assert!(!replay.is_finished());
+
+        assert_eq!(
+            replay.step().unwrap(),
+            Some(&PlannerEvent::Sample {
+                iteration: 1,
+                state: 1
+            })
+        );
+        replay.run_through(1).unwrap();
+        assert!(replay.is_finished());
+        assert_eq!(replay.tree().get_parent(&2), Some(&1));
+        assert_eq!(replay.best_cost(), Some(3.0));
+        assert_eq!(replay.iteration(), 1);
+        assert_eq!(replay.step().unwrap(), None);
+
+        // Events that do not match the tree are reported
+        let bad = [PlannerEvent::NodeAdded { parent: 7, node: 1 }];
+        assert!(Replay::new(0, &bad).step().is_err());
+    }
+
+    #[test]
+    fn test_replay_recorded_run() {
+        let options = RrtOptions {
+            max_extension: 2.0,
+            use_rrtstar: true,
+            rewire_radius: 3.0,
+            record_events: true,
+            ..RrtOptions::default()
+        };
+        let mut samples = [9, 4, 7, 2, 10, 5].into_iter().cycle();
+        let mut stats = PlannerStats::new();
+        let (path, tree) = rrt_with_termination(
+            &0,
+            &10,
+            || samples.next().unwrap(),
+            |from: &i32, to: &i32| from + (to - from).clamp(-2, 2),
+            |_: &i32, _: &i32| true,
+            &options,
+            MaxIterations(20),
+            &mut stats,
+        )
+        .unwrap();
+
+        let events = stats.events.unwrap();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, PlannerEvent::Solution { .. })));
+
+        // Replaying the whole log rebuilds the final tree
+        let mut replay = Replay::new(0, &events);
+        replay.run_through(u64::MAX).unwrap();
+        assert_eq!(replay.tree().size(), tree.size());
+        for node in tree.iter_insertion_order() {
+            assert_eq!(replay.tree().get_parent(node), tree.get_parent(node));
+        }
+        assert_eq!(replay.tree().path(&10).unwrap(), path);
+        assert_eq!(replay.best_cost(), tree.cost(&10).ok());
+    }
+}
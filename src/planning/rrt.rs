@@ -20,190 +20,1962 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::clock::{Clock, Instant, SystemClock};
+use crate::error::PlanningError;
+use crate::planning::info::PlannerInfo;
+use crate::planning::interactive::{IterationSummary, StepControl};
+use crate::planning::radius::AdaptiveRadius;
+use crate::planning::replay::PlannerEvent;
+use crate::planning::stats::{
+    FirstSolution, GoalRejection, GoalRejectionReason, PhaseTimings, PlannerStats, PlanningStats,
+    StopReason,
+};
+use crate::planning::termination::{
+    CancellationToken, MaxDuration, MaxIterations, Or, PlannerProgress, TerminationCondition,
+};
+use crate::planning::tuning::SplitMix64;
 use crate::tree::Distance;
-use crate::tree::HashTree;
+use crate::tree::{EdgeCost, HashTree, Provenance, TieBreak};
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::hash::Hash;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+/// Which way the connectable function is queried when attempting to reach the goal.
+///
+/// Only matters if `connectable_fn` is asymmetric, e.g. for non-holonomic systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionDirection {
+    /// Query `connectable_fn(goal, node)`.
+    GoalToTree,
+
+    /// Query `connectable_fn(node, goal)`, matching the direction of every other edge.
+    TreeToGoal,
+}
+
+/// Which tree nodes are considered when attempting to connect to the goal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoalCandidates {
+    /// Only the most recently added node.
+    Latest,
+
+    /// The `k` nodes in the tree that are nearest to the goal.
+    Nearest(usize),
+}
+
+/// Strategy for connecting the tree to the goal.
+///
+/// The default attempts a single step connection from the latest extension to the goal
+/// every iteration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoalConnection {
+    /// Direction in which to query `connectable_fn`.
+    pub direction: ConnectionDirection,
+
+    /// Only attempt to connect to the goal every `frequency` iterations.
+    pub frequency: u64,
+
+    /// Nodes to attempt to connect from.
+    pub candidates: GoalCandidates,
+
+    /// Skip candidates further than this from the goal without calling `extend_fn` or
+    /// `connectable_fn`, counted in [`PlannerStats::goal_skips`].
+    pub max_distance: f64,
+
+    /// Greedily extend towards the goal from each candidate using `extend_fn`, rather
+    /// than only attempting a direct connection.
+    pub use_connect: bool,
+}
+
+impl Default for GoalConnection {
+    fn default() -> Self {
+        GoalConnection {
+            direction: ConnectionDirection::GoalToTree,
+            frequency: 1,
+            candidates: GoalCandidates::Latest,
+            max_distance: f64::INFINITY,
+            use_connect: false,
+        }
+    }
+}
+
+/// How new states that duplicate a node already in the tree are handled.
+///
+/// In continuous spaces bitwise identical states do occur, e.g. when the same sample is
+/// drawn twice or a greedy extension retraces existing nodes, and the tree cannot hold the
+/// same state twice. If the tree has a [`crate::tree::DedupGrid`], states in the same cell
+/// as an existing node are duplicates of it too, see [`rrt_with_tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the existing node and continue extending from it.
+    #[default]
+    Ignore,
+
+    /// Discard the duplicate and the rest of the extension it was part of.
+    Reject,
+
+    /// Like `Ignore`, but states within this distance of an existing node are also treated
+    /// as duplicates of that node. This requires a nearest neighbor lookup for every new
+    /// state, consider a quantized state type such as [`crate::float::Quantized`] instead
+    /// for large trees.
+    Within(f64),
+}
+
+/// How the node to extend from is chosen for each sample.
+///
+/// Expanding from the nearest node gives RRT its Voronoi bias towards unexplored space,
+/// which can stall in cluttered or narrow regions where nodes on the frontier are
+/// repeatedly chosen but cannot grow. The alternatives pick among the nearest
+/// `candidates` nodes in the style of EST and KPIECE, while keeping RRT's extension step.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NodeSelection {
+    /// Extend from the nearest node.
+    #[default]
+    Nearest,
+
+    /// Extend from whichever candidate has been selected the fewest times, ties going to
+    /// the nearest.
+    LeastVisited { candidates: usize },
+
+    /// Extend from whichever candidate has the fewest nodes within `radius`, ties going to
+    /// the nearest. This requires a neighbor query for every candidate.
+    LowestDensity { candidates: usize, radius: f64 },
+}
+
+/// Limits on how far a single RRT-Connect extension may grow towards a sample.
+///
+/// Unlimited by default. Without a limit a single distant sample can add a long chain of
+/// nodes, which starves exploration in other directions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectLimit {
+    /// Maximum number of nodes added by one extension.
+    pub max_nodes: usize,
+
+    /// Maximum total length of one extension, measured along the added nodes.
+    pub max_length: f64,
+}
+
+impl Default for ConnectLimit {
+    fn default() -> Self {
+        ConnectLimit {
+            max_nodes: usize::MAX,
+            max_length: f64::INFINITY,
+        }
+    }
+}
+
+/// Algorithm parameters for [`rrt_with_termination`].
+///
+/// Note that `connectable_fn` is strictly a motion validator, it should only check that
+/// the motion between two states is valid. How far the tree may grow in a single step is
+/// controlled by `max_extension`, and how far RRT* looks for nodes to rewire is controlled
+/// by `rewire_radius`. The two are independent, rewired edges may be longer than a single
+/// extension.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RrtOptions {
+    /// Maximum distance between a newly added node and its parent. Samples further away
+    /// than this are steered towards using `extend_fn`, which is expected to return
+    /// states no more than `max_extension` away.
+    pub max_extension: f64,
+
+    /// Whether or not to use RRT*.
+    pub use_rrtstar: bool,
+
+    /// If using RRT*, the max distance to identify and rewire neighbors of newly added nodes.
+    pub rewire_radius: f64,
+
+    /// If set, the RRT* rewire radius shrinks as the tree grows, capped at `rewire_radius`.
+    pub adaptive_radius: Option<AdaptiveRadius>,
+
+    /// Whether or not to use RRT-Connect.
+    pub use_rrtconnect: bool,
+
+    /// If using RRT-Connect, limits how far each extension towards a sample may grow.
+    pub connect_limit: ConnectLimit,
+
+    /// How the planner attempts to reach the goal.
+    pub goal_connection: GoalConnection,
+
+    /// Record every failed goal connection attempt in [`PlannerStats::goal_rejections`].
+    pub record_goal_rejections: bool,
+
+    /// How nearest neighbor queries order nodes that are the same distance away.
+    pub tie_break: TieBreak,
+
+    /// How states that duplicate existing nodes are handled, counted in
+    /// [`PlannerStats::duplicate_states`].
+    pub duplicate_policy: DuplicatePolicy,
+
+    /// How the node to extend from is chosen for each sample.
+    pub node_selection: NodeSelection,
+
+    /// Record the iteration and elapsed time at which every node is added, retrievable
+    /// with [`HashTree::provenance`].
+    pub record_provenance: bool,
+
+    /// Record the time spent in each phase of planning in [`PlannerStats::timings`].
+    pub record_timings: bool,
+
+    /// Radius of the start region around the root of the tree. Motions from the root to
+    /// states in the region are not validated, so planning still succeeds if the start
+    /// has drifted slightly into an invalid state.
+    pub start_tolerance: f64,
+
+    /// Record every sample, tree change, and solution improvement in
+    /// [`PlannerStats::events`] for replaying the run later.
+    pub record_events: bool,
+
+    /// Paths to the goal costing more than this are rejected, e.g. to stay within a
+    /// vehicle's range. The planner keeps searching for a path within the budget, and
+    /// reports that there is no solution within the maximum cost if it finds none. Not
+    /// supported by the bidirectional planners.
+    pub max_cost: f64,
+
+    /// Maximum number of iterations before [`plan`] gives up. Other entry points stop
+    /// according to the [`TerminationCondition`] they are given instead.
+    pub max_iterations: u64,
+
+    /// If set, the maximum amount of time [`plan`] may search for.
+    pub timeout: Option<Duration>,
+
+    /// Whether [`plan`] returns as soon as any path is found, rather than searching until
+    /// `max_iterations` or `timeout` for a better one.
+    pub fast_return: bool,
+
+    /// If set, every planner stops within an iteration of the token being cancelled, in
+    /// addition to its own termination condition. Planning then fails with
+    /// [`PlanningError::Cancelled`] unless a path had already been found. Not persisted.
+    pub cancellation: Option<CancellationToken>,
+
+    /// Once a path to the goal is found, redraw samples whose distance from the start plus
+    /// distance to the goal exceeds the cost of that path, as in Informed RRT*. Samples
+    /// are then limited to the prolate hyperspheroid of states that could improve the path,
+    /// or to the states within `max_cost` before any path is found. Requires the distance
+    /// between states to never exceed the cost of moving between them.
+    pub informed_sampling: bool,
+
+    /// Seed for the planner's internal randomness, so that runs given the same samples
+    /// make the same decisions. Only the goal biasing of
+    /// [`crate::planning::interactive::rrt_interactive`] is random, every other planner
+    /// ignores the seed. Use [`RrtOptions::rng`] to draw the samples themselves from the
+    /// same seed, and [`PlannerInfo::with_seed`] to record it.
+    pub seed: u64,
+}
+
+impl Default for RrtOptions {
+    fn default() -> Self {
+        RrtOptions {
+            max_extension: f64::INFINITY,
+            use_rrtstar: false,
+            rewire_radius: 0.0,
+            adaptive_radius: None,
+            use_rrtconnect: false,
+            connect_limit: ConnectLimit::default(),
+            goal_connection: GoalConnection::default(),
+            record_goal_rejections: false,
+            tie_break: TieBreak::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            node_selection: NodeSelection::default(),
+            record_provenance: false,
+            record_timings: false,
+            start_tolerance: 0.0,
+            record_events: false,
+            max_cost: f64::INFINITY,
+            max_iterations: 10_000,
+            timeout: None,
+            fast_return: true,
+            cancellation: None,
+            informed_sampling: false,
+            seed: 0,
+        }
+    }
+}
+
+impl RrtOptions {
+    /// Sets the maximum distance between a newly added node and its parent.
+    #[must_use]
+    pub fn with_max_extension(mut self, max_extension: f64) -> Self {
+        self.max_extension = max_extension;
+        self
+    }
+
+    /// Uses RRT*, rewiring neighbors within `rewire_radius` of newly added nodes.
+    #[must_use]
+    pub fn with_rrtstar(mut self, rewire_radius: f64) -> Self {
+        self.use_rrtstar = true;
+        self.rewire_radius = rewire_radius;
+        self
+    }
+
+    /// Uses RRT-Connect, extending repeatedly towards each sample.
+    #[must_use]
+    pub fn with_rrtconnect(mut self) -> Self {
+        self.use_rrtconnect = true;
+        self
+    }
+
+    /// Sets how the planner attempts to reach the goal.
+    #[must_use]
+    pub fn with_goal_connection(mut self, goal_connection: GoalConnection) -> Self {
+        self.goal_connection = goal_connection;
+        self
+    }
+
+    /// Rejects paths to the goal costing more than `max_cost`.
+    #[must_use]
+    pub fn with_max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = max_cost;
+        self
+    }
+
+    /// Sets the maximum number of iterations for [`plan`].
+    #[must_use]
+    pub fn with_max_iterations(mut self, max_iterations: u64) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Sets the maximum amount of time [`plan`] may search for.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether [`plan`] returns as soon as any path is found.
+    #[must_use]
+    pub fn with_fast_return(mut self, fast_return: bool) -> Self {
+        self.fast_return = fast_return;
+        self
+    }
+
+    /// Stops planning once `cancellation` is cancelled.
+    #[must_use]
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Sets [`RrtOptions::seed`], which seeds the goal biasing of
+    /// [`crate::planning::interactive::rrt_interactive`] and [`RrtOptions::rng`]. No other
+    /// planner makes random decisions of its own, so they ignore it.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Returns a generator of uniform values in `[0, 1)` seeded with `seed`, e.g. for
+    /// [`crate::bounds::Bounds::sampler`], so that a run is reproducible from its options
+    /// alone without depending on an external RNG.
+    ///
+    /// ```
+    /// # use rustplanning::planning::rrt::RrtOptions;
+    /// let options = RrtOptions::default().with_seed(42);
+    /// let (mut a, mut b) = (options.rng(), options.rng());
+    /// let value = a();
+    /// assert!((0.0..1.0).contains(&value));
+    /// assert_eq!(value.to_bits(), b().to_bits());
+    /// ```
+    pub fn rng(&self) -> impl FnMut() -> f64 {
+        let mut rng = SplitMix64(self.seed);
+        move || rng.next_f64()
+    }
+
+    /// Returns the condition [`plan`] stops on, given `max_iterations`, `timeout`, and
+    /// `fast_return`.
+    #[must_use]
+    pub fn termination(&self) -> impl TerminationCondition {
+        let fast_return = self.fast_return;
+        MaxIterations(self.max_iterations)
+            .or(MaxDuration(self.timeout.unwrap_or(Duration::MAX)))
+            .or(move |progress: &PlannerProgress| fast_return && progress.best_cost.is_some())
+    }
+
+    /// Describes the RRT variant and parameters these options configure, for attaching to
+    /// logs and experiment records.
+    #[must_use]
+    pub fn info(&self) -> PlannerInfo {
+        let name = match (self.use_rrtstar, self.use_rrtconnect) {
+            (false, false) => "RRT",
+            (true, false) => "RRT*",
+            (false, true) => "RRT-Connect",
+            (true, true) => "RRT*-Connect",
+        };
+
+        let mut info = PlannerInfo::new(name).with_parameter("max_extension", self.max_extension);
+        if self.use_rrtstar {
+            info = info.with_parameter("rewire_radius", self.rewire_radius);
+            if let Some(adaptive) = self.adaptive_radius {
+                info = info.with_parameter("gamma", adaptive.gamma());
+            }
+        }
+        if self.max_cost < f64::INFINITY {
+            info = info.with_parameter("max_cost", self.max_cost);
+        }
+        if self.informed_sampling {
+            info = info.with_parameter("informed_sampling", true);
+        }
+        if self.node_selection != NodeSelection::Nearest {
+            info = info.with_parameter("node_selection", format!("{:?}", self.node_selection));
+        }
+        info.with_parameter("goal_connection", format!("{:?}", self.goal_connection))
+    }
+
+    /// Returns `termination`, which also stops once [`RrtOptions::cancellation`] is
+    /// cancelled.
+    pub(crate) fn cancellable<TC>(&self, termination: TC) -> Or<Option<CancellationToken>, TC>
+    where
+        TC: TerminationCondition,
+    {
+        Or(self.cancellation.clone(), termination)
+    }
+
+    /// Returns the RRT* rewire radius for a tree of `nodes` nodes.
+    #[must_use]
+    pub fn rewire_radius_at(&self, nodes: usize) -> f64 {
+        match &self.adaptive_radius {
+            Some(adaptive) => adaptive.radius(nodes).min(self.rewire_radius),
+            None => self.rewire_radius,
+        }
+    }
+}
+
+/// Number of samples drawn for each iteration with [`RrtOptions::informed_sampling`]
+/// before settling for one that cannot improve the path, so that planning still makes
+/// progress if the sampler rarely returns states in the informed set.
+const INFORMED_SAMPLE_ATTEMPTS: usize = 100;
+
+/// Relative slack allowed on `max_extension`, so that steering functions which return
+/// states exactly `max_extension` away are not rejected due to rounding.
+const EXTENSION_TOLERANCE: f64 = 1e-9;
+
+/// Returns whether or not a motion of length `distance` is within `max_extension`.
+fn within_reach(distance: f64, max_extension: f64) -> bool {
+    distance <= max_extension * (1.0 + EXTENSION_TOLERANCE)
+}
+
+/// Restricts a motion validator to motions no longer than `max_extension`.
+pub(crate) fn within_extension<T, FC>(
+    connectable: &mut FC,
+    max_extension: f64,
+) -> impl FnMut(&T, &T) -> bool + '_
+where
+    T: Distance,
+    FC: FnMut(&T, &T) -> bool,
+{
+    move |from: &T, to: &T| within_reach(from.distance(to), max_extension) && connectable(from, to)
+}
+
+/// Repeatedly extends from `from` towards `target` until the target is connectable, the
+/// extension is invalid, or we stop making progress towards the target.
+///
+/// Returns the intermediate points, which do not include `from` or `target`. Stops early
+/// once the points would exceed `limit`.
+pub(crate) fn extend_towards<T, FE, FC>(
+    from: &T,
+    target: &T,
+    extend: &mut FE,
+    connectable: &mut FC,
+    limit: &ConnectLimit,
+) -> Vec<T>
+where
+    T: Eq + Clone + Hash + Distance,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let mut path: Vec<T> = Vec::new();
+    let mut distance_to_target = from.distance(target);
+    let mut length = 0.0;
+    loop {
+        let current_point = path.last().unwrap_or(from);
+        if connectable(current_point, target) || path.len() >= limit.max_nodes {
+            break;
+        }
+
+        let new_point = extend(current_point, target);
+        let new_distance_to_target = new_point.distance(target);
+        if new_distance_to_target >= distance_to_target || !connectable(current_point, &new_point) {
+            break;
+        }
+
+        length += current_point.distance(&new_point);
+        if length > limit.max_length {
+            break;
+        }
+        path.push(new_point);
+        distance_to_target = new_distance_to_target;
+    }
+    path
+}
 
 /// Attempts to randomly extend the tree in an arbitrary direction.
 /// Return the new point and the nearest neighbor, if available.
 /// Otherwise return None.
 ///
-/// If `use_connect`, continue extending until the sample is reached or we can't
-/// connect.
-fn extend_tree<T, FE, FC>(
+/// If `connect` is set, continue extending until the sample is reached, we can't
+/// connect, or the limit is reached.
+pub(crate) fn extend_tree<T, FE, FC>(
     tree: &HashTree<T>,
     sample: T,
     extend: &mut FE,
     connectable: &mut FC,
-    use_connect: bool,
-) -> (Vec<T>, T)
-where
-    T: Eq + Copy + Hash + Distance,
+    connect: Option<&ConnectLimit>,
+) -> (Vec<T>, T)
+where
+    T: Eq + Clone + Hash + Distance,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    // Sample the grab the nearest point, and extend in that direction
+    let nearest = tree.nearest_neighbor(&sample);
+    (
+        extend_from(nearest, sample, extend, connectable, connect),
+        nearest.clone(),
+    )
+}
+
+/// Extends from an existing node towards the sample as in [`extend_tree`], returning the
+/// new points.
+pub(crate) fn extend_from<T, FE, FC>(
+    nearest: &T,
+    sample: T,
+    extend: &mut FE,
+    connectable: &mut FC,
+    connect: Option<&ConnectLimit>,
+) -> Vec<T>
+where
+    T: Eq + Clone + Hash + Distance,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let mut path = Vec::new();
+
+    if connectable(nearest, &sample) {
+        path.push(sample);
+    }
+    // If using connect, extend until we can extend no further or we begin
+    // moving further away from the sample.
+    else if let Some(limit) = connect {
+        path = extend_towards(nearest, &sample, extend, connectable, limit);
+        let last = path.last().unwrap_or(nearest);
+        let length: f64 = std::iter::once(nearest)
+            .chain(&path)
+            .zip(&path)
+            .map(|(a, b)| a.distance(b))
+            .sum();
+        if path.len() < limit.max_nodes
+            && length + last.distance(&sample) <= limit.max_length
+            && connectable(last, &sample)
+        {
+            path.push(sample);
+        }
+    } else {
+        let new_point = extend(nearest, &sample);
+        if connectable(nearest, &new_point) {
+            path.push(new_point);
+        }
+    }
+    path
+}
+
+/// Reparents a newly added node under the neighbor that reaches it most cheaply, the
+/// choose-parent step of RRT*. Returns whether the node's parent changed.
+pub(crate) fn choose_parent<T, FC>(
+    tree: &mut HashTree<T>,
+    connectable: &mut FC,
+    point: &T,
+    radius: f64,
+    stats: &mut PlannerStats<T>,
+) -> bool
+where
+    T: Eq + Clone + Hash + Distance,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let Some(parent) = tree.get_parent(point).cloned() else {
+        return false;
+    };
+    let via =
+        |tree: &HashTree<T>, parent: &T| tree.cost(parent).unwrap() + tree.edge_cost(parent, point);
+    let neighbors: Vec<T> = tree
+        .neighbors_within(point, radius)
+        .into_iter()
+        .map(|(neighbor, _)| neighbor.clone())
+        .collect();
+    let mut best = (parent.clone(), tree.cost(point).unwrap());
+    for neighbor in neighbors {
+        // The node's descendants cannot become its parent
+        if neighbor == parent || tree.is_ancestor(point, &neighbor) {
+            continue;
+        }
+        let cost = via(tree, &neighbor);
+        if cost < best.1 && connectable(&neighbor, point) {
+            best = (neighbor, cost);
+        }
+    }
+
+    if best.0 == parent {
+        return false;
+    }
+    match tree.set_parent(point, &best.0) {
+        Ok(()) => {
+            stats.rewires += 1;
+            stats.record(|| PlannerEvent::Rewired {
+                node: point.clone(),
+                parent: best.0.clone(),
+            });
+            true
+        }
+        Err(e) => {
+            stats.tree_errors.push(e);
+            false
+        }
+    }
+}
+
+/// Reparents neighbors of `point` through it where that is cheaper, returning the number of
+/// nodes that were rewired.
+pub(crate) fn rewire_tree<T, FC>(
+    tree: &mut HashTree<T>,
+    connectable: &mut FC,
+    point: &T,
+    rewire_radius: f64,
+    stats: &mut PlannerStats<T>,
+) -> usize
+where
+    T: Eq + Clone + Hash + Distance,
+    FC: FnMut(&T, &T) -> bool,
+{
+    // Get a list of all nodes that are within the sample radius, and rewire if necessary.
+    // Neighbors are visited in insertion order regardless of the tree's tie breaking, so
+    // that rewiring is repeatable.
+    let neighbors: Vec<T> = tree
+        .neighbors_within(point, rewire_radius)
+        .into_iter()
+        .map(|(neighbor, _)| neighbor.clone())
+        .collect();
+    let point_cost = tree.cost(point).unwrap();
+    let mut rewired = 0;
+    for neighbor in &neighbors {
+        // Reparenting an ancestor under the new node would create a cycle
+        if tree.is_ancestor(neighbor, point) {
+            continue;
+        }
+        // If it's cheaper and valid to get to the neighbor from the new node reparent it
+        let old_cost = tree.cost(neighbor).unwrap();
+        let new_cost = point_cost + tree.edge_cost(point, neighbor);
+        if new_cost < old_cost && connectable(point, neighbor) {
+            match tree.set_parent(neighbor, point) {
+                Ok(()) => {
+                    rewired += 1;
+                    stats.rewires += 1;
+                    stats.record(|| PlannerEvent::Rewired {
+                        node: neighbor.clone(),
+                        parent: point.clone(),
+                    });
+                }
+                Err(e) => stats.tree_errors.push(e),
+            }
+        }
+    }
+    rewired
+}
+
+/// The RRT* step after extending the tree: every node inserted since the tree had `size`
+/// nodes is wired through its cheapest neighbor with [`choose_parent`], then each of
+/// `new_points` is offered as a cheaper parent to its neighbors with [`rewire_tree`].
+/// Returns the number of neighbors that were rewired.
+pub(crate) fn rewire_added<T, FC>(
+    tree: &mut HashTree<T>,
+    connectable: &mut FC,
+    size: usize,
+    new_points: &[T],
+    radius: f64,
+    stats: &mut PlannerStats<T>,
+) -> usize
+where
+    T: Eq + Clone + Hash + Distance,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let added: Vec<T> = tree.iter_insertion_order().skip(size).cloned().collect();
+    for node in &added {
+        choose_parent(tree, connectable, node, radius, stats);
+    }
+    new_points
+        .iter()
+        .map(|node| rewire_tree(tree, connectable, node, radius, stats))
+        .sum()
+}
+
+/// Adds a chain of states to the tree starting from `parent`, handling duplicates of
+/// existing nodes according to `policy`.
+///
+/// Returns the nodes of the chain that are in the tree afterwards, where duplicates that
+/// were kept are replaced by the existing node. Any error from the tree is recorded in
+/// `stats` and ends the chain, as does a state with a NaN distance to its parent.
+pub(crate) fn add_path<T>(
+    tree: &mut HashTree<T>,
+    parent: &T,
+    path: Vec<T>,
+    policy: DuplicatePolicy,
+    stats: &mut PlannerStats<T>,
+) -> Vec<T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    let mut added: Vec<T> = Vec::with_capacity(path.len());
+    let total = path.len();
+    for node in path {
+        if node.distance(added.last().unwrap_or(parent)).is_nan() {
+            stats.invalid_states += 1;
+            stats.discarded_states += total - added.len() - 1;
+            break;
+        }
+
+        let duplicate = if tree.contains(&node) {
+            Some(node.clone())
+        } else if let Some(existing) = tree.near_duplicate(&node) {
+            Some(existing.clone())
+        } else if let DuplicatePolicy::Within(tolerance) = policy {
+            let nearest = tree.nearest_neighbor(&node);
+            (nearest.distance(&node) <= tolerance).then(|| nearest.clone())
+        } else {
+            None
+        };
+
+        let Some(existing) = duplicate else {
+            let node_parent = added.last().unwrap_or(parent);
+            if let Err(e) = tree.add_child(node_parent, node.clone()) {
+                // Nothing further along the chain can be attached either
+                stats.tree_errors.push(e);
+                stats.discarded_states += total - added.len() - 1;
+                break;
+            }
+            stats.record(|| PlannerEvent::NodeAdded {
+                parent: node_parent.clone(),
+                node: node.clone(),
+            });
+            added.push(node);
+            continue;
+        };
+        stats.duplicate_states += 1;
+        if policy == DuplicatePolicy::Reject {
+            stats.discarded_states += total - added.len() - 1;
+            break;
+        }
+        added.push(existing);
+    }
+    added
+}
+
+/// Attempts to connect the goal to the tree using the specified strategy, adding the goal
+/// and any intermediate points to the tree on success.
+///
+/// Failed attempts are appended to `rejections`, if provided.
+///
+/// Returns whether or not the goal was added to the tree.
+pub(crate) fn connect_goal<T, FE, FC>(
+    tree: &mut HashTree<T>,
+    goal: &T,
+    latest: Option<&T>,
+    extend: &mut FE,
+    connectable: &mut FC,
+    options: &RrtOptions,
+    stats: &mut PlannerStats<T>,
+) -> bool
+where
+    T: Eq + Clone + Hash + Distance,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let strategy = &options.goal_connection;
+    let candidates: Vec<T> = match strategy.candidates {
+        GoalCandidates::Latest => latest.into_iter().cloned().collect(),
+        GoalCandidates::Nearest(k) => tree
+            .k_nearest_neighbors(goal, k)
+            .into_iter()
+            .map(|(node, _)| node.clone())
+            .collect(),
+    };
+
+    for candidate in candidates {
+        if candidate.distance(goal) > strategy.max_distance {
+            stats.goal_skips += 1;
+            continue;
+        }
+        stats.goal_attempts += 1;
+
+        let path = if strategy.use_connect {
+            let mut reachable = within_extension(connectable, options.max_extension);
+            extend_towards(
+                &candidate,
+                goal,
+                extend,
+                &mut reachable,
+                &ConnectLimit::default(),
+            )
+        } else {
+            Vec::new()
+        };
+
+        let last = path.last().unwrap_or(&candidate);
+        let distance = last.distance(goal);
+        let reason = if !within_reach(distance, options.max_extension) {
+            Some(GoalRejectionReason::OutOfReach)
+        } else if exceeds_max_cost(tree, &candidate, &path, goal, options.max_cost) {
+            stats.cost_rejections += 1;
+            Some(GoalRejectionReason::OverCost)
+        } else {
+            let valid = match strategy.direction {
+                ConnectionDirection::GoalToTree => connectable(goal, last),
+                ConnectionDirection::TreeToGoal => connectable(last, goal),
+            };
+            (!valid).then_some(GoalRejectionReason::InvalidMotion)
+        };
+
+        if let Some(reason) = reason {
+            if options.record_goal_rejections {
+                stats.goal_rejections.push(GoalRejection {
+                    state: last.clone(),
+                    distance,
+                    reason,
+                });
+            }
+            continue;
+        }
+
+        let length = path.len();
+        let path = path
+            .into_iter()
+            .chain(std::iter::once(goal.clone()))
+            .collect();
+        let added = add_path(tree, &candidate, path, options.duplicate_policy, stats);
+
+        // The goal itself must be in the tree, so if it was merged into a nearby node it is
+        // added anyway as a child of the node before it
+        if !tree.contains(goal) && added.len() == length + 1 {
+            let parent = if length == 0 {
+                &candidate
+            } else {
+                &added[length - 1]
+            };
+            if let Err(e) = tree.add_child(parent, goal.clone()) {
+                stats.tree_errors.push(e);
+            }
+        }
+        return tree.contains(goal);
+    }
+
+    false
+}
+
+/// Returns true if reaching the goal from `candidate` through `path` would cost more than
+/// `max_cost`.
+fn exceeds_max_cost<T>(
+    tree: &HashTree<T>,
+    candidate: &T,
+    path: &[T],
+    goal: &T,
+    max_cost: f64,
+) -> bool
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    if max_cost == f64::INFINITY {
+        return false;
+    }
+    let mut cost = tree.cost(candidate).unwrap_or(f64::INFINITY);
+    let mut parent = candidate;
+    for node in path.iter().chain(std::iter::once(goal)) {
+        cost += tree.edge_cost(parent, node);
+        parent = node;
+    }
+    cost > max_cost
+}
+
+/// Returns the path through the tree to the goal, or an error explaining why there is none.
+pub(crate) fn solution_path<T>(
+    tree: &HashTree<T>,
+    goal: &T,
+    options: &RrtOptions,
+    stats: &PlannerStats<T>,
+) -> Result<Vec<T>, PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    let max_cost = options.max_cost;
+    match tree.cost(goal) {
+        Ok(cost) if cost <= max_cost => Ok(tree.path(goal)?),
+        Ok(cost) => Err(PlanningError::NoSolutionWithinCost {
+            max_cost,
+            best_cost: Some(cost),
+        }),
+        Err(_) if stats.cost_rejections > 0 => Err(PlanningError::NoSolutionWithinCost {
+            max_cost,
+            best_cost: None,
+        }),
+        Err(_) => Err(PlanningError::not_found(stats.stop_reason)),
+    }
+}
+
+/// Implementation of RRT planning algorithms.
+///
+/// Will attempt to compute a path using the specified version of RRT given the start pose
+/// and user-defined coverage functions.
+///
+/// # Parameters
+///
+/// - `start`: The reference to the starting pose of type `T`
+/// - `sample_fn`: Function to randomly sample the configuration space
+/// - `extend_fn`: Given two nodes, function to return an intermediate value between them
+/// - `connectable_fn`: Function to determine whether or not a link can be added between two nodes
+/// - `use_rrtstar`: Whether or not to use RRT*
+/// - `rewire_radius`: If using RRT*, the max distance to identify and rewire neighbors of newly added nodes
+/// - `use_rrtconnect`: Whether or not to use RRT-Connect
+/// - `max_iterations`: Maximum number of random samples to attempt before the search fails
+/// - `max_duration`: Maximum amount of time in seconds to find a solution
+/// - `fast_return`: Return as soon as a solution is found, or iterate until `max_iterations` or `max_duration` is reached
+///
+/// # Returns
+/// Returns a [`PlanResult`] containing:
+/// - `path`: Either the path from the start to the goal, if one is found within the given
+///   number of iterations, or why the algorithm failed to find a satisfactory path.
+/// - `tree`: The tree itself, even if no path was found.
+/// - `stats`: A summary of the run, including why it stopped.
+///
+/// Use [`PlanResult::into_result`] for the path and tree as a `Result`.
+///
+/// # Example
+///
+/// Refer to the world example or integration tests.
+///
+/// Prefer [`plan`], which takes these parameters as [`RrtOptions`] so that callers are not
+/// affected as options are added.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt<T, FS, FE, FC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    use_rrtstar: bool,
+    rewire_radius: f64,
+    use_rrtconnect: bool,
+    max_iterations: u64,
+    max_duration: f64,
+    fast_return: bool,
+) -> PlanResult<T>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    // The connectable function has historically been responsible for limiting the
+    // length of motions, so do not impose any additional limits.
+    let options = RrtOptions {
+        use_rrtstar,
+        rewire_radius,
+        use_rrtconnect,
+        max_iterations,
+        timeout: Some(Duration::from_secs_f64(max_duration)),
+        fast_return,
+        ..RrtOptions::default()
+    };
+
+    plan_to_result(start, goal, sample_fn, extend_fn, connectable_fn, &options)
+}
+
+/// Plans as in [`plan`], summarizing the run in a [`PlanResult`].
+fn plan_to_result<T, FS, FE, FC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+) -> PlanResult<T>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let start_time = Instant::now();
+    let mut tree = HashTree::new(start.clone());
+    let mut stats = PlannerStats::new();
+    let path = rrt_with_tree(
+        &mut tree,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        options.termination(),
+        &mut stats,
+    );
+
+    // Only returning the first path stops without a reason
+    let stop_reason = stats.stop_reason.unwrap_or(StopReason::FoundPath);
+    let stats = PlanningStats {
+        iterations: stats.iterations,
+        samples_rejected: stats.rejected_samples,
+        nodes_added: tree.size() - 1,
+        rewires: stats.rewires,
+        elapsed: start_time.elapsed(),
+        path_cost: path.as_ref().ok().and_then(|_| tree.cost(goal).ok()),
+        stop_reason,
+    };
+    PlanResult { path, tree, stats }
+}
+
+/// The outcome of [`rrt`], with the tree and a summary of the run whether or not a path was
+/// found.
+#[derive(Debug)]
+pub struct PlanResult<T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    /// The path from the start to the goal, or why none was found.
+    pub path: Result<Vec<T>, PlanningError>,
+
+    /// The tree grown while planning.
+    pub tree: HashTree<T>,
+
+    /// A summary of the run, including why it stopped.
+    pub stats: PlanningStats,
+}
+
+impl<T> PlanResult<T>
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    /// Returns the path and tree, or why no path was found.
+    ///
+    /// # Errors
+    ///
+    /// If no path to the goal was found.
+    pub fn into_result(self) -> Result<(Vec<T>, HashTree<T>), PlanningError> {
+        self.path.map(|path| (path, self.tree))
+    }
+}
+
+/// Plans a path from `start` to `goal` with the RRT variant configured by `options`,
+/// stopping after [`RrtOptions::max_iterations`] or [`RrtOptions::timeout`], or as soon as
+/// a path is found if [`RrtOptions::fast_return`] is set.
+///
+/// ```
+/// # use rustplanning::float::FloatArray;
+/// # use rustplanning::planning::rrt::{plan, RrtOptions};
+/// # use std::time::Duration;
+/// let options = RrtOptions::default()
+///     .with_max_extension(1.0)
+///     .with_rrtstar(2.0)
+///     .with_timeout(Duration::from_secs(1));
+/// let mut samples = [3.0, -2.0, 5.0, 4.0].into_iter().cycle();
+/// let (path, _) = plan(
+///     &FloatArray([0.0]),
+///     &FloatArray([4.0]),
+///     || FloatArray([samples.next().unwrap()]),
+///     |from: &FloatArray<1>, to: &FloatArray<1>| {
+///         FloatArray([from.0[0] + (to.0[0] - from.0[0]).clamp(-1.0, 1.0)])
+///     },
+///     |_: &FloatArray<1>, _: &FloatArray<1>| true,
+///     &options,
+/// )
+/// .unwrap();
+/// assert_eq!(path.len(), 5);
+/// ```
+///
+/// Use [`rrt_with_termination`] for other stopping criteria, or to collect
+/// [`PlannerStats`].
+///
+/// # Errors
+///
+/// If no path to the goal was found before terminating.
+pub fn plan<T, FS, FE, FC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    rrt_with_termination(
+        start,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        options.termination(),
+        &mut PlannerStats::new(),
+    )
+}
+
+/// Plans as in [`plan`], but stops early with [`PlanningError::Cancelled`] if
+/// `cancellation` is cancelled before a path is found, e.g. by a GUI stop button or a
+/// preempted action on another thread. Equivalent to planning with
+/// [`RrtOptions::with_cancellation`].
+///
+/// The tree grown before cancellation is returned in the [`PlanResult`] either way, along
+/// with a summary of the run. Planners used through
+/// [`crate::planning::planner::Planner`] are cancelled with
+/// [`crate::planning::planner::Planner::plan_cancellable`] instead.
+///
+/// ```
+/// # use rustplanning::float::FloatArray;
+/// # use rustplanning::error::PlanningError;
+/// # use rustplanning::planning::rrt::{plan_with_cancellation, RrtOptions};
+/// # use rustplanning::planning::termination::CancellationToken;
+/// let cancellation = CancellationToken::new();
+/// let stop = cancellation.clone();
+/// let mut samples = 0;
+/// let result = plan_with_cancellation(
+///     &FloatArray([0.0]),
+///     &FloatArray([100.0]),
+///     || {
+///         // Give up partway, as another thread might
+///         samples += 1;
+///         if samples == 10 {
+///             stop.cancel();
+///         }
+///         FloatArray([-1.0])
+///     },
+///     |from: &FloatArray<1>, to: &FloatArray<1>| {
+///         FloatArray([from.0[0] + (to.0[0] - from.0[0]).clamp(-1.0, 1.0)])
+///     },
+///     |_: &FloatArray<1>, _: &FloatArray<1>| true,
+///     &RrtOptions::default().with_max_extension(1.0),
+///     &cancellation,
+/// );
+/// assert_eq!(result.path, Err(PlanningError::Cancelled));
+/// assert_eq!(result.stats.iterations, 10);
+/// assert!(result.tree.size() > 1);
+/// ```
+pub fn plan_with_cancellation<T, FS, FE, FC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    cancellation: &CancellationToken,
+) -> PlanResult<T>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    plan_to_result(
+        start,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        &options.clone().with_cancellation(cancellation.clone()),
+    )
+}
+
+/// Implementation of RRT planning algorithms with a user-defined stopping criteria.
+///
+/// Identical to [`rrt`], except that rather than a fixed iteration count, timeout, and
+/// `fast_return` flag the planner iterates until the provided [`TerminationCondition`]
+/// is met. Refer to [`crate::planning::termination`] for the available conditions.
+///
+/// Algorithm parameters are provided with [`RrtOptions`]. Unlike [`rrt`], `connectable_fn`
+/// should only validate motions, the maximum length of new edges is enforced by the planner
+/// using [`RrtOptions::max_extension`].
+///
+/// Diagnostic information is written to `stats` as the planner runs, so that it is
+/// available even if planning fails.
+///
+/// # Determinism
+///
+/// The planner has no internal randomness and ignores [`RrtOptions::seed`]. Given the
+/// same options and the same sequence of samples, e.g. from an RNG seeded with a fixed
+/// value, the resulting tree and path are identical across runs and platforms. Time based
+/// termination conditions are the exception, since the number of iterations completed will
+/// vary, unless the clock is replaced with [`rrt_with_clock`].
+///
+/// # Errors
+///
+/// If no path to the goal was found before terminating.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_with_termination<T, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut tree = HashTree::new(start.clone());
+    grow_tree(
+        &mut tree,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        stats,
+    );
+
+    let path = solution_path(&tree, goal, options, stats)?;
+    Ok((path, tree))
+}
+
+/// RRT that reads the time elapsed while planning from `clock` rather than the system
+/// clock.
+///
+/// With a [`crate::clock::ManualClock`] and a seeded `sample_fn` every input to the planner is fixed, so
+/// time based termination conditions and recorded provenance are reproducible too, e.g.
+/// for asserting the exact tree built in a unit test. Otherwise identical to
+/// [`rrt_with_termination`].
+///
+/// # Errors
+///
+/// If no path to the goal was found before terminating.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_with_clock<T, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    clock: &dyn Clock,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut tree = HashTree::new(start.clone());
+    grow_tree_with_clock(
+        &mut tree,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        clock,
+        stats,
+    );
+
+    let path = solution_path(&tree, goal, options, stats)?;
+    Ok((path, tree))
+}
+
+/// RRT that grows a tree provided by the caller, rather than starting a new one from a
+/// start state, and returns the path to the goal through it.
+///
+/// This allows configuring the tree before planning, e.g. with
+/// [`HashTree::with_edge_cost`], [`HashTree::set_dedup_grid`] or
+/// [`HashTree::set_spatial_index`], and continuing to grow a tree from an earlier run.
+/// Otherwise identical to [`rrt_with_termination`].
+///
+/// # Errors
+///
+/// If no path to the goal is found before the termination condition is met.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_with_tree<T, FS, FE, FC, TC>(
+    tree: &mut HashTree<T>,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<Vec<T>, PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    grow_tree(
+        tree,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        stats,
+    );
+    solution_path(tree, goal, options, stats)
+}
+
+/// RRT that minimizes a custom edge cost rather than path length, e.g. energy or
+/// actuator effort.
+///
+/// `edge_cost` replaces [`Distance`] wherever path costs are computed, including RRT*
+/// rewiring, so the returned path and tree costs are in its units. Distance is still used
+/// for nearest neighbor queries, `max_extension`, and `rewire_radius`.
+///
+/// # Errors
+///
+/// If no path to the goal is found before the termination condition is met.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_with_edge_cost<T, FS, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    edge_cost: EdgeCost<T>,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut tree = HashTree::with_edge_cost(start.clone(), edge_cost);
+    grow_tree(
+        &mut tree,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        stats,
+    );
+
+    let path = solution_path(&tree, goal, options, stats)?;
+    Ok((path, tree))
+}
+
+/// RRT with a custom strategy for choosing which node to extend towards each sample.
+///
+/// `select_fn` is given the tree and the sample and must return a node in the tree. The
+/// built in strategies in [`NodeSelection`] are configured through `options` instead, and
+/// are ignored here.
+///
+/// # Errors
+///
+/// If no path to the goal is found before the termination condition is met.
+#[allow(clippy::too_many_arguments)]
+pub fn rrt_with_selection<T, FS, FN, FE, FC, TC>(
+    start: &T,
+    goal: &T,
+    sample_fn: FS,
+    select_fn: FN,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FN: FnMut(&HashTree<T>, &T) -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut tree = HashTree::new(start.clone());
+    grow_tree_with_selection(
+        &mut tree,
+        goal,
+        sample_fn,
+        select_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        &SystemClock::start(),
+        0,
+        stats,
+        None,
+    );
+
+    let path = solution_path(&tree, goal, options, stats)?;
+    Ok((path, tree))
+}
+
+/// Runs the RRT loop on an existing tree until the termination condition is met.
+///
+/// Supports planning to several goals with the same tree, nodes added while searching
+/// for one goal are reused for the next.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn grow_tree<T, FS, FE, FC, TC>(
+    tree: &mut HashTree<T>,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    stats: &mut PlannerStats<T>,
+) where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    grow_tree_with_clock(
+        tree,
+        goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        &SystemClock::start(),
+        stats,
+    );
+}
+
+/// Runs the RRT loop as in [`grow_tree`], reading the elapsed time from `clock`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn grow_tree_with_clock<T, FS, FE, FC, TC>(
+    tree: &mut HashTree<T>,
+    goal: &T,
+    sample_fn: FS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    clock: &dyn Clock,
+    stats: &mut PlannerStats<T>,
+) where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut visits = HashMap::new();
+    grow_tree_with_selection(
+        tree,
+        goal,
+        sample_fn,
+        |tree: &HashTree<T>, sample: &T| {
+            select_node(tree, sample, options.node_selection, &mut visits)
+        },
+        extend_fn,
+        connectable_fn,
+        options,
+        termination,
+        clock,
+        0,
+        stats,
+        None,
+    );
+}
+
+/// Chooses the node to extend towards the sample, counting how often each node is chosen.
+pub(crate) fn select_node<T>(
+    tree: &HashTree<T>,
+    sample: &T,
+    selection: NodeSelection,
+    visits: &mut HashMap<T, usize>,
+) -> T
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    let selected = match selection {
+        NodeSelection::Nearest => tree.nearest_neighbor(sample),
+        NodeSelection::LeastVisited { candidates } => tree
+            .k_nearest_neighbors(sample, candidates.max(1))
+            .into_iter()
+            .map(|(node, _)| node)
+            .min_by_key(|node| visits.get(*node).copied().unwrap_or(0))
+            .unwrap_or_else(|| tree.nearest_neighbor(sample)),
+        NodeSelection::LowestDensity { candidates, radius } => tree
+            .k_nearest_neighbors(sample, candidates.max(1))
+            .into_iter()
+            .map(|(node, _)| node)
+            .min_by_key(|node| tree.neighbors_within(node, radius).len())
+            .unwrap_or_else(|| tree.nearest_neighbor(sample)),
+    }
+    .clone();
+    if selection != NodeSelection::Nearest {
+        *visits.entry(selected.clone()).or_insert(0) += 1;
+    }
+    selected
+}
+
+/// Called after every iteration of [`grow_tree_with_selection`] with a summary of what
+/// happened. Planning stops if it returns [`StepControl::Stop`], and the goal moves if it
+/// returns [`StepControl::MoveGoal`], any other control is ignored.
+pub(crate) type Observer<'a, T> =
+    dyn FnMut(&IterationSummary<T>, &HashTree<T>) -> StepControl<T> + 'a;
+
+/// Runs the RRT loop as in [`grow_tree`], but extends from the node chosen by
+/// `select_fn` for each sample rather than the nearest node, with the elapsed time read
+/// from `clock`. Iterations are counted from `first_iteration`, so that a search run a
+/// few iterations at a time counts them as one run would. If provided, `observer` is
+/// called after every iteration and may stop planning.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn grow_tree_with_selection<T, FS, FN, FE, FC, TC>(
+    tree: &mut HashTree<T>,
+    goal: &T,
+    mut sample_fn: FS,
+    mut select_fn: FN,
+    mut extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    termination: TC,
+    clock: &dyn Clock,
+    first_iteration: u64,
+    stats: &mut PlannerStats<T>,
+    mut observer: Option<&mut Observer<T>>,
+) where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FN: FnMut(&HashTree<T>, &T) -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
+{
+    let mut termination = options.cancellable(termination);
+    tree.set_tie_break(options.tie_break);
+    let mut goal = goal.clone();
+
+    let phases = PhaseClock::new(options.record_timings);
+    let mut timings = PhaseTimings::default();
+    let mut feed = EventFeed::start(stats, options.record_events, observer.is_some());
+    stats.stop_reason = None;
+    let mut best_cost = tree.cost(&goal).ok();
+    track_nearest_to_goal(tree, &goal, 0, stats);
+
+    // Leaving the start region is always allowed
+    let root = tree.root().clone();
+    let mut connectable_fn = |from: &T, to: &T| {
+        (*from == root && root.distance(to) <= options.start_tolerance)
+            || phases.check(|| connectable_fn(from, to))
+    };
+
+    for iteration in first_iteration.. {
+        // Are we done?
+        let progress = PlannerProgress {
+            iteration,
+            elapsed: clock.elapsed(),
+            best_cost: tree
+                .cost(&goal)
+                .ok()
+                .filter(|&cost| cost <= options.max_cost),
+        };
+        if termination.should_terminate(&progress) {
+            stats.stop_reason = termination.stop_reason(&progress);
+            break;
+        }
+        stats.iterations += 1;
+        if options.record_provenance {
+            tree.set_provenance_stamp(Some(Provenance {
+                iteration,
+                elapsed: progress.elapsed,
+            }));
+        }
+
+        // Sample the nearest point, and extend in that direction.
+        let size = tree.size();
+        let sample = phases.time(&mut timings.sampling, || {
+            draw_sample(&mut sample_fn, &root, &goal, &progress, options, stats)
+        });
+        if sample.distance(&root).is_nan() {
+            // Nothing sensible can be done with a sample that has no distance, e.g. one
+            // containing NaN
+            stats.invalid_states += 1;
+            stats.rejected_samples += 1;
+            continue;
+        }
+        stats.record(|| PlannerEvent::Sample {
+            iteration,
+            state: sample.clone(),
+        });
+        let summary_sample = observer.is_some().then(|| sample.clone());
+        let nearest = phases.time(&mut timings.nearest_neighbor, || select_fn(tree, &sample));
+        let new_points = phases.time(&mut timings.extension, || {
+            let (extend, connectable) = (&mut extend_fn, &mut connectable_fn);
+            extend_step(tree, &nearest, sample, extend, connectable, options, stats)
+        });
+
+        // Rewire the tree if using RRT*, rewired edges are only limited by the radius.
+        let rewires = if options.use_rrtstar {
+            phases.time(&mut timings.rewiring, || {
+                let radius = options.rewire_radius_at(tree.size());
+                rewire_added(tree, &mut connectable_fn, size, &new_points, radius, stats)
+            })
+        } else {
+            0
+        };
+
+        // If the goal isn't in the tree yet, see if we can reach it.
+        // Once it has been added, RRT* rewiring will continue to improve its cost.
+        let goal_connection = &options.goal_connection;
+        if iteration % goal_connection.frequency.max(1) == 0 && tree.cost(&goal).is_err() {
+            phases.time(&mut timings.goal_checks, || {
+                let (extend, valid) = (&mut extend_fn, &mut connectable_fn);
+                connect_goal(
+                    tree,
+                    &goal,
+                    new_points.last(),
+                    extend,
+                    valid,
+                    options,
+                    stats,
+                )
+            });
+        }
+
+        record_solution(tree, &goal, &progress, &mut best_cost, stats);
+        track_nearest_to_goal(tree, &goal, size, stats);
+
+        if let (Some(observer), Some(sample)) = (observer.as_deref_mut(), summary_sample) {
+            let events = feed.take(stats);
+            let summary =
+                IterationSummary::new(iteration, sample, tree, size, rewires, &goal, stats, events);
+            match observer(&summary, tree) {
+                StepControl::Stop => break,
+                StepControl::MoveGoal(new_goal) => phases.time(&mut timings.goal_checks, || {
+                    best_cost = None;
+                    let (extend, valid) = (&mut extend_fn, &mut connectable_fn);
+                    move_goal(tree, &mut goal, new_goal, extend, valid, options, stats);
+                }),
+                _ => {}
+            }
+        }
+    }
+
+    if options.record_provenance {
+        tree.set_provenance_stamp(None);
+    }
+    feed.finish(stats);
+    timings.collision_checks = phases.collisions.get();
+    stats.timings += timings;
+}
+
+/// Hands out the events recorded during each iteration to an observer. Events are
+/// recorded while observing even if not asked for, but are then dropped once handed out.
+struct EventFeed {
+    keep: bool,
+    delivered: usize,
+}
+
+impl EventFeed {
+    fn start<T>(stats: &mut PlannerStats<T>, record: bool, observing: bool) -> Self {
+        if record {
+            stats.events.get_or_insert_with(Vec::new);
+        }
+        let keep = stats.events.is_some();
+        if observing {
+            stats.events.get_or_insert_with(Vec::new);
+        }
+        let delivered = stats.events.as_ref().map_or(0, Vec::len);
+        EventFeed { keep, delivered }
+    }
+
+    /// Returns the events recorded since the last call.
+    fn take<T: Clone>(&mut self, stats: &mut PlannerStats<T>) -> Vec<PlannerEvent<T>> {
+        let Some(events) = &mut stats.events else {
+            return Vec::new();
+        };
+        if self.keep {
+            let new_events = events[self.delivered..].to_vec();
+            self.delivered = events.len();
+            new_events
+        } else {
+            std::mem::take(events)
+        }
+    }
+
+    fn finish<T>(self, stats: &mut PlannerStats<T>) {
+        if !self.keep {
+            stats.events = None;
+        }
+    }
+}
+
+/// Returns a new sample, redrawing samples that cannot be on a path cheaper than the best
+/// found so far if using informed sampling.
+fn draw_sample<T, FS>(
+    sample_fn: &mut FS,
+    root: &T,
+    goal: &T,
+    progress: &PlannerProgress,
+    options: &RrtOptions,
+    stats: &mut PlannerStats<T>,
+) -> T
+where
+    T: Distance,
+    FS: FnMut() -> T,
+{
+    let cost_bound = progress.best_cost.unwrap_or(options.max_cost);
+    let mut sample = sample_fn();
+    if !options.informed_sampling || cost_bound.is_infinite() {
+        return sample;
+    }
+    for _ in 1..INFORMED_SAMPLE_ATTEMPTS {
+        if root.distance(&sample) + sample.distance(goal) <= cost_bound {
+            break;
+        }
+        stats.informed_rejections += 1;
+        sample = sample_fn();
+    }
+    sample
+}
+
+/// Keeps the first solution found, and records a solution event if recording events and
+/// the cost to reach the goal has improved on `best_cost`.
+fn record_solution<T>(
+    tree: &HashTree<T>,
+    goal: &T,
+    progress: &PlannerProgress,
+    best_cost: &mut Option<f64>,
+    stats: &mut PlannerStats<T>,
+) where
+    T: Eq + Clone + Hash + Distance,
+{
+    let iteration = progress.iteration;
+    if stats.first_solution.is_none() {
+        if let (Ok(path), Ok(cost)) = (tree.path(goal), tree.cost(goal)) {
+            stats.first_solution = Some(FirstSolution {
+                path,
+                cost,
+                iteration,
+                elapsed: progress.elapsed,
+            });
+        }
+    }
+    if stats.events.is_none() {
+        return;
+    }
+    let improved = tree
+        .cost(goal)
+        .ok()
+        .filter(|&cost| best_cost.is_none_or(|best| cost < best));
+    if let Some(cost) = improved {
+        *best_cost = Some(cost);
+        stats.record(|| PlannerEvent::Solution { iteration, cost });
+    }
+}
+
+/// Extends the tree from `nearest` towards `sample`, returning the nodes that were added.
+fn extend_step<T, FE, FC>(
+    tree: &mut HashTree<T>,
+    nearest: &T,
+    sample: T,
+    extend: &mut FE,
+    connectable: &mut FC,
+    options: &RrtOptions,
+    stats: &mut PlannerStats<T>,
+) -> Vec<T>
+where
+    T: Eq + Clone + Hash + Distance,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+{
+    let new_points = extend_from(
+        nearest,
+        sample,
+        extend,
+        &mut within_extension(connectable, options.max_extension),
+        options.use_rrtconnect.then_some(&options.connect_limit),
+    );
+
+    // Add all valid nodes to the tree
+    let size = tree.size();
+    let added = add_path(tree, nearest, new_points, options.duplicate_policy, stats);
+    if tree.size() == size {
+        stats.rejected_samples += 1;
+    }
+    added
+}
+
+/// Moves the goal and connects it to the existing tree, straight away from the previous
+/// goal or the nodes nearest to the new one depending on the goal connection strategy. The
+/// previous goal stays in the tree as an ordinary node.
+fn move_goal<T, FE, FC>(
+    tree: &mut HashTree<T>,
+    goal: &mut T,
+    new_goal: T,
+    extend: &mut FE,
+    connectable: &mut FC,
+    options: &RrtOptions,
+    stats: &mut PlannerStats<T>,
+) where
+    T: Eq + Clone + Hash + Distance,
     FE: FnMut(&T, &T) -> T,
     FC: FnMut(&T, &T) -> bool,
 {
-    // Sample the grab the nearest point, and extend in that direction
-    let nearest = tree.nearest_neighbor(&sample);
-    let mut path = Vec::new();
-
-    if connectable(&nearest, &sample) {
-        path.push(sample);
+    let previous = std::mem::replace(goal, new_goal);
+    track_nearest_to_goal(tree, goal, 0, stats);
+    if !tree.contains(goal) {
+        let latest = tree.contains(&previous).then_some(&previous);
+        connect_goal(tree, goal, latest, extend, connectable, options, stats);
     }
-    // If using connect, extend until we can extend no further or we begin
-    // moving further away from the sample.
-    else if use_connect {
-        let mut current_point = nearest;
-        let mut distance_to_sample = current_point.distance(&sample);
-        while !connectable(&current_point, &sample) {
-            let new_point = extend(&current_point, &sample);
-            let new_distance_to_sample = new_point.distance(&sample);
-            if new_distance_to_sample >= distance_to_sample
-                || !connectable(&current_point, &new_point)
-            {
-                break;
-            }
+}
 
-            path.push(new_point);
-            distance_to_sample = new_distance_to_sample;
-            current_point = path.last().unwrap();
-        }
-        if connectable(&current_point, &sample) {
-            path.push(sample);
-        }
-    } else {
-        let new_point = extend(&nearest, &sample);
-        if connectable(&nearest, &new_point) {
-            path.push(new_point);
+/// Updates the node nearest to the goal with the nodes added to the tree from index `from`,
+/// starting over if `from` is 0.
+fn track_nearest_to_goal<T>(tree: &HashTree<T>, goal: &T, from: usize, stats: &mut PlannerStats<T>)
+where
+    T: Eq + Clone + Hash + Distance,
+{
+    if from == 0 {
+        stats.nearest_to_goal = None;
+    }
+    for node in tree.iter_insertion_order().skip(from) {
+        let distance = node.distance(goal);
+        if stats
+            .nearest_to_goal
+            .as_ref()
+            .is_none_or(|(_, nearest)| distance < *nearest)
+        {
+            stats.nearest_to_goal = Some((node.clone(), distance));
         }
     }
+}
 
-    (path, nearest.clone())
+/// Accumulates the time spent in each planning phase, if enabled.
+struct PhaseClock {
+    enabled: bool,
+
+    // Total time spent validating motions, which is excluded from the other phases.
+    collisions: Cell<Duration>,
 }
 
-fn rewire_tree<T, FC>(tree: &mut HashTree<T>, connectable: &mut FC, point: &T, rewire_radius: f64)
-where
-    T: Eq + Copy + Hash + Distance,
-    FC: FnMut(&T, &T) -> bool,
-{
-    // Get a list of all nodes that are within the sample radius, and rewire if necessary
-    let neighbors = tree.nearest_neighbors(point, rewire_radius);
-    let point_cost = tree.cost(point).unwrap();
-    for (neighbor, distance) in neighbors.iter() {
-        if neighbor == point {
-            continue;
+impl PhaseClock {
+    fn new(enabled: bool) -> Self {
+        PhaseClock {
+            enabled,
+            collisions: Cell::new(Duration::ZERO),
         }
-        // If it's cheaper and valid to get to the neighbor from the new node reparent it
-        let old_cost = tree.cost(neighbor).unwrap();
-        let new_cost = distance + point_cost;
-        if new_cost < old_cost {
-            if connectable(point, neighbor) {
-                let _ = tree.set_parent(neighbor, point);
-            }
+    }
+
+    /// Runs a motion validity check, timing it if enabled.
+    fn check(&self, f: impl FnOnce() -> bool) -> bool {
+        if !self.enabled {
+            return f();
+        }
+        let start = Instant::now();
+        let valid = f();
+        self.collisions.set(self.collisions.get() + start.elapsed());
+        valid
+    }
+
+    /// Runs a phase, adding its duration less any validity checks to `phase` if enabled.
+    fn time<R>(&self, phase: &mut Duration, f: impl FnOnce() -> R) -> R {
+        if !self.enabled {
+            return f();
         }
+        let start = Instant::now();
+        let collisions = self.collisions.get();
+        let result = f();
+        *phase += start
+            .elapsed()
+            .saturating_sub(self.collisions.get().saturating_sub(collisions));
+        result
     }
 }
 
-/// Implementation of RRT planning algorithms.
+/// Plain RRT, intended for teaching and quick prototypes.
 ///
-/// Will attempt to compute a path using the specified version of RRT given the start pose
-/// and user-defined coverage functions.
+/// Grows a single tree from `start` one step at a time until the termination condition
+/// is met, and returns the lowest cost path to any node satisfying `goal_fn`. There is
+/// no rewiring, greedy extension, or goal connection logic, and the state type only needs
+/// to be [`Clone`].
 ///
 /// # Parameters
 ///
-/// - `start`: The reference to the starting pose of type `T`
+/// - `start`: The reference to the starting state of type `T`
+/// - `goal_fn`: Returns true if a state satisfies the goal
 /// - `sample_fn`: Function to randomly sample the configuration space
-/// - `extend_fn`: Given two nodes, function to return an intermediate value between them
-/// - `connectable_fn`: Function to determine whether or not a link can be added between two nodes
-/// - `use_rrtstar`: Whether or not to use RRT*
-/// - `rewire_radius`: If using RRT*, the max distance to identify and rewire neighbors of newly added nodes
-/// - `use_rrtconnect`: Whether or not to use RRT-Connect
-/// - `max_iterations`: Maximum number of random samples to attempt before the search fails
-/// - `max_duration`: Maximum amount of time in seconds to find a solution
-/// - `fast_return`: Return as soon as a solution is found, or iterate until max_iterations or max_duration is reached
-///
-/// # Returns
-/// Returns a `Result` containing either:
-/// - `Ok((Vec<T>, Tree<T>))`: A tuple of a vector of points of type `T` representing the path from the
-///                 start to a poin satisfying the `success` condition, if such a path is found within
-///                 the given number of iterations. Along with the Tree itself.
-/// - `Err(String)`: An error message in a string if the algorithm fails to find a satisfactory path.
-///
-/// # Example
+/// - `steer_fn`: Given the nearest node and a sample, returns a new state in the direction of the sample
+/// - `valid_fn`: Function to determine whether or not the motion between two states is valid
+/// - `termination`: When to stop planning, see [`crate::planning::termination`]
 ///
-/// Refer to the world example or integration tests.
+/// # Errors
 ///
-pub fn rrt<T, FS, FE, FC>(
+/// If no node satisfying the goal was found before terminating.
+pub fn rrt_simple<T, FG, FS, FE, FC, TC>(
     start: &T,
-    goal: &T,
+    mut goal_fn: FG,
     mut sample_fn: FS,
-    mut extend_fn: FE,
-    mut connectable_fn: FC,
-    use_rrtstar: bool,
-    rewire_radius: f64,
-    use_rrtconnect: bool,
-    max_iterations: u64,
-    max_duration: f64,
-    fast_return: bool,
-) -> Result<(Vec<T>, HashTree<T>), String>
+    mut steer_fn: FE,
+    mut valid_fn: FC,
+    mut termination: TC,
+) -> Result<(Vec<T>, HashTree<T>), PlanningError>
 where
-    T: Eq + Copy + Hash + Distance,
+    T: Eq + Clone + Hash + Distance,
+    FG: FnMut(&T) -> bool,
     FS: FnMut() -> T,
     FE: FnMut(&T, &T) -> T,
     FC: FnMut(&T, &T) -> bool,
+    TC: TerminationCondition,
 {
     let mut tree = HashTree::new(start.clone());
     let start_time = Instant::now();
-    let duration_limit = Duration::from_secs_f64(max_duration);
 
-    for _ in 0..max_iterations {
-        // Have we timed out?
-        if start_time.elapsed() > duration_limit {
+    let mut stop_reason = None;
+    // Track the cheapest node satisfying the goal
+    let mut best: Option<(T, f64)> = goal_fn(start).then(|| (start.clone(), 0.0));
+
+    for iteration in 0.. {
+        let progress = PlannerProgress {
+            iteration,
+            elapsed: start_time.elapsed(),
+            best_cost: best.as_ref().map(|(_, cost)| *cost),
+        };
+        if termination.should_terminate(&progress) {
+            stop_reason = termination.stop_reason(&progress);
             break;
         }
 
-        // Sample the nearest point, and extend in that direction.
-        // If we end up with no connectable nodes just try again.
-        let sample = sample_fn();
-        let (new_points, nearest) = extend_tree(
-            &tree,
-            sample,
-            &mut extend_fn,
-            &mut connectable_fn,
-            use_rrtconnect,
-        );
-        if new_points.is_empty() {
+        let (new_points, nearest) =
+            extend_tree(&tree, sample_fn(), &mut steer_fn, &mut valid_fn, None);
+        let Some(node) = new_points.into_iter().next() else {
+            continue;
+        };
+        if tree.add_child(&nearest, node.clone()).is_err() || !goal_fn(&node) {
             continue;
         }
 
-        // Add all valid nodes to the tree
-        let mut parent = &nearest;
-        for node in &new_points {
-            let _ = tree.add_child(parent, *node);
-            parent = &node;
-        }
-
-        // Rewire the tree if using RRT*
-        if use_rrtstar {
-            for node in &new_points {
-                rewire_tree(&mut tree, &mut connectable_fn, &node, rewire_radius);
-            }
-        }
-
-        // If we have reached the goal ensure the link is added to the tree.
-        if connectable_fn(goal, new_points.last().unwrap()) {
-            let _ = tree.add_child(new_points.last().unwrap(), *goal);
-
-            // Then we're done.
-            if fast_return {
-                break;
-            }
+        let cost = tree.cost(&node)?;
+        if best.as_ref().is_none_or(|(_, best_cost)| cost < *best_cost) {
+            best = Some((node, cost));
         }
     }
 
-    match tree.path(goal) {
-        Ok(path) => return Ok((path, tree)),
-        Err(_) => return Err("Failed to find path between poses".into()),
+    match best {
+        Some((node, _)) => Ok((tree.path(&node)?, tree)),
+        None => Err(PlanningError::not_found(stop_reason)),
     }
 }
 
@@ -213,10 +1985,27 @@ where
 
 #[cfg(test)]
 mod tests {
+    use float_cmp::approx_eq;
 
-    use crate::{planning::rrt::rewire_tree, tree::HashTree};
+    use crate::clock::ManualClock;
+    use crate::error::{PlanningError, TreeError};
+    use crate::planning::replay::PlannerEvent;
+    use crate::planning::rrt::rewire_tree;
+    use crate::planning::stats::{GoalRejectionReason, PhaseTimings, PlannerStats, StopReason};
+    use crate::planning::termination::{CancellationToken, MaxDuration, MaxIterations};
+    use crate::planning::tuning::SplitMix64;
+    use crate::tree::{DedupGrid, EdgeCost, HashTree, TieBreak};
+    use std::collections::HashMap;
+    use std::time::Duration;
 
-    use super::extend_tree;
+    use super::{
+        add_path, choose_parent, connect_goal, extend_tree, grow_tree, plan,
+        plan_with_cancellation, rrt_simple, rrt_with_clock, rrt_with_edge_cost, rrt_with_selection,
+        rrt_with_termination, rrt_with_tree, select_node, within_extension, ConnectLimit,
+        ConnectionDirection, DuplicatePolicy, GoalCandidates, GoalConnection, NodeSelection,
+        RrtOptions,
+    };
+    use crate::planning::radius::AdaptiveRadius;
 
     #[test]
     fn test_rewire_tree() {
@@ -228,15 +2017,84 @@ mod tests {
 
         assert_eq!(tree.get_parent(&4).unwrap(), &2);
         assert_eq!(tree.get_parent(&1).unwrap(), &4);
-        assert_eq!(tree.cost(&1).unwrap(), 5.0);
+        assert!(approx_eq!(f64, tree.cost(&1).unwrap(), 5.0));
 
         // When we rewire at 2, 1 should be reparented
         // 2 -> 1
         //   -> 4
-        rewire_tree(&mut tree, &mut is_valid_fn, &2, 5.0);
+        let mut stats = PlannerStats::new();
+        rewire_tree(&mut tree, &mut is_valid_fn, &2, 5.0, &mut stats);
+        assert!(stats.tree_errors.is_empty());
         assert_eq!(tree.get_parent(&4).unwrap(), &2);
         assert_eq!(tree.get_parent(&1).unwrap(), &2);
-        assert_eq!(tree.cost(&1).unwrap(), 1.0);
+        assert!(approx_eq!(f64, tree.cost(&1).unwrap(), 1.0));
+    }
+
+    #[test]
+    fn test_choose_parent() {
+        // Chain added the long way round: 0 -> 5 -> 2 -> 1
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert!(tree.add_child(&0, 5).is_ok());
+        assert!(tree.add_child(&5, 2).is_ok());
+        assert!(tree.add_child(&2, 1).is_ok());
+        let mut stats = PlannerStats::new();
+
+        // 0 cannot reach 2 directly, and 1 is its own descendant
+        let mut not_from_root = |from: &i32, _: &i32| *from != 0;
+        assert!(!choose_parent(
+            &mut tree,
+            &mut not_from_root,
+            &2,
+            10.0,
+            &mut stats
+        ));
+        assert_eq!(tree.get_parent(&2), Some(&5));
+
+        // Otherwise 2 is wired through the root, and its child's cost follows
+        let mut valid = |_: &i32, _: &i32| true;
+        assert!(choose_parent(&mut tree, &mut valid, &2, 10.0, &mut stats));
+        assert!(!choose_parent(
+            &mut tree,
+            &mut not_from_root,
+            &1,
+            10.0,
+            &mut stats
+        ));
+        assert_eq!(tree.get_parent(&2), Some(&0));
+        assert_eq!(tree.get_parent(&1), Some(&2));
+        assert!(approx_eq!(f64, tree.cost(&2).unwrap(), 2.0));
+        assert!(approx_eq!(f64, tree.cost(&1).unwrap(), 3.0));
+
+        // Neighbors outside the radius are not considered, and the root has no parent
+        assert!(tree.add_child(&5, 4).is_ok());
+        assert!(!choose_parent(&mut tree, &mut valid, &4, 1.0, &mut stats));
+        assert!(!choose_parent(&mut tree, &mut valid, &0, 10.0, &mut stats));
+        assert!(stats.tree_errors.is_empty());
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_rewire_tree_skips_ancestors() {
+        // Descending pays back double, so reaching 5 through its own descendant 2 looks
+        // cheaper than reaching it directly
+        let descent = |from: &i32, to: &i32| f64::from(if to < from { 2 } else { 1 } * (to - from));
+        let mut tree = HashTree::with_edge_cost(0, EdgeCost::new(descent));
+        assert!(tree.add_child(&0, 5).is_ok());
+        assert!(tree.add_child(&5, 2).is_ok());
+        assert!(tree.cost(&2).unwrap() + descent(&2, &5) < tree.cost(&5).unwrap());
+
+        let mut stats = PlannerStats::new();
+        let rewired = rewire_tree(
+            &mut tree,
+            &mut |_: &i32, _: &i32| true,
+            &2,
+            10.0,
+            &mut stats,
+        );
+        assert_eq!(rewired, 0);
+        assert!(stats.tree_errors.is_empty());
+        assert_eq!(tree.get_parent(&5), Some(&0));
+        assert!(tree.check_invariants().is_ok());
     }
 
     #[test]
@@ -247,23 +2105,1011 @@ mod tests {
 
         // The sample is right next to the nearest node, so it should connect directly
         let (new_points, nearest) =
-            extend_tree(&tree, 2, &mut extend_fn, &mut connectable_fn, false);
+            extend_tree(&tree, 2, &mut extend_fn, &mut connectable_fn, None);
         let nearest_path = vec![2];
         assert_eq!(nearest, 1);
         assert_eq!(new_points, nearest_path);
 
         // Extend the path by exactly 1
         let (new_points, nearest) =
-            extend_tree(&tree, 3, &mut extend_fn, &mut connectable_fn, false);
+            extend_tree(&tree, 3, &mut extend_fn, &mut connectable_fn, None);
         let nearest_path = vec![2];
         assert_eq!(nearest, 1);
         assert_eq!(new_points, nearest_path);
 
         // Connect all the way to the sample
-        let (new_points, nearest) =
-            extend_tree(&tree, 5, &mut extend_fn, &mut connectable_fn, true);
+        let (new_points, nearest) = extend_tree(
+            &tree,
+            5,
+            &mut extend_fn,
+            &mut connectable_fn,
+            Some(&ConnectLimit::default()),
+        );
         let nearest_path = vec![2, 3, 4, 5];
         assert_eq!(nearest, 1);
         assert_eq!(new_points, nearest_path);
+
+        // Limit the number of nodes or total length of the extension
+        let limit = ConnectLimit {
+            max_nodes: 2,
+            ..ConnectLimit::default()
+        };
+        let (new_points, _) =
+            extend_tree(&tree, 5, &mut extend_fn, &mut connectable_fn, Some(&limit));
+        assert_eq!(new_points, vec![2, 3]);
+
+        let limit = ConnectLimit {
+            max_length: 2.5,
+            ..ConnectLimit::default()
+        };
+        let (new_points, _) =
+            extend_tree(&tree, 5, &mut extend_fn, &mut connectable_fn, Some(&limit));
+        assert_eq!(new_points, vec![2, 3]);
+
+        // The sample itself counts towards the limit
+        let limit = ConnectLimit {
+            max_nodes: 3,
+            max_length: 3.0,
+        };
+        let (new_points, _) =
+            extend_tree(&tree, 4, &mut extend_fn, &mut connectable_fn, Some(&limit));
+        assert_eq!(new_points, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_add_path_duplicates() {
+        // Tree is: 0 -> 1 -> 2
+        let new_tree = || {
+            let mut tree: HashTree<i32> = HashTree::new(0);
+            assert!(tree.add_child(&0, 1).is_ok());
+            assert!(tree.add_child(&1, 2).is_ok());
+            tree
+        };
+
+        // Ignored duplicates are extended from
+        let mut tree = new_tree();
+        let mut stats = PlannerStats::new();
+        let added = add_path(
+            &mut tree,
+            &0,
+            vec![2, 5],
+            DuplicatePolicy::Ignore,
+            &mut stats,
+        );
+        assert_eq!(added, vec![2, 5]);
+        assert_eq!(tree.get_parent(&5), Some(&2));
+        assert_eq!((stats.duplicate_states, stats.discarded_states), (1, 0));
+
+        // Rejected duplicates end the extension
+        let mut tree = new_tree();
+        let mut stats = PlannerStats::new();
+        let path = vec![4, 2, 5, 6];
+        let added = add_path(&mut tree, &0, path, DuplicatePolicy::Reject, &mut stats);
+        assert_eq!(added, vec![4]);
+        assert!(!tree.contains(&5));
+        assert_eq!((stats.duplicate_states, stats.discarded_states), (1, 2));
+
+        // Nearby states are merged into the existing node
+        let mut tree = new_tree();
+        let mut stats = PlannerStats::new();
+        let policy = DuplicatePolicy::Within(1.0);
+        let added = add_path(&mut tree, &0, vec![3, 5], policy, &mut stats);
+        assert_eq!(added, vec![2, 5]);
+        assert!(!tree.contains(&3));
+        assert_eq!(tree.path(&5).unwrap(), vec![0, 1, 2, 5]);
+        assert_eq!(stats.duplicate_states, 1);
+    }
+
+    #[test]
+    fn test_rrt_with_tree_dedup() {
+        // Cells are 3 wide, so most samples are near duplicates of existing nodes, including
+        // the goal which must be added anyway
+        let mut tree = HashTree::new(0);
+        tree.set_dedup_grid(Some(DedupGrid::new(|x: &i32| vec![f64::from(*x)], 3.0)));
+        let mut samples = (1..=10).cycle();
+        let options = RrtOptions {
+            max_extension: 3.0,
+            ..RrtOptions::default()
+        };
+        let mut stats = PlannerStats::new();
+        let path = rrt_with_tree(
+            &mut tree,
+            &10,
+            || samples.next().unwrap(),
+            |from: &i32, to: &i32| from + (to - from).clamp(-3, 3),
+            |_: &i32, _: &i32| true,
+            &options,
+            MaxIterations(100),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(path, vec![0, 2, 5, 8, 10]);
+        assert_eq!(tree.size(), 5);
+        assert!(stats.duplicate_states > 0);
+        assert!(stats.tree_errors.is_empty());
+        assert!(tree.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_nearest_to_goal() {
+        // The goal is behind a wall at 5, so the closest the tree gets is 4
+        let mut samples = [2, 4, 9, 3].into_iter().cycle();
+        let options = RrtOptions {
+            max_extension: 2.0,
+            ..RrtOptions::default()
+        };
+        let mut stats = PlannerStats::new();
+        let result = rrt_with_termination(
+            &0,
+            &9,
+            || samples.next().unwrap(),
+            |from: &i32, to: &i32| from + (to - from).clamp(-2, 2),
+            |_: &i32, to: &i32| *to < 5,
+            &options,
+            MaxIterations(20),
+            &mut stats,
+        );
+        assert!(result.is_err());
+        assert_eq!(stats.nearest_to_goal, Some((4, 5.0)));
+    }
+
+    #[test]
+    fn test_max_cost() {
+        use crate::float::FloatArray;
+
+        // Reaching the goal over (1, 1) costs 2.83, over (0.5, -0.5) only 2.29
+        let run = |max_cost: f64, stats: &mut PlannerStats<FloatArray<2>>| {
+            let mut samples = [FloatArray([1.0, 1.0]), FloatArray([0.5, -0.5])].into_iter();
+            let options = RrtOptions {
+                max_extension: 2.0,
+                max_cost,
+                ..RrtOptions::default()
+            };
+            rrt_with_termination(
+                &FloatArray([0.0, 0.0]),
+                &FloatArray([2.0, 0.0]),
+                || samples.next().unwrap(),
+                |_: &FloatArray<2>, to: &FloatArray<2>| *to,
+                |_: &FloatArray<2>, _: &FloatArray<2>| true,
+                &options,
+                MaxIterations(2),
+                stats,
+            )
+        };
+
+        let mut stats = PlannerStats::new();
+        let (path, _) = run(2.5, &mut stats).unwrap();
+        assert_eq!(path[1], FloatArray([0.5, -0.5]));
+        assert_eq!(stats.cost_rejections, 1);
+        assert_eq!(stats.first_solution.unwrap().iteration, 1);
+
+        let mut stats = PlannerStats::new();
+        let error = run(2.0, &mut stats).unwrap_err();
+        assert_eq!(
+            error,
+            PlanningError::NoSolutionWithinCost {
+                max_cost: 2.0,
+                best_cost: None
+            }
+        );
+        assert_eq!(stats.cost_rejections, 2);
+        assert!(run(f64::INFINITY, &mut PlannerStats::new()).is_ok());
+    }
+
+    #[test]
+    fn test_first_solution() {
+        use crate::float::FloatArray;
+
+        // The goal is first reached over (1, 1), then rewired through (0.5, -0.5)
+        let mut samples = [FloatArray([1.0, 1.0]), FloatArray([0.5, -0.5])].into_iter();
+        let options = RrtOptions {
+            max_extension: 2.0,
+            use_rrtstar: true,
+            rewire_radius: 3.0,
+            ..RrtOptions::default()
+        };
+        let mut stats = PlannerStats::new();
+        let (path, tree) = rrt_with_clock(
+            &FloatArray([0.0, 0.0]),
+            &FloatArray([2.0, 0.0]),
+            || samples.next().unwrap(),
+            |_: &FloatArray<2>, to: &FloatArray<2>| *to,
+            |_: &FloatArray<2>, _: &FloatArray<2>| true,
+            &options,
+            MaxIterations(2),
+            &ManualClock::with_step(Duration::from_secs(1)),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(path[1], FloatArray([0.5, -0.5]));
+
+        let first = stats.first_solution.unwrap();
+        assert_eq!(first.path[1], FloatArray([1.0, 1.0]));
+        assert_eq!(first.iteration, 0);
+        assert_eq!(first.elapsed, Duration::ZERO);
+        assert!(approx_eq!(f64, first.cost, 2.0 * 2f64.sqrt()));
+        assert!(tree.cost(&FloatArray([2.0, 0.0])).unwrap() < first.cost);
+    }
+
+    #[test]
+    fn test_add_path_tree_errors() {
+        // The parent isn't in the tree, so nothing can be added
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        let mut stats = PlannerStats::new();
+        let added = add_path(
+            &mut tree,
+            &9,
+            vec![1, 2],
+            DuplicatePolicy::Ignore,
+            &mut stats,
+        );
+        assert!(added.is_empty());
+        assert_eq!(tree.size(), 1);
+        assert_eq!(stats.tree_errors, vec![TreeError::ParentNotFound]);
+        assert_eq!(stats.discarded_states, 1);
+    }
+
+    #[test]
+    fn test_grow_tree_duplicate_samples() {
+        // Every sample is drawn twice, the second time it is already in the tree
+        let mut samples = [1, 1, 2, 2, 3, 3].into_iter();
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        let mut stats = PlannerStats::new();
+        grow_tree(
+            &mut tree,
+            &10,
+            || samples.next().unwrap(),
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &RrtOptions::default(),
+            MaxIterations(6),
+            &mut stats,
+        );
+        assert_eq!(tree.path(&3).unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(stats.duplicate_states, 3);
+        assert!(stats.tree_errors.is_empty());
+    }
+
+    #[test]
+    fn test_grow_tree_tie_break() {
+        // Tree is: 5 -> 1
+        //            -> 3
+        // Both leaves are 1 away from the sample at 2, and 3 is cheaper to reach
+        let run = |tie_break: TieBreak| {
+            let mut tree: HashTree<i32> = HashTree::new(5);
+            assert!(tree.add_child(&5, 1).is_ok());
+            assert!(tree.add_child(&5, 3).is_ok());
+            let options = RrtOptions {
+                tie_break,
+                ..RrtOptions::default()
+            };
+            grow_tree(
+                &mut tree,
+                &10,
+                || 2,
+                |from: &i32, to: &i32| from + (to - from).signum(),
+                |from: &i32, to: &i32| (to - from).abs() <= 1,
+                &options,
+                MaxIterations(1),
+                &mut PlannerStats::new(),
+            );
+            *tree.get_parent(&2).unwrap()
+        };
+        assert_eq!(run(TieBreak::InsertionOrder), 1);
+        assert_eq!(run(TieBreak::LowestCost), 3);
+    }
+
+    #[test]
+    fn test_grow_tree_provenance() {
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        let mut samples = [-1, 1, 2].into_iter();
+        let options = RrtOptions {
+            max_extension: 1.0,
+            record_provenance: true,
+            ..RrtOptions::default()
+        };
+        grow_tree(
+            &mut tree,
+            &10,
+            || samples.next().unwrap(),
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &options,
+            MaxIterations(3),
+            &mut PlannerStats::new(),
+        );
+
+        let iteration = |node: &i32| tree.provenance(node).map(|p| p.iteration);
+        assert_eq!(iteration(&0), None);
+        assert_eq!(iteration(&-1), Some(0));
+        assert_eq!(iteration(&1), Some(1));
+        assert_eq!(iteration(&2), Some(2));
+        assert!(tree.provenance(&1).unwrap().elapsed <= tree.provenance(&2).unwrap().elapsed);
+
+        // Nodes added outside of planning are not stamped
+        assert!(tree.add_child(&2, 3).is_ok());
+        assert_eq!(tree.provenance(&3), None);
+    }
+
+    #[test]
+    fn test_grow_tree_start_tolerance() {
+        // The start has drifted into an invalid state, but 1 is within the start region
+        let run = |start_tolerance: f64| {
+            let mut tree: HashTree<i32> = HashTree::new(0);
+            let options = RrtOptions {
+                max_extension: 1.0,
+                start_tolerance,
+                ..RrtOptions::default()
+            };
+            grow_tree(
+                &mut tree,
+                &3,
+                || 3,
+                |from: &i32, to: &i32| from + (to - from).signum(),
+                |from: &i32, to: &i32| *from != 0 && (to - from).abs() <= 1,
+                &options,
+                MaxIterations(5),
+                &mut PlannerStats::new(),
+            );
+            tree.path(&3).ok()
+        };
+        assert_eq!(run(0.0), None);
+        assert_eq!(run(1.0), Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn test_grow_tree_timings() {
+        let run = |record_timings: bool| {
+            let mut tree: HashTree<i32> = HashTree::new(0);
+            let mut stats = PlannerStats::new();
+            let options = RrtOptions {
+                max_extension: 1.0,
+                use_rrtstar: true,
+                rewire_radius: 2.0,
+                record_timings,
+                ..RrtOptions::default()
+            };
+            grow_tree(
+                &mut tree,
+                &20,
+                || 10,
+                |from: &i32, to: &i32| from + (to - from).signum(),
+                |from: &i32, to: &i32| {
+                    std::thread::sleep(Duration::from_millis(1));
+                    (to - from).abs() <= 1
+                },
+                &options,
+                MaxIterations(3),
+                &mut stats,
+            );
+            stats.timings
+        };
+        assert_eq!(run(false), PhaseTimings::default());
+
+        // Collision checks dominate and are not counted towards the phases that made them
+        let timings = run(true);
+        assert!(timings.collision_checks >= Duration::from_millis(3));
+        assert!(timings.extension < timings.collision_checks);
+        assert!(timings.rewiring < timings.collision_checks);
+        assert!(timings.goal_checks < timings.collision_checks);
+    }
+
+    #[test]
+    fn test_select_node() {
+        // Tree is: 0 -> 1 -> 2
+        //            -> 10
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert!(tree.add_child(&0, 1).is_ok());
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert!(tree.add_child(&0, 10).is_ok());
+        let mut visits = HashMap::new();
+
+        assert_eq!(
+            select_node(&tree, &3, NodeSelection::Nearest, &mut visits),
+            2
+        );
+        assert!(visits.is_empty());
+
+        // Visits spread across the nearest candidates
+        let least_visited = NodeSelection::LeastVisited { candidates: 2 };
+        assert_eq!(select_node(&tree, &3, least_visited, &mut visits), 2);
+        assert_eq!(select_node(&tree, &3, least_visited, &mut visits), 1);
+        assert_eq!(select_node(&tree, &3, least_visited, &mut visits), 2);
+        assert_eq!(visits[&2], 2);
+        assert_eq!(visits[&1], 1);
+
+        // 10 is the sparsest, but only when it is a candidate
+        let lowest_density = |candidates| NodeSelection::LowestDensity {
+            candidates,
+            radius: 2.0,
+        };
+        assert_eq!(select_node(&tree, &3, lowest_density(3), &mut visits), 2);
+        assert_eq!(select_node(&tree, &3, lowest_density(4), &mut visits), 10);
+    }
+
+    #[test]
+    fn test_rrt_with_selection() {
+        // Always extending from the start never gets further than one step
+        let options = RrtOptions {
+            max_extension: 1.0,
+            ..RrtOptions::default()
+        };
+        let result = rrt_with_selection(
+            &0,
+            &3,
+            || 3,
+            |_: &HashTree<i32>, _: &i32| 0,
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &options,
+            MaxIterations(3),
+            &mut PlannerStats::new(),
+        );
+        assert!(result.is_err());
+
+        let (path, _) = rrt_with_selection(
+            &0,
+            &3,
+            || 3,
+            |tree: &HashTree<i32>, sample: &i32| *tree.nearest_neighbor(sample),
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &options,
+            MaxIterations(3),
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+        assert_eq!(path, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_rrt_with_edge_cost() {
+        // Energy grows with the square of the step, so two short hops beat one long one
+        let edge_cost = EdgeCost::new(|from: &i32, to: &i32| f64::from((to - from).pow(2)));
+        let options = RrtOptions {
+            use_rrtstar: true,
+            rewire_radius: 10.0,
+            ..RrtOptions::default()
+        };
+        let mut samples = [4, 2].into_iter();
+        let (path, tree) = rrt_with_edge_cost(
+            &0,
+            &4,
+            || samples.next().unwrap(),
+            |_: &i32, to: &i32| *to,
+            |_: &i32, _: &i32| true,
+            edge_cost,
+            &options,
+            MaxIterations(2),
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+        assert_eq!(path, vec![0, 2, 4]);
+        assert!(approx_eq!(f64, tree.cost(&4).unwrap(), 8.0));
+    }
+
+    #[test]
+    fn test_connect_goal_repeated() {
+        let mut tree: HashTree<i32> = HashTree::new(1);
+        assert!(tree.add_child(&1, 2).is_ok());
+        let mut extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let mut connectable_fn = |from: &i32, to: &i32| (to - from).abs() <= 1;
+        let options = RrtOptions {
+            max_extension: 1.0,
+            ..RrtOptions::default()
+        };
+
+        // Connecting to a goal that is already in the tree keeps the existing node
+        let mut stats = PlannerStats::new();
+        for _ in 0..2 {
+            assert!(connect_goal(
+                &mut tree,
+                &3,
+                Some(&2),
+                &mut extend_fn,
+                &mut connectable_fn,
+                &options,
+                &mut stats
+            ));
+        }
+        assert_eq!(tree.size(), 3);
+        assert_eq!(stats.duplicate_states, 1);
+        assert!(stats.tree_errors.is_empty());
+    }
+
+    #[test]
+    fn test_connect_goal() {
+        // Tree is: 1 -> 2 -> 3
+        //            -> 6
+        let mut tree: HashTree<i32> = HashTree::new(1);
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert!(tree.add_child(&2, 3).is_ok());
+        assert!(tree.add_child(&1, 6).is_ok());
+        let mut extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+
+        // Only allow edges moving in the positive direction
+        let mut connectable_fn = |from: &i32, to: &i32| to > from;
+        let mut options = RrtOptions {
+            max_extension: 1.0,
+            record_goal_rejections: true,
+            ..RrtOptions::default()
+        };
+        let mut stats = PlannerStats::new();
+
+        // The latest node is not adjacent to the goal
+        assert!(!connect_goal(
+            &mut tree,
+            &7,
+            Some(&3),
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut stats
+        ));
+
+        // The default direction queries connectable(goal, node), so the asymmetric
+        // connectable function fails even though 6 -> 7 is valid.
+        assert!(!connect_goal(
+            &mut tree,
+            &7,
+            Some(&6),
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut stats
+        ));
+
+        // Both failures are recorded
+        let reasons: Vec<_> = stats
+            .goal_rejections
+            .iter()
+            .map(|r| (r.state, r.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (3, GoalRejectionReason::OutOfReach),
+                (6, GoalRejectionReason::InvalidMotion)
+            ]
+        );
+        assert!(approx_eq!(f64, stats.goal_rejections[0].distance, 4.0));
+
+        options.goal_connection.direction = ConnectionDirection::TreeToGoal;
+        assert!(connect_goal(
+            &mut tree,
+            &7,
+            Some(&6),
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut stats
+        ));
+        assert_eq!(tree.path(&7).unwrap(), vec![1, 6, 7]);
+        assert_eq!(stats.goal_rejections.len(), 2);
+    }
+
+    #[test]
+    fn test_connect_goal_distance_gate() {
+        let mut tree: HashTree<i32> = HashTree::new(1);
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert!(tree.add_child(&2, 3).is_ok());
+        let mut extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let mut connectable_calls = 0;
+        let mut connectable_fn = |_: &i32, _: &i32| {
+            connectable_calls += 1;
+            true
+        };
+        let options = RrtOptions {
+            goal_connection: GoalConnection {
+                candidates: GoalCandidates::Nearest(3),
+                max_distance: 3.5,
+                ..GoalConnection::default()
+            },
+            ..RrtOptions::default()
+        };
+        let mut stats = PlannerStats::new();
+
+        // Only 3 is close enough to the goal to be worth checking
+        assert!(connect_goal(
+            &mut tree,
+            &6,
+            None,
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut stats
+        ));
+        assert_eq!(tree.path(&6).unwrap(), vec![1, 2, 3, 6]);
+        assert_eq!(stats.goal_attempts, 1);
+        assert_eq!(stats.goal_skips, 0);
+
+        // Nothing is checked once every candidate is too far
+        assert!(!connect_goal(
+            &mut tree,
+            &-5,
+            None,
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut stats
+        ));
+        assert_eq!(stats.goal_attempts, 1);
+        assert_eq!(stats.goal_skips, 3);
+        assert_eq!(connectable_calls, 1);
+    }
+
+    #[test]
+    fn test_connect_goal_nearest_with_connect() {
+        let mut tree: HashTree<i32> = HashTree::new(1);
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert!(tree.add_child(&2, 3).is_ok());
+        let mut extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let mut connectable_fn = |from: &i32, to: &i32| to > from;
+
+        // Without a latest node there's nothing to try
+        let mut options = RrtOptions {
+            max_extension: 1.0,
+            goal_connection: GoalConnection {
+                direction: ConnectionDirection::TreeToGoal,
+                ..GoalConnection::default()
+            },
+            ..RrtOptions::default()
+        };
+        assert!(!connect_goal(
+            &mut tree,
+            &6,
+            None,
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut PlannerStats::new()
+        ));
+
+        // The nearest node is 3, which can't reach 6 directly
+        options.goal_connection.candidates = GoalCandidates::Nearest(2);
+        assert!(!connect_goal(
+            &mut tree,
+            &6,
+            None,
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut PlannerStats::new()
+        ));
+
+        // But it can by extending towards the goal
+        options.goal_connection.use_connect = true;
+        assert!(connect_goal(
+            &mut tree,
+            &6,
+            None,
+            &mut extend_fn,
+            &mut connectable_fn,
+            &options,
+            &mut PlannerStats::new()
+        ));
+        assert_eq!(tree.path(&6).unwrap(), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_extend_tree_max_extension() {
+        let tree: HashTree<i32> = HashTree::new(1);
+
+        // Steer by 2, but only allow motions of length 1
+        let mut extend_fn = |from: &i32, to: &i32| from + 2 * (to - from).signum();
+        let mut connectable_fn = |_: &i32, _: &i32| true;
+        let mut reachable = within_extension(&mut connectable_fn, 1.0);
+
+        // The sample is adjacent so it is added directly
+        let (new_points, _) = extend_tree(&tree, 2, &mut extend_fn, &mut reachable, None);
+        assert_eq!(new_points, vec![2]);
+
+        // The sample and the steered point are both too far away
+        let (new_points, _) = extend_tree(&tree, 5, &mut extend_fn, &mut reachable, None);
+        assert!(new_points.is_empty());
+        let (new_points, _) = extend_tree(
+            &tree,
+            5,
+            &mut extend_fn,
+            &mut reachable,
+            Some(&ConnectLimit::default()),
+        );
+        assert!(new_points.is_empty());
+    }
+
+    #[test]
+    fn test_rrt_simple() {
+        // Walk up the number line in steps of 1 to anything above 5
+        let mut samples = [3, -2, 8, 10, 10].into_iter().cycle();
+        let sample_fn = || samples.next().unwrap();
+        let steer_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let valid_fn = |from: &i32, to: &i32| (to - from).abs() == 1;
+        let goal_fn = |node: &i32| *node > 5;
+
+        let result = rrt_simple(
+            &0,
+            goal_fn,
+            sample_fn,
+            steer_fn,
+            valid_fn,
+            MaxIterations(100),
+        );
+        let (path, tree) = result.unwrap();
+        assert_eq!(path, vec![0, 1, 2, 3, 4, 5, 6]);
+        assert!(tree.size() >= path.len());
+
+        // Unreachable goals fail once terminated
+        let result = rrt_simple(
+            &0,
+            |node: &i32| *node > 100,
+            || 10,
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |_: &i32, _: &i32| true,
+            MaxIterations(100),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_plan() {
+        let options = RrtOptions::default()
+            .with_max_extension(1.0)
+            .with_rrtstar(1.5)
+            .with_max_iterations(50);
+        assert!(options.use_rrtstar && !options.use_rrtconnect);
+        assert!(options.fast_return);
+
+        // Returns with the first path found
+        let mut rng = SplitMix64(5);
+        #[allow(clippy::cast_possible_truncation)]
+        let mut sample = || (rng.next_f64() * 10.0).round() as i32 - 5;
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let (path, tree) = plan(
+            &0,
+            &3,
+            &mut sample,
+            extend,
+            |_: &i32, _: &i32| true,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(path, vec![0, 1, 2, 3]);
+        assert!(tree.size() < 11);
+
+        // Or keeps growing the tree until the iteration limit
+        let options = options.with_fast_return(false).with_max_iterations(500);
+        let (path, tree) = plan(
+            &0,
+            &3,
+            &mut sample,
+            extend,
+            |_: &i32, _: &i32| true,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(path, vec![0, 1, 2, 3]);
+        assert_eq!(tree.size(), 11);
+
+        // The timeout applies even if the iteration limit is not reached
+        let options = options
+            .with_max_iterations(u64::MAX)
+            .with_timeout(Duration::from_millis(10));
+        let result = plan(
+            &0,
+            &9,
+            &mut sample,
+            extend,
+            |_: &i32, _: &i32| true,
+            &options,
+        );
+        assert_eq!(result.unwrap_err(), PlanningError::Timeout);
+    }
+
+    #[test]
+    fn test_plan_cancellation() {
+        let extend = |from: &i32, to: &i32| from + (to - from).signum();
+        let options = RrtOptions::default()
+            .with_max_extension(1.0)
+            .with_fast_return(false);
+
+        // A path found before cancelling is still returned
+        let cancellation = CancellationToken::new();
+        let stop = cancellation.clone();
+        let mut samples = 0;
+        let result = plan_with_cancellation(
+            &0,
+            &3,
+            || {
+                samples += 1;
+                if samples == 5 {
+                    stop.cancel();
+                }
+                3
+            },
+            extend,
+            |_: &i32, _: &i32| true,
+            &options,
+            &cancellation,
+        );
+        assert_eq!(result.path, Ok(vec![0, 1, 2, 3]));
+        assert_eq!(result.stats.stop_reason, StopReason::Cancelled);
+        assert_eq!(result.stats.iterations, 5);
+
+        // Every entry point checks the token in the options
+        let options = options.with_cancellation(cancellation);
+        let mut stats = PlannerStats::new();
+        let result = rrt_with_termination(
+            &0,
+            &3,
+            || 3,
+            extend,
+            |_: &i32, _: &i32| true,
+            &options,
+            MaxIterations(100),
+            &mut stats,
+        );
+        assert_eq!(result.unwrap_err(), PlanningError::Cancelled);
+        assert_eq!(stats.iterations, 0);
+        assert_eq!(stats.stop_reason, Some(StopReason::Cancelled));
+    }
+
+    #[test]
+    fn test_informed_sampling() {
+        let run = |informed_sampling: bool| {
+            let mut rng = SplitMix64(21);
+            #[allow(clippy::cast_possible_truncation)]
+            let sample_fn = move || (rng.next_f64() * 40.0) as i32 - 20;
+            let options = RrtOptions {
+                max_extension: 1.0,
+                use_rrtstar: true,
+                rewire_radius: 1.5,
+                record_events: true,
+                informed_sampling,
+                ..RrtOptions::default()
+            };
+            let mut stats = PlannerStats::new();
+            let result = rrt_with_termination(
+                &0,
+                &5,
+                sample_fn,
+                |from: &i32, to: &i32| from + (to - from).signum(),
+                |_: &i32, _: &i32| true,
+                &options,
+                MaxIterations(200),
+                &mut stats,
+            );
+            assert!(result.is_ok());
+            stats
+        };
+        let samples_after_solution = |stats: &PlannerStats<i32>| -> Vec<i32> {
+            let found = stats.first_solution.as_ref().unwrap().iteration;
+            let events = stats.events.as_ref().unwrap();
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    PlannerEvent::Sample { iteration, state } if *iteration > found => Some(*state),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Once the goal is reached only states between the start and goal can improve on
+        // the path
+        let stats = run(true);
+        assert!(stats.informed_rejections > 0);
+        let samples = samples_after_solution(&stats);
+        assert!(!samples.is_empty());
+        assert!(samples.iter().all(|sample| (0..=5).contains(sample)));
+
+        let stats = run(false);
+        assert_eq!(stats.informed_rejections, 0);
+        assert!(samples_after_solution(&stats)
+            .iter()
+            .any(|sample| *sample < 0));
+    }
+
+    #[test]
+    fn test_rrt_options_info() {
+        let options = RrtOptions {
+            max_extension: 1.0,
+            use_rrtstar: true,
+            rewire_radius: 2.5,
+            ..RrtOptions::default()
+        };
+        let info = options.info();
+        assert_eq!(info.name, "RRT*");
+        assert_eq!(info.parameter("max_extension"), Some("1"));
+        assert_eq!(info.parameter("rewire_radius"), Some("2.5"));
+        assert!(info.parameter("gamma").is_none());
+        assert!((options.rewire_radius_at(1000) - 2.5).abs() < 1e-12);
+
+        // The adaptive radius shrinks as the tree grows, up to the fixed radius
+        let options = RrtOptions {
+            adaptive_radius: Some(AdaptiveRadius::new(2, 100.0)),
+            ..options
+        };
+        assert!(options.info().parameter("gamma").is_some());
+        assert!((options.rewire_radius_at(1) - 2.5).abs() < 1e-12);
+        assert!(options.rewire_radius_at(10_000) < options.rewire_radius_at(1000));
+        assert!(options.rewire_radius_at(1000) < 2.5);
+
+        // Rewiring parameters are only relevant to RRT*
+        let options = RrtOptions {
+            use_rrtconnect: true,
+            ..RrtOptions::default()
+        };
+        let info = options.info();
+        assert_eq!(info.name, "RRT-Connect");
+        assert_eq!(info.parameter("max_extension"), Some("inf"));
+        assert_eq!(info.parameter("rewire_radius"), None);
+    }
+
+    // Runs 20 iterations of RRT* with a seeded sampler and a clock advancing 10ms per
+    // iteration, returning every edge in the order the nodes were added
+    fn golden_run(seed: u64) -> Vec<(Option<i32>, i32)> {
+        let mut rng = SplitMix64(seed);
+        #[allow(clippy::cast_possible_truncation)]
+        let sample_fn = || (rng.next_f64() * 30.0) as i32 - 10;
+        let extend_fn = |from: &i32, to: &i32| from + (to - from).clamp(-4, 4);
+        // Nothing may stop at 3
+        let connectable_fn = |from: &i32, to: &i32| *from != 3 && *to != 3;
+        let options = RrtOptions {
+            max_extension: 4.0,
+            use_rrtstar: true,
+            rewire_radius: 4.0,
+            record_provenance: true,
+            ..RrtOptions::default()
+        };
+
+        let clock = ManualClock::with_step(Duration::from_millis(10));
+        let (_, tree) = rrt_with_clock(
+            &0,
+            &8,
+            sample_fn,
+            extend_fn,
+            connectable_fn,
+            &options,
+            MaxDuration(Duration::from_millis(195)),
+            &clock,
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+
+        assert!(tree.iter_insertion_order().skip(1).all(|node| {
+            let provenance = tree.provenance(node).unwrap();
+            provenance.iteration < 20
+                && provenance.elapsed == Duration::from_millis(10 * provenance.iteration)
+        }));
+        tree.iter_insertion_order()
+            .map(|node| (tree.get_parent(node).copied(), *node))
+            .collect()
+    }
+
+    #[test]
+    fn test_golden_tree() {
+        let edges = golden_run(7);
+        assert_eq!(edges, golden_run(7));
+        // Any change here means the planner no longer builds the same tree from the same
+        // inputs, which should only happen on purpose
+        let expected = vec![
+            (None, 0),
+            (Some(0), 1),
+            (Some(0), -4),
+            (Some(1), 5),
+            (Some(5), 8),
+            (Some(5), 7),
+            (Some(0), -3),
+            (Some(0), 4),
+            (Some(0), -1),
+            (Some(-4), -6),
+            (Some(1), 2),
+            (Some(-6), -7),
+            (Some(8), 12),
+            (Some(12), 16),
+            (Some(12), 15),
+            (Some(5), 6),
+        ];
+        assert_eq!(edges, expected);
+        assert_ne!(golden_run(8), expected);
     }
 }
@@ -0,0 +1,415 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::TreeError;
+use crate::planning::replay::PlannerEvent;
+use std::fmt;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+/// Why an attempt to connect a node to the goal failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoalRejectionReason {
+    /// The node was further from the goal than the maximum extension length.
+    OutOfReach,
+
+    /// The node was within reach, but the connectable function rejected the motion.
+    InvalidMotion,
+
+    /// The path to the goal through the node would cost more than
+    /// [`crate::planning::rrt::RrtOptions::max_cost`].
+    OverCost,
+}
+
+/// A failed attempt to connect a node to the goal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoalRejection<T> {
+    /// The state the connection was attempted from. When greedily extending towards the
+    /// goal this is the furthest state that was reached.
+    pub state: T,
+
+    /// Distance from `state` to the goal.
+    pub distance: f64,
+
+    /// Why the connection failed.
+    pub reason: GoalRejectionReason,
+}
+
+/// The first path found to the goal, kept while planning continues to improve on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirstSolution<T> {
+    /// The path from the start to the goal at the time.
+    pub path: Vec<T>,
+
+    /// Cost of the path.
+    pub cost: f64,
+
+    /// The planner iteration that found it.
+    pub iteration: u64,
+
+    /// Time since planning started when it was found.
+    pub elapsed: Duration,
+}
+
+/// Time spent in each phase of planning.
+///
+/// Phases do not overlap, time spent validating motions is only counted in
+/// `collision_checks` rather than in the phase that requested the check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PhaseTimings {
+    /// Drawing samples.
+    pub sampling: Duration,
+
+    /// Choosing the node to extend from, normally a nearest neighbor query.
+    pub nearest_neighbor: Duration,
+
+    /// Steering towards samples and adding the new nodes to the tree.
+    pub extension: Duration,
+
+    /// Validating motions with the connectable function.
+    pub collision_checks: Duration,
+
+    /// Rewiring neighbors of new nodes for RRT*.
+    pub rewiring: Duration,
+
+    /// Attempting to connect the tree to the goal.
+    pub goal_checks: Duration,
+}
+
+impl PhaseTimings {
+    /// Total time spent across all phases.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.sampling
+            + self.nearest_neighbor
+            + self.extension
+            + self.collision_checks
+            + self.rewiring
+            + self.goal_checks
+    }
+}
+
+impl AddAssign for PhaseTimings {
+    fn add_assign(&mut self, other: Self) {
+        self.sampling += other.sampling;
+        self.nearest_neighbor += other.nearest_neighbor;
+        self.extension += other.extension;
+        self.collision_checks += other.collision_checks;
+        self.rewiring += other.rewiring;
+        self.goal_checks += other.goal_checks;
+    }
+}
+
+impl fmt::Display for PhaseTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sampling {:?}, nearest neighbor {:?}, extension {:?}, collision checks {:?}, \
+             rewiring {:?}, goal checks {:?}",
+            self.sampling,
+            self.nearest_neighbor,
+            self.extension,
+            self.collision_checks,
+            self.rewiring,
+            self.goal_checks
+        )
+    }
+}
+
+/// Why a planner stopped iterating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// A path was found and the planner was configured to return the first one.
+    FoundPath,
+
+    /// The iteration limit was reached.
+    MaxIterations,
+
+    /// The timeout expired.
+    Timeout,
+
+    /// Planning was cancelled.
+    Cancelled,
+
+    /// The best path stopped improving, or became close enough to optimal.
+    Converged,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StopReason::FoundPath => "found a path",
+            StopReason::MaxIterations => "reached the iteration limit",
+            StopReason::Timeout => "timed out",
+            StopReason::Cancelled => "cancelled",
+            StopReason::Converged => "converged",
+        })
+    }
+}
+
+/// A summary of a run returned alongside the path by [`crate::planning::rrt::rrt`], for
+/// judging how efficiently it planned. See [`PlannerStats`] for the full details.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanningStats {
+    /// Number of planner iterations run.
+    pub iterations: u64,
+
+    /// Number of samples that added no node to the tree.
+    pub samples_rejected: usize,
+
+    /// Number of nodes added to the tree, not counting the root.
+    pub nodes_added: usize,
+
+    /// Number of nodes RRT* moved to a cheaper parent.
+    pub rewires: usize,
+
+    /// Wall clock time spent planning.
+    pub elapsed: Duration,
+
+    /// Cost of the returned path, if one was found.
+    pub path_cost: Option<f64>,
+
+    /// Why the planner stopped.
+    pub stop_reason: StopReason,
+}
+
+impl PlanningStats {
+    /// Returns the fraction of iterations whose sample added to the tree, if any ran.
+    #[must_use]
+    pub fn sample_acceptance_rate(&self) -> Option<f64> {
+        #[allow(clippy::cast_precision_loss)]
+        (self.iterations > 0).then(|| 1.0 - self.samples_rejected as f64 / self.iterations as f64)
+    }
+}
+
+impl fmt::Display for PlanningStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} after {} iterations in {:?}, {} nodes added, {} samples rejected, {} rewires",
+            self.stop_reason,
+            self.iterations,
+            self.elapsed,
+            self.nodes_added,
+            self.samples_rejected,
+            self.rewires
+        )?;
+        match self.path_cost {
+            Some(cost) => write!(f, ", path cost {cost:.4}"),
+            None => write!(f, ", no path"),
+        }
+    }
+}
+
+/// Diagnostic information collected while planning.
+///
+/// Optional records are only collected if enabled in the planner's options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannerStats<T> {
+    /// Every failed attempt to connect to the goal, in the order they occurred.
+    pub goal_rejections: Vec<GoalRejection<T>>,
+
+    /// Number of attempts to connect a node to the goal.
+    pub goal_attempts: usize,
+
+    /// Number of goal connection candidates skipped for being too far from the goal.
+    pub goal_skips: usize,
+
+    /// Number of goal connections rejected for exceeding the maximum path cost.
+    pub cost_rejections: usize,
+
+    /// Number of new states that duplicated a node already in the tree.
+    pub duplicate_states: usize,
+
+    /// Number of new states discarded because an earlier state in the same extension was a
+    /// rejected duplicate.
+    pub discarded_states: usize,
+
+    /// Number of samples or new states skipped because their distance to the tree was NaN,
+    /// which usually means the state itself contains NaN.
+    pub invalid_states: usize,
+
+    /// Number of samples redrawn by informed sampling because they could not be on a path
+    /// cheaper than the best found.
+    pub informed_rejections: usize,
+
+    /// Number of samples that added no node to the tree, because they were invalid, the
+    /// motion towards them was rejected, or they duplicated an existing node.
+    pub rejected_samples: usize,
+
+    /// Number of nodes RRT* moved to a cheaper parent, both when they were added and when
+    /// rewiring around later nodes.
+    pub rewires: usize,
+
+    /// Errors returned by the tree when inserting or rewiring nodes. These indicate a bug
+    /// in the planner rather than a planning failure, so should always be empty.
+    pub tree_errors: Vec<TreeError>,
+
+    /// Time spent in each phase of planning.
+    pub timings: PhaseTimings,
+
+    /// Number of planner iterations run.
+    pub iterations: u64,
+
+    /// Time spent in [`crate::planning::planner::Planner::precompute`] before the query,
+    /// if it was called. This is not included in `timings` or any other elapsed times.
+    pub precompute_time: Option<Duration>,
+
+    /// The first solution found, for comparing against the final path after optimizing
+    /// further with RRT*.
+    pub first_solution: Option<FirstSolution<T>>,
+
+    /// The node closest to the goal and its distance, updated as nodes are added so it can
+    /// be read cheaply while planning. If no path is found, the path to this node is the
+    /// best approximate solution.
+    pub nearest_to_goal: Option<(T, f64)>,
+
+    /// Every sample, tree change, and solution improvement in the order they occurred,
+    /// for replaying the run with [`crate::planning::replay::Replay`]. Only recorded while
+    /// this is `Some`, which planners that support recording set when enabled.
+    pub events: Option<Vec<PlannerEvent<T>>>,
+
+    /// Why the planner stopped iterating, if its termination condition could tell.
+    pub stop_reason: Option<StopReason>,
+}
+
+impl<T> PlannerStats<T> {
+    /// Construct an empty set of stats.
+    #[must_use]
+    pub fn new() -> Self {
+        PlannerStats {
+            goal_rejections: Vec::new(),
+            goal_attempts: 0,
+            goal_skips: 0,
+            cost_rejections: 0,
+            duplicate_states: 0,
+            discarded_states: 0,
+            invalid_states: 0,
+            informed_rejections: 0,
+            rejected_samples: 0,
+            rewires: 0,
+            tree_errors: Vec::new(),
+            timings: PhaseTimings::default(),
+            iterations: 0,
+            precompute_time: None,
+            first_solution: None,
+            nearest_to_goal: None,
+            events: None,
+            stop_reason: None,
+        }
+    }
+
+    /// Returns the closest any rejected goal connection came to the goal, if there were any.
+    #[must_use]
+    pub fn closest_goal_rejection(&self) -> Option<&GoalRejection<T>> {
+        self.goal_rejections
+            .iter()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Records an event if recording is enabled.
+    pub(crate) fn record(&mut self, event: impl FnOnce() -> PlannerEvent<T>) {
+        if let Some(events) = &mut self.events {
+            events.push(event());
+        }
+    }
+}
+
+impl<T> Default for PlannerStats<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest_goal_rejection() {
+        let mut stats: PlannerStats<i32> = PlannerStats::new();
+        assert!(stats.closest_goal_rejection().is_none());
+
+        stats.goal_rejections.push(GoalRejection {
+            state: 1,
+            distance: 3.0,
+            reason: GoalRejectionReason::OutOfReach,
+        });
+        stats.goal_rejections.push(GoalRejection {
+            state: 3,
+            distance: 1.0,
+            reason: GoalRejectionReason::InvalidMotion,
+        });
+        assert_eq!(stats.closest_goal_rejection().unwrap().state, 3);
+    }
+
+    #[test]
+    fn test_planning_stats() {
+        let mut stats = PlanningStats {
+            iterations: 10,
+            samples_rejected: 4,
+            nodes_added: 8,
+            rewires: 2,
+            elapsed: Duration::from_millis(5),
+            path_cost: Some(3.5),
+            stop_reason: StopReason::FoundPath,
+        };
+        assert_eq!(stats.sample_acceptance_rate(), Some(0.6));
+        assert_eq!(
+            stats.to_string(),
+            "found a path after 10 iterations in 5ms, 8 nodes added, 4 samples rejected, \
+             2 rewires, path cost 3.5000"
+        );
+
+        stats.path_cost = None;
+        stats.stop_reason = StopReason::Timeout;
+        assert!(stats.to_string().starts_with("timed out"));
+        assert!(stats.to_string().ends_with("no path"));
+        stats.iterations = 0;
+        assert_eq!(stats.sample_acceptance_rate(), None);
+    }
+
+    #[test]
+    fn test_phase_timings() {
+        let mut timings = PhaseTimings {
+            sampling: Duration::from_millis(1),
+            collision_checks: Duration::from_millis(2),
+            ..PhaseTimings::default()
+        };
+        timings += PhaseTimings {
+            sampling: Duration::from_millis(3),
+            goal_checks: Duration::from_millis(4),
+            ..PhaseTimings::default()
+        };
+        assert_eq!(timings.sampling, Duration::from_millis(4));
+        assert_eq!(timings.total(), Duration::from_millis(10));
+        assert_eq!(
+            timings.to_string(),
+            "sampling 4ms, nearest neighbor 0ns, extension 0ns, collision checks 2ms, \
+             rewiring 0ns, goal checks 4ms"
+        );
+    }
+}
@@ -0,0 +1,474 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::planning::stats::StopReason;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Snapshot of the planner's progress, provided to termination conditions before
+/// every iteration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlannerProgress {
+    /// Number of iterations completed so far.
+    pub iteration: u64,
+
+    /// Wall clock time since planning started.
+    pub elapsed: Duration,
+
+    /// Cost of the best solution found so far, if any.
+    pub best_cost: Option<f64>,
+}
+
+/// Decides when a planner should stop iterating.
+///
+/// Conditions are queried once per iteration and may be stateful, which is required
+/// for things like stall detection. Any `FnMut(&PlannerProgress) -> bool` closure is
+/// also a condition. Conditions can be combined with [`TerminationCondition::and`] and
+/// [`TerminationCondition::or`].
+pub trait TerminationCondition {
+    /// Returns true if the planner should stop given the current progress.
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool;
+
+    /// Returns why the planner stopped, once [`TerminationCondition::should_terminate`]
+    /// has returned true for `progress`. `None` if this condition did not stop it, or
+    /// cannot say why, as for closures.
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        let _ = progress;
+        None
+    }
+
+    /// Terminate only when both `self` and `other` would terminate.
+    fn and<O>(self, other: O) -> And<Self, O>
+    where
+        Self: Sized,
+        O: TerminationCondition,
+    {
+        And(self, other)
+    }
+
+    /// Terminate when either `self` or `other` would terminate.
+    fn or<O>(self, other: O) -> Or<Self, O>
+    where
+        Self: Sized,
+        O: TerminationCondition,
+    {
+        Or(self, other)
+    }
+}
+
+impl<F> TerminationCondition for F
+where
+    F: FnMut(&PlannerProgress) -> bool,
+{
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        self(progress)
+    }
+}
+
+/// Terminates when both conditions are met.
+///
+/// Both conditions are always evaluated so stateful conditions see every iteration.
+#[derive(Debug, Clone)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A, B> TerminationCondition for And<A, B>
+where
+    A: TerminationCondition,
+    B: TerminationCondition,
+{
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        let a = self.0.should_terminate(progress);
+        let b = self.1.should_terminate(progress);
+        a && b
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        self.0
+            .stop_reason(progress)
+            .or_else(|| self.1.stop_reason(progress))
+    }
+}
+
+/// Terminates when either condition is met.
+///
+/// Both conditions are always evaluated so stateful conditions see every iteration.
+#[derive(Debug, Clone)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A, B> TerminationCondition for Or<A, B>
+where
+    A: TerminationCondition,
+    B: TerminationCondition,
+{
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        let a = self.0.should_terminate(progress);
+        let b = self.1.should_terminate(progress);
+        a || b
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        self.0
+            .stop_reason(progress)
+            .or_else(|| self.1.stop_reason(progress))
+    }
+}
+
+/// Terminates after the specified number of iterations.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxIterations(pub u64);
+
+impl TerminationCondition for MaxIterations {
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        progress.iteration >= self.0
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        (progress.iteration >= self.0).then_some(StopReason::MaxIterations)
+    }
+}
+
+/// Terminates once the specified amount of time has elapsed.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxDuration(pub Duration);
+
+impl TerminationCondition for MaxDuration {
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        progress.elapsed > self.0
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        (progress.elapsed > self.0).then_some(StopReason::Timeout)
+    }
+}
+
+/// Terminates as soon as any solution has been found.
+#[derive(Debug, Clone, Copy)]
+pub struct SolutionFound;
+
+impl TerminationCondition for SolutionFound {
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        progress.best_cost.is_some()
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        progress.best_cost.map(|_| StopReason::FoundPath)
+    }
+}
+
+/// Terminates once the best solution is within a fraction of a known lower bound on the
+/// cost, e.g. the straight line distance between the start and the goal.
+///
+/// A `tolerance` of `0.1` stops when the best cost is within 10% of `lower_bound`.
+#[derive(Debug, Clone, Copy)]
+pub struct CostThreshold {
+    pub lower_bound: f64,
+    pub tolerance: f64,
+}
+
+impl CostThreshold {
+    #[must_use]
+    pub fn new(lower_bound: f64, tolerance: f64) -> Self {
+        CostThreshold {
+            lower_bound,
+            tolerance,
+        }
+    }
+}
+
+impl TerminationCondition for CostThreshold {
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        progress
+            .best_cost
+            .is_some_and(|cost| cost <= self.lower_bound * (1.0 + self.tolerance))
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        progress
+            .best_cost
+            .is_some_and(|cost| cost <= self.lower_bound * (1.0 + self.tolerance))
+            .then_some(StopReason::Converged)
+    }
+}
+
+/// Stall detection, terminates when the best cost has not improved for a number of
+/// iterations and/or amount of time.
+///
+/// The stall counters only start once a first solution is found, so a planner that is
+/// still searching for a path is never considered stalled.
+#[derive(Debug, Clone)]
+pub struct Stalled {
+    max_iterations: Option<u64>,
+    max_duration: Option<Duration>,
+
+    // Best cost, iteration, and time of the last improvement
+    last_improvement: Option<(f64, u64, Duration)>,
+}
+
+impl Stalled {
+    /// Terminate after `iterations` iterations without an improvement.
+    #[must_use]
+    pub fn iterations(iterations: u64) -> Self {
+        Stalled {
+            max_iterations: Some(iterations),
+            max_duration: None,
+            last_improvement: None,
+        }
+    }
+
+    /// Terminate after `duration` has passed without an improvement.
+    #[must_use]
+    pub fn duration(duration: Duration) -> Self {
+        Stalled {
+            max_iterations: None,
+            max_duration: Some(duration),
+            last_improvement: None,
+        }
+    }
+}
+
+impl Stalled {
+    /// Returns true if the best cost has not improved on `cost` for too long.
+    fn is_stalled(&self, progress: &PlannerProgress, cost: f64) -> bool {
+        match self.last_improvement {
+            Some((best, iteration, elapsed)) if cost >= best => {
+                let iterations_stalled = self
+                    .max_iterations
+                    .is_some_and(|max| progress.iteration.saturating_sub(iteration) >= max);
+                let duration_stalled = self
+                    .max_duration
+                    .is_some_and(|max| progress.elapsed.saturating_sub(elapsed) >= max);
+                iterations_stalled || duration_stalled
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TerminationCondition for Stalled {
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        let Some(cost) = progress.best_cost else {
+            return false;
+        };
+        if self.is_stalled(progress, cost) {
+            return true;
+        }
+        if self.last_improvement.is_none_or(|(best, _, _)| cost < best) {
+            self.last_improvement = Some((cost, progress.iteration, progress.elapsed));
+        }
+        false
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        progress
+            .best_cost
+            .is_some_and(|cost| self.is_stalled(progress, cost))
+            .then_some(StopReason::Converged)
+    }
+}
+
+/// Cooperative cancellation, terminates once [`CancellationToken::cancel`] has been called
+/// on any clone of the token, e.g. from a GUI stop button or another thread.
+///
+/// The token is checked once per iteration, so planning stops within one iteration of
+/// being cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Construct a token that has not been cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request that planning stops.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if cancellation has been requested.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Tokens are equal if they are clones of one another, and so cancelled together
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl TerminationCondition for CancellationToken {
+    fn should_terminate(&mut self, _: &PlannerProgress) -> bool {
+        self.is_cancelled()
+    }
+
+    fn stop_reason(&self, _: &PlannerProgress) -> Option<StopReason> {
+        self.is_cancelled().then_some(StopReason::Cancelled)
+    }
+}
+
+/// An optional condition, which never terminates if absent.
+impl<C> TerminationCondition for Option<C>
+where
+    C: TerminationCondition,
+{
+    fn should_terminate(&mut self, progress: &PlannerProgress) -> bool {
+        self.as_mut()
+            .is_some_and(|condition| condition.should_terminate(progress))
+    }
+
+    fn stop_reason(&self, progress: &PlannerProgress) -> Option<StopReason> {
+        self.as_ref()?.stop_reason(progress)
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(iteration: u64, best_cost: Option<f64>) -> PlannerProgress {
+        PlannerProgress {
+            iteration,
+            elapsed: Duration::from_secs(iteration),
+            best_cost,
+        }
+    }
+
+    #[test]
+    fn test_basic_conditions() {
+        assert!(!MaxIterations(5).should_terminate(&progress(4, None)));
+        assert!(MaxIterations(5).should_terminate(&progress(5, None)));
+
+        assert!(!MaxDuration(Duration::from_secs(5)).should_terminate(&progress(5, None)));
+        assert!(MaxDuration(Duration::from_secs(5)).should_terminate(&progress(6, None)));
+
+        assert!(!SolutionFound.should_terminate(&progress(1, None)));
+        assert!(SolutionFound.should_terminate(&progress(1, Some(3.0))));
+    }
+
+    #[test]
+    fn test_cost_threshold() {
+        let mut condition = CostThreshold::new(10.0, 0.1);
+        assert!(!condition.should_terminate(&progress(1, None)));
+        assert!(!condition.should_terminate(&progress(1, Some(11.5))));
+        assert!(condition.should_terminate(&progress(1, Some(10.5))));
+    }
+
+    #[test]
+    fn test_stalled() {
+        let mut condition = Stalled::iterations(3);
+
+        // Never stalled without a solution
+        assert!(!condition.should_terminate(&progress(0, None)));
+        assert!(!condition.should_terminate(&progress(10, None)));
+
+        // Improvements reset the counter
+        assert!(!condition.should_terminate(&progress(11, Some(5.0))));
+        assert!(!condition.should_terminate(&progress(13, Some(5.0))));
+        assert!(!condition.should_terminate(&progress(14, Some(4.0))));
+        assert!(!condition.should_terminate(&progress(16, Some(4.0))));
+        assert!(condition.should_terminate(&progress(17, Some(4.0))));
+
+        let mut condition = Stalled::duration(Duration::from_secs(2));
+        assert!(!condition.should_terminate(&progress(1, Some(5.0))));
+        assert!(!condition.should_terminate(&progress(2, Some(5.0))));
+        assert!(condition.should_terminate(&progress(3, Some(5.0))));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let mut condition = MaxIterations(10).or(SolutionFound);
+        assert!(!condition.should_terminate(&progress(1, None)));
+        assert!(condition.should_terminate(&progress(1, Some(1.0))));
+        assert!(condition.should_terminate(&progress(10, None)));
+
+        let mut condition = MaxIterations(10).and(SolutionFound);
+        assert!(!condition.should_terminate(&progress(1, Some(1.0))));
+        assert!(!condition.should_terminate(&progress(10, None)));
+        assert!(condition.should_terminate(&progress(10, Some(1.0))));
+
+        // Closures work as conditions and compose with the rest
+        let mut condition =
+            MaxIterations(10).or(|p: &PlannerProgress| p.best_cost.is_some_and(|c| c < 2.0));
+        assert!(!condition.should_terminate(&progress(1, Some(3.0))));
+        assert!(condition.should_terminate(&progress(1, Some(1.0))));
+    }
+
+    #[test]
+    fn test_combinators_evaluate_both_sides() {
+        // The stall condition must observe the first solution even though the left side
+        // of the Or is already true.
+        let mut condition = SolutionFound.or(Stalled::iterations(2));
+        assert!(condition.should_terminate(&progress(1, Some(5.0))));
+        assert!(condition.1.last_improvement.is_some());
+    }
+
+    #[test]
+    fn test_stop_reason() {
+        let condition = MaxIterations(10).or(MaxDuration(Duration::from_secs(5)));
+        assert_eq!(condition.stop_reason(&progress(1, None)), None);
+        assert_eq!(
+            condition.stop_reason(&progress(6, None)),
+            Some(StopReason::Timeout)
+        );
+        assert_eq!(
+            condition.stop_reason(&progress(10, None)),
+            Some(StopReason::MaxIterations)
+        );
+        assert_eq!(
+            CostThreshold::new(10.0, 0.1).stop_reason(&progress(1, Some(10.5))),
+            Some(StopReason::Converged)
+        );
+
+        // Closures cannot say why they stopped
+        let condition = |p: &PlannerProgress| p.iteration > 1;
+        assert_eq!(condition.stop_reason(&progress(2, None)), None);
+    }
+
+    #[test]
+    fn test_cancellation_token() {
+        let mut token = CancellationToken::new();
+        let other = token.clone();
+        assert!(!token.should_terminate(&progress(1, None)));
+        assert_eq!(token, other);
+        assert_ne!(token, CancellationToken::new());
+        other.cancel();
+        assert!(token.is_cancelled());
+        assert!(token.should_terminate(&progress(1, None)));
+
+        let mut optional = Some(token);
+        assert!(optional.should_terminate(&progress(1, None)));
+        assert_eq!(
+            optional.stop_reason(&progress(1, None)),
+            Some(StopReason::Cancelled)
+        );
+        assert!(!None::<CancellationToken>.should_terminate(&progress(1, None)));
+    }
+}
@@ -0,0 +1,247 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::planning::batch::{plan_batch, BatchOptions, ProblemDefinition};
+use crate::planning::rrt::RrtOptions;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::Distance;
+use std::hash::Hash;
+
+/// Estimates the cost of traveling between every pair of `states` with quick planner runs.
+///
+/// Returns a matrix where entry `[i][j]` is the cost of the path found from `states[i]` to
+/// `states[j]`, or [`f64::INFINITY`] if no path was found. The diagonal is zero. Planning is
+/// done with [`plan_batch`], so the sampler, termination, and batch options behave the same
+/// way. Setting [`BatchOptions::share_tree`] is recommended since it allows a single tree to
+/// be grown from each state to all of the others.
+pub fn pairwise_costs<T, MS, FS, FE, FC, MT, TC>(
+    states: &[T],
+    make_sampler: MS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    make_termination: MT,
+    batch: &BatchOptions,
+) -> Vec<Vec<f64>>
+where
+    T: Eq + Clone + Hash + Distance + Send + Sync,
+    MS: Fn(usize) -> FS + Sync,
+    FS: FnMut() -> T,
+    FE: Fn(&T, &T) -> T + Sync,
+    FC: Fn(&T, &T) -> bool + Sync,
+    MT: Fn() -> TC + Sync,
+    TC: TerminationCondition,
+{
+    let n = states.len();
+    let pairs: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| (0..n).filter(move |&j| i != j).map(move |j| (i, j)))
+        .collect();
+    let problems: Vec<ProblemDefinition<T>> = pairs
+        .iter()
+        .map(|&(i, j)| ProblemDefinition::new(states[i].clone(), states[j].clone()))
+        .collect();
+
+    let results = plan_batch(
+        &problems,
+        make_sampler,
+        extend_fn,
+        connectable_fn,
+        options,
+        make_termination,
+        batch,
+    );
+
+    let mut costs = vec![vec![0.0; n]; n];
+    for (&(i, j), result) in pairs.iter().zip(&results) {
+        costs[i][j] = result.cost.unwrap_or(f64::INFINITY);
+    }
+    costs
+}
+
+/// Returns the total cost of visiting the nodes in `order`, without returning to the start.
+#[must_use]
+pub fn tour_cost(costs: &[Vec<f64>], order: &[usize]) -> f64 {
+    order.windows(2).map(|pair| costs[pair[0]][pair[1]]).sum()
+}
+
+/// Orders the nodes of a cost matrix into a short open tour starting at node 0.
+///
+/// This is a heuristic for small problems: an initial tour is constructed by repeatedly
+/// visiting the cheapest unvisited node, then improved with 2-opt moves until none reduce
+/// the total cost. Costs need not be symmetric. The first node is always kept in place,
+/// and the tour does not return to it.
+///
+/// # Panics
+///
+/// If `costs` is not a square matrix.
+#[must_use]
+pub fn solve_tour(costs: &[Vec<f64>]) -> Vec<usize> {
+    let n = costs.len();
+    assert!(
+        costs.iter().all(|row| row.len() == n),
+        "Cost matrix must be square"
+    );
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // Nearest neighbor construction, ties go to the lowest index
+    let mut order = vec![0];
+    let mut visited = vec![false; n];
+    visited[0] = true;
+    while order.len() < n {
+        let current = *order.last().unwrap();
+        let next = (0..n)
+            .filter(|&j| !visited[j])
+            .min_by(|&a, &b| costs[current][a].total_cmp(&costs[current][b]))
+            .unwrap();
+        visited[next] = true;
+        order.push(next);
+    }
+
+    // 2-opt, reversing a section of the tour whenever it lowers the total cost. The full
+    // cost is recomputed since reversing a section changes direction for asymmetric costs.
+    let mut best = tour_cost(costs, &order);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..n {
+            for j in (i + 1)..n {
+                order[i..=j].reverse();
+                let cost = tour_cost(costs, &order);
+                if cost < best {
+                    best = cost;
+                    improved = true;
+                } else {
+                    order[i..=j].reverse();
+                }
+            }
+        }
+    }
+    order
+}
+
+/// Orders `targets` into a short sequence of waypoints to visit from `start`.
+///
+/// Combines [`pairwise_costs`] and [`solve_tour`]. The returned waypoints begin with `start`
+/// and can be passed directly to [`crate::planning::waypoints::plan_waypoints`].
+#[allow(clippy::too_many_arguments)]
+pub fn order_targets<T, MS, FS, FE, FC, MT, TC>(
+    start: &T,
+    targets: &[T],
+    make_sampler: MS,
+    extend_fn: FE,
+    connectable_fn: FC,
+    options: &RrtOptions,
+    make_termination: MT,
+    batch: &BatchOptions,
+) -> Vec<T>
+where
+    T: Eq + Clone + Hash + Distance + Send + Sync,
+    MS: Fn(usize) -> FS + Sync,
+    FS: FnMut() -> T,
+    FE: Fn(&T, &T) -> T + Sync,
+    FC: Fn(&T, &T) -> bool + Sync,
+    MT: Fn() -> TC + Sync,
+    TC: TerminationCondition,
+{
+    let states: Vec<T> = std::iter::once(start.clone())
+        .chain(targets.iter().cloned())
+        .collect();
+    let costs = pairwise_costs(
+        &states,
+        make_sampler,
+        extend_fn,
+        connectable_fn,
+        options,
+        make_termination,
+        batch,
+    );
+    solve_tour(&costs)
+        .into_iter()
+        .map(|i| states[i].clone())
+        .collect()
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::termination::{MaxIterations, SolutionFound};
+
+    #[test]
+    fn test_solve_tour() {
+        assert!(solve_tour(&[]).is_empty());
+        assert_eq!(solve_tour(&[vec![0.0]]), vec![0]);
+
+        // Points on a line at 0, 5, 1, 6, 2, visiting in order of position is optimal
+        let positions = [0.0_f64, 5.0, 1.0, 6.0, 2.0];
+        let costs: Vec<Vec<f64>> = positions
+            .iter()
+            .map(|a| positions.iter().map(|b| (a - b).abs()).collect())
+            .collect();
+        let order = solve_tour(&costs);
+        assert_eq!(order, vec![0, 2, 4, 1, 3]);
+        assert!((tour_cost(&costs, &order) - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_solve_tour_two_opt() {
+        // Nearest neighbor greedily visits 1 first, then has to cross back over the start
+        let positions = [0.0_f64, 1.0, -2.0, 10.0];
+        let costs: Vec<Vec<f64>> = positions
+            .iter()
+            .map(|a| positions.iter().map(|b| (a - b).abs()).collect())
+            .collect();
+        let order = solve_tour(&costs);
+        assert_eq!(order, vec![0, 2, 1, 3]);
+        assert!((tour_cost(&costs, &order) - 14.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_order_targets() {
+        let make_sampler = |i: usize| {
+            let mut samples = [-10, 20].into_iter().cycle().skip(i);
+            move || samples.next().unwrap()
+        };
+        let extend_fn = |from: &i32, to: &i32| from + (to - from).signum();
+        let connectable_fn = |from: &i32, to: &i32| (to - from).abs() <= 1;
+
+        let ordered = order_targets(
+            &0,
+            &[8, 4, 2, -1],
+            make_sampler,
+            extend_fn,
+            connectable_fn,
+            &RrtOptions::default(),
+            || MaxIterations(200).or(SolutionFound),
+            &BatchOptions {
+                threads: 2,
+                share_tree: true,
+            },
+        );
+        assert_eq!(ordered, vec![0, -1, 2, 4, 8]);
+    }
+}
@@ -0,0 +1,330 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::benchmark::{seed_sweep, ConfigurationReport, RunOutcome, Summary};
+use crate::clock::Instant;
+use crate::planning::batch::ProblemDefinition;
+use crate::planning::rrt::RrtOptions;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::Distance;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// A single assignment of the tunable RRT hyperparameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hyperparameters {
+    /// Distance to extend towards samples, used as [`RrtOptions::max_extension`].
+    pub step_size: f64,
+
+    /// Probability of sampling the goal rather than calling the sampling function.
+    pub goal_bias: f64,
+
+    /// Used as [`RrtOptions::rewire_radius`], only relevant to RRT*.
+    pub rewire_radius: f64,
+}
+
+impl fmt::Display for Hyperparameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step_size={}, goal_bias={}, rewire_radius={}",
+            self.step_size, self.goal_bias, self.rewire_radius
+        )
+    }
+}
+
+/// Candidate values for each hyperparameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterSpace {
+    pub step_sizes: Vec<f64>,
+    pub goal_biases: Vec<f64>,
+    pub rewire_radii: Vec<f64>,
+}
+
+/// How configurations are drawn from a [`ParameterSpace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchStrategy {
+    /// Every combination of the candidate values.
+    Grid,
+
+    /// Combinations drawn uniformly at random with replacement from the candidate values.
+    Random { samples: usize, seed: u64 },
+}
+
+// SplitMix64, to keep the library free of an RNG dependency
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn pick(&mut self, values: &[f64]) -> f64 {
+        values[(self.next_u64() % values.len() as u64) as usize]
+    }
+}
+
+impl ParameterSpace {
+    /// Returns the configurations to evaluate with the given strategy, or none if any
+    /// hyperparameter has no candidate values.
+    #[must_use]
+    pub fn candidates(&self, strategy: SearchStrategy) -> Vec<Hyperparameters> {
+        if self.step_sizes.is_empty() || self.goal_biases.is_empty() || self.rewire_radii.is_empty()
+        {
+            return Vec::new();
+        }
+        match strategy {
+            SearchStrategy::Grid => {
+                let mut candidates = Vec::new();
+                for &step_size in &self.step_sizes {
+                    for &goal_bias in &self.goal_biases {
+                        for &rewire_radius in &self.rewire_radii {
+                            candidates.push(Hyperparameters {
+                                step_size,
+                                goal_bias,
+                                rewire_radius,
+                            });
+                        }
+                    }
+                }
+                candidates
+            }
+            SearchStrategy::Random { samples, seed } => {
+                let mut rng = SplitMix64(seed);
+                (0..samples)
+                    .map(|_| Hyperparameters {
+                        step_size: rng.pick(&self.step_sizes),
+                        goal_bias: rng.pick(&self.goal_biases),
+                        rewire_radius: rng.pick(&self.rewire_radii),
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The evaluation of one configuration by [`tune`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningResult {
+    pub parameters: Hyperparameters,
+    pub report: ConfigurationReport,
+}
+
+// Higher success rates first, then lower median cost, then lower median time
+fn rank(a: &TuningResult, b: &TuningResult) -> Ordering {
+    let median = |summary: Option<Summary>| summary.map_or(f64::INFINITY, |summary| summary.median);
+    b.report
+        .success
+        .rate()
+        .total_cmp(&a.report.success.rate())
+        .then(median(a.report.cost).total_cmp(&median(b.report.cost)))
+        .then(median(a.report.time).total_cmp(&median(b.report.time)))
+}
+
+/// Searches for RRT hyperparameters that work well on a user scenario.
+///
+/// Each configuration drawn from `space` is evaluated with a [`seed_sweep`] over `seeds`.
+/// `make_sampler` constructs the sampling function for a seed, which is then biased towards
+/// the goal by the configuration's goal bias, and `make_extend` constructs an extension
+/// function for a step size. All other planner settings come from `options`.
+///
+/// Configurations are evaluated in order until they are exhausted or `budget` has elapsed,
+/// a configuration that has started is always completed. Results are ranked by success
+/// rate, then median cost, then median planning time, so the first is the best found.
+#[allow(clippy::too_many_arguments)]
+pub fn tune<T, MS, FS, ME, FE, FC, MT, TC>(
+    problem: &ProblemDefinition<T>,
+    space: &ParameterSpace,
+    strategy: SearchStrategy,
+    seeds: &[u64],
+    mut make_sampler: MS,
+    mut make_extend: ME,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    mut make_termination: MT,
+    budget: Duration,
+) -> Vec<TuningResult>
+where
+    T: Eq + Clone + Hash + Distance,
+    MS: FnMut(u64) -> FS,
+    FS: FnMut() -> T,
+    ME: FnMut(f64) -> FE,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    MT: FnMut() -> TC,
+    TC: TerminationCondition,
+{
+    let start_time = Instant::now();
+    let mut results = Vec::new();
+    for parameters in space.candidates(strategy) {
+        if start_time.elapsed() >= budget {
+            break;
+        }
+
+        let candidate_options = RrtOptions {
+            max_extension: parameters.step_size,
+            rewire_radius: parameters.rewire_radius,
+            ..options.clone()
+        };
+        let goal = &problem.goal;
+        let biased_sampler = |seed: u64| {
+            let mut sample_fn = make_sampler(seed);
+            let mut rng = SplitMix64(seed);
+            move || {
+                if rng.next_f64() < parameters.goal_bias {
+                    goal.clone()
+                } else {
+                    sample_fn()
+                }
+            }
+        };
+        let runs: Vec<RunOutcome> = seed_sweep(
+            problem,
+            seeds,
+            biased_sampler,
+            make_extend(parameters.step_size),
+            &mut connectable_fn,
+            &candidate_options,
+            &mut make_termination,
+        )
+        .into_iter()
+        .map(|result| result.outcome)
+        .collect();
+
+        results.push(TuningResult {
+            parameters,
+            report: ConfigurationReport::from_runs(parameters.to_string(), &runs),
+        });
+    }
+    results.sort_by(rank);
+    results
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planning::termination::{MaxIterations, SolutionFound};
+
+    fn space() -> ParameterSpace {
+        ParameterSpace {
+            step_sizes: vec![1.0, 2.0],
+            goal_biases: vec![0.0, 1.0],
+            rewire_radii: vec![0.0],
+        }
+    }
+
+    #[test]
+    fn test_candidates() {
+        let grid = space().candidates(SearchStrategy::Grid);
+        assert_eq!(grid.len(), 4);
+        assert_eq!(
+            grid[1],
+            Hyperparameters {
+                step_size: 1.0,
+                goal_bias: 1.0,
+                rewire_radius: 0.0
+            }
+        );
+
+        let random = space().candidates(SearchStrategy::Random {
+            samples: 10,
+            seed: 3,
+        });
+        assert_eq!(random.len(), 10);
+        assert!(random.iter().all(|candidate| grid.contains(candidate)));
+        assert_eq!(
+            random,
+            space().candidates(SearchStrategy::Random {
+                samples: 10,
+                seed: 3
+            })
+        );
+
+        let empty = ParameterSpace {
+            rewire_radii: vec![],
+            ..space()
+        };
+        assert!(empty.candidates(SearchStrategy::Grid).is_empty());
+    }
+
+    #[test]
+    fn test_tune() {
+        // Sampling always heads away from the goal, so only a goal bias finds it in time
+        let results = tune(
+            &ProblemDefinition::new(0, 6),
+            &space(),
+            SearchStrategy::Grid,
+            &[0, 1, 2],
+            |_| || -10,
+            |step: f64| {
+                #[allow(clippy::cast_possible_truncation)]
+                let step = step as i32;
+                move |from: &i32, to: &i32| from + (to - from).clamp(-step, step)
+            },
+            |from: &i32, to: &i32| (to - from).abs() <= 2,
+            &RrtOptions::default(),
+            || MaxIterations(20).or(SolutionFound),
+            Duration::from_secs(10),
+        );
+
+        assert_eq!(results.len(), 4);
+        for result in &results[..2] {
+            assert_eq!(result.report.success.successes, 3);
+            assert!((result.parameters.goal_bias - 1.0).abs() < 1e-9);
+        }
+        assert!(results[2..]
+            .iter()
+            .all(|result| result.report.success.successes == 0));
+
+        // A zero budget evaluates nothing
+        let results = tune(
+            &ProblemDefinition::new(0, 6),
+            &space(),
+            SearchStrategy::Grid,
+            &[0],
+            |_| || -10,
+            |_| |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &RrtOptions::default(),
+            || MaxIterations(20),
+            Duration::ZERO,
+        );
+        assert!(results.is_empty());
+    }
+}
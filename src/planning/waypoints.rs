@@ -0,0 +1,190 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::WaypointError;
+use crate::planning::rrt::{grow_tree, solution_path, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::TerminationCondition;
+use crate::tree::{Distance, HashTree};
+use std::hash::Hash;
+
+/// Plans a single path through an ordered sequence of waypoints using RRT.
+///
+/// Each consecutive pair of waypoints is planned as a separate segment with the same
+/// sampling, extension, and connectable functions, so any state captured by them (e.g. an
+/// RNG or world model) carries over between segments. `make_termination` is called once per
+/// segment to construct a fresh termination condition.
+///
+/// The segment paths are concatenated, dropping the duplicated waypoint at each join,
+/// and the result is passed through `post_process_fn` before being returned, e.g. to
+/// smooth or shortcut the full path. Pass `|path| path` to skip post-processing.
+///
+/// Each segment continues growing the previous segment's tree, rerooted at the waypoint it
+/// reached with [`HashTree::reroot`], so nodes explored earlier are reused. This reverses
+/// the edges leading to that waypoint, so motions must be reversible. The tree as of the
+/// end of each segment is returned in segment order along with the path. Diagnostics from
+/// every segment are accumulated in `stats`.
+///
+/// # Errors
+///
+/// If fewer than two waypoints are provided, or a segment could not be planned before
+/// terminating. The error contains the index of the offending segment and why planning it
+/// failed.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_waypoints<T, FS, FE, FC, MT, TC, FP>(
+    waypoints: &[T],
+    mut sample_fn: FS,
+    mut extend_fn: FE,
+    mut connectable_fn: FC,
+    options: &RrtOptions,
+    mut make_termination: MT,
+    post_process_fn: FP,
+    stats: &mut PlannerStats<T>,
+) -> Result<(Vec<T>, Vec<HashTree<T>>), WaypointError>
+where
+    T: Eq + Clone + Hash + Distance,
+    FS: FnMut() -> T,
+    FE: FnMut(&T, &T) -> T,
+    FC: FnMut(&T, &T) -> bool,
+    MT: FnMut() -> TC,
+    TC: TerminationCondition,
+    FP: FnOnce(Vec<T>) -> Vec<T>,
+{
+    if waypoints.len() < 2 {
+        return Err(WaypointError::TooFewWaypoints);
+    }
+
+    let mut path: Vec<T> = Vec::new();
+    let mut trees: Vec<HashTree<T>> = Vec::with_capacity(waypoints.len() - 1);
+    for (segment, pair) in waypoints.windows(2).enumerate() {
+        let (start, goal) = (&pair[0], &pair[1]);
+
+        // The previous segment reached this segment's start
+        let mut tree = match trees.last() {
+            Some(previous) => previous
+                .reroot(start)
+                .map_err(|error| WaypointError::Segment {
+                    segment,
+                    error: error.into(),
+                })?,
+            None => HashTree::new(start.clone()),
+        };
+        grow_tree(
+            &mut tree,
+            goal,
+            &mut sample_fn,
+            &mut extend_fn,
+            &mut connectable_fn,
+            options,
+            make_termination(),
+            stats,
+        );
+
+        let segment_path = solution_path(&tree, goal, options, stats)
+            .map_err(|error| WaypointError::Segment { segment, error })?;
+
+        // Every segment after the first starts with the previous segment's goal
+        let skip = usize::from(!path.is_empty());
+        path.extend(segment_path.into_iter().skip(skip));
+        trees.push(tree);
+    }
+
+    Ok((post_process_fn(path), trees))
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PlanningError;
+    use crate::planning::termination::{MaxIterations, SolutionFound};
+
+    fn run_waypoints(waypoints: &[i32]) -> Result<(Vec<i32>, Vec<HashTree<i32>>), WaypointError> {
+        let mut samples = [-10, 20].into_iter().cycle();
+        plan_waypoints(
+            waypoints,
+            || samples.next().unwrap(),
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            // The region between 12 and 15 is blocked
+            |from: &i32, to: &i32| (to - from).abs() <= 1 && !(12..=15).contains(to),
+            &RrtOptions::default(),
+            || MaxIterations(100).or(SolutionFound),
+            |path| path,
+            &mut PlannerStats::new(),
+        )
+    }
+
+    #[test]
+    fn test_plan_waypoints() {
+        let (path, trees) = run_waypoints(&[0, 3, -2, 1]).unwrap();
+        assert_eq!(path, vec![0, 1, 2, 3, 2, 1, 0, -1, -2, -1, 0, 1]);
+        assert_eq!(trees.len(), 3);
+        assert_eq!(trees[1].path(&-2).unwrap(), vec![3, 2, 1, 0, -1, -2]);
+
+        // Later segments reuse the earlier trees, rooted at their own start
+        assert_eq!(trees[2].root(), &-2);
+        assert!(trees[0]
+            .iter_depth_first()
+            .all(|node| trees[2].contains(node)));
+    }
+
+    #[test]
+    fn test_plan_waypoints_post_process() {
+        let mut samples = [-10, 20].into_iter().cycle();
+        let (path, _) = plan_waypoints(
+            &[0, 2, 4],
+            || samples.next().unwrap(),
+            |from: &i32, to: &i32| from + (to - from).signum(),
+            |from: &i32, to: &i32| (to - from).abs() <= 1,
+            &RrtOptions::default(),
+            || MaxIterations(100).or(SolutionFound),
+            |path: Vec<i32>| path.into_iter().step_by(2).collect(),
+            &mut PlannerStats::new(),
+        )
+        .unwrap();
+        assert_eq!(path, vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn test_plan_waypoints_failure() {
+        let err = run_waypoints(&[0, 5, 20, 0]).unwrap_err();
+        assert_eq!(
+            err,
+            WaypointError::Segment {
+                segment: 1,
+                error: PlanningError::MaxIterationsReached,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Segment 1: Reached the iteration limit before finding a path"
+        );
+
+        assert_eq!(
+            run_waypoints(&[0]).unwrap_err(),
+            WaypointError::TooFewWaypoints
+        );
+    }
+}
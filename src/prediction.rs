@@ -0,0 +1,348 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::float::check_resolution;
+use std::fmt;
+use std::sync::Arc;
+
+/// Predicts where a moving obstacle will be, for planning in space and time.
+///
+/// Obstacles are modelled as balls whose center follows the predicted trajectory and
+/// whose radius grows with the uncertainty of the prediction. Times are in seconds from
+/// when the prediction was made, in the same units the planner uses for its time
+/// coordinate.
+pub trait ObstaclePredictor {
+    /// Returns the predicted center of the obstacle at `time`.
+    fn position_at(&self, time: f64) -> Vec<f64>;
+
+    /// Returns the radius of the ball the obstacle may occupy at `time`, including any
+    /// uncertainty in the prediction.
+    fn radius_at(&self, time: f64) -> f64;
+}
+
+/// Predicts that an obstacle keeps moving at its current velocity, becoming less certain
+/// at a constant rate.
+///
+/// Negative times are treated as the time of the prediction.
+///
+/// ```
+/// use rustplanning::prediction::{ConstantVelocity, ObstaclePredictor};
+///
+/// let obstacle = ConstantVelocity::new(vec![0.0, 0.0], vec![1.0, 0.5], 0.5)
+///     .with_radius_growth(0.1);
+/// assert_eq!(obstacle.position_at(2.0), vec![2.0, 1.0]);
+/// assert!((obstacle.radius_at(2.0) - 0.7).abs() < 1e-12);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantVelocity {
+    /// Position of the obstacle's center when the prediction was made.
+    pub position: Vec<f64>,
+
+    /// Velocity of the obstacle's center, per second.
+    pub velocity: Vec<f64>,
+
+    /// Radius of the obstacle when the prediction was made.
+    pub radius: f64,
+
+    /// How quickly the radius grows with uncertainty, per second.
+    pub radius_growth: f64,
+}
+
+impl ConstantVelocity {
+    /// Construct a prediction for an obstacle of `radius` at `position` moving with
+    /// `velocity`, with no growth in uncertainty.
+    ///
+    /// # Panics
+    ///
+    /// If the position and velocity have different dimensions.
+    #[must_use]
+    pub fn new(position: Vec<f64>, velocity: Vec<f64>, radius: f64) -> Self {
+        assert_eq!(
+            position.len(),
+            velocity.len(),
+            "position and velocity must have the same dimension"
+        );
+        ConstantVelocity {
+            position,
+            velocity,
+            radius,
+            radius_growth: 0.0,
+        }
+    }
+
+    /// Grow the radius by `rate` per second to account for uncertainty in the velocity.
+    #[must_use]
+    pub fn with_radius_growth(mut self, rate: f64) -> Self {
+        self.radius_growth = rate;
+        self
+    }
+}
+
+impl ObstaclePredictor for ConstantVelocity {
+    fn position_at(&self, time: f64) -> Vec<f64> {
+        let time = time.max(0.0);
+        self.position
+            .iter()
+            .zip(&self.velocity)
+            .map(|(p, v)| p + v * time)
+            .collect()
+    }
+
+    fn radius_at(&self, time: f64) -> f64 {
+        self.radius + self.radius_growth * time.max(0.0)
+    }
+}
+
+/// Checks positions and motions at given times against predicted obstacles.
+///
+/// The robot is a ball of `robot_radius`, and a position is free at a time if it is
+/// outside every obstacle's predicted ball at that time, inflated by the robot's radius.
+/// Motions are checked at intervals of at most `resolution` seconds, assuming the robot
+/// moves in a straight line at constant speed.
+///
+/// ```
+/// use rustplanning::prediction::{ConstantVelocity, SpaceTimeChecker};
+///
+/// // An obstacle crossing the origin at t = 1
+/// let checker = SpaceTimeChecker::new(0.5)
+///     .with_obstacle(ConstantVelocity::new(vec![-3.0, 0.0], vec![3.0, 0.0], 0.5));
+/// assert!(checker.is_free(&[0.0, 0.0], 0.0));
+/// assert!(!checker.is_free(&[0.0, 0.0], 1.0));
+///
+/// // Waiting at the origin is not safe, but waiting out of the way is
+/// assert!(!checker.is_free_motion(&[0.0, 0.0], 0.0, &[0.0, 0.0], 2.0));
+/// assert!(checker.is_free_motion(&[0.0, 2.0], 0.0, &[0.0, 2.0], 2.0));
+/// ```
+#[derive(Clone)]
+pub struct SpaceTimeChecker {
+    obstacles: Vec<Arc<dyn ObstaclePredictor + Send + Sync>>,
+    robot_radius: f64,
+    resolution: f64,
+}
+
+impl SpaceTimeChecker {
+    /// Construct a checker with no obstacles for a robot of `robot_radius`, checking
+    /// motions every 0.1 seconds.
+    #[must_use]
+    pub fn new(robot_radius: f64) -> Self {
+        SpaceTimeChecker {
+            obstacles: Vec::new(),
+            robot_radius,
+            resolution: 0.1,
+        }
+    }
+
+    /// Add an obstacle with its predicted motion.
+    #[must_use]
+    pub fn with_obstacle(
+        mut self,
+        obstacle: impl ObstaclePredictor + Send + Sync + 'static,
+    ) -> Self {
+        self.obstacles.push(Arc::new(obstacle));
+        self
+    }
+
+    /// Check motions at intervals of at most `resolution` seconds.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    #[must_use]
+    pub fn with_resolution(mut self, resolution: f64) -> Self {
+        check_resolution(resolution);
+        self.resolution = resolution;
+        self
+    }
+
+    /// Returns the number of obstacles.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.obstacles.len()
+    }
+
+    /// Returns true if there are no obstacles.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.obstacles.is_empty()
+    }
+
+    /// Returns the distance from the robot's surface to the nearest predicted obstacle at
+    /// `time`, negative if they overlap, or infinite if there are no obstacles.
+    #[must_use]
+    pub fn clearance(&self, point: &[f64], time: f64) -> f64 {
+        self.obstacles
+            .iter()
+            .map(|obstacle| {
+                let center = obstacle.position_at(time);
+                let distance = point
+                    .iter()
+                    .zip(&center)
+                    .map(|(p, c)| (p - c).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                distance - obstacle.radius_at(time) - self.robot_radius
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Returns true if the robot at `point` is clear of every obstacle at `time`.
+    #[must_use]
+    pub fn is_free(&self, point: &[f64], time: f64) -> bool {
+        self.clearance(point, time) > 0.0
+    }
+
+    /// Returns true if the robot can move in a straight line from `from` at `from_time`
+    /// to `to` at `to_time` without touching an obstacle. Motions backwards in time are
+    /// never free, nor are motions of infinite duration.
+    #[must_use]
+    pub fn is_free_motion(&self, from: &[f64], from_time: f64, to: &[f64], to_time: f64) -> bool {
+        let duration = to_time - from_time;
+        if !duration.is_finite() || duration < 0.0 {
+            return false;
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let steps = ((duration / self.resolution).ceil() as usize).max(1);
+        #[allow(clippy::cast_precision_loss)]
+        (0..=steps).all(|step| {
+            let t = step as f64 / steps as f64;
+            let point: Vec<f64> = from.iter().zip(to).map(|(a, b)| a + (b - a) * t).collect();
+            self.is_free(&point, from_time + duration * t)
+        })
+    }
+}
+
+impl fmt::Debug for SpaceTimeChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpaceTimeChecker")
+            .field("obstacles", &self.obstacles.len())
+            .field("robot_radius", &self.robot_radius)
+            .field("resolution", &self.resolution)
+            .finish()
+    }
+}
+
+/// Returns a state validity check for space time planning.
+///
+/// `state_fn` splits a state into its workspace position and time.
+pub fn space_time_validity<T, FS>(
+    checker: SpaceTimeChecker,
+    mut state_fn: FS,
+) -> impl FnMut(&T) -> bool
+where
+    FS: FnMut(&T) -> (Vec<f64>, f64),
+{
+    move |state: &T| {
+        let (point, time) = state_fn(state);
+        checker.is_free(&point, time)
+    }
+}
+
+/// Returns a motion validity check for space time planning, which rejects motions that go
+/// backwards in time or pass through a predicted obstacle.
+///
+/// `state_fn` splits a state into its workspace position and time.
+pub fn space_time_motion<T, FS>(
+    checker: SpaceTimeChecker,
+    mut state_fn: FS,
+) -> impl FnMut(&T, &T) -> bool
+where
+    FS: FnMut(&T) -> (Vec<f64>, f64),
+{
+    move |from: &T, to: &T| {
+        let (from_point, from_time) = state_fn(from);
+        let (to_point, to_time) = state_fn(to);
+        checker.is_free_motion(&from_point, from_time, &to_point, to_time)
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn test_constant_velocity() {
+        let obstacle = ConstantVelocity::new(vec![1.0, 2.0, 3.0], vec![1.0, 0.0, -1.0], 0.5)
+            .with_radius_growth(0.25);
+        assert_eq!(obstacle.position_at(0.0), vec![1.0, 2.0, 3.0]);
+        assert_eq!(obstacle.position_at(2.0), vec![3.0, 2.0, 1.0]);
+        assert!(approx_eq!(f64, obstacle.radius_at(2.0), 1.0));
+
+        // The past is where the obstacle is now
+        assert_eq!(obstacle.position_at(-1.0), vec![1.0, 2.0, 3.0]);
+        assert!(approx_eq!(f64, obstacle.radius_at(-1.0), 0.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "same dimension")]
+    fn test_constant_velocity_dimension() {
+        let _ = ConstantVelocity::new(vec![0.0, 0.0], vec![1.0], 1.0);
+    }
+
+    #[test]
+    fn test_space_time_checker() {
+        let checker = SpaceTimeChecker::new(0.5)
+            .with_obstacle(ConstantVelocity::new(vec![0.0, 0.0], vec![1.0, 0.0], 0.5))
+            .with_obstacle(
+                ConstantVelocity::new(vec![10.0, 0.0], vec![0.0, 0.0], 0.5).with_radius_growth(1.0),
+            );
+        assert_eq!(checker.len(), 2);
+        assert!(approx_eq!(f64, checker.clearance(&[0.0, 3.0], 0.0), 2.0));
+        assert!(approx_eq!(f64, checker.clearance(&[4.0, 0.0], 2.0), 1.0));
+        assert!(!checker.is_free(&[4.0, 0.0], 4.0));
+
+        // The uncertainty around the still obstacle reaches further over time
+        assert!(checker.is_free(&[7.0, 0.0], 1.0));
+        assert!(!checker.is_free(&[7.0, 0.0], 2.5));
+        assert!(SpaceTimeChecker::new(1.0).is_empty());
+        assert!(SpaceTimeChecker::new(1.0)
+            .clearance(&[0.0], 0.0)
+            .is_infinite());
+    }
+
+    #[test]
+    fn test_space_time_motion() {
+        let checker = SpaceTimeChecker::new(0.1)
+            .with_obstacle(ConstantVelocity::new(vec![0.0, -5.0], vec![0.0, 1.0], 0.4))
+            .with_resolution(0.05);
+
+        // Crossing the obstacle's path before it arrives is fine, but not as it passes
+        assert!(checker.is_free_motion(&[-2.0, 0.0], 0.0, &[2.0, 0.0], 2.0));
+        assert!(!checker.is_free_motion(&[-2.0, 0.0], 3.0, &[2.0, 0.0], 7.0));
+        assert!(!checker.is_free_motion(&[-2.0, 0.0], 2.0, &[-2.0, 0.0], 1.0));
+
+        let mut connectable = space_time_motion(checker.clone(), |state: &[f64; 3]| {
+            (state[..2].to_vec(), state[2])
+        });
+        assert!(connectable(&[-2.0, 0.0, 0.0], &[2.0, 0.0, 2.0]));
+        assert!(!connectable(&[-2.0, 0.0, 3.0], &[2.0, 0.0, 7.0]));
+
+        let mut valid =
+            space_time_validity(checker, |state: &[f64; 3]| (state[..2].to_vec(), state[2]));
+        assert!(valid(&[0.0, 0.0, 0.0]));
+        assert!(!valid(&[0.0, 0.0, 5.0]));
+    }
+}
@@ -0,0 +1,500 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::planning::info::PlannerInfo;
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::PlannerProgress;
+use crate::tree::{Distance, HashTree};
+use std::fmt::{self, Write as _};
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::time::Duration;
+
+const SCENE_SIZE: f64 = 600.0;
+const CURVE_WIDTH: f64 = 600.0;
+const CURVE_HEIGHT: f64 = 300.0;
+const MARGIN: f64 = 20.0;
+
+/// Records how the best solution cost evolves over a planning run.
+///
+/// Feed it from a termination condition, e.g.
+/// `MaxIterations(1000).or(|p: &PlannerProgress| { convergence.record(p); false })`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Convergence {
+    points: Vec<(Duration, f64)>,
+}
+
+impl Convergence {
+    /// Construct an empty recording.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the best cost if it improves on the last recorded cost.
+    pub fn record(&mut self, progress: &PlannerProgress) {
+        let Some(cost) = progress.best_cost else {
+            return;
+        };
+        if self.points.last().is_none_or(|&(_, last)| cost < last) {
+            self.points.push((progress.elapsed, cost));
+        }
+    }
+
+    /// Returns the recorded `(elapsed, best cost)` pairs in the order they occurred.
+    #[must_use]
+    pub fn points(&self) -> &[(Duration, f64)] {
+        &self.points
+    }
+}
+
+/// A self-contained HTML summary of a single planning run.
+///
+/// Bundles the planner configuration, stats, a 2D rendering of the scene, and the
+/// convergence curve into one file with no external scripts or stylesheets, so it can be
+/// archived or attached to a bug report as is. States are drawn with a caller provided
+/// projection to `(x, y)`.
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    title: String,
+    info: Option<PlannerInfo>,
+    stats: Vec<(String, String)>,
+    obstacles: Vec<Vec<(f64, f64)>>,
+    edges: Vec<((f64, f64), (f64, f64))>,
+    path: Vec<(f64, f64)>,
+    convergence: Vec<(Duration, f64)>,
+}
+
+impl RunReport {
+    /// Construct an empty report with the given title.
+    pub fn new(title: impl Into<String>) -> Self {
+        RunReport {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Include the planner and configuration that produced the run.
+    #[must_use]
+    pub fn with_info(mut self, info: PlannerInfo) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Append a named statistic.
+    #[must_use]
+    pub fn with_stat(mut self, name: impl Into<String>, value: impl fmt::Display) -> Self {
+        self.stats.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Append the counters and phase timings from a planner's stats.
+    #[must_use]
+    pub fn with_stats<T>(self, stats: &PlannerStats<T>) -> Self {
+        self.with_stat("goal attempts", stats.goal_attempts)
+            .with_stat("goal rejections", stats.goal_rejections.len())
+            .with_stat("goal skips", stats.goal_skips)
+            .with_stat("duplicate states", stats.duplicate_states)
+            .with_stat("discarded states", stats.discarded_states)
+            .with_stat("tree errors", stats.tree_errors.len())
+            .with_stat("timings", stats.timings)
+    }
+
+    /// Draw an obstacle as a closed polygon.
+    #[must_use]
+    pub fn with_obstacle(mut self, polygon: Vec<(f64, f64)>) -> Self {
+        self.obstacles.push(polygon);
+        self
+    }
+
+    /// Draw every edge of the tree.
+    #[must_use]
+    pub fn with_tree<T, F>(mut self, tree: &HashTree<T>, mut position_fn: F) -> Self
+    where
+        T: Eq + Clone + Hash + Distance,
+        F: FnMut(&T) -> (f64, f64),
+    {
+        for node in tree.iter_insertion_order() {
+            if let Some(parent) = tree.get_parent(node) {
+                self.edges.push((position_fn(parent), position_fn(node)));
+            }
+        }
+        self
+    }
+
+    /// Draw edges between pairs of states, e.g. the edges of a roadmap.
+    #[must_use]
+    pub fn with_edges<'a, T, I, F>(mut self, edges: I, mut position_fn: F) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = (&'a T, &'a T)>,
+        F: FnMut(&T) -> (f64, f64),
+    {
+        for (from, to) in edges {
+            self.edges.push((position_fn(from), position_fn(to)));
+        }
+        self
+    }
+
+    /// Draw the solution path.
+    #[must_use]
+    pub fn with_path<T, F>(mut self, path: &[T], position_fn: F) -> Self
+    where
+        F: FnMut(&T) -> (f64, f64),
+    {
+        self.path = path.iter().map(position_fn).collect();
+        self
+    }
+
+    /// Plot the recorded convergence curve.
+    #[must_use]
+    pub fn with_convergence(mut self, convergence: &Convergence) -> Self {
+        self.convergence = convergence.points().to_vec();
+        self
+    }
+
+    /// Renders the report as a standalone HTML document.
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        // Writing to a String cannot fail
+        let _ = self.render(&mut html);
+        html
+    }
+
+    /// Renders only the scene as a standalone SVG image, e.g. for a gallery of runs.
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        let mut svg = String::new();
+        // Writing to a String cannot fail
+        let _ = self.render_scene(&mut svg);
+        svg
+    }
+
+    /// Writes the report as a standalone HTML document.
+    ///
+    /// # Errors
+    ///
+    /// If writing fails.
+    pub fn write_html<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.to_html().as_bytes())
+    }
+
+    fn render(&self, html: &mut String) -> fmt::Result {
+        let title = escape(&self.title);
+        writeln!(html, "<!DOCTYPE html>")?;
+        writeln!(
+            html,
+            "<html><head><meta charset=\"utf-8\"><title>{title}</title>"
+        )?;
+        writeln!(
+            html,
+            "<style>body{{font-family:sans-serif;margin:2em}}\
+             table{{border-collapse:collapse}}\
+             td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}\
+             svg{{border:1px solid #ccc;background:#fff}}</style>"
+        )?;
+        writeln!(html, "</head><body>")?;
+        writeln!(html, "<h1>{title}</h1>")?;
+
+        if let Some(info) = &self.info {
+            writeln!(html, "<h2>Configuration</h2>")?;
+            let mut rows = vec![("planner".to_string(), info.name.clone())];
+            rows.extend(info.seed.map(|seed| ("seed".to_string(), seed.to_string())));
+            rows.extend(info.parameters.iter().cloned());
+            render_table(html, &rows)?;
+        }
+
+        if !self.stats.is_empty() {
+            writeln!(html, "<h2>Stats</h2>")?;
+            render_table(html, &self.stats)?;
+        }
+
+        if !(self.obstacles.is_empty() && self.edges.is_empty() && self.path.is_empty()) {
+            writeln!(html, "<h2>Scene</h2>")?;
+            self.render_scene(html)?;
+        }
+
+        if !self.convergence.is_empty() {
+            writeln!(html, "<h2>Convergence</h2>")?;
+            self.render_convergence(html)?;
+        }
+
+        writeln!(html, "</body></html>")
+    }
+
+    fn render_scene(&self, html: &mut String) -> fmt::Result {
+        let points = self
+            .obstacles
+            .iter()
+            .flatten()
+            .chain(self.edges.iter().flat_map(|(a, b)| [a, b]))
+            .chain(&self.path);
+        let projection = Projection::fit(points, SCENE_SIZE, SCENE_SIZE, true);
+
+        writeln!(
+            html,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{SCENE_SIZE}\" \
+             height=\"{SCENE_SIZE}\">"
+        )?;
+        for polygon in &self.obstacles {
+            writeln!(
+                html,
+                "<polygon points=\"{}\" fill=\"#888\" stroke=\"#444\"/>",
+                projection.points(polygon)
+            )?;
+        }
+        for (from, to) in &self.edges {
+            let (x1, y1) = projection.apply(*from);
+            let (x2, y2) = projection.apply(*to);
+            writeln!(
+                html,
+                "<line x1=\"{x1:.2}\" y1=\"{y1:.2}\" x2=\"{x2:.2}\" y2=\"{y2:.2}\" \
+                 stroke=\"#6a9fd4\" stroke-width=\"1\"/>"
+            )?;
+        }
+        if !self.path.is_empty() {
+            writeln!(
+                html,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"#d62728\" stroke-width=\"3\"/>",
+                projection.points(&self.path)
+            )?;
+        }
+        writeln!(html, "</svg>")
+    }
+
+    fn render_convergence(&self, html: &mut String) -> fmt::Result {
+        let points: Vec<(f64, f64)> = self
+            .convergence
+            .iter()
+            .map(|(elapsed, cost)| (elapsed.as_secs_f64(), *cost))
+            .collect();
+        let projection = Projection::fit(points.iter(), CURVE_WIDTH, CURVE_HEIGHT, false);
+
+        // Best cost is a step function, hold each value until the next improvement
+        let mut steps = Vec::with_capacity(points.len() * 2);
+        for (i, &(time, cost)) in points.iter().enumerate() {
+            if i > 0 {
+                steps.push((time, points[i - 1].1));
+            }
+            steps.push((time, cost));
+        }
+
+        let (first, last) = (points[0], points[points.len() - 1]);
+        writeln!(
+            html,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{CURVE_WIDTH}\" \
+             height=\"{CURVE_HEIGHT}\">"
+        )?;
+        writeln!(
+            html,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"#2ca02c\" stroke-width=\"2\"/>",
+            projection.points(&steps)
+        )?;
+        writeln!(
+            html,
+            "<text x=\"{MARGIN}\" y=\"{}\" font-size=\"12\">{:.3}s, cost {:.3}</text>",
+            CURVE_HEIGHT - 4.0,
+            first.0,
+            first.1
+        )?;
+        writeln!(
+            html,
+            "<text x=\"{}\" y=\"{MARGIN}\" font-size=\"12\" text-anchor=\"end\">\
+             {:.3}s, cost {:.3}</text>",
+            CURVE_WIDTH - MARGIN,
+            last.0,
+            last.1
+        )?;
+        writeln!(html, "</svg>")
+    }
+}
+
+fn render_table(html: &mut String, rows: &[(String, String)]) -> fmt::Result {
+    writeln!(html, "<table>")?;
+    for (name, value) in rows {
+        writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            escape(name),
+            escape(value)
+        )?;
+    }
+    writeln!(html, "</table>")
+}
+
+// Escapes text for use in HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Maps world coordinates into an SVG canvas with a margin, flipping y so it points up
+struct Projection {
+    min: (f64, f64),
+    scale: (f64, f64),
+    height: f64,
+}
+
+impl Projection {
+    fn fit<'a>(
+        points: impl Iterator<Item = &'a (f64, f64)>,
+        width: f64,
+        height: f64,
+        equal_aspect: bool,
+    ) -> Self {
+        let mut min = (f64::INFINITY, f64::INFINITY);
+        let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in points {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        if min.0 > max.0 {
+            min = (0.0, 0.0);
+            max = (1.0, 1.0);
+        }
+
+        // Degenerate ranges are drawn in the middle of the canvas
+        let span = |low: f64, high: f64| if high > low { high - low } else { 1.0 };
+        let mut scale = (
+            (width - 2.0 * MARGIN) / span(min.0, max.0),
+            (height - 2.0 * MARGIN) / span(min.1, max.1),
+        );
+        if equal_aspect {
+            let common = scale.0.min(scale.1);
+            scale = (common, common);
+        }
+        Projection { min, scale, height }
+    }
+
+    fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            MARGIN + (x - self.min.0) * self.scale.0,
+            self.height - MARGIN - (y - self.min.1) * self.scale.1,
+        )
+    }
+
+    fn points(&self, points: &[(f64, f64)]) -> String {
+        points
+            .iter()
+            .map(|&point| {
+                let (x, y) = self.apply(point);
+                format!("{x:.2},{y:.2}")
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(elapsed: u64, best_cost: Option<f64>) -> PlannerProgress {
+        PlannerProgress {
+            iteration: elapsed,
+            elapsed: Duration::from_secs(elapsed),
+            best_cost,
+        }
+    }
+
+    #[test]
+    fn test_convergence() {
+        let mut convergence = Convergence::new();
+        convergence.record(&progress(0, None));
+        convergence.record(&progress(1, Some(5.0)));
+        convergence.record(&progress(2, Some(5.0)));
+        convergence.record(&progress(3, Some(4.0)));
+        assert_eq!(
+            convergence.points(),
+            &[(Duration::from_secs(1), 5.0), (Duration::from_secs(3), 4.0)]
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("a<b> & \"c\"'"),
+            "a&lt;b&gt; &amp; &quot;c&quot;&#39;"
+        );
+    }
+
+    #[test]
+    fn test_run_report() {
+        let mut tree = HashTree::new(0);
+        tree.add_child(&0, 1).unwrap();
+        tree.add_child(&1, 2).unwrap();
+
+        let mut convergence = Convergence::new();
+        convergence.record(&progress(1, Some(3.0)));
+        convergence.record(&progress(2, Some(2.0)));
+
+        let report = RunReport::new("Run <1>")
+            .with_info(PlannerInfo::new("RRT*").with_seed(42))
+            .with_stats(&PlannerStats::<i32>::new())
+            .with_obstacle(vec![(0.0, 1.0), (1.0, 1.0), (1.0, 2.0)])
+            .with_tree(&tree, |&x| (f64::from(x), 0.0))
+            .with_path(&[0, 1, 2], |&x| (f64::from(x), 0.0))
+            .with_convergence(&convergence);
+
+        let html = report.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Run &lt;1&gt;</title>"));
+        assert!(html.contains("<th>seed</th><td>42</td>"));
+        assert!(html.contains("<th>goal attempts</th><td>0</td>"));
+        assert_eq!(html.matches("<polygon").count(), 1);
+        assert_eq!(html.matches("<line").count(), 2);
+        assert_eq!(html.matches("<polyline").count(), 2);
+        assert!(!html.contains("<script"));
+
+        let mut bytes = Vec::new();
+        report.write_html(&mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), html);
+
+        let svg = report.to_svg();
+        assert!(svg.starts_with("<svg xmlns="));
+        assert_eq!(svg.matches("<line").count(), 2);
+
+        let roadmap =
+            RunReport::new("Roadmap").with_edges([(&0, &1), (&0, &2)], |&x| (f64::from(x), 1.0));
+        assert_eq!(roadmap.to_svg().matches("<line").count(), 2);
+
+        // Sections without data are left out
+        let html = RunReport::new("Empty").to_html();
+        assert!(!html.contains("<svg"));
+        assert!(!html.contains("<table"));
+    }
+}
@@ -0,0 +1,221 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::SceneError;
+use crate::grid::OccupancyGrid;
+use std::cell::OnceCell;
+
+/// An object carried by the robot, modeled as a disc fixed in the robot's frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttachedObject {
+    /// Position of the object's center relative to the robot's center.
+    pub offset: (f64, f64),
+
+    /// Radius of the object.
+    pub radius: f64,
+}
+
+impl AttachedObject {
+    /// Radius of the smallest disc centered on the robot that contains the object in any
+    /// orientation.
+    #[must_use]
+    pub fn reach(&self) -> f64 {
+        self.offset.0.hypot(self.offset.1) + self.radius
+    }
+}
+
+/// Owns the environment and the robot's shape for a planning problem.
+///
+/// The robot is a disc, grown to enclose any attached objects so that the same checks
+/// hold for any heading. Collision checks run against a copy of the world inflated by the
+/// resulting footprint, which is built on first use and rebuilt whenever the world or the
+/// attached objects change. This allows e.g. pick and place to plan with and without a
+/// carried object in the same environment without rebuilding anything by hand.
+#[derive(Debug, Clone)]
+pub struct PlanningScene {
+    world: OccupancyGrid,
+    robot_radius: f64,
+    attached: Vec<(String, AttachedObject)>,
+    collision_grid: OnceCell<OccupancyGrid>,
+}
+
+impl PlanningScene {
+    /// Construct a scene for a disc shaped robot of `robot_radius` with nothing attached.
+    #[must_use]
+    pub fn new(world: OccupancyGrid, robot_radius: f64) -> Self {
+        PlanningScene {
+            world,
+            robot_radius,
+            attached: Vec::new(),
+            collision_grid: OnceCell::new(),
+        }
+    }
+
+    /// Returns the environment.
+    #[must_use]
+    pub fn world(&self) -> &OccupancyGrid {
+        &self.world
+    }
+
+    /// Returns the environment for modification, invalidating the collision cache.
+    pub fn world_mut(&mut self) -> &mut OccupancyGrid {
+        self.collision_grid = OnceCell::new();
+        &mut self.world
+    }
+
+    /// Attaches an object to the robot under a unique name.
+    ///
+    /// # Errors
+    ///
+    /// If an object is already attached with the same name.
+    pub fn attach(
+        &mut self,
+        name: impl Into<String>,
+        object: AttachedObject,
+    ) -> Result<(), SceneError> {
+        let name = name.into();
+        if self.is_attached(&name) {
+            return Err(SceneError::AlreadyAttached(name));
+        }
+        self.attached.push((name, object));
+        self.collision_grid = OnceCell::new();
+        Ok(())
+    }
+
+    /// Detaches the named object, returning it.
+    ///
+    /// # Errors
+    ///
+    /// If no object with that name is attached.
+    pub fn detach(&mut self, name: &str) -> Result<AttachedObject, SceneError> {
+        let index = self
+            .attached
+            .iter()
+            .position(|(attached, _)| attached == name)
+            .ok_or_else(|| SceneError::NotAttached(name.into()))?;
+        self.collision_grid = OnceCell::new();
+        Ok(self.attached.remove(index).1)
+    }
+
+    /// Returns true if an object with the given name is attached.
+    #[must_use]
+    pub fn is_attached(&self, name: &str) -> bool {
+        self.attached.iter().any(|(attached, _)| attached == name)
+    }
+
+    /// Returns the attached objects and their names, in the order they were attached.
+    #[must_use]
+    pub fn attached(&self) -> &[(String, AttachedObject)] {
+        &self.attached
+    }
+
+    /// Radius of the robot's footprint including any attached objects.
+    #[must_use]
+    pub fn footprint_radius(&self) -> f64 {
+        self.attached
+            .iter()
+            .map(|(_, object)| object.reach())
+            .fold(self.robot_radius, f64::max)
+    }
+
+    /// Returns the world inflated by the current footprint, in which the robot can be
+    /// treated as a point.
+    pub fn collision_grid(&self) -> &OccupancyGrid {
+        self.collision_grid
+            .get_or_init(|| self.world.inflated(self.footprint_radius()))
+    }
+
+    /// Returns true if the robot and everything attached to it are clear of obstacles at
+    /// the world coordinate.
+    #[must_use]
+    pub fn is_free(&self, x: f64, y: f64) -> bool {
+        self.collision_grid().is_free(x, y)
+    }
+
+    /// Returns true if the robot and everything attached to it stay clear of obstacles
+    /// moving in a straight line between two world coordinates.
+    #[must_use]
+    pub fn is_segment_free(&self, from: (f64, f64), to: (f64, f64)) -> bool {
+        self.collision_grid().is_segment_free(from, to)
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::CellState;
+
+    // A 10x10 world with a single obstacle cell centered at (5.5, 5.5)
+    fn scene() -> PlanningScene {
+        let mut world = OccupancyGrid::filled(10, 10, 1.0, CellState::Free);
+        world.set_state(5, 5, CellState::Occupied).unwrap();
+        PlanningScene::new(world, 0.5)
+    }
+
+    #[test]
+    fn test_attach_detach() {
+        let mut scene = scene();
+        let object = AttachedObject {
+            offset: (1.0, 0.0),
+            radius: 1.0,
+        };
+        assert!(scene.is_free(3.5, 5.5));
+        assert!(scene.is_segment_free((3.5, 5.5), (3.5, 1.5)));
+
+        // Carrying the object grows the footprint and invalidates the cache
+        scene.attach("box", object).unwrap();
+        assert_eq!(
+            scene.attach("box", object),
+            Err(SceneError::AlreadyAttached("box".into()))
+        );
+        assert!(scene.is_attached("box"));
+        assert_eq!(scene.attached().len(), 1);
+        assert!((scene.footprint_radius() - 2.0).abs() < 1e-9);
+        assert!(!scene.is_free(3.5, 5.5));
+        assert!(!scene.is_segment_free((3.5, 1.5), (3.5, 8.5)));
+        assert!(scene.is_free(1.5, 5.5));
+
+        assert_eq!(scene.detach("box").unwrap(), object);
+        assert_eq!(
+            scene.detach("box"),
+            Err(SceneError::NotAttached("box".into()))
+        );
+        assert!((scene.footprint_radius() - 0.5).abs() < 1e-9);
+        assert!(scene.is_free(3.5, 5.5));
+    }
+
+    #[test]
+    fn test_world_changes() {
+        let mut scene = scene();
+        assert!(scene.is_free(1.5, 1.5));
+        scene
+            .world_mut()
+            .set_state(1, 1, CellState::Occupied)
+            .unwrap();
+        assert!(!scene.is_free(1.5, 1.5));
+        assert_eq!(scene.world().state(1, 1), Some(CellState::Occupied));
+    }
+}
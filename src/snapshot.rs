@@ -0,0 +1,125 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+/// A copy of a tree's structure with each state projected to a position, for rendering.
+///
+/// Snapshots own their data and do not depend on the tree's state type, so they can be
+/// sent to a visualization or UI thread while the planner keeps growing the live tree.
+/// Create one with [`crate::tree::HashTree::snapshot`].
+///
+/// Nodes are in the order they were added to the tree, with the root first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeSnapshot<const N: usize> {
+    /// Projected position of each node.
+    pub positions: Vec<[f64; N]>,
+
+    /// Index of each node's parent, `None` for the root.
+    pub parents: Vec<Option<usize>>,
+
+    /// Cost to reach each node from the root.
+    pub costs: Vec<f64>,
+}
+
+impl<const N: usize> TreeSnapshot<N> {
+    /// Returns the number of nodes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns true if there are no nodes, which is only the case for a default snapshot.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Iterates over the edges as the positions of the parent and child.
+    pub fn edges(&self) -> impl Iterator<Item = ([f64; N], [f64; N])> + '_ {
+        self.parents
+            .iter()
+            .zip(&self.positions)
+            .filter_map(|(parent, &child)| parent.map(|parent| (self.positions[parent], child)))
+    }
+
+    /// Returns the positions along the path from the root to the node at `index`.
+    ///
+    /// # Panics
+    ///
+    /// If the index is out of range.
+    #[must_use]
+    pub fn path_to(&self, index: usize) -> Vec<[f64; N]> {
+        let mut path = vec![self.positions[index]];
+        let mut current = self.parents[index];
+        while let Some(parent) = current {
+            path.push(self.positions[parent]);
+            current = self.parents[parent];
+        }
+        path.reverse();
+        path
+    }
+}
+
+impl<const N: usize> Default for TreeSnapshot<N> {
+    fn default() -> Self {
+        TreeSnapshot {
+            positions: Vec::new(),
+            parents: Vec::new(),
+            costs: Vec::new(),
+        }
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::HashTree;
+
+    #[test]
+    fn test_tree_snapshot() {
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert!(tree.add_child(&0, 2).is_ok());
+        assert!(tree.add_child(&2, 3).is_ok());
+        assert!(tree.add_child(&0, -1).is_ok());
+
+        let snapshot = tree.snapshot(|x| [f64::from(*x), 0.0]);
+        assert_eq!(snapshot.len(), 4);
+        assert_eq!(snapshot.parents, vec![None, Some(0), Some(1), Some(0)]);
+        assert_eq!(snapshot.costs, vec![0.0, 2.0, 3.0, 1.0]);
+        assert_eq!(
+            snapshot.path_to(2),
+            vec![[0.0, 0.0], [2.0, 0.0], [3.0, 0.0]]
+        );
+
+        let edges: Vec<_> = snapshot
+            .edges()
+            .map(|(from, to)| (from[0], to[0]))
+            .collect();
+        assert_eq!(edges, vec![(0.0, 2.0), (2.0, 3.0), (0.0, -1.0)]);
+
+        // The snapshot is unaffected by later changes to the tree
+        assert!(tree.add_child(&3, 4).is_ok());
+        assert_eq!(snapshot.len(), 4);
+    }
+}
@@ -0,0 +1,407 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::TrajectoryError;
+use crate::interpolation::Interpolate;
+use crate::path::{path_length, point_at_distance, Pose2D};
+
+/// Kinematic limits along a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionLimits {
+    pub max_velocity: f64,
+    pub max_acceleration: f64,
+    pub max_jerk: f64,
+}
+
+/// A jerk limited (S-curve) motion profile that moves a given distance from rest to rest.
+///
+/// The profile is made up of seven phases of constant jerk: jerk up, constant acceleration,
+/// and jerk down to reach the peak velocity, an optional cruise, then the mirror image to
+/// decelerate. Acceleration is continuous, which avoids the vibration caused by the
+/// instantaneous acceleration changes of a trapezoidal profile. If the distance is too short
+/// to reach the limits, the peak acceleration and velocity are reduced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SCurveProfile {
+    length: f64,
+
+    // Duration and jerk of each phase
+    phases: [(f64, f64); 7],
+
+    // Distance, velocity, and acceleration at the start of each phase
+    starts: [(f64, f64, f64); 7],
+}
+
+// Advances the (distance, velocity, acceleration) state by `t` under constant `jerk`
+fn integrate((s, v, a): (f64, f64, f64), jerk: f64, t: f64) -> (f64, f64, f64) {
+    (
+        s + v * t + a * t * t / 2.0 + jerk * t * t * t / 6.0,
+        v + a * t + jerk * t * t / 2.0,
+        a + jerk * t,
+    )
+}
+
+impl SCurveProfile {
+    /// Constructs the fastest profile covering `length` within the limits.
+    ///
+    /// # Errors
+    ///
+    /// If the length is negative or any limit is not positive.
+    pub fn new(length: f64, limits: &MotionLimits) -> Result<Self, TrajectoryError> {
+        let MotionLimits {
+            max_velocity,
+            max_acceleration,
+            max_jerk,
+        } = *limits;
+        if length.is_nan() || length < 0.0 {
+            return Err(TrajectoryError::InvalidLength(length));
+        }
+        if !(max_velocity > 0.0 && max_acceleration > 0.0 && max_jerk > 0.0) {
+            return Err(TrajectoryError::InvalidLimits);
+        }
+
+        // Jerk and constant acceleration durations needed to reach a velocity from rest
+        let ramp = |velocity: f64| {
+            if velocity * max_jerk >= max_acceleration * max_acceleration {
+                let jerk_time = max_acceleration / max_jerk;
+                (jerk_time, velocity / max_acceleration - jerk_time)
+            } else {
+                ((velocity / max_jerk).sqrt(), 0.0)
+            }
+        };
+
+        // The velocity profile is symmetric, so the distance to accelerate to a velocity is
+        // the velocity times half the time taken.
+        let ramp_distance = |velocity: f64| {
+            let (jerk_time, accel_time) = ramp(velocity);
+            velocity * (2.0 * jerk_time + accel_time) / 2.0
+        };
+
+        // Reduce the peak velocity until accelerating and decelerating fits in the length
+        let mut peak = max_velocity;
+        if 2.0 * ramp_distance(peak) > length {
+            let (mut low, mut high) = (0.0, max_velocity);
+            for _ in 0..100 {
+                peak = f64::midpoint(low, high);
+                if 2.0 * ramp_distance(peak) > length {
+                    high = peak;
+                } else {
+                    low = peak;
+                }
+            }
+            peak = low;
+        }
+        let (jerk_time, accel_time) = ramp(peak);
+        let cruise_time = if peak > 0.0 {
+            ((length - 2.0 * ramp_distance(peak)) / peak).max(0.0)
+        } else {
+            0.0
+        };
+
+        let phases = [
+            (jerk_time, max_jerk),
+            (accel_time, 0.0),
+            (jerk_time, -max_jerk),
+            (cruise_time, 0.0),
+            (jerk_time, -max_jerk),
+            (accel_time, 0.0),
+            (jerk_time, max_jerk),
+        ];
+        let mut starts = [(0.0, 0.0, 0.0); 7];
+        for i in 1..7 {
+            let (duration, jerk) = phases[i - 1];
+            starts[i] = integrate(starts[i - 1], jerk, duration);
+        }
+
+        Ok(SCurveProfile {
+            length,
+            phases,
+            starts,
+        })
+    }
+
+    /// Total distance covered by the profile.
+    #[must_use]
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// Total time taken by the profile.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        self.phases.iter().map(|(duration, _)| duration).sum()
+    }
+
+    /// Returns the distance, velocity, and acceleration at time `t`, clamped to the
+    /// duration of the profile.
+    #[must_use]
+    pub fn sample(&self, t: f64) -> (f64, f64, f64) {
+        let mut remaining = t.max(0.0);
+        for (&(duration, jerk), &start) in self.phases.iter().zip(&self.starts) {
+            if remaining <= duration {
+                let (s, v, a) = integrate(start, jerk, remaining);
+                return (s.min(self.length), v, a);
+            }
+            remaining -= duration;
+        }
+        (self.length, 0.0, 0.0)
+    }
+}
+
+/// A sample of a trajectory along a 2D path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryPoint {
+    /// Time since the start of the trajectory.
+    pub time: f64,
+
+    /// Position on the path.
+    pub position: (f64, f64),
+
+    /// Distance traveled along the path.
+    pub distance: f64,
+
+    /// Speed along the path.
+    pub velocity: f64,
+
+    /// Acceleration along the path.
+    pub acceleration: f64,
+}
+
+/// Time parameterizes a 2D path with a jerk limited S-curve profile, sampled every `dt`.
+///
+/// The path is followed from rest to rest at the speed given by an [`SCurveProfile`] over
+/// its total length. The final sample is always at the end of the trajectory. The limits
+/// apply along the path, so the path should be smoothed beforehand if it has sharp corners.
+///
+/// # Errors
+///
+/// If the path is empty, `dt` is not positive, or the limits are invalid.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+pub fn s_curve_trajectory(
+    path: &[(f64, f64)],
+    limits: &MotionLimits,
+    dt: f64,
+) -> Result<Vec<TrajectoryPoint>, TrajectoryError> {
+    if path.is_empty() {
+        return Err(TrajectoryError::EmptyPath);
+    }
+    if dt.is_nan() || dt <= 0.0 {
+        return Err(TrajectoryError::InvalidTimeStep(dt));
+    }
+
+    let profile = SCurveProfile::new(path_length(path), limits)?;
+    let duration = profile.duration();
+    let steps = (duration / dt).ceil() as usize;
+    Ok((0..=steps)
+        .map(|i| {
+            let time = (i as f64 * dt).min(duration);
+            let (distance, velocity, acceleration) = profile.sample(time);
+            TrajectoryPoint {
+                time,
+                position: point_at_distance(path, distance),
+                distance,
+                velocity,
+                acceleration,
+            }
+        })
+        .collect())
+}
+
+/// Time parameterizes a path of poses as in [`s_curve_trajectory`], following the motion
+/// between poses given by `interpolation`, e.g. a curvature continuous
+/// [`crate::interpolation::PoseInterpolation::QuinticHermite`].
+///
+/// Each motion is sampled with points no more than `resolution` apart along the straight
+/// line between its poses, so the trajectory follows the same motion as a
+/// [`crate::validity::discretized_motion`] validator using the same interpolation.
+///
+/// # Errors
+///
+/// If the path is empty, `resolution` or `dt` is not positive, or the limits are invalid.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+pub fn s_curve_pose_trajectory<I: Interpolate<Pose2D>>(
+    path: &[Pose2D],
+    interpolation: &I,
+    resolution: f64,
+    limits: &MotionLimits,
+    dt: f64,
+) -> Result<Vec<TrajectoryPoint>, TrajectoryError> {
+    if resolution.is_nan() || resolution <= 0.0 {
+        return Err(TrajectoryError::InvalidResolution(resolution));
+    }
+
+    let mut points: Vec<(f64, f64)> = path.first().map(|p| (p.0, p.1)).into_iter().collect();
+    for pair in path.windows(2) {
+        let length = (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1);
+        let steps = ((length / resolution).ceil() as usize).max(1);
+        points.extend((1..=steps).map(|step| {
+            let pose = interpolation.interpolate(&pair[0], &pair[1], step as f64 / steps as f64);
+            (pose.0, pose.1)
+        }));
+    }
+    s_curve_trajectory(&points, limits, dt)
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::approx_eq;
+    use std::f64::consts::FRAC_PI_2;
+
+    const LIMITS: MotionLimits = MotionLimits {
+        max_velocity: 1.0,
+        max_acceleration: 1.0,
+        max_jerk: 2.0,
+    };
+
+    fn assert_within_limits(profile: &SCurveProfile, limits: &MotionLimits) {
+        let duration = profile.duration();
+        let mut previous = profile.sample(0.0);
+        for i in 1..=1000 {
+            let sample = profile.sample(duration * f64::from(i) / 1000.0);
+            assert!(sample.0 >= previous.0 - 1e-9, "Distance must not decrease");
+            assert!(sample.1 <= limits.max_velocity + 1e-9);
+            assert!(sample.2.abs() <= limits.max_acceleration + 1e-9);
+            previous = sample;
+        }
+    }
+
+    #[test]
+    fn test_full_profile() {
+        // Accelerating to full speed takes 0.5s of jerk, 0.5s constant acceleration, and 0.5s
+        // of jerk, covering 0.75m. The remaining 8.5m are at full speed.
+        let profile = SCurveProfile::new(10.0, &LIMITS).unwrap();
+        assert!(approx_eq!(f64, profile.duration(), 11.5, epsilon = 1e-9));
+        assert_within_limits(&profile, &LIMITS);
+
+        let (s, v, a) = profile.sample(0.5);
+        assert!(approx_eq!(f64, s, 2.0 / 6.0 * 0.125, epsilon = 1e-9));
+        assert!(approx_eq!(f64, v, 0.25, epsilon = 1e-9));
+        assert!(approx_eq!(f64, a, 1.0, epsilon = 1e-9));
+
+        let (s, v, a) = profile.sample(1.5);
+        assert!(approx_eq!(f64, s, 0.75, epsilon = 1e-9));
+        assert!(approx_eq!(f64, v, 1.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, a, 0.0, epsilon = 1e-9));
+
+        let (s, v, a) = profile.sample(profile.duration());
+        assert!(approx_eq!(f64, s, 10.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, v, 0.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, a, 0.0, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_short_profile() {
+        // Too short to reach either the velocity or acceleration limit
+        let profile = SCurveProfile::new(0.1, &LIMITS).unwrap();
+        assert_within_limits(&profile, &LIMITS);
+        let (s, v, _) = profile.sample(profile.duration());
+        assert!(approx_eq!(f64, s, 0.1, epsilon = 1e-6));
+        assert!(approx_eq!(f64, v, 0.0, epsilon = 1e-6));
+
+        let profile = SCurveProfile::new(0.0, &LIMITS).unwrap();
+        assert!(approx_eq!(f64, profile.duration(), 0.0));
+    }
+
+    #[test]
+    fn test_invalid_profile() {
+        assert_eq!(
+            SCurveProfile::new(-1.0, &LIMITS),
+            Err(TrajectoryError::InvalidLength(-1.0))
+        );
+        assert!(SCurveProfile::new(f64::NAN, &LIMITS).is_err());
+        let limits = MotionLimits {
+            max_jerk: 0.0,
+            ..LIMITS
+        };
+        assert_eq!(
+            SCurveProfile::new(1.0, &limits),
+            Err(TrajectoryError::InvalidLimits)
+        );
+    }
+
+    #[test]
+    fn test_s_curve_trajectory() {
+        let path = [(0.0, 0.0), (5.0, 0.0), (5.0, 5.0)];
+        let trajectory = s_curve_trajectory(&path, &LIMITS, 0.1).unwrap();
+        assert_eq!(trajectory[0].position, (0.0, 0.0));
+
+        let end = trajectory.last().unwrap();
+        assert!(approx_eq!(f64, end.time, 11.5, epsilon = 1e-9));
+        assert!(approx_eq!(f64, end.position.0, 5.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, end.position.1, 5.0, epsilon = 1e-9));
+
+        // Halfway through the trajectory is halfway along the path
+        let middle = trajectory[trajectory.len() / 2];
+        assert!(approx_eq!(f64, middle.time, 5.8, epsilon = 1e-9));
+        assert!(approx_eq!(f64, middle.velocity, 1.0, epsilon = 1e-9));
+
+        assert_eq!(
+            s_curve_trajectory(&[], &LIMITS, 0.1),
+            Err(TrajectoryError::EmptyPath)
+        );
+        assert_eq!(
+            s_curve_trajectory(&path, &LIMITS, 0.0),
+            Err(TrajectoryError::InvalidTimeStep(0.0))
+        );
+    }
+    #[test]
+    fn test_s_curve_pose_trajectory() {
+        use crate::interpolation::{Linear, PoseInterpolation};
+
+        // Straight lines between poses match the positional trajectory
+        let poses = [(0.0, 0.0, 0.0), (5.0, 0.0, 0.0), (5.0, 5.0, 0.0)];
+        let linear = s_curve_pose_trajectory(&poses, &Linear, 0.5, &LIMITS, 0.1).unwrap();
+        let expected = s_curve_trajectory(&[(0.0, 0.0), (5.0, 0.0), (5.0, 5.0)], &LIMITS, 0.1);
+        assert_eq!(linear.len(), expected.unwrap().len());
+
+        // Curving through the corner is shorter, and never leaves the path's bounding box
+        let poses = [(0.0, 0.0, 0.0), (5.0, 5.0, FRAC_PI_2)];
+        let interpolation = PoseInterpolation::QuinticHermite;
+        let curved = s_curve_pose_trajectory(&poses, &interpolation, 0.1, &LIMITS, 0.1).unwrap();
+        let end = curved.last().unwrap();
+        assert!(end.time < 11.5);
+        assert!(approx_eq!(f64, end.position.0, 5.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, end.position.1, 5.0, epsilon = 1e-9));
+        assert!(curved.iter().all(|point| {
+            (-1e-9..=5.0 + 1e-9).contains(&point.position.0)
+                && (-1e-9..=5.0 + 1e-9).contains(&point.position.1)
+        }));
+
+        // Points along the trajectory are on the interpolated curve
+        let quarter = interpolation.interpolate(&poses[0], &poses[1], 0.25);
+        assert!(curved.iter().any(|point| {
+            (point.position.0 - quarter.0).hypot(point.position.1 - quarter.1) < 0.1
+        }));
+
+        assert_eq!(
+            s_curve_pose_trajectory(&poses, &Linear, 0.0, &LIMITS, 0.1),
+            Err(TrajectoryError::InvalidResolution(0.0))
+        );
+        assert!(s_curve_pose_trajectory(&[], &Linear, 0.1, &LIMITS, 0.1).is_err());
+    }
+}
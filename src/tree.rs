@@ -20,11 +20,21 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
 
 use linked_hash_set::LinkedHashSet;
 
+use crate::error::TreeError;
+use crate::float::{check_resolution, quantize};
+use crate::geofence::Region;
+use crate::kdtree::KdTree;
+use crate::snapshot::TreeSnapshot;
+
 /// Basic node element for the tree.
 ///
 /// Must be used with [Tree] since children are referenced by index in the [Tree]'s node vector.
@@ -42,25 +52,39 @@ struct Node<T> {
     // Maintains a set of pointers to the children's location in the tree's node list.
     // Using a linked hash set to maintain order for tree traversals.
     children: LinkedHashSet<usize>,
+
+    // When the node was added, if the tree was recording it.
+    provenance: Option<Provenance>,
 }
 
 impl<T> Node<T> {
     fn new(value: T, parent: Option<usize>, cost: f64) -> Self {
         Node {
-            value: value,
-            parent: parent,
-            cost: cost,
+            value,
+            parent,
+            cost,
             children: LinkedHashSet::new(),
+            provenance: None,
         }
     }
 }
 
+/// When a node was added to a [`HashTree`] by a planner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    /// The planner iteration that added the node.
+    pub iteration: u64,
+
+    /// Time since planning started when the node was added.
+    pub elapsed: Duration,
+}
+
 /// Define a distance trait for tree node values.
 pub trait Distance {
     fn distance(&self, other: &Self) -> f64;
 }
 
-/// DFS Iterator for a [Tree]
+/// DFS Iterator for a [`HashTree`]
 pub struct DepthFirstIterator<'a, T>
 where
     T: 'a + Eq + Clone + Distance + Hash,
@@ -101,13 +125,166 @@ where
     }
 }
 
-/// HashTree for use in RRT based-search algorithms.
+/// How [`HashTree`] nearest neighbor queries order nodes that are the same distance away.
+///
+/// Either way results are deterministic for a given sequence of insertions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// Prefer the node that was added to the tree first.
+    #[default]
+    InsertionOrder,
+
+    /// Prefer the node with the lowest cost from the root, then the node added first.
+    LowestCost,
+}
+
+/// Cost of moving from one state to another, used in place of [`Distance`] when computing
+/// path costs in a [`HashTree`], e.g. battery use or elevation gain.
+///
+/// Nearest neighbor and radius queries still use [`Distance`]. Costs may be asymmetric,
+/// and are always queried from parent to child. Costs may also depend on the state before
+/// the parent, e.g. to penalize turning, see [`EdgeCost::with_predecessor`].
+#[derive(Clone)]
+pub struct EdgeCost<T>(Arc<EdgeCostFn<T>>);
+
+type EdgeCostFn<T> = dyn Fn(Option<&T>, &T, &T) -> f64 + Send + Sync;
+
+impl<T> EdgeCost<T> {
+    /// Wraps a function returning the cost of moving from its first to its second state.
+    pub fn new<F>(cost_fn: F) -> Self
+    where
+        F: Fn(&T, &T) -> f64 + Send + Sync + 'static,
+    {
+        EdgeCost(Arc::new(move |_: Option<&T>, from: &T, to: &T| {
+            cost_fn(from, to)
+        }))
+    }
+
+    /// Wraps a function returning the cost of moving from its second to its third state,
+    /// given the state before the second if there is one.
+    ///
+    /// Note that an edge's cost then changes when its parent is moved, which
+    /// [`HashTree::set_parent`] accounts for when updating the costs below the moved node.
+    pub fn with_predecessor<F>(cost_fn: F) -> Self
+    where
+        F: Fn(Option<&T>, &T, &T) -> f64 + Send + Sync + 'static,
+    {
+        EdgeCost(Arc::new(cost_fn))
+    }
+
+    /// Returns the cost of moving from `from` to `to`, having arrived at `from` from
+    /// `previous`.
+    pub fn cost(&self, previous: Option<&T>, from: &T, to: &T) -> f64 {
+        (self.0)(previous, from, to)
+    }
+
+    /// Returns the total cost of a path.
+    pub fn path_cost(&self, path: &[T]) -> f64 {
+        path.windows(2)
+            .enumerate()
+            .map(|(i, edge)| self.cost(i.checked_sub(1).map(|j| &path[j]), &edge[0], &edge[1]))
+            .sum()
+    }
+}
+
+impl<T> fmt::Debug for EdgeCost<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EdgeCost")
+    }
+}
+
+/// Quantizes states to a grid so that a [`HashTree`] can find nodes nearly identical to a
+/// state in constant time, see [`HashTree::set_dedup_grid`].
+#[derive(Clone)]
+pub struct DedupGrid<T> {
+    coordinates: Arc<CoordinatesFn<T>>,
+    resolution: f64,
+}
+
+type CoordinatesFn<T> = dyn Fn(&T) -> Vec<f64> + Send + Sync;
+
+impl<T> DedupGrid<T> {
+    /// States are in the same cell if their `coordinates_fn` rounded to the nearest
+    /// multiple of `resolution` are equal, as for [`crate::float::Quantized`].
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    pub fn new<F>(coordinates_fn: F, resolution: f64) -> Self
+    where
+        F: Fn(&T) -> Vec<f64> + Send + Sync + 'static,
+    {
+        check_resolution(resolution);
+        DedupGrid {
+            coordinates: Arc::new(coordinates_fn),
+            resolution,
+        }
+    }
+
+    /// Returns the size of the grid's cells.
+    #[must_use]
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    /// Returns the cell containing a state.
+    pub fn cell(&self, state: &T) -> Vec<i64> {
+        quantize(&(self.coordinates)(state), self.resolution)
+    }
+}
+
+impl<T> fmt::Debug for DedupGrid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DedupGrid")
+            .field("resolution", &self.resolution)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Maps states to coordinates so that a [`HashTree`] can answer nearest neighbor queries
+/// with a [`KdTree`] rather than by comparing against every node, see
+/// [`HashTree::set_spatial_index`].
+///
+/// Queries return the same nodes as without the index as long as the [`Distance`] between
+/// two states is never less than the difference in any one of their coordinates. E.g. a
+/// pose can be indexed by its position alone if its distance adds a heading term to the
+/// Euclidean distance between positions.
+#[derive(Clone)]
+pub struct SpatialIndex<T> {
+    coordinates: Arc<CoordinatesFn<T>>,
+}
+
+impl<T> SpatialIndex<T> {
+    /// Indexes states by their `coordinates_fn`, which must return the same number of
+    /// coordinates for every state.
+    pub fn new<F>(coordinates_fn: F) -> Self
+    where
+        F: Fn(&T) -> Vec<f64> + Send + Sync + 'static,
+    {
+        SpatialIndex {
+            coordinates: Arc::new(coordinates_fn),
+        }
+    }
+
+    /// Returns the coordinates of a state.
+    pub fn coordinates(&self, state: &T) -> Vec<f64> {
+        (self.coordinates)(state)
+    }
+}
+
+impl<T> fmt::Debug for SpatialIndex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpatialIndex").finish_non_exhaustive()
+    }
+}
+
+/// `HashTree` for use in RRT based-search algorithms.
 ///
 /// Provides functions for creating, growing, finding the nearest neighbors to `T`,
 /// and rewiring are provided.
 /// Node values must be unique and hashable to support constant time lookups.
+/// Nearest neighbor queries scan every node unless a [`SpatialIndex`] is set.
 ///
-/// TODO: Make this a KD Tree?
 /// TODO: Is a hashmap dumb?
 /// TODO: Is there a more efficient way to manage ownership of T?
 #[derive(Debug)]
@@ -120,6 +297,27 @@ where
 
     // Support constant time lookup of nodes data with a value - node index map.
     nodes_map: HashMap<T, usize>,
+
+    // Ordering of equidistant nodes in nearest neighbor queries.
+    tie_break: TieBreak,
+
+    // Provenance recorded for newly added nodes.
+    stamp: Option<Provenance>,
+
+    // Cost of each edge, or the distance between its nodes if not set.
+    edge_cost: Option<EdgeCost<T>>,
+
+    // Grid for finding near duplicates.
+    dedup_grid: Option<DedupGrid<T>>,
+
+    // The first node added in each occupied cell of the dedup grid.
+    dedup_cells: HashMap<Vec<i64>, usize>,
+
+    // Maps states to the coordinates indexed in the k-d tree.
+    spatial_index: Option<SpatialIndex<T>>,
+
+    // Indices of all nodes by their coordinates, if there is a spatial index.
+    kd_tree: KdTree,
 }
 
 impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
@@ -135,7 +333,214 @@ impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
         nodes.push(root_node);
         nodes_map.insert(val, 0);
 
-        HashTree { nodes, nodes_map }
+        HashTree {
+            nodes,
+            nodes_map,
+            tie_break: TieBreak::default(),
+            stamp: None,
+            edge_cost: None,
+            dedup_grid: None,
+            dedup_cells: HashMap::new(),
+            spatial_index: None,
+            kd_tree: KdTree::new(),
+        }
+    }
+
+    /// Construct a new tree with the specified value as the root node, where path costs
+    /// are measured with `edge_cost` rather than [`Distance`].
+    pub fn with_edge_cost(val: T, edge_cost: EdgeCost<T>) -> Self {
+        let mut tree = Self::new(val);
+        tree.edge_cost = Some(edge_cost);
+        tree
+    }
+
+    /// Returns the cost of an edge from `parent` to `child`, whether or not the child is in
+    /// the tree. If the parent is in the tree its own parent is passed to the edge cost as
+    /// the predecessor.
+    pub fn edge_cost(&self, parent: &T, child: &T) -> f64 {
+        match &self.edge_cost {
+            Some(edge_cost) => edge_cost.cost(self.get_parent(parent), parent, child),
+            None => child.distance(parent),
+        }
+    }
+
+    /// Sets how nearest neighbor queries order nodes that are the same distance away.
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
+    }
+
+    /// Returns how nearest neighbor queries order nodes that are the same distance away.
+    #[must_use]
+    pub fn tie_break(&self) -> TieBreak {
+        self.tie_break
+    }
+
+    /// Sets the provenance recorded for every node added from now on, or stops recording
+    /// it if `None`.
+    pub fn set_provenance_stamp(&mut self, stamp: Option<Provenance>) {
+        self.stamp = stamp;
+    }
+
+    /// Returns when the node was added, if the tree was recording provenance at the time.
+    #[must_use]
+    pub fn provenance(&self, val: &T) -> Option<Provenance> {
+        self.get_node(val).and_then(|node| node.provenance)
+    }
+
+    /// Indexes nodes by the cell of `grid` they are in, so that [`HashTree::near_duplicate`]
+    /// finds nodes nearly identical to a state in constant time, or stops indexing if
+    /// `None`. Nodes already in the tree are indexed immediately.
+    ///
+    /// The tree itself still accepts near duplicates, it is up to planners to merge them,
+    /// e.g. RRT handles them like exact duplicates according to its
+    /// [`crate::planning::rrt::DuplicatePolicy`]. This keeps long anytime runs from
+    /// accumulating nearly identical nodes that slow down nearest neighbor queries.
+    pub fn set_dedup_grid(&mut self, grid: Option<DedupGrid<T>>) {
+        self.dedup_grid = grid;
+        self.reindex_cells();
+    }
+
+    /// Returns the first node added in the same dedup grid cell as `val`, which may be `val`
+    /// itself. Always `None` if no grid is set.
+    #[must_use]
+    pub fn near_duplicate(&self, val: &T) -> Option<&T> {
+        let grid = self.dedup_grid.as_ref()?;
+        self.dedup_cells
+            .get(&grid.cell(val))
+            .map(|&index| &self.nodes[index].value)
+    }
+
+    // Adds a node to its dedup grid cell, if no other node is already there.
+    fn index_cell(&mut self, index: usize) {
+        if let Some(grid) = &self.dedup_grid {
+            self.dedup_cells
+                .entry(grid.cell(&self.nodes[index].value))
+                .or_insert(index);
+        }
+    }
+
+    fn reindex_cells(&mut self) {
+        self.dedup_cells.clear();
+        for index in 0..self.nodes.len() {
+            self.index_cell(index);
+        }
+    }
+
+    /// Indexes nodes by their coordinates in `index`, so that nearest neighbor and radius
+    /// queries take logarithmic rather than linear time in the size of the tree, or goes
+    /// back to scanning every node if `None`. Nodes already in the tree are indexed
+    /// immediately.
+    pub fn set_spatial_index(&mut self, index: Option<SpatialIndex<T>>) {
+        self.spatial_index = index;
+        self.reindex_points();
+    }
+
+    /// Returns the spatial index of the tree, if set.
+    #[must_use]
+    pub fn spatial_index(&self) -> Option<&SpatialIndex<T>> {
+        self.spatial_index.as_ref()
+    }
+
+    // Adds a node to the k-d tree.
+    fn index_point(&mut self, index: usize) {
+        if let Some(spatial_index) = &self.spatial_index {
+            let point = spatial_index.coordinates(&self.nodes[index].value);
+            self.kd_tree.insert(index, point);
+        }
+    }
+
+    // Rebuilds a balanced k-d tree of all nodes.
+    fn reindex_points(&mut self) {
+        self.kd_tree = match &self.spatial_index {
+            Some(spatial_index) => KdTree::build(
+                self.nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, node)| (index, spatial_index.coordinates(&node.value))),
+            ),
+            None => KdTree::new(),
+        };
+    }
+
+    /// Returns the nodes whose spatial index coordinates are inside `region`, in the order
+    /// they were added to the tree. Only nodes within the region's bounding box are
+    /// checked.
+    ///
+    /// # Errors
+    ///
+    /// If the tree has no spatial index.
+    pub fn nodes_in_region(&self, region: &Region) -> Result<Vec<&T>, TreeError> {
+        if self.spatial_index.is_none() {
+            return Err(TreeError::NoSpatialIndex);
+        }
+        let (lower, upper) = region.bounding_box();
+        let mut found: Vec<usize> = self
+            .kd_tree
+            .in_box(&lower, &upper)
+            .into_iter()
+            .filter(|(_, point)| region.contains(point))
+            .map(|(index, _)| index)
+            .collect();
+        found.sort_unstable();
+        Ok(found
+            .into_iter()
+            .map(|index| &self.nodes[index].value)
+            .collect())
+    }
+
+    /// Returns the nodes for which `contains` is true, in the order they were added to the
+    /// tree.
+    ///
+    /// With a spatial index only nodes with coordinates between `lower` and `upper` are
+    /// checked, so the box must contain every state that `contains` accepts. Dimensions
+    /// past the end of either limit are unbounded. Every node is checked without an index.
+    pub fn nodes_in_box_where<F>(&self, lower: &[f64], upper: &[f64], mut contains: F) -> Vec<&T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut candidates: Vec<usize> = if self.spatial_index.is_some() {
+            let in_box = self.kd_tree.in_box(lower, upper);
+            in_box.into_iter().map(|(index, _)| index).collect()
+        } else {
+            (0..self.nodes.len()).collect()
+        };
+        candidates.sort_unstable();
+        candidates
+            .into_iter()
+            .map(|index| &self.nodes[index].value)
+            .filter(|value| contains(value))
+            .collect()
+    }
+
+    fn distance_to(&self, val: &T, index: usize) -> f64 {
+        val.distance(&self.nodes[index].value)
+    }
+
+    // Returns the indices and distances of nodes within the radius, in insertion order.
+    fn indices_within(&self, val: &T, radius: f64) -> Vec<(usize, f64)> {
+        match &self.spatial_index {
+            Some(spatial_index) => {
+                let point = spatial_index.coordinates(val);
+                let mut found = self
+                    .kd_tree
+                    .within(&point, radius, |index| self.distance_to(val, index));
+                found.sort_unstable_by_key(|&(index, _)| index);
+                found
+            }
+            None => (0..self.nodes.len())
+                .map(|index| (index, self.distance_to(val, index)))
+                .filter(|(_, distance)| *distance <= radius)
+                .collect(),
+        }
+    }
+
+    // Orders nodes by distance, breaking ties with the tree's policy and finally by index.
+    fn compare_neighbors(&self, a: (usize, f64), b: (usize, f64)) -> Ordering {
+        let by_cost = match self.tie_break {
+            TieBreak::InsertionOrder => Ordering::Equal,
+            TieBreak::LowestCost => self.nodes[a.0].cost.total_cmp(&self.nodes[b.0].cost),
+        };
+        a.1.total_cmp(&b.1).then(by_cost).then(a.0.cmp(&b.0))
     }
 
     /// Adds the value to the specified node's children
@@ -144,26 +549,29 @@ impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
     ///
     /// If the parent is not found in the tree.
     /// If the child is already in the tree.
-    pub fn add_child(&mut self, parent: &T, child: T) -> Result<(), String> {
+    pub fn add_child(&mut self, parent: &T, child: T) -> Result<(), TreeError> {
         // Cannot duplicate children
         if self.nodes_map.contains_key(&child) {
-            return Err("The child is already in the tree".to_string());
+            return Err(TreeError::DuplicateNode);
         }
 
         let parent_idx = *self
             .nodes_map
             .get(parent)
-            .ok_or("The parent was not found in the tree")?;
+            .ok_or(TreeError::ParentNotFound)?;
 
-        // The cost is the parent's cost + the distance to the parent
-        let cost = self.nodes[parent_idx].cost + child.distance(parent);
-        let child_node = Node::new(child.clone(), Some(parent_idx), cost);
+        // The cost is the parent's cost + the cost of the edge from the parent
+        let cost = self.nodes[parent_idx].cost + self.edge_cost(parent, &child);
+        let mut child_node = Node::new(child.clone(), Some(parent_idx), cost);
+        child_node.provenance = self.stamp;
 
         // Append the child node to the nodes vector and note the location in the map.
         let child_idx = self.nodes.len();
         self.nodes.push(child_node);
         self.nodes_map.insert(child, child_idx);
         self.nodes[parent_idx].children.insert(child_idx);
+        self.index_cell(child_idx);
+        self.index_point(child_idx);
 
         Ok(())
     }
@@ -179,22 +587,51 @@ impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
         }
     }
 
+    /// Returns true if `ancestor` is `node` or lies on the path from the root to `node`.
+    /// False if either is not in the tree.
+    #[must_use]
+    pub fn is_ancestor(&self, ancestor: &T, node: &T) -> bool {
+        match (self.nodes_map.get(ancestor), self.nodes_map.get(node)) {
+            (Some(&ancestor), Some(&node)) => self.is_ancestor_index(ancestor, node),
+            _ => false,
+        }
+    }
+
+    fn is_ancestor_index(&self, ancestor: usize, node: usize) -> bool {
+        let mut current = Some(node);
+        while let Some(index) = current {
+            if index == ancestor {
+                return true;
+            }
+            current = self.nodes[index].parent;
+        }
+        false
+    }
+
     /// Moves the specified child to be a direct descendant of the specified parent.
-    /// Updates cost data accordingly.
+    /// Updates the costs of the child and all of its descendants accordingly.
     ///
     /// # Errors
     ///
     /// If either the child or the parent are not in the tree.
     /// If the child is the root of the tree.
-    pub fn set_parent(&mut self, child: &T, parent: &T) -> Result<(), String> {
+    /// If the parent is the child or one of its descendants, which would create a cycle.
+    ///
+    /// # Panics
+    ///
+    /// If a non-root node is missing its parent, which indicates a corrupt tree.
+    pub fn set_parent(&mut self, child: &T, parent: &T) -> Result<(), TreeError> {
         // Validate that this is a reasonable request
         let parent_idx = *self
             .nodes_map
             .get(parent)
-            .ok_or("Parent not found in tree")?;
-        let child_idx = *self.nodes_map.get(child).ok_or("Child not found in tree")?;
+            .ok_or(TreeError::ParentNotFound)?;
+        let child_idx = *self.nodes_map.get(child).ok_or(TreeError::NodeNotFound)?;
         if child_idx == 0 {
-            return Err("Cannot reparent the root of the tree!".to_string());
+            return Err(TreeError::ReparentRoot);
+        }
+        if self.is_ancestor_index(child_idx, parent_idx) {
+            return Err(TreeError::Cycle);
         }
 
         // Remove the child from the parent
@@ -205,63 +642,454 @@ impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
         self.nodes[child_idx].parent = Some(parent_idx);
         self.nodes[parent_idx].children.insert(child_idx);
 
-        // Update cost
-        let cost = self.nodes[parent_idx].cost + child.distance(parent);
-        self.nodes[child_idx].cost = cost;
+        // Update the cost of the child and everything below it
+        self.update_costs(child_idx);
 
         Ok(())
     }
 
+    /// Recomputes the cost of the node at `index` from its parent, then the costs of all of
+    /// its descendants.
+    fn update_costs(&mut self, index: usize) {
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            if let Some(parent) = self.nodes[index].parent {
+                let (parent_node, node) = (&self.nodes[parent], &self.nodes[index]);
+                let cost = parent_node.cost + self.edge_cost(&parent_node.value, &node.value);
+                self.nodes[index].cost = cost;
+            }
+            stack.extend(self.nodes[index].children.iter().copied());
+        }
+    }
+
+    /// Removes every edge for which `valid(parent, child)` returns false, e.g. after an
+    /// obstacle appears, and detaches the subtrees below them.
+    ///
+    /// Returns the detached subtrees in the order their roots were added to the tree, each
+    /// rooted at the child of an invalidated edge with costs measured from that root. A
+    /// subtree below another invalidated edge is returned separately rather than nested.
+    /// The remaining nodes keep their costs and insertion order.
+    pub fn invalidate_edges<F>(&mut self, mut valid: F) -> Vec<HashTree<T>>
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let orphaned: Vec<bool> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.parent
+                    .is_some_and(|parent| !valid(&self.nodes[parent].value, &node.value))
+            })
+            .collect();
+        if !orphaned.contains(&true) {
+            return Vec::new();
+        }
+
+        let subtrees = (0..self.nodes.len())
+            .filter(|&index| orphaned[index])
+            .map(|index| self.extract(index, &orphaned))
+            .collect();
+
+        // Keep everything still reachable from the root
+        let mut keep = vec![false; self.nodes.len()];
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            keep[index] = true;
+            stack.extend(
+                self.nodes[index]
+                    .children
+                    .iter()
+                    .filter(|&&child| !orphaned[child]),
+            );
+        }
+        self.retain(&keep);
+
+        subtrees
+    }
+
+    /// Attaches another tree below the specified node, with the other tree's root becoming
+    /// a child of `parent`. Costs in the attached nodes are updated to include the cost of
+    /// reaching `parent`.
+    ///
+    /// # Errors
+    ///
+    /// If the parent is not found in the tree.
+    /// If any node of the other tree is already in this tree, in which case nothing is added.
+    pub fn graft(&mut self, parent: &T, subtree: &HashTree<T>) -> Result<(), TreeError> {
+        let parent_idx = *self
+            .nodes_map
+            .get(parent)
+            .ok_or(TreeError::ParentNotFound)?;
+        if subtree.nodes.iter().any(|node| self.contains(&node.value)) {
+            return Err(TreeError::Overlap);
+        }
+
+        // Breadth first from the subtree's root, so every parent is added before its children
+        let mut order = vec![(0, parent_idx)];
+        let mut next = 0;
+        while let Some(&(index, new_parent)) = order.get(next) {
+            next += 1;
+            let node = &subtree.nodes[index];
+            let new_index = self.nodes.len();
+            order.extend(node.children.iter().map(|&child| (child, new_index)));
+            self.push_child(new_parent, node);
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the tree rooted at `val`, e.g. to keep searching from the goal of a
+    /// previous search. Edges on the path from the current root to `val` are reversed, and
+    /// costs are measured from the new root.
+    ///
+    /// Reversed edges are only valid motions if motions are reversible.
+    ///
+    /// # Errors
+    ///
+    /// If the node is not in the tree.
+    pub fn reroot(&self, val: &T) -> Result<HashTree<T>, TreeError> {
+        let root = *self.nodes_map.get(val).ok_or(TreeError::NodeNotFound)?;
+        let mut tree = self.empty_copy(root);
+
+        // Breadth first over edges in either direction, from the new root outwards
+        let mut pending = vec![(root, None, 0)];
+        let mut next = 0;
+        while let Some(&(index, from, new_index)) = pending.get(next) {
+            next += 1;
+            let node = &self.nodes[index];
+            let neighbors = node.parent.into_iter().chain(node.children.iter().copied());
+            for neighbor in neighbors.filter(|&neighbor| Some(neighbor) != from) {
+                let child_index = tree.nodes.len();
+                tree.push_child(new_index, &self.nodes[neighbor]);
+                pending.push((neighbor, Some(index), child_index));
+            }
+        }
+        Ok(tree)
+    }
+
+    // Returns a tree with the same settings as this one, containing only the node at `root`.
+    fn empty_copy(&self, root: usize) -> HashTree<T> {
+        let mut tree = HashTree::new(self.nodes[root].value.clone());
+        tree.tie_break = self.tie_break;
+        tree.edge_cost.clone_from(&self.edge_cost);
+        tree.nodes[0].provenance = self.nodes[root].provenance;
+        tree.set_dedup_grid(self.dedup_grid.clone());
+        tree.set_spatial_index(self.spatial_index.clone());
+        tree
+    }
+
+    // Adds a copy of `node` below the node at `parent`, keeping its provenance.
+    fn push_child(&mut self, parent: usize, node: &Node<T>) {
+        let parent_node = &self.nodes[parent];
+        let cost = parent_node.cost + self.edge_cost(&parent_node.value, &node.value);
+        let mut new_node = Node::new(node.value.clone(), Some(parent), cost);
+        new_node.provenance = node.provenance;
+
+        let index = self.nodes.len();
+        self.nodes_map.insert(node.value.clone(), index);
+        self.nodes[parent].children.insert(index);
+        self.nodes.push(new_node);
+        self.index_cell(index);
+        self.index_point(index);
+    }
+
+    // Copies the subtree rooted at `root` into a new tree, stopping at `boundary` nodes.
+    fn extract(&self, root: usize, boundary: &[bool]) -> HashTree<T> {
+        let mut tree = self.empty_copy(root);
+
+        // Breadth first, so nodes and children keep their relative order
+        let mut pending = vec![(root, 0)];
+        let mut next = 0;
+        while let Some(&(index, new_index)) = pending.get(next) {
+            next += 1;
+            for &child in &self.nodes[index].children {
+                if boundary[child] {
+                    continue;
+                }
+                pending.push((child, tree.nodes.len()));
+                tree.push_child(new_index, &self.nodes[child]);
+            }
+        }
+        tree
+    }
+
+    // Drops every node not marked to keep, which must include the root and the parents of
+    // all kept nodes.
+    fn retain(&mut self, keep: &[bool]) {
+        let mut new_index = vec![None; self.nodes.len()];
+        let kept = (0..keep.len()).filter(|&index| keep[index]);
+        for (next, index) in kept.enumerate() {
+            new_index[index] = Some(next);
+        }
+
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes_map.clear();
+        for (index, mut node) in nodes.into_iter().enumerate() {
+            if !keep[index] {
+                continue;
+            }
+            node.parent = node.parent.and_then(|parent| new_index[parent]);
+            node.children = node
+                .children
+                .iter()
+                .filter_map(|&child| new_index[child])
+                .collect();
+            self.nodes_map.insert(node.value.clone(), self.nodes.len());
+            self.nodes.push(node);
+        }
+        self.reindex_cells();
+        self.reindex_points();
+    }
+
+    /// Returns the root of the tree.
+    #[must_use]
+    pub fn root(&self) -> &T {
+        &self.nodes[0].value
+    }
+
     /// Return the size of the tree
+    #[must_use]
     pub fn size(&self) -> usize {
         self.nodes.len()
     }
 
+    /// Returns the number of edges on the longest path from the root to a leaf.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut stack = vec![(0, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            deepest = deepest.max(depth);
+            stack.extend(
+                self.nodes[index]
+                    .children
+                    .iter()
+                    .map(|&child| (child, depth + 1)),
+            );
+        }
+        deepest
+    }
+
+    /// Returns whether or not the value is in the tree
+    #[must_use]
+    pub fn contains(&self, val: &T) -> bool {
+        self.nodes_map.contains_key(val)
+    }
+
+    /// Checks the structural invariants of the tree, for tests and debugging corrupt trees.
+    ///
+    /// Every node must be indexed by its value, every node other than the root must have a
+    /// parent that lists it as a child and lead back to the root without cycles, and every
+    /// cost must equal its parent's cost plus the cost of the edge between them.
+    ///
+    /// # Errors
+    ///
+    /// Describing the first violated invariant.
+    pub fn check_invariants(&self) -> Result<(), TreeError> {
+        if self.nodes_map.len() != self.nodes.len() {
+            return Err(TreeError::Corrupt(format!(
+                "{} nodes but {} indexed values",
+                self.nodes.len(),
+                self.nodes_map.len()
+            )));
+        }
+        if self.nodes[0].parent.is_some() || self.nodes[0].cost != 0.0 {
+            return Err(TreeError::Corrupt(
+                "The root has a parent or non zero cost".to_string(),
+            ));
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if self.nodes_map.get(&node.value) != Some(&index) {
+                return Err(TreeError::Corrupt(format!(
+                    "Node {index} is not indexed by its value"
+                )));
+            }
+            if let Some(&child) = node
+                .children
+                .iter()
+                .find(|&&child| self.nodes.get(child).and_then(|c| c.parent) != Some(index))
+            {
+                return Err(TreeError::Corrupt(format!(
+                    "Node {index} lists {child} as a child, which it is not"
+                )));
+            }
+            if index == 0 {
+                continue;
+            }
+
+            let parent = node
+                .parent
+                .ok_or_else(|| TreeError::Corrupt(format!("Node {index} has no parent")))?;
+            let parent_node = self.nodes.get(parent).ok_or_else(|| {
+                TreeError::Corrupt(format!("Node {index} has missing parent {parent}"))
+            })?;
+            if !parent_node.children.contains(&index) {
+                return Err(TreeError::Corrupt(format!(
+                    "Node {index} is not listed as a child of {parent}"
+                )));
+            }
+
+            // Any path longer than the tree has repeated a node
+            let mut ancestor = parent;
+            for _ in 0..self.nodes.len() {
+                match self.nodes[ancestor].parent {
+                    Some(next) => ancestor = next,
+                    None => break,
+                }
+            }
+            if ancestor != 0 {
+                return Err(TreeError::Corrupt(format!(
+                    "Node {index} does not lead back to the root"
+                )));
+            }
+
+            let expected = parent_node.cost + self.edge_cost(&parent_node.value, &node.value);
+            if (node.cost - expected).abs() > 1e-9 * (1.0 + expected.abs()) {
+                return Err(TreeError::Corrupt(format!(
+                    "Node {index} has cost {} but its parent and edge give {expected}",
+                    node.cost
+                )));
+            }
+        }
+        if let Some(grid) = &self.dedup_grid {
+            let indexed = self
+                .nodes
+                .iter()
+                .all(|node| self.dedup_cells.contains_key(&grid.cell(&node.value)));
+            let placed = self.dedup_cells.iter().all(|(cell, &index)| {
+                index < self.nodes.len() && grid.cell(&self.nodes[index].value) == *cell
+            });
+            if !indexed || !placed {
+                return Err(TreeError::Corrupt(
+                    "The dedup grid index is out of date".to_string(),
+                ));
+            }
+        }
+        if let Some(spatial_index) = &self.spatial_index {
+            let mut indexed = vec![false; self.nodes.len()];
+            let placed = self.kd_tree.iter().all(|(index, point)| {
+                let current = index < self.nodes.len()
+                    && !indexed[index]
+                    && spatial_index.coordinates(&self.nodes[index].value) == point;
+                if current {
+                    indexed[index] = true;
+                }
+                current
+            });
+            if !placed || indexed.contains(&false) || !self.kd_tree.is_consistent() {
+                return Err(TreeError::Corrupt(
+                    "The spatial index is out of date".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Return the cost to reach a particular node
     ///
     /// # Errors
     ///
     /// If the value is not in the tree.
-    pub fn cost(&self, val: &T) -> Result<f64, String> {
-        let node_idx: usize = *self
-            .nodes_map
-            .get(val)
-            .ok_or("Specified value is not present in the tree".to_string())?;
+    pub fn cost(&self, val: &T) -> Result<f64, TreeError> {
+        let node_idx: usize = *self.nodes_map.get(val).ok_or(TreeError::NodeNotFound)?;
 
         Ok(self.nodes[node_idx].cost)
     }
 
-    /// Returns the closest element to the specified value
+    /// Returns the closest element to the specified value, with ties broken by the tree's
+    /// [`TieBreak`].
+    ///
+    /// # Panics
+    ///
+    /// If the tree is empty, which cannot happen since the root is never removed.
     pub fn nearest_neighbor(&self, val: &T) -> &T {
-        &self
-            .nodes
-            .iter()
-            .min_by(|a, b| {
-                let da = val.distance(&a.value);
-                let db = val.distance(&b.value);
-                da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
-            })
-            .unwrap()
-            .value
+        let compare = |a, b| self.compare_neighbors(a, b);
+        let nearest = match &self.spatial_index {
+            Some(spatial_index) => self.kd_tree.nearest(
+                &spatial_index.coordinates(val),
+                |index| self.distance_to(val, index),
+                compare,
+            ),
+            None => (0..self.nodes.len())
+                .map(|index| (index, self.distance_to(val, index)))
+                .min_by(|&a, &b| compare(a, b)),
+        };
+        let (index, _) = nearest.unwrap();
+        &self.nodes[index].value
     }
 
     /// Finds all nodes that are within the specified radius and returns a map of
     /// all closest elements and their values.
     pub fn nearest_neighbors(&self, val: &T, radius: f64) -> HashMap<T, f64> {
-        // First iterate over all nodes to identify all neighbors
-        let mut neighbors = HashMap::new();
-        for (_i, check) in self.nodes.iter().enumerate() {
-            let distance = val.distance(&check.value);
-            if distance <= radius {
-                neighbors.insert(check.value.clone(), distance);
-            }
-        }
+        self.indices_within(val, radius)
+            .into_iter()
+            .map(|(index, distance)| (self.nodes[index].value.clone(), distance))
+            .collect()
+    }
 
+    /// Finds all nodes that are within the specified radius and returns them along with
+    /// their distances, in the order they were added to the tree.
+    ///
+    /// Unlike [`HashTree::nearest_neighbors`] the ordering is deterministic, which planners
+    /// rely on to produce repeatable results.
+    pub fn neighbors_within(&self, val: &T, radius: f64) -> Vec<(&T, f64)> {
+        self.indices_within(val, radius)
+            .into_iter()
+            .map(|(index, distance)| (&self.nodes[index].value, distance))
+            .collect()
+    }
+
+    /// Returns up to `k` of the closest elements to the specified value along with their
+    /// distances, sorted from closest to furthest with ties ordered by the tree's
+    /// [`TieBreak`].
+    pub fn k_nearest_neighbors(&self, val: &T, k: usize) -> Vec<(&T, f64)> {
+        let neighbors = if let Some(spatial_index) = &self.spatial_index {
+            self.kd_tree.k_nearest(
+                &spatial_index.coordinates(val),
+                k,
+                |index| self.distance_to(val, index),
+                |a, b| self.compare_neighbors(a, b),
+            )
+        } else {
+            let mut neighbors: Vec<(usize, f64)> = (0..self.nodes.len())
+                .map(|index| (index, self.distance_to(val, index)))
+                .collect();
+            neighbors.sort_by(|&a, &b| self.compare_neighbors(a, b));
+            neighbors.truncate(k);
+            neighbors
+        };
         neighbors
+            .into_iter()
+            .map(|(index, distance)| (&self.nodes[index].value, distance))
+            .collect()
     }
 
-    /// Returns a [DepthFirstIterator] for the tree
-    pub fn iter_depth_first(&self) -> DepthFirstIterator<T> {
+    /// Iterates over the nodes in the order they were added to the tree, starting with the
+    /// root.
+    pub fn iter_insertion_order(&self) -> impl Iterator<Item = &T> {
+        self.nodes.iter().map(|node| &node.value)
+    }
+
+    /// Copies the structure and costs of the tree with each node projected to a position
+    /// by `projection`, e.g. `|p| [p.x, p.y]`, for rendering without holding the tree.
+    pub fn snapshot<const N: usize, F>(&self, mut projection: F) -> TreeSnapshot<N>
+    where
+        F: FnMut(&T) -> [f64; N],
+    {
+        TreeSnapshot {
+            positions: self
+                .nodes
+                .iter()
+                .map(|node| projection(&node.value))
+                .collect(),
+            parents: self.nodes.iter().map(|node| node.parent).collect(),
+            costs: self.nodes.iter().map(|node| node.cost).collect(),
+        }
+    }
+
+    /// Returns a [`DepthFirstIterator`] for the tree
+    #[must_use]
+    pub fn iter_depth_first(&self) -> DepthFirstIterator<'_, T> {
         DepthFirstIterator::new(self)
     }
 
@@ -270,21 +1098,20 @@ impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
     /// # Errors
     ///
     /// If the specified node is not found in the Tree
-    pub fn path(&self, end: &T) -> Result<Vec<T>, String> {
+    pub fn path(&self, end: &T) -> Result<Vec<T>, TreeError> {
         // Must be a valid node
-        if !self.nodes_map.contains_key(&end) {
-            return Err("Node is not present in tree".to_string());
+        if !self.nodes_map.contains_key(end) {
+            return Err(TreeError::NodeNotFound);
         }
 
         // Build the path from end to beginning
         let mut path = Vec::new();
 
         // Loop until you get to the root
-        let mut cur_idx = Some(self.nodes_map[&end]);
+        let mut cur_idx = Some(self.nodes_map[end]);
         while let Some(idx) = cur_idx {
             path.push(self.nodes[idx].value.clone());
             cur_idx = self.nodes[idx].parent;
-
         }
 
         // Reverse it to get the path in order
@@ -292,10 +1119,35 @@ impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
         Ok(path)
     }
 
+    /// Returns the remainder of the path to `end`, starting from the node on it nearest to
+    /// `pose`. Useful when the robot has drifted from the root between planning and
+    /// execution.
+    ///
+    /// # Errors
+    ///
+    /// If the specified node is not found in the tree.
+    /// If no node on the path is within `tolerance` of the pose.
+    pub fn path_from_nearest(
+        &self,
+        end: &T,
+        pose: &T,
+        tolerance: f64,
+    ) -> Result<Vec<T>, TreeError> {
+        let mut path = self.path(end)?;
+        let (nearest, _) = path
+            .iter()
+            .map(|node| pose.distance(node))
+            .enumerate()
+            .filter(|&(_, distance)| distance <= tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .ok_or(TreeError::OutOfTolerance)?;
+        path.drain(..nearest);
+        Ok(path)
+    }
+
     /// Returns the node with the specified value
     ///
     /// Returns None if the specified value is not in the tree.
-    #[allow(dead_code)]
     fn get_node(&self, val: &T) -> Option<&Node<T>> {
         self.nodes_map
             .get(val)
@@ -303,6 +1155,23 @@ impl<T: Eq + Clone + Distance + Hash> HashTree<T> {
     }
 }
 
+impl<T: Eq + Clone + Distance + Hash> fmt::Display for HashTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (min, max) = self
+            .nodes
+            .iter()
+            .fold((f64::INFINITY, 0.0f64), |(min, max), node| {
+                (min.min(node.cost), max.max(node.cost))
+            });
+        write!(
+            f,
+            "{} nodes, depth {}, costs {min:.4}..{max:.4}",
+            self.size(),
+            self.depth()
+        )
+    }
+}
+
 //
 // Unit tests
 //
@@ -331,6 +1200,8 @@ mod tests {
         assert!(tree.add_child(&1, 2).is_ok());
         assert_eq!(tree.get_parent(&2).unwrap(), &1);
         assert_eq!(tree.size(), 2);
+        assert!(tree.contains(&2));
+        assert!(!tree.contains(&3));
 
         // Make the tree bigger
         assert!(tree.add_child(&1, 3).is_ok());
@@ -343,10 +1214,366 @@ mod tests {
         assert!(approx_eq!(f64, tree.get_node(&4).unwrap().cost, 3.0));
 
         // Add an existing child and everything is not ok
-        assert!(tree.add_child(&1, 2).is_err());
+        assert_eq!(tree.add_child(&1, 2), Err(TreeError::DuplicateNode));
 
         // Add to a nonexistent parent and everything is not ok
-        assert!(tree.add_child(&3, 2).is_err());
+        assert_eq!(tree.add_child(&9, 5), Err(TreeError::ParentNotFound));
+    }
+
+    #[test]
+    fn test_tree_invalidate_edges() {
+        // Tree is: 0 -> 1 -> 2 -> 3 -> 4
+        //                     -> 5
+        //            -> 6 -> 7
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        for (parent, child) in [(0, 1), (1, 2), (2, 3), (3, 4), (2, 5), (0, 6), (6, 7)] {
+            assert!(tree.add_child(&parent, child).is_ok());
+        }
+
+        // Nothing changes if every edge is still valid
+        assert!(tree.invalidate_edges(|_, _| true).is_empty());
+        assert_eq!(tree.size(), 8);
+
+        // An obstacle now blocks 1 -> 2 and 3 -> 4
+        let mut subtrees =
+            tree.invalidate_edges(|parent, child| !matches!((parent, child), (1, 2) | (3, 4)));
+        assert_eq!(subtrees.len(), 2);
+
+        let order: Vec<i32> = tree.iter_insertion_order().copied().collect();
+        assert_eq!(order, vec![0, 1, 6, 7]);
+        assert_eq!(tree.get_parent(&7), Some(&6));
+        assert!(approx_eq!(f64, tree.cost(&7).unwrap(), 7.0));
+        let dfs: Vec<i32> = tree.iter_depth_first().copied().collect();
+        assert_eq!(dfs, vec![0, 1, 6, 7]);
+        assert!(!tree.contains(&2));
+
+        // Detached subtrees are rooted at the children of the removed edges
+        let lower = subtrees.remove(1);
+        assert_eq!(lower.size(), 1);
+        assert!(lower.contains(&4));
+
+        let upper = subtrees.remove(0);
+        let dfs: Vec<i32> = upper.iter_depth_first().copied().collect();
+        assert_eq!(dfs, vec![2, 3, 5]);
+        assert_eq!(upper.get_parent(&2), None);
+        assert_eq!(upper.get_parent(&5), Some(&2));
+        assert!(approx_eq!(f64, upper.cost(&3).unwrap(), 1.0));
+        assert!(approx_eq!(f64, upper.cost(&5).unwrap(), 3.0));
+
+        // The tree is still usable afterwards
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert_eq!(tree.path(&2).unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_tree_edge_cost() {
+        // Climbing costs twice as much as descending
+        let climb = EdgeCost::new(|from: &i32, to: &i32| {
+            if to > from {
+                f64::from(2 * (to - from))
+            } else {
+                f64::from(from - to)
+            }
+        });
+        let mut tree: HashTree<i32> = HashTree::with_edge_cost(0, climb);
+        assert!(approx_eq!(f64, tree.edge_cost(&0, &3), 6.0));
+        assert!(approx_eq!(f64, tree.edge_cost(&3, &0), 3.0));
+
+        assert!(tree.add_child(&0, 3).is_ok());
+        assert!(tree.add_child(&3, 1).is_ok());
+        assert!(tree.add_child(&0, -1).is_ok());
+        assert!(approx_eq!(f64, tree.cost(&3).unwrap(), 6.0));
+        assert!(approx_eq!(f64, tree.cost(&1).unwrap(), 8.0));
+
+        assert!(tree.set_parent(&1, &-1).is_ok());
+        assert!(approx_eq!(f64, tree.cost(&1).unwrap(), 5.0));
+
+        // Detached subtrees keep measuring costs the same way
+        let subtrees = tree.invalidate_edges(|parent, _| *parent != 0);
+        assert_eq!(subtrees.len(), 2);
+        let mut subtree = subtrees.into_iter().find(|t| t.contains(&-1)).unwrap();
+        assert!(subtree.add_child(&1, 2).is_ok());
+        assert!(approx_eq!(f64, subtree.cost(&2).unwrap(), 6.0));
+    }
+
+    #[test]
+    fn test_tree_dedup_grid() {
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert!(tree.add_child(&0, 8).is_ok());
+        assert_eq!(tree.near_duplicate(&9), None);
+
+        // Cells are 4 wide and centered on multiples of 4
+        tree.set_dedup_grid(Some(DedupGrid::new(|x: &i32| vec![f64::from(*x)], 4.0)));
+        assert_eq!(tree.near_duplicate(&1), Some(&0));
+        assert_eq!(tree.near_duplicate(&9), Some(&8));
+        assert_eq!(tree.near_duplicate(&4), None);
+
+        // Near duplicates are still accepted, the first node in a cell represents it
+        assert!(tree.add_child(&8, 7).is_ok());
+        assert!(tree.add_child(&0, 4).is_ok());
+        assert_eq!(tree.near_duplicate(&7), Some(&8));
+        assert_eq!(tree.near_duplicate(&5), Some(&4));
+        assert!(tree.check_invariants().is_ok());
+
+        // Removed nodes leave the index, and detached subtrees keep the grid
+        let subtrees = tree.invalidate_edges(|_, child| *child != 8);
+        assert_eq!(tree.near_duplicate(&7), None);
+        assert!(tree.check_invariants().is_ok());
+        assert_eq!(subtrees[0].near_duplicate(&9), Some(&8));
+        assert!(subtrees[0].check_invariants().is_ok());
+
+        tree.set_dedup_grid(None);
+        assert_eq!(tree.near_duplicate(&5), None);
+    }
+
+    #[test]
+    fn test_tree_nodes_in_region() {
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        for value in [5, -3, 8, 2, 12, -7] {
+            assert!(tree.add_child(&0, value).is_ok());
+        }
+        let region = Region::Box {
+            min: vec![-4.0],
+            max: vec![6.0],
+        };
+        let in_range = |x: &i32| (-4..=6).contains(x);
+        assert_eq!(
+            tree.nodes_in_region(&region),
+            Err(TreeError::NoSpatialIndex)
+        );
+        assert_eq!(
+            tree.nodes_in_box_where(&[-4.0], &[6.0], in_range),
+            vec![&0, &5, &-3, &2]
+        );
+
+        // Same nodes in insertion order once indexed
+        tree.set_spatial_index(Some(SpatialIndex::new(|x: &i32| vec![f64::from(*x)])));
+        assert_eq!(
+            tree.nodes_in_region(&region).unwrap(),
+            vec![&0, &5, &-3, &2]
+        );
+        assert_eq!(
+            tree.nodes_in_box_where(&[-4.0], &[6.0], in_range),
+            vec![&0, &5, &-3, &2]
+        );
+
+        // The predicate can be stricter than the box
+        let even = tree.nodes_in_box_where(&[-10.0], &[10.0], |x| x % 2 == 0);
+        assert_eq!(even, vec![&0, &8, &2]);
+    }
+
+    #[test]
+    fn test_tree_spatial_index() {
+        // A long chain, so that the index is built from an unbalanced insertion order
+        let mut indexed: HashTree<i32> = HashTree::new(0);
+        let mut scanned: HashTree<i32> = HashTree::new(0);
+        for tree in [&mut indexed, &mut scanned] {
+            tree.set_tie_break(TieBreak::LowestCost);
+            for value in 1..40 {
+                assert!(tree.add_child(&(value - 1), value).is_ok());
+            }
+            assert!(tree.add_child(&0, -10).is_ok());
+        }
+        indexed.set_spatial_index(Some(SpatialIndex::new(|x: &i32| vec![f64::from(*x)])));
+        assert!(indexed.spatial_index().is_some());
+        assert!(indexed.check_invariants().is_ok());
+
+        let same_answers = |indexed: &HashTree<i32>, scanned: &HashTree<i32>| {
+            (-15..45).all(|query| {
+                indexed.nearest_neighbor(&query) == scanned.nearest_neighbor(&query)
+                    && indexed.k_nearest_neighbors(&query, 4)
+                        == scanned.k_nearest_neighbors(&query, 4)
+                    && indexed.neighbors_within(&query, 3.0)
+                        == scanned.neighbors_within(&query, 3.0)
+                    && indexed.nearest_neighbors(&query, 3.0)
+                        == scanned.nearest_neighbors(&query, 3.0)
+            })
+        };
+        // Ties between -5 and 5 go to the root's cheaper neighbor either way
+        assert_eq!(indexed.nearest_neighbor(&-5), &0);
+        assert!(same_answers(&indexed, &scanned));
+
+        // The index follows nodes that are added or removed
+        for tree in [&mut indexed, &mut scanned] {
+            assert!(tree.add_child(&-10, -11).is_ok());
+            let subtrees = tree.invalidate_edges(|_, child| *child != 20);
+            assert_eq!(subtrees.len(), 1);
+            assert!(tree.graft(&-11, &subtrees[0]).is_ok());
+        }
+        assert!(indexed.check_invariants().is_ok());
+        assert!(same_answers(&indexed, &scanned));
+
+        // Detached subtrees keep the index
+        let subtrees = indexed.invalidate_edges(|_, child| *child != 25);
+        assert!(subtrees[0].spatial_index().is_some());
+        assert!(subtrees[0].check_invariants().is_ok());
+
+        indexed.set_spatial_index(None);
+        assert!(indexed.spatial_index().is_none());
+        assert_eq!(indexed.nearest_neighbor(&-20), &-11);
+    }
+
+    #[test]
+    fn test_tree_graft() {
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert!(tree.add_child(&0, 1).is_ok());
+
+        // Subtree is: 3 -> 4 -> 5
+        //                -> 6
+        let mut subtree: HashTree<i32> = HashTree::new(3);
+        assert!(subtree.add_child(&3, 4).is_ok());
+        assert!(subtree.add_child(&4, 5).is_ok());
+        assert!(subtree.add_child(&3, 6).is_ok());
+        assert_eq!(subtree.root(), &3);
+
+        let mut overlapping: HashTree<i32> = HashTree::new(7);
+        assert!(overlapping.add_child(&7, 1).is_ok());
+        assert_eq!(tree.graft(&1, &overlapping), Err(TreeError::Overlap));
+        assert_eq!(
+            tree.graft(&9, &HashTree::new(8)),
+            Err(TreeError::ParentNotFound)
+        );
+        assert_eq!(tree.size(), 2);
+
+        assert!(tree.graft(&1, &subtree).is_ok());
+        assert_eq!(tree.size(), 6);
+        assert_eq!(tree.root(), &0);
+        assert_eq!(tree.path(&5).unwrap(), vec![0, 1, 3, 4, 5]);
+        assert_eq!(tree.get_parent(&6), Some(&3));
+        assert!(approx_eq!(f64, tree.cost(&3).unwrap(), 3.0));
+        assert!(approx_eq!(f64, tree.cost(&5).unwrap(), 5.0));
+        assert!(approx_eq!(f64, tree.cost(&6).unwrap(), 6.0));
+        let dfs: Vec<i32> = tree.iter_depth_first().copied().collect();
+        assert_eq!(dfs, vec![0, 1, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_tree_reroot() {
+        // Tree is: 0 -> 1 -> 2 -> 3
+        //                   -> 4
+        //              -> 5
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert!(tree.add_child(&0, 1).is_ok());
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert!(tree.add_child(&2, 3).is_ok());
+        assert!(tree.add_child(&2, 4).is_ok());
+        assert!(tree.add_child(&0, 5).is_ok());
+
+        let rerooted = tree.reroot(&2).unwrap();
+        assert_eq!(rerooted.root(), &2);
+        assert_eq!(rerooted.size(), tree.size());
+        assert_eq!(rerooted.path(&5).unwrap(), vec![2, 1, 0, 5]);
+        assert_eq!(rerooted.get_parent(&3), Some(&2));
+        assert!(approx_eq!(f64, rerooted.cost(&5).unwrap(), 7.0));
+        assert!(approx_eq!(f64, rerooted.cost(&4).unwrap(), 2.0));
+        assert!(rerooted.check_invariants().is_ok());
+
+        // The original is unchanged
+        assert_eq!(tree.root(), &0);
+        assert_eq!(tree.reroot(&9).unwrap_err(), TreeError::NodeNotFound);
+    }
+
+    #[test]
+    fn test_tree_path_from_nearest() {
+        // Tree is: 0 -> 2 -> 4 -> 6
+        //            -> -2
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        for (parent, child) in [(0, 2), (2, 4), (4, 6), (0, -2)] {
+            assert!(tree.add_child(&parent, child).is_ok());
+        }
+
+        assert_eq!(
+            tree.path_from_nearest(&6, &0, 0.0).unwrap(),
+            vec![0, 2, 4, 6]
+        );
+        assert_eq!(tree.path_from_nearest(&6, &3, 1.0).unwrap(), vec![2, 4, 6]);
+        assert_eq!(tree.path_from_nearest(&6, &5, 1.0).unwrap(), vec![4, 6]);
+
+        // Only nodes on the path are considered
+        assert_eq!(
+            tree.path_from_nearest(&6, &-2, 2.0).unwrap(),
+            vec![0, 2, 4, 6]
+        );
+        assert_eq!(
+            tree.path_from_nearest(&6, &-3, 2.0),
+            Err(TreeError::OutOfTolerance)
+        );
+        assert_eq!(
+            tree.path_from_nearest(&7, &0, 2.0),
+            Err(TreeError::NodeNotFound)
+        );
+    }
+
+    #[test]
+    fn test_tree_provenance() {
+        let mut tree: HashTree<i32> = HashTree::new(1);
+        assert!(tree.add_child(&1, 2).is_ok());
+        let stamp = Provenance {
+            iteration: 3,
+            elapsed: Duration::from_millis(5),
+        };
+        tree.set_provenance_stamp(Some(stamp));
+        assert!(tree.add_child(&1, 0).is_ok());
+        assert!(tree.add_child(&2, 3).is_ok());
+        tree.set_provenance_stamp(None);
+        assert!(tree.add_child(&3, 4).is_ok());
+
+        assert_eq!(tree.provenance(&1), None);
+        assert_eq!(tree.provenance(&2), None);
+        assert_eq!(tree.provenance(&0), Some(stamp));
+        assert_eq!(tree.provenance(&3), Some(stamp));
+        assert_eq!(tree.provenance(&4), None);
+        assert_eq!(tree.provenance(&5), None);
+
+        // Reparenting keeps the provenance
+        assert!(tree.set_parent(&3, &0).is_ok());
+        assert_eq!(tree.provenance(&3), Some(stamp));
+
+        let order: Vec<i32> = tree.iter_insertion_order().copied().collect();
+        assert_eq!(order, vec![1, 2, 0, 3, 4]);
+    }
+
+    #[test]
+    fn test_tree_tie_break() {
+        // 1 and 3 are both 1 away from 2, but 1 was added first and 3 is cheaper to reach
+        let mut tree: HashTree<i32> = HashTree::new(5);
+        assert!(tree.add_child(&5, 1).is_ok());
+        assert!(tree.add_child(&5, 3).is_ok());
+        assert_eq!(tree.tie_break(), TieBreak::InsertionOrder);
+        assert_eq!(tree.nearest_neighbor(&2), &1);
+        let nearest: Vec<i32> = tree
+            .k_nearest_neighbors(&2, 3)
+            .into_iter()
+            .map(|(node, _)| *node)
+            .collect();
+        assert_eq!(nearest, vec![1, 3, 5]);
+
+        tree.set_tie_break(TieBreak::LowestCost);
+        assert_eq!(tree.nearest_neighbor(&2), &3);
+        let nearest: Vec<i32> = tree
+            .k_nearest_neighbors(&2, 2)
+            .into_iter()
+            .map(|(node, _)| *node)
+            .collect();
+        assert_eq!(nearest, vec![3, 1]);
+
+        // Ties in cost fall back to insertion order
+        assert!(tree.add_child(&5, 7).is_ok());
+        assert_eq!(tree.nearest_neighbor(&5), &5);
+        let nearest: Vec<i32> = tree
+            .k_nearest_neighbors(&5, 3)
+            .into_iter()
+            .map(|(node, _)| *node)
+            .collect();
+        assert_eq!(nearest, vec![5, 3, 7]);
+
+        // Radius queries are always in insertion order
+        let within: Vec<i32> = tree
+            .neighbors_within(&4, 1.0)
+            .into_iter()
+            .map(|(node, _)| *node)
+            .collect();
+        assert_eq!(within, vec![5, 3]);
     }
 
     #[test]
@@ -359,9 +1586,17 @@ mod tests {
         assert_eq!(tree.get_node(&2).unwrap().children.len(), 1);
 
         // Validate failures
-        assert!(tree.set_parent(&1, &2).is_err());
-        assert!(tree.set_parent(&4, &1).is_err());
-        assert!(tree.set_parent(&2, &3).is_err());
+        assert_eq!(tree.set_parent(&1, &2), Err(TreeError::ReparentRoot));
+        assert_eq!(tree.set_parent(&4, &1), Err(TreeError::NodeNotFound));
+        assert_eq!(tree.set_parent(&2, &3), Err(TreeError::ParentNotFound));
+
+        // Cycles are rejected without modifying the tree
+        assert!(tree.is_ancestor(&2, &0));
+        assert!(!tree.is_ancestor(&0, &2));
+        assert_eq!(tree.set_parent(&2, &0), Err(TreeError::Cycle));
+        assert!(tree.set_parent(&2, &2).is_err());
+        assert_eq!(tree.get_parent(&2), Some(&1));
+        assert!(tree.check_invariants().is_ok());
 
         // Reparent and validate the tree
         assert!(tree.set_parent(&0, &1).is_ok());
@@ -370,6 +1605,80 @@ mod tests {
         assert_eq!(tree.get_node(&2).unwrap().children.len(), 0);
     }
 
+    #[test]
+    fn test_tree_reparenting_subtree() {
+        // 0 -> -5 -> 5 -> 6 -> ... -> 100, with a branch 5 -> 4 -> 3
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert!(tree.add_child(&0, -5).is_ok());
+        assert!(tree.add_child(&-5, 5).is_ok());
+        for i in 6..=100 {
+            assert!(tree.add_child(&(i - 1), i).is_ok());
+        }
+        assert!(tree.add_child(&5, 4).is_ok());
+        assert!(tree.add_child(&4, 3).is_ok());
+        assert!(approx_eq!(f64, tree.cost(&100).unwrap(), 110.0));
+
+        // Every descendant of the moved node is now 10 cheaper
+        assert!(tree.set_parent(&5, &0).is_ok());
+        assert!(tree.check_invariants().is_ok());
+        for i in 5..=100 {
+            assert!(approx_eq!(f64, tree.cost(&i).unwrap(), f64::from(i)));
+        }
+        assert!(approx_eq!(f64, tree.cost(&3).unwrap(), 7.0));
+        assert!(approx_eq!(f64, tree.cost(&-5).unwrap(), 5.0));
+
+        // Including edges whose cost depends on their grandparent, here turning around
+        let turns = EdgeCost::with_predecessor(|previous: Option<&i32>, from: &i32, to: &i32| {
+            let turn = previous.is_some_and(|p| (from - p).signum() != (to - from).signum());
+            f64::from((to - from).abs()) + if turn { 10.0 } else { 0.0 }
+        });
+        let mut tree = HashTree::with_edge_cost(0, turns);
+        assert!(tree.add_child(&0, 2).is_ok());
+        assert!(tree.add_child(&2, 1).is_ok());
+        assert!(tree.add_child(&1, -1).is_ok());
+        assert!(tree.add_child(&-1, -2).is_ok());
+        assert!(approx_eq!(f64, tree.cost(&-2).unwrap(), 16.0));
+        assert!(tree.set_parent(&-1, &0).is_ok());
+        assert!(tree.check_invariants().is_ok());
+        assert!(approx_eq!(f64, tree.cost(&-2).unwrap(), 2.0));
+    }
+
+    #[test]
+    fn test_tree_display() {
+        let mut tree: HashTree<i32> = HashTree::new(0);
+        assert_eq!(tree.depth(), 0);
+        assert_eq!(tree.to_string(), "1 nodes, depth 0, costs 0.0000..0.0000");
+
+        tree.add_child(&0, 2).unwrap();
+        tree.add_child(&2, 3).unwrap();
+        tree.add_child(&0, -1).unwrap();
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(tree.to_string(), "4 nodes, depth 2, costs 0.0000..3.0000");
+    }
+
+    #[test]
+    fn test_tree_check_invariants() {
+        let mut tree: HashTree<i32> = HashTree::new(1);
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert!(tree.add_child(&2, 0).is_ok());
+        assert!(tree.check_invariants().is_ok());
+
+        tree.nodes[2].cost = 5.0;
+        assert!(tree.check_invariants().is_err());
+        tree.nodes[2].cost = 3.0;
+        assert!(tree.check_invariants().is_ok());
+
+        // Node 0 claims a parent that does not list it
+        tree.nodes[2].parent = Some(0);
+        assert!(tree.check_invariants().is_err());
+
+        // A cycle between 2 and 0, detached from the root
+        tree.nodes[1].parent = Some(2);
+        tree.nodes[0].children.clear();
+        tree.nodes[2].children.insert(1);
+        assert!(tree.check_invariants().is_err());
+    }
+
     #[test]
     fn test_tree_get_nearest() {
         // Construct tree with many nodes
@@ -400,7 +1709,7 @@ mod tests {
 
         // Expected order
         let expected_dfs_order = vec![1, 2, 4, 5, 3, 6];
-        let dfs_order: Vec<i32> = tree.iter_depth_first().cloned().collect();
+        let dfs_order: Vec<i32> = tree.iter_depth_first().copied().collect();
 
         // Compare
         assert_eq!(dfs_order, expected_dfs_order);
@@ -428,7 +1737,7 @@ mod tests {
         assert_eq!(cp2, ep2);
 
         // Invalid node
-        assert!(tree.path(&8).is_err());
+        assert_eq!(tree.path(&8), Err(TreeError::NodeNotFound));
     }
 
     #[test]
@@ -449,4 +1758,39 @@ mod tests {
         assert!(approx_eq!(f64, *neighbors.get(&2).unwrap(), 2.0));
         assert!(approx_eq!(f64, *neighbors.get(&5).unwrap(), 1.0));
     }
+
+    #[test]
+    fn test_tree_k_nearest_neighbors() {
+        let mut tree: HashTree<i32> = HashTree::new(1);
+
+        assert!(tree.add_child(&1, 2).is_ok());
+        assert!(tree.add_child(&1, 4).is_ok());
+        assert!(tree.add_child(&2, 5).is_ok());
+        assert!(tree.add_child(&4, 9).is_ok());
+
+        // Sorted closest to furthest
+        let neighbors = tree.k_nearest_neighbors(&6, 3);
+        let values: Vec<i32> = neighbors.iter().map(|(v, _)| **v).collect();
+        assert_eq!(values, vec![5, 4, 9]);
+        assert!(approx_eq!(f64, neighbors[0].1, 1.0));
+
+        // Asking for more than the tree holds returns everything
+        assert_eq!(tree.k_nearest_neighbors(&6, 10).len(), 5);
+    }
+
+    #[test]
+    fn test_tree_neighbors_within() {
+        let mut tree: HashTree<i32> = HashTree::new(1);
+
+        assert!(tree.add_child(&1, 7).is_ok());
+        assert!(tree.add_child(&1, 5).is_ok());
+        assert!(tree.add_child(&5, 2).is_ok());
+        assert!(tree.add_child(&7, 4).is_ok());
+
+        // Insertion order, not distance order
+        let neighbors = tree.neighbors_within(&4, 2.0);
+        let values: Vec<i32> = neighbors.iter().map(|(v, _)| **v).collect();
+        assert_eq!(values, vec![5, 2, 4]);
+        assert!(approx_eq!(f64, neighbors[1].1, 2.0));
+    }
 }
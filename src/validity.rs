@@ -0,0 +1,632 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::float::check_resolution;
+use crate::interpolation::Interpolate;
+use crate::tree::Distance;
+use std::f64::consts::TAU;
+
+/// Requires at least `radius` of clearance using a distance query.
+///
+/// `distance_fn` returns the distance from a state to the nearest obstacle, e.g. from a
+/// signed distance field or geometry library. The returned check accepts states whose
+/// distance is at least `radius`.
+///
+/// For occupancy grids, inflate the grid once with [`crate::grid::OccupancyGrid::inflated`]
+/// rather than checking clearance on every query.
+pub fn with_clearance<T, FD>(mut distance_fn: FD, radius: f64) -> impl FnMut(&T) -> bool
+where
+    FD: FnMut(&T) -> f64,
+{
+    move |state: &T| distance_fn(state) >= radius
+}
+
+/// Inflates an arbitrary validity check by `radius` by sampling a disc of offsets.
+///
+/// The returned check accepts a state if `valid_fn` accepts the state itself and `samples`
+/// states evenly spaced on a circle of `radius` around it. `offset_fn` applies a planar
+/// `(dx, dy)` offset to a state, leaving any other components such as heading unchanged.
+///
+/// This is an approximation, obstacles thinner than the spacing between samples may fit
+/// between them, so use [`with_clearance`] if a distance query is available.
+pub fn with_disc_margin<T, FV, FO>(
+    mut valid_fn: FV,
+    mut offset_fn: FO,
+    radius: f64,
+    samples: usize,
+) -> impl FnMut(&T) -> bool
+where
+    FV: FnMut(&T) -> bool,
+    FO: FnMut(&T, (f64, f64)) -> T,
+{
+    #[allow(clippy::cast_precision_loss)]
+    let offsets: Vec<(f64, f64)> = (0..samples)
+        .map(|i| {
+            let angle = TAU * i as f64 / samples as f64;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+
+    move |state: &T| {
+        valid_fn(state)
+            && offsets
+                .iter()
+                .all(|&offset| valid_fn(&offset_fn(state, offset)))
+    }
+}
+
+/// A pair of links, by index, found to be in collision with each other.
+pub type LinkPair = (usize, usize);
+
+/// Checks an articulated robot for collisions between its own links.
+///
+/// Implementations typically run forward kinematics for the joint state and test link
+/// geometry pairwise. Any `FnMut(&T) -> bool` closure returning true for collision free
+/// states is also a checker, though one that cannot report the offending links. Combine
+/// with an environment check using [`with_self_collision`].
+pub trait SelfCollisionChecker<T> {
+    /// Returns `Ok` if no links collide at `state`, otherwise the colliding pair if known.
+    ///
+    /// # Errors
+    ///
+    /// If any links are in collision.
+    fn check_self_collision(&mut self, state: &T) -> Result<(), Option<LinkPair>>;
+
+    /// Returns true if no links collide at `state`.
+    fn is_self_collision_free(&mut self, state: &T) -> bool {
+        self.check_self_collision(state).is_ok()
+    }
+}
+
+impl<T, F> SelfCollisionChecker<T> for F
+where
+    F: FnMut(&T) -> bool,
+{
+    fn check_self_collision(&mut self, state: &T) -> Result<(), Option<LinkPair>> {
+        if self(state) {
+            Ok(())
+        } else {
+            Err(None)
+        }
+    }
+}
+
+/// Checks a fixed set of link pairs with a per pair collision test.
+///
+/// Only the listed pairs are tested, so adjacent links that always touch and pairs that
+/// can never reach each other should be left out.
+#[derive(Debug, Clone)]
+pub struct PairwiseSelfCollision<F> {
+    pairs: Vec<LinkPair>,
+    collides_fn: F,
+}
+
+impl<F> PairwiseSelfCollision<F> {
+    /// Construct a checker testing `pairs`, where `collides_fn(state, a, b)` returns true
+    /// if links `a` and `b` collide at `state`.
+    pub fn new(pairs: Vec<LinkPair>, collides_fn: F) -> Self {
+        PairwiseSelfCollision { pairs, collides_fn }
+    }
+
+    /// Returns the link pairs that are tested.
+    #[must_use]
+    pub fn pairs(&self) -> &[LinkPair] {
+        &self.pairs
+    }
+}
+
+impl<T, F> SelfCollisionChecker<T> for PairwiseSelfCollision<F>
+where
+    F: FnMut(&T, usize, usize) -> bool,
+{
+    fn check_self_collision(&mut self, state: &T) -> Result<(), Option<LinkPair>> {
+        match self
+            .pairs
+            .iter()
+            .find(|&&(a, b)| (self.collides_fn)(state, a, b))
+        {
+            Some(&pair) => Err(Some(pair)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Combines an environment validity check with a self collision check.
+///
+/// The returned check accepts a state only if both accept it. The environment is checked
+/// first, as it is usually the cheaper of the two.
+pub fn with_self_collision<T, FV, SC>(mut valid_fn: FV, mut checker: SC) -> impl FnMut(&T) -> bool
+where
+    FV: FnMut(&T) -> bool,
+    SC: SelfCollisionChecker<T>,
+{
+    move |state: &T| valid_fn(state) && checker.is_self_collision_free(state)
+}
+
+/// Validates motions by conservative advancement, which never misses a collision along an
+/// edge regardless of its length or how thin the obstacles are.
+///
+/// `distance_fn` returns the distance from a state to the nearest obstacle, and
+/// `interpolate_fn(from, to, t)` returns the state a fraction `t` of the way along the
+/// motion. `max_speed` bounds how far any point on the robot moves per unit of
+/// [`Distance`] in state space, e.g. 1 for a point robot, or the distance from the center
+/// to the furthest point of the body for a rotating one. Each step advances exactly as far
+/// as the current clearance guarantees is safe, so steps are long in open space and short
+/// near obstacles.
+///
+/// Motions that come within `tolerance` of an obstacle are rejected, which also bounds the
+/// number of steps.
+///
+/// # Panics
+///
+/// If the tolerance is not positive, as motions touching an obstacle would never finish.
+pub fn conservative_advancement<T, FD, FI>(
+    mut distance_fn: FD,
+    mut interpolate_fn: FI,
+    max_speed: f64,
+    tolerance: f64,
+) -> impl FnMut(&T, &T) -> bool
+where
+    T: Distance,
+    FD: FnMut(&T) -> f64,
+    FI: FnMut(&T, &T, f64) -> T,
+{
+    assert!(tolerance > 0.0, "tolerance must be positive");
+    move |from: &T, to: &T| {
+        let sweep = max_speed * from.distance(to);
+        let mut t = 0.0;
+        loop {
+            let clearance = if t == 0.0 {
+                distance_fn(from)
+            } else {
+                distance_fn(&interpolate_fn(from, to, t))
+            };
+            if clearance.is_nan() || clearance < tolerance {
+                return false;
+            }
+            if t >= 1.0 {
+                return true;
+            }
+            // Nothing can be hit before the robot has swept the current clearance
+            t = if sweep > 0.0 {
+                (t + clearance / sweep).min(1.0)
+            } else {
+                1.0
+            };
+        }
+    }
+}
+
+/// Validates motions by checking states at intervals along them, with the motion between
+/// states given by `interpolation`.
+///
+/// Checks the state at the end of the motion, and states along it spaced no more than
+/// `resolution` apart by [`Distance`] between the endpoints, but not the state the motion
+/// starts from. Curved interpolations such as
+/// [`crate::interpolation::PoseInterpolation::CubicHermite`] are longer than that
+/// distance, so they are checked somewhat more sparsely along their actual length. Use
+/// the same interpolation to generate the trajectory, e.g. with
+/// [`crate::trajectory::s_curve_pose_trajectory`], so that the motion executed is the
+/// one validated.
+///
+/// # Panics
+///
+/// If the resolution is not finite and positive.
+pub fn discretized_motion<T, FV, I>(
+    mut valid_fn: FV,
+    interpolation: I,
+    resolution: f64,
+) -> impl FnMut(&T, &T) -> bool
+where
+    T: Distance,
+    FV: FnMut(&T) -> bool,
+    I: Interpolate<T>,
+{
+    check_resolution(resolution);
+    move |from: &T, to: &T| {
+        let distance = from.distance(to);
+        if distance.is_nan() {
+            return false;
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let steps = ((distance / resolution).ceil() as usize).max(1);
+        #[allow(clippy::cast_precision_loss)]
+        (1..=steps)
+            .all(|step| valid_fn(&interpolation.interpolate(from, to, step as f64 / steps as f64)))
+    }
+}
+
+/// A boxed state validity check.
+type StateCheck<'a, T> = Box<dyn FnMut(&T) -> bool + 'a>;
+
+/// A boxed motion validity check.
+type MotionCheck<'a, T> = Box<dyn FnMut(&T, &T) -> bool + 'a>;
+
+/// Inserts `check` after any existing checks of equal or lower cost.
+fn insert_by_cost<C>(checks: &mut Vec<(f64, C)>, cost: f64, check: C) {
+    let index = checks.partition_point(|(existing, _)| existing.total_cmp(&cost).is_le());
+    checks.insert(index, (cost, check));
+}
+
+/// Accepts a state only if every check accepts it.
+///
+/// Checks run in order of their relative `cost`, cheapest first, and stop at the first
+/// rejection. Checks with equal cost run in the order they were added. An empty set
+/// accepts every state.
+///
+/// ```
+/// use rustplanning::validity::AllOf;
+///
+/// let mut valid = AllOf::new()
+///     .with(10.0, |p: &(f64, f64)| p.0.hypot(p.1) > 1.0)
+///     .with(1.0, |p: &(f64, f64)| p.0.abs() < 5.0 && p.1.abs() < 5.0)
+///     .into_fn();
+/// assert!(valid(&(2.0, 0.0)));
+/// assert!(!valid(&(6.0, 0.0)));
+/// ```
+pub struct AllOf<'a, T> {
+    checks: Vec<(f64, StateCheck<'a, T>)>,
+}
+
+impl<'a, T> AllOf<'a, T> {
+    /// Construct an empty set of checks.
+    #[must_use]
+    pub fn new() -> Self {
+        AllOf { checks: Vec::new() }
+    }
+
+    /// Adds a check with the given relative cost.
+    #[must_use]
+    pub fn with<F>(mut self, cost: f64, check: F) -> Self
+    where
+        F: FnMut(&T) -> bool + 'a,
+    {
+        insert_by_cost(&mut self.checks, cost, Box::new(check));
+        self
+    }
+
+    /// Returns true if every check accepts `state`.
+    pub fn check(&mut self, state: &T) -> bool {
+        self.checks.iter_mut().all(|(_, check)| check(state))
+    }
+
+    /// Converts the set into a validity closure.
+    pub fn into_fn(mut self) -> impl FnMut(&T) -> bool + 'a
+    where
+        T: 'a,
+    {
+        move |state: &T| self.check(state)
+    }
+}
+
+impl<T> Default for AllOf<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accepts a state if any check accepts it.
+///
+/// Checks run in order of their relative `cost`, cheapest first, and stop at the first
+/// acceptance. An empty set rejects every state.
+pub struct AnyOf<'a, T> {
+    checks: Vec<(f64, StateCheck<'a, T>)>,
+}
+
+impl<'a, T> AnyOf<'a, T> {
+    /// Construct an empty set of checks.
+    #[must_use]
+    pub fn new() -> Self {
+        AnyOf { checks: Vec::new() }
+    }
+
+    /// Adds a check with the given relative cost.
+    #[must_use]
+    pub fn with<F>(mut self, cost: f64, check: F) -> Self
+    where
+        F: FnMut(&T) -> bool + 'a,
+    {
+        insert_by_cost(&mut self.checks, cost, Box::new(check));
+        self
+    }
+
+    /// Returns true if any check accepts `state`.
+    pub fn check(&mut self, state: &T) -> bool {
+        self.checks.iter_mut().any(|(_, check)| check(state))
+    }
+
+    /// Converts the set into a validity closure.
+    pub fn into_fn(mut self) -> impl FnMut(&T) -> bool + 'a
+    where
+        T: 'a,
+    {
+        move |state: &T| self.check(state)
+    }
+}
+
+impl<T> Default for AnyOf<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accepts a motion only if every check accepts it, the motion counterpart of [`AllOf`].
+///
+/// Useful for running a cheap bounding or endpoint test before an expensive swept
+/// collision check.
+pub struct AllOfMotions<'a, T> {
+    checks: Vec<(f64, MotionCheck<'a, T>)>,
+}
+
+impl<'a, T> AllOfMotions<'a, T> {
+    /// Construct an empty set of checks.
+    #[must_use]
+    pub fn new() -> Self {
+        AllOfMotions { checks: Vec::new() }
+    }
+
+    /// Adds a check with the given relative cost.
+    #[must_use]
+    pub fn with<F>(mut self, cost: f64, check: F) -> Self
+    where
+        F: FnMut(&T, &T) -> bool + 'a,
+    {
+        insert_by_cost(&mut self.checks, cost, Box::new(check));
+        self
+    }
+
+    /// Returns true if every check accepts the motion from `from` to `to`.
+    pub fn check(&mut self, from: &T, to: &T) -> bool {
+        self.checks.iter_mut().all(|(_, check)| check(from, to))
+    }
+
+    /// Converts the set into a motion validity closure.
+    pub fn into_fn(mut self) -> impl FnMut(&T, &T) -> bool + 'a
+    where
+        T: 'a,
+    {
+        move |from: &T, to: &T| self.check(from, to)
+    }
+}
+
+impl<T> Default for AllOfMotions<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Inverts a state validity check, e.g. to turn a keep out zone test into a validity check.
+pub fn not<T, F>(mut check: F) -> impl FnMut(&T) -> bool
+where
+    F: FnMut(&T) -> bool,
+{
+    move |state: &T| !check(state)
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    // A single round obstacle of radius 1 at the origin
+    fn obstacle_distance(p: &(f64, f64)) -> f64 {
+        (p.0 * p.0 + p.1 * p.1).sqrt() - 1.0
+    }
+
+    #[test]
+    fn test_with_clearance() {
+        let mut valid = with_clearance(obstacle_distance, 0.5);
+        assert!(valid(&(2.0, 0.0)));
+        assert!(valid(&(0.0, -1.5)));
+        assert!(!valid(&(1.2, 0.0)));
+    }
+
+    // Two link planar arm with unit links, as (shoulder, elbow) angles. The second link
+    // folds back onto the base link when the elbow approaches pi.
+    fn folded(state: &(f64, f64), a: usize, b: usize) -> bool {
+        (a, b) == (0, 1) && (state.1.abs() - PI).abs() < 0.3
+    }
+
+    #[test]
+    fn test_self_collision() {
+        let mut checker = PairwiseSelfCollision::new(vec![(0, 1)], folded);
+        assert_eq!(checker.pairs(), &[(0, 1)]);
+        assert!(checker.check_self_collision(&(0.0, 0.5)).is_ok());
+        assert_eq!(checker.check_self_collision(&(0.0, 3.0)), Err(Some((0, 1))));
+        assert!(!checker.is_self_collision_free(&(1.0, -3.1)));
+
+        // Closures are checkers without link information
+        let mut checker = |state: &(f64, f64)| state.1.abs() < 2.0;
+        assert_eq!(checker.check_self_collision(&(0.0, 3.0)), Err(None));
+
+        // Environment: the shoulder may not point below the horizontal
+        let environment = |state: &(f64, f64)| state.0 >= 0.0;
+        let mut valid = with_self_collision(
+            environment,
+            PairwiseSelfCollision::new(vec![(0, 1)], folded),
+        );
+        assert!(valid(&(0.5, 0.5)));
+        assert!(!valid(&(-0.5, 0.5)));
+        assert!(!valid(&(0.5, 3.0)));
+    }
+
+    #[test]
+    fn test_conservative_advancement() {
+        use crate::float::FloatArray;
+
+        // A wall 0.01 thick at x = 5.5 between y = -1 and y = 1
+        let wall = |p: &FloatArray<2>| {
+            let dx = (p.0[0] - 5.5).abs() - 0.005;
+            let dy = p.0[1].abs() - 1.0;
+            dx.max(0.0).hypot(dy.max(0.0)) + dx.max(dy).min(0.0)
+        };
+        let interpolate = |a: &FloatArray<2>, b: &FloatArray<2>, t: f64| {
+            FloatArray([
+                a.0[0] + (b.0[0] - a.0[0]) * t,
+                a.0[1] + (b.0[1] - a.0[1]) * t,
+            ])
+        };
+        let mut valid = conservative_advancement(wall, interpolate, 1.0, 1e-3);
+
+        // Checking at fixed intervals of 1 would step right over the wall
+        assert!((0..=10).all(|i| wall(&FloatArray([f64::from(i), 0.0])) > 0.0));
+        assert!(!valid(&FloatArray([0.0, 0.0]), &FloatArray([10.0, 0.0])));
+
+        assert!(valid(&FloatArray([0.0, 2.0]), &FloatArray([10.0, 2.0])));
+        assert!(valid(&FloatArray([0.0, 0.0]), &FloatArray([0.0, 0.0])));
+        assert!(!valid(&FloatArray([5.5, 0.0]), &FloatArray([0.0, 0.0])));
+        assert!(!valid(&FloatArray([0.0, 0.0]), &FloatArray([5.5, 0.0])));
+
+        // Passing just within the tolerance of the end of the wall is rejected
+        assert!(!valid(
+            &FloatArray([0.0, 1.0005]),
+            &FloatArray([10.0, 1.0005])
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "tolerance must be positive")]
+    fn test_conservative_advancement_tolerance() {
+        use crate::float::FloatArray;
+
+        let interpolate = |a: &FloatArray<1>, _: &FloatArray<1>, _: f64| *a;
+        let _ = conservative_advancement(|_: &FloatArray<1>| 1.0, interpolate, 1.0, 0.0);
+    }
+
+    #[test]
+    fn test_discretized_motion() {
+        use crate::float::FloatArray;
+        use crate::interpolation::Linear;
+
+        let mut checked = Vec::new();
+        let mut valid = discretized_motion(
+            |p: &FloatArray<2>| {
+                checked.push(p.0);
+                p.0[0] < 5.5
+            },
+            Linear,
+            1.0,
+        );
+        assert!(valid(&FloatArray([0.0, 0.0]), &FloatArray([2.0, 0.0])));
+        assert!(!valid(&FloatArray([0.0, 0.0]), &FloatArray([10.0, 0.0])));
+        assert!(valid(&FloatArray([0.0, 0.0]), &FloatArray([0.0, 0.0])));
+        drop(valid);
+        assert_eq!(
+            checked,
+            vec![
+                [1.0, 0.0],
+                [2.0, 0.0],
+                [1.0, 0.0],
+                [2.0, 0.0],
+                [3.0, 0.0],
+                [4.0, 0.0],
+                [5.0, 0.0],
+                [6.0, 0.0],
+                [0.0, 0.0]
+            ]
+        );
+
+        // A curve validated with the interpolation it is driven along
+        let bend = |a: &FloatArray<2>, b: &FloatArray<2>, t: f64| {
+            let bulge = (b.0[0] - a.0[0]) * 2.0 * t * (1.0 - t);
+            FloatArray([
+                a.0[0] + (b.0[0] - a.0[0]) * t,
+                a.0[1] + (b.0[1] - a.0[1]) * t + bulge,
+            ])
+        };
+        let mut valid = discretized_motion(|p: &FloatArray<2>| p.0[1] < 2.0, bend, 0.1);
+        assert!(valid(&FloatArray([0.0, 0.0]), &FloatArray([2.0, 0.0])));
+        assert!(!valid(&FloatArray([0.0, 0.0]), &FloatArray([6.0, 0.0])));
+    }
+
+    #[test]
+    fn test_with_disc_margin() {
+        let offset = |p: &(f64, f64), (dx, dy): (f64, f64)| (p.0 + dx, p.1 + dy);
+        let mut valid =
+            with_disc_margin(|p: &(f64, f64)| obstacle_distance(p) > 0.0, offset, 0.5, 8);
+        assert!(valid(&(2.0, 0.0)));
+        assert!(!valid(&(1.2, 0.0)));
+        assert!(!valid(&(0.0, 0.0)));
+
+        // No samples only checks the state itself
+        let mut valid =
+            with_disc_margin(|p: &(f64, f64)| obstacle_distance(p) > 0.0, offset, 0.5, 0);
+        assert!(valid(&(1.2, 0.0)));
+    }
+
+    #[test]
+    fn test_combinators() {
+        use std::cell::RefCell;
+
+        let calls = RefCell::new(Vec::new());
+        let in_bounds = |p: &(f64, f64)| {
+            calls.borrow_mut().push("bounds");
+            p.0.abs() < 5.0 && p.1.abs() < 5.0
+        };
+        let keep_out = |p: &(f64, f64)| {
+            calls.borrow_mut().push("keep_out");
+            p.0 > 3.0 && p.1 > 3.0
+        };
+        let clearance = |p: &(f64, f64)| {
+            calls.borrow_mut().push("clearance");
+            obstacle_distance(p) > 0.5
+        };
+
+        // Added out of order, run cheapest first
+        let mut valid = AllOf::new()
+            .with(10.0, clearance)
+            .with(1.0, in_bounds)
+            .with(2.0, not(keep_out))
+            .into_fn();
+        assert!(valid(&(2.0, 0.0)));
+        assert_eq!(*calls.borrow(), vec!["bounds", "keep_out", "clearance"]);
+
+        // Short circuits on the first rejection
+        calls.borrow_mut().clear();
+        assert!(!valid(&(6.0, 0.0)));
+        assert_eq!(*calls.borrow(), vec!["bounds"]);
+        assert!(!valid(&(4.0, 4.0)));
+        assert!(!valid(&(0.0, 1.2)));
+        drop(valid);
+
+        let mut either = AnyOf::new()
+            .with(1.0, |p: &(f64, f64)| p.0 > 0.0)
+            .with(1.0, |p: &(f64, f64)| p.1 > 0.0);
+        assert!(either.check(&(1.0, -1.0)));
+        assert!(either.check(&(-1.0, 1.0)));
+        assert!(!either.check(&(-1.0, -1.0)));
+
+        assert!(AllOf::default().check(&0));
+        assert!(!AnyOf::default().check(&0));
+
+        let mut motion = AllOfMotions::new()
+            .with(5.0, |_: &i32, _: &i32| {
+                panic!("expensive check should not run")
+            })
+            .with(1.0, |from: &i32, to: &i32| (to - from).abs() <= 1);
+        assert!(!motion.check(&0, &3));
+    }
+}
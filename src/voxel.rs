@@ -0,0 +1,433 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::GridError;
+use crate::float::check_resolution;
+use crate::grid::{CellState, UnknownSpacePolicy};
+
+/// A point in 3D world coordinates.
+pub type Point3 = (f64, f64, f64);
+
+/// The `(x, y, z)` index of a voxel.
+pub type VoxelIndex = (usize, usize, usize);
+
+/// A dense 3D occupancy grid of cubic voxels.
+///
+/// The 3D counterpart of [`crate::grid::OccupancyGrid`]. Voxel `(0, 0, 0)` occupies
+/// `[0, resolution)` along each axis in world coordinates, and voxels share the same
+/// [`CellState`] and [`UnknownSpacePolicy`] semantics as grid cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelGrid {
+    dimensions: VoxelIndex,
+    resolution: f64,
+    voxels: Vec<CellState>,
+    unknown_policy: UnknownSpacePolicy,
+}
+
+impl VoxelGrid {
+    /// Constructs an entirely free grid with `dimensions` voxels along each axis.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    #[must_use]
+    pub fn new(dimensions: VoxelIndex, resolution: f64) -> Self {
+        Self::filled(dimensions, resolution, CellState::Free)
+    }
+
+    /// Constructs a grid with every voxel in the specified state.
+    ///
+    /// # Panics
+    ///
+    /// If the resolution is not finite and positive.
+    #[must_use]
+    pub fn filled(dimensions: VoxelIndex, resolution: f64, state: CellState) -> Self {
+        check_resolution(resolution);
+        VoxelGrid {
+            dimensions,
+            resolution,
+            voxels: vec![state; dimensions.0 * dimensions.1 * dimensions.2],
+            unknown_policy: UnknownSpacePolicy::default(),
+        }
+    }
+
+    /// Sets whether planning queries may traverse unknown voxels.
+    #[must_use]
+    pub fn with_unknown_policy(mut self, policy: UnknownSpacePolicy) -> Self {
+        self.unknown_policy = policy;
+        self
+    }
+
+    /// Number of voxels along each axis.
+    #[must_use]
+    pub fn dimensions(&self) -> VoxelIndex {
+        self.dimensions
+    }
+
+    /// Side length of a voxel in world units.
+    #[must_use]
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    /// The minimum and maximum world coordinates covered by the grid.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bounds(&self) -> (Point3, Point3) {
+        let (nx, ny, nz) = self.dimensions;
+        (
+            (0.0, 0.0, 0.0),
+            (
+                nx as f64 * self.resolution,
+                ny as f64 * self.resolution,
+                nz as f64 * self.resolution,
+            ),
+        )
+    }
+
+    fn index(&self, (x, y, z): VoxelIndex) -> Option<usize> {
+        let (nx, ny, nz) = self.dimensions;
+        (x < nx && y < ny && z < nz).then(|| (z * ny + y) * nx + x)
+    }
+
+    /// Returns the state of the voxel, if it is within the grid.
+    #[must_use]
+    pub fn state(&self, voxel: VoxelIndex) -> Option<CellState> {
+        self.index(voxel).map(|i| self.voxels[i])
+    }
+
+    /// Sets the state of a voxel.
+    ///
+    /// # Errors
+    ///
+    /// If the voxel is outside the grid.
+    pub fn set_state(&mut self, voxel: VoxelIndex, state: CellState) -> Result<(), GridError> {
+        let i = self.index(voxel).ok_or(GridError::OutOfBounds)?;
+        self.voxels[i] = state;
+        Ok(())
+    }
+
+    /// Returns true if the voxel is occupied, voxels outside the grid are considered occupied.
+    #[must_use]
+    pub fn is_occupied(&self, voxel: VoxelIndex) -> bool {
+        self.state(voxel)
+            .is_none_or(|state| state == CellState::Occupied)
+    }
+
+    /// Returns true if the planner may pass through the voxel, which depends on the
+    /// [`UnknownSpacePolicy`] for unknown voxels.
+    #[must_use]
+    pub fn is_traversable(&self, voxel: VoxelIndex) -> bool {
+        match self.state(voxel) {
+            Some(CellState::Free) => true,
+            Some(CellState::Unknown) => self.unknown_policy == UnknownSpacePolicy::Traversable,
+            Some(CellState::Occupied) | None => false,
+        }
+    }
+
+    /// Returns the voxel containing the world coordinate, if it is within the grid.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn voxel_at(&self, (x, y, z): Point3) -> Option<VoxelIndex> {
+        if x < 0.0 || y < 0.0 || z < 0.0 {
+            return None;
+        }
+        let voxel = (
+            (x / self.resolution).floor() as usize,
+            (y / self.resolution).floor() as usize,
+            (z / self.resolution).floor() as usize,
+        );
+        self.index(voxel).map(|_| voxel)
+    }
+
+    /// Returns the world coordinate of the center of a voxel.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn voxel_center(&self, (x, y, z): VoxelIndex) -> Point3 {
+        (
+            (x as f64 + 0.5) * self.resolution,
+            (y as f64 + 0.5) * self.resolution,
+            (z as f64 + 0.5) * self.resolution,
+        )
+    }
+
+    /// Returns true if the world coordinate lies in a traversable voxel within the grid.
+    #[must_use]
+    pub fn is_free(&self, point: Point3) -> bool {
+        self.voxel_at(point)
+            .is_some_and(|voxel| self.is_traversable(voxel))
+    }
+
+    // World coordinates along the segment at intervals of at most half the resolution
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    fn segment_points(&self, from: Point3, to: Point3) -> impl Iterator<Item = Point3> {
+        let delta = (to.0 - from.0, to.1 - from.1, to.2 - from.2);
+        let length = (delta.0 * delta.0 + delta.1 * delta.1 + delta.2 * delta.2).sqrt();
+        let steps = (length / (self.resolution / 2.0)).ceil() as usize;
+        (0..=steps).map(move |i| {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                i as f64 / steps as f64
+            };
+            (
+                from.0 + t * delta.0,
+                from.1 + t * delta.1,
+                from.2 + t * delta.2,
+            )
+        })
+    }
+
+    /// Returns true if the straight line between two world coordinates only passes
+    /// through traversable voxels.
+    ///
+    /// The line is checked at intervals of half the resolution, which is suitable for use in
+    /// a planner's connectable function.
+    #[must_use]
+    pub fn is_segment_free(&self, from: Point3, to: Point3) -> bool {
+        self.segment_points(from, to)
+            .all(|point| self.is_free(point))
+    }
+
+    // Marks the voxels along the ray free, excluding the voxel containing the endpoint
+    fn clear_ray(&mut self, origin: Point3, endpoint: Point3) {
+        let hit = self.voxel_at(endpoint);
+        let free: Vec<VoxelIndex> = self
+            .segment_points(origin, endpoint)
+            .filter_map(|point| self.voxel_at(point))
+            .filter(|&voxel| Some(voxel) != hit)
+            .collect();
+        for voxel in free {
+            let _ = self.set_state(voxel, CellState::Free);
+        }
+    }
+
+    // Marks the voxel containing the point occupied, if it is within the grid
+    fn mark_hit(&mut self, point: Point3) {
+        if let Some(voxel) = self.voxel_at(point) {
+            let _ = self.set_state(voxel, CellState::Occupied);
+        }
+    }
+
+    /// Inserts a depth sensor return into the grid.
+    ///
+    /// Voxels along the ray from the sensor `origin` to the measured `endpoint` are marked
+    /// free, and the voxel containing the endpoint is marked occupied. Portions of the ray
+    /// outside the grid are ignored.
+    pub fn insert_ray(&mut self, origin: Point3, endpoint: Point3) {
+        self.clear_ray(origin, endpoint);
+        self.mark_hit(endpoint);
+    }
+
+    /// Inserts a point cloud into the grid.
+    ///
+    /// Every point marks the voxel containing it as occupied. If the sensor `origin` is
+    /// provided, the space between the sensor and each point is also marked free as with
+    /// [`VoxelGrid::insert_ray`]. All free space is cleared before any points are marked,
+    /// so a ray passing through a voxel hit by another point in the same cloud does not
+    /// erase it. Points outside the grid are ignored.
+    pub fn insert_point_cloud(&mut self, points: &[Point3], origin: Option<Point3>) {
+        if let Some(origin) = origin {
+            for &point in points {
+                self.clear_ray(origin, point);
+            }
+        }
+        for &point in points {
+            self.mark_hit(point);
+        }
+    }
+
+    // Inclusive voxel index range overlapping the box, clamped to the grid
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn voxel_range(&self, min: Point3, max: Point3) -> Option<(VoxelIndex, VoxelIndex)> {
+        let (nx, ny, nz) = self.dimensions;
+        let axis = |low: f64, high: f64, n: usize| {
+            (n > 0 && low <= high && high >= 0.0).then(|| {
+                let low = (low / self.resolution).floor().max(0.0) as usize;
+                let high = (high / self.resolution).floor() as usize;
+                (low, high.min(n - 1))
+            })
+        };
+        let (x, y, z) = (
+            axis(min.0, max.0, nx)?,
+            axis(min.1, max.1, ny)?,
+            axis(min.2, max.2, nz)?,
+        );
+        (x.0 <= x.1 && y.0 <= y.1 && z.0 <= z.1).then_some(((x.0, y.0, z.0), (x.1, y.1, z.1)))
+    }
+
+    /// Returns every voxel overlapping the axis aligned box between `min` and `max`.
+    #[must_use]
+    pub fn voxels_in_box(&self, min: Point3, max: Point3) -> Vec<VoxelIndex> {
+        let Some((low, high)) = self.voxel_range(min, max) else {
+            return Vec::new();
+        };
+        let mut voxels = Vec::new();
+        for z in low.2..=high.2 {
+            for y in low.1..=high.1 {
+                for x in low.0..=high.0 {
+                    voxels.push((x, y, z));
+                }
+            }
+        }
+        voxels
+    }
+
+    /// Returns true if the axis aligned box between `min` and `max` lies within the grid
+    /// and every voxel it overlaps is traversable, e.g. for checking a robot's bounding box.
+    #[must_use]
+    pub fn is_box_free(&self, min: Point3, max: Point3) -> bool {
+        let (low, high) = self.bounds();
+        let inside = low.0 <= min.0
+            && low.1 <= min.1
+            && low.2 <= min.2
+            && max.0 < high.0
+            && max.1 < high.1
+            && max.2 < high.2;
+        inside
+            && self
+                .voxels_in_box(min, max)
+                .into_iter()
+                .all(|voxel| self.is_traversable(voxel))
+    }
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "Resolution must be finite and positive")]
+    fn test_voxel_grid_nan_resolution() {
+        let _ = VoxelGrid::new((1, 1, 1), f64::NAN);
+    }
+
+    #[test]
+    fn test_voxel_state() {
+        let mut grid = VoxelGrid::new((2, 3, 4), 0.5);
+        assert_eq!(grid.bounds(), ((0.0, 0.0, 0.0), (1.0, 1.5, 2.0)));
+        assert_eq!(grid.state((1, 2, 3)), Some(CellState::Free));
+        assert_eq!(grid.state((2, 2, 3)), None);
+        assert!(grid.is_occupied((0, 0, 4)));
+
+        grid.set_state((1, 2, 3), CellState::Occupied).unwrap();
+        assert!(grid.is_occupied((1, 2, 3)));
+        assert_eq!(
+            grid.set_state((1, 3, 3), CellState::Occupied),
+            Err(GridError::OutOfBounds)
+        );
+
+        assert_eq!(grid.voxel_at((0.75, 1.4, 1.9)), Some((1, 2, 3)));
+        assert_eq!(grid.voxel_at((0.75, 1.5, 1.9)), None);
+        assert_eq!(grid.voxel_center((1, 2, 3)), (0.75, 1.25, 1.75));
+        assert!(!grid.is_free((0.75, 1.4, 1.9)));
+        assert!(grid.is_free((0.25, 1.4, 1.9)));
+    }
+
+    #[test]
+    fn test_segment_free() {
+        let mut grid = VoxelGrid::new((4, 4, 4), 1.0);
+        grid.set_state((2, 2, 2), CellState::Occupied).unwrap();
+        assert!(grid.is_segment_free((0.5, 0.5, 0.5), (3.5, 0.5, 3.5)));
+        assert!(!grid.is_segment_free((0.5, 0.5, 0.5), (3.5, 3.5, 3.5)));
+        assert!(!grid.is_segment_free((0.5, 0.5, 0.5), (0.5, 0.5, 4.5)));
+
+        let grid = VoxelGrid::filled((4, 4, 4), 1.0, CellState::Unknown);
+        assert!(!grid.is_segment_free((0.5, 0.5, 0.5), (3.5, 0.5, 3.5)));
+        let grid = grid.with_unknown_policy(UnknownSpacePolicy::Traversable);
+        assert!(grid.is_segment_free((0.5, 0.5, 0.5), (3.5, 0.5, 3.5)));
+    }
+
+    #[test]
+    fn test_insert_ray() {
+        let mut grid = VoxelGrid::filled((5, 2, 2), 1.0, CellState::Unknown);
+        grid.insert_ray((0.5, 0.5, 0.5), (3.5, 0.5, 0.5));
+        for x in 0..3 {
+            assert_eq!(grid.state((x, 0, 0)), Some(CellState::Free));
+        }
+        assert_eq!(grid.state((3, 0, 0)), Some(CellState::Occupied));
+        assert_eq!(grid.state((4, 0, 0)), Some(CellState::Unknown));
+        assert_eq!(grid.state((0, 1, 0)), Some(CellState::Unknown));
+
+        // Rays leaving the grid only clear the voxels inside it
+        grid.insert_ray((0.5, 1.5, 1.5), (10.5, 1.5, 1.5));
+        for x in 0..5 {
+            assert_eq!(grid.state((x, 1, 1)), Some(CellState::Free));
+        }
+    }
+
+    #[test]
+    fn test_insert_point_cloud() {
+        // The first point lies on the ray to the second, and must not be cleared by it
+        let cloud = [
+            (2.5, 0.5, 0.5),
+            (4.5, 0.5, 0.5),
+            (0.5, 1.5, 1.5),
+            (9.0, 9.0, 9.0),
+        ];
+
+        let mut grid = VoxelGrid::filled((5, 2, 2), 1.0, CellState::Unknown);
+        grid.insert_point_cloud(&cloud, Some((0.5, 0.5, 0.5)));
+        assert_eq!(grid.state((0, 0, 0)), Some(CellState::Free));
+        assert_eq!(grid.state((1, 0, 0)), Some(CellState::Free));
+        assert_eq!(grid.state((2, 0, 0)), Some(CellState::Occupied));
+        assert_eq!(grid.state((3, 0, 0)), Some(CellState::Free));
+        assert_eq!(grid.state((4, 0, 0)), Some(CellState::Occupied));
+        assert_eq!(grid.state((0, 1, 1)), Some(CellState::Occupied));
+
+        // Without an origin only the hits are recorded
+        let mut grid = VoxelGrid::filled((5, 2, 2), 1.0, CellState::Unknown);
+        grid.insert_point_cloud(&cloud, None);
+        assert_eq!(grid.state((1, 0, 0)), Some(CellState::Unknown));
+        assert_eq!(grid.state((2, 0, 0)), Some(CellState::Occupied));
+        assert_eq!(grid.state((4, 0, 0)), Some(CellState::Occupied));
+    }
+
+    #[test]
+    fn test_box_queries() {
+        let mut grid = VoxelGrid::new((4, 4, 4), 1.0);
+        assert_eq!(
+            grid.voxels_in_box((0.5, 0.5, 0.5), (1.5, 1.0, 0.9)),
+            vec![(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0)]
+        );
+        assert_eq!(
+            grid.voxels_in_box((-5.0, -5.0, -5.0), (0.5, 0.5, 0.5)),
+            vec![(0, 0, 0)]
+        );
+        assert!(grid
+            .voxels_in_box((5.0, 5.0, 5.0), (6.0, 6.0, 6.0))
+            .is_empty());
+        assert!(grid
+            .voxels_in_box((2.0, 2.0, 2.0), (1.0, 1.0, 1.0))
+            .is_empty());
+
+        grid.set_state((2, 2, 2), CellState::Occupied).unwrap();
+        assert!(grid.is_box_free((0.0, 0.0, 0.0), (1.9, 3.9, 3.9)));
+        assert!(!grid.is_box_free((1.5, 1.5, 1.5), (2.5, 2.5, 2.5)));
+        assert!(!grid.is_box_free((-0.5, 0.0, 0.0), (1.0, 1.0, 1.0)));
+    }
+}
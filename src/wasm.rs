@@ -0,0 +1,254 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::float::FloatArray;
+use crate::grid::{CellState, OccupancyGrid};
+use crate::planning::rrt::{grow_tree, RrtOptions};
+use crate::planning::stats::PlannerStats;
+use crate::planning::termination::MaxIterations;
+use crate::planning::tuning::SplitMix64;
+use crate::tree::HashTree;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// The result of [`plan_grid`], exposed to JavaScript as flat `Float64Array`s.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WasmPlan {
+    path: Vec<f64>,
+    edges: Vec<f64>,
+    cost: f64,
+}
+
+#[wasm_bindgen]
+impl WasmPlan {
+    /// Path waypoints as `[x0, y0, x1, y1, ...]`, empty if no path was found.
+    #[must_use]
+    #[wasm_bindgen(getter)]
+    pub fn path(&self) -> Vec<f64> {
+        self.path.clone()
+    }
+
+    /// Every edge of the tree as `[parent_x, parent_y, child_x, child_y, ...]`.
+    #[must_use]
+    #[wasm_bindgen(getter)]
+    pub fn edges(&self) -> Vec<f64> {
+        self.edges.clone()
+    }
+
+    /// Length of the path, or infinity if no path was found.
+    #[must_use]
+    #[wasm_bindgen(getter)]
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    /// Whether a path to the goal was found.
+    #[must_use]
+    #[wasm_bindgen(getter)]
+    pub fn solved(&self) -> bool {
+        !self.path.is_empty()
+    }
+}
+
+/// Plans between two points on an occupancy grid, exported to JavaScript as `planGrid`.
+///
+/// `occupied` holds one byte per cell in row major order starting from the cell at the
+/// origin, where any non zero value is an obstacle. `start` and `goal` are `[x, y]` in
+/// world coordinates. Runs RRT for `iterations` iterations, or RRT* if `rewire_radius` is
+/// positive, sampling with `seed` so runs are reproducible. The tree is returned even if
+/// no path was found, for drawing.
+///
+/// This only uses the portable subset of the crate: no threads, file system access, or
+/// system clock reads that panic in the browser.
+///
+/// # Errors
+///
+/// If the grid, start, or goal are malformed.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen(js_name = planGrid)]
+pub fn plan_grid(
+    width: usize,
+    height: usize,
+    resolution: f64,
+    occupied: &[u8],
+    start: &[f64],
+    goal: &[f64],
+    max_extension: f64,
+    rewire_radius: f64,
+    iterations: u32,
+    seed: u32,
+) -> Result<WasmPlan, String> {
+    if !(resolution.is_finite() && resolution > 0.0)
+        || max_extension.is_nan()
+        || max_extension <= 0.0
+    {
+        return Err(
+            "Resolution must be finite and positive, and maximum extension positive".into(),
+        );
+    }
+    if occupied.len() != width * height {
+        return Err(format!(
+            "Expected {} occupancy values, got {}",
+            width * height,
+            occupied.len()
+        ));
+    }
+    let (Ok(start), Ok(goal)) = (<[f64; 2]>::try_from(start), <[f64; 2]>::try_from(goal)) else {
+        return Err("Start and goal must be [x, y]".into());
+    };
+
+    let mut grid = OccupancyGrid::new(width, height, resolution);
+    for (index, _) in occupied.iter().enumerate().filter(|(_, &cell)| cell != 0) {
+        grid.set_state(index % width, index / width, CellState::Occupied)
+            .map_err(|error| error.to_string())?;
+    }
+    if !grid.is_free(start[0], start[1]) || !grid.is_free(goal[0], goal[1]) {
+        return Err("Start and goal must be in free cells".into());
+    }
+
+    let mut rng = SplitMix64(u64::from(seed));
+    let sample_fn = grid.bounds().sampler(|| rng.next_f64());
+    let extend_fn = |from: &FloatArray<2>, to: &FloatArray<2>| {
+        let (dx, dy) = (to.0[0] - from.0[0], to.0[1] - from.0[1]);
+        let scale = (max_extension / dx.hypot(dy)).min(1.0);
+        FloatArray([from.0[0] + dx * scale, from.0[1] + dy * scale])
+    };
+    let connectable_fn = |from: &FloatArray<2>, to: &FloatArray<2>| {
+        grid.is_segment_free((from.0[0], from.0[1]), (to.0[0], to.0[1]))
+    };
+    let options = RrtOptions {
+        max_extension,
+        use_rrtstar: rewire_radius > 0.0,
+        rewire_radius,
+        ..RrtOptions::default()
+    };
+
+    let goal = FloatArray(goal);
+    let mut tree = HashTree::new(FloatArray(start));
+    grow_tree(
+        &mut tree,
+        &goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        &options,
+        MaxIterations(u64::from(iterations)),
+        &mut PlannerStats::new(),
+    );
+
+    let edges = tree
+        .iter_insertion_order()
+        .filter_map(|node| tree.get_parent(node).map(|parent| (parent, node)))
+        .flat_map(|(parent, node)| [parent.0[0], parent.0[1], node.0[0], node.0[1]])
+        .collect();
+    let (path, cost) = match tree.path(&goal) {
+        Ok(path) => (
+            path.iter().flat_map(|point| point.0).collect(),
+            tree.cost(&goal).map_err(|error| error.to_string())?,
+        ),
+        Err(_) => (Vec::new(), f64::INFINITY),
+    };
+    Ok(WasmPlan { path, edges, cost })
+}
+
+//
+// Unit tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_grid() {
+        // A 10 x 10 grid with a wall along column 5 leaving a gap at the top
+        let mut occupied = vec![0; 100];
+        for row in 0..8 {
+            occupied[row * 10 + 5] = 1;
+        }
+
+        let plan = plan_grid(
+            10,
+            10,
+            1.0,
+            &occupied,
+            &[1.0, 1.0],
+            &[9.0, 1.0],
+            1.0,
+            0.0,
+            2000,
+            7,
+        )
+        .unwrap();
+        assert!(plan.solved());
+        assert_eq!(&plan.path()[..2], &[1.0, 1.0]);
+        assert_eq!(&plan.path()[plan.path().len() - 2..], &[9.0, 1.0]);
+        assert!(plan.cost() > 8.0 + 2.0 * 6.0);
+        assert_eq!(plan.edges().len() % 4, 0);
+
+        // Not enough iterations still returns the tree
+        let plan = plan_grid(
+            10,
+            10,
+            1.0,
+            &occupied,
+            &[1.0, 1.0],
+            &[9.0, 1.0],
+            1.0,
+            0.0,
+            3,
+            7,
+        )
+        .unwrap();
+        assert!(!plan.solved());
+        assert!(plan.cost().is_infinite());
+        assert!(!plan.edges().is_empty());
+
+        assert!(plan_grid(
+            10,
+            10,
+            1.0,
+            &occupied[1..],
+            &[1.0, 1.0],
+            &[9.0, 1.0],
+            1.0,
+            0.0,
+            1,
+            7
+        )
+        .is_err());
+        assert!(plan_grid(10, 10, 1.0, &occupied, &[1.0], &[9.0, 1.0], 1.0, 0.0, 1, 7).is_err());
+        assert!(plan_grid(
+            10,
+            10,
+            1.0,
+            &occupied,
+            &[5.5, 1.0],
+            &[9.0, 1.0],
+            1.0,
+            0.0,
+            1,
+            7
+        )
+        .is_err());
+    }
+}
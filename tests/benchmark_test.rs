@@ -0,0 +1,77 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Memory measurements need the counting allocator installed as the global allocator, so
+// these run in their own test binary.
+
+use rustplanning::benchmark::{seed_sweep_with_memory, ConfigurationReport, CountingAllocator};
+use rustplanning::float::FloatArray;
+use rustplanning::planning::batch::ProblemDefinition;
+use rustplanning::planning::rrt::RrtOptions;
+use rustplanning::planning::termination::MaxIterations;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+
+#[test]
+fn test_memory_tracking() {
+    let (_, usage) = ALLOCATOR.measure(|| {
+        let mut values: Vec<u64> = Vec::with_capacity(100);
+        values.extend(0..1000);
+        values
+    });
+    assert_eq!(usage.allocations, 2);
+    assert!(usage.bytes_allocated >= 8800);
+    assert!(usage.peak_bytes >= 8000);
+
+    // Larger trees use more memory
+    let problem = ProblemDefinition::new(FloatArray([0.0]), FloatArray([1000.0]));
+    let options = RrtOptions::default().with_max_extension(1.0);
+    let results = seed_sweep_with_memory(
+        &problem,
+        &[50, 500],
+        &ALLOCATOR,
+        |seed| {
+            let mut next = 0.0;
+            move || {
+                next = (next + 1.0) % f64::from(u32::try_from(seed).unwrap());
+                FloatArray([next])
+            }
+        },
+        |from: &FloatArray<1>, to: &FloatArray<1>| {
+            FloatArray([from.0[0] + (to.0[0] - from.0[0]).clamp(-1.0, 1.0)])
+        },
+        |_: &FloatArray<1>, _: &FloatArray<1>| true,
+        &options,
+        || MaxIterations(1000),
+    );
+    let memory: Vec<_> = results
+        .iter()
+        .map(|result| result.outcome.memory.unwrap())
+        .collect();
+    assert!(memory[0].allocations > 0);
+    assert!(memory[1].peak_bytes > memory[0].peak_bytes);
+
+    let outcomes: Vec<_> = results.iter().map(|result| result.outcome).collect();
+    let report = ConfigurationReport::from_runs("RRT", &outcomes).to_string();
+    assert!(report.contains("peak memory (bytes): median"), "{report}");
+}
@@ -20,37 +20,38 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use ordered_float::OrderedFloat;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
-use rustplanning::planning::rrt::rrt;
-use rustplanning::tree::Distance;
-use std::f64::EPSILON;
+use rustplanning::bounds::Bounds;
+use rustplanning::error::PlanningError;
+use rustplanning::float::{FloatArray, FloatState};
+use rustplanning::path::{compare_to_golden, cost_ratio, path_cost};
+use rustplanning::planning::rrt::{
+    rrt, rrt_simple, rrt_with_termination, rrt_with_tree, ConnectionDirection, GoalCandidates,
+    GoalConnection, RrtOptions,
+};
+use rustplanning::planning::stats::{GoalRejectionReason, PlannerStats, StopReason};
+use rustplanning::planning::termination::{
+    CostThreshold, MaxIterations, SolutionFound, Stalled, TerminationCondition,
+};
+use rustplanning::tree::{Distance, HashTree, SpatialIndex};
 use std::fmt;
 
 /// Basic 2D point class for representing hashable points in the plane
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
-struct Point2D(OrderedFloat<f64>, OrderedFloat<f64>);
+#[derive(Debug, Clone, Copy, FloatState)]
+struct Point2D(f64, f64);
 
 impl Point2D {
     pub fn new(x: f64, y: f64) -> Self {
-        Point2D(OrderedFloat(x), OrderedFloat(y))
+        Point2D(x, y)
     }
 
     pub fn x(&self) -> f64 {
-        self.0.into_inner()
+        self.0
     }
 
     pub fn y(&self) -> f64 {
-        self.1.into_inner()
-    }
-}
-
-// Norm distance function for 2D points
-impl Distance for Point2D {
-    fn distance(&self, other: &Point2D) -> f64 {
-        let (dx, dy) = (self.x() - other.x(), self.y() - other.y());
-        (dx * dx + dy * dy).sqrt()
+        self.1
     }
 }
 
@@ -67,6 +68,17 @@ fn sample_2d(rng: &mut StdRng, max_x: f64, max_y: f64) -> Point2D {
     Point2D::new(rng.gen_range(0.0..=max_x), rng.gen_range(0.0..=max_y))
 }
 
+/// Samples the 2-D plane with the crate's own generator, which is identical on every platform
+fn seeded_sampler_2d(seed: u64, max_x: f64, max_y: f64) -> impl FnMut() -> Point2D {
+    let mut sample = Bounds::new([0.0, 0.0], [max_x, max_y])
+        .unwrap()
+        .seeded_sampler(seed);
+    move || {
+        let FloatArray([x, y]) = sample();
+        Point2D::new(x, y)
+    }
+}
+
 // Returns a point that is step_size along the line between the specified start and end pose
 fn extend_2d(start: &Point2D, end: &Point2D, step_size: f64) -> Point2D {
     let direction = (end.0 - start.0, end.1 - start.1);
@@ -88,8 +100,7 @@ fn run_rrt(use_rrtstar: bool, use_connect: bool, start: &Point2D, goal: &Point2D
     let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
     let mut sample_fn = || sample_2d(&mut rng, grid_size, grid_size);
     let connectable_fn = |start: &Point2D, end: &Point2D| {
-        end.distance(&obstacle) > 3.0 &&
-        start.distance(end) < step_size
+        end.distance(&obstacle) > 3.0 && start.distance(end) < step_size
     };
 
     let result = rrt(
@@ -101,21 +112,29 @@ fn run_rrt(use_rrtstar: bool, use_connect: bool, start: &Point2D, goal: &Point2D
         use_rrtstar,
         2.0,
         use_connect,
-        100000,
+        100_000,
         10.0,
         true,
     );
 
-    assert!(result.is_ok(), "Expected Ok result, got Err");
+    // Returning on the first path, the run reports where its iterations went
+    let stats = result.stats;
+    assert_eq!(stats.stop_reason, StopReason::FoundPath);
+    assert!(stats.iterations < 100_000);
+    assert_eq!(stats.nodes_added, result.tree.size() - 1);
+    assert!(stats.samples_rejected < usize::try_from(stats.iterations).unwrap());
+    assert_eq!(stats.rewires > 0, use_rrtstar);
+    assert!(result.path.is_ok(), "Expected Ok result, got Err");
 
-    let (path, _) = result.unwrap();
+    let path = result.path.unwrap();
     assert!(!path.is_empty(), "Path should not be empty");
+    assert!((stats.path_cost.unwrap() - path_cost(&path)).abs() < 1e-9);
     assert_eq!(path[0], *start, "Path should start at the start point");
 
     // Verify it ends at the goal
     let end = path.last().unwrap();
     assert!(
-        end.distance(&goal) < EPSILON,
+        end.distance(goal) < f64::EPSILON,
         "Path should end at the goal"
     );
 }
@@ -143,3 +162,412 @@ fn test_rrtconnect() {
     let grid_size = 10.0;
     run_rrt(false, true, &start, &end, grid_size);
 }
+
+#[test]
+fn test_rrtstar_with_termination() {
+    let start = Point2D::new(1.0, 1.0);
+    let goal = Point2D::new(10.0, 10.0);
+    let grid_size = 10.0;
+    let step_size = 1.0;
+    let mut rng = StdRng::seed_from_u64(1);
+
+    let obstacle = Point2D::new(grid_size / 2.0, grid_size / 2.0);
+    let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
+    let sample_fn = || sample_2d(&mut rng, grid_size, grid_size);
+    let connectable_fn = |start: &Point2D, end: &Point2D| {
+        end.distance(&obstacle) > 3.0 && start.distance(end) < step_size
+    };
+
+    // Keep optimizing until we are within 50% of the straight line distance, or the
+    // solution stops improving.
+    let lower_bound = start.distance(&goal);
+    let termination = CostThreshold::new(lower_bound, 0.5)
+        .or(Stalled::iterations(5000))
+        .or(MaxIterations(100_000));
+
+    let result = rrt_with_termination(
+        &start,
+        &goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        &RrtOptions {
+            use_rrtstar: true,
+            rewire_radius: 2.0,
+            ..RrtOptions::default()
+        },
+        termination,
+        &mut PlannerStats::new(),
+    );
+    assert!(result.is_ok(), "Expected Ok result, got Err");
+
+    let (path, tree) = result.unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(*path.last().unwrap(), goal);
+    assert!(tree.cost(&goal).unwrap() <= lower_bound * 1.5);
+}
+
+#[test]
+fn test_rrt_goal_connection_strategy() {
+    let start = Point2D::new(1.0, 1.0);
+    let goal = Point2D::new(10.0, 10.0);
+    let grid_size = 10.0;
+    let step_size = 1.0;
+    let mut rng = StdRng::seed_from_u64(1);
+
+    let obstacle = Point2D::new(grid_size / 2.0, grid_size / 2.0);
+    let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
+    let sample_fn = || sample_2d(&mut rng, grid_size, grid_size);
+    let connectable_fn = |start: &Point2D, end: &Point2D| {
+        end.distance(&obstacle) > 3.0 && start.distance(end) < step_size
+    };
+
+    // Greedily extend from the few nodes nearest the goal every 10 iterations
+    let goal_connection = GoalConnection {
+        direction: ConnectionDirection::TreeToGoal,
+        frequency: 10,
+        candidates: GoalCandidates::Nearest(3),
+        use_connect: true,
+        ..GoalConnection::default()
+    };
+
+    let result = rrt_with_termination(
+        &start,
+        &goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        &RrtOptions {
+            goal_connection,
+            ..RrtOptions::default()
+        },
+        MaxIterations(100_000).or(SolutionFound),
+        &mut PlannerStats::new(),
+    );
+    assert!(result.is_ok(), "Expected Ok result, got Err");
+
+    let (path, _) = result.unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(*path.last().unwrap(), goal);
+}
+
+#[test]
+fn test_rrtstar_max_extension_and_rewire_radius() {
+    let start = Point2D::new(1.0, 1.0);
+    let goal = Point2D::new(10.0, 10.0);
+    let grid_size = 10.0;
+    let mut rng = StdRng::seed_from_u64(1);
+
+    // The extension length and rewire radius are entirely separate from the motion
+    // validator, which only checks for collisions.
+    let max_extension = 1.0;
+    let rewire_radius = 2.5;
+    let obstacle = Point2D::new(grid_size / 2.0, grid_size / 2.0);
+    let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, max_extension);
+    let sample_fn = || sample_2d(&mut rng, grid_size, grid_size);
+    let connectable_fn = |_: &Point2D, end: &Point2D| end.distance(&obstacle) > 3.0;
+
+    let options = RrtOptions {
+        max_extension,
+        use_rrtstar: true,
+        rewire_radius,
+        ..RrtOptions::default()
+    };
+    let result = rrt_with_termination(
+        &start,
+        &goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        &options,
+        MaxIterations(3000),
+        &mut PlannerStats::new(),
+    );
+    assert!(result.is_ok(), "Expected Ok result, got Err");
+
+    // Every edge must respect either the extension length or the rewire radius, and
+    // rewiring should have produced some edges longer than a single extension.
+    let (_, tree) = result.unwrap();
+    let mut rewired_edges = 0;
+    for node in tree.iter_depth_first() {
+        if let Some(parent) = tree.get_parent(node) {
+            let length = node.distance(parent);
+            assert!(length <= rewire_radius);
+            if length > max_extension + 1e-9 {
+                rewired_edges += 1;
+            }
+        }
+    }
+    assert!(rewired_edges > 0);
+}
+
+#[test]
+fn test_rrt_simple() {
+    let start = Point2D::new(1.0, 1.0);
+    let goal = Point2D::new(10.0, 10.0);
+    let grid_size = 10.0;
+    let step_size = 1.0;
+    let mut rng = StdRng::seed_from_u64(1);
+
+    // Plan to anywhere within a step of the goal
+    let obstacle = Point2D::new(grid_size / 2.0, grid_size / 2.0);
+    let goal_fn = |point: &Point2D| point.distance(&goal) < step_size;
+    let sample_fn = || sample_2d(&mut rng, grid_size, grid_size);
+    let steer_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
+    let valid_fn = |_: &Point2D, end: &Point2D| end.distance(&obstacle) > 3.0;
+
+    let result = rrt_simple(
+        &start,
+        goal_fn,
+        sample_fn,
+        steer_fn,
+        valid_fn,
+        MaxIterations(100_000).or(SolutionFound),
+    );
+    assert!(result.is_ok(), "Expected Ok result, got Err");
+
+    let (path, _) = result.unwrap();
+    assert_eq!(path[0], start);
+    assert!(path.last().unwrap().distance(&goal) < step_size);
+}
+
+/// Joint state for an N-DOF arm, which cannot be `Copy`
+#[derive(Debug, Clone, FloatState)]
+struct JointState(Vec<f64>);
+
+impl JointState {
+    fn new(joints: &[f64]) -> Self {
+        JointState(joints.to_vec())
+    }
+}
+
+fn run_rrt_joint_state(use_rrtstar: bool, use_rrtconnect: bool) {
+    let start = JointState::new(&[0.0, 0.0, 0.0]);
+    let goal = JointState::new(&[3.0, -2.0, 1.0]);
+    let step_size = 0.5;
+    let mut rng = StdRng::seed_from_u64(1);
+
+    let sample_fn = || {
+        let joints: Vec<f64> = (0..3).map(|_| rng.gen_range(-4.0..=4.0)).collect();
+        JointState::new(&joints)
+    };
+    let extend_fn = |from: &JointState, to: &JointState| {
+        let scale = step_size / from.distance(to);
+        let joints: Vec<f64> = from
+            .0
+            .iter()
+            .zip(&to.0)
+            .map(|(a, b)| a + (b - a) * scale)
+            .collect();
+        JointState::new(&joints)
+    };
+    let connectable_fn = |from: &JointState, to: &JointState| from.distance(to) < step_size;
+
+    let result = rrt(
+        &start,
+        &goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        use_rrtstar,
+        1.0,
+        use_rrtconnect,
+        100_000,
+        10.0,
+        true,
+    )
+    .into_result();
+    assert!(result.is_ok(), "Expected Ok result, got Err");
+
+    let (path, _) = result.unwrap();
+    assert_eq!(path[0], start);
+    assert_eq!(*path.last().unwrap(), goal);
+}
+
+#[test]
+fn test_rrt_clone_only_state() {
+    run_rrt_joint_state(false, false);
+    run_rrt_joint_state(true, false);
+    run_rrt_joint_state(false, true);
+}
+
+#[test]
+fn test_rrt_records_goal_rejections() {
+    let start = Point2D::new(1.0, 1.0);
+    let goal = Point2D::new(10.0, 10.0);
+    let grid_size = 10.0;
+    let step_size = 1.0;
+    let mut rng = StdRng::seed_from_u64(1);
+
+    // Motions may only move in the positive x direction, so querying the connectable
+    // function from the goal back to the tree will essentially never succeed.
+    let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
+    let sample_fn = || sample_2d(&mut rng, grid_size, grid_size);
+    let connectable_fn = |from: &Point2D, to: &Point2D| to.x() >= from.x();
+
+    let options = RrtOptions {
+        max_extension: step_size,
+        goal_connection: GoalConnection {
+            direction: ConnectionDirection::GoalToTree,
+            ..GoalConnection::default()
+        },
+        record_goal_rejections: true,
+        ..RrtOptions::default()
+    };
+    let mut stats = PlannerStats::new();
+    let result = rrt_with_termination(
+        &start,
+        &goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        &options,
+        MaxIterations(2000),
+        &mut stats,
+    );
+    assert!(result.is_err());
+
+    // The tree got within reach of the goal, but the motion was rejected
+    let closest = stats.closest_goal_rejection().unwrap();
+    assert!(closest.distance <= step_size);
+    assert_eq!(closest.reason, GoalRejectionReason::InvalidMotion);
+    assert!(stats
+        .goal_rejections
+        .iter()
+        .any(|rejection| rejection.reason == GoalRejectionReason::OutOfReach));
+}
+
+/// Order dependent fingerprint of the tree's nodes and edges, computed from the raw bits
+/// of each coordinate so that it is identical on every platform.
+fn tree_fingerprint(tree: &HashTree<Point2D>) -> u64 {
+    tree.iter_depth_first().fold(0u64, |acc, node| {
+        let parent = tree.get_parent(node).copied().unwrap_or(*node);
+        [node.x(), node.y(), parent.x(), parent.y()]
+            .iter()
+            .fold(acc, |acc, v| acc.wrapping_mul(31).wrapping_add(v.to_bits()))
+    })
+}
+
+fn run_seeded_rrtstar(seed: u64) -> Result<(Vec<Point2D>, HashTree<Point2D>), PlanningError> {
+    let start = Point2D::new(1.0, 1.0);
+    let goal = Point2D::new(10.0, 10.0);
+    let grid_size = 10.0;
+    let step_size = 1.0;
+
+    let obstacle = Point2D::new(grid_size / 2.0, grid_size / 2.0);
+    let extend_fn = |start: &Point2D, end: &Point2D| extend_2d(start, end, step_size);
+    let sample_fn = seeded_sampler_2d(seed, grid_size, grid_size);
+    let connectable_fn = |_: &Point2D, end: &Point2D| end.distance(&obstacle) > 3.0;
+
+    let options = RrtOptions {
+        max_extension: step_size,
+        use_rrtstar: true,
+        rewire_radius: 2.0,
+        ..RrtOptions::default()
+    };
+    rrt_with_termination(
+        &start,
+        &goal,
+        sample_fn,
+        extend_fn,
+        connectable_fn,
+        &options,
+        MaxIterations(1000),
+        &mut PlannerStats::new(),
+    )
+}
+
+#[test]
+fn test_rrtstar_determinism() {
+    // Same seed and configuration produce the same tree
+    let (path, tree) = run_seeded_rrtstar(42).unwrap();
+    let (other_path, other_tree) = run_seeded_rrtstar(42).unwrap();
+    assert_eq!(path, other_path);
+    assert_eq!(tree_fingerprint(&tree), tree_fingerprint(&other_tree));
+
+    // And that tree is identical on every platform. If a change to the planner
+    // intentionally alters its behavior these values must be regenerated.
+    assert_eq!(tree.size(), 724);
+    assert_eq!(path.len(), 11);
+    assert_eq!(tree_fingerprint(&tree), 5_345_118_184_639_414_056);
+}
+
+#[test]
+fn test_rrtstar_spatial_index() {
+    // Indexing the tree by position speeds up queries without changing what is planned
+    let start = Point2D::new(1.0, 1.0);
+    let obstacle = Point2D::new(5.0, 5.0);
+    let options = RrtOptions {
+        max_extension: 1.0,
+        use_rrtstar: true,
+        rewire_radius: 2.0,
+        ..RrtOptions::default()
+    };
+
+    let mut tree = HashTree::new(start);
+    tree.set_spatial_index(Some(SpatialIndex::new(|p: &Point2D| vec![p.x(), p.y()])));
+    let path = rrt_with_tree(
+        &mut tree,
+        &Point2D::new(10.0, 10.0),
+        seeded_sampler_2d(42, 10.0, 10.0),
+        |start: &Point2D, end: &Point2D| extend_2d(start, end, 1.0),
+        |_: &Point2D, end: &Point2D| end.distance(&obstacle) > 3.0,
+        &options,
+        MaxIterations(1000),
+        &mut PlannerStats::new(),
+    )
+    .unwrap();
+
+    let (expected_path, expected_tree) = run_seeded_rrtstar(42).unwrap();
+    assert_eq!(path, expected_path);
+    assert_eq!(tree_fingerprint(&tree), tree_fingerprint(&expected_tree));
+}
+
+#[test]
+fn test_rrtstar_quality_regression() {
+    // A previously planned route around the top of the obstacle
+    let golden: Vec<Point2D> = [
+        (1.0, 1.0),
+        (0.98, 2.0),
+        (1.34, 2.94),
+        (1.76, 3.84),
+        (1.68, 5.56),
+        (2.66, 7.0),
+        (3.45, 7.62),
+        (4.34, 8.07),
+        (5.33, 8.21),
+        (7.42, 8.45),
+        (8.59, 8.94),
+        (10.0, 10.0),
+    ]
+    .iter()
+    .map(|&(x, y)| Point2D::new(x, y))
+    .collect();
+
+    // The obstacle is symmetric about the line from the start to the goal, so routes
+    // around the bottom are just as good
+    let mirrored: Vec<Point2D> = golden.iter().map(|p| Point2D::new(p.y(), p.x())).collect();
+
+    let mut ratios = Vec::new();
+    for seed in 0..32 {
+        let Ok((path, _)) = run_seeded_rrtstar(seed) else {
+            continue;
+        };
+        if let Err(e) = compare_to_golden(&path, &golden, 0.05, 1.5)
+            .or_else(|_| compare_to_golden(&path, &mirrored, 0.05, 1.5))
+        {
+            panic!("Seed {seed}: {e}");
+        }
+        ratios.push(cost_ratio(&path, &golden));
+    }
+
+    // Nearly every run should find a path, typically no worse than the golden one
+    assert!(
+        ratios.len() >= 30,
+        "{} of 32 runs found a path",
+        ratios.len()
+    );
+    ratios.sort_by(f64::total_cmp);
+    let median = ratios[ratios.len() / 2];
+    assert!(median <= 1.0, "Median cost ratio {median}");
+}
@@ -0,0 +1,158 @@
+// MIT License
+//
+// Copyright (c) 2024 Erik Holum
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// Randomized sequences of tree operations, checking the tree's structural invariants and
+// the results of queries against a brute force model after every step.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustplanning::tree::{Distance, HashTree};
+
+// Nodes are integer points so that equality and hashing are exact
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+struct Cell(i32, i32);
+
+impl Distance for Cell {
+    fn distance(&self, other: &Cell) -> f64 {
+        f64::from(self.0 - other.0).hypot(f64::from(self.1 - other.1))
+    }
+}
+
+fn random_node(rng: &mut StdRng, tree: &HashTree<Cell>) -> Cell {
+    *tree
+        .iter_insertion_order()
+        .nth(rng.gen_range(0..tree.size()))
+        .unwrap()
+}
+
+fn random_cell(rng: &mut StdRng) -> Cell {
+    Cell(rng.gen_range(-20..=20), rng.gen_range(-20..=20))
+}
+
+fn check_queries(rng: &mut StdRng, tree: &HashTree<Cell>) {
+    let node = random_node(rng, tree);
+    let path = tree.path(&node).unwrap();
+    assert_eq!(path.first(), Some(tree.root()));
+    assert_eq!(path.last(), Some(&node));
+    let length: f64 = path.windows(2).map(|pair| pair[0].distance(&pair[1])).sum();
+    assert!((tree.cost(&node).unwrap() - length).abs() < 1e-9);
+
+    assert_eq!(tree.iter_depth_first().count(), tree.size());
+
+    let query = random_cell(rng);
+    let nearest = tree.nearest_neighbor(&query).distance(&query);
+    let brute_force = tree
+        .iter_insertion_order()
+        .map(|node| node.distance(&query))
+        .fold(f64::INFINITY, f64::min);
+    assert!((nearest - brute_force).abs() < f64::EPSILON);
+}
+
+// Applies one random operation, returning a description for failure messages
+fn apply_random_operation(
+    rng: &mut StdRng,
+    tree: &mut HashTree<Cell>,
+    detached: &mut Vec<HashTree<Cell>>,
+) -> String {
+    match rng.gen_range(0..10) {
+        0..=4 => {
+            let (parent, child) = (random_node(rng, tree), random_cell(rng));
+            let present = tree.contains(&child);
+            assert_eq!(tree.add_child(&parent, child).is_ok(), !present);
+            format!("add_child({parent:?}, {child:?})")
+        }
+        5..=6 => {
+            let (child, parent) = (random_node(rng, tree), random_node(rng, tree));
+            let cycle = tree.is_ancestor(&child, &parent);
+            if child != *tree.root() {
+                assert_eq!(tree.set_parent(&child, &parent).is_ok(), !cycle);
+            }
+            format!("set_parent({child:?}, {parent:?})")
+        }
+        7 => {
+            let cut = random_node(rng, tree);
+            let before = tree.size();
+            let subtrees = tree.invalidate_edges(|_, child| *child != cut);
+            let removed: usize = subtrees.iter().map(HashTree::size).sum();
+            assert_eq!(tree.size() + removed, before);
+            assert!(!tree.contains(&cut) || cut == *tree.root());
+            detached.extend(subtrees);
+            format!("invalidate_edges(to {cut:?})")
+        }
+        8 => {
+            let Some(subtree) = detached.pop() else {
+                return "graft(nothing)".to_string();
+            };
+            let parent = random_node(rng, tree);
+            let before = tree.size();
+            // Cells may have been added to the tree again since the subtree was detached
+            let overlaps = subtree
+                .iter_insertion_order()
+                .any(|node| tree.contains(node));
+            if overlaps {
+                assert!(tree.graft(&parent, &subtree).is_err());
+                assert_eq!(tree.size(), before);
+            } else {
+                assert!(tree.graft(&parent, &subtree).is_ok());
+                assert_eq!(tree.size(), before + subtree.size());
+                assert_eq!(tree.get_parent(subtree.root()), Some(&parent));
+            }
+            format!("graft({:?}, {parent:?})", subtree.root())
+        }
+        _ => {
+            check_queries(rng, tree);
+            "queries".to_string()
+        }
+    }
+}
+
+#[test]
+fn test_tree_random_operations() {
+    for seed in 0..50 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut tree = HashTree::new(Cell(0, 0));
+        let mut detached = Vec::new();
+        let mut history = Vec::new();
+
+        for _ in 0..300 {
+            history.push(apply_random_operation(&mut rng, &mut tree, &mut detached));
+            if let Err(e) = tree.check_invariants() {
+                panic!("Seed {seed} broke the tree with {e} after {history:?}");
+            }
+            for subtree in &detached {
+                assert!(subtree.check_invariants().is_ok());
+            }
+        }
+
+        // Detached subtrees that no longer overlap the tree can always be reattached
+        let root = *tree.root();
+        for subtree in detached {
+            if subtree
+                .iter_insertion_order()
+                .all(|node| !tree.contains(node))
+            {
+                assert!(tree.graft(&root, &subtree).is_ok());
+            }
+        }
+        assert!(tree.check_invariants().is_ok());
+    }
+}